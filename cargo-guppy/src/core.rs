@@ -102,7 +102,7 @@ pub struct FilterOptions {
     pub include_build: bool,
 
     #[clap(long)]
-    /// Target to filter, "current", "any" or "always" [default: any]
+    /// Target to filter, "current", "any", "always" or a target JSON file [default: any]
     pub target: Option<String>,
 }
 
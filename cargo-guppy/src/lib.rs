@@ -222,11 +222,11 @@ pub struct ResolveCargoOptions {
     base_filter_opts: BaseFilterOptions,
 
     #[clap(long = "target-platform")]
-    /// Evaluate against target platform, "current" or "any" (default: any)
+    /// Evaluate against target platform, "current", "any" or a target JSON file (default: any)
     target_platform: Option<String>,
 
     #[clap(long = "host-platform")]
-    /// Evaluate against host platform, "current" or "any" (default: any)
+    /// Evaluate against host platform, "current", "any" or a target JSON file (default: any)
     host_platform: Option<String>,
 
     #[clap(long, arg_enum, default_value = "all")]
@@ -90,6 +90,7 @@ pub enum CargoResolverVersionCmd {
     V1,
     V1Install,
     V2,
+    V3,
 }
 
 #[derive(ArgEnum, Clone, Copy, Debug)]
@@ -124,6 +125,7 @@ impl CargoResolverVersionCmd {
             CargoResolverVersionCmd::V1 => CargoResolverVersion::V1,
             CargoResolverVersionCmd::V1Install => CargoResolverVersion::V1Install,
             CargoResolverVersionCmd::V2 => CargoResolverVersion::V2,
+            CargoResolverVersionCmd::V3 => CargoResolverVersion::V3,
         }
     }
 }
@@ -175,14 +177,17 @@ impl CargoMetadataOptions {
     }
 }
 
-/// Parse a given triple, the string "current", or "any", into a platform.
-///
-/// TODO: This should eventually support JSON specs as well, probably.
+/// Parse a given triple, a path to a custom target JSON file, the string "current", or "any", into
+/// a platform.
 pub fn string_to_platform_spec(s: Option<&str>) -> Result<PlatformSpec> {
     match s {
         Some("current") => Ok(PlatformSpec::current()?),
         Some("always") => Ok(PlatformSpec::Always),
         Some("any") => Ok(PlatformSpec::Any),
+        // As with `cargo build --target`, a path ending in .json is a custom target.
+        Some(path) if path.ends_with(".json") => {
+            Ok(Platform::new_custom_from_path(path, TargetFeatures::Unknown)?.into())
+        }
         Some(triple) => Ok(Platform::new(triple.to_owned(), TargetFeatures::Unknown)?.into()),
         None => Ok(PlatformSpec::Any),
     }
@@ -8,8 +8,14 @@
 //! The main entry point is `SummaryDiff`, which can be created through the `diff` method on
 //! summaries or through `SummaryDiff::new`.
 
-pub use crate::report::SummaryReport;
-use crate::{PackageInfo, PackageMap, PackageStatus, Summary, SummaryId, SummarySource};
+pub use crate::{
+    report::{CombinedReport, SummaryHtmlReport, SummaryMarkdownReport, SummaryReport},
+    rules::{ChangeKind, ClassifiedChange, DiffRule, DiffRules, Severity},
+};
+use crate::{
+    PackageInfo, PackageMap, PackageStatus, PlatformKind, Summary, SummaryId, SummarySource,
+    SummaryV2,
+};
 use ahash::AHashMap;
 use diffus::{edit, Diffable};
 use semver::Version;
@@ -29,6 +35,11 @@ use std::{
 /// The [`report`](SummaryDiff::report) method can be used with `fmt::Display` to generate a
 /// friendly, human-readable report.
 ///
+/// For reports meant to be posted on pull requests or published as CI artifacts, the
+/// [`markdown_report`](SummaryDiff::markdown_report) and
+/// [`html_report`](SummaryDiff::html_report) methods render the same information as Markdown and
+/// HTML respectively, with a collapsible section for each category of change.
+///
 /// ## Machine-readable serialization
 ///
 /// A `SummaryDiff` can be serialized through `serde`. The output format is part of the API.
@@ -104,6 +115,242 @@ impl<'a> SummaryDiff<'a> {
     pub fn report<'b>(&'b self) -> SummaryReport<'a, 'b> {
         SummaryReport::new(self)
     }
+
+    /// Returns a Markdown report for this diff.
+    ///
+    /// This report can be used with `fmt::Display`.
+    pub fn markdown_report<'b>(&'b self) -> SummaryMarkdownReport<'a, 'b> {
+        SummaryMarkdownReport::new(self)
+    }
+
+    /// Returns a standalone HTML report for this diff.
+    ///
+    /// This report can be used with `fmt::Display`.
+    pub fn html_report<'b>(&'b self) -> SummaryHtmlReport<'a, 'b> {
+        SummaryHtmlReport::new(self)
+    }
+
+    /// Calls `f` for each individual change in this diff.
+    ///
+    /// Packages are visited in the same order as in reports, target packages first. A modified
+    /// package results in one call for each aspect that changed, for example once for a version
+    /// change and once for every added feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use guppy_summaries::{diff::SummaryChange, Summary};
+    ///
+    /// static OLD: &str = r#"
+    /// [[target-package]]
+    /// name = "foo"
+    /// version = "1.2.3"
+    /// crates-io = true
+    /// status = "direct"
+    /// features = ["std"]
+    /// "#;
+    ///
+    /// static NEW: &str = r#"
+    /// [[target-package]]
+    /// name = "foo"
+    /// version = "1.3.0"
+    /// crates-io = true
+    /// status = "direct"
+    /// features = ["alloc", "std"]
+    /// "#;
+    ///
+    /// let old = Summary::parse(OLD).unwrap();
+    /// let new = Summary::parse(NEW).unwrap();
+    ///
+    /// let mut notes = vec![];
+    /// old.diff(&new).for_each_change(|entry| match entry.change {
+    ///     SummaryChange::VersionChanged { old_version, new_version } => {
+    ///         notes.push(format!("{}: {} -> {}", entry.summary_id.name, old_version, new_version));
+    ///     }
+    ///     SummaryChange::FeatureAdded { feature } => {
+    ///         notes.push(format!("{}: enabled {}", entry.summary_id.name, feature));
+    ///     }
+    ///     _ => {}
+    /// });
+    /// assert_eq!(notes, ["foo: 1.2.3 -> 1.3.0", "foo: enabled alloc"]);
+    /// ```
+    pub fn for_each_change(&self, mut f: impl FnMut(SummaryDiffEntry<'a>)) {
+        for (kind, package_diff) in [
+            (PlatformKind::Target, &self.target_packages),
+            (PlatformKind::Host, &self.host_packages),
+        ] {
+            let mut changed: Vec<_> = package_diff.changed.iter().collect();
+            changed.sort_by_key(|(summary_id, status)| changed_sort_key(summary_id, status));
+            for (summary_id, status) in changed {
+                SummaryChange::for_status(status, summary_id, |change| {
+                    f(SummaryDiffEntry {
+                        kind,
+                        summary_id,
+                        change,
+                    })
+                });
+            }
+        }
+    }
+}
+
+/// A diff of two combined summaries, performed platform by platform.
+///
+/// A combined summary is a v2 summary that covers several platforms, for example one produced
+/// through [`SummaryV2::merge`]. Each platform label is diffed separately, and identical changes
+/// across platforms are then grouped together so that they only need to be looked at once.
+///
+/// Created through [`SummaryV2::diff`] or [`CombinedDiff::new`].
+#[derive(Clone, Debug)]
+pub struct CombinedDiff {
+    old: BTreeMap<String, Summary>,
+    new: BTreeMap<String, Summary>,
+    // Used for platforms that are only present on one side.
+    empty: Summary,
+}
+
+impl CombinedDiff {
+    /// Computes a diff between two combined summaries.
+    pub fn new(old: &SummaryV2, new: &SummaryV2) -> Self {
+        let split = |summary: &SummaryV2| -> BTreeMap<String, Summary> {
+            summary
+                .platforms()
+                .into_iter()
+                .map(|platform| (platform.to_owned(), summary.to_v1_for_platform(platform)))
+                .collect()
+        };
+        Self {
+            old: split(old),
+            new: split(new),
+            empty: Summary::default(),
+        }
+    }
+
+    /// Returns the platform labels present in either summary, in sorted order.
+    pub fn platforms(&self) -> BTreeSet<&str> {
+        self.old
+            .keys()
+            .chain(self.new.keys())
+            .map(|platform| platform.as_str())
+            .collect()
+    }
+
+    /// Returns the diff for an individual platform label.
+    ///
+    /// A platform that's only present in one of the summaries is diffed against an empty summary.
+    /// Returns `None` if the platform isn't present in either summary.
+    pub fn platform_diff(&self, platform: &str) -> Option<SummaryDiff<'_>> {
+        let old = self.old.get(platform);
+        let new = self.new.get(platform);
+        if old.is_none() && new.is_none() {
+            return None;
+        }
+        Some(SummaryDiff::new(
+            old.unwrap_or(&self.empty),
+            new.unwrap_or(&self.empty),
+        ))
+    }
+
+    /// Returns true if there are any changes in this diff.
+    pub fn is_changed(&self) -> bool {
+        !self.is_unchanged()
+    }
+
+    /// Returns true if there are no changes on any platform.
+    pub fn is_unchanged(&self) -> bool {
+        self.platforms().into_iter().all(|platform| {
+            self.platform_diff(platform)
+                .map_or(true, |diff| diff.is_unchanged())
+        })
+    }
+
+    /// Returns the changes across all platforms.
+    ///
+    /// A change that's identical on several platforms is only returned once, along with the list
+    /// of platforms it applies to. Changes are returned in the same order as in reports: added,
+    /// then modified, then removed.
+    pub fn changes(&self) -> Vec<CombinedChange<'_>> {
+        let mut changes: Vec<CombinedChange<'_>> = vec![];
+        for platform in self.platforms() {
+            let diff = self
+                .platform_diff(platform)
+                .expect("platform is present in at least one summary");
+            for (kind, package_diff) in [
+                (PlatformKind::Target, diff.target_packages),
+                (PlatformKind::Host, diff.host_packages),
+            ] {
+                for (summary_id, status) in package_diff.changed {
+                    match changes.iter_mut().find(|change| {
+                        change.kind == kind
+                            && change.summary_id == summary_id
+                            && change.status == status
+                    }) {
+                        Some(change) => {
+                            change.platforms.insert(platform);
+                        }
+                        None => changes.push(CombinedChange {
+                            kind,
+                            summary_id,
+                            status,
+                            platforms: std::iter::once(platform).collect(),
+                        }),
+                    }
+                }
+            }
+        }
+
+        changes.sort_by(|a, b| {
+            (
+                a.kind,
+                changed_sort_key(a.summary_id, &a.status),
+                &a.platforms,
+            )
+                .cmp(&(
+                    b.kind,
+                    changed_sort_key(b.summary_id, &b.status),
+                    &b.platforms,
+                ))
+        });
+        changes
+    }
+
+    /// Returns a report for this diff.
+    ///
+    /// This report can be used with `fmt::Display`.
+    pub fn report(&self) -> CombinedReport<'_> {
+        CombinedReport::new(self)
+    }
+}
+
+impl Serialize for CombinedDiff {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut state = serializer.serialize_struct("CombinedDiff", 2)?;
+        state.serialize_field("platforms", &self.platforms())?;
+        state.serialize_field("changes", &self.changes())?;
+        state.end()
+    }
+}
+
+/// A change in a [`CombinedDiff`], along with the platforms it applies to.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CombinedChange<'a> {
+    /// Whether this change is for target or host packages.
+    pub kind: PlatformKind,
+
+    /// The package that changed.
+    #[serde(flatten)]
+    pub summary_id: &'a SummaryId,
+
+    /// The change.
+    #[serde(flatten)]
+    pub status: SummaryDiffStatus<'a>,
+
+    /// The platform labels this change applies to, in sorted order.
+    pub platforms: BTreeSet<&'a str>,
 }
 
 /// Type alias for list entries in the `PackageDiff::unchanged` map.
@@ -454,6 +701,164 @@ impl<'a> SummaryDiffStatus<'a> {
     }
 }
 
+/// An individual change in a [`SummaryDiff`], passed to [`SummaryDiff::for_each_change`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SummaryDiffEntry<'a> {
+    /// Whether this change is for a target or host package.
+    pub kind: PlatformKind,
+
+    /// The package that changed.
+    ///
+    /// For modified packages, this has the new version and source.
+    pub summary_id: &'a SummaryId,
+
+    /// The change.
+    pub change: SummaryChange<'a>,
+}
+
+/// The kind of an individual change in a [`SummaryDiffEntry`], along with its details.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SummaryChange<'a> {
+    /// The package was added.
+    PackageAdded {
+        /// The information for this package.
+        info: &'a PackageInfo,
+    },
+
+    /// The package was removed.
+    PackageRemoved {
+        /// The information this package used to have.
+        old_info: &'a PackageInfo,
+    },
+
+    /// The version of the package changed.
+    VersionChanged {
+        /// The old version.
+        old_version: &'a Version,
+
+        /// The new version.
+        new_version: &'a Version,
+    },
+
+    /// The source of the package changed.
+    SourceChanged {
+        /// The old source.
+        old_source: &'a SummarySource,
+
+        /// The new source.
+        new_source: &'a SummarySource,
+    },
+
+    /// The status of the package changed.
+    StatusChanged {
+        /// The old status.
+        old_status: PackageStatus,
+
+        /// The new status.
+        new_status: PackageStatus,
+    },
+
+    /// A feature was enabled for the package.
+    FeatureAdded {
+        /// The name of the feature.
+        feature: &'a str,
+    },
+
+    /// A feature was disabled for the package.
+    FeatureRemoved {
+        /// The name of the feature.
+        feature: &'a str,
+    },
+
+    /// An optional dependency was enabled for the package.
+    OptionalDepAdded {
+        /// The name of the optional dependency.
+        dep: &'a str,
+    },
+
+    /// An optional dependency was disabled for the package.
+    OptionalDepRemoved {
+        /// The name of the optional dependency.
+        dep: &'a str,
+    },
+}
+
+impl<'a> SummaryChange<'a> {
+    /// Returns the [`ChangeKind`] this change falls under, as used by [`DiffRules`].
+    pub fn change_kind(&self) -> ChangeKind {
+        match self {
+            SummaryChange::PackageAdded { .. } => ChangeKind::Added,
+            SummaryChange::PackageRemoved { .. } => ChangeKind::Removed,
+            SummaryChange::VersionChanged { .. } => ChangeKind::VersionChanged,
+            SummaryChange::SourceChanged { .. } => ChangeKind::SourceChanged,
+            SummaryChange::StatusChanged { .. } => ChangeKind::StatusChanged,
+            SummaryChange::FeatureAdded { .. } | SummaryChange::FeatureRemoved { .. } => {
+                ChangeKind::FeaturesChanged
+            }
+            SummaryChange::OptionalDepAdded { .. } | SummaryChange::OptionalDepRemoved { .. } => {
+                ChangeKind::OptionalDepsChanged
+            }
+        }
+    }
+
+    /// Splits a diff status into individual changes, calling `f` for each one.
+    fn for_status(
+        status: &SummaryDiffStatus<'a>,
+        summary_id: &'a SummaryId,
+        mut f: impl FnMut(SummaryChange<'a>),
+    ) {
+        match status {
+            SummaryDiffStatus::Added { info } => f(SummaryChange::PackageAdded { info }),
+            SummaryDiffStatus::Removed { old_info } => {
+                f(SummaryChange::PackageRemoved { old_info })
+            }
+            SummaryDiffStatus::Modified {
+                old_version,
+                old_source,
+                old_status,
+                new_status,
+                added_features,
+                removed_features,
+                added_optional_deps,
+                removed_optional_deps,
+                ..
+            } => {
+                if let Some(old_version) = old_version {
+                    f(SummaryChange::VersionChanged {
+                        old_version,
+                        new_version: &summary_id.version,
+                    });
+                }
+                if let Some(old_source) = old_source {
+                    f(SummaryChange::SourceChanged {
+                        old_source,
+                        new_source: &summary_id.source,
+                    });
+                }
+                if let Some(old_status) = old_status {
+                    f(SummaryChange::StatusChanged {
+                        old_status: *old_status,
+                        new_status: *new_status,
+                    });
+                }
+                for feature in added_features {
+                    f(SummaryChange::FeatureAdded { feature });
+                }
+                for feature in removed_features {
+                    f(SummaryChange::FeatureRemoved { feature });
+                }
+                for dep in added_optional_deps {
+                    f(SummaryChange::OptionalDepAdded { dep });
+                }
+                for dep in removed_optional_deps {
+                    f(SummaryChange::OptionalDepRemoved { dep });
+                }
+            }
+        }
+    }
+}
+
 mod removed_impl {
     use super::*;
     use serde::Serializer;
@@ -0,0 +1,93 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! JSON serialization for summaries and diffs.
+//!
+//! The JSON representations use the same field names and layout as the TOML ones.
+
+use crate::{
+    diff::{CombinedDiff, SummaryDiff},
+    summary_v2::VersionProbe,
+    AnySummary, Summary, SummaryFormat, SummaryV2,
+};
+
+impl SummaryFormat {
+    /// Detects the format of the given JSON summary string, through its `summary-version` key.
+    ///
+    /// Summaries without a `summary-version` key are in the v1 format.
+    pub fn detect_json(s: &str) -> Result<Self, serde_json::Error> {
+        let probe: VersionProbe = serde_json::from_str(s)?;
+        Self::from_version(probe.summary_version)
+    }
+}
+
+impl Summary {
+    /// Deserializes a summary from the given JSON string.
+    ///
+    /// Returns an error if the string is a summary in the v2 format. To read summaries in either
+    /// format, use [`AnySummary::parse_json`].
+    pub fn parse_json(s: &str) -> Result<Self, serde_json::Error> {
+        match SummaryFormat::detect_json(s)? {
+            SummaryFormat::V1 => serde_json::from_str(s),
+            SummaryFormat::V2 => Err(serde::de::Error::custom(
+                "summary is in the v2 format: use SummaryV2::parse_json or AnySummary::parse_json",
+            )),
+        }
+    }
+
+    /// Serializes this summary to a pretty-printed JSON string.
+    pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl SummaryV2 {
+    /// Deserializes a v2 summary from the given JSON string.
+    pub fn parse_json(s: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+
+    /// Serializes this summary to a pretty-printed JSON string.
+    pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl AnySummary {
+    /// Deserializes a summary in either format from the given JSON string.
+    ///
+    /// The format is determined through [`SummaryFormat::detect_json`].
+    pub fn parse_json(s: &str) -> Result<Self, serde_json::Error> {
+        match SummaryFormat::detect_json(s)? {
+            SummaryFormat::V1 => Ok(AnySummary::V1(serde_json::from_str(s)?)),
+            SummaryFormat::V2 => Ok(AnySummary::V2(serde_json::from_str(s)?)),
+        }
+    }
+
+    /// Serializes this summary to a pretty-printed JSON string, in its own format.
+    pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
+        match self {
+            AnySummary::V1(summary) => summary.to_json_string(),
+            AnySummary::V2(summary) => summary.to_json_string(),
+        }
+    }
+}
+
+impl<'a> SummaryDiff<'a> {
+    /// Serializes this diff to a pretty-printed JSON string.
+    ///
+    /// The output has the same layout as the TOML serialization of a `SummaryDiff`.
+    pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl CombinedDiff {
+    /// Serializes this diff to a pretty-printed JSON string.
+    ///
+    /// The output has a list of `platforms` present in either summary, and a list of `changes` as
+    /// returned by [`CombinedDiff::changes`].
+    pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
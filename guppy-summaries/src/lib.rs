@@ -106,15 +106,70 @@
 //!     diff_str,
 //! );
 //! ```
+//!
+//! Reports can also be rendered as Markdown, for posting on pull requests, or as a standalone
+//! HTML document, for publishing as a CI artifact:
+//!
+//! ```rust
+//! # use guppy_summaries::Summary;
+//! # let summary = Summary::default();
+//! let diff = summary.diff(&summary);
+//! let markdown = format!("{}", diff.markdown_report());
+//! let html = format!("{}", diff.html_report());
+//! ```
+//!
+//! To gate CI on a diff, changes can be classified with `DiffRules`, a list of rules matching
+//! on package names, change kinds and features. Each change is assigned a severity (ignore, info,
+//! warn or error) by the first matching rule, and `SummaryDiff::max_severity` returns the highest
+//! severity in the diff. For example, version bumps of internal crates can be ignored while any new
+//! third-party package is an error.
+//!
+//! To react to changes programmatically, `SummaryDiff::for_each_change` visits every individual
+//! change in a diff, such as a version bump or an added feature, as a typed `SummaryChange`.
+//!
+//! # Summary formats
+//!
+//! The format shown above is the v1 format, represented by `Summary`. When a build is performed
+//! for several platforms, the v2 format, represented by `SummaryV2`, records features and
+//! dependency kinds separately for every platform rather than flattening them together. Summaries
+//! in either format can be read with `AnySummary::parse`, and v2 summaries can be converted to v1
+//! for an individual platform or for all platforms together.
+//!
+//! Summaries generated separately, for example one per CI job, can be combined with
+//! `SummaryV2::merge`, with each platform label acting as a provenance tag. Diffing two combined
+//! summaries with `SummaryV2::diff` compares them platform by platform, and groups changes that are
+//! identical across platforms.
+//!
+//! # Optional features
+//!
+//! * `json`: Adds JSON serialization and deserialization for summaries and diffs, through methods
+//!   like `Summary::parse_json` and `SummaryDiff::to_json_string`. The JSON representation has the
+//!   same field names and layout as the TOML one, for consumption by non-Rust tooling.
+//! * `signing`: Adds a stable content hash for summaries through `Summary::content_hash`, and hooks
+//!   to sign summaries and verify detached signatures through `Summary::sign` and `Summary::verify`.
+//!   This can be used to check that a summary checked into a repository was generated by CI rather
+//!   than edited by hand.
 
 #![forbid(unsafe_code)]
 #![warn(missing_docs)]
 
 pub mod diff;
+#[cfg(feature = "json")]
+mod json;
 // report::SummaryReport is exported through the diff module.
 mod report;
+// The rule types are exported through the diff module.
+mod rules;
+#[cfg(feature = "signing")]
+mod signing;
 mod summary;
+mod summary_v2;
 #[cfg(test)]
 mod unit_tests;
 
+#[doc(hidden)]
+pub use rules::glob_match;
+#[cfg(feature = "signing")]
+pub use signing::*;
 pub use summary::*;
+pub use summary_v2::*;
@@ -2,8 +2,11 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
-    diff::{changed_sort_key, PackageDiff, SummaryDiff, SummaryDiffStatus},
-    SummaryId,
+    diff::{
+        changed_sort_key, CombinedChange, CombinedDiff, PackageDiff, SummaryDiff,
+        SummaryDiffStatus, SummaryDiffTag,
+    },
+    PlatformKind, SummaryId,
 };
 use std::fmt;
 
@@ -42,6 +45,26 @@ impl<'a, 'b> SummaryReport<'a, 'b> {
 
         v
     }
+
+    /// Returns the sections of this report that have changes, along with their headings.
+    fn sections(
+        &self,
+    ) -> impl Iterator<Item = (&'static str, &'b PackageDiff<'a>, &[SortedEntry<'_>])> {
+        [
+            (
+                "Target packages",
+                &self.diff.target_packages,
+                self.sorted_target.as_slice(),
+            ),
+            (
+                "Host packages",
+                &self.diff.host_packages,
+                self.sorted_host.as_slice(),
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, package_diff, _)| !package_diff.is_unchanged())
+    }
 }
 
 impl<'a, 'b> fmt::Display for SummaryReport<'a, 'b> {
@@ -98,112 +121,421 @@ impl<'x> fmt::Display for PackageReport<'x> {
             )?;
 
             // Print out other versions if available.
-            if let Some(unchanged_list) = self.package_diff.unchanged.get(summary_id.name.as_str())
-            {
-                write!(f, " (other versions: ")?;
-                display_list(f, unchanged_list.iter().map(|(version, _, _)| *version))?;
-                write!(f, ")")?;
+            if let Some(other_versions) = other_versions(self.package_diff, summary_id) {
+                write!(f, " (other versions: {})", other_versions)?;
             }
 
             writeln!(f)?;
 
-            match status {
-                SummaryDiffStatus::Added { info } => {
-                    write!(f, "    * features: ")?;
-                    display_list(f, &info.features)?;
-                    writeln!(f)?;
-                }
-                SummaryDiffStatus::Removed { old_info } => {
-                    write!(f, "    * (old features: ")?;
-                    display_list(f, &old_info.features)?;
-                    writeln!(f, ")")?;
-                }
-                SummaryDiffStatus::Modified {
-                    old_version,
-                    old_source,
-                    old_status,
-                    // The new status is printed in the package header.
-                    new_status: _,
-                    added_features,
-                    removed_features,
-                    unchanged_features,
-                    added_optional_deps,
-                    removed_optional_deps,
-                    unchanged_optional_deps,
-                } => {
-                    if let Some(old_version) = old_version {
-                        let change_str = if summary_id.version > **old_version {
-                            "upgraded"
-                        } else {
-                            "DOWNGRADED"
-                        };
-                        writeln!(f, "    * version {} from {}", change_str, old_version)?;
-                    }
-                    if let Some(old_source) = old_source {
-                        writeln!(f, "    * source changed from {}", old_source)?;
-                    }
-                    if let Some(old_status) = old_status {
-                        writeln!(f, "    * status changed from {}", old_status)?;
-                    }
+            for detail in package_details(summary_id, status) {
+                writeln!(f, "    * {}", detail)?;
+            }
+        }
 
-                    // ---
+        Ok(())
+    }
+}
 
-                    if !added_features.is_empty() {
-                        write!(f, "    * added features: ")?;
-                        display_list(f, added_features.iter().copied())?;
-                        writeln!(f)?;
+/// A report of a diff between two summaries, rendered as Markdown.
+///
+/// Changes are grouped into collapsible sections by category (added, modified and removed), which
+/// makes this report suitable for posting as a comment on a pull request.
+///
+/// This report can be generated or written to a file through `fmt::Display`.
+#[derive(Clone, Debug)]
+pub struct SummaryMarkdownReport<'a, 'b> {
+    report: SummaryReport<'a, 'b>,
+}
+
+impl<'a, 'b> SummaryMarkdownReport<'a, 'b> {
+    /// Creates a new `SummaryMarkdownReport` that can be displayed.
+    pub fn new(diff: &'b SummaryDiff<'a>) -> Self {
+        Self {
+            report: SummaryReport::new(diff),
+        }
+    }
+}
+
+impl<'a, 'b> fmt::Display for SummaryMarkdownReport<'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let report = &self.report;
+        if report.diff.is_unchanged() {
+            return writeln!(f, "No changes.");
+        }
+
+        for (heading, package_diff, sorted) in report.sections() {
+            writeln!(f, "### {}\n", heading)?;
+            for (tag, entries) in group_by_tag(sorted) {
+                writeln!(
+                    f,
+                    "<details>\n<summary>{} ({})</summary>\n",
+                    tag_label(tag),
+                    entries.len()
+                )?;
+                for (summary_id, status) in entries {
+                    write!(
+                        f,
+                        "- `{} {}` ({}, {})",
+                        summary_id.name,
+                        summary_id.version,
+                        status.latest_status(),
+                        MarkdownEscape(&summary_id.source.to_string()),
+                    )?;
+                    if let Some(other_versions) = other_versions(package_diff, summary_id) {
+                        write!(
+                            f,
+                            " (other versions: {})",
+                            MarkdownEscape(&other_versions.to_string())
+                        )?;
                     }
-                    if !removed_features.is_empty() {
-                        write!(f, "    * removed features: ")?;
-                        display_list(f, removed_features.iter().copied())?;
-                        writeln!(f)?;
+                    writeln!(f)?;
+
+                    for detail in package_details(summary_id, status) {
+                        writeln!(f, "  - {}", MarkdownEscape(&detail))?;
                     }
-                    write!(f, "    * (unchanged features: ")?;
-                    display_list(f, unchanged_features.iter().copied())?;
-                    writeln!(f, ")")?;
+                }
+                writeln!(f, "\n</details>\n")?;
+            }
+        }
 
-                    // ---
+        Ok(())
+    }
+}
+
+/// A report of a diff between two summaries, rendered as a standalone HTML document.
+///
+/// Changes are grouped into collapsible sections by category (added, modified and removed), which
+/// makes this report suitable for publishing as a CI artifact.
+///
+/// This report can be generated or written to a file through `fmt::Display`.
+#[derive(Clone, Debug)]
+pub struct SummaryHtmlReport<'a, 'b> {
+    report: SummaryReport<'a, 'b>,
+    title: String,
+}
+
+impl<'a, 'b> SummaryHtmlReport<'a, 'b> {
+    /// Creates a new `SummaryHtmlReport` that can be displayed.
+    pub fn new(diff: &'b SummaryDiff<'a>) -> Self {
+        Self {
+            report: SummaryReport::new(diff),
+            title: "Summary diff".to_owned(),
+        }
+    }
+
+    /// Sets the title of the HTML document.
+    ///
+    /// The default title is "Summary diff".
+    pub fn set_title(&mut self, title: impl Into<String>) -> &mut Self {
+        self.title = title.into();
+        self
+    }
+}
 
-                    if !added_optional_deps.is_empty() {
-                        write!(f, "    * added optional dependencies: ")?;
-                        display_list(f, added_optional_deps.iter().copied())?;
-                        writeln!(f)?;
+impl<'a, 'b> fmt::Display for SummaryHtmlReport<'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let report = &self.report;
+        let title = HtmlEscape(&self.title);
+        writeln!(f, "<!DOCTYPE html>")?;
+        writeln!(f, "<html>")?;
+        writeln!(f, "<head>")?;
+        writeln!(f, "<meta charset=\"utf-8\">")?;
+        writeln!(f, "<title>{}</title>", title)?;
+        writeln!(f, "</head>")?;
+        writeln!(f, "<body>")?;
+        writeln!(f, "<h1>{}</h1>", title)?;
+
+        if report.diff.is_unchanged() {
+            writeln!(f, "<p>No changes.</p>")?;
+        }
+
+        for (heading, package_diff, sorted) in report.sections() {
+            writeln!(f, "<h2>{}</h2>", heading)?;
+            for (tag, entries) in group_by_tag(sorted) {
+                writeln!(f, "<details open>")?;
+                writeln!(
+                    f,
+                    "<summary>{} ({})</summary>",
+                    tag_label(tag),
+                    entries.len()
+                )?;
+                writeln!(f, "<ul>")?;
+                for (summary_id, status) in entries {
+                    write!(
+                        f,
+                        "<li><code>{} {}</code> ({}, {})",
+                        HtmlEscape(&summary_id.name),
+                        summary_id.version,
+                        status.latest_status(),
+                        HtmlEscape(&summary_id.source.to_string()),
+                    )?;
+                    if let Some(other_versions) = other_versions(package_diff, summary_id) {
+                        write!(
+                            f,
+                            " (other versions: {})",
+                            HtmlEscape(&other_versions.to_string())
+                        )?;
                     }
-                    if !removed_optional_deps.is_empty() {
-                        write!(f, "    * removed optional dependencies: ")?;
-                        display_list(f, removed_optional_deps.iter().copied())?;
-                        writeln!(f)?;
+                    writeln!(f)?;
+
+                    writeln!(f, "<ul>")?;
+                    for detail in package_details(summary_id, status) {
+                        writeln!(f, "<li>{}</li>", HtmlEscape(&detail))?;
                     }
-                    write!(f, "    * (unchanged optional dependencies: ")?;
-                    display_list(f, unchanged_optional_deps.iter().copied())?;
-                    writeln!(f, ")")?;
+                    writeln!(f, "</ul>")?;
+                    writeln!(f, "</li>")?;
                 }
+                writeln!(f, "</ul>")?;
+                writeln!(f, "</details>")?;
             }
         }
 
+        writeln!(f, "</body>")?;
+        writeln!(f, "</html>")
+    }
+}
+
+/// A report of a diff between two combined summaries.
+///
+/// Each change is printed once, followed by the platforms it applies to in square brackets.
+///
+/// This report can be generated or written to a file through `fmt::Display`.
+#[derive(Clone, Debug)]
+pub struct CombinedReport<'a> {
+    changes: Vec<CombinedChange<'a>>,
+}
+
+impl<'a> CombinedReport<'a> {
+    /// Creates a new `CombinedReport` that can be displayed.
+    pub fn new(diff: &'a CombinedDiff) -> Self {
+        Self {
+            changes: diff.changes(),
+        }
+    }
+}
+
+impl<'a> fmt::Display for CombinedReport<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (kind, heading) in [
+            (PlatformKind::Target, "target packages"),
+            (PlatformKind::Host, "host packages"),
+        ] {
+            let mut changes = self
+                .changes
+                .iter()
+                .filter(|change| change.kind == kind)
+                .peekable();
+            if changes.peek().is_none() {
+                continue;
+            }
+
+            writeln!(f, "{}:", heading)?;
+            for change in changes {
+                writeln!(
+                    f,
+                    "  {} {} {} ({}, {}) [{}]",
+                    change.status.tag(),
+                    change.summary_id.name,
+                    change.summary_id.version,
+                    change.status.latest_status(),
+                    change.summary_id.source,
+                    DisplayList::new(&change.platforms),
+                )?;
+                for detail in package_details(change.summary_id, &change.status) {
+                    writeln!(f, "    * {}", detail)?;
+                }
+            }
+            writeln!(f)?;
+        }
+
         Ok(())
     }
 }
 
-fn display_list<I>(f: &mut fmt::Formatter, items: I) -> fmt::Result
-where
-    I: IntoIterator,
-    I::Item: fmt::Display,
-    I::IntoIter: ExactSizeIterator,
-{
-    let items = items.into_iter();
-    let len = items.len();
-    if len == 0 {
-        write!(f, "[none]")?;
+type SortedEntry<'x> = (&'x SummaryId, &'x SummaryDiffStatus<'x>);
+
+/// Splits a sorted list of changes into runs with the same tag.
+fn group_by_tag<'x, 'y>(
+    sorted: &'y [SortedEntry<'x>],
+) -> impl Iterator<Item = (SummaryDiffTag, &'y [SortedEntry<'x>])> {
+    let mut rest = sorted;
+    std::iter::from_fn(move || {
+        let tag = rest.first()?.1.tag();
+        let len = rest
+            .iter()
+            .position(|(_, status)| status.tag() != tag)
+            .unwrap_or(rest.len());
+        let (group, remaining) = rest.split_at(len);
+        rest = remaining;
+        Some((tag, group))
+    })
+}
+
+fn tag_label(tag: SummaryDiffTag) -> &'static str {
+    match tag {
+        SummaryDiffTag::Added => "Added",
+        SummaryDiffTag::Modified => "Modified",
+        SummaryDiffTag::Removed => "Removed",
     }
+}
 
-    for (idx, item) in items.enumerate() {
-        write!(f, "{}", item)?;
-        // Add a comma for all items except the last one.
-        if idx + 1 < len {
-            write!(f, ", ")?;
+/// Returns the other, unchanged versions of this package, if any.
+fn other_versions<'x>(
+    package_diff: &'x PackageDiff<'x>,
+    summary_id: &SummaryId,
+) -> Option<DisplayList<&'x semver::Version>> {
+    let unchanged_list = package_diff.unchanged.get(summary_id.name.as_str())?;
+    Some(DisplayList(
+        unchanged_list
+            .iter()
+            .map(|(version, _, _)| *version)
+            .collect(),
+    ))
+}
+
+/// Returns the lines of detail printed out for each changed package.
+fn package_details(summary_id: &SummaryId, status: &SummaryDiffStatus<'_>) -> Vec<String> {
+    let mut details = vec![];
+    match status {
+        SummaryDiffStatus::Added { info } => {
+            details.push(format!("features: {}", DisplayList::new(&info.features)));
         }
+        SummaryDiffStatus::Removed { old_info } => {
+            details.push(format!(
+                "(old features: {})",
+                DisplayList::new(&old_info.features)
+            ));
+        }
+        SummaryDiffStatus::Modified {
+            old_version,
+            old_source,
+            old_status,
+            // The new status is printed in the package header.
+            new_status: _,
+            added_features,
+            removed_features,
+            unchanged_features,
+            added_optional_deps,
+            removed_optional_deps,
+            unchanged_optional_deps,
+        } => {
+            if let Some(old_version) = old_version {
+                let change_str = if summary_id.version > **old_version {
+                    "upgraded"
+                } else {
+                    "DOWNGRADED"
+                };
+                details.push(format!("version {} from {}", change_str, old_version));
+            }
+            if let Some(old_source) = old_source {
+                details.push(format!("source changed from {}", old_source));
+            }
+            if let Some(old_status) = old_status {
+                details.push(format!("status changed from {}", old_status));
+            }
+
+            // ---
+
+            if !added_features.is_empty() {
+                details.push(format!(
+                    "added features: {}",
+                    DisplayList::new(added_features)
+                ));
+            }
+            if !removed_features.is_empty() {
+                details.push(format!(
+                    "removed features: {}",
+                    DisplayList::new(removed_features)
+                ));
+            }
+            details.push(format!(
+                "(unchanged features: {})",
+                DisplayList::new(unchanged_features)
+            ));
+
+            // ---
+
+            if !added_optional_deps.is_empty() {
+                details.push(format!(
+                    "added optional dependencies: {}",
+                    DisplayList::new(added_optional_deps)
+                ));
+            }
+            if !removed_optional_deps.is_empty() {
+                details.push(format!(
+                    "removed optional dependencies: {}",
+                    DisplayList::new(removed_optional_deps)
+                ));
+            }
+            details.push(format!(
+                "(unchanged optional dependencies: {})",
+                DisplayList::new(unchanged_optional_deps)
+            ));
+        }
+    }
+
+    details
+}
+
+/// A comma-separated list, or `[none]` if empty.
+struct DisplayList<T>(Vec<T>);
+
+impl<T> DisplayList<T> {
+    fn new(items: impl IntoIterator<Item = T>) -> Self {
+        Self(items.into_iter().collect())
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for DisplayList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "[none]");
+        }
+
+        for (idx, item) in self.0.iter().enumerate() {
+            write!(f, "{}", item)?;
+            // Add a comma for all items except the last one.
+            if idx + 1 < self.0.len() {
+                write!(f, ", ")?;
+            }
+        }
+
+        Ok(())
     }
+}
+
+/// Escapes characters that have a special meaning in Markdown.
+struct MarkdownEscape<'s>(&'s str);
+
+impl<'s> fmt::Display for MarkdownEscape<'s> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' | '~' => {
+                    write!(f, "\\{}", c)?
+                }
+                c => write!(f, "{}", c)?,
+            }
+        }
+        Ok(())
+    }
+}
 
-    Ok(())
+/// Escapes characters that have a special meaning in HTML.
+struct HtmlEscape<'s>(&'s str);
+
+impl<'s> fmt::Display for HtmlEscape<'s> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&#39;")?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,516 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    diff::{changed_sort_key, PackageDiff, SummaryDiff, SummaryDiffStatus},
+    PackageStatus, PlatformKind, SummaryId,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, fmt};
+
+/// How important a change in a summary diff is.
+///
+/// Severities are ordered from least to most important, so `Severity::Ignore < Severity::Error`.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// The change should be ignored.
+    Ignore,
+
+    /// The change is informational.
+    Info,
+
+    /// The change should be flagged, but is not an error.
+    Warn,
+
+    /// The change is an error, for example one that should fail a CI check.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Ignore => write!(f, "ignore"),
+            Severity::Info => write!(f, "info"),
+            Severity::Warn => write!(f, "warn"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// An individual aspect of a change to a package.
+///
+/// A modified package may have several of these at once, for example if both its version and its
+/// features changed.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeKind {
+    /// The package was added.
+    Added,
+
+    /// The package was removed.
+    Removed,
+
+    /// The version of the package changed.
+    VersionChanged,
+
+    /// The source of the package changed.
+    SourceChanged,
+
+    /// The status of the package changed.
+    StatusChanged,
+
+    /// Features were added to or removed from the package.
+    FeaturesChanged,
+
+    /// Optional dependencies were added to or removed from the package.
+    OptionalDepsChanged,
+}
+
+impl ChangeKind {
+    /// Returns the set of change kinds that make up this diff status.
+    pub fn for_status(status: &SummaryDiffStatus<'_>) -> BTreeSet<ChangeKind> {
+        let mut kinds = BTreeSet::new();
+        match status {
+            SummaryDiffStatus::Added { .. } => {
+                kinds.insert(ChangeKind::Added);
+            }
+            SummaryDiffStatus::Removed { .. } => {
+                kinds.insert(ChangeKind::Removed);
+            }
+            SummaryDiffStatus::Modified {
+                old_version,
+                old_source,
+                old_status,
+                added_features,
+                removed_features,
+                added_optional_deps,
+                removed_optional_deps,
+                ..
+            } => {
+                if old_version.is_some() {
+                    kinds.insert(ChangeKind::VersionChanged);
+                }
+                if old_source.is_some() {
+                    kinds.insert(ChangeKind::SourceChanged);
+                }
+                if old_status.is_some() {
+                    kinds.insert(ChangeKind::StatusChanged);
+                }
+                if !added_features.is_empty() || !removed_features.is_empty() {
+                    kinds.insert(ChangeKind::FeaturesChanged);
+                }
+                if !added_optional_deps.is_empty() || !removed_optional_deps.is_empty() {
+                    kinds.insert(ChangeKind::OptionalDepsChanged);
+                }
+            }
+        }
+        kinds
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::Added => write!(f, "added"),
+            ChangeKind::Removed => write!(f, "removed"),
+            ChangeKind::VersionChanged => write!(f, "version-changed"),
+            ChangeKind::SourceChanged => write!(f, "source-changed"),
+            ChangeKind::StatusChanged => write!(f, "status-changed"),
+            ChangeKind::FeaturesChanged => write!(f, "features-changed"),
+            ChangeKind::OptionalDepsChanged => write!(f, "optional-deps-changed"),
+        }
+    }
+}
+
+/// A set of rules used to assign a severity to each change in a summary diff.
+///
+/// Rules are checked in order, and the first rule that matches a change determines its severity.
+/// Changes that aren't matched by any rule are assigned the default severity.
+///
+/// # Examples
+///
+/// Rules can be written in TOML:
+///
+/// ```rust
+/// use guppy_summaries::{diff::{DiffRules, Severity}, Summary};
+///
+/// // Version bumps of internal crates are expected, but any new third-party package should
+/// // fail CI.
+/// static RULES: &str = r#"
+/// default-severity = "info"
+///
+/// [[rule]]
+/// name = "internal-*"
+/// change = ["version-changed"]
+/// severity = "ignore"
+///
+/// [[rule]]
+/// status = ["direct", "transitive"]
+/// change = ["added"]
+/// severity = "error"
+/// "#;
+///
+/// let rules = DiffRules::parse(RULES).expect("rules are valid");
+///
+/// static OLD: &str = r#"
+/// [[target-package]]
+/// name = "internal-util"
+/// version = "0.1.0"
+/// workspace-path = "util"
+/// status = "workspace"
+/// features = []
+/// "#;
+///
+/// static NEW: &str = r#"
+/// [[target-package]]
+/// name = "internal-util"
+/// version = "0.2.0"
+/// workspace-path = "util"
+/// status = "workspace"
+/// features = []
+///
+/// [[target-package]]
+/// name = "once_cell"
+/// version = "1.19.0"
+/// crates-io = true
+/// status = "direct"
+/// features = ["std"]
+/// "#;
+///
+/// let old = Summary::parse(OLD).unwrap();
+/// let new = Summary::parse(NEW).unwrap();
+/// let diff = old.diff(&new);
+/// assert_eq!(diff.max_severity(&rules), Severity::Error);
+/// ```
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DiffRules {
+    #[serde(default = "default_severity")]
+    default_severity: Severity,
+
+    #[serde(rename = "rule", default, skip_serializing_if = "Vec::is_empty")]
+    rules: Vec<DiffRule>,
+}
+
+fn default_severity() -> Severity {
+    Severity::Info
+}
+
+impl DiffRules {
+    /// Creates a new, empty set of rules which assigns `Severity::Info` to every change.
+    pub fn new() -> Self {
+        Self {
+            default_severity: default_severity(),
+            rules: vec![],
+        }
+    }
+
+    /// Deserializes a set of rules from the given TOML string.
+    pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    /// Sets the severity for changes that aren't matched by any rule.
+    ///
+    /// The default is `Severity::Info`.
+    pub fn set_default_severity(&mut self, severity: Severity) -> &mut Self {
+        self.default_severity = severity;
+        self
+    }
+
+    /// Adds a rule to the end of this set. The rule is checked after all existing rules.
+    pub fn add_rule(&mut self, rule: DiffRule) -> &mut Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Returns the rules in this set, in the order they're checked.
+    pub fn rules(&self) -> &[DiffRule] {
+        &self.rules
+    }
+
+    /// Returns the index of the first rule matching this change, if any.
+    pub fn matching_rule(
+        &self,
+        kind: PlatformKind,
+        summary_id: &SummaryId,
+        status: &SummaryDiffStatus<'_>,
+    ) -> Option<usize> {
+        let change_kinds = ChangeKind::for_status(status);
+        self.rules
+            .iter()
+            .position(|rule| rule.matches_impl(kind, summary_id, status, &change_kinds))
+    }
+
+    /// Returns the severity of this change.
+    pub fn classify(
+        &self,
+        kind: PlatformKind,
+        summary_id: &SummaryId,
+        status: &SummaryDiffStatus<'_>,
+    ) -> Severity {
+        match self.matching_rule(kind, summary_id, status) {
+            Some(idx) => self.rules[idx].severity,
+            None => self.default_severity,
+        }
+    }
+}
+
+impl Default for DiffRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A rule which assigns a severity to matching changes in a summary diff.
+///
+/// A rule matches a change if all of its conditions match. A rule without any conditions matches
+/// every change.
+///
+/// Name and feature patterns are globs, where `*` matches any sequence of characters and `?`
+/// matches any single character.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DiffRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kind: Option<PlatformKind>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<BTreeSet<PackageStatus>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    change: Option<BTreeSet<ChangeKind>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    feature: Option<String>,
+
+    severity: Severity,
+}
+
+impl DiffRule {
+    /// Creates a new rule which assigns this severity to every change.
+    ///
+    /// Use the setters to restrict which changes this rule matches.
+    pub fn new(severity: Severity) -> Self {
+        Self {
+            name: None,
+            kind: None,
+            status: None,
+            change: None,
+            feature: None,
+            severity,
+        }
+    }
+
+    /// Only matches packages with names matching this glob.
+    pub fn set_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Only matches changes to either target or host packages.
+    pub fn set_kind(&mut self, kind: PlatformKind) -> &mut Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Only matches packages with one of these statuses.
+    ///
+    /// For modified packages, the new status is used.
+    pub fn set_statuses(&mut self, statuses: impl IntoIterator<Item = PackageStatus>) -> &mut Self {
+        self.status = Some(statuses.into_iter().collect());
+        self
+    }
+
+    /// Only matches changes made up entirely of these change kinds.
+    ///
+    /// For example, a rule with `ChangeKind::VersionChanged` matches a package whose version was
+    /// bumped, but not one whose version was bumped and whose features changed as well.
+    pub fn set_changes(&mut self, changes: impl IntoIterator<Item = ChangeKind>) -> &mut Self {
+        self.change = Some(changes.into_iter().collect());
+        self
+    }
+
+    /// Only matches changes involving a feature matching this glob.
+    ///
+    /// For added and removed packages, every feature of the package is considered. For modified
+    /// packages, only the features that were added or removed are considered.
+    pub fn set_feature(&mut self, feature: impl Into<String>) -> &mut Self {
+        self.feature = Some(feature.into());
+        self
+    }
+
+    /// Returns the severity assigned by this rule.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Returns true if this rule matches the given change.
+    pub fn matches(
+        &self,
+        kind: PlatformKind,
+        summary_id: &SummaryId,
+        status: &SummaryDiffStatus<'_>,
+    ) -> bool {
+        self.matches_impl(kind, summary_id, status, &ChangeKind::for_status(status))
+    }
+
+    fn matches_impl(
+        &self,
+        kind: PlatformKind,
+        summary_id: &SummaryId,
+        status: &SummaryDiffStatus<'_>,
+        change_kinds: &BTreeSet<ChangeKind>,
+    ) -> bool {
+        if let Some(name) = &self.name {
+            if !glob_match(name, &summary_id.name) {
+                return false;
+            }
+        }
+        if self.kind.is_some_and(|rule_kind| rule_kind != kind) {
+            return false;
+        }
+        if let Some(statuses) = &self.status {
+            if !statuses.contains(&status.latest_status()) {
+                return false;
+            }
+        }
+        if let Some(changes) = &self.change {
+            if !change_kinds.is_subset(changes) {
+                return false;
+            }
+        }
+        if let Some(feature) = &self.feature {
+            let mut features: Box<dyn Iterator<Item = &str>> = match status {
+                SummaryDiffStatus::Added { info } => {
+                    Box::new(info.features.iter().map(|f| f.as_str()))
+                }
+                SummaryDiffStatus::Removed { old_info } => {
+                    Box::new(old_info.features.iter().map(|f| f.as_str()))
+                }
+                SummaryDiffStatus::Modified {
+                    added_features,
+                    removed_features,
+                    ..
+                } => Box::new(added_features.iter().chain(removed_features).copied()),
+            };
+            if !features.any(|f| glob_match(feature, f)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// A change in a summary diff, along with its severity.
+///
+/// Returned by [`SummaryDiff::classify`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClassifiedChange<'a, 'b> {
+    /// Whether this change is for a target or host package.
+    pub kind: PlatformKind,
+
+    /// The package that changed.
+    pub summary_id: &'a SummaryId,
+
+    /// The change.
+    pub status: &'b SummaryDiffStatus<'a>,
+
+    /// The severity assigned to this change.
+    pub severity: Severity,
+
+    /// The index of the rule that assigned the severity, or `None` if the default severity was
+    /// used.
+    pub rule: Option<usize>,
+}
+
+impl<'a> SummaryDiff<'a> {
+    /// Classifies every change in this diff according to the given rules.
+    ///
+    /// Changes are returned in the same order as in reports, target packages first, with ignored
+    /// changes included.
+    pub fn classify<'b>(&'b self, rules: &DiffRules) -> Vec<ClassifiedChange<'a, 'b>> {
+        let mut changes = vec![];
+        for (kind, package_diff) in [
+            (PlatformKind::Target, &self.target_packages),
+            (PlatformKind::Host, &self.host_packages),
+        ] {
+            changes.extend(classify_packages(kind, package_diff, rules));
+        }
+        changes
+    }
+
+    /// Returns the highest severity of any change in this diff according to the given rules.
+    ///
+    /// Returns `Severity::Ignore` if there are no changes.
+    pub fn max_severity(&self, rules: &DiffRules) -> Severity {
+        self.classify(rules)
+            .into_iter()
+            .map(|change| change.severity)
+            .max()
+            .unwrap_or(Severity::Ignore)
+    }
+}
+
+fn classify_packages<'a, 'b>(
+    kind: PlatformKind,
+    package_diff: &'b PackageDiff<'a>,
+    rules: &DiffRules,
+) -> Vec<ClassifiedChange<'a, 'b>> {
+    let mut changes: Vec<_> = package_diff
+        .changed
+        .iter()
+        .map(|(summary_id, status)| {
+            let rule = rules.matching_rule(kind, summary_id, status);
+            ClassifiedChange {
+                kind,
+                summary_id,
+                status,
+                severity: match rule {
+                    Some(idx) => rules.rules[idx].severity,
+                    None => rules.default_severity,
+                },
+                rule,
+            }
+        })
+        .collect();
+    changes.sort_by_key(|change| changed_sort_key(change.summary_id, change.status));
+    changes
+}
+
+/// Matches `s` against a glob pattern supporting `*` and `?`.
+///
+/// This is also used by `guppy` to match package names, so that both use the same glob syntax.
+pub fn glob_match(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+
+    // Standard greedy matching with backtracking to the last `*`.
+    let (mut p_idx, mut s_idx) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while s_idx < s.len() {
+        match pattern.get(p_idx) {
+            Some('*') => {
+                star = Some((p_idx, s_idx));
+                p_idx += 1;
+            }
+            Some(&c) if c == '?' || c == s[s_idx] => {
+                p_idx += 1;
+                s_idx += 1;
+            }
+            _ => match star {
+                Some((star_p, star_s)) => {
+                    p_idx = star_p + 1;
+                    s_idx = star_s + 1;
+                    star = Some((star_p, star_s + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p_idx..].iter().all(|&c| c == '*')
+}
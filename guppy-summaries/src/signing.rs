@@ -0,0 +1,357 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{Summary, SummaryV2};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::{error, fmt, str::FromStr};
+
+/// A SHA-256 hash of the contents of a summary.
+///
+/// The hash is computed over the TOML serialization of the summary, so it doesn't depend on how
+/// the summary file happens to be formatted. Any change to the packages, features or metadata
+/// in a summary results in a different hash.
+///
+/// Content hashes are displayed and serialized as `sha256:` followed by the hash in lowercase
+/// hexadecimal.
+///
+/// Requires the `signing` feature to be enabled.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ContentHash([u8; 32]);
+
+impl ContentHash {
+    const PREFIX: &'static str = "sha256:";
+
+    /// Computes the content hash of the given serialized summary.
+    fn from_serialized(serialized: &str) -> Self {
+        Self(Sha256::digest(serialized.as_bytes()).into())
+    }
+
+    /// Returns the raw bytes of this hash.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Self::PREFIX)?;
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ContentHash {
+    type Err = ParseContentHashError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseContentHashError {
+            input: s.to_owned(),
+        };
+        let hex = s.strip_prefix(Self::PREFIX).ok_or_else(err)?;
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(err());
+        }
+        let mut bytes = [0; 32];
+        for (idx, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * idx..2 * idx + 2], 16).map_err(|_| err())?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl Serialize for ContentHash {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentHash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// An error that occurred while parsing a [`ContentHash`].
+///
+/// Requires the `signing` feature to be enabled.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseContentHashError {
+    input: String,
+}
+
+impl fmt::Display for ParseContentHashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid content hash '{}': expected 'sha256:' followed by 64 hex digits",
+            self.input
+        )
+    }
+}
+
+impl error::Error for ParseContentHashError {}
+
+/// A hook that signs the content hash of a summary.
+///
+/// This is implemented for closures of the form `Fn(&ContentHash) -> Result<String, E>`, so
+/// signing can be delegated to any tool, for example a key held by CI.
+///
+/// Requires the `signing` feature to be enabled.
+pub trait SummarySigner {
+    /// The error returned by this signer.
+    type Error;
+
+    /// Produces a signature for this content hash.
+    ///
+    /// The signature is stored as a string, so binary signatures should be encoded, for example
+    /// as base64.
+    fn sign(&self, content_hash: &ContentHash) -> Result<String, Self::Error>;
+}
+
+impl<F, E> SummarySigner for F
+where
+    F: Fn(&ContentHash) -> Result<String, E>,
+{
+    type Error = E;
+
+    fn sign(&self, content_hash: &ContentHash) -> Result<String, Self::Error> {
+        self(content_hash)
+    }
+}
+
+/// A hook that verifies a signature produced by a [`SummarySigner`].
+///
+/// This is implemented for closures of the form `Fn(&ContentHash, &str) -> Result<(), E>`.
+///
+/// Requires the `signing` feature to be enabled.
+pub trait SummaryVerifier {
+    /// The error returned by this verifier.
+    type Error;
+
+    /// Verifies that `signature` is a valid signature for this content hash.
+    fn verify(&self, content_hash: &ContentHash, signature: &str) -> Result<(), Self::Error>;
+}
+
+impl<F, E> SummaryVerifier for F
+where
+    F: Fn(&ContentHash, &str) -> Result<(), E>,
+{
+    type Error = E;
+
+    fn verify(&self, content_hash: &ContentHash, signature: &str) -> Result<(), Self::Error> {
+        self(content_hash, signature)
+    }
+}
+
+/// A signature for a summary, stored separately from it.
+///
+/// A detached signature is typically written out next to the summary it signs, so that it can be
+/// checked in CI: if the summary is edited by hand, its content hash no longer matches the one
+/// in the signature.
+///
+/// Created by [`Summary::sign`] or [`SummaryV2::sign`].
+///
+/// Requires the `signing` feature to be enabled.
+///
+/// # Examples
+///
+/// ```rust
+/// use guppy_summaries::{ContentHash, DetachedSignature, Summary};
+/// use std::convert::Infallible;
+///
+/// let summary = Summary::default();
+///
+/// // A real signer would use a private key here.
+/// let signer = |hash: &ContentHash| Ok::<_, Infallible>(format!("signed {}", hash));
+/// let signature = summary.sign(&signer).expect("signing succeeded");
+///
+/// // The signature can be written out and read back.
+/// let serialized = signature.to_string().expect("serialization succeeded");
+/// let signature = DetachedSignature::parse(&serialized).expect("deserialization succeeded");
+///
+/// let verifier = |hash: &ContentHash, signature: &str| {
+///     if signature == format!("signed {}", hash) {
+///         Ok(())
+///     } else {
+///         Err("bad signature")
+///     }
+/// };
+/// summary.verify(&signature, &verifier).expect("signature is valid");
+/// ```
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DetachedSignature {
+    /// The content hash of the summary when it was signed.
+    pub content_hash: ContentHash,
+
+    /// The signature produced by the signer.
+    pub signature: String,
+}
+
+impl DetachedSignature {
+    /// Deserializes a detached signature from the given TOML string.
+    pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    /// Serializes this signature to a TOML string.
+    pub fn to_string(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+}
+
+/// An error that occurred while signing or verifying a summary.
+///
+/// `E` is the error type of the signing or verification hook.
+///
+/// Requires the `signing` feature to be enabled.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SignatureError<E> {
+    /// An error occurred while serializing the summary to compute its content hash.
+    Serialize(toml::ser::Error),
+
+    /// The content hash of the summary doesn't match the one in the signature.
+    ///
+    /// This usually means that the summary was changed after it was signed.
+    HashMismatch {
+        /// The content hash recorded in the signature.
+        expected: ContentHash,
+
+        /// The content hash of the summary.
+        actual: ContentHash,
+    },
+
+    /// The signing or verification hook returned an error.
+    Hook(E),
+}
+
+impl<E: fmt::Display> fmt::Display for SignatureError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::Serialize(_) => write!(f, "failed to serialize summary"),
+            SignatureError::HashMismatch { expected, actual } => write!(
+                f,
+                "summary content hash {} doesn't match signed hash {}",
+                actual, expected
+            ),
+            SignatureError::Hook(err) => write!(f, "signature hook failed: {}", err),
+        }
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for SignatureError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SignatureError::Serialize(err) => Some(err),
+            SignatureError::HashMismatch { .. } => None,
+            SignatureError::Hook(err) => Some(err),
+        }
+    }
+}
+
+impl Summary {
+    /// Computes the content hash of this summary.
+    ///
+    /// Requires the `signing` feature to be enabled.
+    pub fn content_hash(&self) -> Result<ContentHash, toml::ser::Error> {
+        Ok(ContentHash::from_serialized(&self.to_string()?))
+    }
+
+    /// Signs the content hash of this summary with the given signer.
+    ///
+    /// Requires the `signing` feature to be enabled.
+    pub fn sign<S: SummarySigner>(
+        &self,
+        signer: &S,
+    ) -> Result<DetachedSignature, SignatureError<S::Error>> {
+        sign_impl(self.content_hash(), signer)
+    }
+
+    /// Verifies a detached signature for this summary with the given verifier.
+    ///
+    /// Returns an error if the summary has changed since it was signed, or if the verifier rejects
+    /// the signature.
+    ///
+    /// Requires the `signing` feature to be enabled.
+    pub fn verify<V: SummaryVerifier>(
+        &self,
+        signature: &DetachedSignature,
+        verifier: &V,
+    ) -> Result<(), SignatureError<V::Error>> {
+        verify_impl(self.content_hash(), signature, verifier)
+    }
+}
+
+impl SummaryV2 {
+    /// Computes the content hash of this summary.
+    ///
+    /// Requires the `signing` feature to be enabled.
+    pub fn content_hash(&self) -> Result<ContentHash, toml::ser::Error> {
+        Ok(ContentHash::from_serialized(&self.to_string()?))
+    }
+
+    /// Signs the content hash of this summary with the given signer.
+    ///
+    /// Requires the `signing` feature to be enabled.
+    pub fn sign<S: SummarySigner>(
+        &self,
+        signer: &S,
+    ) -> Result<DetachedSignature, SignatureError<S::Error>> {
+        sign_impl(self.content_hash(), signer)
+    }
+
+    /// Verifies a detached signature for this summary with the given verifier.
+    ///
+    /// Returns an error if the summary has changed since it was signed, or if the verifier rejects
+    /// the signature.
+    ///
+    /// Requires the `signing` feature to be enabled.
+    pub fn verify<V: SummaryVerifier>(
+        &self,
+        signature: &DetachedSignature,
+        verifier: &V,
+    ) -> Result<(), SignatureError<V::Error>> {
+        verify_impl(self.content_hash(), signature, verifier)
+    }
+}
+
+fn sign_impl<S: SummarySigner>(
+    content_hash: Result<ContentHash, toml::ser::Error>,
+    signer: &S,
+) -> Result<DetachedSignature, SignatureError<S::Error>> {
+    let content_hash = content_hash.map_err(SignatureError::Serialize)?;
+    let signature = signer.sign(&content_hash).map_err(SignatureError::Hook)?;
+    Ok(DetachedSignature {
+        content_hash,
+        signature,
+    })
+}
+
+fn verify_impl<V: SummaryVerifier>(
+    content_hash: Result<ContentHash, toml::ser::Error>,
+    signature: &DetachedSignature,
+    verifier: &V,
+) -> Result<(), SignatureError<V::Error>> {
+    let actual = content_hash.map_err(SignatureError::Serialize)?;
+    if actual != signature.content_hash {
+        return Err(SignatureError::HashMismatch {
+            expected: signature.content_hash,
+            actual,
+        });
+    }
+    // Verify against the hash that was actually signed.
+    verifier
+        .verify(&signature.content_hash, &signature.signature)
+        .map_err(SignatureError::Hook)
+}
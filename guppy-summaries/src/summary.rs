@@ -1,7 +1,7 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::diff::SummaryDiff;
+use crate::{diff::SummaryDiff, SummaryFormat};
 use camino::{Utf8Path, Utf8PathBuf};
 use semver::Version;
 use serde::{Deserialize, Serialize};
@@ -63,8 +63,16 @@ impl Summary {
     }
 
     /// Deserializes a summary from the given string, with optional custom metadata.
+    ///
+    /// Returns an error if the string is a summary in the v2 format. To read summaries in either
+    /// format, use [`AnySummary::parse`](crate::AnySummary::parse).
     pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
-        toml::from_str(s)
+        match SummaryFormat::detect(s)? {
+            SummaryFormat::V1 => toml::from_str(s),
+            SummaryFormat::V2 => Err(serde::de::Error::custom(
+                "summary is in the v2 format: use SummaryV2::parse or AnySummary::parse",
+            )),
+        }
     }
 
     /// Perform a diff of this summary against another.
@@ -0,0 +1,664 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{diff::CombinedDiff, PackageInfo, PackageMap, PackageStatus, Summary, SummaryId};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error, fmt,
+};
+use toml::{value::Table, Serializer};
+
+/// A type representing a package map as used in `SummaryV2` instances.
+pub type PackageBuildMap = BTreeMap<SummaryId, BTreeMap<BuildKey, BuildInfo>>;
+
+/// The version of the format a summary is stored in.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum SummaryFormat {
+    /// The original format, represented by [`Summary`].
+    ///
+    /// Packages are split into target and host packages, with a single set of features for each.
+    V1,
+
+    /// The second format, represented by [`SummaryV2`].
+    ///
+    /// Each package has its features and dependency kinds recorded separately for every platform
+    /// it was built on.
+    V2,
+}
+
+impl SummaryFormat {
+    /// Returns the value of the `summary-version` key for this format.
+    pub fn as_u32(self) -> u32 {
+        match self {
+            SummaryFormat::V1 => 1,
+            SummaryFormat::V2 => 2,
+        }
+    }
+
+    /// Detects the format of the given summary string, through its `summary-version` key.
+    ///
+    /// Summaries without a `summary-version` key are in the v1 format.
+    pub fn detect(s: &str) -> Result<Self, toml::de::Error> {
+        let probe: VersionProbe = toml::from_str(s)?;
+        Self::from_version(probe.summary_version)
+    }
+
+    /// Returns the format for the given value of the `summary-version` key, if any.
+    pub(crate) fn from_version<E: serde::de::Error>(version: Option<u32>) -> Result<Self, E> {
+        match version {
+            None | Some(1) => Ok(SummaryFormat::V1),
+            Some(2) => Ok(SummaryFormat::V2),
+            Some(other) => Err(E::custom(format!(
+                "unsupported summary-version {} (supported versions: 1, 2)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Reads just the `summary-version` key of a summary.
+#[derive(Deserialize)]
+pub(crate) struct VersionProbe {
+    #[serde(rename = "summary-version")]
+    pub(crate) summary_version: Option<u32>,
+}
+
+impl fmt::Display for SummaryFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.as_u32())
+    }
+}
+
+/// A build summary in either the v1 or the v2 format.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnySummary {
+    /// A summary in the v1 format.
+    V1(Summary),
+
+    /// A summary in the v2 format.
+    V2(SummaryV2),
+}
+
+impl AnySummary {
+    /// Deserializes a summary in either format from the given string.
+    ///
+    /// The format is determined through [`SummaryFormat::detect`].
+    pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        match SummaryFormat::detect(s)? {
+            SummaryFormat::V1 => Ok(AnySummary::V1(toml::from_str(s)?)),
+            SummaryFormat::V2 => Ok(AnySummary::V2(toml::from_str(s)?)),
+        }
+    }
+
+    /// Returns the format this summary is in.
+    pub fn format(&self) -> SummaryFormat {
+        match self {
+            AnySummary::V1(_) => SummaryFormat::V1,
+            AnySummary::V2(_) => SummaryFormat::V2,
+        }
+    }
+
+    /// Converts this summary to the v1 format.
+    ///
+    /// For v2 summaries, this flattens all platforms together through [`SummaryV2::to_v1`].
+    pub fn into_v1(self) -> Summary {
+        match self {
+            AnySummary::V1(summary) => summary,
+            AnySummary::V2(summary) => summary.to_v1(),
+        }
+    }
+
+    /// Serializes this summary to a TOML string, in its own format.
+    pub fn to_string(&self) -> Result<String, toml::ser::Error> {
+        match self {
+            AnySummary::V1(summary) => summary.to_string(),
+            AnySummary::V2(summary) => summary.to_string(),
+        }
+    }
+}
+
+/// An in-memory representation of a build summary in the v2 format.
+///
+/// Unlike [`Summary`], which records a single set of features for each package on the target and
+/// host platforms, a `SummaryV2` records features and dependency kinds separately for every
+/// platform a package was built on. This makes it possible to represent several builds, for example
+/// one per target platform, in a single summary without losing information.
+///
+/// v2 summaries are marked with `summary-version = 2`. To read a summary in either format, use
+/// [`AnySummary::parse`].
+///
+/// # Examples
+///
+/// ```rust
+/// use guppy_summaries::{BuildKey, DepKind, PlatformKind, SummaryId, SummarySource, SummaryV2};
+/// use semver::Version;
+///
+/// static SUMMARY: &str = r#"
+/// summary-version = 2
+///
+/// [[package]]
+/// name = "foo"
+/// version = "1.2.3"
+/// crates-io = true
+///
+/// [[package.build]]
+/// platform = "x86_64-unknown-linux-gnu"
+/// kind = "target"
+/// status = "direct"
+/// dep-kinds = ["normal"]
+/// features = ["default", "std"]
+///
+/// [[package.build]]
+/// platform = "x86_64-pc-windows-msvc"
+/// kind = "target"
+/// status = "direct"
+/// dep-kinds = ["normal"]
+/// features = ["default"]
+/// "#;
+///
+/// let summary = SummaryV2::parse(SUMMARY).expect("summary is valid");
+/// let summary_id = SummaryId::new("foo", Version::new(1, 2, 3), SummarySource::crates_io());
+/// let builds = &summary.packages[&summary_id];
+/// let linux = &builds[&BuildKey::new("x86_64-unknown-linux-gnu", PlatformKind::Target)];
+/// assert!(linux.features.contains("std"));
+/// assert!(linux.dep_kinds.contains(&DepKind::Normal));
+///
+/// // A v1 summary can be produced for an individual platform, or for all platforms together.
+/// let windows = summary.to_v1_for_platform("x86_64-pc-windows-msvc");
+/// assert!(!windows.target_packages[&summary_id].features.contains("std"));
+/// let flattened = summary.to_v1();
+/// assert!(flattened.target_packages[&summary_id].features.contains("std"));
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct SummaryV2 {
+    /// Always `2`. This must be serialized first, before any tables.
+    #[serde(with = "version_impl")]
+    summary_version: VersionTag,
+
+    /// Extra metadata associated with the summary.
+    ///
+    /// This may be used for storing extra information about the summary.
+    #[serde(default, skip_serializing_if = "Table::is_empty")]
+    pub metadata: Table,
+
+    /// The packages in this summary, along with what they were built with on each platform.
+    #[serde(
+        rename = "package",
+        with = "package_build_map_impl",
+        default = "PackageBuildMap::new",
+        skip_serializing_if = "PackageBuildMap::is_empty"
+    )]
+    pub packages: PackageBuildMap,
+}
+
+impl SummaryV2 {
+    /// Constructs a new summary with the provided metadata, and no packages.
+    pub fn with_metadata(metadata: &impl Serialize) -> Result<Self, toml::ser::Error> {
+        let toml_str = toml::to_string(metadata)?;
+        let metadata =
+            toml::from_str(&toml_str).expect("toml::to_string creates a valid TOML string");
+        Ok(Self {
+            metadata,
+            ..Self::default()
+        })
+    }
+
+    /// Constructs a new summary from a v1 summary, built with the given platform label.
+    ///
+    /// The metadata is copied over from the v1 summary. Since v1 summaries don't record dependency
+    /// kinds, the `dep_kinds` of every build will be empty.
+    pub fn from_v1(summary: &Summary, platform: &str) -> Self {
+        let mut summary_v2 = Self {
+            metadata: summary.metadata.clone(),
+            ..Self::default()
+        };
+        summary_v2.add_v1(summary, platform);
+        summary_v2
+    }
+
+    /// Adds the packages in a v1 summary to this one, using the given platform label.
+    ///
+    /// The metadata of the v1 summary is ignored. Any existing builds for this platform are
+    /// replaced.
+    pub fn add_v1(&mut self, summary: &Summary, platform: &str) -> &mut Self {
+        for (kind, package_map) in [
+            (PlatformKind::Target, &summary.target_packages),
+            (PlatformKind::Host, &summary.host_packages),
+        ] {
+            for (summary_id, info) in package_map {
+                self.insert(
+                    summary_id.clone(),
+                    BuildKey::new(platform, kind),
+                    BuildInfo::from_package_info(info.clone()),
+                );
+            }
+        }
+        self
+    }
+
+    /// Records that this package was built with this information for the given platform.
+    ///
+    /// Returns the previous information for this package and platform, if any.
+    pub fn insert(
+        &mut self,
+        summary_id: SummaryId,
+        key: BuildKey,
+        info: BuildInfo,
+    ) -> Option<BuildInfo> {
+        self.packages
+            .entry(summary_id)
+            .or_default()
+            .insert(key, info)
+    }
+
+    /// Merges the packages in another summary into this one.
+    ///
+    /// This can be used to combine summaries generated separately, for example by different CI
+    /// jobs, into a single summary. The platform labels of each build act as provenance tags, so
+    /// summaries to be merged should use distinct labels. Summaries in the v1 format can be
+    /// labeled through [`from_v1`](Self::from_v1) before being merged.
+    ///
+    /// The metadata of `self` is retained, and the metadata of `other` is ignored.
+    ///
+    /// Returns an error if both summaries have a build for the same package and platform, but with
+    /// different information. In that case, `self` is left unchanged.
+    pub fn merge(&mut self, other: &SummaryV2) -> Result<&mut Self, MergeError> {
+        for (summary_id, builds) in &other.packages {
+            let Some(existing) = self.packages.get(summary_id) else {
+                continue;
+            };
+            for (key, info) in builds {
+                if existing.get(key).is_some_and(|existing| existing != info) {
+                    return Err(MergeError {
+                        summary_id: Box::new(summary_id.clone()),
+                        key: key.clone(),
+                    });
+                }
+            }
+        }
+
+        for (summary_id, builds) in &other.packages {
+            self.packages
+                .entry(summary_id.clone())
+                .or_default()
+                .extend(builds.iter().map(|(key, info)| (key.clone(), info.clone())));
+        }
+        Ok(self)
+    }
+
+    /// Creates a new summary by merging all of the given summaries together.
+    ///
+    /// The metadata of the first summary is retained. For more, see [`merge`](Self::merge).
+    pub fn merge_all<'a>(
+        summaries: impl IntoIterator<Item = &'a SummaryV2>,
+    ) -> Result<Self, MergeError> {
+        let mut summaries = summaries.into_iter();
+        let mut merged = match summaries.next() {
+            Some(first) => first.clone(),
+            None => return Ok(Self::default()),
+        };
+        for summary in summaries {
+            merged.merge(summary)?;
+        }
+        Ok(merged)
+    }
+
+    /// Returns the platform labels this package was built for with the given kind, in sorted
+    /// order.
+    ///
+    /// For a merged summary, this indicates which of the original summaries the package came from.
+    pub fn provenance(&self, summary_id: &SummaryId, kind: PlatformKind) -> BTreeSet<&str> {
+        self.packages
+            .get(summary_id)
+            .into_iter()
+            .flat_map(|builds| builds.keys())
+            .filter(|key| key.kind == kind)
+            .map(|key| key.platform.as_str())
+            .collect()
+    }
+
+    /// Perform a diff of this summary against another, platform by platform.
+    ///
+    /// This doesn't diff the metadata, just the packages.
+    pub fn diff(&self, other: &SummaryV2) -> CombinedDiff {
+        CombinedDiff::new(self, other)
+    }
+
+    /// Returns the platform labels in this summary, in sorted order.
+    pub fn platforms(&self) -> BTreeSet<&str> {
+        self.packages
+            .values()
+            .flat_map(|builds| builds.keys().map(|key| key.platform.as_str()))
+            .collect()
+    }
+
+    /// Converts this summary to the v1 format, keeping only the builds for the given platform.
+    ///
+    /// Dependency kinds are dropped, since v1 summaries don't record them.
+    pub fn to_v1_for_platform(&self, platform: &str) -> Summary {
+        self.to_v1_impl(|key| key.platform == platform)
+    }
+
+    /// Converts this summary to the v1 format, merging all platforms together.
+    ///
+    /// For each package, the features and optional dependencies are the union across all platforms,
+    /// and the status is the most significant one (e.g. `initial` over `direct`). Dependency kinds
+    /// are dropped, since v1 summaries don't record them.
+    pub fn to_v1(&self) -> Summary {
+        self.to_v1_impl(|_| true)
+    }
+
+    fn to_v1_impl(&self, mut filter: impl FnMut(&BuildKey) -> bool) -> Summary {
+        let mut summary = Summary {
+            metadata: self.metadata.clone(),
+            ..Summary::default()
+        };
+        for (summary_id, builds) in &self.packages {
+            for (key, info) in builds {
+                if !filter(key) {
+                    continue;
+                }
+                let package_map: &mut PackageMap = match key.kind {
+                    PlatformKind::Target => &mut summary.target_packages,
+                    PlatformKind::Host => &mut summary.host_packages,
+                };
+                match package_map.get_mut(summary_id) {
+                    Some(existing) => {
+                        existing.status = existing.status.min(info.status);
+                        existing.features.extend(info.features.iter().cloned());
+                        existing
+                            .optional_deps
+                            .extend(info.optional_deps.iter().cloned());
+                    }
+                    None => {
+                        package_map.insert(summary_id.clone(), info.to_package_info());
+                    }
+                }
+            }
+        }
+        summary
+    }
+
+    /// Deserializes a v2 summary from the given string.
+    ///
+    /// Returns an error if the string isn't a v2 summary.
+    pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    /// Serializes this summary to a TOML string.
+    pub fn to_string(&self) -> Result<String, toml::ser::Error> {
+        let mut dst = String::new();
+        self.write_to_string(&mut dst)?;
+        Ok(dst)
+    }
+
+    /// Serializes this summary into the given TOML string, using pretty TOML syntax.
+    pub fn write_to_string(&self, dst: &mut String) -> Result<(), toml::ser::Error> {
+        let mut serializer = Serializer::pretty(dst);
+        serializer.pretty_array(false);
+        self.serialize(&mut serializer)
+    }
+}
+
+/// Identifies an individual build of a package within a [`SummaryV2`].
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BuildKey {
+    /// A label for the target platform of the build, typically a target triple.
+    ///
+    /// Host builds are labeled with the target platform of the build they're part of, since the
+    /// packages and features built on the host can depend on the target platform.
+    pub platform: String,
+
+    /// Whether the package was built for the target or for the host.
+    pub kind: PlatformKind,
+}
+
+impl BuildKey {
+    /// Creates a new `BuildKey`.
+    pub fn new(platform: impl Into<String>, kind: PlatformKind) -> Self {
+        Self {
+            platform: platform.into(),
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for BuildKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.platform, self.kind)
+    }
+}
+
+/// Whether a package was built for the target or the host platform.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlatformKind {
+    /// The package was built for the target platform.
+    Target,
+
+    /// The package was built for the host platform, e.g. as a build dependency or a proc macro.
+    Host,
+}
+
+impl fmt::Display for PlatformKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlatformKind::Target => write!(f, "target"),
+            PlatformKind::Host => write!(f, "host"),
+        }
+    }
+}
+
+/// A kind of dependency through which a package was included in a build.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DepKind {
+    /// The package was included as a dependency in a `[dependencies]` section.
+    Normal,
+
+    /// The package was included as a dependency in a `[build-dependencies]` section.
+    Build,
+
+    /// The package was included as a dependency in a `[dev-dependencies]` section.
+    Dev,
+}
+
+impl fmt::Display for DepKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DepKind::Normal => write!(f, "normal"),
+            DepKind::Build => write!(f, "build"),
+            DepKind::Dev => write!(f, "dev"),
+        }
+    }
+}
+
+/// Information about an individual build of a package in a [`SummaryV2`].
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct BuildInfo {
+    /// Where this package lies in the dependency graph for this build.
+    pub status: PackageStatus,
+
+    /// The kinds of dependencies this package was included through in this build.
+    ///
+    /// This is empty for packages in the initial set that aren't depended on by anything else, and
+    /// for builds converted from v1 summaries.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
+    pub dep_kinds: BTreeSet<DepKind>,
+
+    /// The features built for this package.
+    pub features: BTreeSet<String>,
+
+    /// The optional dependencies built for this package.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
+    pub optional_deps: BTreeSet<String>,
+}
+
+impl BuildInfo {
+    /// Creates a new `BuildInfo` from a v1 `PackageInfo`, with no dependency kinds.
+    pub fn from_package_info(info: PackageInfo) -> Self {
+        Self {
+            status: info.status,
+            dep_kinds: BTreeSet::new(),
+            features: info.features,
+            optional_deps: info.optional_deps,
+        }
+    }
+
+    /// Converts this to a v1 `PackageInfo`, dropping the dependency kinds.
+    pub fn to_package_info(&self) -> PackageInfo {
+        PackageInfo {
+            status: self.status,
+            features: self.features.clone(),
+            optional_deps: self.optional_deps.clone(),
+        }
+    }
+}
+
+/// An error that occurred while merging two v2 summaries.
+///
+/// Returned by [`SummaryV2::merge`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MergeError {
+    // Boxed to keep the error small.
+    summary_id: Box<SummaryId>,
+    key: BuildKey,
+}
+
+impl MergeError {
+    /// Returns the package that had conflicting builds.
+    pub fn summary_id(&self) -> &SummaryId {
+        &self.summary_id
+    }
+
+    /// Returns the platform and kind that the conflicting builds were for.
+    pub fn key(&self) -> &BuildKey {
+        &self.key
+    }
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "for package {}, conflicting builds for {}",
+            self.summary_id, self.key
+        )
+    }
+}
+
+impl error::Error for MergeError {}
+
+/// Marker for the `summary-version` key of a v2 summary.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct VersionTag;
+
+/// Serialization and deserialization for the `summary-version` key.
+mod version_impl {
+    use super::*;
+    use serde::{de::Error, Deserializer, Serializer};
+
+    pub fn serialize<S>(_: &VersionTag, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u32(SummaryFormat::V2.as_u32())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<VersionTag, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let version = u32::deserialize(deserializer)?;
+        if version != SummaryFormat::V2.as_u32() {
+            return Err(D::Error::custom(format!(
+                "expected summary-version 2, found {}",
+                version
+            )));
+        }
+        Ok(VersionTag)
+    }
+}
+
+/// Serialization and deserialization for `PackageBuildMap` instances.
+mod package_build_map_impl {
+    use super::*;
+    use serde::{de::Error, Deserializer, Serializer};
+
+    pub fn serialize<S>(package_map: &PackageBuildMap, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // Within a package, builds are written out in `BuildKey` order.
+        let package_list: Vec<_> = package_map
+            .iter()
+            .map(|(summary_id, builds)| PackageSerialize {
+                summary_id,
+                build: builds
+                    .iter()
+                    .map(|(key, info)| BuildSerialize { key, info })
+                    .collect(),
+            })
+            .collect();
+        package_list.serialize(serializer)
+    }
+
+    /// TOML representation of a package in a v2 summary, for serialization.
+    #[derive(Serialize)]
+    struct PackageSerialize<'a> {
+        #[serde(flatten)]
+        summary_id: &'a SummaryId,
+        build: Vec<BuildSerialize<'a>>,
+    }
+
+    #[derive(Serialize)]
+    struct BuildSerialize<'a> {
+        #[serde(flatten)]
+        key: &'a BuildKey,
+        #[serde(flatten)]
+        info: &'a BuildInfo,
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<PackageBuildMap, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let packages = Vec::<PackageDeserialize>::deserialize(deserializer)?;
+        let mut package_map: PackageBuildMap = BTreeMap::new();
+
+        for package in packages {
+            let builds = package_map.entry(package.summary_id.clone()).or_default();
+            for build in package.build {
+                if builds.insert(build.key.clone(), build.info).is_some() {
+                    return Err(D::Error::custom(format!(
+                        "for package {}, duplicate build for {}",
+                        package.summary_id, build.key
+                    )));
+                }
+            }
+        }
+        Ok(package_map)
+    }
+
+    /// TOML representation of a package in a v2 summary, for deserialization.
+    #[derive(Deserialize)]
+    struct PackageDeserialize {
+        #[serde(flatten)]
+        summary_id: SummaryId,
+        #[serde(default)]
+        build: Vec<BuildDeserialize>,
+    }
+
+    #[derive(Deserialize)]
+    struct BuildDeserialize {
+        #[serde(flatten)]
+        key: BuildKey,
+        #[serde(flatten)]
+        info: BuildInfo,
+    }
+}
@@ -2,14 +2,14 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
-    diff::SummaryDiffStatus, PackageInfo, PackageMap, PackageStatus, Summary, SummaryId,
-    SummarySource,
+    diff::{SummaryChange, SummaryDiffStatus},
+    PackageInfo, PackageMap, PackageStatus, Summary, SummaryId, SummarySource,
 };
 use pretty_assertions::assert_eq;
 use semver::Version;
 use std::collections::BTreeSet;
 
-static SERIALIZED_SUMMARY: &str = r#"# This is a test @generated summary.
+pub(super) static SERIALIZED_SUMMARY: &str = r#"# This is a test @generated summary.
 
 [[target-package]]
 name = 'foo'
@@ -51,7 +51,7 @@ features = []
 optional-deps = ['dep4']
 "#;
 
-static SUMMARY2: &str = r#"# This is a test @generated summary.
+pub(super) static SUMMARY2: &str = r#"# This is a test @generated summary.
 
 [[target-package]]
 name = 'foo'
@@ -603,3 +603,166 @@ fn make_summary(list: Vec<(SummaryId, PackageStatus, Vec<&str>, Vec<&str>)>) ->
         })
         .collect()
 }
+
+#[test]
+fn test_markdown_report() {
+    let summary = Summary::parse(SERIALIZED_SUMMARY).expect("from_str succeeded");
+    let summary2 = Summary::parse(SUMMARY2).expect("from_str succeeded");
+    let diff = summary.diff(&summary2);
+
+    static EXPECTED_MARKDOWN: &str = indoc::indoc!(
+        r#"### Target packages
+
+        <details>
+        <summary>Added (2)</summary>
+
+        - `dep 0.4.3` (direct third-party, crates.io)
+          - features: std
+        - `dep 0.5.0` (transitive third-party, crates.io)
+          - features: std
+
+        </details>
+
+        <details>
+        <summary>Modified (1)</summary>
+
+        - `foo 1.2.3` (initial, path 'foo')
+          - added features: feature2
+          - (unchanged features: default, feature1)
+          - added optional dependencies: dep3
+          - removed optional dependencies: dep2
+          - (unchanged optional dependencies: dep1)
+
+        </details>
+
+        <details>
+        <summary>Removed (1)</summary>
+
+        - `dep 0.4.2` (direct third-party, crates.io)
+          - (old features: std)
+
+        </details>
+
+        ### Host packages
+
+        <details>
+        <summary>Added (1)</summary>
+
+        - `local-dep 2.0.0` (transitive third-party, path '../local-dep-2')
+          - features: \[none\]
+
+        </details>
+
+        <details>
+        <summary>Modified (2)</summary>
+
+        - `bar 0.2.0` (initial, path 'dir/bar')
+          - version upgraded from 0.1.0
+          - status changed from workspace
+          - (unchanged features: default, feature2)
+          - (unchanged optional dependencies: \[none\])
+        - `local-dep 1.1.2` (transitive third-party, path '../local-dep')
+          - added features: dep-feature
+          - (unchanged features: \[none\])
+          - removed optional dependencies: dep4
+          - (unchanged optional dependencies: \[none\])
+
+        </details>
+
+        "#
+    );
+    assert_eq!(diff.markdown_report().to_string(), EXPECTED_MARKDOWN);
+
+    let unchanged = summary.diff(&summary);
+    assert_eq!(unchanged.markdown_report().to_string(), "No changes.\n");
+}
+
+#[test]
+fn test_html_report() {
+    let summary = Summary::parse(SERIALIZED_SUMMARY).expect("from_str succeeded");
+    let summary2 = Summary::parse(SUMMARY2).expect("from_str succeeded");
+    let diff = summary.diff(&summary2);
+
+    let mut report = diff.html_report();
+    report.set_title("Changes in <main>");
+    let html = report.to_string();
+
+    assert!(html.starts_with("<!DOCTYPE html>\n<html>\n"));
+    assert!(html.ends_with("</body>\n</html>\n"));
+    assert!(html.contains("<title>Changes in &lt;main&gt;</title>"));
+    for expected in [
+        "<h1>Changes in &lt;main&gt;</h1>\n",
+        "<h2>Target packages</h2>\n<details open>\n<summary>Added (2)</summary>\n",
+        "<h2>Host packages</h2>\n<details open>\n<summary>Added (1)</summary>\n",
+        "<summary>Modified (2)</summary>\n",
+        "<li><code>foo 1.2.3</code> (initial, path &#39;foo&#39;)\n<ul>\n\
+         <li>added features: feature2</li>\n",
+        "<li><code>dep 0.4.2</code> (direct third-party, crates.io)\n<ul>\n\
+         <li>(old features: std)</li>\n</ul>\n</li>\n",
+    ] {
+        assert!(html.contains(expected), "html contains {:?}", expected);
+    }
+
+    let unchanged = summary.diff(&summary);
+    let html = unchanged.html_report().to_string();
+    assert!(html.contains("<h1>Summary diff</h1>\n<p>No changes.</p>\n</body>"));
+}
+
+#[test]
+fn test_for_each_change() {
+    let summary = Summary::parse(SERIALIZED_SUMMARY).expect("from_str succeeded");
+    let summary2 = Summary::parse(SUMMARY2).expect("from_str succeeded");
+    let diff = summary.diff(&summary2);
+
+    let mut changes = vec![];
+    diff.for_each_change(|entry| {
+        let package = format!(
+            "{} {} {}",
+            entry.kind, entry.summary_id.name, entry.summary_id.version
+        );
+        let change = match entry.change {
+            SummaryChange::PackageAdded { info } => format!("added ({})", info.status),
+            SummaryChange::PackageRemoved { old_info } => format!("removed ({})", old_info.status),
+            SummaryChange::VersionChanged {
+                old_version,
+                new_version,
+            } => format!("version {} -> {}", old_version, new_version),
+            SummaryChange::SourceChanged {
+                old_source,
+                new_source,
+            } => format!("source {} -> {}", old_source, new_source),
+            SummaryChange::StatusChanged {
+                old_status,
+                new_status,
+            } => format!("status {} -> {}", old_status, new_status),
+            SummaryChange::FeatureAdded { feature } => format!("+feature {}", feature),
+            SummaryChange::FeatureRemoved { feature } => format!("-feature {}", feature),
+            SummaryChange::OptionalDepAdded { dep } => format!("+optional dep {}", dep),
+            SummaryChange::OptionalDepRemoved { dep } => format!("-optional dep {}", dep),
+        };
+        changes.push(format!("{}: {}", package, change));
+    });
+
+    assert_eq!(
+        changes,
+        [
+            "target dep 0.4.3: added (direct third-party)",
+            "target dep 0.5.0: added (transitive third-party)",
+            "target foo 1.2.3: +feature feature2",
+            "target foo 1.2.3: +optional dep dep3",
+            "target foo 1.2.3: -optional dep dep2",
+            "target dep 0.4.2: removed (direct third-party)",
+            "host local-dep 2.0.0: added (transitive third-party)",
+            "host bar 0.2.0: version 0.1.0 -> 0.2.0",
+            "host bar 0.2.0: status workspace -> initial",
+            "host local-dep 1.1.2: +feature dep-feature",
+            "host local-dep 1.1.2: -optional dep dep4",
+        ],
+    );
+
+    let mut unchanged_count = 0;
+    summary
+        .diff(&summary)
+        .for_each_change(|_| unchanged_count += 1);
+    assert_eq!(unchanged_count, 0, "unchanged diff has no changes");
+}
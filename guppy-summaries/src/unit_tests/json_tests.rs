@@ -0,0 +1,102 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::{
+    basic_tests::{SERIALIZED_SUMMARY, SUMMARY2},
+    v2_tests::SERIALIZED_SUMMARY_V2,
+};
+use crate::{AnySummary, Summary, SummaryFormat, SummaryV2};
+use pretty_assertions::assert_eq;
+use serde_json::Value;
+
+#[test]
+fn json_roundtrip() {
+    let summary = Summary::parse(SERIALIZED_SUMMARY).expect("from_str succeeded");
+    let json = summary.to_json_string().expect("to_json_string succeeded");
+    assert_eq!(
+        SummaryFormat::detect_json(&json).expect("detect succeeded"),
+        SummaryFormat::V1,
+    );
+    assert_eq!(
+        Summary::parse_json(&json).expect("parse_json succeeded"),
+        summary,
+        "v1 summary round-trips through JSON"
+    );
+
+    let summary_v2 = SummaryV2::parse(SERIALIZED_SUMMARY_V2).expect("v2 summary is valid");
+    let json_v2 = summary_v2
+        .to_json_string()
+        .expect("to_json_string succeeded");
+    assert_eq!(
+        SummaryFormat::detect_json(&json_v2).expect("detect succeeded"),
+        SummaryFormat::V2,
+    );
+    assert_eq!(
+        SummaryV2::parse_json(&json_v2).expect("parse_json succeeded"),
+        summary_v2,
+        "v2 summary round-trips through JSON"
+    );
+
+    // AnySummary picks the right format, and Summary rejects v2 summaries.
+    assert_eq!(
+        AnySummary::parse_json(&json).expect("v1 parses").format(),
+        SummaryFormat::V1,
+    );
+    assert_eq!(
+        AnySummary::parse_json(&json_v2)
+            .expect("v2 parses")
+            .format(),
+        SummaryFormat::V2,
+    );
+    let err = Summary::parse_json(&json_v2).expect_err("v2 summary rejected");
+    assert!(
+        err.to_string().contains("summary is in the v2 format"),
+        "actual error: {err}"
+    );
+    SummaryFormat::detect_json(r#"{"summary-version": 3}"#).expect_err("unsupported version");
+}
+
+#[test]
+fn json_layout() {
+    // The JSON layout uses the same field names as the TOML layout.
+    let summary = Summary::parse(SERIALIZED_SUMMARY).expect("from_str succeeded");
+    let value: Value =
+        serde_json::from_str(&summary.to_json_string().expect("to_json_string succeeded"))
+            .expect("valid JSON");
+    let first = &value["target-package"][0];
+    assert_eq!(first["name"], "foo");
+    assert_eq!(first["version"], "1.2.3");
+    assert_eq!(first["workspace-path"], "foo");
+    assert_eq!(first["status"], "initial");
+    assert_eq!(first["optional-deps"], serde_json::json!(["dep1", "dep2"]));
+
+    let summary2 = Summary::parse(SUMMARY2).expect("from_str succeeded");
+    let diff = summary.diff(&summary2);
+    let value: Value =
+        serde_json::from_str(&diff.to_json_string().expect("diff serializes")).expect("valid JSON");
+    assert_eq!(value, serde_json::to_value(&diff).expect("diff serializes"));
+    assert!(value["target-packages"]["changed"].is_array());
+
+    let combined = SummaryV2::parse(SERIALIZED_SUMMARY_V2).expect("v2 summary is valid");
+    let combined_diff = SummaryV2::default().diff(&combined);
+    let value: Value = serde_json::from_str(
+        &combined_diff
+            .to_json_string()
+            .expect("combined diff serializes"),
+    )
+    .expect("valid JSON");
+    assert_eq!(
+        value["platforms"],
+        serde_json::json!(["x86_64-pc-windows-msvc", "x86_64-unknown-linux-gnu"]),
+    );
+    let changes = value["changes"].as_array().expect("changes is an array");
+    assert_eq!(changes.len(), combined_diff.changes().len());
+    for change in changes {
+        assert_eq!(change["change"], "added");
+        assert!(
+            change["kind"] == "target" || change["kind"] == "host",
+            "kind is serialized: {change}"
+        );
+        assert!(change["platforms"].is_array());
+    }
+}
@@ -4,3 +4,9 @@
 //! Unit tests for guppy-summaries.
 
 mod basic_tests;
+#[cfg(feature = "json")]
+mod json_tests;
+mod rules_tests;
+#[cfg(feature = "signing")]
+mod signing_tests;
+mod v2_tests;
@@ -0,0 +1,210 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    diff::{ChangeKind, DiffRule, DiffRules, Severity},
+    rules::glob_match,
+    PackageStatus, PlatformKind, Summary,
+};
+use pretty_assertions::assert_eq;
+
+static OLD: &str = r#"
+[[target-package]]
+name = "internal-util"
+version = "0.1.0"
+workspace-path = "util"
+status = "workspace"
+features = []
+
+[[target-package]]
+name = "internal-core"
+version = "0.1.0"
+workspace-path = "core"
+status = "initial"
+features = ["std"]
+
+[[target-package]]
+name = "serde"
+version = "1.0.200"
+crates-io = true
+status = "direct"
+features = ["std"]
+
+[[host-package]]
+name = "cc"
+version = "1.0.90"
+crates-io = true
+status = "transitive"
+features = []
+"#;
+
+static NEW: &str = r#"
+[[target-package]]
+name = "internal-util"
+version = "0.2.0"
+workspace-path = "util"
+status = "workspace"
+features = []
+
+[[target-package]]
+name = "internal-core"
+version = "0.2.0"
+workspace-path = "core"
+status = "initial"
+features = ["std", "unstable-simd"]
+
+[[target-package]]
+name = "serde"
+version = "1.0.200"
+crates-io = true
+status = "direct"
+features = ["std"]
+
+[[target-package]]
+name = "once_cell"
+version = "1.19.0"
+crates-io = true
+status = "transitive"
+features = ["std"]
+"#;
+
+static RULES: &str = r#"
+default-severity = "warn"
+
+[[rule]]
+name = "internal-*"
+change = ["version-changed"]
+severity = "ignore"
+
+[[rule]]
+feature = "unstable-*"
+severity = "error"
+
+[[rule]]
+status = ["direct", "transitive"]
+change = ["added"]
+severity = "error"
+
+[[rule]]
+kind = "host"
+change = ["removed"]
+severity = "info"
+"#;
+
+#[test]
+fn classify() {
+    let old = Summary::parse(OLD).expect("old summary is valid");
+    let new = Summary::parse(NEW).expect("new summary is valid");
+    let diff = old.diff(&new);
+
+    let rules = DiffRules::parse(RULES).expect("rules are valid");
+    let classified: Vec<_> = diff
+        .classify(&rules)
+        .into_iter()
+        .map(|change| {
+            (
+                change.kind,
+                change.summary_id.name.as_str(),
+                change.severity,
+                change.rule,
+            )
+        })
+        .collect();
+    assert_eq!(
+        classified,
+        [
+            (PlatformKind::Target, "once_cell", Severity::Error, Some(2)),
+            (
+                PlatformKind::Target,
+                "internal-core",
+                Severity::Error,
+                Some(1)
+            ),
+            (
+                PlatformKind::Target,
+                "internal-util",
+                Severity::Ignore,
+                Some(0)
+            ),
+            (PlatformKind::Host, "cc", Severity::Info, Some(3)),
+        ],
+    );
+    assert_eq!(diff.max_severity(&rules), Severity::Error);
+
+    // Without the rule for unstable features, the feature change to internal-core falls through
+    // to the default severity.
+    let mut rules = DiffRules::new();
+    rules
+        .set_default_severity(Severity::Warn)
+        .add_rule(
+            DiffRule::new(Severity::Ignore)
+                .set_name("internal-*")
+                .set_changes([ChangeKind::VersionChanged])
+                .clone(),
+        )
+        .add_rule(
+            DiffRule::new(Severity::Ignore)
+                .set_statuses([PackageStatus::Direct, PackageStatus::Transitive])
+                .clone(),
+        );
+    let severities: Vec<_> = diff
+        .classify(&rules)
+        .into_iter()
+        .map(|change| (change.summary_id.name.as_str(), change.severity))
+        .collect();
+    assert_eq!(
+        severities,
+        [
+            ("once_cell", Severity::Ignore),
+            ("internal-core", Severity::Warn),
+            ("internal-util", Severity::Ignore),
+            ("cc", Severity::Ignore),
+        ],
+    );
+    assert_eq!(diff.max_severity(&rules), Severity::Warn);
+
+    // An unchanged diff has no severity.
+    assert_eq!(old.diff(&old).max_severity(&rules), Severity::Ignore);
+}
+
+#[test]
+fn rules_roundtrip() {
+    let rules = DiffRules::parse(RULES).expect("rules are valid");
+    assert_eq!(rules.rules().len(), 4);
+    let serialized = toml::to_string(&rules).expect("rules serialize");
+    assert_eq!(
+        DiffRules::parse(&serialized).expect("serialized rules are valid"),
+        rules,
+    );
+
+    assert_eq!(
+        DiffRules::parse("").expect("empty rules are valid"),
+        DiffRules::new(),
+    );
+    DiffRules::parse("[[rule]]\nseverity = \"fatal\"\n").expect_err("unknown severity");
+}
+
+#[test]
+fn glob() {
+    for (pattern, s, expected) in [
+        ("serde", "serde", true),
+        ("serde", "serde_json", false),
+        ("serde*", "serde_json", true),
+        ("serde*", "serde", true),
+        ("*-sys", "openssl-sys", true),
+        ("*-sys", "openssl", false),
+        ("tokio-?", "tokio-a", true),
+        ("tokio-?", "tokio-ab", false),
+        ("*a*b*", "xaybz", true),
+        ("*a*b*", "xbya", false),
+        ("*", "", true),
+        ("", "", true),
+        ("", "a", false),
+    ] {
+        assert_eq!(
+            glob_match(pattern, s),
+            expected,
+            "pattern {pattern:?} matching {s:?}",
+        );
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::{basic_tests::SERIALIZED_SUMMARY, v2_tests::SERIALIZED_SUMMARY_V2};
+use crate::{ContentHash, DetachedSignature, PackageStatus, SignatureError, Summary, SummaryV2};
+use pretty_assertions::assert_eq;
+
+fn signer(hash: &ContentHash) -> Result<String, String> {
+    Ok(format!("key1:{}", hash))
+}
+
+fn verifier(hash: &ContentHash, signature: &str) -> Result<(), String> {
+    if signature == format!("key1:{}", hash) {
+        Ok(())
+    } else {
+        Err(format!("bad signature {}", signature))
+    }
+}
+
+#[test]
+fn content_hash() {
+    let summary = Summary::parse(SERIALIZED_SUMMARY).expect("from_str succeeded");
+    let hash = summary.content_hash().expect("hash computed");
+
+    // The hash doesn't depend on formatting or comments in the summary file.
+    let reformatted = summary.to_string().expect("to_string succeeded");
+    assert_ne!(reformatted, SERIALIZED_SUMMARY, "summary is reformatted");
+    assert_eq!(
+        Summary::parse(&reformatted)
+            .expect("from_str succeeded")
+            .content_hash()
+            .expect("hash computed"),
+        hash,
+    );
+
+    // ... but does depend on the contents.
+    let mut changed = summary.clone();
+    changed
+        .target_packages
+        .values_mut()
+        .next()
+        .expect("at least one package")
+        .status = PackageStatus::Transitive;
+    assert_ne!(changed.content_hash().expect("hash computed"), hash);
+
+    // Content hashes round-trip through strings.
+    let hash_str = hash.to_string();
+    assert!(hash_str.starts_with("sha256:"), "hash has prefix");
+    assert_eq!(hash_str.len(), "sha256:".len() + 64);
+    assert_eq!(hash_str.parse::<ContentHash>(), Ok(hash));
+    let non_hex = format!("{}g", &hash_str[..hash_str.len() - 1]);
+    for invalid in [
+        "",
+        "sha256:",
+        "sha256:abcd",
+        &hash_str["sha256:".len()..],
+        &non_hex,
+    ] {
+        invalid
+            .parse::<ContentHash>()
+            .expect_err("invalid content hash");
+    }
+
+    // An empty summary has the SHA-256 of an empty string.
+    assert_eq!(
+        Summary::default()
+            .content_hash()
+            .expect("hash computed")
+            .to_string(),
+        "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+    );
+}
+
+#[test]
+fn sign_and_verify() {
+    let summary = Summary::parse(SERIALIZED_SUMMARY).expect("from_str succeeded");
+    let signature = summary.sign(&signer).expect("signing succeeded");
+    assert_eq!(
+        signature.content_hash,
+        summary.content_hash().expect("hash computed")
+    );
+
+    let serialized = signature.to_string().expect("serialization succeeded");
+    let signature = DetachedSignature::parse(&serialized).expect("deserialization succeeded");
+    summary
+        .verify(&signature, &verifier)
+        .expect("signature is valid");
+
+    // A hand-edited summary doesn't match the signature.
+    let mut edited = summary.clone();
+    edited.target_packages.clear();
+    match edited.verify(&signature, &verifier) {
+        Err(SignatureError::HashMismatch { expected, actual }) => {
+            assert_eq!(expected, signature.content_hash);
+            assert_eq!(actual, edited.content_hash().expect("hash computed"));
+        }
+        other => panic!("expected hash mismatch, found {:?}", other),
+    }
+
+    // A forged signature is rejected by the verifier.
+    let forged = DetachedSignature {
+        content_hash: signature.content_hash,
+        signature: "key2:forged".to_owned(),
+    };
+    match summary.verify(&forged, &verifier) {
+        Err(SignatureError::Hook(err)) => assert_eq!(err, "bad signature key2:forged"),
+        other => panic!("expected verifier error, found {:?}", other),
+    }
+
+    // Signer errors are passed through.
+    let failing_signer = |_: &ContentHash| Err::<String, _>("no key available");
+    match summary.sign(&failing_signer) {
+        Err(SignatureError::Hook(err)) => assert_eq!(err, "no key available"),
+        other => panic!("expected signer error, found {:?}", other),
+    }
+}
+
+#[test]
+fn sign_v2() {
+    let summary = SummaryV2::parse(SERIALIZED_SUMMARY_V2).expect("v2 summary is valid");
+    let signature = summary.sign(&signer).expect("signing succeeded");
+    summary
+        .verify(&signature, &verifier)
+        .expect("signature is valid");
+    assert_ne!(
+        signature.content_hash,
+        summary.to_v1().content_hash().expect("hash computed"),
+        "v1 and v2 summaries have different hashes"
+    );
+}
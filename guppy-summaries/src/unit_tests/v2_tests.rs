@@ -0,0 +1,406 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    diff::SummaryDiffTag, AnySummary, BuildInfo, BuildKey, DepKind, PackageStatus, PlatformKind,
+    Summary, SummaryFormat, SummaryId, SummarySource, SummaryV2,
+};
+use pretty_assertions::assert_eq;
+use semver::Version;
+
+pub(super) static SERIALIZED_SUMMARY_V2: &str = r#"summary-version = 2
+
+[metadata]
+resolver = '2'
+
+[[package]]
+name = 'foo'
+version = '1.2.3'
+workspace-path = 'foo'
+
+[[package.build]]
+platform = 'x86_64-pc-windows-msvc'
+kind = 'target'
+status = 'initial'
+features = ['default']
+
+[[package.build]]
+platform = 'x86_64-unknown-linux-gnu'
+kind = 'target'
+status = 'initial'
+features = ['default', 'unix-only']
+optional-deps = ['libc']
+
+[[package]]
+name = 'libc'
+version = '0.2.155'
+crates-io = true
+
+[[package.build]]
+platform = 'x86_64-unknown-linux-gnu'
+kind = 'target'
+status = 'direct'
+dep-kinds = ['normal']
+features = ['std']
+
+[[package.build]]
+platform = 'x86_64-unknown-linux-gnu'
+kind = 'host'
+status = 'transitive'
+dep-kinds = ['normal', 'build']
+features = []
+"#;
+
+fn foo_id() -> SummaryId {
+    SummaryId::new(
+        "foo",
+        Version::new(1, 2, 3),
+        SummarySource::workspace("foo"),
+    )
+}
+
+fn libc_id() -> SummaryId {
+    SummaryId::new("libc", Version::new(0, 2, 155), SummarySource::crates_io())
+}
+
+fn build_info(
+    status: PackageStatus,
+    dep_kinds: &[DepKind],
+    features: &[&str],
+    optional_deps: &[&str],
+) -> BuildInfo {
+    BuildInfo {
+        status,
+        dep_kinds: dep_kinds.iter().copied().collect(),
+        features: features.iter().map(|s| s.to_string()).collect(),
+        optional_deps: optional_deps.iter().map(|s| s.to_string()).collect(),
+    }
+}
+
+fn make_summary_v2() -> SummaryV2 {
+    let mut summary =
+        SummaryV2::with_metadata(&toml::toml! { resolver = "2" }).expect("metadata is valid");
+    let linux = "x86_64-unknown-linux-gnu";
+    let windows = "x86_64-pc-windows-msvc";
+    summary.insert(
+        foo_id(),
+        BuildKey::new(linux, PlatformKind::Target),
+        build_info(
+            PackageStatus::Initial,
+            &[],
+            &["default", "unix-only"],
+            &["libc"],
+        ),
+    );
+    summary.insert(
+        foo_id(),
+        BuildKey::new(windows, PlatformKind::Target),
+        build_info(PackageStatus::Initial, &[], &["default"], &[]),
+    );
+    summary.insert(
+        libc_id(),
+        BuildKey::new(linux, PlatformKind::Target),
+        build_info(PackageStatus::Direct, &[DepKind::Normal], &["std"], &[]),
+    );
+    summary.insert(
+        libc_id(),
+        BuildKey::new(linux, PlatformKind::Host),
+        build_info(
+            PackageStatus::Transitive,
+            &[DepKind::Normal, DepKind::Build],
+            &[],
+            &[],
+        ),
+    );
+    summary
+}
+
+#[test]
+fn v2_roundtrip() {
+    let summary = make_summary_v2();
+    let serialized = summary.to_string().expect("serialization succeeded");
+    assert_eq!(serialized, SERIALIZED_SUMMARY_V2);
+
+    let deserialized = SummaryV2::parse(&serialized).expect("deserialization succeeded");
+    assert_eq!(summary, deserialized);
+
+    assert_eq!(
+        summary.platforms().into_iter().collect::<Vec<_>>(),
+        ["x86_64-pc-windows-msvc", "x86_64-unknown-linux-gnu"],
+    );
+}
+
+#[test]
+fn v2_empty_roundtrip() {
+    let summary = SummaryV2::default();
+    let serialized = summary.to_string().expect("serialization succeeded");
+    assert_eq!(serialized, "summary-version = 2\n");
+    assert_eq!(
+        SummaryV2::parse(&serialized).expect("deserialization succeeded"),
+        summary
+    );
+}
+
+#[test]
+fn v2_to_v1() {
+    let summary = make_summary_v2();
+
+    let windows = summary.to_v1_for_platform("x86_64-pc-windows-msvc");
+    assert_eq!(windows.metadata, summary.metadata);
+    assert_eq!(
+        windows.target_packages.keys().collect::<Vec<_>>(),
+        [&foo_id()]
+    );
+    assert!(windows.host_packages.is_empty());
+
+    let flattened = summary.to_v1();
+    let foo = &flattened.target_packages[&foo_id()];
+    assert_eq!(
+        foo.features.iter().collect::<Vec<_>>(),
+        ["default", "unix-only"]
+    );
+    assert_eq!(foo.optional_deps.iter().collect::<Vec<_>>(), ["libc"]);
+    assert_eq!(
+        flattened.host_packages[&libc_id()].status,
+        PackageStatus::Transitive
+    );
+
+    // Converting the linux summary back to v2 loses the dependency kinds, but nothing else.
+    let linux = summary.to_v1_for_platform("x86_64-unknown-linux-gnu");
+    let roundtrip = SummaryV2::from_v1(&linux, "x86_64-unknown-linux-gnu");
+    let libc_builds = &roundtrip.packages[&libc_id()];
+    let libc_host = &libc_builds[&BuildKey::new("x86_64-unknown-linux-gnu", PlatformKind::Host)];
+    assert!(libc_host.dep_kinds.is_empty());
+    assert_eq!(libc_host.to_package_info(), linux.host_packages[&libc_id()]);
+    assert_eq!(roundtrip.to_v1(), linux);
+}
+
+#[test]
+fn any_summary_parse() {
+    let v2 = AnySummary::parse(SERIALIZED_SUMMARY_V2).expect("v2 summary parsed");
+    assert_eq!(v2.format(), SummaryFormat::V2);
+    assert_eq!(
+        v2.to_string().expect("serialization succeeded"),
+        SERIALIZED_SUMMARY_V2
+    );
+
+    let v1_str = v2.into_v1().to_string().expect("serialization succeeded");
+    let v1 = AnySummary::parse(&v1_str).expect("v1 summary parsed");
+    assert_eq!(v1.format(), SummaryFormat::V1);
+    assert_eq!(v1.to_string().expect("serialization succeeded"), v1_str);
+
+    // An explicit summary-version = 1 is accepted as well.
+    let explicit_v1 = format!("summary-version = 1\n{}", v1_str);
+    assert_eq!(
+        SummaryFormat::detect(&explicit_v1).expect("detected"),
+        SummaryFormat::V1
+    );
+    Summary::parse(&explicit_v1).expect("v1 summary parsed");
+}
+
+#[test]
+fn mismatched_versions() {
+    let err = Summary::parse(SERIALIZED_SUMMARY_V2).expect_err("v1 parser rejects v2");
+    assert!(
+        err.to_string().contains("summary is in the v2 format"),
+        "error: {}",
+        err
+    );
+
+    let err = SummaryV2::parse("[metadata]\n").expect_err("v2 parser rejects v1");
+    assert!(
+        err.to_string().contains("summary-version"),
+        "error: {}",
+        err
+    );
+
+    let err = AnySummary::parse("summary-version = 3\n").expect_err("unknown version");
+    assert!(
+        err.to_string().contains("unsupported summary-version 3"),
+        "error: {}",
+        err
+    );
+
+    let duplicate = r#"summary-version = 2
+
+[[package]]
+name = 'libc'
+version = '0.2.155'
+crates-io = true
+
+[[package.build]]
+platform = 'x86_64-unknown-linux-gnu'
+kind = 'target'
+status = 'direct'
+features = []
+
+[[package.build]]
+platform = 'x86_64-unknown-linux-gnu'
+kind = 'target'
+status = 'transitive'
+features = []
+"#;
+    let err = SummaryV2::parse(duplicate).expect_err("duplicate builds");
+    assert!(
+        err.to_string().contains("duplicate build"),
+        "error: {}",
+        err
+    );
+}
+
+fn v1_summary(packages: &[(&str, u64, PackageStatus, &[&str])]) -> Summary {
+    let mut summary = Summary::default();
+    for &(name, minor, status, features) in packages {
+        summary.target_packages.insert(
+            SummaryId::new(name, Version::new(1, minor, 0), SummarySource::crates_io()),
+            build_info(status, &[], features, &[]).to_package_info(),
+        );
+    }
+    summary
+}
+
+#[test]
+fn merge() {
+    let linux = SummaryV2::from_v1(
+        &v1_summary(&[
+            ("common", 0, PackageStatus::Direct, &["std"]),
+            ("libc", 2, PackageStatus::Direct, &[]),
+        ]),
+        "linux",
+    );
+    let windows = SummaryV2::from_v1(
+        &v1_summary(&[
+            ("common", 0, PackageStatus::Direct, &["std"]),
+            ("winapi", 3, PackageStatus::Transitive, &[]),
+        ]),
+        "windows",
+    );
+
+    let merged = SummaryV2::merge_all([&linux, &windows]).expect("merge succeeded");
+    assert_eq!(
+        merged.platforms().into_iter().collect::<Vec<_>>(),
+        ["linux", "windows"]
+    );
+    let common_id = SummaryId::new("common", Version::new(1, 0, 0), SummarySource::crates_io());
+    assert_eq!(
+        merged
+            .provenance(&common_id, PlatformKind::Target)
+            .into_iter()
+            .collect::<Vec<_>>(),
+        ["linux", "windows"]
+    );
+    assert!(merged.provenance(&common_id, PlatformKind::Host).is_empty());
+    assert_eq!(merged.to_v1_for_platform("linux"), linux.to_v1());
+
+    // Merging in the same builds again is a no-op.
+    let mut merged_again = merged.clone();
+    merged_again.merge(&linux).expect("merge succeeded");
+    assert_eq!(merged_again, merged);
+
+    // Conflicting builds are rejected, and leave the summary unchanged.
+    let conflicting = SummaryV2::from_v1(
+        &v1_summary(&[
+            ("aaa", 0, PackageStatus::Direct, &[]),
+            ("common", 0, PackageStatus::Direct, &[]),
+        ]),
+        "linux",
+    );
+    let mut merged_conflict = merged.clone();
+    let err = merged_conflict
+        .merge(&conflicting)
+        .expect_err("conflicting builds");
+    assert_eq!(err.summary_id(), &common_id);
+    assert_eq!(err.key(), &BuildKey::new("linux", PlatformKind::Target));
+    assert_eq!(merged_conflict, merged, "summary is unchanged");
+
+    assert_eq!(
+        SummaryV2::merge_all([]).expect("merge succeeded"),
+        SummaryV2::default()
+    );
+}
+
+#[test]
+fn combined_diff() {
+    let old = SummaryV2::merge_all(&[
+        SummaryV2::from_v1(
+            &v1_summary(&[
+                ("common", 0, PackageStatus::Direct, &["std"]),
+                ("libc", 2, PackageStatus::Direct, &[]),
+            ]),
+            "linux",
+        ),
+        SummaryV2::from_v1(
+            &v1_summary(&[("common", 0, PackageStatus::Direct, &["std"])]),
+            "windows",
+        ),
+    ])
+    .expect("merge succeeded");
+    let new = SummaryV2::merge_all(&[
+        SummaryV2::from_v1(
+            &v1_summary(&[
+                ("common", 0, PackageStatus::Direct, &["alloc", "std"]),
+                ("libc", 3, PackageStatus::Direct, &[]),
+            ]),
+            "linux",
+        ),
+        SummaryV2::from_v1(
+            &v1_summary(&[("common", 0, PackageStatus::Direct, &["alloc", "std"])]),
+            "windows",
+        ),
+        SummaryV2::from_v1(
+            &v1_summary(&[("mach", 1, PackageStatus::Direct, &[])]),
+            "macos",
+        ),
+    ])
+    .expect("merge succeeded");
+
+    let diff = old.diff(&new);
+    assert!(diff.is_changed());
+    assert_eq!(
+        diff.platforms().into_iter().collect::<Vec<_>>(),
+        ["linux", "macos", "windows"]
+    );
+    assert!(diff.platform_diff("freebsd").is_none());
+
+    let changes = diff.changes();
+    let summary: Vec<_> = changes
+        .iter()
+        .map(|change| {
+            (
+                change.status.tag(),
+                change.summary_id.name.as_str(),
+                change.platforms.iter().copied().collect::<Vec<_>>(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (SummaryDiffTag::Added, "mach", vec!["macos"]),
+            (SummaryDiffTag::Modified, "common", vec!["linux", "windows"]),
+            (SummaryDiffTag::Modified, "libc", vec!["linux"]),
+        ]
+    );
+
+    assert_eq!(
+        diff.report().to_string(),
+        r#"target packages:
+  A mach 1.1.0 (direct third-party, crates.io) [macos]
+    * features: [none]
+  M common 1.0.0 (direct third-party, crates.io) [linux, windows]
+    * added features: alloc
+    * (unchanged features: std)
+    * (unchanged optional dependencies: [none])
+  M libc 1.3.0 (direct third-party, crates.io) [linux]
+    * version upgraded from 1.2.0
+    * (unchanged features: [none])
+    * (unchanged optional dependencies: [none])
+
+"#
+    );
+
+    let unchanged = new.diff(&new);
+    assert!(unchanged.is_unchanged());
+    assert!(unchanged.changes().is_empty());
+    assert_eq!(unchanged.report().to_string(), "");
+}
@@ -71,6 +71,41 @@ pub enum Error {
     /// An error occurred while serializing to TOML.
     #[cfg(feature = "summaries")]
     TomlSerializeError(toml::ser::Error),
+    /// An error occurred while reading a Cargo configuration file.
+    ///
+    /// This is present if the `cargo-config` feature is enabled.
+    #[cfg(feature = "cargo-config")]
+    CargoConfigReadError(Utf8PathBuf, std::io::Error),
+    /// An error occurred while parsing a Cargo configuration file.
+    ///
+    /// This is present if the `cargo-config` feature is enabled.
+    #[cfg(feature = "cargo-config")]
+    CargoConfigParseError(Utf8PathBuf, toml::de::Error),
+    /// An error occurred while writing to the `cargo metadata` cache.
+    ///
+    /// This is present if the `metadata-cache` feature is enabled.
+    #[cfg(feature = "metadata-cache")]
+    MetadataCacheError(std::path::PathBuf, std::io::Error),
+    /// An error occurred while reading a security advisory.
+    ///
+    /// This is present if the `rustsec` feature is enabled.
+    #[cfg(feature = "rustsec")]
+    AdvisoryReadError(Utf8PathBuf, std::io::Error),
+    /// An error occurred while parsing a security advisory.
+    ///
+    /// This is present if the `rustsec` feature is enabled.
+    #[cfg(feature = "rustsec")]
+    AdvisoryParseError(Utf8PathBuf, toml::de::Error),
+    /// An error occurred while reading the registry index entry for a crate.
+    ///
+    /// This is present if the `outdated` feature is enabled.
+    #[cfg(feature = "outdated")]
+    RegistryIndexError(String, Box<dyn error::Error + Send + Sync>),
+    /// An error occurred while parsing a dependency policy.
+    ///
+    /// This is present if the `policy` feature is enabled.
+    #[cfg(feature = "policy")]
+    PolicyParseError(toml::de::Error),
 }
 
 impl Error {
@@ -146,6 +181,28 @@ impl fmt::Display for Error {
             }
             #[cfg(feature = "summaries")]
             TomlSerializeError(_) => write!(f, "failed to serialize to TOML"),
+            #[cfg(feature = "cargo-config")]
+            CargoConfigReadError(path, _) => {
+                write!(f, "failed to read Cargo config at {}", path)
+            }
+            #[cfg(feature = "cargo-config")]
+            CargoConfigParseError(path, _) => {
+                write!(f, "failed to parse Cargo config at {}", path)
+            }
+            #[cfg(feature = "metadata-cache")]
+            MetadataCacheError(path, _) => {
+                write!(f, "failed to write metadata cache at {}", path.display())
+            }
+            #[cfg(feature = "rustsec")]
+            AdvisoryReadError(path, _) => write!(f, "failed to read advisory at {}", path),
+            #[cfg(feature = "rustsec")]
+            AdvisoryParseError(path, _) => write!(f, "failed to parse advisory at {}", path),
+            #[cfg(feature = "outdated")]
+            RegistryIndexError(name, _) => {
+                write!(f, "failed to read registry index entry for {}", name)
+            }
+            #[cfg(feature = "policy")]
+            PolicyParseError(_) => write!(f, "failed to parse dependency policy"),
         }
     }
 }
@@ -172,6 +229,20 @@ impl error::Error for Error {
             UnknownRegistryName { .. } => None,
             #[cfg(feature = "summaries")]
             TomlSerializeError(err) => Some(err),
+            #[cfg(feature = "cargo-config")]
+            CargoConfigReadError(_, err) => Some(err),
+            #[cfg(feature = "cargo-config")]
+            CargoConfigParseError(_, err) => Some(err),
+            #[cfg(feature = "metadata-cache")]
+            MetadataCacheError(_, err) => Some(err),
+            #[cfg(feature = "rustsec")]
+            AdvisoryReadError(_, err) => Some(err),
+            #[cfg(feature = "rustsec")]
+            AdvisoryParseError(_, err) => Some(err),
+            #[cfg(feature = "outdated")]
+            RegistryIndexError(_, err) => Some(err.as_ref()),
+            #[cfg(feature = "policy")]
+            PolicyParseError(err) => Some(err),
         }
     }
 }
@@ -0,0 +1,557 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Audit package sets against the [RustSec advisory database](https://rustsec.org/).
+//!
+//! Unlike auditing a `Cargo.lock` file, auditing a [`CargoSet`] only reports packages that are
+//! actually built for a given set of features and target platform. For example, a vulnerable
+//! dependency that's only used on Windows isn't reported for a Linux build.
+//!
+//! Advisories are read from a local checkout of the
+//! [advisory database repository](https://github.com/rustsec/advisory-db) through
+//! [`AdvisoryDatabase::load`]. Keeping the database up to date is left to the caller.
+//!
+//! Requires the `rustsec` feature to be enabled.
+//!
+//! # Examples
+//!
+//! ````
+//! use guppy::{
+//!     graph::audit::{Advisory, AdvisoryDatabase, AuditOptions},
+//!     CargoMetadata,
+//! };
+//!
+//! static ADVISORY: &str = r#"```toml
+//! [advisory]
+//! id = "RUSTSEC-0000-0001"
+//! package = "lazy_static"
+//! date = "2019-01-01"
+//!
+//! [versions]
+//! patched = [">= 1.0.0"]
+//! ```
+//!
+//! ## Example advisory for lazy_static
+//! "#;
+//!
+//! let metadata = CargoMetadata::parse_json(include_str!("../../../fixtures/small/metadata_dups.json")).unwrap();
+//! let graph = metadata.build_graph().unwrap();
+//!
+//! let mut db = AdvisoryDatabase::new();
+//! db.add(Advisory::parse("RUSTSEC-0000-0001.md", ADVISORY).unwrap());
+//!
+//! let report = db.audit_package_set(&graph.resolve_all(), &AuditOptions::new());
+//! for finding in report.findings() {
+//!     println!("{}: {}", finding.advisory().id(), finding.package().id());
+//!     for chain in finding.chains() {
+//!         let names: Vec<_> = chain.iter().map(|package| package.name()).collect();
+//!         println!("  via {}", names.join(" -> "));
+//!     }
+//! }
+//! # assert_eq!(report.findings().len(), 1);
+//! ````
+
+use crate::{
+    graph::{
+        cargo::{BuildPlatform, CargoSet},
+        chains::{BuiltPackage, DependencyChains},
+        DependencyDirection, PackageLink, PackageMetadata, PackageSet,
+    },
+    platform::PlatformSpec,
+    Error, PackageId,
+};
+use camino::Utf8Path;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+};
+use target_spec::TargetSpec;
+
+/// A collection of security advisories.
+///
+/// Requires the `rustsec` feature to be enabled.
+#[derive(Clone, Debug, Default)]
+pub struct AdvisoryDatabase {
+    // Advisories keyed by the name of the package they're for.
+    advisories: BTreeMap<String, Vec<Advisory>>,
+}
+
+impl AdvisoryDatabase {
+    /// Creates a new, empty advisory database.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads advisories from a checkout of the RustSec advisory database.
+    ///
+    /// Advisories are read from Markdown files in the `crates` directory within `dir`.
+    pub fn load(dir: impl AsRef<Utf8Path>) -> Result<Self, Error> {
+        let mut db = Self::new();
+        db.load_dir(&dir.as_ref().join("crates"))?;
+        Ok(db)
+    }
+
+    /// Adds an advisory to this database.
+    pub fn add(&mut self, advisory: Advisory) -> &mut Self {
+        self.advisories
+            .entry(advisory.package.clone())
+            .or_default()
+            .push(advisory);
+        self
+    }
+
+    /// Returns the number of advisories in this database.
+    pub fn len(&self) -> usize {
+        self.advisories
+            .values()
+            .map(|advisories| advisories.len())
+            .sum()
+    }
+
+    /// Returns true if this database has no advisories.
+    pub fn is_empty(&self) -> bool {
+        self.advisories.is_empty()
+    }
+
+    /// Iterates over all the advisories in this database, ordered by package name.
+    pub fn advisories(&self) -> impl Iterator<Item = &Advisory> + '_ {
+        self.advisories.values().flatten()
+    }
+
+    /// Iterates over the advisories for the given package name.
+    pub fn advisories_for<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a Advisory> + 'a {
+        self.advisories.get(name).into_iter().flatten()
+    }
+
+    /// Audits the packages in a `PackageSet`.
+    ///
+    /// Dependency chains start from the packages in the set that don't have any dependents within
+    /// it, and may go through dev-dependencies.
+    pub fn audit_package_set<'g>(
+        &self,
+        package_set: &PackageSet<'g>,
+        options: &AuditOptions,
+    ) -> AuditReport<'g, '_> {
+        let packages = package_set
+            .packages(DependencyDirection::Forward)
+            .map(BuiltPackage::new);
+        AuditReport::new(
+            self,
+            options,
+            None,
+            packages,
+            package_set.root_ids(DependencyDirection::Forward),
+            package_set.links(DependencyDirection::Forward),
+        )
+    }
+
+    /// Audits the packages built by a `CargoSet`.
+    ///
+    /// Only packages built for the features and platforms the `CargoSet` was computed for are
+    /// audited, and advisories limited to other operating systems or architectures are skipped.
+    /// Dependency chains start from the initials of the `CargoSet`.
+    pub fn audit_cargo_set<'g>(
+        &self,
+        cargo_set: &CargoSet<'g>,
+        options: &AuditOptions,
+    ) -> AuditReport<'g, '_> {
+        let initials = cargo_set.initials().to_package_set();
+        AuditReport::new(
+            self,
+            options,
+            Some(cargo_set),
+            BuiltPackage::from_cargo_set(cargo_set),
+            initials.package_ids(DependencyDirection::Forward),
+            cargo_set.followed_links().map(|(link, _)| link),
+        )
+    }
+
+    fn load_dir(&mut self, dir: &Utf8Path) -> Result<(), Error> {
+        let entries = dir
+            .read_dir_utf8()
+            .map_err(|err| Error::AdvisoryReadError(dir.to_owned(), err))?;
+        let mut paths = vec![];
+        for entry in entries {
+            let entry = entry.map_err(|err| Error::AdvisoryReadError(dir.to_owned(), err))?;
+            paths.push(entry.into_path());
+        }
+        // Sort paths so that advisories are loaded in a consistent order.
+        paths.sort_unstable();
+
+        for path in paths {
+            if path.is_dir() {
+                self.load_dir(&path)?;
+            } else if path.extension() == Some("md") {
+                let contents = fs::read_to_string(&path)
+                    .map_err(|err| Error::AdvisoryReadError(path.clone(), err))?;
+                self.add(Advisory::parse(&path, &contents)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A security advisory for a crate.
+///
+/// Requires the `rustsec` feature to be enabled.
+#[derive(Clone, Debug)]
+pub struct Advisory {
+    id: String,
+    package: String,
+    title: String,
+    description: String,
+    date: String,
+    url: Option<String>,
+    aliases: Vec<String>,
+    informational: Option<String>,
+    withdrawn: Option<String>,
+    affected_os: Vec<String>,
+    affected_arch: Vec<String>,
+    patched: Vec<VersionReq>,
+    unaffected: Vec<VersionReq>,
+}
+
+impl Advisory {
+    /// Parses an advisory in the format used by the RustSec advisory database.
+    ///
+    /// Advisories are Markdown files starting with TOML front matter in a `toml` code block,
+    /// followed by a title and a description. The path is used for error reporting.
+    pub fn parse(path: impl AsRef<Utf8Path>, contents: &str) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let parse_error = |message: &str| {
+            Error::AdvisoryParseError(path.to_owned(), serde::de::Error::custom(message))
+        };
+
+        let rest = contents
+            .trim_start()
+            .strip_prefix("```toml")
+            .ok_or_else(|| parse_error("advisory must start with TOML front matter"))?;
+        let (front_matter, markdown) = rest
+            .split_once("\n```")
+            .ok_or_else(|| parse_error("TOML front matter isn't terminated"))?;
+        let front_matter: FrontMatter = toml::from_str(front_matter)
+            .map_err(|err| Error::AdvisoryParseError(path.to_owned(), err))?;
+
+        let markdown = markdown.trim();
+        let (title, description) = match markdown.strip_prefix("# ") {
+            Some(rest) => rest.split_once('\n').unwrap_or((rest, "")),
+            None => ("", markdown),
+        };
+
+        let FrontMatter {
+            advisory,
+            affected,
+            versions,
+        } = front_matter;
+        Ok(Self {
+            id: advisory.id,
+            package: advisory.package,
+            title: title.trim().to_owned(),
+            description: description.trim().to_owned(),
+            date: advisory.date,
+            url: advisory.url,
+            aliases: advisory.aliases,
+            informational: advisory.informational,
+            withdrawn: advisory.withdrawn,
+            affected_os: affected.os,
+            affected_arch: affected.arch,
+            patched: versions.patched,
+            unaffected: versions.unaffected,
+        })
+    }
+
+    /// Returns the ID of this advisory, for example `RUSTSEC-2020-0071`.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the name of the crate this advisory is for.
+    pub fn package(&self) -> &str {
+        &self.package
+    }
+
+    /// Returns the title of this advisory.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Returns the description of this advisory, in Markdown.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Returns the date this advisory was published, in `YYYY-MM-DD` format.
+    pub fn date(&self) -> &str {
+        &self.date
+    }
+
+    /// Returns a URL with more information about this advisory, if any.
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Returns other IDs for this advisory, for example CVE or GHSA IDs.
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    /// Returns the kind of informational advisory this is, for example `unmaintained` or
+    /// `unsound`.
+    ///
+    /// Returns `None` for security vulnerabilities.
+    pub fn informational(&self) -> Option<&str> {
+        self.informational.as_deref()
+    }
+
+    /// Returns true if this advisory has been withdrawn.
+    ///
+    /// Withdrawn advisories are never reported.
+    pub fn is_withdrawn(&self) -> bool {
+        self.withdrawn.is_some()
+    }
+
+    /// Returns the operating systems this advisory is limited to.
+    ///
+    /// An empty list means that all operating systems are affected.
+    pub fn affected_os(&self) -> &[String] {
+        &self.affected_os
+    }
+
+    /// Returns the CPU architectures this advisory is limited to.
+    ///
+    /// An empty list means that all architectures are affected.
+    pub fn affected_arch(&self) -> &[String] {
+        &self.affected_arch
+    }
+
+    /// Returns the version requirements for versions where this advisory has been fixed.
+    pub fn patched(&self) -> &[VersionReq] {
+        &self.patched
+    }
+
+    /// Returns the version requirements for versions that were never affected by this advisory.
+    pub fn unaffected(&self) -> &[VersionReq] {
+        &self.unaffected
+    }
+
+    /// Returns true if builds for the given platform may be affected by this advisory, based on
+    /// [`affected_os`](Self::affected_os) and [`affected_arch`](Self::affected_arch).
+    ///
+    /// Only [`PlatformSpec::Platform`] can be ruled out: the other variants don't name a single
+    /// operating system or architecture, so they are always considered affected.
+    pub fn affects_platform(&self, platform: &PlatformSpec) -> bool {
+        let platform = match platform {
+            PlatformSpec::Platform(platform) => platform,
+            PlatformSpec::Always | PlatformSpec::Any => return true,
+        };
+        let matches = |key: &str, values: &[String]| {
+            values.is_empty()
+                || values.iter().any(|value| {
+                    // Treat unparseable values and unknown results as matching, so that
+                    // advisories are never suppressed by mistake.
+                    match TargetSpec::new(format!("cfg({key} = \"{value}\")")) {
+                        Ok(spec) => spec.eval(platform) != Some(false),
+                        Err(_) => true,
+                    }
+                })
+        };
+        matches("target_os", &self.affected_os) && matches("target_arch", &self.affected_arch)
+    }
+
+    /// Returns true if the given version of the crate is affected by this advisory.
+    pub fn is_affected(&self, version: &Version) -> bool {
+        !self
+            .patched
+            .iter()
+            .chain(&self.unaffected)
+            .any(|req| req.matches(version))
+    }
+}
+
+#[derive(Deserialize)]
+struct FrontMatter {
+    advisory: AdvisoryMetadata,
+    #[serde(default)]
+    affected: AffectedMetadata,
+    #[serde(default)]
+    versions: VersionsMetadata,
+}
+
+#[derive(Deserialize)]
+struct AdvisoryMetadata {
+    id: String,
+    package: String,
+    date: String,
+    url: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    informational: Option<String>,
+    withdrawn: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct AffectedMetadata {
+    #[serde(default)]
+    os: Vec<String>,
+    #[serde(default)]
+    arch: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct VersionsMetadata {
+    #[serde(default)]
+    patched: Vec<VersionReq>,
+    #[serde(default)]
+    unaffected: Vec<VersionReq>,
+}
+
+/// Options for auditing packages against an [`AdvisoryDatabase`].
+///
+/// Requires the `rustsec` feature to be enabled.
+#[derive(Clone, Debug, Default)]
+pub struct AuditOptions {
+    include_informational: bool,
+    ignore: BTreeSet<String>,
+}
+
+impl AuditOptions {
+    /// Creates a new `AuditOptions` with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether informational advisories, such as notices that a crate is unmaintained, are
+    /// reported.
+    ///
+    /// The default is false.
+    pub fn set_include_informational(&mut self, include_informational: bool) -> &mut Self {
+        self.include_informational = include_informational;
+        self
+    }
+
+    /// Adds advisory IDs to ignore.
+    pub fn add_ignored<'a>(&mut self, ids: impl IntoIterator<Item = &'a str>) -> &mut Self {
+        self.ignore.extend(ids.into_iter().map(|id| id.to_owned()));
+        self
+    }
+
+    fn is_reported(&self, advisory: &Advisory) -> bool {
+        !advisory.is_withdrawn()
+            && (self.include_informational || advisory.informational.is_none())
+            && !self.ignore.contains(&advisory.id)
+    }
+}
+
+/// The results of auditing packages against an [`AdvisoryDatabase`].
+///
+/// Returned by [`AdvisoryDatabase::audit_package_set`] and [`AdvisoryDatabase::audit_cargo_set`].
+///
+/// Requires the `rustsec` feature to be enabled.
+#[derive(Clone, Debug)]
+pub struct AuditReport<'g, 'a> {
+    findings: Vec<AdvisoryFinding<'g, 'a>>,
+}
+
+impl<'g, 'a> AuditReport<'g, 'a> {
+    fn new(
+        db: &'a AdvisoryDatabase,
+        options: &AuditOptions,
+        cargo_set: Option<&CargoSet<'g>>,
+        packages: impl IntoIterator<Item = BuiltPackage<'g>>,
+        roots: impl IntoIterator<Item = &'g PackageId>,
+        links: impl IntoIterator<Item = PackageLink<'g>>,
+    ) -> Self {
+        let packages: Vec<_> = packages.into_iter().collect();
+        let chains = DependencyChains::new(
+            packages.iter().map(|package| package.metadata),
+            roots,
+            links,
+        );
+
+        let mut findings = vec![];
+        for built in packages {
+            let package = built.metadata;
+            // The advisory database only covers crates.io.
+            if !package.source().is_crates_io() {
+                continue;
+            }
+            for advisory in db.advisories_for(package.name()) {
+                if !options.is_reported(advisory) || !advisory.is_affected(package.version()) {
+                    continue;
+                }
+                let mut build_platforms = built.build_platforms();
+                if let Some(cargo_set) = cargo_set {
+                    build_platforms.retain(|&build_platform| {
+                        advisory.affects_platform(cargo_set.platform(build_platform))
+                    });
+                    if build_platforms.is_empty() {
+                        continue;
+                    }
+                }
+                findings.push(AdvisoryFinding {
+                    advisory,
+                    package,
+                    build_platforms,
+                    chains: chains.chains_to(package.id()),
+                });
+            }
+        }
+        findings.sort_by(|a, b| {
+            (a.advisory.id(), a.package.id()).cmp(&(b.advisory.id(), b.package.id()))
+        });
+
+        Self { findings }
+    }
+
+    /// Returns the advisories that affect audited packages, sorted by advisory ID and package ID.
+    pub fn findings(&self) -> &[AdvisoryFinding<'g, 'a>] {
+        &self.findings
+    }
+
+    /// Returns true if no audited packages are affected by any advisories.
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+/// An advisory that affects an audited package.
+///
+/// Requires the `rustsec` feature to be enabled.
+#[derive(Clone, Debug)]
+pub struct AdvisoryFinding<'g, 'a> {
+    advisory: &'a Advisory,
+    package: PackageMetadata<'g>,
+    build_platforms: Vec<BuildPlatform>,
+    chains: Vec<Vec<PackageMetadata<'g>>>,
+}
+
+impl<'g, 'a> AdvisoryFinding<'g, 'a> {
+    /// Returns the advisory.
+    pub fn advisory(&self) -> &'a Advisory {
+        self.advisory
+    }
+
+    /// Returns the affected package.
+    pub fn package(&self) -> PackageMetadata<'g> {
+        self.package
+    }
+
+    /// Returns the build platforms the affected package is built on, limited to those whose
+    /// platform the advisory applies to.
+    ///
+    /// This is empty for audits of package sets, which don't track build platforms.
+    pub fn build_platforms(&self) -> &[BuildPlatform] {
+        &self.build_platforms
+    }
+
+    /// Returns dependency chains through which the affected package is included.
+    ///
+    /// There is one chain for each root package that the affected package is reachable from:
+    /// the shortest path from the root to the affected package, inclusive of both. Chains are
+    /// sorted by root package ID.
+    pub fn chains(&self) -> &[Vec<PackageMetadata<'g>>] {
+        &self.chains
+    }
+}
@@ -8,10 +8,11 @@ use crate::{
         PackageIx, PackageLinkImpl, PackageMetadataImpl, PackagePublishImpl, PackageSourceImpl,
         WorkspaceImpl,
     },
+    platform::PlatformStatusImpl,
     sorted_set::SortedSet,
     Error, PackageId,
 };
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use camino::{Utf8Path, Utf8PathBuf};
 use cargo_metadata::{
     DepKindInfo, Dependency, DependencyKind, Metadata, Node, NodeDep, Package, Target,
@@ -24,9 +25,9 @@ use semver::{Version, VersionReq};
 use smallvec::SmallVec;
 use std::{
     borrow::Cow,
-    cell::RefCell,
     collections::{BTreeMap, HashSet},
-    rc::Rc,
+    hash::Hash,
+    sync::{Arc, Mutex},
 };
 use target_spec::TargetSpec;
 
@@ -51,12 +52,36 @@ impl PackageGraph {
             &workspace_members,
         )?;
 
-        let packages: AHashMap<_, _> = metadata
+        // Processing packages is independent for each package, so it can be done in parallel.
+        // Edges are added to the graph afterwards, in the original order, to ensure that the
+        // graph is the same regardless of whether processing happens in parallel.
+        #[cfg(feature = "rayon1")]
+        let processed: Vec<_> = {
+            use rayon::prelude::*;
+
+            metadata
+                .packages
+                .into_par_iter()
+                .map(|package| build_state.process_package(package))
+                .collect::<Result<_, _>>()?
+        };
+        #[cfg(not(feature = "rayon1"))]
+        let processed: Vec<_> = metadata
             .packages
             .into_iter()
             .map(|package| build_state.process_package(package))
             .collect::<Result<_, _>>()?;
 
+        // The lookup tables aren't needed any more, so free them before the graph is filled out.
+        build_state.release_lookup_tables();
+
+        let mut packages = AHashMap::with_capacity(processed.len());
+        for mut processed in processed {
+            build_state.add_links(processed.package_ix, processed.links);
+            processed.metadata.intern(&mut build_state.interner);
+            packages.insert(processed.package_id, processed.metadata);
+        }
+
         let dep_graph = build_state.finish();
 
         let workspace = WorkspaceImpl::new(
@@ -79,6 +104,70 @@ impl PackageGraph {
     }
 }
 
+impl PackageGraph {
+    /// Creates a new graph consisting of just the included packages and the links between them.
+    pub(super) fn subgraph(&self, included: &FixedBitSet) -> Self {
+        let mut dep_graph = Graph::default();
+        let mut new_ixs = vec![None; self.dep_graph.node_count()];
+        let mut packages = AHashMap::with_capacity(included.count_ones(..));
+
+        // Iterate over nodes and edges in their original order, so that the new graph's indexes
+        // are in the same relative order.
+        for package_ix in self.dep_graph.node_indices() {
+            if !included.contains(package_ix.index()) {
+                continue;
+            }
+            let package_id = &self.dep_graph[package_ix];
+            let new_ix = dep_graph.add_node(package_id.clone());
+            new_ixs[package_ix.index()] = Some(new_ix);
+
+            let mut metadata = self.data.packages[package_id].clone();
+            metadata.package_ix = new_ix;
+            packages.insert(package_id.clone(), metadata);
+        }
+
+        for edge in self.dep_graph.edge_references() {
+            if let (Some(from_ix), Some(to_ix)) = (
+                new_ixs[edge.source().index()],
+                new_ixs[edge.target().index()],
+            ) {
+                dep_graph.add_edge(from_ix, to_ix, edge.weight().clone());
+            }
+        }
+
+        let workspace = &self.data.workspace;
+        let workspace = WorkspaceImpl {
+            root: workspace.root.clone(),
+            target_directory: workspace.target_directory.clone(),
+            metadata_table: workspace.metadata_table.clone(),
+            members_by_path: workspace
+                .members_by_path
+                .iter()
+                .filter(|(_, id)| packages.contains_key(*id))
+                .map(|(path, id)| (path.clone(), id.clone()))
+                .collect(),
+            members_by_name: workspace
+                .members_by_name
+                .iter()
+                .filter(|(_, id)| packages.contains_key(*id))
+                .map(|(name, id)| (name.clone(), id.clone()))
+                .collect(),
+            #[cfg(feature = "proptest1")]
+            name_list: OnceCell::new(),
+        };
+
+        Self {
+            dep_graph,
+            sccs: OnceCell::new(),
+            feature_graph: OnceCell::new(),
+            data: PackageGraphData {
+                packages,
+                workspace,
+            },
+        }
+    }
+}
+
 impl WorkspaceImpl {
     /// Indexes and creates a new workspace.
     fn new(
@@ -113,7 +202,7 @@ impl WorkspaceImpl {
             };
             members_by_path.insert(workspace_path.to_path_buf(), id.clone());
 
-            match members_by_name.entry(package_metadata.name.clone().into_boxed_str()) {
+            match members_by_name.entry(package_metadata.name.as_ref().into()) {
                 Entry::Vacant(vacant) => {
                     vacant.insert(id.clone());
                 }
@@ -144,15 +233,15 @@ impl WorkspaceImpl {
 /// Helper struct for building up dependency graph.
 struct GraphBuildState<'a> {
     dep_graph: Graph<PackageId, PackageLinkImpl, Directed, PackageIx>,
-    package_data: AHashMap<PackageId, Rc<PackageDataValue>>,
+    package_data: AHashMap<PackageId, Arc<PackageDataValue>>,
     // The above, except by package name.
-    by_package_name: AHashMap<String, Vec<Rc<PackageDataValue>>>,
+    by_package_name: AHashMap<String, Vec<Arc<PackageDataValue>>>,
 
-    // The values of resolve_data are the resolved dependencies. This is mutated so it is stored
-    // separately from package_data.
+    // The values of resolve_data are the resolved dependencies.
     resolve_data: AHashMap<PackageId, Vec<NodeDep>>,
     workspace_root: &'a Utf8Path,
     workspace_members: &'a HashSet<PackageId>,
+    interner: Interner,
 }
 
 impl<'a> GraphBuildState<'a> {
@@ -177,7 +266,7 @@ impl<'a> GraphBuildState<'a> {
 
         // While it is possible to have duplicate names so the hash map is smaller, just make this
         // as big as package_data.
-        let mut by_package_name: AHashMap<String, Vec<Rc<PackageDataValue>>> =
+        let mut by_package_name: AHashMap<String, Vec<Arc<PackageDataValue>>> =
             AHashMap::with_capacity(all_package_data.len());
         for package_data in all_package_data.values() {
             by_package_name
@@ -205,13 +294,11 @@ impl<'a> GraphBuildState<'a> {
             resolve_data,
             workspace_root,
             workspace_members,
+            interner: Interner::default(),
         })
     }
 
-    fn process_package(
-        &mut self,
-        package: Package,
-    ) -> Result<(PackageId, PackageMetadataImpl), Box<Error>> {
+    fn process_package(&self, package: Package) -> Result<ProcessedPackage, Box<Error>> {
         let package_id = PackageId::from_metadata(package.id);
         let (package_data, build_targets) =
             self.package_data_and_remove_build_targets(&package_id)?;
@@ -240,7 +327,10 @@ impl<'a> GraphBuildState<'a> {
         };
 
         // resolved_deps is missing if the metadata was generated with --no-deps.
-        let resolved_deps = self.resolve_data.remove(&package_id).unwrap_or_default();
+        let resolved_deps = self
+            .resolve_data
+            .get(&package_id)
+            .map_or(&[][..], |deps| deps.as_slice());
 
         let dep_resolver = DependencyResolver::new(
             &package_id,
@@ -249,6 +339,7 @@ impl<'a> GraphBuildState<'a> {
             &package.dependencies,
         );
 
+        let mut links = Vec::with_capacity(resolved_deps.len());
         for NodeDep {
             name: resolved_name,
             pkg,
@@ -256,14 +347,10 @@ impl<'a> GraphBuildState<'a> {
             ..
         } in resolved_deps
         {
-            let dep_id = PackageId::from_metadata(pkg);
-            let (dep_data, deps) = dep_resolver.resolve(&resolved_name, &dep_id, &dep_kinds)?;
-            let link = PackageLinkImpl::new(&package_id, &resolved_name, deps)?;
-            // Use update_edge instead of add_edge to prevent multiple edges from being added
-            // between these two nodes.
-            // XXX maybe check for an existing edge?
-            self.dep_graph
-                .update_edge(package_data.package_ix, dep_data.package_ix, link);
+            let dep_id = PackageId::from_metadata(pkg.clone());
+            let (dep_data, deps) = dep_resolver.resolve(resolved_name, &dep_id, dep_kinds)?;
+            let link = PackageLinkImpl::new(&package_id, resolved_name, deps)?;
+            links.push((dep_data.package_ix, link));
         }
 
         let has_default_feature = package.features.contains_key("default");
@@ -278,14 +365,14 @@ impl<'a> GraphBuildState<'a> {
         // one). Some of them might be optional, some might not be. List a dependency here if *any*
         // of those specifications are optional, since that's how Cargo features work. But also
         // dedup them.
-        let optional_deps: IndexSet<_> = package
+        let optional_deps: IndexSet<Arc<str>> = package
             .dependencies
             .into_iter()
             .filter_map(|dep| {
                 if dep.optional {
                     match dep.rename {
-                        Some(rename) => Some(rename.into_boxed_str()),
-                        None => Some(dep.name.into_boxed_str()),
+                        Some(rename) => Some(rename.into()),
+                        None => Some(dep.name.into()),
                     }
                 } else {
                     None
@@ -316,7 +403,7 @@ impl<'a> GraphBuildState<'a> {
                     }
                     parsed_deps.push(dep);
                 }
-                Ok((feature_name.into_boxed_str(), parsed_deps))
+                Ok((feature_name.into(), parsed_deps))
             })
             .collect::<Result<_, Error>>()?;
 
@@ -346,11 +433,13 @@ impl<'a> GraphBuildState<'a> {
                 }],
             });
 
-        Ok((
+        Ok(ProcessedPackage {
+            package_ix: package_data.package_ix,
+            links,
             package_id,
-            PackageMetadataImpl {
-                name: package.name,
-                version: package.version,
+            metadata: PackageMetadataImpl {
+                name: package.name.into(),
+                version: package.version.into(),
                 authors: package.authors,
                 description: package.description.map(|s| s.into()),
                 license: package.license.map(|s| s.into()),
@@ -377,18 +466,37 @@ impl<'a> GraphBuildState<'a> {
                 build_targets,
                 has_default_feature,
             },
-        ))
+        })
+    }
+
+    fn add_links(
+        &mut self,
+        from_ix: NodeIndex<PackageIx>,
+        links: Vec<(NodeIndex<PackageIx>, PackageLinkImpl)>,
+    ) {
+        for (to_ix, mut link) in links {
+            link.intern(&mut self.interner);
+            // Use update_edge instead of add_edge to prevent multiple edges from being added
+            // between these two nodes.
+            // XXX maybe check for an existing edge?
+            self.dep_graph.update_edge(from_ix, to_ix, link);
+        }
     }
 
     fn package_data_and_remove_build_targets(
         &self,
         id: &PackageId,
-    ) -> Result<(Rc<PackageDataValue>, BuildTargetMap), Box<Error>> {
+    ) -> Result<(Arc<PackageDataValue>, BuildTargetMap), Box<Error>> {
         let package_data = self.package_data.get(id).ok_or_else(|| {
             Error::PackageGraphConstructError(format!("no package data found for package '{}'", id))
         })?;
         let package_data = package_data.clone();
-        let build_targets = std::mem::take(&mut *package_data.build_targets.borrow_mut());
+        let build_targets = std::mem::take(
+            &mut *package_data
+                .build_targets
+                .lock()
+                .expect("build targets lock not poisoned"),
+        );
         Ok((package_data, build_targets))
     }
 
@@ -417,11 +525,122 @@ impl<'a> GraphBuildState<'a> {
         Ok(convert_forward_slashes(workspace_path).into_boxed_path())
     }
 
+    /// Frees data only used while processing packages, to reduce peak memory use.
+    fn release_lookup_tables(&mut self) {
+        self.package_data = AHashMap::new();
+        self.by_package_name = AHashMap::new();
+        self.resolve_data = AHashMap::new();
+    }
+
     fn finish(self) -> Graph<PackageId, PackageLinkImpl, Directed, PackageIx> {
         self.dep_graph
     }
 }
 
+/// Deduplicates values that are frequently repeated across the graph, like package, dependency
+/// and feature names, and versions.
+#[derive(Debug, Default)]
+struct Interner {
+    strings: AHashSet<Arc<str>>,
+    versions: AHashSet<Arc<Version>>,
+}
+
+impl Interner {
+    fn intern_str(&mut self, s: &mut Arc<str>) {
+        intern_in(&mut self.strings, s);
+    }
+
+    fn intern_version(&mut self, version: &mut Arc<Version>) {
+        intern_in(&mut self.versions, version);
+    }
+}
+
+fn intern_in<T: ?Sized + Eq + Hash>(set: &mut AHashSet<Arc<T>>, value: &mut Arc<T>) {
+    match set.get(value.as_ref()) {
+        Some(existing) => *value = existing.clone(),
+        None => {
+            set.insert(value.clone());
+        }
+    }
+}
+
+impl PackageMetadataImpl {
+    fn intern(&mut self, interner: &mut Interner) {
+        interner.intern_str(&mut self.name);
+        interner.intern_version(&mut self.version);
+        // IndexMap and IndexSet keys can't be mutated in place, so rebuild them. This preserves
+        // their order, which feature indexes rely on.
+        self.optional_deps = std::mem::take(&mut self.optional_deps)
+            .into_iter()
+            .map(|mut dep_name| {
+                interner.intern_str(&mut dep_name);
+                dep_name
+            })
+            .collect();
+        self.named_features = std::mem::take(&mut self.named_features)
+            .into_iter()
+            .map(|(mut feature, mut deps)| {
+                interner.intern_str(&mut feature);
+                for dep in &mut deps {
+                    dep.intern(interner);
+                }
+                (feature, deps)
+            })
+            .collect();
+    }
+}
+
+impl NamedFeatureDep {
+    fn intern(&mut self, interner: &mut Interner) {
+        match self {
+            NamedFeatureDep::NamedFeature(name) | NamedFeatureDep::OptionalDependency(name) => {
+                interner.intern_str(name);
+            }
+            NamedFeatureDep::DependencyNamedFeature {
+                dep_name, feature, ..
+            } => {
+                interner.intern_str(dep_name);
+                interner.intern_str(feature);
+            }
+        }
+    }
+}
+
+impl PackageLinkImpl {
+    fn intern(&mut self, interner: &mut Interner) {
+        interner.intern_str(&mut self.dep_name);
+        interner.intern_str(&mut self.resolved_name);
+        for req in [&mut self.normal, &mut self.build, &mut self.dev] {
+            req.required.intern(interner);
+            req.optional.intern(interner);
+        }
+    }
+}
+
+impl DepRequiredOrOptional {
+    fn intern(&mut self, interner: &mut Interner) {
+        if self.feature_targets.is_empty() {
+            return;
+        }
+        // BTreeMap keys can't be mutated in place, so rebuild the map.
+        self.feature_targets = std::mem::take(&mut self.feature_targets)
+            .into_iter()
+            .map(|(mut feature, status)| {
+                interner.intern_str(&mut feature);
+                (feature, status)
+            })
+            .collect();
+    }
+}
+
+/// The result of processing a single package, before its links are added to the graph.
+struct ProcessedPackage {
+    package_id: PackageId,
+    package_ix: NodeIndex<PackageIx>,
+    metadata: PackageMetadataImpl,
+    links: Vec<(NodeIndex<PackageIx>, PackageLinkImpl)>,
+}
+
 /// Intermediate state for a package as stored in `GraphBuildState`.
 #[derive(Debug)]
 struct PackageDataValue {
@@ -430,7 +649,7 @@ struct PackageDataValue {
     resolved_name: ResolvedName,
     // build_targets is used in two spots: in the constructor here, and removed from this field in
     // package_data_and_remove_build_targets.
-    build_targets: RefCell<BuildTargetMap>,
+    build_targets: Mutex<BuildTargetMap>,
     version: Version,
 }
 
@@ -438,7 +657,7 @@ impl PackageDataValue {
     fn new(
         package: &mut Package,
         dep_graph: &mut Graph<PackageId, PackageLinkImpl, Directed, PackageIx>,
-    ) -> Result<(PackageId, Rc<Self>), Box<Error>> {
+    ) -> Result<(PackageId, Arc<Self>), Box<Error>> {
         let package_id = PackageId::from_metadata(package.id.clone());
         let package_ix = dep_graph.add_node(package_id.clone());
 
@@ -474,11 +693,11 @@ impl PackageDataValue {
             package_ix,
             name: package.name.clone(),
             resolved_name,
-            build_targets: RefCell::new(build_targets),
+            build_targets: Mutex::new(build_targets),
             version: package.version.clone(),
         };
 
-        Ok((package_id, Rc::new(value)))
+        Ok((package_id, Arc::new(value)))
     }
 }
 
@@ -697,7 +916,7 @@ struct DependencyResolver<'g> {
     from_id: &'g PackageId,
 
     /// The package data, inherited from the graph build state.
-    package_data: &'g AHashMap<PackageId, Rc<PackageDataValue>>,
+    package_data: &'g AHashMap<PackageId, Arc<PackageDataValue>>,
 
     /// This is a list of dependency requirements. We don't know the package ID yet so we don't have
     /// a great key to work with. This could be improved in the future by matching on requirements
@@ -709,8 +928,8 @@ impl<'g> DependencyResolver<'g> {
     /// Constructs a new resolver using the provided package data and dependencies.
     fn new(
         from_id: &'g PackageId,
-        package_data: &'g AHashMap<PackageId, Rc<PackageDataValue>>,
-        by_package_name: &'g AHashMap<String, Vec<Rc<PackageDataValue>>>,
+        package_data: &'g AHashMap<PackageId, Arc<PackageDataValue>>,
+        by_package_name: &'g AHashMap<String, Vec<Arc<PackageDataValue>>>,
         package_deps: impl IntoIterator<Item = &'g Dependency>,
     ) -> Self {
         let mut dep_reqs = DependencyReqs::default();
@@ -755,7 +974,7 @@ impl<'g> DependencyResolver<'g> {
         dep_kinds: &'a [DepKindInfo],
     ) -> Result<
         (
-            &'g Rc<PackageDataValue>,
+            &'g Arc<PackageDataValue>,
             impl Iterator<Item = &'g Dependency> + 'a,
         ),
         Error,
@@ -890,7 +1109,7 @@ impl PackageLinkImpl {
         });
 
         Ok(Self {
-            dep_name,
+            dep_name: dep_name.into(),
             resolved_name: resolved_name.into(),
             version_req,
             normal,
@@ -962,10 +1181,14 @@ impl DepRequiredOrOptional {
         }
 
         for feature in &dep.features {
-            self.feature_targets
-                .entry(feature.clone())
-                .or_default()
-                .add_spec(target_spec.as_ref());
+            match self.feature_targets.get_mut(feature.as_str()) {
+                Some(status) => status.add_spec(target_spec.as_ref()),
+                None => {
+                    let mut status = PlatformStatusImpl::default();
+                    status.add_spec(target_spec.as_ref());
+                    self.feature_targets.insert(feature.as_str().into(), status);
+                }
+            }
         }
         Ok(())
     }
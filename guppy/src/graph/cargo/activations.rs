@@ -0,0 +1,125 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::{
+        cargo::{BuildPlatform, CargoOptions, CargoSet},
+        feature::{FeatureList, FeatureSet},
+        DependencyDirection, PackageMetadata,
+    },
+    Error, PackageId,
+};
+use std::collections::BTreeMap;
+
+/// Third-party feature activations, grouped by the initial package that activates them.
+///
+/// A [`CargoSet`] represents the result of feature unification: for every package, the union of
+/// all features requested by every dependent. `FeatureActivations` breaks that result down: for
+/// every third-party (non-workspace) dependency, it records the features each initial package
+/// would cause to be built if it were built on its own.
+///
+/// This is useful for attributing feature bloat to particular workspace packages.
+///
+/// The union of the activations for a dependency is not necessarily the same as the features
+/// in a unified `CargoSet`: unifying features can cause additional features or packages to be
+/// enabled.
+#[derive(Clone, Debug)]
+pub struct FeatureActivations<'g> {
+    map: BTreeMap<&'g PackageId, Vec<FeatureActivation<'g>>>,
+}
+
+assert_covariant!(FeatureActivations);
+
+impl<'g> FeatureActivations<'g> {
+    /// Computes feature activations for each package in `initials` with the given options.
+    ///
+    /// A separate Cargo build is simulated for every package in `initials`, with the features
+    /// in `initials` for that package.
+    pub fn new(initials: &FeatureSet<'g>, opts: &CargoOptions<'_>) -> Result<Self, Error> {
+        let mut map: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        let feature_graph = initials.graph();
+
+        for initial in initials.packages_with_features(DependencyDirection::Forward) {
+            let activator = *initial.package();
+            let package_initials = feature_graph.resolve_ids(initial.iter())?;
+            let cargo_set = CargoSet::new(package_initials, feature_graph.resolve_none(), opts)?;
+
+            for (build_platform, feature_set) in cargo_set.all_features() {
+                for features in feature_set.packages_with_features(DependencyDirection::Forward) {
+                    if features.package().in_workspace() {
+                        continue;
+                    }
+                    map.entry(features.package().id())
+                        .or_default()
+                        .push(FeatureActivation {
+                            activator,
+                            build_platform,
+                            features,
+                        });
+                }
+            }
+        }
+
+        Ok(Self { map })
+    }
+
+    /// Returns the number of third-party dependencies activated by any initial.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns true if no third-party dependencies were activated.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the activations for a third-party dependency.
+    ///
+    /// Returns `None` if this dependency wasn't activated by any initial.
+    pub fn activations_for(&self, package_id: &PackageId) -> Option<&[FeatureActivation<'g>]> {
+        self.map
+            .get(package_id)
+            .map(|activations| activations.as_slice())
+    }
+
+    /// Iterates over third-party dependencies and their activations, ordered by package ID.
+    ///
+    /// Within a dependency, activations are ordered by initial package (in topological order),
+    /// then by build platform.
+    pub fn iter<'a>(
+        &'a self,
+    ) -> impl ExactSizeIterator<Item = (&'g PackageId, &'a [FeatureActivation<'g>])> + 'a {
+        self.map
+            .iter()
+            .map(|(package_id, activations)| (*package_id, activations.as_slice()))
+    }
+}
+
+/// The features of a third-party dependency built by a single initial package.
+///
+/// Part of [`FeatureActivations`].
+#[derive(Clone, Debug)]
+pub struct FeatureActivation<'g> {
+    activator: PackageMetadata<'g>,
+    build_platform: BuildPlatform,
+    features: FeatureList<'g>,
+}
+
+assert_covariant!(FeatureActivation);
+
+impl<'g> FeatureActivation<'g> {
+    /// Returns the initial package that activates these features.
+    pub fn activator(&self) -> PackageMetadata<'g> {
+        self.activator
+    }
+
+    /// Returns the platform the dependency is built on.
+    pub fn build_platform(&self) -> BuildPlatform {
+        self.build_platform
+    }
+
+    /// Returns the features built for the dependency.
+    pub fn features(&self) -> &FeatureList<'g> {
+        &self.features
+    }
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    debug_ignore::DebugIgnore,
     graph::{
         cargo::{
             CargoIntermediateSet, CargoOptions, CargoResolverVersion, CargoSet, InitialsPlatform,
@@ -15,6 +16,7 @@ use crate::{
 };
 use fixedbitset::FixedBitSet;
 use petgraph::{prelude::*, visit::VisitMap};
+use std::sync::Arc;
 
 pub(super) struct CargoSetBuildState<'a> {
     opts: &'a CargoOptions<'a>,
@@ -40,42 +42,77 @@ impl<'a> CargoSetBuildState<'a> {
         initials: FeatureSet<'g>,
         features_only: FeatureSet<'g>,
     ) -> CargoSet<'g> {
-        match self.opts.resolver {
-            CargoResolverVersion::V1 => self.new_v1(initials, features_only, false),
-            CargoResolverVersion::V1Install => {
-                let avoid_dev_deps = !self.opts.include_dev;
-                self.new_v1(initials, features_only, avoid_dev_deps)
-            }
-            CargoResolverVersion::V2 => self.new_v2(initials, features_only),
-        }
+        let target_platform = &self.opts.target_platform;
+        let inputs = initials.union(&features_only);
+        let resolution = self.resolve_features(inputs, target_platform);
+        self.build_set(initials, features_only, target_platform, resolution)
     }
 
-    pub(super) fn build_intermediate(self, query: FeatureQuery) -> CargoIntermediateSet {
-        match self.opts.resolver {
-            CargoResolverVersion::V1 => self.new_v1_intermediate(query, false),
-            CargoResolverVersion::V1Install => {
-                let avoid_dev_deps = !self.opts.include_dev;
-                self.new_v1_intermediate(query, avoid_dev_deps)
-            }
-            CargoResolverVersion::V2 => self.new_v2_intermediate(query),
-        }
+    /// Like `build`, but reuses feature resolution from `previous` if none of its inputs changed.
+    pub(super) fn rebuild<'g>(
+        self,
+        previous: &CargoSet<'g>,
+        initials: FeatureSet<'g>,
+        features_only: FeatureSet<'g>,
+    ) -> CargoSet<'g> {
+        let target_platform = &self.opts.target_platform;
+        let inputs = initials.union(&features_only);
+        let key = self.resolution_key(target_platform);
+        let resolution = if previous.resolution.key == key && previous.resolution.inputs == inputs {
+            previous.resolution.0.clone()
+        } else {
+            self.resolve_features(inputs, target_platform)
+        };
+        self.build_set(initials, features_only, target_platform, resolution)
     }
 
-    fn new_v1<'g>(
+    pub(super) fn build_matrix<'g>(
         self,
         initials: FeatureSet<'g>,
         features_only: FeatureSet<'g>,
-        avoid_dev_deps: bool,
-    ) -> CargoSet<'g> {
-        self.build_set(initials, features_only, |query| {
-            self.new_v1_intermediate(query, avoid_dev_deps)
-        })
+        target_platforms: Vec<PlatformSpec>,
+    ) -> Vec<(PlatformSpec, CargoSet<'g>)> {
+        // The inputs to feature resolution are shared across all platforms.
+        let inputs = initials.union(&features_only);
+
+        // The V1 resolver unifies features across all platforms, so the intermediate set is the
+        // same for every target platform. Compute it just once.
+        let v1_resolution = match self.opts.resolver {
+            CargoResolverVersion::V1 | CargoResolverVersion::V1Install => {
+                Some(self.resolve_features(inputs.clone(), &PlatformSpec::Any))
+            }
+            CargoResolverVersion::V2 | CargoResolverVersion::V3 => None,
+        };
+
+        let build_one = |target_platform: PlatformSpec| {
+            let resolution = match &v1_resolution {
+                Some(resolution) => resolution.clone(),
+                None => self.resolve_features(inputs.clone(), &target_platform),
+            };
+            let cargo_set = self.build_set(
+                initials.clone(),
+                features_only.clone(),
+                &target_platform,
+                resolution,
+            );
+            (target_platform, cargo_set)
+        };
+
+        // Simulations for each target platform are independent of each other, so they can be
+        // performed in parallel.
+        #[cfg(feature = "rayon1")]
+        {
+            use rayon::prelude::*;
+            target_platforms.into_par_iter().map(build_one).collect()
+        }
+        #[cfg(not(feature = "rayon1"))]
+        {
+            target_platforms.into_iter().map(build_one).collect()
+        }
     }
 
-    fn new_v2<'g>(self, initials: FeatureSet<'g>, features_only: FeatureSet<'g>) -> CargoSet<'g> {
-        self.build_set(initials, features_only, |query| {
-            self.new_v2_intermediate(query)
-        })
+    pub(super) fn build_intermediate(self, query: FeatureQuery) -> CargoIntermediateSet {
+        self.intermediate_for_platform(query, &self.opts.target_platform)
     }
 
     // ---
@@ -86,11 +123,65 @@ impl<'a> CargoSetBuildState<'a> {
         self.omitted_packages.contains(&package_ix)
     }
 
+    fn v1_avoid_dev_deps(&self) -> bool {
+        match self.opts.resolver {
+            CargoResolverVersion::V1Install => !self.opts.include_dev,
+            CargoResolverVersion::V1 | CargoResolverVersion::V2 | CargoResolverVersion::V3 => false,
+        }
+    }
+
+    fn resolution_key(&self, target_platform: &PlatformSpec) -> ResolutionKey {
+        let platforms = match self.opts.resolver {
+            CargoResolverVersion::V1 | CargoResolverVersion::V1Install => None,
+            CargoResolverVersion::V2 | CargoResolverVersion::V3 => Some(V2ResolutionKey {
+                include_dev: self.opts.include_dev,
+                initials_platform: self.opts.initials_platform,
+                host_platform: self.opts.host_platform.clone(),
+                target_platform: target_platform.clone(),
+            }),
+        };
+        ResolutionKey {
+            resolver: self.opts.resolver,
+            omitted_packages: self.omitted_packages.clone(),
+            avoid_dev_deps: self.v1_avoid_dev_deps(),
+            platforms,
+        }
+    }
+
+    fn resolve_features<'g>(
+        &self,
+        inputs: FeatureSet<'g>,
+        target_platform: &PlatformSpec,
+    ) -> Arc<FeatureResolution<'g>> {
+        let query = inputs.to_feature_query(DependencyDirection::Forward);
+        Arc::new(FeatureResolution {
+            key: self.resolution_key(target_platform),
+            intermediate: self.intermediate_for_platform(query, target_platform),
+            inputs,
+        })
+    }
+
+    fn intermediate_for_platform<'g>(
+        &self,
+        query: FeatureQuery<'g>,
+        target_platform: &PlatformSpec,
+    ) -> CargoIntermediateSet<'g> {
+        match self.opts.resolver {
+            CargoResolverVersion::V1 | CargoResolverVersion::V1Install => {
+                self.new_v1_intermediate(query, self.v1_avoid_dev_deps())
+            }
+            CargoResolverVersion::V2 | CargoResolverVersion::V3 => {
+                self.new_v2_intermediate(query, target_platform)
+            }
+        }
+    }
+
     fn build_set<'g>(
         &self,
         initials: FeatureSet<'g>,
         features_only: FeatureSet<'g>,
-        intermediate_fn: impl FnOnce(FeatureQuery<'g>) -> CargoIntermediateSet<'g>,
+        target_platform: &PlatformSpec,
+        resolution: Arc<FeatureResolution<'g>>,
     ) -> CargoSet<'g> {
         // Prepare a package query for step 2.
         let graph = *initials.graph();
@@ -132,13 +223,9 @@ impl<'a> CargoSetBuildState<'a> {
             .package_graph
             .query_from_parts(SortedSet::new(target_ixs), DependencyDirection::Forward);
 
-        // 1. Build the intermediate set containing the features for any possible package that can
-        // be built, including features-only packages.
-        let initials_plus_features_only = initials.union(&features_only);
-        let intermediate_set = intermediate_fn(
-            initials_plus_features_only.to_feature_query(DependencyDirection::Forward),
-        );
-        let (target_set, host_set) = intermediate_set.target_host_sets();
+        // 1. The intermediate set contains the features for any possible package that can be
+        // built, including features-only packages. It is computed by the caller.
+        let (target_set, host_set) = resolution.intermediate.target_host_sets();
 
         // While doing traversal 2 below, record any packages discovered along build edges for use
         // in host ixs, to prepare for step 3. This will also include proc-macros.
@@ -185,7 +272,6 @@ impl<'a> CargoSetBuildState<'a> {
 
         // 2. Figure out what packages will be included on the target platform, i.e. normal + dev
         // (if requested).
-        let target_platform = &self.opts.target_platform;
         let host_platform = &self.opts.host_platform;
 
         let target_packages = target_query.resolve_with_fn(|query, link| {
@@ -308,8 +394,11 @@ impl<'a> CargoSetBuildState<'a> {
             host_features,
             target_direct_deps,
             host_direct_deps,
+            target_platform: target_platform.clone(),
+            host_platform: self.opts.host_platform.clone(),
             proc_macro_edge_ixs: SortedSet::new(proc_macro_edge_ixs),
             build_dep_edge_ixs: SortedSet::new(build_dep_edge_ixs),
+            resolution: DebugIgnore(resolution),
         }
     }
 
@@ -340,7 +429,11 @@ impl<'a> CargoSetBuildState<'a> {
         CargoIntermediateSet::Unified(complete_set)
     }
 
-    fn new_v2_intermediate<'g>(&self, query: FeatureQuery<'g>) -> CargoIntermediateSet<'g> {
+    fn new_v2_intermediate<'g>(
+        &self,
+        query: FeatureQuery<'g>,
+        target_platform: &PlatformSpec,
+    ) -> CargoIntermediateSet<'g> {
         let graph = *query.graph();
         // Note that proc macros specified in initials take part in feature resolution
         // for both target and host ixs. If they didn't, then the query would be partitioned into
@@ -381,7 +474,6 @@ impl<'a> CargoSetBuildState<'a> {
         let target_query_2 = target_query.clone();
 
         // 1. Perform a feature query for the target.
-        let target_platform = &self.opts.target_platform;
         let host_platform = &self.opts.host_platform;
         let target = target_query.resolve_with_fn(|query, link| {
             let (from, to) = link.endpoints();
@@ -461,3 +553,31 @@ impl<'a> CargoSetBuildState<'a> {
         CargoIntermediateSet::TargetHost { target, host }
     }
 }
+
+/// The result of feature resolution (step 1 of a build simulation), along with its inputs.
+///
+/// This is stored in a `CargoSet` so that it can be reused by `CargoSet::recompute`.
+#[derive(Debug)]
+pub(super) struct FeatureResolution<'g> {
+    key: ResolutionKey,
+    inputs: FeatureSet<'g>,
+    intermediate: CargoIntermediateSet<'g>,
+}
+
+/// The options that feature resolution depends on.
+#[derive(Debug, Eq, PartialEq)]
+struct ResolutionKey {
+    resolver: CargoResolverVersion,
+    omitted_packages: SortedSet<NodeIndex<PackageIx>>,
+    avoid_dev_deps: bool,
+    // The V1 resolvers unify features across all platforms, so this is None for them.
+    platforms: Option<V2ResolutionKey>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct V2ResolutionKey {
+    include_dev: bool,
+    initials_platform: InitialsPlatform,
+    host_platform: PlatformSpec,
+    target_platform: PlatformSpec,
+}
@@ -2,10 +2,15 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    debug_ignore::DebugIgnore,
     graph::{
-        cargo::build::CargoSetBuildState,
-        feature::{FeatureGraph, FeatureSet},
-        DependencyDirection, PackageGraph, PackageIx, PackageLink, PackageSet,
+        cargo::{
+            build::{CargoSetBuildState, FeatureResolution},
+            DormantDependencies,
+        },
+        feature::{FeatureGraph, FeatureLabel, FeatureSet},
+        BuildTarget, BuildTargetId, DependencyDirection, PackageGraph, PackageIx, PackageLink,
+        PackageMetadata, PackageSet,
     },
     platform::PlatformSpec,
     sorted_set::SortedSet,
@@ -13,7 +18,7 @@ use crate::{
 };
 use petgraph::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, fmt};
+use std::{collections::HashSet, fmt, sync::Arc};
 
 /// Options for queries which simulate what Cargo does.
 ///
@@ -50,6 +55,26 @@ impl<'a> CargoOptions<'a> {
         }
     }
 
+    /// Creates a new `CargoOptions` that simulates `cargo test` on the initials.
+    ///
+    /// This differs from [`CargoOptions::new`] in the following ways:
+    ///
+    /// * dev-dependencies of initials are followed, and take part in feature unification
+    /// * proc macros specified in the initials are also built on the target platform, since their
+    ///   tests run there.
+    ///
+    /// As with `cargo test -p foo`, only the initials are built in test mode: any other workspace
+    /// packages they depend on are built as regular libraries, without their dev-dependencies.
+    ///
+    /// To find out which targets within the initials are built, use
+    /// [`CargoSet::test_targets`](CargoSet::test_targets).
+    pub fn new_test() -> Self {
+        let mut opts = Self::new();
+        opts.set_include_dev(true)
+            .set_initials_platform(InitialsPlatform::ProcMacrosOnTarget);
+        opts
+    }
+
     /// Sets the Cargo feature resolver version.
     ///
     /// For more about feature resolution, see the documentation for `CargoResolverVersion`.
@@ -157,6 +182,22 @@ pub enum CargoResolverVersion {
     /// workspace's `Cargo.toml`.
     #[serde(rename = "2", alias = "v2")]
     V2,
+
+    /// [Version 3 of the resolver](https://doc.rust-lang.org/cargo/reference/resolver.html#resolver-versions),
+    /// available since Rust 1.84 and the default for the 2024 edition.
+    ///
+    /// Version 3 makes the dependency resolver MSRV-aware: when versions are picked for the
+    /// `Cargo.lock`, versions compatible with the workspace's `rust-version` are preferred.
+    /// Feature resolution is the same as with [`V2`](Self::V2).
+    ///
+    /// guppy works off of an already-resolved `Cargo.lock`, so the versions picked by the MSRV-aware
+    /// resolver are already reflected in the package graph. Build simulations with this version
+    /// produce the same results as with `V2`.
+    ///
+    /// Version 3 of the resolver can be enabled by specifying `resolver = "3"` in the workspace's
+    /// `Cargo.toml`.
+    #[serde(rename = "3", alias = "v3")]
+    V3,
 }
 
 /// For a given Cargo build simulation, what platform to assume the initials are being built on.
@@ -204,8 +245,12 @@ pub struct CargoSet<'g> {
     pub(super) host_features: FeatureSet<'g>,
     pub(super) target_direct_deps: PackageSet<'g>,
     pub(super) host_direct_deps: PackageSet<'g>,
+    pub(super) target_platform: PlatformSpec,
+    pub(super) host_platform: PlatformSpec,
     pub(super) proc_macro_edge_ixs: SortedSet<EdgeIndex<PackageIx>>,
     pub(super) build_dep_edge_ixs: SortedSet<EdgeIndex<PackageIx>>,
+    // Kept around for CargoSet::recompute.
+    pub(super) resolution: DebugIgnore<Arc<FeatureResolution<'g>>>,
 }
 
 assert_covariant!(CargoSet);
@@ -240,6 +285,67 @@ impl<'g> CargoSet<'g> {
         Ok(build_state.build(initials, features_only))
     }
 
+    /// Simulates a Cargo build, reusing work from this `CargoSet` where possible.
+    ///
+    /// This returns the same result as calling [`CargoSet::new`] with the same arguments, but is
+    /// faster if the inputs to feature resolution haven't changed since this `CargoSet` was
+    /// computed. Feature resolution is the most expensive part of a build simulation, and depends
+    /// on:
+    ///
+    /// * the union of `initials` and `features_only`
+    /// * the resolver version and omitted packages
+    /// * with the V1 install resolver, whether dev-dependencies are included
+    /// * with the V2 resolver, the host and target platforms, whether dev-dependencies are
+    ///   included, and the initials platform.
+    ///
+    /// For example, with the V1 resolver, changing the target platform or flipping
+    /// [`include_dev`](CargoOptions::set_include_dev) reuses feature resolution, as does moving
+    /// packages between `initials` and `features_only`. Adding or removing a feature requires
+    /// feature resolution to be performed again.
+    pub fn recompute(
+        &self,
+        initials: FeatureSet<'g>,
+        features_only: FeatureSet<'g>,
+        opts: &CargoOptions<'_>,
+    ) -> Result<Self, Error> {
+        let build_state = CargoSetBuildState::new(initials.graph().package_graph, opts)?;
+        Ok(build_state.rebuild(self, initials, features_only))
+    }
+
+    /// Simulates a Cargo build of the same initials and features-only set with different options,
+    /// reusing work from this `CargoSet` where possible.
+    ///
+    /// For more about which options allow work to be reused, see
+    /// [`recompute`](Self::recompute).
+    pub fn with_options(&self, opts: &CargoOptions<'_>) -> Result<Self, Error> {
+        self.recompute(self.initials.clone(), self.features_only.clone(), opts)
+    }
+
+    /// Simulates Cargo builds of this feature set for several target platforms at once.
+    ///
+    /// This is equivalent to calling [`CargoSet::new`](CargoSet::new) once per target platform,
+    /// with the target platform in `opts` replaced by each element of `target_platforms` in turn.
+    /// The host platform and all other options are taken from `opts`.
+    ///
+    /// Computing the matrix in one call shares work across platforms. With the V1 resolvers,
+    /// feature resolution is platform-independent, so it is only performed once. With the `rayon1`
+    /// feature enabled, simulations for different platforms are run in parallel.
+    ///
+    /// The returned [`CargoSetMatrix`] also provides the features common to every platform, along
+    /// with per-platform deltas relative to them.
+    pub fn compute_matrix(
+        initials: FeatureSet<'g>,
+        features_only: FeatureSet<'g>,
+        opts: &CargoOptions<'_>,
+        target_platforms: impl IntoIterator<Item = impl Into<PlatformSpec>>,
+    ) -> Result<CargoSetMatrix<'g>, Error> {
+        let feature_graph = *initials.graph();
+        let build_state = CargoSetBuildState::new(feature_graph.package_graph, opts)?;
+        let target_platforms = target_platforms.into_iter().map(Into::into).collect();
+        let entries = build_state.build_matrix(initials, features_only, target_platforms);
+        Ok(CargoSetMatrix::new(feature_graph, entries))
+    }
+
     /// Creates a new `CargoIntermediateSet` based on the given query and options.
     ///
     /// This set contains an over-estimate of targets and features.
@@ -313,6 +419,24 @@ impl<'g> CargoSet<'g> {
         ]
     }
 
+    /// Returns the platform this `CargoSet` was computed for as the target platform.
+    pub fn target_platform(&self) -> &PlatformSpec {
+        &self.target_platform
+    }
+
+    /// Returns the platform this `CargoSet` was computed for as the host platform.
+    pub fn host_platform(&self) -> &PlatformSpec {
+        &self.host_platform
+    }
+
+    /// Returns the platform this `CargoSet` was computed for on the specified build platform.
+    pub fn platform(&self, build_platform: BuildPlatform) -> &PlatformSpec {
+        match build_platform {
+            BuildPlatform::Target => self.target_platform(),
+            BuildPlatform::Host => self.host_platform(),
+        }
+    }
+
     /// Returns the set of workspace and direct dependency packages on the target platform.
     ///
     /// The packages in this set are a subset of the packages in `target_features`.
@@ -344,6 +468,66 @@ impl<'g> CargoSet<'g> {
         ]
     }
 
+    /// Returns the build targets within the initials that `cargo test` would build.
+    ///
+    /// This includes library, binary, test and example targets. Targets with
+    /// [`required-features`](BuildTarget::required_features) are only returned if all of their
+    /// required features are enabled.
+    ///
+    /// This is most useful for `CargoSet` instances constructed with
+    /// [`CargoOptions::new_test`](CargoOptions::new_test).
+    pub fn test_targets<'a>(&'a self) -> impl Iterator<Item = BuildTarget<'g>> + 'a {
+        self.initials
+            .packages_with_features(DependencyDirection::Forward)
+            .flat_map(move |feature_list| {
+                let package = *feature_list.package();
+                package.build_targets().filter(move |build_target| {
+                    match build_target.id() {
+                        BuildTargetId::Library => true,
+                        BuildTargetId::Binary(_)
+                        | BuildTargetId::Test(_)
+                        | BuildTargetId::Example(_) => build_target
+                            .required_features()
+                            .iter()
+                            .all(|feature| self.is_required_feature_enabled(package, feature)),
+                        // Benchmarks are only built if they set `test = true`, which isn't
+                        // available in cargo metadata.
+                        _ => false,
+                    }
+                })
+            })
+    }
+
+    /// Returns true if a `required-features` entry for a build target in this package is enabled.
+    fn is_required_feature_enabled(&self, package: PackageMetadata<'g>, feature: &str) -> bool {
+        // Targets within initials are built on the target platform, except for proc macros in a
+        // standard build.
+        let feature_set = if self
+            .target_features
+            .contains_package(package.id())
+            .unwrap_or(false)
+        {
+            &self.target_features
+        } else {
+            &self.host_features
+        };
+        let contains = |package_id: &PackageId, label: FeatureLabel<'_>| {
+            feature_set.contains((package_id, label)).unwrap_or(false)
+        };
+
+        match feature.split_once('/') {
+            Some((dep_name, dep_feature)) => {
+                // This is a "dep/feature" or "dep?/feature" requirement.
+                let dep_name = dep_name.strip_suffix('?').unwrap_or(dep_name);
+                package.direct_links().any(|link| {
+                    link.dep_name() == dep_name
+                        && contains(link.to().id(), FeatureLabel::Named(dep_feature))
+                })
+            }
+            None => contains(package.id(), FeatureLabel::Named(feature)),
+        }
+    }
+
     /// Returns `PackageLink` instances for procedural macro dependencies from target packages.
     ///
     /// Procedural macros straddle the line between target and host: they're built for the host
@@ -379,6 +563,169 @@ impl<'g> CargoSet<'g> {
             .iter()
             .map(move |edge_ix| package_graph.edge_ix_to_link(*edge_ix))
     }
+
+    /// Returns the links followed by this build, along with whether each link is followed as a
+    /// dev-dependency.
+    ///
+    /// This includes links within each build platform, followed by links that cross from the
+    /// target to the host platform. A link may be returned more than once.
+    #[cfg(any(feature = "policy", feature = "rustsec", feature = "sbom"))]
+    pub(crate) fn followed_links<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (PackageLink<'g>, bool)> + 'a {
+        let platform_links = self
+            .all_features()
+            .into_iter()
+            .flat_map(move |(_, features)| {
+                features
+                    .to_package_set()
+                    .links(DependencyDirection::Forward)
+                    .filter_map(move |link| {
+                        // Dev-dependencies are only built for initials.
+                        let dev = link.dev().is_present()
+                            && self.initials.contains_package_ix(link.from().package_ix());
+                        (link.normal().is_present() || link.build().is_present() || dev)
+                            .then_some((link, dev))
+                    })
+                    .collect::<Vec<_>>()
+            });
+        platform_links.chain(
+            self.build_dep_links()
+                .chain(self.proc_macro_links())
+                .map(|link| (link, false)),
+        )
+    }
+
+    /// Returns optional dependencies of built packages that are never activated by this
+    /// simulation.
+    ///
+    /// To find optional dependencies that are never activated by any of several simulations, use
+    /// [`DormantDependencies::new`](DormantDependencies::new).
+    pub fn dormant_deps(&self) -> DormantDependencies<'g> {
+        DormantDependencies::new(self.package_graph(), [self])
+    }
+}
+
+/// Cargo build simulations for a set of target platforms.
+///
+/// Returned by [`CargoSet::compute_matrix`](CargoSet::compute_matrix).
+#[derive(Clone, Debug)]
+pub struct CargoSetMatrix<'g> {
+    entries: Vec<(PlatformSpec, CargoSet<'g>)>,
+    common_target_features: FeatureSet<'g>,
+    common_host_features: FeatureSet<'g>,
+}
+
+assert_covariant!(CargoSetMatrix);
+
+impl<'g> CargoSetMatrix<'g> {
+    fn new(feature_graph: FeatureGraph<'g>, entries: Vec<(PlatformSpec, CargoSet<'g>)>) -> Self {
+        let common_features = |build_platform: BuildPlatform| {
+            let mut sets = entries
+                .iter()
+                .map(|(_, cargo_set)| cargo_set.platform_features(build_platform));
+            match sets.next() {
+                Some(first) => sets.fold(first.clone(), |acc, set| acc.intersection(set)),
+                None => feature_graph.resolve_none(),
+            }
+        };
+        let common_target_features = common_features(BuildPlatform::Target);
+        let common_host_features = common_features(BuildPlatform::Host);
+
+        Self {
+            entries,
+            common_target_features,
+            common_host_features,
+        }
+    }
+
+    /// Returns the number of target platforms in this matrix.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if this matrix has no target platforms.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the `CargoSet` for the target platform at this index, along with the platform.
+    ///
+    /// Indexes are in the same order as the target platforms passed into
+    /// [`CargoSet::compute_matrix`](CargoSet::compute_matrix).
+    pub fn get(&self, index: usize) -> Option<(&PlatformSpec, &CargoSet<'g>)> {
+        self.entries
+            .get(index)
+            .map(|(platform, cargo_set)| (platform, cargo_set))
+    }
+
+    /// Iterates over target platforms and their corresponding `CargoSet` instances.
+    pub fn iter<'a>(
+        &'a self,
+    ) -> impl ExactSizeIterator<Item = (&'a PlatformSpec, &'a CargoSet<'g>)> + 'a {
+        self.entries
+            .iter()
+            .map(|(platform, cargo_set)| (platform, cargo_set))
+    }
+
+    /// Returns the features enabled on the given build platform across every target platform.
+    ///
+    /// If the matrix is empty, this is the empty set.
+    pub fn common_features(&self, build_platform: BuildPlatform) -> &FeatureSet<'g> {
+        match build_platform {
+            BuildPlatform::Target => &self.common_target_features,
+            BuildPlatform::Host => &self.common_host_features,
+        }
+    }
+
+    /// Iterates over the features specific to each target platform.
+    ///
+    /// Each delta contains the features enabled for that platform, minus the
+    /// [common features](Self::common_features).
+    pub fn deltas<'a>(&'a self) -> impl ExactSizeIterator<Item = CargoSetDelta<'g>> + 'a {
+        self.entries
+            .iter()
+            .map(move |(platform, cargo_set)| CargoSetDelta {
+                platform: platform.clone(),
+                target_features: cargo_set
+                    .target_features()
+                    .difference(&self.common_target_features),
+                host_features: cargo_set
+                    .host_features()
+                    .difference(&self.common_host_features),
+            })
+    }
+}
+
+/// The features specific to a single target platform within a [`CargoSetMatrix`].
+#[derive(Clone, Debug)]
+pub struct CargoSetDelta<'g> {
+    platform: PlatformSpec,
+    target_features: FeatureSet<'g>,
+    host_features: FeatureSet<'g>,
+}
+
+assert_covariant!(CargoSetDelta);
+
+impl<'g> CargoSetDelta<'g> {
+    /// Returns the target platform this delta is for.
+    pub fn platform(&self) -> &PlatformSpec {
+        &self.platform
+    }
+
+    /// Returns the features enabled on the given build platform for this target platform, but not
+    /// across every target platform in the matrix.
+    pub fn added_features(&self, build_platform: BuildPlatform) -> &FeatureSet<'g> {
+        match build_platform {
+            BuildPlatform::Target => &self.target_features,
+            BuildPlatform::Host => &self.host_features,
+        }
+    }
+
+    /// Returns true if no features are specific to this target platform.
+    pub fn is_empty(&self) -> bool {
+        self.target_features.is_empty() && self.host_features.is_empty()
+    }
 }
 
 /// Either the target or the host platform.
@@ -0,0 +1,345 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::cargo::{cargo_home, CargoOptions},
+    platform::{Platform, TargetFeatures},
+    Error,
+};
+use camino::Utf8Path;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs};
+use target_spec::TargetSpec;
+
+/// Settings read from Cargo's [configuration
+/// files](https://doc.rust-lang.org/cargo/reference/config.html) that affect build simulations.
+///
+/// Cargo build simulations in `guppy` don't look at `.cargo/config.toml` by default. This struct
+/// can be used to opt into reading the settings that influence dependency resolution:
+///
+/// * `build.target`, which sets the default target platform.
+/// * `build.rustflags` and `target.<triple>.rustflags` / `target.<cfg>.rustflags`, from which
+///   `--cfg` flags are read. These flags are used when evaluating `cfg()` expressions in
+///   platform-specific dependencies.
+///
+/// Environment variables like `CARGO_BUILD_TARGET` and `RUSTFLAGS` are not consulted. Target
+/// features set through `-C target-feature` are not currently read.
+///
+/// Requires the `cargo-config` feature to be enabled.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CargoConfig {
+    build_targets: Vec<String>,
+    build_rustflags: Vec<String>,
+    // A map of target keys (triples or cfg expressions) to rustflags.
+    target_rustflags: BTreeMap<String, Vec<String>>,
+}
+
+impl CargoConfig {
+    /// Discovers and reads Cargo configuration files for the given directory.
+    ///
+    /// As with Cargo, this reads `.cargo/config.toml` (or `.cargo/config`) in `dir` and all of its
+    /// ancestors, followed by `$CARGO_HOME/config.toml`. Files in deeper directories take
+    /// precedence, and arrays are merged across files.
+    pub fn discover(dir: impl AsRef<Utf8Path>) -> Result<Self, Error> {
+        Self::discover_impl(dir.as_ref(), cargo_home().as_deref())
+    }
+
+    /// Discovers and reads Cargo configuration files for the given directory, with a custom
+    /// Cargo home directory.
+    ///
+    /// Pass in `None` to skip reading configuration from the Cargo home directory.
+    pub fn discover_with_cargo_home(
+        dir: impl AsRef<Utf8Path>,
+        cargo_home: Option<&Utf8Path>,
+    ) -> Result<Self, Error> {
+        Self::discover_impl(dir.as_ref(), cargo_home)
+    }
+
+    /// Parses a single Cargo configuration file.
+    ///
+    /// `path` is used for error messages.
+    pub fn parse(path: impl AsRef<Utf8Path>, contents: &str) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let config: ConfigToml = toml::from_str(contents)
+            .map_err(|err| Error::CargoConfigParseError(path.to_owned(), err))?;
+        Ok(Self {
+            build_targets: config
+                .build
+                .target
+                .map_or_else(Vec::new, StringOrVec::into_targets),
+            build_rustflags: config
+                .build
+                .rustflags
+                .map_or_else(Vec::new, StringOrVec::into_flags),
+            target_rustflags: config
+                .target
+                .into_iter()
+                .filter_map(|(key, target)| Some((key, target.rustflags?.into_flags())))
+                .collect(),
+        })
+    }
+
+    /// Merges in configuration with lower precedence than `self`.
+    ///
+    /// Values in `self` override those in `lower`, and arrays are concatenated with values in
+    /// `self` placed later.
+    pub fn merge_lower(&mut self, lower: CargoConfig) {
+        if self.build_targets.is_empty() {
+            self.build_targets = lower.build_targets;
+        }
+        let mut build_rustflags = lower.build_rustflags;
+        build_rustflags.append(&mut self.build_rustflags);
+        self.build_rustflags = build_rustflags;
+
+        for (key, mut flags) in lower.target_rustflags {
+            let existing = self.target_rustflags.entry(key).or_default();
+            flags.append(existing);
+            *existing = flags;
+        }
+    }
+
+    /// Returns the targets specified in `build.target`.
+    ///
+    /// This is usually zero or one target, but Cargo supports building for multiple targets at
+    /// once.
+    pub fn build_targets(&self) -> &[String] {
+        &self.build_targets
+    }
+
+    /// Returns the rustflags that Cargo would use for the given platform.
+    ///
+    /// As with Cargo, if any `target.<triple>.rustflags` or `target.<cfg>.rustflags` match the
+    /// platform, they are joined together and returned. Otherwise, `build.rustflags` is returned.
+    pub fn rustflags_for(&self, platform: &Platform) -> Vec<&str> {
+        let target_flags: Vec<_> = self
+            .target_rustflags
+            .iter()
+            .filter(|(key, _)| target_key_matches(key, platform))
+            .flat_map(|(_, flags)| flags.iter().map(|flag| flag.as_str()))
+            .collect();
+        if target_flags.is_empty() {
+            self.build_rustflags
+                .iter()
+                .map(|flag| flag.as_str())
+                .collect()
+        } else {
+            target_flags
+        }
+    }
+
+    /// Creates a `Platform` for the given triple, including any `--cfg` flags from rustflags.
+    pub fn platform_for(&self, triple: impl Into<String>) -> Result<Platform, Error> {
+        let triple = triple.into();
+        let mut platform =
+            Platform::new(triple.clone(), TargetFeatures::Unknown).map_err(|err| {
+                Error::TargetSpecError(
+                    format!("creating platform for Cargo config target '{}'", triple),
+                    err,
+                )
+            })?;
+        let cfg_flags: Vec<_> = cfg_flags(&self.rustflags_for(&platform))
+            .map(|flag| flag.to_owned())
+            .collect();
+        platform.add_flags(cfg_flags);
+        Ok(platform)
+    }
+
+    /// Returns platforms for every target in `build.target`, including any `--cfg` flags from
+    /// rustflags.
+    pub fn target_platforms(&self) -> Result<Vec<Platform>, Error> {
+        self.build_targets
+            .iter()
+            .map(|triple| self.platform_for(triple.clone()))
+            .collect()
+    }
+
+    /// Configures `CargoOptions` with the target platform from this configuration.
+    ///
+    /// If `build.target` specifies exactly one target, sets the target platform to it and returns
+    /// true. Otherwise, leaves `opts` unchanged and returns false. (For multiple targets, consider
+    /// passing [`target_platforms`](Self::target_platforms) into
+    /// [`CargoSet::compute_matrix`](crate::graph::cargo::CargoSet::compute_matrix).)
+    pub fn configure(&self, opts: &mut CargoOptions<'_>) -> Result<bool, Error> {
+        match self.build_targets.as_slice() {
+            [triple] => {
+                opts.set_target_platform(self.platform_for(triple.clone())?);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn discover_impl(dir: &Utf8Path, cargo_home: Option<&Utf8Path>) -> Result<Self, Error> {
+        let mut config = Self::default();
+        // Ancestors are returned deepest-first, i.e. in order of decreasing precedence.
+        for ancestor in dir.ancestors() {
+            if let Some(lower) = Self::read_dir_config(&ancestor.join(".cargo"))? {
+                config.merge_lower(lower);
+            }
+        }
+        if let Some(cargo_home) = cargo_home {
+            if let Some(lower) = Self::read_dir_config(cargo_home)? {
+                config.merge_lower(lower);
+            }
+        }
+        Ok(config)
+    }
+
+    fn read_dir_config(dir: &Utf8Path) -> Result<Option<Self>, Error> {
+        // Cargo prefers config.toml, but also reads the extension-less config.
+        for file_name in ["config.toml", "config"] {
+            let path = dir.join(file_name);
+            if path.is_file() {
+                let contents = fs::read_to_string(&path)
+                    .map_err(|err| Error::CargoConfigReadError(path.clone(), err))?;
+                return Self::parse(&path, &contents).map(Some);
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn target_key_matches(key: &str, platform: &Platform) -> bool {
+    if key.starts_with("cfg(") {
+        match TargetSpec::new(key.to_owned()) {
+            // Treat unknown results (e.g. due to target features) as not matching.
+            Ok(spec) => spec.eval(platform) == Some(true),
+            Err(_) => false,
+        }
+    } else {
+        key == platform.triple_str()
+    }
+}
+
+/// Returns the names passed in through `--cfg` flags that don't have a value.
+fn cfg_flags<'a>(rustflags: &'a [&'a str]) -> impl Iterator<Item = &'a str> + 'a {
+    let mut iter = rustflags.iter().copied();
+    std::iter::from_fn(move || loop {
+        let flag = iter.next()?;
+        let value = if flag == "--cfg" {
+            iter.next()?
+        } else if let Some(value) = flag.strip_prefix("--cfg=") {
+            value
+        } else {
+            continue;
+        };
+        // Key-value cfgs like `feature="foo"` aren't supported by target-spec flags.
+        if !value.contains('=') {
+            return Some(value);
+        }
+    })
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ConfigToml {
+    #[serde(default)]
+    build: BuildToml,
+    #[serde(default)]
+    target: BTreeMap<String, TargetToml>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct BuildToml {
+    target: Option<StringOrVec>,
+    rustflags: Option<StringOrVec>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TargetToml {
+    rustflags: Option<StringOrVec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StringOrVec {
+    String(String),
+    Vec(Vec<String>),
+}
+
+impl StringOrVec {
+    fn into_targets(self) -> Vec<String> {
+        match self {
+            StringOrVec::String(s) => vec![s],
+            StringOrVec::Vec(v) => v,
+        }
+    }
+
+    fn into_flags(self) -> Vec<String> {
+        match self {
+            // A string is split on whitespace.
+            StringOrVec::String(s) => s.split_whitespace().map(|flag| flag.to_owned()).collect(),
+            StringOrVec::Vec(v) => v,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_merge() {
+        let workspace = CargoConfig::parse(
+            "workspace/.cargo/config.toml",
+            r#"
+[build]
+target = "x86_64-unknown-linux-gnu"
+rustflags = "--cfg workspace_flag"
+
+[target.x86_64-unknown-linux-gnu]
+rustflags = ["--cfg", "linux_flag", "--cfg=feature=\"foo\""]
+
+[target.'cfg(windows)']
+rustflags = ["--cfg=windows_flag"]
+"#,
+        )
+        .expect("parsed successfully");
+        let mut config = CargoConfig::parse(
+            "workspace/subdir/.cargo/config.toml",
+            r#"
+[target.'cfg(unix)']
+rustflags = "--cfg unix_flag"
+"#,
+        )
+        .expect("parsed successfully");
+        config.merge_lower(workspace);
+
+        assert_eq!(config.build_targets(), ["x86_64-unknown-linux-gnu"]);
+
+        let linux = config
+            .platform_for("x86_64-unknown-linux-gnu")
+            .expect("known platform");
+        assert_eq!(
+            linux.flags().collect::<Vec<_>>(),
+            ["linux_flag", "unix_flag"],
+            "target-specific flags replace build.rustflags"
+        );
+
+        let windows = config
+            .platform_for("x86_64-pc-windows-msvc")
+            .expect("known platform");
+        assert_eq!(windows.flags().collect::<Vec<_>>(), ["windows_flag"]);
+
+        let wasm = config
+            .platform_for("wasm32-unknown-unknown")
+            .expect("known platform");
+        assert_eq!(
+            wasm.flags().collect::<Vec<_>>(),
+            ["workspace_flag"],
+            "build.rustflags used if no target-specific flags match"
+        );
+
+        let mut opts = CargoOptions::new();
+        assert!(config.configure(&mut opts).expect("configured"));
+    }
+
+    #[test]
+    fn parse_error() {
+        let err = CargoConfig::parse("config.toml", "[build\n").expect_err("invalid TOML");
+        assert!(matches!(err, Error::CargoConfigParseError(path, _) if path == "config.toml"));
+    }
+}
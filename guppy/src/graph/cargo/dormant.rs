@@ -0,0 +1,126 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::{
+        cargo::CargoSet, feature::FeatureId, DependencyDirection, PackageGraph, PackageLink,
+        PackageSet,
+    },
+    PackageId,
+};
+
+/// Optional dependencies that are never activated by a set of Cargo build simulations.
+///
+/// An optional dependency is *dormant* if the package that declares it is built, but the
+/// optional dependency's feature (`dep:name`) is never enabled on either the target or the host
+/// platform.
+///
+/// This can be computed for a single [`CargoSet`], or across several of them (for example, every
+/// platform and feature combination a workspace is built with). In the latter case, an optional
+/// dependency is dormant only if it is never activated by any of the simulations.
+///
+/// A common use for this is pruning a vendor directory: see
+/// [`prunable_packages`](Self::prunable_packages).
+#[derive(Clone, Debug)]
+pub struct DormantDependencies<'g> {
+    built: PackageSet<'g>,
+    links: Vec<PackageLink<'g>>,
+    prunable: PackageSet<'g>,
+}
+
+assert_covariant!(DormantDependencies);
+
+impl<'g> DormantDependencies<'g> {
+    /// Computes dormant optional dependencies across all the given Cargo build simulations.
+    ///
+    /// If `cargo_sets` is empty, no packages are considered built and there are no dormant
+    /// dependencies.
+    pub fn new<'a>(
+        package_graph: &'g PackageGraph,
+        cargo_sets: impl IntoIterator<Item = &'a CargoSet<'g>>,
+    ) -> Self
+    where
+        'g: 'a,
+    {
+        let mut features = package_graph.feature_graph().resolve_none();
+        for cargo_set in cargo_sets {
+            for (_, platform_features) in cargo_set.all_features() {
+                features = features.union(platform_features);
+            }
+        }
+        let built = features.to_package_set();
+
+        let links: Vec<_> = built
+            .packages(DependencyDirection::Forward)
+            .flat_map(|package| package.direct_links())
+            .filter(|link| {
+                let optional = !link.normal().status().optional_status().is_never()
+                    || !link.build().status().optional_status().is_never();
+                optional
+                    && !features
+                        .contains(FeatureId::optional_dependency(
+                            link.from().id(),
+                            link.dep_name(),
+                        ))
+                        .unwrap_or(false)
+            })
+            .collect();
+
+        // Packages reachable only through dormant dependencies are never built either.
+        // Dev-dependencies of those packages aren't followed, since they're never built as part
+        // of a dependent.
+        let prunable = package_graph
+            .query_forward(links.iter().map(|link| link.to().id()))
+            .expect("valid package IDs")
+            .resolve_with_fn(|_, link| !link.dev_only())
+            .difference(&built);
+
+        Self {
+            built,
+            links,
+            prunable,
+        }
+    }
+
+    /// Returns the packages built by at least one of the simulations.
+    pub fn built_packages(&self) -> &PackageSet<'g> {
+        &self.built
+    }
+
+    /// Returns the number of dormant optional dependencies.
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    /// Returns true if there are no dormant optional dependencies.
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// Iterates over links corresponding to dormant optional dependencies.
+    ///
+    /// The `from` end of each link is a built package, and the `to` end is the optional dependency
+    /// that is never activated. The `to` end may still be built through some other path: see
+    /// [`prunable_packages`](Self::prunable_packages) for packages that are never built at all.
+    pub fn links<'a>(&'a self) -> impl ExactSizeIterator<Item = PackageLink<'g>> + 'a {
+        self.links.iter().copied()
+    }
+
+    /// Iterates over dormant optional dependencies declared by the given package.
+    pub fn links_from<'a>(
+        &'a self,
+        package_id: &'a PackageId,
+    ) -> impl Iterator<Item = PackageLink<'g>> + 'a {
+        self.links()
+            .filter(move |link| link.from().id() == package_id)
+    }
+
+    /// Returns packages that are only reachable through dormant optional dependencies, and are
+    /// never built by any of the simulations.
+    ///
+    /// These packages can be safely removed from a vendor directory, as long as the set of
+    /// simulations covers every way the workspace is built.
+    pub fn prunable_packages(&self) -> &PackageSet<'g> {
+        &self.prunable
+    }
+}
@@ -6,7 +6,28 @@
 //! Cargo comes with a set of algorithms to figure out what packages or features are built. This
 //! module reimplements those algorithms using `guppy`'s data structures.
 
+mod activations;
 pub(super) mod build;
 mod cargo_api;
+#[cfg(feature = "cargo-config")]
+mod config;
+mod dormant;
 
+pub use activations::*;
 pub use cargo_api::*;
+#[cfg(feature = "cargo-config")]
+pub use config::*;
+pub use dormant::*;
+
+/// Returns Cargo's home directory: `$CARGO_HOME` if set, otherwise `~/.cargo`.
+#[cfg(any(feature = "cargo-config", feature = "metadata-cache"))]
+pub(crate) fn cargo_home() -> Option<camino::Utf8PathBuf> {
+    use std::env;
+
+    if let Some(cargo_home) = env::var_os("CARGO_HOME") {
+        return camino::Utf8PathBuf::from_path_buf(cargo_home.into()).ok();
+    }
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    let home = camino::Utf8PathBuf::from_path_buf(home.into()).ok()?;
+    Some(home.join(".cargo"))
+}
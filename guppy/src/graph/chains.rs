@@ -0,0 +1,131 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::{
+        cargo::{BuildPlatform, CargoSet},
+        DependencyDirection, PackageLink, PackageMetadata,
+    },
+    PackageId,
+};
+use ahash::AHashMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// A package in a set, along with the features it's built with on each platform.
+pub(crate) struct BuiltPackage<'g> {
+    pub(crate) metadata: PackageMetadata<'g>,
+    /// The features built on the target and host platforms, or `None` if the package isn't built
+    /// on that platform. Both are `None` for package sets, which don't track features.
+    pub(crate) target_features: Option<Vec<&'g str>>,
+    pub(crate) host_features: Option<Vec<&'g str>>,
+}
+
+impl<'g> BuiltPackage<'g> {
+    /// Creates a `BuiltPackage` without any build information, for package sets.
+    pub(crate) fn new(metadata: PackageMetadata<'g>) -> Self {
+        Self {
+            metadata,
+            target_features: None,
+            host_features: None,
+        }
+    }
+
+    /// Returns the packages built by a `CargoSet` on either platform, sorted by package ID.
+    pub(crate) fn from_cargo_set(cargo_set: &CargoSet<'g>) -> Vec<Self> {
+        let mut packages: BTreeMap<&'g PackageId, Self> = BTreeMap::new();
+        for (build_platform, features) in cargo_set.all_features() {
+            for feature_list in features.packages_with_features(DependencyDirection::Forward) {
+                let metadata = *feature_list.package();
+                let package = packages
+                    .entry(metadata.id())
+                    .or_insert_with(|| Self::new(metadata));
+                let named_features = Some(feature_list.named_features().collect());
+                match build_platform {
+                    BuildPlatform::Target => package.target_features = named_features,
+                    BuildPlatform::Host => package.host_features = named_features,
+                }
+            }
+        }
+        packages.into_values().collect()
+    }
+
+    /// Returns the platforms this package is built on, or an empty list for package sets.
+    #[cfg_attr(not(any(feature = "policy", feature = "rustsec")), allow(dead_code))]
+    pub(crate) fn build_platforms(&self) -> Vec<BuildPlatform> {
+        let mut build_platforms = vec![];
+        if self.target_features.is_some() {
+            build_platforms.push(BuildPlatform::Target);
+        }
+        if self.host_features.is_some() {
+            build_platforms.push(BuildPlatform::Host);
+        }
+        build_platforms
+    }
+}
+
+/// Explains why packages are included in a set, through the dependency chains that lead to them
+/// from a set of roots.
+#[cfg_attr(not(any(feature = "policy", feature = "rustsec")), allow(dead_code))]
+pub(crate) struct DependencyChains<'g> {
+    metadatas: AHashMap<&'g PackageId, PackageMetadata<'g>>,
+    roots: BTreeSet<&'g PackageId>,
+    reverse_deps: AHashMap<&'g PackageId, BTreeSet<&'g PackageId>>,
+}
+
+#[cfg_attr(not(any(feature = "policy", feature = "rustsec")), allow(dead_code))]
+impl<'g> DependencyChains<'g> {
+    pub(crate) fn new(
+        packages: impl IntoIterator<Item = PackageMetadata<'g>>,
+        roots: impl IntoIterator<Item = &'g PackageId>,
+        links: impl IntoIterator<Item = PackageLink<'g>>,
+    ) -> Self {
+        let metadatas = packages
+            .into_iter()
+            .map(|package| (package.id(), package))
+            .collect();
+        let mut reverse_deps: AHashMap<&'g PackageId, BTreeSet<&'g PackageId>> = AHashMap::new();
+        for link in links {
+            reverse_deps
+                .entry(link.to().id())
+                .or_default()
+                .insert(link.from().id());
+        }
+        Self {
+            metadatas,
+            roots: roots.into_iter().collect(),
+            reverse_deps,
+        }
+    }
+
+    /// Finds the shortest dependency chain from each root to `package_id`, sorted by root
+    /// package ID.
+    pub(crate) fn chains_to(&self, package_id: &'g PackageId) -> Vec<Vec<PackageMetadata<'g>>> {
+        // Breadth-first search over reverse dependencies, recording the next package on the path
+        // towards `package_id` for each package visited.
+        let mut next: AHashMap<&'g PackageId, Option<&'g PackageId>> = AHashMap::new();
+        next.insert(package_id, None);
+        let mut queue = VecDeque::from([package_id]);
+        while let Some(current) = queue.pop_front() {
+            for &dependent in self.reverse_deps.get(current).into_iter().flatten() {
+                if !next.contains_key(dependent) {
+                    next.insert(dependent, Some(current));
+                    queue.push_back(dependent);
+                }
+            }
+        }
+
+        self.roots
+            .iter()
+            .filter(|root| next.contains_key(*root))
+            .map(|&root| {
+                let mut chain = vec![];
+                let mut current = Some(root);
+                while let Some(package_id) = current {
+                    chain.push(self.metadatas[package_id]);
+                    current = next[package_id];
+                }
+                chain
+            })
+            .collect()
+    }
+}
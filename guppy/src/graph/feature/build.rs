@@ -296,7 +296,26 @@ impl FeatureGraphBuildState {
         }
     }
 
-    pub(super) fn add_dependency_edges(&mut self, link: PackageLink<'_>) {
+    /// Adds edges computed by [`Self::dependency_edges`] to the graph.
+    pub(super) fn add_dependency_edges(&mut self, edges: DependencyEdges, graph: &PackageGraph) {
+        let DependencyEdges {
+            required,
+            optional,
+            mut warnings,
+        } = edges;
+        self.warnings.append(&mut warnings);
+
+        let (from_node, to_nodes_edges) = required;
+        self.add_edges(from_node, to_nodes_edges, graph);
+        if let Some((from_node, to_nodes_edges)) = optional {
+            self.add_edges(from_node, to_nodes_edges, graph);
+        }
+    }
+
+    /// Computes the feature edges corresponding to a package link.
+    ///
+    /// This doesn't depend on the build state, so it can be called for several links in parallel.
+    pub(super) fn dependency_edges(link: PackageLink<'_>) -> DependencyEdges {
         let from = link.from();
 
         // Sometimes the same package is depended on separately in different sections like so:
@@ -343,21 +362,21 @@ impl FeatureGraphBuildState {
             .chain(iter::once((DependencyKind::Build, link.build())))
             .chain(iter::once((DependencyKind::Development, link.dev())));
 
+        let mut warnings = vec![];
         let mut required_req = FeatureReq::new(link);
         let mut optional_req = FeatureReq::new(link);
         for (kind, dependency_req) in unified_metadata {
-            required_req.add_features(kind, &dependency_req.inner.required, &mut self.warnings);
-            optional_req.add_features(kind, &dependency_req.inner.optional, &mut self.warnings);
+            required_req.add_features(kind, &dependency_req.inner.required, &mut warnings);
+            optional_req.add_features(kind, &dependency_req.inner.optional, &mut warnings);
         }
 
-        // Add the required edges (base -> features).
-        self.add_edges(
+        // The required edges (base -> features).
+        let required = (
             FeatureNode::base(from.package_ix()),
-            required_req.finish(),
-            link.from().graph(),
+            required_req.finish().collect(),
         );
 
-        if !optional_req.is_empty() {
+        let optional = if !optional_req.is_empty() {
             // This means that there is at least one instance of this dependency with optional =
             // true. The dep name should have been added as an optional dependency node to the
             // package metadata.
@@ -372,7 +391,15 @@ impl FeatureGraphBuildState {
                     );
                     }),
             );
-            self.add_edges(from_node, optional_req.finish(), link.from().graph());
+            Some((from_node, optional_req.finish().collect()))
+        } else {
+            None
+        };
+
+        DependencyEdges {
+            required,
+            optional,
+            warnings,
         }
     }
 
@@ -503,6 +530,17 @@ impl FeatureGraphBuildState {
     }
 }
 
+/// Feature edges corresponding to a single package link, computed by
+/// [`FeatureGraphBuildState::dependency_edges`].
+#[derive(Debug)]
+pub(super) struct DependencyEdges {
+    // Edges from the base feature of the `from` package.
+    required: (FeatureNode, Vec<(FeatureNode, FeatureEdge)>),
+    // Edges from the optional dependency feature of the `from` package, if any.
+    optional: Option<(FeatureNode, Vec<(FeatureNode, FeatureEdge)>)>,
+    warnings: Vec<FeatureGraphWarning>,
+}
+
 #[derive(Debug)]
 struct FeatureReq<'g> {
     link: PackageLink<'g>,
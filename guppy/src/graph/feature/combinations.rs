@@ -0,0 +1,191 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Iteration over combinations of features for a package, similar to `cargo hack`.
+
+use crate::{
+    graph::{
+        cargo::{CargoOptions, CargoSet},
+        feature::{FeatureGraph, FeatureId, FeatureLabel, FeatureSet},
+        DependencyDirection, PackageMetadata,
+    },
+    Error, PackageId,
+};
+use itertools::{Combinations, Itertools};
+use std::{collections::HashSet, iter, ops::Range};
+
+/// The strategy used by [`FeatureCombinations`] to generate combinations of features.
+///
+/// All combinations are built with default features disabled, i.e. as if `--no-default-features`
+/// were passed in. The `default` feature is treated like any other named feature.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum FeatureCombinationKind {
+    /// No features, followed by each named feature on its own.
+    ///
+    /// This is similar to `cargo hack --each-feature`.
+    EachFeature,
+
+    /// Every subset of named features, in order of increasing size.
+    ///
+    /// This is similar to `cargo hack --feature-powerset`.
+    Powerset {
+        /// The maximum number of features in each combination, or `None` for no limit.
+        ///
+        /// This is similar to `cargo hack --depth`.
+        max_depth: Option<usize>,
+    },
+}
+
+impl FeatureCombinationKind {
+    fn max_depth(self, feature_count: usize) -> usize {
+        match self {
+            FeatureCombinationKind::EachFeature => 1,
+            FeatureCombinationKind::Powerset { max_depth } => max_depth.unwrap_or(feature_count),
+        }
+        .min(feature_count)
+    }
+}
+
+/// An iterator over combinations of features for a package.
+///
+/// By default, combinations that enable exactly the same features as an earlier combination are
+/// skipped. For example, if feature `a` enables feature `b`, then `[a, b]` resolves identically to
+/// `[a]` and is not returned. Use [`set_prune`](Self::set_prune) to change this.
+///
+/// Constructed through `FeatureGraph::feature_combinations`.
+#[derive(Clone, Debug)]
+pub struct FeatureCombinations<'g> {
+    feature_graph: FeatureGraph<'g>,
+    package: PackageMetadata<'g>,
+    features: Vec<&'g str>,
+    max_depth: usize,
+    current_depth: usize,
+    current: Combinations<Range<usize>>,
+    prune: bool,
+    seen: HashSet<Vec<FeatureLabel<'g>>>,
+}
+
+assert_covariant!(FeatureCombinations);
+
+impl<'g> FeatureCombinations<'g> {
+    pub(super) fn new(
+        feature_graph: FeatureGraph<'g>,
+        package_id: &PackageId,
+        kind: FeatureCombinationKind,
+    ) -> Result<Self, Error> {
+        let package = feature_graph.package_graph.metadata(package_id)?;
+        let features: Vec<_> = feature_graph
+            .all_features_for(package_id)?
+            .named_features()
+            .collect();
+        let max_depth = kind.max_depth(features.len());
+
+        Ok(Self {
+            feature_graph,
+            package,
+            current: (0..features.len()).combinations(0),
+            features,
+            max_depth,
+            current_depth: 0,
+            prune: true,
+            seen: HashSet::new(),
+        })
+    }
+
+    /// Sets whether combinations that resolve identically to an earlier one are skipped.
+    ///
+    /// The default is true.
+    pub fn set_prune(&mut self, prune: bool) -> &mut Self {
+        self.prune = prune;
+        self
+    }
+
+    /// Returns the package that combinations are generated for.
+    pub fn package(&self) -> PackageMetadata<'g> {
+        self.package
+    }
+
+    fn next_indexes(&mut self) -> Option<Vec<usize>> {
+        loop {
+            if let Some(indexes) = self.current.next() {
+                return Some(indexes);
+            }
+            if self.current_depth >= self.max_depth {
+                return None;
+            }
+            self.current_depth += 1;
+            self.current = (0..self.features.len()).combinations(self.current_depth);
+        }
+    }
+}
+
+impl<'g> Iterator for FeatureCombinations<'g> {
+    type Item = FeatureCombination<'g>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let package_id = self.package.id();
+        loop {
+            let indexes = self.next_indexes()?;
+            let features: Vec<_> = indexes.into_iter().map(|ix| self.features[ix]).collect();
+            let feature_ids = iter::once(FeatureId::base(package_id)).chain(
+                features
+                    .iter()
+                    .map(|feature_name| FeatureId::named(package_id, feature_name)),
+            );
+            let initials = self
+                .feature_graph
+                .resolve_ids(feature_ids)
+                .expect("valid feature IDs");
+
+            if self.prune {
+                // Two combinations resolve identically if and only if they enable the same set of
+                // features within this package. Feature links within a package are always
+                // followed, so this doesn't need to look at any other packages.
+                let enabled = initials
+                    .to_feature_query(DependencyDirection::Forward)
+                    .resolve_with_fn(|_, _| false)
+                    .features_for(package_id)
+                    .expect("valid package ID")
+                    .expect("package is present")
+                    .into_labels();
+                if !self.seen.insert(enabled) {
+                    continue;
+                }
+            }
+
+            return Some(FeatureCombination { features, initials });
+        }
+    }
+}
+
+/// A single combination of features returned by [`FeatureCombinations`].
+#[derive(Clone, Debug)]
+pub struct FeatureCombination<'g> {
+    features: Vec<&'g str>,
+    initials: FeatureSet<'g>,
+}
+
+assert_covariant!(FeatureCombination);
+
+impl<'g> FeatureCombination<'g> {
+    /// Returns the named features in this combination.
+    pub fn features(&self) -> &[&'g str] {
+        &self.features
+    }
+
+    /// Returns the feature set for this combination.
+    ///
+    /// This contains the base feature for the package, plus the named features in this
+    /// combination. It does not contain transitive dependencies.
+    pub fn feature_set(&self) -> &FeatureSet<'g> {
+        &self.initials
+    }
+
+    /// Simulates a Cargo build of this combination, with the given options.
+    ///
+    /// This is a shortcut for `feature_set().clone().into_cargo_set(opts)`.
+    pub fn to_cargo_set(&self, opts: &CargoOptions<'_>) -> Result<CargoSet<'g>, Error> {
+        self.initials.clone().into_cargo_set(opts)
+    }
+}
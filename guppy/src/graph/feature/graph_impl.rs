@@ -7,9 +7,10 @@ use crate::{
     graph::{
         feature::{
             build::{FeatureGraphBuildState, FeaturePetgraph},
-            Cycles, FeatureFilter, FeatureList, WeakDependencies, WeakIndex,
+            Cycles, FeatureCombinationKind, FeatureCombinations, FeatureFilter, FeatureImpact,
+            FeatureList, WeakDependencies, WeakIndex,
         },
-        DependencyDirection, FeatureIndexInPackage, FeatureIx, PackageGraph, PackageIx,
+        DependencyDirection, FeatureIndexInPackage, FeatureIx, HeapSize, PackageGraph, PackageIx,
         PackageLink, PackageMetadata,
     },
     petgraph_support::{scc::Sccs, topo::TopoWithCycles},
@@ -20,10 +21,11 @@ use ahash::AHashMap;
 use once_cell::sync::OnceCell;
 use petgraph::{
     algo::has_path_connecting,
+    graph::{Edge, Node},
     prelude::*,
     visit::{EdgeFiltered, IntoNodeReferences},
 };
-use std::{fmt, iter, iter::FromIterator};
+use std::{fmt, iter, iter::FromIterator, mem};
 
 // Some general notes about feature graphs:
 //
@@ -53,6 +55,12 @@ impl PackageGraph {
     ///
     /// The feature graph is constructed the first time this method is called. The graph is cached
     /// so that repeated calls to this method are cheap.
+    ///
+    /// Package-level operations, such as [`PackageQuery`](crate::graph::PackageQuery) and
+    /// [`PackageSet`](crate::graph::PackageSet) queries, never construct the feature graph. It is
+    /// only constructed by feature-level operations like
+    /// [`PackageSet::to_feature_set`](crate::graph::PackageSet::to_feature_set) and Cargo build
+    /// simulations, so tools that only perform package-level queries don't pay its cost.
     pub fn feature_graph(&self) -> FeatureGraph {
         let inner = self.get_feature_graph();
         FeatureGraph {
@@ -199,6 +207,29 @@ impl<'g> FeatureGraph<'g> {
         Cycles::new(*self)
     }
 
+    /// Returns an iterator over combinations of features for this package, similar to
+    /// `cargo hack`.
+    ///
+    /// Returns an error if the package ID was unknown.
+    ///
+    /// For more information, see the documentation for `FeatureCombinations`.
+    pub fn feature_combinations(
+        &self,
+        package_id: &PackageId,
+        kind: FeatureCombinationKind,
+    ) -> Result<FeatureCombinations<'g>, Error> {
+        FeatureCombinations::new(*self, package_id, kind)
+    }
+
+    /// Returns an analysis of the additional packages each named feature of this package pulls in.
+    ///
+    /// Returns an error if the package ID was unknown.
+    ///
+    /// For more information, see the documentation for `FeatureImpact`.
+    pub fn feature_impact(&self, package_id: &PackageId) -> Result<FeatureImpact<'g>, Error> {
+        FeatureImpact::new(*self, package_id)
+    }
+
     // ---
     // Helper methods
     // ---
@@ -691,15 +722,54 @@ impl FeatureGraphImpl {
             build_state.add_named_feature_edges(metadata);
         }
 
-        for link in package_graph
-            .resolve_all()
-            .links(DependencyDirection::Reverse)
-        {
-            build_state.add_dependency_edges(link);
+        // Computing the edges for each link is independent of the others, so it can be done in
+        // parallel. The edges are then added in order, so the resulting graph is the same either
+        // way.
+        let all_packages = package_graph.resolve_all();
+        let links = all_packages.links(DependencyDirection::Reverse);
+        #[cfg(feature = "rayon1")]
+        let dependency_edges: Vec<_> = {
+            use rayon::prelude::*;
+
+            links
+                .collect::<Vec<_>>()
+                .into_par_iter()
+                .map(FeatureGraphBuildState::dependency_edges)
+                .collect()
+        };
+        #[cfg(not(feature = "rayon1"))]
+        let dependency_edges = links.map(FeatureGraphBuildState::dependency_edges);
+
+        for edges in dependency_edges {
+            build_state.add_dependency_edges(edges, package_graph);
         }
 
         build_state.build()
     }
+
+    /// Returns an estimate of the memory used by this feature graph, in bytes.
+    pub(in crate::graph) fn memory_usage(&self) -> usize {
+        let edge_data = self
+            .graph
+            .edge_weights()
+            .map(|edge| match edge {
+                FeatureEdge::FeatureToBase | FeatureEdge::NamedFeature => 0,
+                FeatureEdge::DependenciesSection(link)
+                | FeatureEdge::NamedFeatureDepColon(link)
+                | FeatureEdge::NamedFeatureWithSlash { link, .. } => {
+                    link.normal.heap_size() + link.build.heap_size() + link.dev.heap_size()
+                }
+            })
+            .sum::<usize>();
+
+        self.graph.node_count() * mem::size_of::<Node<FeatureNode, FeatureIx>>()
+            + self.graph.edge_count() * mem::size_of::<Edge<FeatureEdge, FeatureIx>>()
+            + edge_data
+            + self.base_ixs.len() * mem::size_of::<NodeIndex<FeatureIx>>()
+            + self.map.len() * mem::size_of::<(FeatureNode, FeatureMetadataImpl)>()
+            + self.warnings.len() * mem::size_of::<FeatureGraphWarning>()
+            + self.weak.memory_usage()
+    }
 }
 
 /// A feature dependency that is conditionally activated.
@@ -884,7 +954,7 @@ impl FeatureNode {
     pub(in crate::graph) fn optional_dep(package_ix: NodeIndex<PackageIx>, dep_idx: usize) -> Self {
         Self {
             package_ix,
-            feature_idx: FeatureIndexInPackage::OptionalDependency(dep_idx),
+            feature_idx: FeatureIndexInPackage::optional_dep(dep_idx),
         }
     }
 
@@ -894,7 +964,7 @@ impl FeatureNode {
     ) -> Self {
         Self {
             package_ix,
-            feature_idx: FeatureIndexInPackage::Named(named_idx),
+            feature_idx: FeatureIndexInPackage::named(named_idx),
         }
     }
 
@@ -0,0 +1,101 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Analysis of the packages pulled in by individual features.
+
+use crate::{
+    graph::{
+        feature::{FeatureGraph, FeatureId},
+        PackageMetadata, PackageSet,
+    },
+    Error, PackageId,
+};
+
+/// For a given package, the additional packages each of its named features pulls in.
+///
+/// Each feature is compared against a baseline: the package built with no features enabled (i.e.
+/// with `--no-default-features`). Dependency resolution follows normal and build dependencies
+/// across all platforms -- dev-dependencies are not followed, since they are never built for
+/// consumers of the package.
+///
+/// This can be used to document how "expensive" each feature is, or to set dependency budgets for
+/// features.
+///
+/// Constructed through `FeatureGraph::feature_impact`.
+#[derive(Clone, Debug)]
+pub struct FeatureImpact<'g> {
+    package: PackageMetadata<'g>,
+    baseline: PackageSet<'g>,
+    features: Vec<(&'g str, PackageSet<'g>)>,
+}
+
+assert_covariant!(FeatureImpact);
+
+impl<'g> FeatureImpact<'g> {
+    pub(super) fn new(
+        feature_graph: FeatureGraph<'g>,
+        package_id: &PackageId,
+    ) -> Result<Self, Error> {
+        let package = feature_graph.package_graph.metadata(package_id)?;
+        let package_id = package.id();
+
+        let resolve = |feature_ids: &[FeatureId<'g>]| {
+            feature_graph
+                .query_forward(feature_ids.iter().copied())
+                .expect("valid feature IDs")
+                .resolve_with_fn(|_, link| !link.dev_only())
+                .to_package_set()
+        };
+
+        let base_id = FeatureId::base(package_id);
+        let baseline = resolve(&[base_id]);
+
+        // The base feature is resolved along with each named feature: this is required for weak
+        // dependencies (`dep?/feature`) to be evaluated correctly.
+        let features = feature_graph
+            .all_features_for(package_id)?
+            .named_features()
+            .map(|feature_name| {
+                let packages = resolve(&[base_id, FeatureId::named(package_id, feature_name)]);
+                (feature_name, packages.difference(&baseline))
+            })
+            .collect();
+
+        Ok(Self {
+            package,
+            baseline,
+            features,
+        })
+    }
+
+    /// Returns the package this analysis is for.
+    pub fn package(&self) -> PackageMetadata<'g> {
+        self.package
+    }
+
+    /// Returns the packages built with no features enabled, including the package itself.
+    pub fn baseline(&self) -> &PackageSet<'g> {
+        &self.baseline
+    }
+
+    /// Returns the additional packages pulled in by the given named feature, relative to the
+    /// baseline.
+    ///
+    /// Returns `None` if the feature name is unknown.
+    pub fn additional_packages(&self, feature_name: &str) -> Option<&PackageSet<'g>> {
+        self.features
+            .iter()
+            .find(|(name, _)| *name == feature_name)
+            .map(|(_, packages)| packages)
+    }
+
+    /// Iterates over named features and the additional packages each of them pulls in, relative
+    /// to the baseline.
+    ///
+    /// Features are returned in the order they're stored in the feature graph.
+    pub fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'g str, &'a PackageSet<'g>)> + 'a {
+        self.features
+            .iter()
+            .map(|(name, packages)| (*name, packages))
+    }
+}
@@ -8,9 +8,11 @@
 //! number of extra dependencies. This module allows for those subgraphs to be filtered out.
 
 mod build;
+mod combinations;
 mod cycles;
 pub mod feature_list;
 mod graph_impl;
+mod impact;
 #[cfg(feature = "proptest1")]
 mod proptest_helpers;
 mod query;
@@ -18,9 +20,11 @@ mod resolve;
 mod weak;
 
 use build::*;
+pub use combinations::*;
 pub use cycles::*;
 pub use feature_list::FeatureList;
 pub use graph_impl::*;
+pub use impact::*;
 pub use query::*;
 pub use resolve::*;
 pub use weak::*;
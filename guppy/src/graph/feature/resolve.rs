@@ -138,6 +138,20 @@ impl<'g> FeatureSet<'g> {
         }
     }
 
+    pub(in crate::graph) fn from_core(
+        graph: FeatureGraph<'g>,
+        core: ResolveCore<FeatureGraphSpec>,
+    ) -> Self {
+        Self {
+            graph: DebugIgnore(graph),
+            core,
+        }
+    }
+
+    pub(in crate::graph) fn into_parts(self) -> (FeatureGraph<'g>, ResolveCore<FeatureGraphSpec>) {
+        (self.graph.0, self.core)
+    }
+
     /// Returns the `FeatureGraph` that this feature set was computed against.
     pub fn graph(&self) -> &FeatureGraph<'g> {
         &self.graph.0
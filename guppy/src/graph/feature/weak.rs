@@ -11,6 +11,7 @@ use indexmap::IndexSet;
 use itertools::Either;
 use petgraph::graph::EdgeIndex;
 use smallvec::SmallVec;
+use std::mem;
 
 /// Data structure that tracks pairs of package indexes that form weak dependencies.
 #[derive(Clone, Debug)]
@@ -29,6 +30,11 @@ impl WeakDependencies {
         WeakIndex(self.ixs.insert_full(edge_ix).0)
     }
 
+    pub(super) fn memory_usage(&self) -> usize {
+        // IndexSet stores a hash alongside each entry.
+        self.ixs.len() * mem::size_of::<(u64, EdgeIndex<PackageIx>)>()
+    }
+
     pub(super) fn get(&self, edge_ix: EdgeIndex<PackageIx>) -> Option<WeakIndex> {
         self.ixs.get_index_of(&edge_ix).map(WeakIndex)
     }
@@ -0,0 +1,288 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::{
+        BuildTargetKindImpl, DepRequiredOrOptional, DependencyReqImpl, OwnedBuildTargetId,
+        PackageGraph, PackageLinkImpl, PackageMetadataImpl, PackageSourceImpl,
+    },
+    platform::PlatformStatusImpl,
+    PackageId,
+};
+use petgraph::visit::EdgeRef;
+use std::{fmt, hash::Hasher};
+use twox_hash::XxHash64;
+
+/// Options for [`PackageGraph::fingerprint_with_options`].
+#[derive(Clone, Debug)]
+pub struct FingerprintOptions {
+    include_workspace_location: bool,
+}
+
+impl FingerprintOptions {
+    /// Creates a new `FingerprintOptions` with the default settings.
+    ///
+    /// By default, the location of the workspace on disk is included in the fingerprint.
+    pub fn new() -> Self {
+        Self {
+            include_workspace_location: true,
+        }
+    }
+
+    /// Sets whether the location of the workspace on disk is included in the fingerprint.
+    ///
+    /// The package IDs of workspace members and path dependencies contain absolute paths. If this
+    /// is set to false, such packages are instead identified by their name, version, and path
+    /// relative to the workspace root. This means that two checkouts of the same workspace at
+    /// different locations have the same fingerprint.
+    ///
+    /// The default is true.
+    pub fn set_include_workspace_location(&mut self, include: bool) -> &mut Self {
+        self.include_workspace_location = include;
+        self
+    }
+}
+
+impl Default for FingerprintOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A stable hash of a [`PackageGraph`], suitable for use as a cache key.
+///
+/// A fingerprint covers package IDs, names, versions, sources, features, build targets, and
+/// dependency edges, along with the platforms and features they're enabled for. Unlike a hash of
+/// `Cargo.lock`, it also changes if manifests change in ways that don't affect the lockfile, like
+/// adding a feature.
+///
+/// Fingerprints are stable across runs and platforms, but may change across `guppy` versions.
+///
+/// Returned by [`PackageGraph::fingerprint`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct GraphFingerprint(u64);
+
+impl GraphFingerprint {
+    /// Returns the fingerprint as an integer.
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Formats the fingerprint as a 16-character hexadecimal string.
+impl fmt::Display for GraphFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl PackageGraph {
+    /// Returns a stable fingerprint of this graph, with the default options.
+    ///
+    /// For more about fingerprints, see [`GraphFingerprint`].
+    pub fn fingerprint(&self) -> GraphFingerprint {
+        self.fingerprint_with_options(&FingerprintOptions::new())
+    }
+
+    /// Returns a stable fingerprint of this graph, with the given options.
+    pub fn fingerprint_with_options(&self, opts: &FingerprintOptions) -> GraphFingerprint {
+        // Compute an identity for every package, indexed by package ix.
+        let identities: Vec<String> = self
+            .dep_graph
+            .node_weights()
+            .map(|package_id| {
+                let metadata = &self.data.packages[package_id];
+                package_identity(package_id, metadata, opts)
+            })
+            .collect();
+
+        // Hash packages and edges in a fixed order, independent of the order in the metadata.
+        let mut package_ixs: Vec<_> = self.dep_graph.node_indices().collect();
+        package_ixs.sort_unstable_by(|a, b| identities[a.index()].cmp(&identities[b.index()]));
+
+        let mut hasher = FingerprintHasher::default();
+        hasher.write_len(package_ixs.len());
+        for package_ix in package_ixs {
+            hasher.write_str(&identities[package_ix.index()]);
+            let package_id = &self.dep_graph[package_ix];
+            hasher.write_package(&self.data.packages[package_id], opts);
+
+            let mut links: Vec<_> = self
+                .dep_graph
+                .edges(package_ix)
+                .map(|edge| (&identities[edge.target().index()], edge.weight()))
+                .collect();
+            links.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            hasher.write_len(links.len());
+            for (to_identity, link) in links {
+                hasher.write_str(to_identity);
+                hasher.write_link(link);
+            }
+        }
+
+        GraphFingerprint(hasher.0.finish())
+    }
+}
+
+fn package_identity(
+    package_id: &PackageId,
+    metadata: &PackageMetadataImpl,
+    opts: &FingerprintOptions,
+) -> String {
+    match &metadata.source {
+        PackageSourceImpl::Workspace(path) | PackageSourceImpl::Path(path)
+            if !opts.include_workspace_location =>
+        {
+            // Paths stored in the source are relative to the workspace root.
+            format!("{} {} (path {})", metadata.name, metadata.version, path)
+        }
+        _ => package_id.repr().to_owned(),
+    }
+}
+
+/// A hasher that writes values in an unambiguous, platform-independent way.
+#[derive(Default)]
+struct FingerprintHasher(XxHash64);
+
+impl FingerprintHasher {
+    fn write_len(&mut self, len: usize) {
+        self.0.write(&(len as u64).to_le_bytes());
+    }
+
+    fn write_bool(&mut self, b: bool) {
+        self.0.write(&[b as u8]);
+    }
+
+    fn write_str(&mut self, s: &str) {
+        self.write_len(s.len());
+        self.0.write(s.as_bytes());
+    }
+
+    fn write_display(&mut self, value: impl fmt::Display) {
+        self.write_str(&value.to_string());
+    }
+
+    fn write_package(&mut self, metadata: &PackageMetadataImpl, opts: &FingerprintOptions) {
+        self.write_str(&metadata.name);
+        self.write_display(&metadata.version);
+        match &metadata.source {
+            PackageSourceImpl::Workspace(path) => {
+                self.write_str("workspace");
+                self.write_str(path.as_str());
+            }
+            PackageSourceImpl::Path(path) => {
+                self.write_str("path");
+                self.write_str(path.as_str());
+            }
+            PackageSourceImpl::CratesIo => self.write_str("crates-io"),
+            PackageSourceImpl::External(source) => {
+                self.write_str("external");
+                self.write_str(source);
+            }
+        }
+
+        // Features are stored in the order they're listed in the manifest, so sort them.
+        let mut named_features: Vec<_> = metadata.named_features.iter().collect();
+        named_features.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        self.write_len(named_features.len());
+        for (name, deps) in named_features {
+            self.write_str(name);
+            let mut deps: Vec<_> = deps.iter().map(|dep| dep.to_string()).collect();
+            deps.sort_unstable();
+            self.write_len(deps.len());
+            for dep in deps {
+                self.write_str(&dep);
+            }
+        }
+
+        let mut optional_deps: Vec<_> = metadata.optional_deps.iter().collect();
+        optional_deps.sort_unstable();
+        self.write_len(optional_deps.len());
+        for dep in optional_deps {
+            self.write_str(dep);
+        }
+
+        // Build targets are stored in a BTreeMap, so they're already sorted. Paths to build
+        // targets are absolute, so only include them if the workspace location is included.
+        self.write_len(metadata.build_targets.len());
+        for (id, target) in &metadata.build_targets {
+            match id {
+                OwnedBuildTargetId::Library => self.write_str("lib"),
+                OwnedBuildTargetId::BuildScript => self.write_str("build-script"),
+                OwnedBuildTargetId::Binary(name) => {
+                    self.write_str("bin");
+                    self.write_str(name);
+                }
+                OwnedBuildTargetId::Example(name) => {
+                    self.write_str("example");
+                    self.write_str(name);
+                }
+                OwnedBuildTargetId::Test(name) => {
+                    self.write_str("test");
+                    self.write_str(name);
+                }
+                OwnedBuildTargetId::Benchmark(name) => {
+                    self.write_str("bench");
+                    self.write_str(name);
+                }
+            }
+            match &target.kind {
+                BuildTargetKindImpl::LibraryOrExample(crate_types) => {
+                    self.write_str("lib-or-example");
+                    self.write_len(crate_types.len());
+                    for crate_type in crate_types.iter() {
+                        self.write_str(crate_type);
+                    }
+                }
+                BuildTargetKindImpl::ProcMacro => self.write_str("proc-macro"),
+                BuildTargetKindImpl::Binary => self.write_str("binary"),
+            }
+            self.write_len(target.required_features.len());
+            for feature in &target.required_features {
+                self.write_str(feature);
+            }
+            if opts.include_workspace_location {
+                self.write_str(target.path.as_str());
+            }
+        }
+    }
+
+    fn write_link(&mut self, link: &PackageLinkImpl) {
+        self.write_str(&link.dep_name);
+        self.write_str(&link.resolved_name);
+        self.write_display(&link.version_req);
+        for req in [&link.normal, &link.build, &link.dev] {
+            self.write_req(req);
+        }
+    }
+
+    fn write_req(&mut self, req: &DependencyReqImpl) {
+        self.write_required_or_optional(&req.required);
+        self.write_required_or_optional(&req.optional);
+    }
+
+    fn write_required_or_optional(&mut self, req: &DepRequiredOrOptional) {
+        self.write_status(&req.build_if);
+        self.write_status(&req.default_features_if);
+        self.write_status(&req.no_default_features_if);
+        // feature_targets is a BTreeMap, so it's already sorted.
+        self.write_len(req.feature_targets.len());
+        for (feature, status) in &req.feature_targets {
+            self.write_str(feature);
+            self.write_status(status);
+        }
+    }
+
+    fn write_status(&mut self, status: &PlatformStatusImpl) {
+        match status {
+            PlatformStatusImpl::Always => self.write_bool(true),
+            PlatformStatusImpl::Specs(specs) => {
+                self.write_bool(false);
+                self.write_len(specs.len());
+                for spec in specs {
+                    self.write_display(spec);
+                }
+            }
+        }
+    }
+}
@@ -6,7 +6,7 @@ use crate::{
         cargo_version_matches,
         feature::{FeatureGraphImpl, FeatureId, FeatureLabel, FeatureNode},
         BuildTarget, BuildTargetId, BuildTargetImpl, BuildTargetKind, Cycles, DependencyDirection,
-        OwnedBuildTargetId, PackageIx, PackageQuery, PackageSet,
+        MemoryUsage, OwnedBuildTargetId, PackageIx, PackageQuery, PackageSet,
     },
     petgraph_support::{scc::Sccs, topo::TopoWithCycles, IxBitSet},
     platform::{EnabledTernary, PlatformSpec, PlatformStatus, PlatformStatusImpl},
@@ -29,6 +29,7 @@ use std::{
     collections::{BTreeMap, HashSet},
     fmt, iter,
     iter::FromIterator,
+    sync::Arc,
 };
 
 use super::feature::{FeatureFilter, FeatureSet};
@@ -275,6 +276,14 @@ impl PackageGraph {
         self.dep_graph.edge_count()
     }
 
+    /// Returns an estimate of the memory used by this graph.
+    ///
+    /// This is a diagnostic intended for tools that hold many graphs at once. The feature graph
+    /// is only included if it has been computed.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::new(self)
+    }
+
     /// Creates a new cache for `depends_on` queries.
     ///
     /// The cache is optional but can speed up some queries.
@@ -1077,12 +1086,12 @@ impl<'g> PackageMetadata<'g> {
                 .inner
                 .optional_deps
                 .get_index_of(dep_name)
-                .map(FeatureIndexInPackage::OptionalDependency),
+                .map(FeatureIndexInPackage::optional_dep),
             FeatureLabel::Named(feature_name) => self
                 .inner
                 .named_features
                 .get_index_of(feature_name)
-                .map(FeatureIndexInPackage::Named),
+                .map(FeatureIndexInPackage::named),
         }
     }
 
@@ -1092,14 +1101,14 @@ impl<'g> PackageMetadata<'g> {
             FeatureIndexInPackage::OptionalDependency(idx) => FeatureLabel::OptionalDependency(
                 self.inner
                     .optional_deps
-                    .get_index(idx)
+                    .get_index(idx as usize)
                     .expect("feature idx in optional_deps should be valid")
                     .as_ref(),
             ),
             FeatureIndexInPackage::Named(idx) => FeatureLabel::Named(
                 self.inner
                     .named_features
-                    .get_index(idx)
+                    .get_index(idx as usize)
                     .expect("feature idx in optional_deps should be valid")
                     .0
                     .as_ref(),
@@ -1135,7 +1144,7 @@ impl<'g> PackageMetadata<'g> {
             .enumerate()
             .map(|(idx, (feature, deps))| {
                 (
-                    FeatureIndexInPackage::Named(idx),
+                    FeatureIndexInPackage::named(idx),
                     feature.as_ref(),
                     deps.as_slice(),
                 )
@@ -1151,20 +1160,28 @@ impl<'g> PackageMetadata<'g> {
             // IndexMap is documented to use indexes 0..n without holes, so this enumerate()
             // is correct.
             .enumerate()
-            .map(|(idx, dep_name)| {
-                (
-                    FeatureIndexInPackage::OptionalDependency(idx),
-                    dep_name.as_ref(),
-                )
-            })
+            .map(|(idx, dep_name)| (FeatureIndexInPackage::optional_dep(idx), dep_name.as_ref()))
     }
 }
 
+// Indexes are stored as u32 to keep feature nodes compact.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub(crate) enum FeatureIndexInPackage {
     Base,
-    OptionalDependency(usize),
-    Named(usize),
+    OptionalDependency(u32),
+    Named(u32),
+}
+
+impl FeatureIndexInPackage {
+    pub(crate) fn optional_dep(idx: usize) -> Self {
+        FeatureIndexInPackage::OptionalDependency(
+            u32::try_from(idx).expect("feature count fits in u32"),
+        )
+    }
+
+    pub(crate) fn named(idx: usize) -> Self {
+        FeatureIndexInPackage::Named(u32::try_from(idx).expect("feature count fits in u32"))
+    }
 }
 
 /// `PackageMetadata`'s `PartialEq` implementation uses pointer equality for the `PackageGraph`.
@@ -1181,10 +1198,12 @@ impl<'g> Eq for PackageMetadata<'g> {}
 #[derive(Clone, Debug)]
 pub(crate) struct PackageMetadataImpl {
     // Implementation note: we use Box<str> and Box<Path> to save on memory use when possible.
+    // Names, versions and feature labels are frequently repeated across packages, so they're
+    // interned across the whole graph.
 
     // Fields extracted from the package.
-    pub(super) name: String,
-    pub(super) version: Version,
+    pub(super) name: Arc<str>,
+    pub(super) version: Arc<Version>,
     pub(super) authors: Vec<String>,
     pub(super) description: Option<Box<str>>,
     pub(super) license: Option<Box<str>>,
@@ -1203,8 +1222,8 @@ pub(crate) struct PackageMetadataImpl {
     pub(super) default_run: Option<Box<str>>,
     pub(super) rust_version: Option<Version>,
     pub(super) rust_version_req: Option<VersionReq>,
-    pub(super) named_features: IndexMap<Box<str>, SmallVec<[NamedFeatureDep; 4]>>,
-    pub(super) optional_deps: IndexSet<Box<str>>,
+    pub(super) named_features: IndexMap<Arc<str>, SmallVec<[NamedFeatureDep; 4]>>,
+    pub(super) optional_deps: IndexSet<Arc<str>>,
 
     // Other information.
     pub(super) package_ix: NodeIndex<PackageIx>,
@@ -1891,8 +1910,9 @@ pub struct PackageLinkPtrs {
 
 #[derive(Clone, Debug)]
 pub(crate) struct PackageLinkImpl {
-    pub(super) dep_name: String,
-    pub(super) resolved_name: String,
+    // Names are interned across all links in the graph, since they're frequently repeated.
+    pub(super) dep_name: Arc<str>,
+    pub(super) resolved_name: Arc<str>,
     pub(super) version_req: VersionReq,
     pub(super) normal: DependencyReqImpl,
     pub(super) build: DependencyReqImpl,
@@ -2064,11 +2084,11 @@ impl<'g> EnabledStatus<'g> {
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub(super) enum NamedFeatureDep {
-    NamedFeature(Box<str>),
-    OptionalDependency(Box<str>),
+    NamedFeature(Arc<str>),
+    OptionalDependency(Arc<str>),
     DependencyNamedFeature {
-        dep_name: Box<str>,
-        feature: Box<str>,
+        dep_name: Arc<str>,
+        feature: Arc<str>,
         weak: bool,
     },
 }
@@ -2076,12 +2096,12 @@ pub(super) enum NamedFeatureDep {
 impl NamedFeatureDep {
     #[inline]
     pub(super) fn named_feature(feature_name: impl Into<String>) -> Self {
-        Self::NamedFeature(feature_name.into().into_boxed_str())
+        Self::NamedFeature(feature_name.into().into())
     }
 
     #[inline]
     pub(super) fn optional_dependency(dep_name: impl Into<String>) -> Self {
-        Self::OptionalDependency(dep_name.into().into_boxed_str())
+        Self::OptionalDependency(dep_name.into().into())
     }
 
     #[inline]
@@ -2091,8 +2111,8 @@ impl NamedFeatureDep {
         weak: bool,
     ) -> Self {
         Self::DependencyNamedFeature {
-            dep_name: dep_name.into().into_boxed_str(),
-            feature: feature.into().into_boxed_str(),
+            dep_name: dep_name.into().into(),
+            feature: feature.into().into(),
             weak,
         }
     }
@@ -2173,11 +2193,12 @@ pub(super) struct DepRequiredOrOptional {
     pub(super) build_if: PlatformStatusImpl,
     pub(super) default_features_if: PlatformStatusImpl,
     pub(super) no_default_features_if: PlatformStatusImpl,
-    pub(super) feature_targets: BTreeMap<String, PlatformStatusImpl>,
+    // Feature names are interned across all links in the graph.
+    pub(super) feature_targets: BTreeMap<Arc<str>, PlatformStatusImpl>,
 }
 
 impl DepRequiredOrOptional {
     pub(super) fn all_features(&self) -> impl Iterator<Item = &str> {
-        self.feature_targets.keys().map(|s| s.as_str())
+        self.feature_targets.keys().map(|s| s.as_ref())
     }
 }
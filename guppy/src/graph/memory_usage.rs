@@ -0,0 +1,409 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::{
+        BuildTargetImpl, BuildTargetKindImpl, DepRequiredOrOptional, DependencyReqImpl,
+        NamedFeatureDep, OwnedBuildTargetId, PackageGraph, PackageIx, PackageLinkImpl,
+        PackageMetadataImpl, PackagePublishImpl, PackageSourceImpl, WorkspaceImpl,
+    },
+    platform::PlatformStatusImpl,
+    JsonValue, PackageId,
+};
+use ahash::AHashSet;
+use camino::{Utf8Path, Utf8PathBuf};
+use petgraph::graph::{Edge, Node};
+use semver::{Version, VersionReq};
+use std::{mem, sync::Arc};
+
+/// An estimate of the memory used by a [`PackageGraph`], in bytes.
+///
+/// The estimate covers the data structures stored by `guppy` and the heap allocations they own.
+/// It doesn't account for allocator overhead, unused capacity in collections, or caches other
+/// than the feature graph.
+///
+/// Returned by [`PackageGraph::memory_usage`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MemoryUsage {
+    packages: usize,
+    dependency_graph: usize,
+    shared_strings: usize,
+    feature_graph: Option<usize>,
+}
+
+impl MemoryUsage {
+    pub(super) fn new(graph: &PackageGraph) -> Self {
+        let mut shared = SharedValues::default();
+        let packages = mem::size_of::<WorkspaceImpl>()
+            + graph.data.workspace.heap_size()
+            + graph
+                .data
+                .packages
+                .iter()
+                .map(|(package_id, metadata)| {
+                    mem::size_of::<(PackageId, PackageMetadataImpl)>()
+                        + package_id.heap_size()
+                        + metadata.heap_size(&mut shared)
+                })
+                .sum::<usize>();
+
+        let dep_graph = &graph.dep_graph;
+        let dependency_graph = dep_graph.node_count()
+            * mem::size_of::<Node<PackageId, PackageIx>>()
+            + dep_graph
+                .node_weights()
+                .map(|package_id| package_id.heap_size())
+                .sum::<usize>()
+            + dep_graph.edge_count() * mem::size_of::<Edge<PackageLinkImpl, PackageIx>>()
+            + dep_graph
+                .edge_weights()
+                .map(|link| link.heap_size(&mut shared))
+                .sum::<usize>();
+
+        Self {
+            packages,
+            dependency_graph,
+            shared_strings: shared.size,
+            feature_graph: graph
+                .feature_graph
+                .get()
+                .map(|feature_graph| feature_graph.memory_usage()),
+        }
+    }
+
+    /// Returns the memory used by package metadata and workspace information.
+    pub fn packages(&self) -> usize {
+        self.packages
+    }
+
+    /// Returns the memory used by the dependency graph, including information about every link
+    /// but not including shared strings.
+    pub fn dependency_graph(&self) -> usize {
+        self.dependency_graph
+    }
+
+    /// Returns the memory used by interned values that are shared across packages and links, such
+    /// as package, dependency and feature names, and versions.
+    pub fn shared_strings(&self) -> usize {
+        self.shared_strings
+    }
+
+    /// Returns the memory used by the feature graph.
+    ///
+    /// The feature graph is computed lazily, so this is `None` if it hasn't been computed yet.
+    pub fn feature_graph(&self) -> Option<usize> {
+        self.feature_graph
+    }
+
+    /// Returns the total memory used.
+    pub fn total(&self) -> usize {
+        self.packages
+            + self.dependency_graph
+            + self.shared_strings
+            + self.feature_graph.unwrap_or(0)
+    }
+}
+
+/// Tracks values shared through `Arc`s, so that each one is only counted once.
+#[derive(Debug, Default)]
+struct SharedValues {
+    seen: AHashSet<*const u8>,
+    size: usize,
+}
+
+impl SharedValues {
+    fn add(&mut self, s: &Arc<str>) {
+        self.add_impl(Arc::as_ptr(s) as *const u8, s.len());
+    }
+
+    fn add_version(&mut self, version: &Arc<Version>) {
+        self.add_impl(
+            Arc::as_ptr(version) as *const u8,
+            mem::size_of::<Version>() + version.heap_size(),
+        );
+    }
+
+    fn add_impl(&mut self, ptr: *const u8, size: usize) {
+        if self.seen.insert(ptr) {
+            // Arcs store a strong and a weak count alongside the data.
+            self.size += 2 * mem::size_of::<usize>() + size;
+        }
+    }
+}
+
+/// The number of bytes owned by a value on the heap.
+pub(super) trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+impl HeapSize for str {
+    fn heap_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl HeapSize for Box<str> {
+    fn heap_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl HeapSize for Box<Utf8Path> {
+    fn heap_size(&self) -> usize {
+        self.as_str().len()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, T::heap_size)
+    }
+}
+
+impl<T: HeapSize> HeapSize for [T] {
+    fn heap_size(&self) -> usize {
+        mem::size_of_val(self) + self.iter().map(T::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.as_slice().heap_size()
+    }
+}
+
+impl HeapSize for PackageId {
+    fn heap_size(&self) -> usize {
+        self.repr().len()
+    }
+}
+
+impl HeapSize for Version {
+    fn heap_size(&self) -> usize {
+        // Short identifiers are stored inline, so this is an upper bound.
+        self.pre.len() + self.build.len()
+    }
+}
+
+impl HeapSize for VersionReq {
+    fn heap_size(&self) -> usize {
+        self.comparators.len() * mem::size_of::<semver::Comparator>()
+    }
+}
+
+impl HeapSize for JsonValue {
+    fn heap_size(&self) -> usize {
+        match self {
+            JsonValue::Null | JsonValue::Bool(_) | JsonValue::Number(_) => 0,
+            JsonValue::String(s) => s.heap_size(),
+            JsonValue::Array(values) => values.heap_size(),
+            JsonValue::Object(map) => map
+                .iter()
+                .map(|(key, value)| {
+                    mem::size_of::<(String, JsonValue)>() + key.heap_size() + value.heap_size()
+                })
+                .sum(),
+        }
+    }
+}
+
+impl HeapSize for PlatformStatusImpl {
+    fn heap_size(&self) -> usize {
+        match self {
+            PlatformStatusImpl::Always => 0,
+            // The contents of target specs are opaque, so only count the specs themselves.
+            PlatformStatusImpl::Specs(specs) => mem::size_of_val(specs.as_slice()),
+        }
+    }
+}
+
+impl HeapSize for WorkspaceImpl {
+    fn heap_size(&self) -> usize {
+        self.root.as_str().len()
+            + self.target_directory.as_str().len()
+            + self.metadata_table.heap_size()
+            + self
+                .members_by_path
+                .iter()
+                .map(|(path, id)| {
+                    mem::size_of::<(Utf8PathBuf, PackageId)>()
+                        + path.as_str().len()
+                        + id.heap_size()
+                })
+                .sum::<usize>()
+            + self
+                .members_by_name
+                .iter()
+                .map(|(name, id)| {
+                    mem::size_of::<(Box<str>, PackageId)>() + name.heap_size() + id.heap_size()
+                })
+                .sum::<usize>()
+    }
+}
+
+impl PackageMetadataImpl {
+    fn heap_size(&self, shared: &mut SharedValues) -> usize {
+        shared.add(&self.name);
+        shared.add_version(&self.version);
+        let named_features = self
+            .named_features
+            .iter()
+            .map(|(name, deps)| {
+                shared.add(name);
+                for dep in deps {
+                    dep.add_shared(shared);
+                }
+                mem::size_of::<(Arc<str>, NamedFeatureDepList)>()
+                    + if deps.spilled() {
+                        mem::size_of_val(deps.as_slice())
+                    } else {
+                        0
+                    }
+            })
+            .sum::<usize>();
+        let optional_deps = self
+            .optional_deps
+            .iter()
+            .map(|dep| {
+                shared.add(dep);
+                mem::size_of::<Arc<str>>()
+            })
+            .sum::<usize>();
+        let build_targets = self
+            .build_targets
+            .iter()
+            .map(|(id, target)| {
+                mem::size_of::<(OwnedBuildTargetId, BuildTargetImpl)>()
+                    + id.heap_size()
+                    + target.heap_size()
+            })
+            .sum::<usize>();
+
+        self.authors.heap_size()
+            + self.description.heap_size()
+            + self.license.heap_size()
+            + self.license_file.heap_size()
+            + self.manifest_path.heap_size()
+            + self.categories.heap_size()
+            + self.keywords.heap_size()
+            + self.readme.heap_size()
+            + self.repository.heap_size()
+            + self.homepage.heap_size()
+            + self.documentation.heap_size()
+            + self.edition.heap_size()
+            + self.metadata_table.heap_size()
+            + self.links.heap_size()
+            + self.publish.heap_size()
+            + self.default_run.heap_size()
+            + self.rust_version.heap_size()
+            + self.rust_version_req.heap_size()
+            + named_features
+            + optional_deps
+            + self.source.heap_size()
+            + build_targets
+    }
+}
+
+type NamedFeatureDepList = smallvec::SmallVec<[NamedFeatureDep; 4]>;
+
+impl NamedFeatureDep {
+    fn add_shared(&self, shared: &mut SharedValues) {
+        match self {
+            NamedFeatureDep::NamedFeature(name) | NamedFeatureDep::OptionalDependency(name) => {
+                shared.add(name);
+            }
+            NamedFeatureDep::DependencyNamedFeature {
+                dep_name, feature, ..
+            } => {
+                shared.add(dep_name);
+                shared.add(feature);
+            }
+        }
+    }
+}
+
+impl HeapSize for PackagePublishImpl {
+    fn heap_size(&self) -> usize {
+        match self {
+            PackagePublishImpl::Unrestricted => 0,
+            PackagePublishImpl::Registries(registries) => registries.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for PackageSourceImpl {
+    fn heap_size(&self) -> usize {
+        match self {
+            PackageSourceImpl::Workspace(path) | PackageSourceImpl::Path(path) => path.heap_size(),
+            PackageSourceImpl::CratesIo => 0,
+            PackageSourceImpl::External(source) => source.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for OwnedBuildTargetId {
+    fn heap_size(&self) -> usize {
+        match self {
+            OwnedBuildTargetId::Library | OwnedBuildTargetId::BuildScript => 0,
+            OwnedBuildTargetId::Binary(name)
+            | OwnedBuildTargetId::Example(name)
+            | OwnedBuildTargetId::Test(name)
+            | OwnedBuildTargetId::Benchmark(name) => name.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for BuildTargetImpl {
+    fn heap_size(&self) -> usize {
+        let kind = match &self.kind {
+            BuildTargetKindImpl::LibraryOrExample(crate_types) => {
+                crate_types.iter().map(|s| s.len()).sum::<usize>()
+                    + crate_types.len() * mem::size_of::<String>()
+            }
+            BuildTargetKindImpl::ProcMacro | BuildTargetKindImpl::Binary => 0,
+        };
+        kind + self.lib_name.heap_size()
+            + self.required_features.heap_size()
+            + self.path.heap_size()
+            + self.edition.heap_size()
+    }
+}
+
+impl PackageLinkImpl {
+    fn heap_size(&self, shared: &mut SharedValues) -> usize {
+        shared.add(&self.dep_name);
+        shared.add(&self.resolved_name);
+        self.version_req.heap_size()
+            + self.normal.heap_size(shared)
+            + self.build.heap_size(shared)
+            + self.dev.heap_size(shared)
+    }
+}
+
+impl DependencyReqImpl {
+    fn heap_size(&self, shared: &mut SharedValues) -> usize {
+        self.required.heap_size(shared) + self.optional.heap_size(shared)
+    }
+}
+
+impl DepRequiredOrOptional {
+    fn heap_size(&self, shared: &mut SharedValues) -> usize {
+        self.build_if.heap_size()
+            + self.default_features_if.heap_size()
+            + self.no_default_features_if.heap_size()
+            + self
+                .feature_targets
+                .iter()
+                .map(|(feature, status)| {
+                    shared.add(feature);
+                    mem::size_of::<(Arc<str>, PlatformStatusImpl)>() + status.heap_size()
+                })
+                .sum::<usize>()
+    }
+}
@@ -10,26 +10,42 @@ use crate::PackageId;
 use petgraph::prelude::*;
 use std::fmt;
 
+#[cfg(feature = "rustsec")]
+pub mod audit;
 mod build;
 mod build_targets;
 pub mod cargo;
+#[cfg(any(feature = "policy", feature = "rustsec", feature = "sbom"))]
+mod chains;
 mod cycles;
 pub mod feature;
+mod fingerprint;
 mod graph_impl;
+mod memory_usage;
+#[cfg(feature = "outdated")]
+pub mod outdated;
+mod owned;
+#[cfg(feature = "policy")]
+pub mod policy;
 #[cfg(feature = "proptest1")]
 mod proptest_helpers;
 mod query;
 mod query_core;
 mod resolve;
 mod resolve_core;
+#[cfg(feature = "sbom")]
+pub mod sbom;
 #[cfg(feature = "summaries")]
 pub mod summaries;
 
 pub use crate::petgraph_support::dot::DotWrite;
 pub use build_targets::*;
 pub use cycles::*;
+pub use fingerprint::*;
 pub use graph_impl::*;
+pub use memory_usage::*;
 use once_cell::sync::Lazy;
+pub use owned::*;
 use petgraph::graph::IndexType;
 #[cfg(feature = "proptest1")]
 pub use proptest_helpers::*;
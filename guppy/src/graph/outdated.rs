@@ -0,0 +1,442 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Find third-party dependencies with newer versions available.
+//!
+//! This module reports, for each third-party package in a `PackageSet`, the latest version
+//! compatible with the one currently in use and the latest version overall. Each package also
+//! lists the version requirements declared on it by workspace packages, along with the latest
+//! version matching each requirement.
+//!
+//! Versions are read from a [registry index](https://doc.rust-lang.org/cargo/reference/registry-index.html)
+//! through the [`RegistryIndex`] trait. `guppy` doesn't perform any network access itself: to use
+//! the crates.io sparse index, implement `RegistryIndex` by fetching
+//! `https://index.crates.io/` followed by [`index_path`]. A local mirror of the index can be read
+//! through [`LocalRegistryIndex`].
+//!
+//! Requires the `outdated` feature to be enabled.
+//!
+//! # Examples
+//!
+//! ```
+//! use guppy::{
+//!     graph::outdated::{OutdatedOptions, OutdatedReport},
+//!     CargoMetadata,
+//! };
+//! use std::error::Error;
+//!
+//! let metadata = CargoMetadata::parse_json(include_str!("../../../fixtures/small/metadata1.json")).unwrap();
+//! let graph = metadata.build_graph().unwrap();
+//!
+//! // A real index would fetch this data over the network.
+//! let index = |name: &str| -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+//!     Ok((name == "datatest").then(|| {
+//!         r#"{"name":"datatest","vers":"0.4.2","deps":[],"cksum":"","features":{},"yanked":false}
+//! {"name":"datatest","vers":"0.9.0","deps":[],"cksum":"","features":{},"yanked":false}"#
+//!             .to_owned()
+//!     }))
+//! };
+//!
+//! let report = OutdatedReport::compute(&graph.resolve_all(), &index, &OutdatedOptions::new()).unwrap();
+//! for package in report.packages() {
+//!     println!(
+//!         "{} {}: latest compatible {:?}, latest {:?}",
+//!         package.package().name(),
+//!         package.package().version(),
+//!         package.latest_compatible(),
+//!         package.latest(),
+//!     );
+//! }
+//! # assert_eq!(report.packages().len(), 1);
+//! ```
+
+use crate::{
+    graph::{DependencyDirection, PackageLink, PackageMetadata, PackageSet},
+    Error, PackageId,
+};
+use ahash::AHashMap;
+use camino::{Utf8Path, Utf8PathBuf};
+use semver::{Comparator, Op, Version, VersionReq};
+use serde::Deserialize;
+use std::{collections::BTreeMap, error, fs, io};
+
+/// Access to a registry index.
+///
+/// This is implemented for closures of the form
+/// `Fn(&str) -> Result<Option<String>, Box<dyn Error + Send + Sync>>`.
+///
+/// Requires the `outdated` feature to be enabled.
+pub trait RegistryIndex {
+    /// Returns the contents of the index file for the crate with this name, or `None` if the crate
+    /// isn't in the index.
+    ///
+    /// The index file is in the JSON lines format used by Cargo registries, with one line per
+    /// published version.
+    fn index_file(&self, name: &str)
+        -> Result<Option<String>, Box<dyn error::Error + Send + Sync>>;
+}
+
+impl<F> RegistryIndex for F
+where
+    F: Fn(&str) -> Result<Option<String>, Box<dyn error::Error + Send + Sync>>,
+{
+    fn index_file(
+        &self,
+        name: &str,
+    ) -> Result<Option<String>, Box<dyn error::Error + Send + Sync>> {
+        self(name)
+    }
+}
+
+/// A registry index stored on the local filesystem, for example a checkout of the
+/// [crates.io index repository](https://github.com/rust-lang/crates.io-index) or a mirror.
+///
+/// Requires the `outdated` feature to be enabled.
+#[derive(Clone, Debug)]
+pub struct LocalRegistryIndex {
+    root: Utf8PathBuf,
+}
+
+impl LocalRegistryIndex {
+    /// Creates a new `LocalRegistryIndex` rooted at this directory.
+    pub fn new(root: impl Into<Utf8PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the root directory of this index.
+    pub fn root(&self) -> &Utf8Path {
+        &self.root
+    }
+}
+
+impl RegistryIndex for LocalRegistryIndex {
+    fn index_file(
+        &self,
+        name: &str,
+    ) -> Result<Option<String>, Box<dyn error::Error + Send + Sync>> {
+        match fs::read_to_string(self.root.join(index_path(name))) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Box::new(err)),
+        }
+    }
+}
+
+/// Returns the path of the index file for this crate, relative to the root of the index.
+///
+/// For example, the index file for `serde` is at `se/rd/serde`.
+///
+/// Requires the `outdated` feature to be enabled.
+pub fn index_path(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[..2], &name[2..4], name),
+    }
+}
+
+/// A version of a crate published to a registry.
+///
+/// Requires the `outdated` feature to be enabled.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IndexVersion {
+    /// The version number.
+    pub version: Version,
+
+    /// Whether this version has been yanked.
+    pub yanked: bool,
+}
+
+impl IndexVersion {
+    /// Parses the contents of an index file.
+    ///
+    /// Lines with version numbers that aren't valid semver are skipped.
+    pub fn parse_index_file(contents: &str) -> Result<Vec<Self>, serde_json::Error> {
+        #[derive(Deserialize)]
+        struct IndexLine {
+            vers: String,
+            #[serde(default)]
+            yanked: bool,
+        }
+
+        let mut versions = vec![];
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let line: IndexLine = serde_json::from_str(line)?;
+            if let Ok(version) = line.vers.parse() {
+                versions.push(Self {
+                    version,
+                    yanked: line.yanked,
+                });
+            }
+        }
+        Ok(versions)
+    }
+}
+
+/// Options for computing an [`OutdatedReport`].
+///
+/// Requires the `outdated` feature to be enabled.
+#[derive(Clone, Debug, Default)]
+pub struct OutdatedOptions {
+    include_up_to_date: bool,
+    include_prereleases: bool,
+}
+
+impl OutdatedOptions {
+    /// Creates a new `OutdatedOptions` with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether packages already at their latest version are included in the report.
+    ///
+    /// The default is false.
+    pub fn set_include_up_to_date(&mut self, include_up_to_date: bool) -> &mut Self {
+        self.include_up_to_date = include_up_to_date;
+        self
+    }
+
+    /// Sets whether pre-release versions are considered when looking for newer versions.
+    ///
+    /// Pre-release versions are always considered for packages currently at a pre-release
+    /// version. The default is false.
+    pub fn set_include_prereleases(&mut self, include_prereleases: bool) -> &mut Self {
+        self.include_prereleases = include_prereleases;
+        self
+    }
+}
+
+/// A report on third-party packages with newer versions available.
+///
+/// Requires the `outdated` feature to be enabled.
+#[derive(Clone, Debug)]
+pub struct OutdatedReport<'g> {
+    packages: Vec<OutdatedPackage<'g>>,
+}
+
+impl<'g> OutdatedReport<'g> {
+    /// Computes a report for the third-party packages in this package set, using the given
+    /// index.
+    ///
+    /// Only packages from crates.io are checked, and the index is expected to be for crates.io or
+    /// a mirror of it. The index is queried once for each distinct package name.
+    pub fn compute(
+        package_set: &PackageSet<'g>,
+        index: &impl RegistryIndex,
+        options: &OutdatedOptions,
+    ) -> Result<Self, Error> {
+        // Index the requirements declared by workspace packages.
+        let mut requirements: AHashMap<&'g PackageId, Vec<DeclaredRequirement<'g>>> =
+            AHashMap::new();
+        for link in package_set.links(DependencyDirection::Forward) {
+            if link.from().in_workspace() {
+                requirements
+                    .entry(link.to().id())
+                    .or_default()
+                    .push(DeclaredRequirement {
+                        link,
+                        latest_matching: None,
+                    });
+            }
+        }
+
+        let mut index_versions: BTreeMap<&'g str, Vec<IndexVersion>> = BTreeMap::new();
+        let mut packages = vec![];
+        for package in package_set.packages(DependencyDirection::Forward) {
+            if !package.source().is_crates_io() {
+                continue;
+            }
+            let versions = match index_versions.get(package.name()) {
+                Some(versions) => versions,
+                None => {
+                    let versions = fetch_versions(index, package.name())?;
+                    index_versions.entry(package.name()).or_insert(versions)
+                }
+            };
+            // Pre-releases are always considered for packages currently at a pre-release.
+            let include_prereleases =
+                options.include_prereleases || !package.version().pre.is_empty();
+            let candidates: Vec<_> = versions
+                .iter()
+                .filter(|v| !v.yanked && (include_prereleases || v.version.pre.is_empty()))
+                .map(|v| &v.version)
+                .collect();
+
+            let latest_matching = |req: &VersionReq| {
+                candidates
+                    .iter()
+                    .filter(|version| req.matches(version))
+                    .max()
+                    .map(|version| (*version).clone())
+            };
+            let latest = candidates.iter().max().map(|version| (*version).clone());
+            let latest_compatible = latest_matching(&compatible_req(package.version()));
+
+            let is_outdated = latest
+                .as_ref()
+                .is_some_and(|latest| latest > package.version());
+            if !is_outdated && !options.include_up_to_date {
+                continue;
+            }
+
+            let mut requirements = requirements.remove(package.id()).unwrap_or_default();
+            for requirement in &mut requirements {
+                requirement.latest_matching = latest_matching(requirement.link.version_req());
+            }
+            requirements.sort_by(|a, b| {
+                (a.link.from().id(), a.link.dep_name())
+                    .cmp(&(b.link.from().id(), b.link.dep_name()))
+            });
+
+            packages.push(OutdatedPackage {
+                package,
+                latest_compatible,
+                latest,
+                requirements,
+            });
+        }
+
+        packages.sort_by(|a, b| {
+            let (a, b) = (a.package, b.package);
+            (a.name(), a.version(), a.id()).cmp(&(b.name(), b.version(), b.id()))
+        });
+        Ok(Self { packages })
+    }
+
+    /// Returns the packages in this report, sorted by name and version.
+    pub fn packages(&self) -> &[OutdatedPackage<'g>] {
+        &self.packages
+    }
+
+    /// Returns the packages in this report grouped by the workspace packages that depend on them
+    /// directly.
+    ///
+    /// Transitive dependencies aren't included in the result.
+    pub fn by_workspace_package(&self) -> BTreeMap<&'g PackageId, Vec<&OutdatedPackage<'g>>> {
+        let mut by_workspace_package: BTreeMap<_, Vec<&OutdatedPackage<'g>>> = BTreeMap::new();
+        for package in &self.packages {
+            for requirement in &package.requirements {
+                let packages = by_workspace_package
+                    .entry(requirement.link.from().id())
+                    .or_default();
+                // A workspace package may declare several requirements on the same package, for
+                // example with different dependency names. Requirements are sorted by workspace
+                // package, so any duplicate is the last package added.
+                if !packages
+                    .last()
+                    .is_some_and(|last| std::ptr::eq(*last, package))
+                {
+                    packages.push(package);
+                }
+            }
+        }
+        by_workspace_package
+    }
+}
+
+/// A third-party package in an [`OutdatedReport`].
+///
+/// Requires the `outdated` feature to be enabled.
+#[derive(Clone, Debug)]
+pub struct OutdatedPackage<'g> {
+    package: PackageMetadata<'g>,
+    latest_compatible: Option<Version>,
+    latest: Option<Version>,
+    requirements: Vec<DeclaredRequirement<'g>>,
+}
+
+impl<'g> OutdatedPackage<'g> {
+    /// Returns the package.
+    pub fn package(&self) -> PackageMetadata<'g> {
+        self.package
+    }
+
+    /// Returns the latest version that's semver-compatible with the version currently in use.
+    ///
+    /// Returns `None` if the package isn't in the index.
+    pub fn latest_compatible(&self) -> Option<&Version> {
+        self.latest_compatible.as_ref()
+    }
+
+    /// Returns the latest version overall.
+    ///
+    /// Returns `None` if the package isn't in the index.
+    pub fn latest(&self) -> Option<&Version> {
+        self.latest.as_ref()
+    }
+
+    /// Returns true if a newer version of this package is available.
+    pub fn is_outdated(&self) -> bool {
+        self.latest
+            .as_ref()
+            .is_some_and(|latest| latest > self.package.version())
+    }
+
+    /// Returns the version requirements declared on this package by workspace packages, sorted by
+    /// workspace package ID.
+    ///
+    /// This is empty for packages that are only transitive dependencies.
+    pub fn requirements(&self) -> &[DeclaredRequirement<'g>] {
+        &self.requirements
+    }
+}
+
+/// A version requirement declared by a workspace package on a third-party package.
+///
+/// Requires the `outdated` feature to be enabled.
+#[derive(Clone, Debug)]
+pub struct DeclaredRequirement<'g> {
+    link: PackageLink<'g>,
+    latest_matching: Option<Version>,
+}
+
+impl<'g> DeclaredRequirement<'g> {
+    /// Returns the link from the workspace package to the third-party package.
+    pub fn link(&self) -> PackageLink<'g> {
+        self.link
+    }
+
+    /// Returns the workspace package that declares this requirement.
+    pub fn workspace_package(&self) -> PackageMetadata<'g> {
+        self.link.from()
+    }
+
+    /// Returns the version requirement.
+    pub fn version_req(&self) -> &'g VersionReq {
+        self.link.version_req()
+    }
+
+    /// Returns the latest version matching this requirement.
+    ///
+    /// If this is older than [`OutdatedPackage::latest`], the requirement must be changed to
+    /// update to the latest version.
+    pub fn latest_matching(&self) -> Option<&Version> {
+        self.latest_matching.as_ref()
+    }
+}
+
+fn fetch_versions(index: &impl RegistryIndex, name: &str) -> Result<Vec<IndexVersion>, Error> {
+    let contents = index
+        .index_file(name)
+        .map_err(|err| Error::RegistryIndexError(name.to_owned(), err))?;
+    match contents {
+        Some(contents) => IndexVersion::parse_index_file(&contents)
+            .map_err(|err| Error::RegistryIndexError(name.to_owned(), Box::new(err))),
+        None => Ok(vec![]),
+    }
+}
+
+/// Returns the requirement matching versions semver-compatible with this one, like `^1.2.3`.
+fn compatible_req(version: &Version) -> VersionReq {
+    VersionReq {
+        comparators: vec![Comparator {
+            op: Op::Caret,
+            major: version.major,
+            minor: Some(version.minor),
+            patch: Some(version.patch),
+            pre: version.pre.clone(),
+        }],
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    debug_ignore::DebugIgnore,
+    graph::{
+        feature::FeatureSet, resolve_core::ResolveCore, FeatureGraphSpec, PackageGraph, PackageSet,
+    },
+};
+use std::sync::Arc;
+
+/// A `PackageSet` that owns a reference-counted handle to its package graph.
+///
+/// A [`PackageSet`] borrows from the [`PackageGraph`] it was computed against, which makes it
+/// hard to store alongside the graph (for example, in a long-lived service or a cache). An
+/// `OwnedPackageSet` holds an `Arc<PackageGraph>` instead, so it has no lifetime parameter and is
+/// `Send + Sync + 'static`.
+///
+/// Use [`get`](Self::get) to access the set.
+///
+/// # Examples
+///
+/// ```
+/// use guppy::{graph::OwnedPackageSet, CargoMetadata};
+/// use std::sync::Arc;
+///
+/// let metadata = CargoMetadata::parse_json(include_str!("../../../fixtures/small/metadata1.json"))
+///     .unwrap();
+/// let graph = Arc::new(metadata.build_graph().unwrap());
+///
+/// let workspace_deps = OwnedPackageSet::new(graph, |graph| graph.query_workspace().resolve());
+///
+/// // workspace_deps can now be stored anywhere, independently of the graph.
+/// assert!(workspace_deps.get().len() > 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct OwnedPackageSet {
+    graph: DebugIgnore<Arc<PackageGraph>>,
+    core: ResolveCore<PackageGraph>,
+}
+
+impl OwnedPackageSet {
+    /// Creates a new `OwnedPackageSet` from the package set returned by `f`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the set returned by `f` is for a different package graph.
+    pub fn new(graph: Arc<PackageGraph>, f: impl FnOnce(&PackageGraph) -> PackageSet<'_>) -> Self {
+        let (set_graph, core) = f(&graph).into_parts();
+        assert!(
+            std::ptr::eq(&*graph, set_graph),
+            "package set must be for the provided package graph"
+        );
+        Self {
+            graph: DebugIgnore(graph),
+            core,
+        }
+    }
+
+    /// Creates a new `OwnedPackageSet` from the package set returned by `f`, or returns the error
+    /// returned by `f`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the set returned by `f` is for a different package graph.
+    pub fn try_new<E>(
+        graph: Arc<PackageGraph>,
+        f: impl FnOnce(&PackageGraph) -> Result<PackageSet<'_>, E>,
+    ) -> Result<Self, E> {
+        let (set_graph, core) = f(&graph)?.into_parts();
+        assert!(
+            std::ptr::eq(&*graph, set_graph),
+            "package set must be for the provided package graph"
+        );
+        Ok(Self {
+            graph: DebugIgnore(graph),
+            core,
+        })
+    }
+
+    /// Returns the package graph this set was computed against.
+    pub fn graph(&self) -> &Arc<PackageGraph> {
+        &self.graph
+    }
+
+    /// Returns the package set, borrowing from the graph held by `self`.
+    ///
+    /// This clones the underlying set, which is cheap compared to resolving it again.
+    pub fn get(&self) -> PackageSet<'_> {
+        PackageSet::from_core(&self.graph, self.core.clone())
+    }
+}
+
+/// A `FeatureSet` that owns a reference-counted handle to its package graph.
+///
+/// This is the [`FeatureSet`] counterpart to [`OwnedPackageSet`]. It has no lifetime parameter
+/// and is `Send + Sync + 'static`.
+///
+/// Use [`get`](Self::get) to access the set.
+#[derive(Clone, Debug)]
+pub struct OwnedFeatureSet {
+    graph: DebugIgnore<Arc<PackageGraph>>,
+    core: ResolveCore<FeatureGraphSpec>,
+}
+
+impl OwnedFeatureSet {
+    /// Creates a new `OwnedFeatureSet` from the feature set returned by `f`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the set returned by `f` is for a different package graph.
+    pub fn new(graph: Arc<PackageGraph>, f: impl FnOnce(&PackageGraph) -> FeatureSet<'_>) -> Self {
+        let (feature_graph, core) = f(&graph).into_parts();
+        assert!(
+            std::ptr::eq(&*graph, feature_graph.package_graph),
+            "feature set must be for the provided package graph"
+        );
+        Self {
+            graph: DebugIgnore(graph),
+            core,
+        }
+    }
+
+    /// Creates a new `OwnedFeatureSet` from the feature set returned by `f`, or returns the error
+    /// returned by `f`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the set returned by `f` is for a different package graph.
+    pub fn try_new<E>(
+        graph: Arc<PackageGraph>,
+        f: impl FnOnce(&PackageGraph) -> Result<FeatureSet<'_>, E>,
+    ) -> Result<Self, E> {
+        let (feature_graph, core) = f(&graph)?.into_parts();
+        assert!(
+            std::ptr::eq(&*graph, feature_graph.package_graph),
+            "feature set must be for the provided package graph"
+        );
+        Ok(Self {
+            graph: DebugIgnore(graph),
+            core,
+        })
+    }
+
+    /// Returns the package graph this set was computed against.
+    pub fn graph(&self) -> &Arc<PackageGraph> {
+        &self.graph
+    }
+
+    /// Returns the feature set, borrowing from the graph held by `self`.
+    ///
+    /// This clones the underlying set, which is cheap compared to resolving it again.
+    pub fn get(&self) -> FeatureSet<'_> {
+        FeatureSet::from_core(self.graph.feature_graph(), self.core.clone())
+    }
+}
@@ -0,0 +1,636 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Evaluate declarative dependency policies against package sets.
+//!
+//! A [`Policy`] is a set of rules, written in TOML, about which third-party packages may be
+//! depended on:
+//!
+//! ```toml
+//! # Ban packages, optionally restricted to a range of versions.
+//! [[bans]]
+//! name = "openssl"
+//! reason = "use rustls instead"
+//!
+//! [[bans]]
+//! name = "lazy_static"
+//! version = "< 1"
+//!
+//! # Deny packages whose license expressions can't be satisfied without these licenses. License
+//! # IDs are matched case-insensitively, ignoring "-only", "-or-later" and "+" suffixes: this
+//! # also denies GPL-3.0-only, GPL-3.0-or-later and GPL-3.0+.
+//! [licenses]
+//! deny = ["GPL-3.0", "AGPL-3.0"]
+//!
+//! # Only allow packages from these registries and Git repositories. "crates-io" stands for
+//! # crates.io, whether accessed through the Git or the sparse protocol. If a list isn't
+//! # specified, packages from any registry or repository are allowed.
+//! [sources]
+//! allow-registries = ["crates-io"]
+//! allow-git = ["https://github.com/rust-lang/cargo"]
+//!
+//! # Cap the number of versions of the same package.
+//! [duplicates]
+//! max-versions = 1
+//! skip = ["windows-sys"]
+//! ```
+//!
+//! Rules only apply to packages outside the workspace.
+//!
+//! Evaluating a policy against a [`CargoSet`] only considers packages that are actually built for
+//! a given set of features and platforms: for example, a banned dependency that's only used on
+//! Windows isn't reported for a Linux build. To evaluate a policy against every package in a
+//! graph, use [`PackageGraph::resolve_all`](crate::graph::PackageGraph::resolve_all) and
+//! [`Policy::evaluate_package_set`].
+//!
+//! Each violation comes with the dependency chains through which the offending package is
+//! included.
+//!
+//! Requires the `policy` feature to be enabled.
+//!
+//! # Examples
+//!
+//! ```
+//! use guppy::{graph::policy::Policy, CargoMetadata};
+//!
+//! let metadata = CargoMetadata::parse_json(include_str!("../../../fixtures/small/metadata_dups.json")).unwrap();
+//! let graph = metadata.build_graph().unwrap();
+//!
+//! let policy = Policy::parse(r#"
+//! [duplicates]
+//! max-versions = 1
+//! "#).unwrap();
+//!
+//! let report = policy.evaluate_package_set(&graph.resolve_all());
+//! for violation in report.violations() {
+//!     println!("{}: {}", violation.package().id(), violation.kind());
+//!     for chain in violation.chains() {
+//!         let names: Vec<_> = chain.iter().map(|package| package.name()).collect();
+//!         println!("  via {}", names.join(" -> "));
+//!     }
+//! }
+//! // Both bytes and lazy_static have two versions.
+//! # assert_eq!(report.violations().len(), 4);
+//! ```
+
+use crate::{
+    graph::{
+        cargo::{BuildPlatform, CargoSet},
+        chains::{BuiltPackage, DependencyChains},
+        DependencyDirection, ExternalSource, PackageLink, PackageMetadata, PackageSet,
+    },
+    Error, PackageId,
+};
+use semver::VersionReq;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// A set of rules about which third-party packages may be depended on.
+///
+/// For the format, see the [module documentation](self).
+///
+/// Requires the `policy` feature to be enabled.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Policy {
+    #[serde(default)]
+    bans: Vec<BanRule>,
+    #[serde(default)]
+    licenses: LicensePolicy,
+    #[serde(default)]
+    sources: SourcePolicy,
+    #[serde(default)]
+    duplicates: DuplicatePolicy,
+}
+
+impl Policy {
+    /// The name that stands for crates.io in the `allow-registries` list.
+    pub const CRATES_IO: &'static str = "crates-io";
+
+    /// The sparse index URL for crates.io.
+    const CRATES_IO_SPARSE: &'static str = "sparse+https://index.crates.io/";
+
+    /// Parses a policy from a TOML string.
+    ///
+    /// Unknown keys are rejected, so that misspelled rules aren't silently ignored.
+    pub fn parse(contents: &str) -> Result<Self, Error> {
+        toml::from_str(contents).map_err(Error::PolicyParseError)
+    }
+
+    /// Returns the ban rules in this policy.
+    pub fn bans(&self) -> &[BanRule] {
+        &self.bans
+    }
+
+    /// Evaluates this policy against the third-party packages in a `PackageSet`.
+    ///
+    /// Dependency chains start from the packages in the set that have no dependents within it.
+    pub fn evaluate_package_set<'g>(&self, package_set: &PackageSet<'g>) -> PolicyReport<'g, '_> {
+        let packages = package_set
+            .packages(DependencyDirection::Forward)
+            .map(BuiltPackage::new);
+        PolicyReport::new(
+            self,
+            packages,
+            package_set.root_ids(DependencyDirection::Forward),
+            package_set.links(DependencyDirection::Forward),
+        )
+    }
+
+    /// Evaluates this policy against the third-party packages built by a `CargoSet`.
+    ///
+    /// Only packages built for the features and platforms the `CargoSet` was computed for are
+    /// considered. Dependency chains start from the initials of the `CargoSet`.
+    pub fn evaluate_cargo_set<'g>(&self, cargo_set: &CargoSet<'g>) -> PolicyReport<'g, '_> {
+        let initials = cargo_set.initials().to_package_set();
+        PolicyReport::new(
+            self,
+            BuiltPackage::from_cargo_set(cargo_set),
+            initials.package_ids(DependencyDirection::Forward),
+            cargo_set.followed_links().map(|(link, _)| link),
+        )
+    }
+
+    fn is_registry_allowed(&self, package: &PackageMetadata<'_>, registry: &str) -> bool {
+        let allowed = match &self.sources.allow_registries {
+            Some(allowed) => allowed,
+            None => return true,
+        };
+        let is_crates_io = package.source().is_crates_io() || registry == Self::CRATES_IO_SPARSE;
+        allowed.iter().any(|allowed| {
+            (allowed == Self::CRATES_IO && is_crates_io)
+                || allowed.trim_end_matches('/') == registry.trim_end_matches('/')
+        })
+    }
+
+    fn is_git_allowed(&self, repository: &str) -> bool {
+        let normalize = |url: &str| {
+            let url = url.trim_end_matches('/');
+            url.strip_suffix(".git").unwrap_or(url).to_owned()
+        };
+        match &self.sources.allow_git {
+            Some(allowed) => {
+                let repository = normalize(repository);
+                allowed
+                    .iter()
+                    .any(|allowed| normalize(allowed) == repository)
+            }
+            None => true,
+        }
+    }
+}
+
+/// A rule banning a package, or a range of versions of a package.
+///
+/// Requires the `policy` feature to be enabled.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BanRule {
+    name: String,
+    #[serde(default)]
+    version: Option<VersionReq>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+impl BanRule {
+    /// Returns the name of the banned package.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the banned versions, or `None` if every version is banned.
+    pub fn version_req(&self) -> Option<&VersionReq> {
+        self.version.as_ref()
+    }
+
+    /// Returns the reason the package is banned, if one was given.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// Returns true if this rule bans the given package.
+    pub fn matches(&self, package: &PackageMetadata<'_>) -> bool {
+        package.name() == self.name
+            && self
+                .version
+                .as_ref()
+                .map_or(true, |req| req.matches(package.version()))
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct LicensePolicy {
+    #[serde(default)]
+    deny: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct SourcePolicy {
+    #[serde(default)]
+    allow_registries: Option<Vec<String>>,
+    #[serde(default)]
+    allow_git: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct DuplicatePolicy {
+    #[serde(default)]
+    max_versions: Option<usize>,
+    #[serde(default)]
+    skip: BTreeSet<String>,
+}
+
+/// The results of evaluating a [`Policy`].
+///
+/// Returned by [`Policy::evaluate_package_set`] and [`Policy::evaluate_cargo_set`].
+///
+/// Requires the `policy` feature to be enabled.
+#[derive(Clone, Debug)]
+pub struct PolicyReport<'g, 'p> {
+    violations: Vec<PolicyViolation<'g, 'p>>,
+}
+
+impl<'g, 'p> PolicyReport<'g, 'p> {
+    fn new(
+        policy: &'p Policy,
+        packages: impl IntoIterator<Item = BuiltPackage<'g>>,
+        roots: impl IntoIterator<Item = &'g PackageId>,
+        links: impl IntoIterator<Item = PackageLink<'g>>,
+    ) -> Self {
+        let mut packages: Vec<_> = packages.into_iter().collect();
+        let chains = DependencyChains::new(
+            packages.iter().map(|package| package.metadata),
+            roots,
+            links,
+        );
+        packages.retain(|package| !package.metadata.in_workspace());
+        packages.sort_by_key(|package| {
+            let metadata = package.metadata;
+            (metadata.name(), metadata.version(), metadata.id())
+        });
+
+        let mut version_counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for package in &packages {
+            *version_counts.entry(package.metadata.name()).or_default() += 1;
+        }
+
+        let mut violations = vec![];
+        for built in &packages {
+            let package = &built.metadata;
+            let mut kinds = vec![];
+            kinds.extend(
+                policy
+                    .bans
+                    .iter()
+                    .filter(|rule| rule.matches(package))
+                    .map(ViolationKind::Banned),
+            );
+            if let Some(license) = package.license() {
+                let licenses = denied_licenses(license, &policy.licenses.deny);
+                if !licenses.is_empty() {
+                    kinds.push(ViolationKind::DeniedLicense { licenses });
+                }
+            }
+            match package.source().parse_external() {
+                Some(ExternalSource::Registry(registry))
+                    if !policy.is_registry_allowed(package, registry) =>
+                {
+                    kinds.push(ViolationKind::UnlistedRegistry { registry });
+                }
+                Some(ExternalSource::Git { repository, .. })
+                    if !policy.is_git_allowed(repository) =>
+                {
+                    kinds.push(ViolationKind::UnlistedGitRepository { repository });
+                }
+                _ => {}
+            }
+            if let Some(max) = policy.duplicates.max_versions {
+                let count = version_counts[package.name()];
+                if count > max && !policy.duplicates.skip.contains(package.name()) {
+                    kinds.push(ViolationKind::DuplicateVersions { count, max });
+                }
+            }
+
+            if kinds.is_empty() {
+                continue;
+            }
+            let package_chains = chains.chains_to(package.id());
+            violations.extend(kinds.into_iter().map(|kind| PolicyViolation {
+                kind,
+                package: *package,
+                build_platforms: built.build_platforms(),
+                chains: package_chains.clone(),
+            }));
+        }
+
+        Self { violations }
+    }
+
+    /// Returns the policy violations, sorted by package name and version.
+    ///
+    /// Violations for the same package are in the order the rules are documented in: bans,
+    /// licenses, sources and duplicates.
+    pub fn violations(&self) -> &[PolicyViolation<'g, 'p>] {
+        &self.violations
+    }
+
+    /// Returns true if the evaluated packages comply with the policy.
+    pub fn is_empty(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// A package that violates a rule in a [`Policy`].
+///
+/// Requires the `policy` feature to be enabled.
+#[derive(Clone, Debug)]
+pub struct PolicyViolation<'g, 'p> {
+    kind: ViolationKind<'g, 'p>,
+    package: PackageMetadata<'g>,
+    build_platforms: Vec<BuildPlatform>,
+    chains: Vec<Vec<PackageMetadata<'g>>>,
+}
+
+impl<'g, 'p> PolicyViolation<'g, 'p> {
+    /// Returns the kind of rule that was violated.
+    pub fn kind(&self) -> &ViolationKind<'g, 'p> {
+        &self.kind
+    }
+
+    /// Returns the package that violates the rule.
+    pub fn package(&self) -> PackageMetadata<'g> {
+        self.package
+    }
+
+    /// Returns the build platforms the package is built on.
+    ///
+    /// This is empty for evaluations of package sets, which don't track build platforms.
+    pub fn build_platforms(&self) -> &[BuildPlatform] {
+        &self.build_platforms
+    }
+
+    /// Returns dependency chains through which the package is included.
+    ///
+    /// There is one chain for each root package that the package is reachable from: the shortest
+    /// path from the root to the package, inclusive of both. Chains are sorted by root package
+    /// ID.
+    pub fn chains(&self) -> &[Vec<PackageMetadata<'g>>] {
+        &self.chains
+    }
+}
+
+/// The kind of rule violated by a package.
+///
+/// The `Display` implementation describes the violation.
+///
+/// Requires the `policy` feature to be enabled.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ViolationKind<'g, 'p> {
+    /// The package is banned.
+    Banned(&'p BanRule),
+
+    /// The package's license expression can't be satisfied without a denied license.
+    DeniedLicense {
+        /// The denied licenses in the expression.
+        licenses: Vec<&'g str>,
+    },
+
+    /// The package is from a registry that isn't allowed.
+    UnlistedRegistry {
+        /// The URL of the registry.
+        registry: &'g str,
+    },
+
+    /// The package is from a Git repository that isn't allowed.
+    UnlistedGitRepository {
+        /// The URL of the repository.
+        repository: &'g str,
+    },
+
+    /// More versions of the package are included than the policy allows.
+    DuplicateVersions {
+        /// The number of versions included.
+        count: usize,
+        /// The maximum number of versions allowed.
+        max: usize,
+    },
+}
+
+impl<'g, 'p> fmt::Display for ViolationKind<'g, 'p> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ViolationKind::Banned(rule) => {
+                write!(f, "package is banned")?;
+                if let Some(reason) = rule.reason() {
+                    write!(f, ": {}", reason)?;
+                }
+                Ok(())
+            }
+            ViolationKind::DeniedLicense { licenses } => {
+                write!(f, "license requires denied license {}", licenses.join(", "))
+            }
+            ViolationKind::UnlistedRegistry { registry } => {
+                write!(f, "registry {} isn't allowed", registry)
+            }
+            ViolationKind::UnlistedGitRepository { repository } => {
+                write!(f, "Git repository {} isn't allowed", repository)
+            }
+            ViolationKind::DuplicateVersions { count, max } => {
+                write!(f, "{} versions are included, more than {}", count, max)
+            }
+        }
+    }
+}
+
+/// Returns the denied licenses that make a license expression unacceptable, or an empty list if
+/// the expression can be satisfied without any denied licenses.
+///
+/// `/` is treated as `OR`, as in older Cargo license fields. Exceptions added with `WITH` don't
+/// affect the result. If the expression can't be parsed, it's unacceptable if it mentions any
+/// denied license.
+///
+/// License IDs are compared without their `-only`, `-or-later` and `+` suffixes, so that denying
+/// `GPL-3.0` also denies `GPL-3.0-only`, `GPL-3.0-or-later` and `GPL-3.0+`.
+fn denied_licenses<'g>(expression: &'g str, deny: &[String]) -> Vec<&'g str> {
+    if deny.is_empty() {
+        return vec![];
+    }
+    let is_denied = |license: &str| {
+        let license = license_base(license);
+        deny.iter()
+            .any(|denied| license_base(denied).eq_ignore_ascii_case(license))
+    };
+
+    let mut tokens = vec![];
+    for word in expression.split_whitespace() {
+        let mut rest = word;
+        while let Some(idx) = rest.find(['(', ')', '/']) {
+            if idx > 0 {
+                tokens.push(&rest[..idx]);
+            }
+            tokens.push(match &rest[idx..=idx] {
+                "/" => "OR",
+                paren => paren,
+            });
+            rest = &rest[idx + 1..];
+        }
+        if !rest.is_empty() {
+            tokens.push(rest);
+        }
+    }
+
+    let mut parser = LicenseParser {
+        tokens: &tokens,
+        pos: 0,
+        is_denied: &is_denied,
+    };
+    let acceptable = match parser.parse_or() {
+        Some(acceptable) if parser.pos == tokens.len() => acceptable,
+        _ => !tokens.iter().any(|token| is_denied(token)),
+    };
+    if acceptable {
+        return vec![];
+    }
+
+    let mut denied: Vec<_> = tokens
+        .into_iter()
+        .filter(|token| is_denied(token))
+        .collect();
+    denied.sort_unstable();
+    denied.dedup();
+    denied
+}
+
+/// Returns a license ID without its `-only`, `-or-later` or `+` suffix.
+fn license_base(license: &str) -> &str {
+    if let Some(base) = license.strip_suffix('+') {
+        return base;
+    }
+    for suffix in ["-only", "-or-later"] {
+        let split = license.len().saturating_sub(suffix.len());
+        if license.is_char_boundary(split) && license[split..].eq_ignore_ascii_case(suffix) {
+            return &license[..split];
+        }
+    }
+    license
+}
+
+/// A recursive descent parser for SPDX license expressions, which evaluates whether the
+/// expression can be satisfied without denied licenses.
+struct LicenseParser<'a> {
+    tokens: &'a [&'a str],
+    pos: usize,
+    is_denied: &'a dyn Fn(&str) -> bool,
+}
+
+impl<'a> LicenseParser<'a> {
+    fn parse_or(&mut self) -> Option<bool> {
+        let mut acceptable = self.parse_and()?;
+        while self.eat("OR") {
+            acceptable |= self.parse_and()?;
+        }
+        Some(acceptable)
+    }
+
+    fn parse_and(&mut self) -> Option<bool> {
+        let mut acceptable = self.parse_primary()?;
+        while self.eat("AND") {
+            acceptable &= self.parse_primary()?;
+        }
+        Some(acceptable)
+    }
+
+    fn parse_primary(&mut self) -> Option<bool> {
+        if self.eat("(") {
+            let acceptable = self.parse_or()?;
+            return self.eat(")").then_some(acceptable);
+        }
+        let license = self.next_license()?;
+        if self.eat("WITH") {
+            self.next_license()?;
+        }
+        Some(!(self.is_denied)(license))
+    }
+
+    fn next_license(&mut self) -> Option<&'a str> {
+        let token = *self.tokens.get(self.pos)?;
+        if ["(", ")", "OR", "AND", "WITH"]
+            .iter()
+            .any(|keyword| token.eq_ignore_ascii_case(keyword))
+        {
+            return None;
+        }
+        self.pos += 1;
+        Some(token)
+    }
+
+    fn eat(&mut self, keyword: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(token) if token.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn license_expressions() {
+        let deny = ["GPL-3.0".to_owned(), "AGPL-3.0".to_owned()];
+        for (expression, expected) in [
+            ("MIT", vec![]),
+            ("GPL-3.0", vec!["GPL-3.0"]),
+            ("gpl-3.0", vec!["gpl-3.0"]),
+            ("MIT OR GPL-3.0", vec![]),
+            ("MIT/GPL-3.0", vec![]),
+            ("MIT AND GPL-3.0", vec!["GPL-3.0"]),
+            ("MIT AND (Apache-2.0 OR GPL-3.0)", vec![]),
+            ("(MIT OR GPL-3.0) AND AGPL-3.0", vec!["AGPL-3.0", "GPL-3.0"]),
+            ("GPL-3.0 WITH Classpath-exception-2.0", vec!["GPL-3.0"]),
+            ("Apache-2.0 WITH LLVM-exception OR GPL-3.0", vec![]),
+            // Unparseable expressions are denied if they mention a denied license.
+            ("MIT GPL-3.0", vec!["GPL-3.0"]),
+            ("(MIT", vec![]),
+            // Version suffixes are ignored, both in expressions and in the deny list.
+            ("GPL-3.0-only", vec!["GPL-3.0-only"]),
+            ("GPL-3.0-or-later", vec!["GPL-3.0-or-later"]),
+            ("GPL-3.0+", vec!["GPL-3.0+"]),
+            ("gpl-3.0-ONLY", vec!["gpl-3.0-ONLY"]),
+            ("MIT OR GPL-3.0-or-later", vec![]),
+            (
+                "AGPL-3.0-only AND GPL-3.0+",
+                vec!["AGPL-3.0-only", "GPL-3.0+"],
+            ),
+            ("LGPL-3.0-only", vec![]),
+            ("GPL-2.0-or-later", vec![]),
+        ] {
+            assert_eq!(
+                denied_licenses(expression, &deny),
+                expected,
+                "for expression {expression}"
+            );
+        }
+        assert_eq!(denied_licenses("GPL-3.0", &[]), Vec::<&str>::new());
+        assert_eq!(
+            denied_licenses("GPL-3.0 OR GPL-2.0+", &["GPL-2.0-or-later".to_owned()]),
+            Vec::<&str>::new(),
+        );
+        assert_eq!(
+            denied_licenses("GPL-2.0", &["GPL-2.0-or-later".to_owned()]),
+            vec!["GPL-2.0"],
+        );
+    }
+}
@@ -5,6 +5,7 @@ use crate::{
     debug_ignore::DebugIgnore,
     graph::{
         feature::{FeatureFilter, FeatureSet},
+        query_core::reachable_map,
         resolve_core::{ResolveCore, Topo},
         DependencyDirection, PackageGraph, PackageIx, PackageLink, PackageLinkImpl,
         PackageMetadata, PackageQuery,
@@ -21,7 +22,7 @@ use camino::Utf8Path;
 use fixedbitset::FixedBitSet;
 use petgraph::{
     prelude::*,
-    visit::{NodeFiltered, NodeRef},
+    visit::{NodeFiltered, NodeRef, Reversed},
 };
 use std::fmt;
 
@@ -126,6 +127,48 @@ impl PackageGraph {
         })
     }
 
+    /// Resolves a batch of queries at once, returning a `PackageSet` for each query in order.
+    ///
+    /// This is equivalent to calling [`resolve`](PackageQuery::resolve) on each query, but work
+    /// is shared across queries: the set of packages reachable from each package is computed once,
+    /// and every query's result is assembled from those sets. For large numbers of queries, this
+    /// is much faster than resolving each one independently.
+    ///
+    /// Memory use is proportional to the number of packages reachable from any initial, multiplied
+    /// by the number of packages in the graph.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the queries are for a different package graph.
+    pub fn resolve_many<'g>(
+        &'g self,
+        queries: impl IntoIterator<Item = PackageQuery<'g>>,
+    ) -> Vec<PackageSet<'g>> {
+        let queries: Vec<_> = queries.into_iter().collect();
+        assert!(
+            queries
+                .iter()
+                .all(|query| ::std::ptr::eq(self, query.graph)),
+            "all queries must be for this package graph"
+        );
+
+        let mut forward = None;
+        let mut reverse = None;
+        queries
+            .iter()
+            .map(|query| {
+                let reach =
+                    match query.direction() {
+                        DependencyDirection::Forward => forward
+                            .get_or_insert_with(|| SharedReach::new(self, &queries, Outgoing)),
+                        DependencyDirection::Reverse => reverse
+                            .get_or_insert_with(|| SharedReach::new(self, &queries, Incoming)),
+                    };
+                PackageSet::from_included(self, reach.resolve(query.params.initials()))
+            })
+            .collect()
+    }
+
     /// Creates a new `PackageSet` consisting of packages with the given name.
     ///
     /// The result is empty if there are no packages with the given name.
@@ -150,6 +193,85 @@ impl PackageGraph {
     }
 }
 
+/// Reachability information shared across queries in one direction, used by
+/// `PackageGraph::resolve_many`.
+struct SharedReach {
+    // The index of the SCC each package belongs to.
+    scc_of: Vec<usize>,
+    // The packages reachable from each SCC. Only computed for SCCs reachable from an initial.
+    reach: Vec<Option<FixedBitSet>>,
+}
+
+impl SharedReach {
+    fn new(graph: &PackageGraph, queries: &[PackageQuery<'_>], direction: Direction) -> Self {
+        let dep_graph = &graph.dep_graph;
+        let node_count = dep_graph.node_count();
+        let sccs: Vec<_> = graph.sccs().iter().collect();
+
+        let mut scc_of = vec![0; node_count];
+        for (scc_idx, scc) in sccs.iter().enumerate() {
+            for package_ix in *scc {
+                scc_of[package_ix.index()] = scc_idx;
+            }
+        }
+
+        // Only compute reachability for packages that some query can reach.
+        let initials: Vec<_> = queries
+            .iter()
+            .filter(|query| query.params.direction() == direction.into())
+            .flat_map(|query| query.params.initials().iter().copied())
+            .collect();
+        let (relevant, _) = match direction {
+            Outgoing => reachable_map(dep_graph, initials),
+            Incoming => reachable_map(Reversed(dep_graph), initials),
+        };
+
+        // SCCs are in forward topological order, so iterate in reverse for forward queries. This
+        // ensures that an SCC's neighbors are always processed before the SCC itself.
+        let mut order: Vec<_> = (0..sccs.len()).collect();
+        if direction == Outgoing {
+            order.reverse();
+        }
+
+        let mut reach: Vec<Option<FixedBitSet>> = vec![None; sccs.len()];
+        for scc_idx in order {
+            let scc = sccs[scc_idx];
+            if !relevant.contains(scc[0].index()) {
+                continue;
+            }
+            let mut included = FixedBitSet::with_capacity(node_count);
+            for package_ix in scc {
+                included.insert(package_ix.index());
+                for neighbor_ix in dep_graph.neighbors_directed(*package_ix, direction) {
+                    let neighbor_scc_idx = scc_of[neighbor_ix.index()];
+                    if neighbor_scc_idx != scc_idx {
+                        included.union_with(
+                            reach[neighbor_scc_idx]
+                                .as_ref()
+                                .expect("neighbors are processed first"),
+                        );
+                    }
+                }
+            }
+            reach[scc_idx] = Some(included);
+        }
+
+        Self { scc_of, reach }
+    }
+
+    fn resolve(&self, initials: &[NodeIndex<PackageIx>]) -> FixedBitSet {
+        let mut included = FixedBitSet::with_capacity(self.scc_of.len());
+        for package_ix in initials {
+            included.union_with(
+                self.reach[self.scc_of[package_ix.index()]]
+                    .as_ref()
+                    .expect("initials are reachable"),
+            );
+        }
+        included
+    }
+}
+
 /// A set of resolved packages in a package graph.
 ///
 /// Created by `PackageQuery::resolve`.
@@ -177,6 +299,17 @@ impl<'g> PackageSet<'g> {
         }
     }
 
+    pub(super) fn from_core(graph: &'g PackageGraph, core: ResolveCore<PackageGraph>) -> Self {
+        Self {
+            graph: DebugIgnore(graph),
+            core,
+        }
+    }
+
+    pub(super) fn into_parts(self) -> (&'g PackageGraph, ResolveCore<PackageGraph>) {
+        (self.graph.0, self.core)
+    }
+
     pub(super) fn with_resolver(
         query: PackageQuery<'g>,
         mut resolver: impl PackageResolver<'g>,
@@ -403,6 +536,25 @@ impl<'g> PackageSet<'g> {
         FeatureSet::from_included(feature_graph, included)
     }
 
+    // ---
+    // Conversion to PackageGraph
+    // ---
+
+    /// Creates a new, standalone `PackageGraph` consisting of the packages in this set and the
+    /// links between them.
+    ///
+    /// The new graph doesn't borrow from the original graph, so it can be stored or passed around
+    /// independently of it. Links to packages outside this set are dropped, and workspace members
+    /// outside this set are no longer part of the workspace.
+    ///
+    /// Package metadata, such as optional dependencies and named features, is copied over
+    /// unchanged. This means that the new graph may contain features that refer to dependencies
+    /// that are no longer in the graph. As with metadata generated with `--no-deps`, such
+    /// dependencies are treated as unresolved.
+    pub fn into_graph(self) -> PackageGraph {
+        self.graph.subgraph(&self.core.included)
+    }
+
     // ---
     // Iterators
     // ---
@@ -0,0 +1,183 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Generate software bills of materials (SBOMs) from `PackageSet` and `CargoSet` instances.
+//!
+//! Two formats are supported:
+//!
+//! * [CycloneDX](https://cyclonedx.org/) 1.5 JSON documents, through [`CycloneDxBom`].
+//! * [SPDX](https://spdx.dev/) 2.3 documents, in either the tag-value or the JSON format, through
+//!   [`SpdxDocument`].
+//!
+//! Dependency snapshots for GitHub's Dependency Submission API can also be generated through
+//! [`GitHubSnapshot`].
+//!
+//! `cargo metadata` doesn't include package checksums, so to include them in an SBOM, read them
+//! from the workspace's `Cargo.lock` through [`CargoLockChecksums`] and pass them in through
+//! [`SbomOptions::set_checksums`].
+//!
+//! Requires the `sbom` feature to be enabled.
+
+mod checksums;
+mod cyclonedx;
+mod github;
+mod spdx;
+
+use crate::{
+    graph::{
+        cargo::CargoSet, chains::BuiltPackage, DependencyDirection, PackageLink, PackageMetadata,
+        PackageSet,
+    },
+    PackageId,
+};
+pub use checksums::*;
+pub use cyclonedx::*;
+pub use github::*;
+pub use spdx::*;
+use std::collections::BTreeMap;
+
+/// Options for generating an SBOM.
+///
+/// Requires the `sbom` feature to be enabled.
+#[derive(Clone, Debug, Default)]
+pub struct SbomOptions<'a> {
+    checksums: Option<&'a CargoLockChecksums>,
+    timestamp: Option<String>,
+    serial_number: Option<String>,
+    document_name: Option<String>,
+}
+
+impl<'a> SbomOptions<'a> {
+    /// Creates a new `SbomOptions` with no checksums, timestamp or serial number.
+    ///
+    /// By default, generated CycloneDX SBOMs are fully determined by their inputs. SPDX documents
+    /// must have a creation timestamp, so the current time is used if one isn't set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the checksums to record for each package, typically read from `Cargo.lock`.
+    pub fn set_checksums(&mut self, checksums: &'a CargoLockChecksums) -> &mut Self {
+        self.checksums = Some(checksums);
+        self
+    }
+
+    /// Sets the creation timestamp to record in the SBOM, in RFC 3339 format (for example
+    /// `2024-07-01T12:00:00Z`).
+    pub fn set_timestamp(&mut self, timestamp: impl Into<String>) -> &mut Self {
+        self.timestamp = Some(timestamp.into());
+        self
+    }
+
+    /// Sets a unique identifier for the SBOM.
+    ///
+    /// For CycloneDX, this is the serial number, which must be a UUID URN of the form
+    /// `urn:uuid:...`. For SPDX, this is the document namespace, which must be a URI.
+    pub fn set_serial_number(&mut self, serial_number: impl Into<String>) -> &mut Self {
+        self.serial_number = Some(serial_number.into());
+        self
+    }
+
+    /// Sets the name of the SBOM document.
+    ///
+    /// This is only used by SPDX. By default, the names of the root packages are used.
+    pub fn set_document_name(&mut self, document_name: impl Into<String>) -> &mut Self {
+        self.document_name = Some(document_name.into());
+        self
+    }
+}
+
+/// The packages and dependency links that make up an SBOM.
+struct SbomInput<'g> {
+    /// Packages sorted by name, version and ID.
+    packages: Vec<BuiltPackage<'g>>,
+    /// The packages the SBOM describes: the initials for cargo sets, and the packages without
+    /// any dependents for package sets. Sorted by package ID.
+    roots: Vec<&'g PackageId>,
+    /// Links between packages, sorted by the IDs of their endpoints.
+    links: Vec<SbomLink<'g>>,
+}
+
+struct SbomLink<'g> {
+    link: PackageLink<'g>,
+    /// Whether this link is followed as a dev-dependency.
+    dev: bool,
+}
+
+impl<'g> SbomInput<'g> {
+    fn from_package_set(package_set: &PackageSet<'g>) -> Self {
+        let packages = package_set
+            .packages(DependencyDirection::Forward)
+            .map(BuiltPackage::new)
+            .collect();
+        let links = package_set
+            .links(DependencyDirection::Forward)
+            .map(|link| SbomLink {
+                link,
+                dev: link.dev().is_present(),
+            });
+        Self::new(
+            packages,
+            package_set.root_ids(DependencyDirection::Forward).collect(),
+            links,
+        )
+    }
+
+    fn from_cargo_set(cargo_set: &CargoSet<'g>) -> Self {
+        let links = cargo_set
+            .followed_links()
+            .map(|(link, dev)| SbomLink { link, dev });
+
+        let roots = cargo_set
+            .initials()
+            .to_package_set()
+            .package_ids(DependencyDirection::Forward)
+            .collect();
+        Self::new(BuiltPackage::from_cargo_set(cargo_set), roots, links)
+    }
+
+    fn new(
+        mut packages: Vec<BuiltPackage<'g>>,
+        mut roots: Vec<&'g PackageId>,
+        links: impl IntoIterator<Item = SbomLink<'g>>,
+    ) -> Self {
+        packages.sort_by_key(|package| {
+            let metadata = package.metadata;
+            (metadata.name(), metadata.version(), metadata.id())
+        });
+        roots.sort_unstable();
+        // Deduplicate links, since a link can be followed on both build platforms.
+        let mut deduped: BTreeMap<_, SbomLink<'g>> = BTreeMap::new();
+        for link in links {
+            deduped
+                .entry((link.link.from().id(), link.link.to().id()))
+                .and_modify(|existing| existing.dev |= link.dev)
+                .or_insert(link);
+        }
+        Self {
+            packages,
+            roots,
+            links: deduped.into_values().collect(),
+        }
+    }
+}
+
+/// Converts a Cargo license field into an SPDX license expression.
+///
+/// Older crates use `/` as a separator, which is equivalent to `OR`.
+fn license_expression(license: &str) -> String {
+    license
+        .split('/')
+        .map(|part| part.trim())
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
+
+/// Returns the [package URL](https://github.com/package-url/purl-spec) for this package, if it's
+/// from crates.io.
+fn purl(metadata: &PackageMetadata<'_>) -> Option<String> {
+    metadata
+        .source()
+        .is_crates_io()
+        .then(|| format!("pkg:cargo/{}@{}", metadata.name(), metadata.version()))
+}
@@ -0,0 +1,134 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::graph::{PackageMetadata, PackageSource};
+use semver::Version;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use toml::value::Table;
+
+/// Package checksums read from a `Cargo.lock` file.
+///
+/// Cargo records a SHA-256 checksum for every package downloaded from a registry. These checksums
+/// aren't part of `cargo metadata` output, so they need to be read from `Cargo.lock` separately.
+///
+/// Requires the `sbom` feature to be enabled.
+///
+/// # Examples
+///
+/// ```
+/// use guppy::{graph::sbom::CargoLockChecksums, CargoMetadata, PackageId};
+///
+/// static CARGO_LOCK: &str = r#"
+/// version = 3
+///
+/// [[package]]
+/// name = "bytes"
+/// version = "0.5.4"
+/// source = "registry+https://github.com/rust-lang/crates.io-index"
+/// checksum = "130aac562c0dd69c56b3b1cc8ffd2e17be31d0b6c25b61c96b76231aa23e39e1"
+/// "#;
+///
+/// let checksums = CargoLockChecksums::parse(CARGO_LOCK).expect("Cargo.lock is valid");
+///
+/// let metadata = CargoMetadata::parse_json(include_str!("../../../../fixtures/small/metadata_dups.json")).unwrap();
+/// let graph = metadata.build_graph().unwrap();
+/// let bytes = graph
+///     .metadata(&PackageId::new("bytes 0.5.4 (registry+https://github.com/rust-lang/crates.io-index)"))
+///     .unwrap();
+/// assert_eq!(
+///     checksums.get(&bytes),
+///     Some("130aac562c0dd69c56b3b1cc8ffd2e17be31d0b6c25b61c96b76231aa23e39e1"),
+/// );
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CargoLockChecksums {
+    // Keyed by name, version and source.
+    checksums: BTreeMap<(String, Version, String), String>,
+}
+
+impl CargoLockChecksums {
+    /// Reads checksums from the contents of a `Cargo.lock` file.
+    ///
+    /// Both the current format, with a `checksum` field for each package, and the original
+    /// format, with checksums stored in a `[metadata]` table, are supported.
+    pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        let lockfile: Lockfile = toml::from_str(s)?;
+        let mut checksums = BTreeMap::new();
+        for package in lockfile.package {
+            if let (Some(source), Some(checksum)) = (package.source, package.checksum) {
+                checksums.insert((package.name, package.version, source), checksum);
+            }
+        }
+
+        // The original format has keys of the form "checksum <name> <version> (<source>)".
+        for (key, value) in &lockfile.metadata {
+            let (Some(rest), Some(checksum)) = (key.strip_prefix("checksum "), value.as_str())
+            else {
+                continue;
+            };
+            let mut parts = rest.splitn(3, ' ');
+            let (Some(name), Some(version), Some(source)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            let source = source.trim_start_matches('(').trim_end_matches(')');
+            if let Ok(version) = version.parse() {
+                // Packages without a checksum are recorded as "<none>".
+                if checksum != "<none>" {
+                    checksums.insert(
+                        (name.to_owned(), version, source.to_owned()),
+                        checksum.to_owned(),
+                    );
+                }
+            }
+        }
+
+        Ok(Self { checksums })
+    }
+
+    /// Returns the checksum for this package, if one was recorded.
+    ///
+    /// Packages that aren't from a registry, such as workspace and path dependencies, don't have
+    /// checksums.
+    pub fn get(&self, metadata: &PackageMetadata<'_>) -> Option<&str> {
+        let source = match metadata.source() {
+            PackageSource::External(source) => source,
+            PackageSource::Workspace(_) | PackageSource::Path(_) => return None,
+        };
+        self.checksums
+            .get(&(
+                metadata.name().to_owned(),
+                metadata.version().clone(),
+                source.to_owned(),
+            ))
+            .map(|checksum| checksum.as_str())
+    }
+
+    /// Returns the number of checksums recorded.
+    pub fn len(&self) -> usize {
+        self.checksums.len()
+    }
+
+    /// Returns true if no checksums were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.checksums.is_empty()
+    }
+}
+
+#[derive(Deserialize)]
+struct Lockfile {
+    #[serde(default)]
+    package: Vec<LockfilePackage>,
+    #[serde(default)]
+    metadata: Table,
+}
+
+#[derive(Deserialize)]
+struct LockfilePackage {
+    name: String,
+    version: Version,
+    source: Option<String>,
+    checksum: Option<String>,
+}
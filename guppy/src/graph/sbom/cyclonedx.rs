@@ -0,0 +1,281 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::graph::{
+    cargo::CargoSet,
+    chains::BuiltPackage,
+    sbom::{license_expression, purl, SbomInput, SbomLink, SbomOptions},
+    PackageSet,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// A [CycloneDX](https://cyclonedx.org/) 1.5 software bill of materials.
+///
+/// Each package becomes a `library` component, identified (through its `bom-ref`) by its package
+/// ID. Components include:
+///
+/// * license expressions, with `/` separators in older license fields converted to `OR`;
+/// * SHA-256 hashes, if [checksums](super::CargoLockChecksums) were provided;
+/// * [package URLs](https://github.com/package-url/purl-spec) for packages from crates.io;
+/// * links to the repository and homepage;
+/// * enabled features, recorded as `cargo:target-feature` and `cargo:host-feature` properties.
+///
+/// Dependencies between packages are recorded in the `dependencies` section.
+///
+/// Requires the `sbom` feature to be enabled.
+///
+/// # Examples
+///
+/// ```
+/// use guppy::{
+///     graph::{
+///         cargo::{CargoOptions, CargoResolverVersion},
+///         feature::StandardFeatures,
+///         sbom::{CycloneDxBom, SbomOptions},
+///     },
+///     CargoMetadata,
+/// };
+///
+/// let metadata = CargoMetadata::parse_json(include_str!("../../../../fixtures/small/metadata1.json")).unwrap();
+/// let graph = metadata.build_graph().unwrap();
+///
+/// let workspace = graph.resolve_workspace().to_feature_set(StandardFeatures::Default);
+/// let cargo_set = workspace
+///     .into_cargo_set(CargoOptions::new().set_resolver(CargoResolverVersion::V2))
+///     .unwrap();
+///
+/// let bom = CycloneDxBom::from_cargo_set(&cargo_set, &SbomOptions::new());
+/// let json = bom.to_json_string().unwrap();
+/// assert!(json.contains(r#""specVersion": "1.5""#));
+/// ```
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxBom {
+    bom_format: &'static str,
+    spec_version: &'static str,
+    version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    serial_number: Option<String>,
+    metadata: Metadata,
+    components: Vec<Component>,
+    dependencies: Vec<Dependency>,
+}
+
+impl CycloneDxBom {
+    /// Creates a new CycloneDX SBOM from the packages built by a `CargoSet`.
+    ///
+    /// The SBOM includes packages built on both the target and host platforms. Dev-dependencies
+    /// are only recorded for initial packages, and only if they're built (for example, through
+    /// [`CargoOptions::set_include_dev`](crate::graph::cargo::CargoOptions::set_include_dev)).
+    pub fn from_cargo_set(cargo_set: &CargoSet<'_>, options: &SbomOptions<'_>) -> Self {
+        Self::new(SbomInput::from_cargo_set(cargo_set), options)
+    }
+
+    /// Creates a new CycloneDX SBOM from a `PackageSet`.
+    ///
+    /// Package sets don't track features, so no feature properties are recorded. All links between
+    /// packages in the set, including dev-dependencies, are recorded.
+    pub fn from_package_set(package_set: &PackageSet<'_>, options: &SbomOptions<'_>) -> Self {
+        Self::new(SbomInput::from_package_set(package_set), options)
+    }
+
+    /// Returns the number of components in this SBOM.
+    pub fn component_count(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Serializes this SBOM to a pretty-printed JSON string.
+    pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    fn new(input: SbomInput<'_>, options: &SbomOptions<'_>) -> Self {
+        let components = input
+            .packages
+            .iter()
+            .map(|package| Component::new(package, options))
+            .collect();
+
+        // Every component gets an entry, even if it has no dependencies: this indicates that the
+        // dependencies are known rather than missing.
+        let mut dependencies: BTreeMap<String, Vec<String>> = input
+            .packages
+            .iter()
+            .map(|package| (package.metadata.id().repr().to_owned(), vec![]))
+            .collect();
+        for SbomLink { link, .. } in &input.links {
+            if let Some(depends_on) = dependencies.get_mut(link.from().id().repr()) {
+                depends_on.push(link.to().id().repr().to_owned());
+            }
+        }
+        let dependencies = dependencies
+            .into_iter()
+            .map(|(dep_ref, depends_on)| Dependency {
+                dep_ref,
+                depends_on,
+            })
+            .collect();
+
+        Self {
+            bom_format: "CycloneDX",
+            spec_version: "1.5",
+            version: 1,
+            serial_number: options.serial_number.clone(),
+            metadata: Metadata {
+                timestamp: options.timestamp.clone(),
+                tools: Tools {
+                    components: vec![Tool {
+                        tool_type: "application",
+                        name: "guppy",
+                        version: env!("CARGO_PKG_VERSION"),
+                    }],
+                },
+            },
+            components,
+            dependencies,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Metadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<String>,
+    tools: Tools,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Tools {
+    components: Vec<Tool>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Tool {
+    #[serde(rename = "type")]
+    tool_type: &'static str,
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Component {
+    #[serde(rename = "type")]
+    component_type: &'static str,
+    #[serde(rename = "bom-ref")]
+    bom_ref: String,
+    name: String,
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hashes: Vec<Hash>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    licenses: Vec<License>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    purl: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    external_references: Vec<ExternalReference>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    properties: Vec<Property>,
+}
+
+impl Component {
+    fn new(package: &BuiltPackage<'_>, options: &SbomOptions<'_>) -> Self {
+        let metadata = package.metadata;
+
+        let hashes = options
+            .checksums
+            .and_then(|checksums| checksums.get(&metadata))
+            .map(|checksum| Hash {
+                alg: "SHA-256",
+                content: checksum.to_owned(),
+            })
+            .into_iter()
+            .collect();
+        let licenses = metadata
+            .license()
+            .map(|license| License {
+                expression: license_expression(license),
+            })
+            .into_iter()
+            .collect();
+
+        let mut external_references = vec![];
+        if let Some(repository) = metadata.repository() {
+            external_references.push(ExternalReference {
+                reference_type: "vcs",
+                url: repository.to_owned(),
+            });
+        }
+        if let Some(homepage) = metadata.homepage() {
+            external_references.push(ExternalReference {
+                reference_type: "website",
+                url: homepage.to_owned(),
+            });
+        }
+
+        let mut properties = vec![];
+        for (name, features) in [
+            ("cargo:target-feature", &package.target_features),
+            ("cargo:host-feature", &package.host_features),
+        ] {
+            properties.extend(features.iter().flatten().map(|feature| Property {
+                name,
+                value: (*feature).to_owned(),
+            }));
+        }
+        if let Some(license_file) = metadata.license_file() {
+            properties.push(Property {
+                name: "cargo:license-file",
+                value: license_file.to_string(),
+            });
+        }
+
+        Self {
+            component_type: "library",
+            bom_ref: metadata.id().repr().to_owned(),
+            name: metadata.name().to_owned(),
+            version: metadata.version().to_string(),
+            description: metadata.description().map(|s| s.to_owned()),
+            hashes,
+            licenses,
+            purl: purl(&metadata),
+            external_references,
+            properties,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Hash {
+    alg: &'static str,
+    content: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct License {
+    expression: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct ExternalReference {
+    #[serde(rename = "type")]
+    reference_type: &'static str,
+    url: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Property {
+    name: &'static str,
+    value: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Dependency {
+    #[serde(rename = "ref")]
+    dep_ref: String,
+    depends_on: Vec<String>,
+}
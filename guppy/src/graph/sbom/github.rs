@@ -0,0 +1,379 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::{
+        cargo::CargoSet,
+        sbom::{purl, spdx::format_timestamp},
+        DependencyDirection, ExternalSource, PackageMetadata,
+    },
+    PackageId,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::SystemTime,
+};
+
+/// Options for generating a [`GitHubSnapshot`].
+///
+/// The commit, ref and job are required by GitHub's Dependency Submission API. In GitHub Actions,
+/// they're typically `$GITHUB_SHA`, `$GITHUB_REF`, a string identifying the workflow and job (for
+/// example `"$GITHUB_WORKFLOW $GITHUB_JOB"`), and `$GITHUB_RUN_ID`.
+///
+/// Requires the `sbom` feature to be enabled.
+#[derive(Clone, Debug)]
+pub struct GitHubSnapshotOptions {
+    sha: String,
+    git_ref: String,
+    job_correlator: String,
+    job_id: String,
+    job_html_url: Option<String>,
+    scanned: Option<String>,
+}
+
+impl GitHubSnapshotOptions {
+    /// Creates a new `GitHubSnapshotOptions`.
+    ///
+    /// * `sha` is the 40-character commit hash the snapshot was computed for.
+    /// * `git_ref` is the fully qualified Git ref, for example `refs/heads/main`.
+    /// * `job_correlator` identifies the job across runs: snapshots with the same correlator
+    ///   replace each other.
+    /// * `job_id` identifies this particular run of the job.
+    pub fn new(
+        sha: impl Into<String>,
+        git_ref: impl Into<String>,
+        job_correlator: impl Into<String>,
+        job_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            sha: sha.into(),
+            git_ref: git_ref.into(),
+            job_correlator: job_correlator.into(),
+            job_id: job_id.into(),
+            job_html_url: None,
+            scanned: None,
+        }
+    }
+
+    /// Sets the URL of the job's run, shown on GitHub next to the submitted dependencies.
+    pub fn set_job_html_url(&mut self, job_html_url: impl Into<String>) -> &mut Self {
+        self.job_html_url = Some(job_html_url.into());
+        self
+    }
+
+    /// Sets the time the dependencies were scanned, in RFC 3339 format (for example
+    /// `2024-07-01T12:00:00Z`).
+    ///
+    /// By default, the current time is used.
+    pub fn set_scanned(&mut self, scanned: impl Into<String>) -> &mut Self {
+        self.scanned = Some(scanned.into());
+        self
+    }
+}
+
+/// A snapshot for GitHub's
+/// [Dependency Submission API](https://docs.github.com/en/rest/dependency-graph/dependency-submission).
+///
+/// Each initial package of the `CargoSet` becomes a manifest, keyed by the path to its
+/// `Cargo.toml` relative to the workspace root. A manifest lists every third-party package built
+/// for it, with:
+///
+/// * its [package URL](https://github.com/package-url/purl-spec), which also identifies it in the
+///   manifest. Packages from registries other than crates.io have a `repository_url` qualifier,
+///   and Git packages have a `vcs_url` qualifier;
+/// * its relationship: `direct` if the manifest declares it, `indirect` otherwise;
+/// * its scope: `development` if it's only built through dev-dependencies of the manifest,
+///   `runtime` otherwise;
+/// * the package URLs of its own dependencies.
+///
+/// Workspace packages aren't listed as dependencies, but the packages they pull in are.
+///
+/// Requires the `sbom` feature to be enabled.
+///
+/// # Examples
+///
+/// ```
+/// use guppy::{
+///     graph::{
+///         cargo::CargoOptions,
+///         feature::StandardFeatures,
+///         sbom::{GitHubSnapshot, GitHubSnapshotOptions},
+///     },
+///     CargoMetadata,
+/// };
+///
+/// let metadata = CargoMetadata::parse_json(include_str!("../../../../fixtures/small/metadata1.json")).unwrap();
+/// let graph = metadata.build_graph().unwrap();
+///
+/// let cargo_set = graph
+///     .resolve_workspace()
+///     .to_feature_set(StandardFeatures::Default)
+///     .into_cargo_set(&CargoOptions::new())
+///     .unwrap();
+///
+/// let options = GitHubSnapshotOptions::new(
+///     "ce34a1fc4e2a4f3e9e7fe1ba51a1a7e8bbbbbb2f",
+///     "refs/heads/main",
+///     "ci check",
+///     "1234",
+/// );
+/// let snapshot = GitHubSnapshot::from_cargo_set(&cargo_set, &options);
+/// assert_eq!(snapshot.manifest_count(), 1);
+/// let json = snapshot.to_json_string().unwrap();
+/// assert!(json.contains(r#""package_url": "pkg:cargo/datatest@0.4.2""#));
+/// ```
+#[derive(Clone, Debug, Serialize)]
+pub struct GitHubSnapshot {
+    version: u32,
+    sha: String,
+    #[serde(rename = "ref")]
+    git_ref: String,
+    job: Job,
+    detector: Detector,
+    scanned: String,
+    manifests: BTreeMap<String, Manifest>,
+}
+
+impl GitHubSnapshot {
+    /// Creates a new snapshot from the packages built by a `CargoSet`.
+    ///
+    /// Dev-dependencies are only recorded if they're built (for example, through
+    /// [`CargoOptions::set_include_dev`](crate::graph::cargo::CargoOptions::set_include_dev)).
+    pub fn from_cargo_set(cargo_set: &CargoSet<'_>, options: &GitHubSnapshotOptions) -> Self {
+        // Collect the dependencies followed by the simulation, deduplicating links followed on
+        // both build platforms. The flag is true if the link is only a dev-dependency.
+        let mut deps: BTreeMap<&PackageId, BTreeMap<&PackageId, (PackageMetadata<'_>, bool)>> =
+            BTreeMap::new();
+        for (link, _) in cargo_set.followed_links() {
+            let dev_only = !link.normal().is_present() && !link.build().is_present();
+            deps.entry(link.from().id())
+                .or_default()
+                .entry(link.to().id())
+                .and_modify(|(_, existing)| *existing &= dev_only)
+                .or_insert((link.to(), dev_only));
+        }
+
+        let manifests = cargo_set
+            .initials()
+            .to_package_set()
+            .packages(DependencyDirection::Forward)
+            .map(|package| {
+                let manifest = Manifest::new(package, &deps);
+                (manifest.file.source_location.clone(), manifest)
+            })
+            .collect();
+
+        Self {
+            version: 0,
+            sha: options.sha.clone(),
+            git_ref: options.git_ref.clone(),
+            job: Job {
+                correlator: options.job_correlator.clone(),
+                id: options.job_id.clone(),
+                html_url: options.job_html_url.clone(),
+            },
+            detector: Detector {
+                name: "guppy",
+                version: env!("CARGO_PKG_VERSION"),
+                url: env!("CARGO_PKG_REPOSITORY"),
+            },
+            scanned: options
+                .scanned
+                .clone()
+                .unwrap_or_else(|| format_timestamp(SystemTime::now())),
+            manifests,
+        }
+    }
+
+    /// Returns the number of manifests in this snapshot.
+    pub fn manifest_count(&self) -> usize {
+        self.manifests.len()
+    }
+
+    /// Serializes this snapshot to a pretty-printed JSON string, suitable as the body of a
+    /// request to the Dependency Submission API.
+    pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Job {
+    correlator: String,
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    html_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Detector {
+    name: &'static str,
+    version: &'static str,
+    url: &'static str,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Manifest {
+    name: String,
+    file: ManifestFile,
+    resolved: BTreeMap<String, ResolvedDependency>,
+}
+
+impl Manifest {
+    fn new<'g>(
+        package: PackageMetadata<'g>,
+        deps: &BTreeMap<&'g PackageId, BTreeMap<&'g PackageId, (PackageMetadata<'g>, bool)>>,
+    ) -> Self {
+        let root = package.id();
+        let deps_of = |id: &PackageId| deps.get(id).into_iter().flat_map(|deps| deps.values());
+
+        // Find every package built for this manifest, and whether it's built outside of its
+        // dev-dependencies. Dev-dependencies are only followed from the manifest's own package:
+        // other initials may be reached through normal dependencies, but their dev-dependencies
+        // aren't built for this manifest.
+        let mut reached: BTreeMap<&PackageId, (PackageMetadata<'g>, bool)> = BTreeMap::new();
+        let mut queue: VecDeque<_> = deps_of(root)
+            .map(|&(metadata, dev_only)| (metadata, !dev_only))
+            .collect();
+        while let Some((metadata, runtime)) = queue.pop_front() {
+            if metadata.id() == root {
+                continue;
+            }
+            match reached.get_mut(metadata.id()) {
+                Some((_, existing)) if *existing || !runtime => continue,
+                Some((_, existing)) => *existing = true,
+                None => {
+                    reached.insert(metadata.id(), (metadata, runtime));
+                }
+            }
+            queue.extend(
+                deps_of(metadata.id())
+                    .filter(|(_, dev_only)| !dev_only)
+                    .map(|&(dep, _)| (dep, runtime)),
+            );
+        }
+
+        let included = |metadata: &PackageMetadata<'_>| {
+            !metadata.source().is_workspace() && reached.contains_key(metadata.id())
+        };
+        let mut resolved: BTreeMap<String, ResolvedDependency> = BTreeMap::new();
+        for (metadata, runtime) in reached.values() {
+            if !included(metadata) {
+                continue;
+            }
+            let direct = deps
+                .get(root)
+                .is_some_and(|deps| deps.contains_key(metadata.id()));
+            let dependencies: Vec<_> = deps_of(metadata.id())
+                .filter(|(dep, dev_only)| !dev_only && included(dep))
+                .map(|(dep, _)| package_url(dep))
+                .collect();
+            // Distinct packages can share a package URL (for example, path dependencies with the
+            // same name and version), so merge them.
+            let package_url = package_url(metadata);
+            let entry = resolved
+                .entry(package_url.clone())
+                .or_insert_with(|| ResolvedDependency {
+                    package_url,
+                    relationship: Relationship::Indirect,
+                    scope: Scope::Development,
+                    dependencies: vec![],
+                });
+            if direct {
+                entry.relationship = Relationship::Direct;
+            }
+            if *runtime {
+                entry.scope = Scope::Runtime;
+            }
+            entry.dependencies.extend(dependencies);
+            entry.dependencies.sort_unstable();
+            entry.dependencies.dedup();
+        }
+
+        Self {
+            name: package.name().to_owned(),
+            file: ManifestFile {
+                source_location: source_location(&package),
+            },
+            resolved,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct ManifestFile {
+    source_location: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct ResolvedDependency {
+    package_url: String,
+    relationship: Relationship,
+    scope: Scope,
+    dependencies: Vec<String>,
+}
+
+#[derive(Copy, Clone, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Relationship {
+    Direct,
+    Indirect,
+}
+
+#[derive(Copy, Clone, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Scope {
+    Runtime,
+    Development,
+}
+
+/// Returns the path to the package's manifest relative to the workspace root, with `/` as the
+/// separator.
+fn source_location(package: &PackageMetadata<'_>) -> String {
+    let manifest_path = package.manifest_path();
+    let relative = manifest_path
+        .strip_prefix(package.graph().workspace().root())
+        .unwrap_or(manifest_path);
+    relative
+        .components()
+        .map(|component| component.as_str())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns the package URL for this package, qualified by its source if it isn't from crates.io.
+fn package_url(metadata: &PackageMetadata<'_>) -> String {
+    if let Some(purl) = purl(metadata) {
+        return purl;
+    }
+    let base = format!("pkg:cargo/{}@{}", metadata.name(), metadata.version());
+    match metadata.source().parse_external() {
+        Some(ExternalSource::Registry(registry)) => {
+            format!("{}?repository_url={}", base, encode_qualifier(registry))
+        }
+        Some(ExternalSource::Git {
+            repository,
+            resolved,
+            ..
+        }) => format!(
+            "{}?vcs_url={}",
+            base,
+            encode_qualifier(&format!("git+{}@{}", repository, resolved))
+        ),
+        _ => base,
+    }
+}
+
+/// Percent-encodes a package URL qualifier value.
+fn encode_qualifier(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~:/".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
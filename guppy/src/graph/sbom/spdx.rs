@@ -0,0 +1,499 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::{
+        cargo::CargoSet,
+        chains::BuiltPackage,
+        sbom::{license_expression, purl, SbomInput, SbomOptions},
+        PackageSet,
+    },
+    PackageId,
+};
+use ahash::AHashMap;
+use serde::Serialize;
+use std::{
+    fmt::{self, Write},
+    hash::Hasher,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use twox_hash::XxHash64;
+
+/// An [SPDX](https://spdx.dev/) 2.3 document describing a set of packages.
+///
+/// Each package becomes an SPDX package, with:
+///
+/// * the declared license, with `/` separators in older license fields converted to `OR`;
+/// * a SHA-256 checksum, if [checksums](super::CargoLockChecksums) were provided;
+/// * a download location and a [package URL](https://github.com/package-url/purl-spec) for
+///   packages from crates.io.
+///
+/// Dependencies are recorded as relationships, based on the kind of each dependency:
+///
+/// * `A DEPENDS_ON B` if `B` is a normal dependency of `A`;
+/// * `B BUILD_DEPENDENCY_OF A` if `B` is a build dependency of `A`;
+/// * `B DEV_DEPENDENCY_OF A` if `B` is a dev-dependency of `A`.
+///
+/// The document `DESCRIBES` its root packages.
+///
+/// ## Missing data
+///
+/// SPDX distinguishes between information that is known to be absent (`NONE`) and information
+/// that wasn't determined (`NOASSERTION`). `guppy` only has access to package metadata, so:
+///
+/// * the declared license is `NOASSERTION` if a package doesn't specify a license expression. If
+///   it specifies a license file instead, the path to the file is recorded as a license comment;
+/// * the concluded license and copyright text are always `NOASSERTION`;
+/// * the download location is `NOASSERTION` for packages not from crates.io.
+///
+/// Requires the `sbom` feature to be enabled.
+///
+/// # Examples
+///
+/// ```
+/// use guppy::{
+///     graph::sbom::{SbomOptions, SpdxDocument},
+///     CargoMetadata,
+/// };
+///
+/// let metadata = CargoMetadata::parse_json(include_str!("../../../../fixtures/small/metadata1.json")).unwrap();
+/// let graph = metadata.build_graph().unwrap();
+///
+/// let mut options = SbomOptions::new();
+/// options
+///     .set_timestamp("2024-07-01T12:00:00Z")
+///     .set_serial_number("https://example.com/spdxdocs/testcrate");
+/// let document = SpdxDocument::from_package_set(&graph.resolve_all(), &options);
+///
+/// let tag_value = document.to_tag_value();
+/// assert!(tag_value.starts_with("SPDXVersion: SPDX-2.3\n"));
+/// assert!(tag_value.contains("DocumentNamespace: https://example.com/spdxdocs/testcrate\n"));
+/// ```
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxDocument {
+    spdx_version: &'static str,
+    data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: &'static str,
+    name: String,
+    document_namespace: String,
+    creation_info: CreationInfo,
+    packages: Vec<Package>,
+    relationships: Vec<Relationship>,
+}
+
+impl SpdxDocument {
+    /// The value used for information that wasn't determined.
+    pub const NOASSERTION: &'static str = "NOASSERTION";
+
+    /// Creates a new SPDX document from a `PackageSet`.
+    ///
+    /// The document describes the packages in the set that don't have any dependents within it.
+    /// All links between packages in the set, including dev-dependencies, are recorded.
+    pub fn from_package_set(package_set: &PackageSet<'_>, options: &SbomOptions<'_>) -> Self {
+        Self::new(SbomInput::from_package_set(package_set), options)
+    }
+
+    /// Creates a new SPDX document from the packages built by a `CargoSet`.
+    ///
+    /// The document describes the initial packages. Packages built on both the target and host
+    /// platforms are included, and dev-dependencies are only recorded for initial packages.
+    pub fn from_cargo_set(cargo_set: &CargoSet<'_>, options: &SbomOptions<'_>) -> Self {
+        Self::new(SbomInput::from_cargo_set(cargo_set), options)
+    }
+
+    /// Returns the number of packages in this document.
+    pub fn package_count(&self) -> usize {
+        self.packages.len()
+    }
+
+    /// Serializes this document to a pretty-printed JSON string.
+    pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Serializes this document to the SPDX tag-value format.
+    pub fn to_tag_value(&self) -> String {
+        let mut out = String::new();
+        self.write_tag_value(&mut out)
+            .expect("writing to a string never fails");
+        out
+    }
+
+    fn new(input: SbomInput<'_>, options: &SbomOptions<'_>) -> Self {
+        let mut spdx_ids = SpdxIds::default();
+        let packages: Vec<_> = input
+            .packages
+            .iter()
+            .map(|package| Package::new(package, spdx_ids.insert(package), options))
+            .collect();
+
+        let mut relationships: Vec<_> = input
+            .roots
+            .iter()
+            .map(|root| Relationship {
+                spdx_element_id: "SPDXRef-DOCUMENT".to_owned(),
+                relationship_type: "DESCRIBES",
+                related_spdx_element: spdx_ids.get(root).to_owned(),
+            })
+            .collect();
+        for sbom_link in &input.links {
+            let link = sbom_link.link;
+            let from = spdx_ids.get(link.from().id());
+            let to = spdx_ids.get(link.to().id());
+            if link.normal().is_present() {
+                relationships.push(Relationship::new(from, "DEPENDS_ON", to));
+            }
+            if link.build().is_present() {
+                relationships.push(Relationship::new(to, "BUILD_DEPENDENCY_OF", from));
+            }
+            if sbom_link.dev {
+                relationships.push(Relationship::new(to, "DEV_DEPENDENCY_OF", from));
+            }
+        }
+
+        let name = options.document_name.clone().unwrap_or_else(|| {
+            let names: Vec<_> = input
+                .roots
+                .iter()
+                .filter_map(|root| packages.iter().find(|package| package.package_id == **root))
+                .map(|package| package.name.as_str())
+                .collect();
+            if names.is_empty() {
+                "sbom".to_owned()
+            } else {
+                names.join(", ")
+            }
+        });
+        let created = options
+            .timestamp
+            .clone()
+            .unwrap_or_else(|| format_timestamp(SystemTime::now()));
+        let document_namespace = options.serial_number.clone().unwrap_or_else(|| {
+            // Document namespaces must be unique, so derive one from the contents of the document.
+            let mut hasher = XxHash64::default();
+            hasher.write(name.as_bytes());
+            hasher.write(created.as_bytes());
+            for package in &packages {
+                hasher.write(package.package_id.repr().as_bytes());
+            }
+            format!(
+                "https://spdx.org/spdxdocs/{}-{:016x}",
+                sanitize(&name),
+                hasher.finish()
+            )
+        });
+
+        Self {
+            spdx_version: "SPDX-2.3",
+            data_license: "CC0-1.0",
+            spdx_id: "SPDXRef-DOCUMENT",
+            name,
+            document_namespace,
+            creation_info: CreationInfo {
+                created,
+                creators: vec![format!("Tool: guppy-{}", env!("CARGO_PKG_VERSION"))],
+            },
+            packages,
+            relationships,
+        }
+    }
+
+    fn write_tag_value(&self, out: &mut String) -> fmt::Result {
+        writeln!(out, "SPDXVersion: {}", self.spdx_version)?;
+        writeln!(out, "DataLicense: {}", self.data_license)?;
+        writeln!(out, "SPDXID: {}", self.spdx_id)?;
+        writeln!(out, "DocumentName: {}", self.name)?;
+        writeln!(out, "DocumentNamespace: {}", self.document_namespace)?;
+        for creator in &self.creation_info.creators {
+            writeln!(out, "Creator: {}", creator)?;
+        }
+        writeln!(out, "Created: {}", self.creation_info.created)?;
+
+        for package in &self.packages {
+            writeln!(out)?;
+            writeln!(out, "PackageName: {}", package.name)?;
+            writeln!(out, "SPDXID: {}", package.spdx_id)?;
+            writeln!(out, "PackageVersion: {}", package.version_info)?;
+            writeln!(
+                out,
+                "PackageDownloadLocation: {}",
+                package.download_location
+            )?;
+            writeln!(out, "FilesAnalyzed: {}", package.files_analyzed)?;
+            for checksum in &package.checksums {
+                writeln!(
+                    out,
+                    "PackageChecksum: {}: {}",
+                    checksum.algorithm, checksum.checksum_value
+                )?;
+            }
+            if let Some(homepage) = &package.homepage {
+                writeln!(out, "PackageHomePage: {}", homepage)?;
+            }
+            writeln!(
+                out,
+                "PackageLicenseConcluded: {}",
+                package.license_concluded
+            )?;
+            writeln!(out, "PackageLicenseDeclared: {}", package.license_declared)?;
+            if let Some(comments) = &package.license_comments {
+                writeln!(out, "PackageLicenseComments: {}", text(comments))?;
+            }
+            writeln!(out, "PackageCopyrightText: {}", package.copyright_text)?;
+            if let Some(description) = &package.description {
+                writeln!(out, "PackageDescription: {}", text(description))?;
+            }
+            for external_ref in &package.external_refs {
+                writeln!(
+                    out,
+                    "ExternalRef: {} {} {}",
+                    external_ref.reference_category,
+                    external_ref.reference_type,
+                    external_ref.reference_locator
+                )?;
+            }
+        }
+
+        if !self.relationships.is_empty() {
+            writeln!(out)?;
+        }
+        for relationship in &self.relationships {
+            writeln!(
+                out,
+                "Relationship: {} {} {}",
+                relationship.spdx_element_id,
+                relationship.relationship_type,
+                relationship.related_spdx_element
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct CreationInfo {
+    created: String,
+    creators: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Package {
+    #[serde(skip)]
+    package_id: PackageId,
+    name: String,
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    version_info: String,
+    download_location: String,
+    files_analyzed: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    checksums: Vec<Checksum>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    homepage: Option<String>,
+    license_concluded: &'static str,
+    license_declared: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    license_comments: Option<String>,
+    copyright_text: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    external_refs: Vec<ExternalRef>,
+}
+
+impl Package {
+    fn new(package: &BuiltPackage<'_>, spdx_id: String, options: &SbomOptions<'_>) -> Self {
+        let metadata = package.metadata;
+
+        let download_location = if metadata.source().is_crates_io() {
+            format!(
+                "https://crates.io/api/v1/crates/{}/{}/download",
+                metadata.name(),
+                metadata.version()
+            )
+        } else {
+            SpdxDocument::NOASSERTION.to_owned()
+        };
+        let checksums = options
+            .checksums
+            .and_then(|checksums| checksums.get(&metadata))
+            .map(|checksum| Checksum {
+                algorithm: "SHA256",
+                checksum_value: checksum.to_owned(),
+            })
+            .into_iter()
+            .collect();
+        let license_declared = metadata
+            .license()
+            .map_or_else(|| SpdxDocument::NOASSERTION.to_owned(), license_expression);
+        let license_comments = metadata
+            .license_file()
+            .map(|license_file| format!("License file: {}", license_file));
+        let external_refs = purl(&metadata)
+            .map(|purl| ExternalRef {
+                reference_category: "PACKAGE-MANAGER",
+                reference_type: "purl",
+                reference_locator: purl,
+            })
+            .into_iter()
+            .collect();
+
+        Self {
+            package_id: metadata.id().clone(),
+            name: metadata.name().to_owned(),
+            spdx_id,
+            version_info: metadata.version().to_string(),
+            download_location,
+            files_analyzed: false,
+            checksums,
+            homepage: metadata.homepage().map(|s| s.to_owned()),
+            license_concluded: SpdxDocument::NOASSERTION,
+            license_declared,
+            license_comments,
+            copyright_text: SpdxDocument::NOASSERTION,
+            description: metadata.description().map(|s| s.to_owned()),
+            external_refs,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Checksum {
+    algorithm: &'static str,
+    checksum_value: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExternalRef {
+    reference_category: &'static str,
+    reference_type: &'static str,
+    reference_locator: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Relationship {
+    spdx_element_id: String,
+    relationship_type: &'static str,
+    related_spdx_element: String,
+}
+
+impl Relationship {
+    fn new(from: &str, relationship_type: &'static str, to: &str) -> Self {
+        Self {
+            spdx_element_id: from.to_owned(),
+            relationship_type,
+            related_spdx_element: to.to_owned(),
+        }
+    }
+}
+
+/// Assigns SPDX identifiers to packages.
+///
+/// Identifiers are based on package names and versions, and are unique within a document.
+#[derive(Default)]
+struct SpdxIds {
+    ids: AHashMap<PackageId, String>,
+    counts: AHashMap<String, usize>,
+}
+
+impl SpdxIds {
+    fn insert(&mut self, package: &BuiltPackage<'_>) -> String {
+        let metadata = package.metadata;
+        let base = format!(
+            "SPDXRef-Package-{}-{}",
+            sanitize(metadata.name()),
+            sanitize(&metadata.version().to_string())
+        );
+        // Packages with the same name and version can come from different sources.
+        let count = self.counts.entry(base.clone()).or_default();
+        *count += 1;
+        let spdx_id = if *count == 1 {
+            base
+        } else {
+            format!("{}-{}", base, count)
+        };
+        self.ids.insert(metadata.id().clone(), spdx_id.clone());
+        spdx_id
+    }
+
+    fn get(&self, package_id: &PackageId) -> &str {
+        self.ids
+            .get(package_id)
+            .expect("links are between packages in the document")
+    }
+}
+
+/// Replaces characters that aren't allowed in SPDX identifiers with `-`.
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Wraps free-form text for the tag-value format.
+fn text(s: &str) -> String {
+    format!("<text>{}</text>", s)
+}
+/// Formats a time as an RFC 3339 timestamp in UTC, for example `2024-07-01T12:00:00Z`.
+/// Formats a time as an SPDX timestamp, for example `2024-07-01T12:00:00Z`.
+pub(super) fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // Convert days since the epoch to a civil date, from
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn timestamps() {
+        for (secs, expected) in [
+            (0, "1970-01-01T00:00:00Z"),
+            (951_782_400, "2000-02-29T00:00:00Z"),
+            (1_719_835_200, "2024-07-01T12:00:00Z"),
+            (4_107_542_399, "2100-02-28T23:59:59Z"),
+        ] {
+            assert_eq!(
+                format_timestamp(UNIX_EPOCH + Duration::from_secs(secs)),
+                expected
+            );
+        }
+    }
+}
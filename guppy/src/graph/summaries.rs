@@ -1,7 +1,8 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-//! Generate build summaries from `CargoSet` instances.
+//! Generate build summaries from `CargoSet` instances, or from arbitrary `PackageSet` and
+//! `FeatureSet` instances.
 //!
 //! Requires the `summaries` feature to be enabled.
 
@@ -9,13 +10,18 @@ mod package_set;
 
 use crate::{
     graph::{
-        cargo::{CargoOptions, CargoResolverVersion, CargoSet, InitialsPlatform},
-        feature::FeatureSet,
-        DependencyDirection, PackageGraph, PackageMetadata, PackageSet, PackageSource,
+        cargo::{
+            BuildPlatform, CargoOptions, CargoResolverVersion, CargoSet, CargoSetMatrix,
+            InitialsPlatform,
+        },
+        feature::{FeatureLabel, FeatureList, FeatureSet, StandardFeatures},
+        DependencyDirection, DependencyReq, PackageGraph, PackageLink, PackageMetadata, PackageSet,
+        PackageSource,
     },
-    platform::PlatformSpecSummary,
-    Error,
+    platform::{EnabledTernary, PlatformSpec, PlatformSpecSummary},
+    Error, PackageId,
 };
+use ahash::AHashMap;
 pub use guppy_summaries::*;
 pub use package_set::*;
 use serde::{Deserialize, Serialize};
@@ -41,6 +47,227 @@ impl<'g> CargoSet<'g> {
     }
 }
 
+impl<'g> CargoSet<'g> {
+    /// Creates a v2 build summary with the given options.
+    ///
+    /// The summary contains a single platform, labeled with the target platform in `opts`: the
+    /// target triple for an individual platform, or `any` or `always` otherwise. To create a
+    /// summary covering several target platforms, use
+    /// [`CargoSetMatrix::to_summary_v2`](CargoSetMatrix::to_summary_v2).
+    ///
+    /// Requires the `summaries` feature to be enabled.
+    pub fn to_summary_v2(&self, opts: &CargoOptions<'_>) -> Result<SummaryV2, Error> {
+        let metadata = CargoOptionsSummary::new(
+            self.initials().graph().package_graph,
+            self.features_only(),
+            opts,
+        )?;
+        let mut summary = SummaryV2::with_metadata(&metadata).map_err(Error::TomlSerializeError)?;
+        self.add_to_summary_v2(&mut summary, &opts.target_platform, opts);
+        Ok(summary)
+    }
+
+    /// Adds the packages built by this `CargoSet` to a v2 summary.
+    fn add_to_summary_v2(
+        &self,
+        summary: &mut SummaryV2,
+        target_platform: &PlatformSpec,
+        opts: &CargoOptions<'_>,
+    ) {
+        let platform = platform_label(target_platform);
+        for (build_platform, features) in self.all_features() {
+            let kind = match build_platform {
+                BuildPlatform::Target => PlatformKind::Target,
+                BuildPlatform::Host => PlatformKind::Host,
+            };
+            let mut dep_kinds = self.dep_kinds(build_platform, target_platform, opts);
+            let infos = features.package_infos(
+                |package| self.initials().contains_package_ix(package.package_ix()),
+                self.platform_direct_deps(build_platform),
+            );
+            for (package, info) in infos {
+                let mut build_info = BuildInfo::from_package_info(info);
+                build_info.dep_kinds = dep_kinds.remove(package.id()).unwrap_or_default();
+                summary.insert(
+                    package.to_summary_id(),
+                    BuildKey::new(platform.clone(), kind),
+                    build_info,
+                );
+            }
+        }
+    }
+
+    /// Returns the kinds of dependency edges through which each package on this build platform was
+    /// included.
+    fn dep_kinds(
+        &self,
+        build_platform: BuildPlatform,
+        target_platform: &PlatformSpec,
+        opts: &CargoOptions<'_>,
+    ) -> AHashMap<&'g PackageId, BTreeSet<DepKind>> {
+        let mut dep_kinds: AHashMap<_, BTreeSet<_>> = AHashMap::new();
+        let mut add = |link: PackageLink<'g>, kind: DepKind| {
+            dep_kinds.entry(link.to().id()).or_default().insert(kind);
+        };
+
+        let features = self.platform_features(build_platform);
+        let platform_spec = match build_platform {
+            BuildPlatform::Target => target_platform,
+            BuildPlatform::Host => &opts.host_platform,
+        };
+
+        // Links between packages on this build platform.
+        for feature_list in features.packages_with_features(DependencyDirection::Forward) {
+            let from = feature_list.package();
+            let is_initial = self.initials().contains_package_ix(from.package_ix());
+            for link in from.direct_links() {
+                if !features.contains_package_ix(link.to().package_ix()) {
+                    continue;
+                }
+                if is_link_enabled(&feature_list, link, link.normal(), platform_spec) {
+                    add(link, DepKind::Normal);
+                }
+                // Build dependencies of target packages are built on the host, and are handled
+                // below.
+                if build_platform == BuildPlatform::Host
+                    && is_link_enabled(&feature_list, link, link.build(), platform_spec)
+                {
+                    add(link, DepKind::Build);
+                }
+                if build_platform == BuildPlatform::Target
+                    && opts.include_dev
+                    && is_initial
+                    && is_link_enabled(&feature_list, link, link.dev(), platform_spec)
+                {
+                    add(link, DepKind::Dev);
+                }
+            }
+        }
+
+        // Links from target packages to host packages.
+        if build_platform == BuildPlatform::Host {
+            for link in self.build_dep_links() {
+                add(link, DepKind::Build);
+            }
+            for link in self.proc_macro_links() {
+                if link.normal().is_present() {
+                    add(link, DepKind::Normal);
+                } else {
+                    add(link, DepKind::Dev);
+                }
+            }
+        }
+
+        dep_kinds
+    }
+}
+
+impl<'g> CargoSetMatrix<'g> {
+    /// Creates a v2 build summary covering every target platform in this matrix.
+    ///
+    /// `opts` should be the options the matrix was computed with. Each target platform is labeled
+    /// with its target triple, or `any` or `always` for the corresponding `PlatformSpec` variants.
+    ///
+    /// Requires the `summaries` feature to be enabled.
+    pub fn to_summary_v2(&self, opts: &CargoOptions<'_>) -> Result<SummaryV2, Error> {
+        let common_features = self.common_features(BuildPlatform::Target);
+        let feature_graph = *common_features.graph();
+        let features_only = match self.get(0) {
+            Some((_, cargo_set)) => cargo_set.features_only().clone(),
+            None => feature_graph.resolve_none(),
+        };
+        let metadata = CargoOptionsSummary::new(feature_graph.package_graph, &features_only, opts)?;
+        let mut summary = SummaryV2::with_metadata(&metadata).map_err(Error::TomlSerializeError)?;
+        for (target_platform, cargo_set) in self.iter() {
+            cargo_set.add_to_summary_v2(&mut summary, target_platform, opts);
+        }
+        Ok(summary)
+    }
+}
+
+impl<'g> PackageSet<'g> {
+    /// Creates a build summary for this package set, labeled with `label`.
+    ///
+    /// This is useful for tracking changes to sets that don't correspond to a single Cargo build,
+    /// such as the production dependencies of a binary. Since a `PackageSet` doesn't contain
+    /// features, packages are recorded without any. To record features as well, use
+    /// [`FeatureSet::to_build_summary`].
+    ///
+    /// For more about how packages are recorded, see [`FeatureSet::to_build_summary`].
+    ///
+    /// Requires the `summaries` feature to be enabled.
+    pub fn to_build_summary(&self, label: &str) -> Result<Summary, Error> {
+        self.to_feature_set(StandardFeatures::None)
+            .to_build_summary(label)
+    }
+}
+
+impl<'g> FeatureSet<'g> {
+    /// Creates a build summary for this feature set, labeled with `label`.
+    ///
+    /// This is useful for tracking changes to sets that don't correspond to a single Cargo build,
+    /// such as the production dependencies of a binary. The label is stored in the summary's
+    /// metadata as a [`SetSummaryMetadata`], and the summary can be diffed like any other.
+    ///
+    /// All packages are recorded as target packages. Packages that aren't depended on by any
+    /// other package in the set are marked `initial`, and third-party packages depended on by a
+    /// workspace package in the set are marked `direct`.
+    ///
+    /// Requires the `summaries` feature to be enabled.
+    pub fn to_build_summary(&self, label: &str) -> Result<Summary, Error> {
+        let package_set = self.to_package_set();
+        let package_graph = self.graph().package_graph;
+        let initials = package_graph
+            .resolve_ids(package_set.root_ids(DependencyDirection::Forward))
+            .expect("root IDs are known to the graph");
+        let direct_deps = package_graph
+            .resolve_ids(
+                package_set
+                    .links(DependencyDirection::Forward)
+                    .filter(|link| link.from().in_workspace() && !link.to().in_workspace())
+                    .map(|link| link.to().id()),
+            )
+            .expect("link IDs are known to the graph");
+
+        let metadata = SetSummaryMetadata {
+            label: label.to_owned(),
+        };
+        let mut summary = Summary::with_metadata(&metadata).map_err(Error::TomlSerializeError)?;
+        summary.target_packages = self
+            .package_infos(
+                |package| initials.contains_ix(package.package_ix()),
+                &direct_deps,
+            )
+            .map(|(package, info)| (package.to_summary_id(), info))
+            .collect();
+        Ok(summary)
+    }
+}
+
+/// Returns true if this link is followed from a package with the given features.
+fn is_link_enabled(
+    from_features: &FeatureList<'_>,
+    link: PackageLink<'_>,
+    req: DependencyReq<'_>,
+    platform_spec: &PlatformSpec,
+) -> bool {
+    let status = req.status();
+    if status.required_on(platform_spec) != EnabledTernary::Disabled {
+        return true;
+    }
+    status.enabled_on(platform_spec) != EnabledTernary::Disabled
+        && from_features.contains(FeatureLabel::OptionalDependency(link.dep_name()))
+}
+
+/// Returns the label used for this target platform in v2 summaries.
+fn platform_label(platform_spec: &PlatformSpec) -> String {
+    match platform_spec {
+        PlatformSpec::Always => "always".to_owned(),
+        PlatformSpec::Platform(platform) => platform.triple_str().to_owned(),
+        PlatformSpec::Any => "any".to_owned(),
+    }
+}
+
 impl<'g> FeatureSet<'g> {
     /// Creates a `PackageMap` from this `FeatureSet`.
     ///
@@ -50,11 +277,27 @@ impl<'g> FeatureSet<'g> {
         initials: &FeatureSet<'g>,
         direct_deps: &PackageSet<'g>,
     ) -> PackageMap {
+        self.package_infos(
+            |package| initials.contains_package_ix(package.package_ix()),
+            direct_deps,
+        )
+        .map(|(package, info)| (package.to_summary_id(), info))
+        .collect()
+    }
+
+    /// Iterates over the packages in this `FeatureSet`, along with their `PackageInfo`.
+    ///
+    /// `is_initial` and `direct_deps` are used to assign a PackageStatus.
+    fn package_infos<'a>(
+        &'a self,
+        is_initial: impl Fn(PackageMetadata<'g>) -> bool + 'a,
+        direct_deps: &'a PackageSet<'g>,
+    ) -> impl Iterator<Item = (PackageMetadata<'g>, PackageInfo)> + 'a {
         self.packages_with_features(DependencyDirection::Forward)
-            .map(|feature_list| {
-                let package = feature_list.package();
+            .map(move |feature_list| {
+                let package = *feature_list.package();
 
-                let status = if initials.contains_package_ix(package.package_ix()) {
+                let status = if is_initial(package) {
                     PackageStatus::Initial
                 } else if package.in_workspace() {
                     PackageStatus::Workspace
@@ -76,9 +319,8 @@ impl<'g> FeatureSet<'g> {
                         .collect(),
                 };
 
-                (feature_list.package().to_summary_id(), info)
+                (package, info)
             })
-            .collect()
     }
 }
 
@@ -125,6 +367,20 @@ impl<'g> PackageMetadata<'g> {
     }
 }
 
+/// Metadata for a summary created from a `PackageSet` or `FeatureSet`.
+///
+/// Created by [`PackageSet::to_build_summary`] and [`FeatureSet::to_build_summary`], and can be
+/// read back from a summary's `metadata` table.
+///
+/// Requires the `summaries` feature to be enabled.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct SetSummaryMetadata {
+    /// The label provided when creating the summary.
+    pub label: String,
+}
+
 /// A summary of Cargo options used to build a `CargoSet`.
 ///
 /// Requires the `summaries` feature to be enabled.
@@ -11,7 +11,7 @@ use crate::{
 };
 use ahash::AHashMap;
 use camino::Utf8PathBuf;
-use guppy_summaries::SummaryId;
+use guppy_summaries::{glob_match, SummaryId};
 use semver::VersionReq;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use smallvec::SmallVec;
@@ -110,6 +110,9 @@ pub struct PackageSetSummary {
 
     /// Workspace packages, specified by names. Typically used in config files.
     ///
+    /// Names can contain the wildcards `*` (matching any sequence of characters) and `?`
+    /// (matching any single character), e.g. `"my-crate-*"`.
+    ///
     /// These require a `PackageGraph` as context.
     #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
     pub workspace_members: BTreeSet<String>,
@@ -230,6 +233,9 @@ impl PackageSetSummary {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ThirdPartySummary {
     /// The name of the package. Must be specified.
+    ///
+    /// The name can contain the wildcards `*` (matching any sequence of characters) and `?`
+    /// (matching any single character), e.g. `"tokio-*"`.
     pub name: String,
 
     /// A version specifier for the package. Can be skipped: defaults to [`VersionReq::STAR`].
@@ -568,7 +574,9 @@ struct PackageMatcher<'a> {
     // against something in the metadata.
     summary_ids: AHashMap<&'a SummaryId, bool>,
     workspace_members: &'a BTreeSet<String>,
+    workspace_member_globs: Vec<&'a str>,
     third_party: AHashMap<&'a str, SmallVec<[(&'a ThirdPartySummary, bool); 2]>>,
+    third_party_globs: Vec<(&'a ThirdPartySummary, bool)>,
     registry_names_to_urls: AHashMap<&'a str, &'a str>,
 }
 
@@ -584,7 +592,15 @@ impl<'a> PackageMatcher<'a> {
             .map(|summary_id| (summary_id, false))
             .collect();
 
+        let workspace_member_globs = summary
+            .workspace_members
+            .iter()
+            .map(|name| name.as_str())
+            .filter(|name| is_glob(name))
+            .collect();
+
         let mut third_party: AHashMap<_, SmallVec<[_; 2]>> = AHashMap::new();
+        let mut third_party_globs = Vec::new();
         let mut registry_names_to_urls = AHashMap::new();
         for tp_summary in &summary.third_party {
             if let ThirdPartySource::Registry(Some(name)) = &tp_summary.source {
@@ -603,16 +619,22 @@ impl<'a> PackageMatcher<'a> {
                     }
                 }
             }
-            third_party
-                .entry(tp_summary.name.as_str())
-                .or_default()
-                .push((tp_summary, false));
+            if is_glob(&tp_summary.name) {
+                third_party_globs.push((tp_summary, false));
+            } else {
+                third_party
+                    .entry(tp_summary.name.as_str())
+                    .or_default()
+                    .push((tp_summary, false));
+            }
         }
 
         Ok(Self {
             summary_ids,
             workspace_members: &summary.workspace_members,
+            workspace_member_globs,
             third_party,
+            third_party_globs,
             registry_names_to_urls,
         })
     }
@@ -632,28 +654,37 @@ impl<'a> PackageMatcher<'a> {
         };
         let in_selectors = if metadata.in_workspace() {
             self.workspace_members.contains(name)
+                || self
+                    .workspace_member_globs
+                    .iter()
+                    .any(|glob| glob_match(glob, name))
         } else {
             let registry_names_to_urls = &self.registry_names_to_urls;
-            match self.third_party.get_mut(name) {
-                Some(matches) => {
-                    let mut is_match = false;
-                    for (summary, is_match_store) in matches {
-                        if summary.version.matches(metadata.version())
-                            && Self::source_matches(
-                                metadata.source(),
-                                &summary.source,
-                                registry_names_to_urls,
-                            )
-                        {
-                            // This is a match.
-                            is_match = true;
-                            *is_match_store = true;
-                        }
-                    }
-                    is_match
+            let globs = self
+                .third_party_globs
+                .iter_mut()
+                .filter(|(summary, _)| glob_match(&summary.name, name));
+            let mut is_match = false;
+            for (summary, is_match_store) in self
+                .third_party
+                .get_mut(name)
+                .into_iter()
+                .flatten()
+                .chain(globs)
+            {
+                if summary.version.matches(metadata.version())
+                    && Self::source_matches(
+                        metadata.source(),
+                        &summary.source,
+                        registry_names_to_urls,
+                    )
+                {
+                    // This is a match.
+                    is_match = true;
+                    *is_match_store = true;
                 }
-                None => false,
             }
+            is_match
         };
 
         in_ids || in_selectors
@@ -682,7 +713,14 @@ impl<'a> PackageMatcher<'a> {
             .workspace_members
             .iter()
             .filter_map(|member| {
-                if workspace.contains_name(member) {
+                let is_known = if is_glob(member) {
+                    workspace
+                        .iter()
+                        .any(|package| glob_match(member, package.name()))
+                } else {
+                    workspace.contains_name(member)
+                };
+                if is_known {
                     None
                 } else {
                     Some(member.clone())
@@ -690,19 +728,21 @@ impl<'a> PackageMatcher<'a> {
             })
             .collect();
 
-        let mut unknown_third_party: Vec<_> =
-            self.third_party
-                .into_iter()
-                .flat_map(|(_, summaries)| {
-                    summaries.into_iter().filter_map(|(summary, matched)| {
-                        if matched {
-                            None
-                        } else {
-                            Some(summary.clone())
-                        }
-                    })
-                })
-                .collect();
+        let mut unknown_third_party: Vec<_> = self
+            .third_party
+            .into_iter()
+            .flat_map(|(_, summaries)| summaries)
+            .chain(self.third_party_globs)
+            .filter_map(
+                |(summary, matched)| {
+                    if matched {
+                        None
+                    } else {
+                        Some(summary.clone())
+                    }
+                },
+            )
+            .collect();
         unknown_third_party.sort_by(|x, y| x.name.cmp(&y.name));
 
         if unknown_summary_ids.is_empty()
@@ -781,6 +821,10 @@ impl<'a> PackageMatcher<'a> {
     }
 }
 
+fn is_glob(name: &str) -> bool {
+    name.contains(['*', '?'])
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::vec_init_then_push)]
@@ -1001,6 +1045,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn glob_matches_names() {
+        for (pattern, name, expected) in [
+            ("guppy", "guppy", true),
+            ("guppy", "guppy-summaries", false),
+            ("guppy-*", "guppy-summaries", true),
+            ("guppy-*", "guppy", false),
+            ("*-hack", "product-a-hack", true),
+            ("*a*a*", "banana", true),
+            ("*a*a*a*a", "banana", false),
+            ("guppy?", "guppy2", true),
+            ("guppy?", "guppy", false),
+            ("*", "", true),
+        ] {
+            assert_eq!(
+                glob_match(pattern, name),
+                expected,
+                "pattern {pattern} against name {name}"
+            );
+        }
+    }
+
     fn format_input(input: &str) -> String {
         format!("input:\n---\n{}\n---", input)
     }
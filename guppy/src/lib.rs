@@ -48,10 +48,26 @@
 //!
 //! # Optional features
 //!
+//! * `cargo-config`: Support for reading [Cargo configuration](https://doc.rust-lang.org/cargo/reference/config.html)
+//!   files, to set up build simulations with the same target platform and `--cfg` flags.
+//! * `metadata-cache`: Support for caching `cargo metadata` output on disk, through
+//!   [`MetadataCommand::cache_dir`].
+//! * `outdated`: Support for finding third-party dependencies with newer versions available in a
+//!   registry index, through the `graph::outdated` module.
+//! * `policy`: Support for evaluating declarative dependency policies (banned crates, denied licenses,
+//!   allowed sources and duplicate versions) written in TOML, through the `graph::policy` module.
 //! * `proptest1`: Support for [property-based testing](https://jessitron.com/2013/04/25/property-based-testing-what-is-it/)
 //!   using the [`proptest`](https://altsysrq.github.io/proptest-book/intro.html) framework.
 //! * `rayon1`: Support for parallel iterators through [Rayon](docs.rs/rayon/1) (preliminary work
-//!   so far, more parallel iterators to be added in the future).
+//!   so far, more parallel iterators to be added in the future). With this feature enabled,
+//!   package and feature graphs are also constructed in parallel, and
+//!   [`CargoSet::compute_matrix`](graph::cargo::CargoSet::compute_matrix) simulates builds for
+//!   each target platform in parallel.
+//! * `rustsec`: Support for auditing package sets against the [RustSec](https://rustsec.org/) advisory
+//!   database, taking features and target platforms into account, through the `graph::audit` module.
+//! * `sbom`: Support for generating software bills of materials in the [CycloneDX](https://cyclonedx.org/)
+//!   and [SPDX](https://spdx.dev/) formats, and dependency snapshots for GitHub's dependency submission
+//!   API, through the `graph::sbom` module.
 //! * `summaries`: Support for writing out [build summaries](https://github.com/guppy-rs/guppy/tree/main/guppy-summaries).
 //!
 //! # Examples
@@ -96,6 +112,8 @@ pub use debug_ignore;
 mod dependency_kind;
 pub mod errors;
 pub mod graph;
+#[cfg(feature = "metadata-cache")]
+mod metadata_cache;
 mod metadata_command;
 mod package_id;
 pub(crate) mod petgraph_support;
@@ -0,0 +1,324 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! On-disk caching for `cargo metadata` output.
+
+use crate::{graph::cargo::cargo_home, CargoMetadata, Error, MetadataCommand};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    env, fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    process,
+};
+use twox_hash::XxHash64;
+
+/// Bump this whenever the format of `CacheEntry` changes.
+const CACHE_FORMAT_VERSION: u32 = 2;
+
+pub(crate) fn exec_cached(
+    command: &MetadataCommand,
+    cache_dir: &Path,
+) -> Result<CargoMetadata, Error> {
+    let cache_path = cache_dir.join(format!("metadata-{:016x}.json", command_key(command)));
+
+    // Any errors while reading the cache are treated as cache misses.
+    if let Some(entry) = CacheEntry::read(&cache_path) {
+        if entry.is_fresh() {
+            return Ok(entry.metadata);
+        }
+    }
+
+    let metadata = command.exec_uncached()?;
+    let entry = CacheEntry::new(metadata, current_dir(command).as_deref());
+    entry
+        .write(&cache_path)
+        .map_err(|err| Error::MetadataCacheError(cache_path, err))?;
+    Ok(entry.metadata)
+}
+
+/// Computes a key for this command, so that different invocations are cached separately.
+fn command_key(command: &MetadataCommand) -> u64 {
+    let command = command.cargo_command();
+    let mut hasher = XxHash64::default();
+    CACHE_FORMAT_VERSION.hash(&mut hasher);
+    command.get_program().hash(&mut hasher);
+    for arg in command.get_args() {
+        arg.hash(&mut hasher);
+    }
+    // Relative manifest paths are resolved against the current directory.
+    match command.get_current_dir() {
+        Some(dir) => dir.hash(&mut hasher),
+        None => env::current_dir().ok().hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+/// Returns the directory `cargo metadata` runs in, which is where Cargo starts looking for
+/// configuration files.
+fn current_dir(command: &MetadataCommand) -> Option<Utf8PathBuf> {
+    let dir = match command.cargo_command().get_current_dir() {
+        Some(dir) => dir.to_owned(),
+        None => env::current_dir().ok()?,
+    };
+    Utf8PathBuf::from_path_buf(dir).ok()
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CacheEntry {
+    format_version: u32,
+    inputs: Vec<CacheInput>,
+    // Absolute glob patterns from `workspace.members` and `workspace.exclude`, along with the
+    // package manifests they matched.
+    member_globs: Vec<String>,
+    member_manifests: BTreeSet<Utf8PathBuf>,
+    metadata: CargoMetadata,
+}
+
+impl CacheEntry {
+    fn new(metadata: CargoMetadata, current_dir: Option<&Utf8Path>) -> Self {
+        // The inputs are the lockfile, the workspace root manifest, and the manifests of all
+        // workspace members and path dependencies (i.e. packages without a source).
+        let workspace_root = &metadata.0.workspace_root;
+        let mut paths = BTreeSet::new();
+        paths.insert(workspace_root.join("Cargo.lock"));
+        paths.insert(workspace_root.join("Cargo.toml"));
+        paths.extend(
+            metadata
+                .0
+                .packages
+                .iter()
+                .filter(|package| package.source.is_none())
+                .map(|package| package.manifest_path.clone()),
+        );
+
+        // Cargo configuration can change the output too, e.g. through `[patch]` or source
+        // replacement. Cargo looks for it in the current directory and its ancestors, and in the
+        // Cargo home directory. Also check the workspace root's ancestors, in case the command
+        // was run from outside the workspace with `--manifest-path`. Files that don't exist yet
+        // are recorded as well, so that creating one invalidates the entry.
+        let config_dirs = current_dir
+            .into_iter()
+            .chain(Some(workspace_root.as_path()))
+            .flat_map(|dir| dir.ancestors())
+            .map(|dir| dir.join(".cargo"))
+            .chain(cargo_home());
+        for config_dir in config_dirs {
+            paths.insert(config_dir.join("config.toml"));
+            paths.insert(config_dir.join("config"));
+        }
+
+        // A workspace member can be added without changing the root manifest, by creating a
+        // package in a directory matched by a glob in `workspace.members`. Record which
+        // directories the globs match, so that new packages invalidate the entry.
+        let member_globs = member_globs(workspace_root);
+        let member_manifests = expand_member_globs(&member_globs);
+
+        let inputs = paths
+            .into_iter()
+            .map(|path| {
+                let hash = hash_file(&path);
+                CacheInput { path, hash }
+            })
+            .collect();
+
+        Self {
+            format_version: CACHE_FORMAT_VERSION,
+            inputs,
+            member_globs,
+            member_manifests,
+            metadata,
+        }
+    }
+
+    fn read(path: &Path) -> Option<Self> {
+        let contents = fs::read(path).ok()?;
+        let entry: Self = serde_json::from_slice(&contents).ok()?;
+        (entry.format_version == CACHE_FORMAT_VERSION).then_some(entry)
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.inputs
+            .iter()
+            .all(|input| hash_file(&input.path) == input.hash)
+            && expand_member_globs(&self.member_globs) == self.member_manifests
+    }
+
+    fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file and rename it, so that concurrent readers never see a
+        // partially written entry.
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(format!(".tmp-{}", process::id()));
+        let temp_path = PathBuf::from(temp_path);
+
+        let contents = serde_json::to_vec(self).map_err(io::Error::from)?;
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, path)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CacheInput {
+    path: Utf8PathBuf,
+    // None if the file doesn't exist.
+    hash: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+struct RootManifest {
+    #[serde(default)]
+    workspace: WorkspaceManifest,
+}
+
+#[derive(Default, Deserialize)]
+struct WorkspaceManifest {
+    #[serde(default)]
+    members: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+/// Returns the `workspace.members` and `workspace.exclude` entries in the root manifest, as
+/// absolute glob patterns.
+///
+/// Exclusions are included because excluded directories can't be told apart from other ones
+/// without reimplementing Cargo's workspace discovery. Creating a package in an excluded
+/// directory may cause a spurious cache miss, which is harmless.
+fn member_globs(workspace_root: &Utf8Path) -> Vec<String> {
+    // An unreadable or invalid root manifest is already an input, so it can be ignored here.
+    let manifest: RootManifest = fs::read_to_string(workspace_root.join("Cargo.toml"))
+        .ok()
+        .and_then(|contents| toml::from_str(&contents).ok())
+        .unwrap_or_default();
+    let root = glob::Pattern::escape(workspace_root.as_str());
+    manifest
+        .workspace
+        .members
+        .iter()
+        .chain(&manifest.workspace.exclude)
+        .map(|pattern| format!("{root}/{pattern}"))
+        .collect()
+}
+
+/// Returns the package manifests in directories matching the given glob patterns.
+fn expand_member_globs(globs: &[String]) -> BTreeSet<Utf8PathBuf> {
+    globs
+        .iter()
+        .filter_map(|pattern| glob::glob(pattern).ok())
+        .flatten()
+        .filter_map(|dir| Utf8PathBuf::from_path_buf(dir.ok()?).ok())
+        .map(|dir| dir.join("Cargo.toml"))
+        .filter(|manifest| manifest.is_file())
+        .collect()
+}
+
+fn hash_file(path: impl AsRef<Path>) -> Option<u64> {
+    let contents = fs::read(path).ok()?;
+    let mut hasher = XxHash64::default();
+    hasher.write(&contents);
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_entry_freshness() {
+        let dir = Utf8PathBuf::try_from(env::temp_dir())
+            .expect("temp dir is valid UTF-8")
+            .join(format!("guppy-metadata-cache-test-{}", process::id()));
+        fs::create_dir_all(&dir).expect("created temp dir");
+
+        let metadata = empty_metadata(&dir);
+
+        fs::write(dir.join("Cargo.toml"), "[workspace]\n").expect("wrote manifest");
+        let entry = CacheEntry::new(metadata, Some(&dir));
+        assert!(entry.is_fresh(), "entry is fresh right after creation");
+
+        let cache_path = dir.join("cache").join("metadata.json");
+        entry.write(cache_path.as_std_path()).expect("wrote entry");
+        let entry = CacheEntry::read(cache_path.as_std_path()).expect("read entry");
+        assert!(entry.is_fresh(), "entry is fresh after roundtrip");
+
+        fs::write(dir.join("Cargo.lock"), "version = 3\n").expect("wrote lockfile");
+        assert!(
+            !entry.is_fresh(),
+            "creating a lockfile invalidates the entry"
+        );
+        let entry = CacheEntry::new(entry.metadata, Some(&dir));
+        assert!(entry.is_fresh());
+
+        fs::write(dir.join("Cargo.toml"), "[workspace]\nmembers = []\n").expect("wrote manifest");
+        assert!(
+            !entry.is_fresh(),
+            "changing the manifest invalidates the entry"
+        );
+
+        fs::remove_dir_all(&dir).expect("removed temp dir");
+    }
+
+    #[test]
+    fn cache_entry_members_and_config() {
+        let dir = Utf8PathBuf::try_from(env::temp_dir())
+            .expect("temp dir is valid UTF-8")
+            .join(format!(
+                "guppy-metadata-cache-members-test-{}",
+                process::id()
+            ));
+        fs::create_dir_all(dir.join("crates/a")).expect("created temp dir");
+        fs::write(
+            dir.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\n",
+        )
+        .expect("wrote manifest");
+        fs::write(dir.join("crates/a/Cargo.toml"), "[package]\n").expect("wrote manifest");
+
+        let entry = CacheEntry::new(empty_metadata(&dir), Some(&dir));
+        assert!(entry.is_fresh(), "entry is fresh right after creation");
+
+        fs::create_dir_all(dir.join("crates/b")).expect("created dir");
+        assert!(
+            entry.is_fresh(),
+            "a directory without a manifest isn't a workspace member"
+        );
+        fs::write(dir.join("crates/b/Cargo.toml"), "[package]\n").expect("wrote manifest");
+        assert!(
+            !entry.is_fresh(),
+            "adding a member matching a glob invalidates the entry"
+        );
+
+        let entry = CacheEntry::new(entry.metadata, Some(&dir));
+        assert!(entry.is_fresh());
+        fs::create_dir_all(dir.join(".cargo")).expect("created dir");
+        fs::write(dir.join(".cargo/config.toml"), "[build]\n").expect("wrote config");
+        assert!(
+            !entry.is_fresh(),
+            "creating a Cargo config file invalidates the entry"
+        );
+
+        fs::remove_dir_all(&dir).expect("removed temp dir");
+    }
+
+    fn empty_metadata(dir: &Utf8Path) -> CargoMetadata {
+        CargoMetadata::parse_json(format!(
+            r#"{{
+                "packages": [],
+                "workspace_members": [],
+                "resolve": null,
+                "workspace_root": {root},
+                "target_directory": {target},
+                "version": 1
+            }}"#,
+            root = serde_json::to_string(dir).unwrap(),
+            target = serde_json::to_string(&dir.join("target")).unwrap(),
+        ))
+        .expect("valid metadata")
+    }
+}
@@ -4,7 +4,13 @@
 use crate::{graph::PackageGraph, Error};
 use cargo_metadata::CargoOpt;
 use serde::{Deserialize, Serialize};
-use std::{convert::TryFrom, io, path::PathBuf, process::Command};
+use std::{
+    convert::TryFrom,
+    io::{self, BufRead, BufReader, Read},
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread,
+};
 
 /// A builder for configuring `cargo metadata` invocations.
 ///
@@ -24,6 +30,8 @@ use std::{convert::TryFrom, io, path::PathBuf, process::Command};
 #[derive(Clone, Debug, Default)]
 pub struct MetadataCommand {
     inner: cargo_metadata::MetadataCommand,
+    #[cfg(feature = "metadata-cache")]
+    cache_dir: Option<PathBuf>,
 }
 
 impl MetadataCommand {
@@ -35,7 +43,11 @@ impl MetadataCommand {
         let mut inner = cargo_metadata::MetadataCommand::new();
         // Always use --all-features so that we get a full view of the graph.
         inner.features(CargoOpt::AllFeatures);
-        Self { inner }
+        Self {
+            inner,
+            #[cfg(feature = "metadata-cache")]
+            cache_dir: None,
+        }
     }
 
     /// Sets the path to the `cargo` executable.
@@ -97,6 +109,25 @@ impl MetadataCommand {
         self
     }
 
+    /// Caches `cargo metadata` output in the given directory.
+    ///
+    /// With a cache directory set, [`exec`](Self::exec) and [`build_graph`](Self::build_graph)
+    /// reuse the output of a previous invocation with the same options, as long as `Cargo.lock`,
+    /// the workspace root's `Cargo.toml`, the manifests of all workspace members and path
+    /// dependencies, and any `.cargo/config.toml` files Cargo would read are unchanged, and no
+    /// packages have been added to directories matched by `workspace.members` globs. Files are
+    /// compared by hash, not by modification time.
+    ///
+    /// Changes to other inputs, such as environment variables or the version of Cargo, are not
+    /// detected. Clear the cache directory if those change.
+    ///
+    /// Requires the `metadata-cache` feature to be enabled.
+    #[cfg(feature = "metadata-cache")]
+    pub fn cache_dir(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.cache_dir = Some(path.into());
+        self
+    }
+
     /// Builds a [`Command`] instance. This is the first part of calling
     /// [`exec`](Self::exec).
     pub fn cargo_command(&self) -> Command {
@@ -104,9 +135,58 @@ impl MetadataCommand {
     }
 
     /// Runs the configured `cargo metadata` and returns a deserialized `CargoMetadata`.
+    ///
+    /// If a [cache directory](Self::cache_dir) is set, this may return cached output instead.
     pub fn exec(&self) -> Result<CargoMetadata, Error> {
-        let inner = self.inner.exec().map_err(Error::command_error)?;
-        Ok(CargoMetadata(inner))
+        #[cfg(feature = "metadata-cache")]
+        if let Some(cache_dir) = &self.cache_dir {
+            return crate::metadata_cache::exec_cached(self, cache_dir);
+        }
+        self.exec_uncached()
+    }
+
+    pub(crate) fn exec_uncached(&self) -> Result<CargoMetadata, Error> {
+        // Deserialize the output as it's produced rather than buffering all of it: for large
+        // workspaces, the JSON output can be hundreds of megabytes.
+        let mut child = self
+            .cargo_command()
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(cargo_metadata::Error::Io)
+            .map_err(Error::command_error)?;
+
+        // Read stderr on a separate thread so that cargo doesn't block on a full pipe.
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        let stderr_thread = thread::spawn(move || {
+            let mut buf = Vec::new();
+            stderr.read_to_end(&mut buf).map(|_| buf)
+        });
+
+        // The reader (and with it, our end of the pipe) is dropped once parsing is done, so cargo
+        // can't block on writing output if parsing fails partway through.
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let parsed = parse_metadata_output(BufReader::new(stdout));
+
+        let status = child
+            .wait()
+            .map_err(cargo_metadata::Error::Io)
+            .map_err(Error::command_error)?;
+        let stderr = stderr_thread
+            .join()
+            .expect("stderr thread doesn't panic")
+            .map_err(cargo_metadata::Error::Io)
+            .map_err(Error::command_error)?;
+
+        if !status.success() {
+            let stderr = String::from_utf8(stderr)
+                .map_err(cargo_metadata::Error::ErrUtf8)
+                .map_err(Error::command_error)?;
+            return Err(Error::command_error(cargo_metadata::Error::CargoMetadata {
+                stderr,
+            }));
+        }
+        parsed.map(CargoMetadata).map_err(Error::command_error)
     }
 
     /// Runs the configured `cargo metadata` and returns a parsed `PackageGraph`.
@@ -154,6 +234,16 @@ impl CargoMetadata {
         Ok(Self(inner))
     }
 
+    /// Deserializes JSON from the given reader into a `CargoMetadata`.
+    ///
+    /// Unlike [`parse_json`](Self::parse_json), this doesn't require the entire JSON blob to be in
+    /// memory at once, which can substantially reduce peak memory use for large workspaces. The
+    /// reader isn't buffered internally, so consider wrapping it in an [`io::BufReader`].
+    pub fn parse_reader(reader: impl io::Read) -> Result<Self, Error> {
+        let inner = serde_json::from_reader(reader).map_err(Error::MetadataParseError)?;
+        Ok(Self(inner))
+    }
+
     /// Serializes this metadata into the given writer.
     pub fn serialize(&self, writer: &mut impl io::Write) -> Result<(), Error> {
         serde_json::to_writer(writer, &self.0).map_err(Error::MetadataSerializeError)
@@ -165,6 +255,28 @@ impl CargoMetadata {
     }
 }
 
+/// Deserializes `cargo metadata` output from a reader.
+///
+/// As with `cargo_metadata`, lines before the JSON blob (for example, output from wrapper scripts)
+/// are skipped, as is anything after it.
+fn parse_metadata_output(
+    mut reader: impl BufRead,
+) -> Result<cargo_metadata::Metadata, cargo_metadata::Error> {
+    let mut skipped = Vec::new();
+    loop {
+        match reader.fill_buf()?.first() {
+            Some(b'{') => break,
+            Some(_) => {
+                skipped.clear();
+                reader.read_until(b'\n', &mut skipped)?;
+            }
+            None => return Err(cargo_metadata::Error::NoJson),
+        }
+    }
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    Ok(cargo_metadata::Metadata::deserialize(&mut deserializer)?)
+}
+
 impl TryFrom<CargoMetadata> for PackageGraph {
     type Error = Error;
 
@@ -172,3 +284,31 @@ impl TryFrom<CargoMetadata> for PackageGraph {
         metadata.build_graph()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: &str = r#"{
+        "packages": [],
+        "workspace_members": [],
+        "resolve": null,
+        "workspace_root": "/workspace",
+        "target_directory": "/workspace/target",
+        "version": 1
+    }"#;
+
+    #[test]
+    fn parse_output() {
+        let output = format!("wrapper script output\n\n{}\ntrailing output\n", METADATA);
+        let metadata = parse_metadata_output(output.as_bytes()).expect("parsed successfully");
+        assert_eq!(metadata.workspace_root, "/workspace");
+
+        let err = parse_metadata_output(&b"no json here\n"[..]).expect_err("no JSON");
+        assert!(matches!(err, cargo_metadata::Error::NoJson));
+
+        let metadata =
+            CargoMetadata::parse_reader(METADATA.as_bytes()).expect("parsed successfully");
+        assert_eq!(metadata.0.target_directory, "/workspace/target");
+    }
+}
@@ -62,6 +62,11 @@ impl<Ix: IndexType> Sccs<Ix> {
         }
     }
 
+    /// Returns all the SCCs, in forward topological order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &[NodeIndex<Ix>]> {
+        self.sccs.iter()
+    }
+
     /// Returns all the SCCs with more than one element.
     pub fn multi_sccs(&self) -> impl DoubleEndedIterator<Item = &[NodeIndex<Ix>]> {
         self.sccs.iter().filter(|scc| scc.len() > 1)
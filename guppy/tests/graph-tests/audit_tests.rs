@@ -0,0 +1,362 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use camino::Utf8PathBuf;
+use fixtures::json::{self, JsonFixture};
+use guppy::{
+    errors::Error,
+    graph::{
+        audit::{Advisory, AdvisoryDatabase, AuditOptions},
+        cargo::{BuildPlatform, CargoOptions, CargoSet},
+        feature::StandardFeatures,
+        PackageGraph,
+    },
+    platform::{Platform, PlatformSpec, TargetFeatures},
+};
+use std::{env, fs, process};
+
+static LAZY_STATIC_ADVISORY: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2019-9001"
+package = "lazy_static"
+date = "2019-01-01"
+url = "https://example.com/advisories/lazy_static"
+aliases = ["CVE-2019-9001"]
+
+[versions]
+patched = [">= 1.0.0"]
+unaffected = ["< 0.2.0"]
+```
+
+# Memory corruption in lazy_static
+
+Versions of lazy_static before 1.0 are affected.
+"#;
+
+static BYTES_ADVISORY: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2020-9002"
+package = "bytes"
+date = "2020-01-01"
+
+[versions]
+patched = ["^0.5.4", ">= 0.6.0"]
+```
+
+# Integer overflow in bytes
+"#;
+
+static UNMAINTAINED_ADVISORY: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2021-9003"
+package = "lazy_static"
+date = "2021-01-01"
+informational = "unmaintained"
+
+[versions]
+patched = []
+```
+
+# lazy_static is unmaintained
+"#;
+
+static WITHDRAWN_ADVISORY: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2021-9004"
+package = "bytes"
+date = "2021-01-01"
+withdrawn = "2021-02-01"
+
+[versions]
+patched = []
+```
+
+# Withdrawn advisory
+"#;
+
+static WINDOWS_ADVISORY: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2022-9005"
+package = "bytes"
+date = "2022-01-01"
+
+[affected]
+os = ["windows"]
+arch = ["x86_64"]
+
+[versions]
+patched = []
+```
+
+# Windows-only issue in bytes
+"#;
+
+fn advisory_db() -> AdvisoryDatabase {
+    let mut db = AdvisoryDatabase::new();
+    for (path, contents) in [
+        ("lazy_static/RUSTSEC-2019-9001.md", LAZY_STATIC_ADVISORY),
+        ("bytes/RUSTSEC-2020-9002.md", BYTES_ADVISORY),
+        ("lazy_static/RUSTSEC-2021-9003.md", UNMAINTAINED_ADVISORY),
+        ("bytes/RUSTSEC-2021-9004.md", WITHDRAWN_ADVISORY),
+    ] {
+        db.add(Advisory::parse(path, contents).expect("advisory is valid"));
+    }
+    db
+}
+
+#[test]
+fn parse_advisory() {
+    let advisory =
+        Advisory::parse("RUSTSEC-2019-9001.md", LAZY_STATIC_ADVISORY).expect("advisory is valid");
+    assert_eq!(advisory.id(), "RUSTSEC-2019-9001");
+    assert_eq!(advisory.package(), "lazy_static");
+    assert_eq!(advisory.title(), "Memory corruption in lazy_static");
+    assert_eq!(
+        advisory.description(),
+        "Versions of lazy_static before 1.0 are affected."
+    );
+    assert_eq!(advisory.date(), "2019-01-01");
+    assert_eq!(
+        advisory.url(),
+        Some("https://example.com/advisories/lazy_static")
+    );
+    assert_eq!(advisory.aliases(), ["CVE-2019-9001"]);
+    assert_eq!(advisory.informational(), None);
+    assert!(!advisory.is_withdrawn());
+
+    for (version, affected) in [
+        ("0.1.16", false),
+        ("0.2.0", true),
+        ("0.2.11", true),
+        ("1.0.0", false),
+        ("1.4.0", false),
+    ] {
+        assert_eq!(
+            advisory.is_affected(&version.parse().unwrap()),
+            affected,
+            "for version {version}"
+        );
+    }
+
+    let err = Advisory::parse("invalid.md", "# No front matter").expect_err("invalid advisory");
+    assert!(matches!(err, Error::AdvisoryParseError(path, _) if path == "invalid.md"));
+    let err = Advisory::parse(
+        "invalid.md",
+        "```toml\n[advisory]\nid = \"RUSTSEC-0000-0000\"\n```",
+    )
+    .expect_err("missing fields");
+    assert!(matches!(err, Error::AdvisoryParseError(path, _) if path == "invalid.md"));
+}
+
+#[test]
+fn load_advisory_db() {
+    let dir = Utf8PathBuf::try_from(env::temp_dir())
+        .expect("temp dir is valid UTF-8")
+        .join(format!("guppy-advisory-db-test-{}", process::id()));
+    let crates_dir = dir.join("crates");
+    fs::create_dir_all(crates_dir.join("lazy_static")).expect("created temp dir");
+    fs::create_dir_all(crates_dir.join("bytes")).expect("created temp dir");
+    fs::write(
+        crates_dir.join("lazy_static/RUSTSEC-2019-9001.md"),
+        LAZY_STATIC_ADVISORY,
+    )
+    .expect("wrote advisory");
+    fs::write(
+        crates_dir.join("bytes/RUSTSEC-2020-9002.md"),
+        BYTES_ADVISORY,
+    )
+    .expect("wrote advisory");
+    fs::write(crates_dir.join("bytes/README.txt"), "not an advisory").expect("wrote file");
+
+    let db = AdvisoryDatabase::load(&dir).expect("advisory DB loaded");
+    assert_eq!(db.len(), 2);
+    let ids: Vec<_> = db.advisories().map(|advisory| advisory.id()).collect();
+    assert_eq!(ids, ["RUSTSEC-2020-9002", "RUSTSEC-2019-9001"]);
+
+    fs::write(crates_dir.join("bytes/RUSTSEC-2020-9003.md"), "invalid").expect("wrote advisory");
+    let err = AdvisoryDatabase::load(&dir).expect_err("invalid advisory");
+    assert!(
+        matches!(&err, Error::AdvisoryParseError(path, _) if path.ends_with("RUSTSEC-2020-9003.md")),
+        "actual error: {err}"
+    );
+
+    fs::remove_dir_all(&dir).expect("removed temp dir");
+    let err = AdvisoryDatabase::load(&dir).expect_err("missing DB");
+    assert!(matches!(err, Error::AdvisoryReadError(..)));
+}
+
+#[test]
+fn audit_package_set() {
+    let graph = JsonFixture::metadata_targets1().graph();
+    let db = advisory_db();
+
+    // Without platform information, every version of lazy_static in the graph is considered.
+    let report = db.audit_package_set(&graph.resolve_all(), &AuditOptions::new());
+    let findings: Vec<_> = report
+        .findings()
+        .iter()
+        .map(|finding| (finding.advisory().id(), finding.package().id().repr()))
+        .collect();
+    assert_eq!(
+        findings,
+        [
+            ("RUSTSEC-2019-9001", json::METADATA_TARGETS1_LAZY_STATIC_02),
+            ("RUSTSEC-2020-9002", json::METADATA_TARGETS1_BYTES),
+        ],
+    );
+
+    let finding = &report.findings()[0];
+    assert!(finding.build_platforms().is_empty());
+    let chains: Vec<Vec<_>> = finding
+        .chains()
+        .iter()
+        .map(|chain| chain.iter().map(|package| package.id().repr()).collect())
+        .collect();
+    assert_eq!(
+        chains,
+        [[
+            json::METADATA_TARGETS1_TESTCRATE,
+            json::METADATA_TARGETS1_LAZY_STATIC_02
+        ]],
+    );
+
+    // Informational advisories are only reported if requested, and advisories can be ignored.
+    let mut options = AuditOptions::new();
+    options
+        .set_include_informational(true)
+        .add_ignored(["RUSTSEC-2020-9002"]);
+    let report = db.audit_package_set(&graph.resolve_all(), &options);
+    let findings: Vec<_> = report
+        .findings()
+        .iter()
+        .map(|finding| (finding.advisory().id(), finding.package().id().repr()))
+        .collect();
+    assert_eq!(
+        findings,
+        [
+            ("RUSTSEC-2019-9001", json::METADATA_TARGETS1_LAZY_STATIC_02),
+            ("RUSTSEC-2021-9003", json::METADATA_TARGETS1_LAZY_STATIC_01),
+            ("RUSTSEC-2021-9003", json::METADATA_TARGETS1_LAZY_STATIC_02),
+            ("RUSTSEC-2021-9003", json::METADATA_TARGETS1_LAZY_STATIC_1),
+        ],
+    );
+}
+
+#[test]
+fn audit_cargo_set() {
+    let graph = JsonFixture::metadata_targets1().graph();
+    let db = advisory_db();
+
+    // lazy_static 0.2.11 is only a dependency on non-Windows platforms.
+    let linux = make_cargo_set(graph, "x86_64-unknown-linux-gnu");
+    let report = db.audit_cargo_set(&linux, &AuditOptions::new());
+    let findings: Vec<_> = report
+        .findings()
+        .iter()
+        .map(|finding| {
+            (
+                finding.package().id().repr(),
+                finding.build_platforms().to_vec(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        findings,
+        [
+            (
+                json::METADATA_TARGETS1_LAZY_STATIC_02,
+                vec![BuildPlatform::Target]
+            ),
+            (json::METADATA_TARGETS1_BYTES, vec![BuildPlatform::Target]),
+        ],
+    );
+    for finding in report.findings() {
+        assert_eq!(finding.chains().len(), 1, "one chain from the initial");
+        assert_eq!(
+            finding.chains()[0][0].id().repr(),
+            json::METADATA_TARGETS1_TESTCRATE
+        );
+    }
+
+    let windows = make_cargo_set(graph, "x86_64-pc-windows-msvc");
+    let report = db.audit_cargo_set(&windows, &AuditOptions::new());
+    let findings: Vec<_> = report
+        .findings()
+        .iter()
+        .map(|finding| finding.package().id().repr())
+        .collect();
+    assert_eq!(findings, [json::METADATA_TARGETS1_BYTES]);
+}
+
+#[test]
+fn audit_cargo_set_affected_platforms() {
+    let graph = JsonFixture::metadata_targets1().graph();
+    let advisory =
+        Advisory::parse("RUSTSEC-2022-9005.md", WINDOWS_ADVISORY).expect("advisory is valid");
+    assert_eq!(advisory.affected_os(), ["windows"]);
+    assert_eq!(advisory.affected_arch(), ["x86_64"]);
+    assert!(advisory.affects_platform(&PlatformSpec::Always));
+    assert!(advisory.affects_platform(&PlatformSpec::Any));
+    let mut db = AdvisoryDatabase::new();
+    db.add(advisory);
+
+    // bytes is built on every platform, but the advisory only affects Windows.
+    let linux = make_cargo_set(graph, "x86_64-unknown-linux-gnu");
+    assert!(db.audit_cargo_set(&linux, &AuditOptions::new()).is_empty());
+    let windows_arm = make_cargo_set(graph, "aarch64-pc-windows-msvc");
+    assert!(db
+        .audit_cargo_set(&windows_arm, &AuditOptions::new())
+        .is_empty());
+
+    let windows = make_cargo_set(graph, "x86_64-pc-windows-msvc");
+    let report = db.audit_cargo_set(&windows, &AuditOptions::new());
+    let findings: Vec<_> = report
+        .findings()
+        .iter()
+        .map(|finding| {
+            (
+                finding.package().id().repr(),
+                finding.build_platforms().to_vec(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        findings,
+        [(json::METADATA_TARGETS1_BYTES, vec![BuildPlatform::Target])],
+    );
+
+    // Cross-compiling from Windows to Linux only reports the advisory for the host platform,
+    // where bytes isn't built.
+    let mut options = CargoOptions::new();
+    options
+        .set_target_platform(
+            Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown)
+                .expect("known triple"),
+        )
+        .set_host_platform(
+            Platform::new("x86_64-pc-windows-msvc", TargetFeatures::Unknown).expect("known triple"),
+        );
+    let cross = graph
+        .resolve_workspace()
+        .to_feature_set(StandardFeatures::Default)
+        .into_cargo_set(&options)
+        .expect("cargo set resolved");
+    assert!(db.audit_cargo_set(&cross, &AuditOptions::new()).is_empty());
+
+    // Package sets don't track platforms, so the advisory is reported.
+    let report = db.audit_package_set(&graph.resolve_all(), &AuditOptions::new());
+    assert_eq!(report.findings().len(), 1);
+}
+
+fn make_cargo_set<'g>(graph: &'g PackageGraph, triple: &'static str) -> CargoSet<'g> {
+    let platform = Platform::new(triple, TargetFeatures::Unknown).expect("known triple");
+    let mut options = CargoOptions::new();
+    options
+        .set_target_platform(platform.clone())
+        .set_host_platform(platform);
+    graph
+        .resolve_workspace()
+        .to_feature_set(StandardFeatures::Default)
+        .into_cargo_set(&options)
+        .expect("cargo set resolved")
+}
@@ -6,11 +6,17 @@ use fixtures::{
     package_id,
 };
 use guppy::graph::{
+    cargo::{CargoOptions, CargoResolverVersion, CargoSet, FeatureActivations, InitialsPlatform},
     feature::{named_feature_filter, FeatureId, FeatureLabel, StandardFeatures},
-    BuildTargetId, BuildTargetKind, DependencyDirection, DotWrite, PackageDotVisitor, PackageLink,
+    BuildTargetId, BuildTargetKind, DependencyDirection, DotWrite, FingerprintOptions,
+    OwnedFeatureSet, OwnedPackageSet, PackageDotVisitor, PackageGraph, PackageLink,
     PackageMetadata,
 };
-use std::{fmt, iter};
+use guppy::{
+    platform::{Platform, TargetFeatures},
+    CargoMetadata,
+};
+use std::{collections::BTreeSet, fmt, iter, sync::Arc};
 
 mod small {
     use super::*;
@@ -181,6 +187,11 @@ mod small {
 
     proptest_suite!(metadata_cycle2);
 
+    #[test]
+    fn metadata_cycle2_resolve_many() {
+        assert_resolve_many_matches(JsonFixture::metadata_cycle2().graph());
+    }
+
     #[test]
     fn metadata_cycle_features() {
         let metadata_cycle_features = JsonFixture::metadata_cycle_features();
@@ -355,6 +366,378 @@ mod large {
 
     proptest_suite!(metadata_libra);
 
+    #[test]
+    fn metadata_libra_memory_usage() {
+        let graph = CargoMetadata::parse_json(JsonFixture::metadata_libra().json())
+            .expect("valid metadata")
+            .build_graph()
+            .expect("valid graph");
+
+        let usage = graph.memory_usage();
+        assert!(usage.packages() > 0, "packages use memory");
+        assert!(usage.dependency_graph() > 0, "links use memory");
+        assert!(usage.shared_strings() > 0, "dependency names are shared");
+        assert_eq!(
+            usage.feature_graph(),
+            None,
+            "feature graph not computed yet"
+        );
+
+        graph.feature_graph();
+        let with_features = graph.memory_usage();
+        assert!(
+            with_features
+                .feature_graph()
+                .expect("feature graph computed")
+                > 0,
+            "feature graph uses memory"
+        );
+        assert_eq!(
+            with_features.total(),
+            usage.total() + with_features.feature_graph().unwrap(),
+            "only the feature graph was added"
+        );
+    }
+
+    #[test]
+    fn metadata_libra_fingerprint() {
+        let json = JsonFixture::metadata_libra().json();
+        let build = |json: &str| {
+            CargoMetadata::parse_json(json)
+                .expect("valid metadata")
+                .build_graph()
+                .expect("valid graph")
+        };
+        let graph = build(json);
+        assert_eq!(
+            graph.fingerprint(),
+            JsonFixture::metadata_libra().graph().fingerprint(),
+            "fingerprint is deterministic"
+        );
+        assert_ne!(
+            graph.fingerprint(),
+            JsonFixture::metadata_libra_f0091a4().graph().fingerprint(),
+            "different graphs have different fingerprints"
+        );
+
+        // Moving the workspace changes the fingerprint, unless the workspace location is excluded.
+        let moved = build(&json.replace("/Users/fakeuser/local/libra", "/home/other/libra"));
+        assert_ne!(graph.fingerprint(), moved.fingerprint());
+        let mut opts = FingerprintOptions::new();
+        opts.set_include_workspace_location(false);
+        assert_eq!(
+            graph.fingerprint_with_options(&opts),
+            moved.fingerprint_with_options(&opts),
+            "workspace location excluded"
+        );
+    }
+
+    #[test]
+    fn metadata_libra_into_graph() {
+        let graph = JsonFixture::metadata_libra().graph();
+        let set = graph
+            .query_workspace_names(["libra-node"])
+            .expect("valid workspace name")
+            .resolve_with_fn(|_, link| !link.dev_only());
+        let expected_ids: BTreeSet<_> = set
+            .package_ids(DependencyDirection::Forward)
+            .cloned()
+            .collect();
+
+        let subgraph = set.into_graph();
+        subgraph.verify().expect("subgraph is valid");
+        assert_eq!(subgraph.package_count(), expected_ids.len());
+        for member in subgraph.workspace().iter() {
+            assert!(
+                expected_ids.contains(member.id()),
+                "workspace member {} is in the set",
+                member.name()
+            );
+        }
+
+        // Queries against the subgraph return the same results as the original set.
+        let subgraph_ids: BTreeSet<_> = subgraph
+            .query_workspace_names(["libra-node"])
+            .expect("libra-node is still a workspace member")
+            .resolve()
+            .package_ids(DependencyDirection::Forward)
+            .cloned()
+            .collect();
+        assert_eq!(subgraph_ids, expected_ids);
+
+        // The feature graph can be built for the subgraph.
+        let feature_set = subgraph
+            .resolve_all()
+            .to_feature_set(StandardFeatures::Default);
+        assert!(!feature_set.is_empty(), "features resolved in subgraph");
+    }
+
+    #[test]
+    fn metadata_libra_resolve_many() {
+        assert_resolve_many_matches(JsonFixture::metadata_libra().graph());
+    }
+
+    #[test]
+    fn metadata_libra_owned_sets() {
+        let graph = Arc::new(
+            CargoMetadata::parse_json(JsonFixture::metadata_libra().json())
+                .expect("valid metadata")
+                .build_graph()
+                .expect("valid graph"),
+        );
+
+        let packages =
+            OwnedPackageSet::new(graph.clone(), |graph| graph.query_workspace().resolve());
+        let features = OwnedFeatureSet::try_new(graph.clone(), |graph| {
+            Ok::<_, guppy::Error>(
+                graph
+                    .query_workspace_names(["libra-node"])?
+                    .to_feature_query(StandardFeatures::Default)
+                    .resolve(),
+            )
+        })
+        .expect("valid workspace name");
+
+        // The owned sets can be moved across threads independently of the graph.
+        let handle = std::thread::spawn(move || (packages.get().len(), features.get().len()));
+        let (package_count, feature_count) = handle.join().expect("thread doesn't panic");
+        assert_eq!(package_count, graph.query_workspace().resolve().len());
+        assert!(feature_count > 0, "libra-node has features");
+
+        let other_graph = JsonFixture::metadata_libra().graph();
+        let result = std::panic::catch_unwind(|| {
+            OwnedPackageSet::new(graph.clone(), |_| other_graph.resolve_all())
+        });
+        assert!(result.is_err(), "sets from other graphs are rejected");
+    }
+
+    #[test]
+    fn metadata_libra_package_queries_skip_feature_graph() {
+        let graph = CargoMetadata::parse_json(JsonFixture::metadata_libra().json())
+            .expect("valid metadata")
+            .build_graph()
+            .expect("valid graph");
+
+        let workspace = graph.resolve_workspace();
+        let deps = workspace
+            .to_package_query(DependencyDirection::Forward)
+            .resolve();
+        assert!(deps.len() > workspace.len(), "workspace has dependencies");
+        let reverse_deps = graph
+            .query_reverse(deps.root_ids(DependencyDirection::Reverse))
+            .expect("valid package IDs")
+            .resolve_with_fn(|_, link| !link.dev_only());
+        assert!(!reverse_deps.is_empty(), "reverse deps found");
+        for package in deps.packages(DependencyDirection::Forward) {
+            package.named_features().for_each(drop);
+        }
+
+        assert_eq!(
+            graph.memory_usage().feature_graph(),
+            None,
+            "package-level queries don't construct the feature graph"
+        );
+    }
+
+    #[test]
+    fn metadata_libra_cargo_set_recompute() {
+        let graph = JsonFixture::metadata_libra().graph();
+        let workspace = graph.resolve_workspace();
+        let initials = workspace.to_feature_set(StandardFeatures::Default);
+        let linux = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown)
+            .expect("known platform");
+
+        // Split the workspace in two to move packages between initials and features_only.
+        let (first_half, second_half): (Vec<_>, Vec<_>) = workspace
+            .package_ids(DependencyDirection::Forward)
+            .enumerate()
+            .partition(|(idx, _)| idx % 2 == 0);
+        let first_half = graph
+            .resolve_ids(first_half.into_iter().map(|(_, id)| id))
+            .expect("valid package IDs")
+            .to_feature_set(StandardFeatures::Default);
+        let second_half = graph
+            .resolve_ids(second_half.into_iter().map(|(_, id)| id))
+            .expect("valid package IDs")
+            .to_feature_set(StandardFeatures::Default);
+
+        for resolver in [CargoResolverVersion::V1, CargoResolverVersion::V2] {
+            let mut opts = CargoOptions::new();
+            opts.set_resolver(resolver);
+            let previous = initials
+                .clone()
+                .into_cargo_set(&opts)
+                .expect("cargo set computed");
+
+            let mut variants = Vec::new();
+            variants.push((initials.clone(), opts.clone()));
+            let mut dev_opts = opts.clone();
+            dev_opts.set_include_dev(true);
+            variants.push((initials.clone(), dev_opts));
+            let mut linux_opts = opts.clone();
+            linux_opts.set_target_platform(linux.clone());
+            variants.push((initials.clone(), linux_opts));
+            let mut host_opts = opts.clone();
+            host_opts.set_initials_platform(InitialsPlatform::Host);
+            variants.push((initials.clone(), host_opts));
+            variants.push((
+                workspace.to_feature_set(StandardFeatures::All),
+                opts.clone(),
+            ));
+
+            for (variant_initials, variant_opts) in variants {
+                let expected = variant_initials
+                    .clone()
+                    .into_cargo_set(&variant_opts)
+                    .expect("cargo set computed");
+                let actual = previous
+                    .recompute(
+                        variant_initials,
+                        graph.feature_graph().resolve_none(),
+                        &variant_opts,
+                    )
+                    .expect("cargo set recomputed");
+                assert_cargo_sets_eq(&actual, &expected);
+            }
+
+            // Moving packages between initials and features_only.
+            let expected = CargoSet::new(first_half.clone(), second_half.clone(), &opts)
+                .expect("cargo set computed");
+            let actual = previous
+                .recompute(first_half.clone(), second_half.clone(), &opts)
+                .expect("cargo set recomputed");
+            assert_cargo_sets_eq(&actual, &expected);
+        }
+    }
+
+    #[test]
+    fn metadata_libra_resolver_v3() {
+        let graph = JsonFixture::metadata_libra().graph();
+        let initials = graph
+            .resolve_workspace()
+            .to_feature_set(StandardFeatures::Default);
+        let linux = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown)
+            .expect("known platform");
+
+        // Version 3 of the resolver only changes how versions are picked for the Cargo.lock, so
+        // build simulations match version 2.
+        for include_dev in [false, true] {
+            let mut v2_opts = CargoOptions::new();
+            v2_opts
+                .set_resolver(CargoResolverVersion::V2)
+                .set_include_dev(include_dev)
+                .set_target_platform(linux.clone());
+            let mut v3_opts = v2_opts.clone();
+            v3_opts.set_resolver(CargoResolverVersion::V3);
+
+            let v2 = initials
+                .clone()
+                .into_cargo_set(&v2_opts)
+                .expect("cargo set computed");
+            let v3 = initials
+                .clone()
+                .into_cargo_set(&v3_opts)
+                .expect("cargo set computed");
+            assert_cargo_sets_eq(&v3, &v2);
+        }
+    }
+
+    #[test]
+    fn metadata_libra_feature_activations() {
+        let graph = JsonFixture::metadata_libra().graph();
+        let initials = graph
+            .resolve_workspace()
+            .to_feature_set(StandardFeatures::Default);
+        let cargo_options = CargoOptions::new();
+        let activations =
+            FeatureActivations::new(&initials, &cargo_options).expect("activations computed");
+        let unified = initials
+            .into_cargo_set(&cargo_options)
+            .expect("cargo set computed");
+
+        assert!(!activations.is_empty(), "third-party deps activated");
+        let mut multiple_activators = false;
+        for (package_id, dep_activations) in activations.iter() {
+            assert!(
+                !graph.metadata(package_id).unwrap().in_workspace(),
+                "{} is a third-party dependency",
+                package_id
+            );
+            multiple_activators |= dep_activations.len() > 1;
+            // Features built by any single activator are a subset of the unified features.
+            for activation in dep_activations {
+                let unified_features = unified
+                    .platform_features(activation.build_platform())
+                    .features_for(package_id)
+                    .expect("valid package ID")
+                    .expect("package present in unified set");
+                for label in activation.features().labels() {
+                    assert!(
+                        unified_features.contains(*label),
+                        "for {} activated by {}, feature {} is unified",
+                        package_id,
+                        activation.activator().name(),
+                        label
+                    );
+                }
+            }
+        }
+        assert!(
+            multiple_activators,
+            "some deps activated by multiple packages"
+        );
+    }
+
+    #[test]
+    fn metadata_libra_test_targets() {
+        let graph = JsonFixture::metadata_libra().graph();
+        let tiny_keccak = graph
+            .packages()
+            .find(|package| package.name() == "tiny-keccak")
+            .expect("tiny-keccak is present");
+
+        let test_target_ids = |features: &[&str]| {
+            let cargo_set = graph
+                .feature_graph()
+                .resolve_ids(
+                    iter::once(FeatureId::base(tiny_keccak.id())).chain(
+                        features
+                            .iter()
+                            .map(|feature| FeatureId::named(tiny_keccak.id(), feature)),
+                    ),
+                )
+                .expect("valid feature IDs")
+                .into_cargo_set(&CargoOptions::new_test())
+                .expect("cargo set computed");
+            let mut ids: Vec<_> = cargo_set
+                .test_targets()
+                .map(|build_target| build_target.id())
+                .collect();
+            ids.sort_unstable();
+            ids
+        };
+
+        // The "kangaroo" test requires the k12 feature, and benchmarks are never built.
+        assert_eq!(
+            test_target_ids(&["default"]),
+            [
+                BuildTargetId::Library,
+                BuildTargetId::Example("simple"),
+                BuildTargetId::Test("keccak"),
+            ],
+            "default features"
+        );
+        assert_eq!(
+            test_target_ids(&["k12"]),
+            [
+                BuildTargetId::Library,
+                BuildTargetId::Example("simple"),
+                BuildTargetId::Test("kangaroo"),
+            ],
+            "k12 only"
+        );
+    }
+
     #[test]
     fn metadata_libra_f0091a4() {
         let metadata = JsonFixture::metadata_libra_f0091a4();
@@ -489,6 +872,45 @@ mod guppy_tests {
     proptest_suite!(metadata_guppy_44b62fa);
 }
 
+fn assert_cargo_sets_eq(actual: &CargoSet<'_>, expected: &CargoSet<'_>) {
+    assert_eq!(actual.target_features(), expected.target_features());
+    assert_eq!(actual.host_features(), expected.host_features());
+    assert_eq!(actual.target_direct_deps(), expected.target_direct_deps());
+    assert_eq!(actual.host_direct_deps(), expected.host_direct_deps());
+    assert_eq!(
+        actual.proc_macro_links().len(),
+        expected.proc_macro_links().len()
+    );
+    assert_eq!(
+        actual.build_dep_links().len(),
+        expected.build_dep_links().len()
+    );
+}
+
+/// Checks that `resolve_many` returns the same results as resolving queries one by one.
+fn assert_resolve_many_matches(graph: &PackageGraph) {
+    let mut queries = vec![graph.query_workspace()];
+    for package in graph.packages() {
+        for direction in [DependencyDirection::Forward, DependencyDirection::Reverse] {
+            queries.push(
+                graph
+                    .query_directed(iter::once(package.id()), direction)
+                    .expect("valid package ID"),
+            );
+        }
+    }
+
+    let expected: Vec<_> = queries
+        .iter()
+        .map(|query| query.clone().resolve())
+        .collect();
+    let actual = graph.resolve_many(queries);
+    assert_eq!(actual.len(), expected.len(), "one result per query");
+    for (actual, expected) in actual.iter().zip(&expected) {
+        assert_eq!(actual, expected, "resolve_many matches resolve");
+    }
+}
+
 struct NameVisitor;
 
 impl PackageDotVisitor for NameVisitor {
@@ -12,7 +12,15 @@ macro_rules! proptest_suite {
     };
 }
 
+#[cfg(feature = "rustsec")]
+mod audit_tests;
 mod feature_helpers;
 mod graph_tests;
 mod invalid_tests;
+#[cfg(feature = "outdated")]
+mod outdated_tests;
+#[cfg(feature = "policy")]
+mod policy_tests;
+#[cfg(feature = "sbom")]
+mod sbom_tests;
 mod weak_namespaced;
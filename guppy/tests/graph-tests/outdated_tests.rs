@@ -0,0 +1,254 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use camino::Utf8PathBuf;
+use fixtures::json::{self, JsonFixture};
+use guppy::{
+    errors::Error,
+    graph::outdated::{
+        index_path, IndexVersion, LocalRegistryIndex, OutdatedOptions, OutdatedReport,
+        RegistryIndex,
+    },
+    Version,
+};
+use pretty_assertions::assert_eq;
+use std::{env, error, fs, process};
+
+fn index_line(name: &str, version: &str, yanked: bool) -> String {
+    format!(
+        r#"{{"name":"{name}","vers":"{version}","deps":[],"cksum":"0000","features":{{}},"yanked":{yanked}}}"#
+    )
+}
+
+fn index_file(name: &str) -> Option<String> {
+    let versions: &[(&str, bool)] = match name {
+        "bytes" => &[
+            ("0.3.0", false),
+            ("0.4.12", false),
+            ("0.5.4", false),
+            ("0.5.6", false),
+            ("1.6.0", false),
+            ("1.7.0-alpha.1", false),
+            ("1.7.0", true),
+        ],
+        "lazy_static" => &[("0.2.11", false), ("1.4.0", false), ("1.5.0", false)],
+        _ => return None,
+    };
+    let lines: Vec<_> = versions
+        .iter()
+        .map(|(version, yanked)| index_line(name, version, *yanked))
+        .collect();
+    Some(lines.join("\n"))
+}
+
+fn test_index(name: &str) -> Result<Option<String>, Box<dyn error::Error + Send + Sync>> {
+    Ok(index_file(name))
+}
+
+fn v(version: &str) -> Version {
+    version.parse().expect("valid version")
+}
+
+#[test]
+fn index_paths() {
+    assert_eq!(index_path("a"), "1/a");
+    assert_eq!(index_path("ab"), "2/ab");
+    assert_eq!(index_path("abc"), "3/a/abc");
+    assert_eq!(index_path("serde"), "se/rd/serde");
+    assert_eq!(index_path("Inflector"), "in/fl/inflector");
+}
+
+#[test]
+fn parse_index_file() {
+    let versions = IndexVersion::parse_index_file(&format!(
+        "{}\n\n{}\n{}\n",
+        index_line("foo", "1.0.0", false),
+        index_line("foo", "not-a-version", false),
+        index_line("foo", "1.1.0", true),
+    ))
+    .expect("index file is valid");
+    assert_eq!(
+        versions,
+        [
+            IndexVersion {
+                version: v("1.0.0"),
+                yanked: false,
+            },
+            IndexVersion {
+                version: v("1.1.0"),
+                yanked: true,
+            },
+        ],
+    );
+
+    IndexVersion::parse_index_file("not json").expect_err("invalid index file");
+}
+
+#[test]
+fn outdated_report() {
+    let graph = JsonFixture::metadata_dups().graph();
+    let report =
+        OutdatedReport::compute(&graph.resolve_all(), &test_index, &OutdatedOptions::new())
+            .expect("report computed");
+
+    let summary: Vec<_> = report
+        .packages()
+        .iter()
+        .map(|package| {
+            (
+                package.package().id().repr(),
+                package.latest_compatible().cloned(),
+                package.latest().cloned(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            // Yanked versions and pre-releases are skipped.
+            (
+                json::METADATA_DUPS_BYTES_03,
+                Some(v("0.3.0")),
+                Some(v("1.6.0"))
+            ),
+            (
+                json::METADATA_DUPS_BYTES_05,
+                Some(v("0.5.6")),
+                Some(v("1.6.0"))
+            ),
+            (
+                json::METADATA_DUPS_LAZY_STATIC_02,
+                Some(v("0.2.11")),
+                Some(v("1.5.0"))
+            ),
+            (
+                json::METADATA_DUPS_LAZY_STATIC_1,
+                Some(v("1.5.0")),
+                Some(v("1.5.0"))
+            ),
+        ],
+    );
+
+    // Requirements are recorded for the workspace package that declares them.
+    let bytes_05 = &report.packages()[1];
+    assert!(bytes_05.is_outdated());
+    let requirements: Vec<_> = bytes_05
+        .requirements()
+        .iter()
+        .map(|req| {
+            (
+                req.workspace_package().id().repr(),
+                req.version_req().to_string(),
+                req.latest_matching().cloned(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        requirements,
+        [(
+            json::METADATA_DUPS_TESTCRATE,
+            "^0.5".to_owned(),
+            Some(v("0.5.6"))
+        )],
+    );
+
+    let by_workspace_package = report.by_workspace_package();
+    assert_eq!(by_workspace_package.len(), 1);
+    let (workspace_package, packages) = by_workspace_package
+        .into_iter()
+        .next()
+        .expect("one workspace package");
+    assert_eq!(workspace_package.repr(), json::METADATA_DUPS_TESTCRATE);
+    assert_eq!(packages.len(), 4, "all direct dependencies are outdated");
+}
+
+#[test]
+fn outdated_options() {
+    let graph = JsonFixture::metadata_dups().graph();
+    let up_to_date_index =
+        |name: &str| -> Result<Option<String>, Box<dyn error::Error + Send + Sync>> {
+            Ok(match name {
+                "bytes" => Some(format!(
+                    "{}\n{}",
+                    index_line(name, "0.5.4", false),
+                    index_line(name, "0.6.0-rc.1", false)
+                )),
+                _ => None,
+            })
+        };
+
+    let report = OutdatedReport::compute(
+        &graph.resolve_all(),
+        &up_to_date_index,
+        &OutdatedOptions::new(),
+    )
+    .expect("report computed");
+    let ids: Vec<_> = report
+        .packages()
+        .iter()
+        .map(|package| package.package().id().repr())
+        .collect();
+    assert_eq!(
+        ids,
+        [json::METADATA_DUPS_BYTES_03],
+        "bytes 0.5.4 is up to date"
+    );
+
+    let mut options = OutdatedOptions::new();
+    options
+        .set_include_up_to_date(true)
+        .set_include_prereleases(true);
+    let report = OutdatedReport::compute(&graph.resolve_all(), &up_to_date_index, &options)
+        .expect("report computed");
+    let summary: Vec<_> = report
+        .packages()
+        .iter()
+        .map(|package| (package.package().id().repr(), package.latest().cloned()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (json::METADATA_DUPS_BYTES_03, Some(v("0.6.0-rc.1"))),
+            (json::METADATA_DUPS_BYTES_05, Some(v("0.6.0-rc.1"))),
+            // Packages not in the index are included, without any versions.
+            (json::METADATA_DUPS_LAZY_STATIC_02, None),
+            (json::METADATA_DUPS_LAZY_STATIC_1, None),
+        ],
+    );
+
+    let failing_index =
+        |name: &str| -> Result<Option<String>, Box<dyn error::Error + Send + Sync>> {
+            Err(format!("no network access for {name}").into())
+        };
+    let err = OutdatedReport::compute(
+        &graph.resolve_all(),
+        &failing_index,
+        &OutdatedOptions::new(),
+    )
+    .expect_err("index errors are returned");
+    assert!(
+        matches!(&err, Error::RegistryIndexError(name, _) if name == "bytes"),
+        "actual error: {err}"
+    );
+}
+
+#[test]
+fn local_registry_index() {
+    let dir = Utf8PathBuf::try_from(env::temp_dir())
+        .expect("temp dir is valid UTF-8")
+        .join(format!("guppy-registry-index-test-{}", process::id()));
+    let index = LocalRegistryIndex::new(&dir);
+    assert_eq!(index.root(), dir);
+
+    let path = dir.join(index_path("lazy_static"));
+    fs::create_dir_all(path.parent().unwrap()).expect("created index dir");
+    fs::write(&path, index_file("lazy_static").unwrap()).expect("wrote index file");
+
+    assert_eq!(
+        index.index_file("lazy_static").expect("index file read"),
+        index_file("lazy_static"),
+    );
+    assert_eq!(index.index_file("bytes").expect("missing file is OK"), None);
+
+    fs::remove_dir_all(&dir).expect("removed temp dir");
+}
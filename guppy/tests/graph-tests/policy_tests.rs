@@ -0,0 +1,265 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use fixtures::json::{self, JsonFixture};
+use guppy::{
+    errors::Error,
+    graph::{
+        cargo::{BuildPlatform, CargoOptions, CargoSet},
+        feature::StandardFeatures,
+        policy::{Policy, ViolationKind},
+        PackageGraph,
+    },
+    platform::{Platform, TargetFeatures},
+};
+use pretty_assertions::assert_eq;
+
+#[test]
+fn parse_policy() {
+    let policy = Policy::parse(
+        r#"
+        [[bans]]
+        name = "lazy_static"
+        version = "< 1"
+        reason = "use once_cell instead"
+
+        [[bans]]
+        name = "openssl"
+        "#,
+    )
+    .expect("policy is valid");
+    let bans: Vec<_> = policy
+        .bans()
+        .iter()
+        .map(|rule| {
+            (
+                rule.name(),
+                rule.version_req().map(|req| req.to_string()),
+                rule.reason(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        bans,
+        [
+            (
+                "lazy_static",
+                Some("<1".to_owned()),
+                Some("use once_cell instead")
+            ),
+            ("openssl", None, None),
+        ],
+    );
+
+    for invalid in [
+        "[licenses]\nallow = [\"MIT\"]",
+        "[[bans]]\nversion = \"1\"",
+        "[duplicates]\nmax-versions = \"one\"",
+    ] {
+        let err = Policy::parse(invalid).expect_err("policy is invalid");
+        assert!(
+            matches!(err, Error::PolicyParseError(_)),
+            "for policy {invalid}: {err}"
+        );
+    }
+}
+
+#[test]
+fn evaluate_package_set() {
+    let graph = JsonFixture::metadata_dups().graph();
+    let policy = Policy::parse(
+        r#"
+        [[bans]]
+        name = "lazy_static"
+        version = "< 1"
+        reason = "use once_cell instead"
+
+        [licenses]
+        deny = ["MIT"]
+
+        [duplicates]
+        max-versions = 1
+        skip = ["bytes"]
+        "#,
+    )
+    .expect("policy is valid");
+
+    let report = policy.evaluate_package_set(&graph.resolve_all());
+    let violations: Vec<_> = report
+        .violations()
+        .iter()
+        .map(|violation| {
+            (
+                violation.package().id().repr(),
+                violation.kind().to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        violations,
+        [
+            (
+                json::METADATA_DUPS_BYTES_03,
+                "license requires denied license MIT".to_owned()
+            ),
+            (
+                json::METADATA_DUPS_BYTES_05,
+                "license requires denied license MIT".to_owned()
+            ),
+            (
+                json::METADATA_DUPS_LAZY_STATIC_02,
+                "package is banned: use once_cell instead".to_owned()
+            ),
+            // lazy_static is licensed under MIT/Apache-2.0, which can be satisfied through
+            // Apache-2.0.
+            (
+                json::METADATA_DUPS_LAZY_STATIC_02,
+                "2 versions are included, more than 1".to_owned()
+            ),
+            (
+                json::METADATA_DUPS_LAZY_STATIC_1,
+                "2 versions are included, more than 1".to_owned()
+            ),
+        ],
+    );
+
+    let violation = &report.violations()[2];
+    assert!(
+        matches!(violation.kind(), ViolationKind::Banned(rule) if rule.name() == "lazy_static")
+    );
+    assert!(violation.build_platforms().is_empty());
+    let chains: Vec<Vec<_>> = violation
+        .chains()
+        .iter()
+        .map(|chain| chain.iter().map(|package| package.id().repr()).collect())
+        .collect();
+    assert_eq!(
+        chains,
+        [[
+            json::METADATA_DUPS_TESTCRATE,
+            json::METADATA_DUPS_LAZY_STATIC_02
+        ]],
+    );
+
+    assert!(Policy::parse("")
+        .expect("empty policy is valid")
+        .evaluate_package_set(&graph.resolve_all())
+        .is_empty());
+}
+
+#[test]
+fn evaluate_sources() {
+    let graph = JsonFixture::metadata_alternate_registries().graph();
+    let policy = Policy::parse(
+        r#"
+        [sources]
+        allow-registries = ["crates-io"]
+        "#,
+    )
+    .expect("policy is valid");
+    let report = policy.evaluate_package_set(&graph.resolve_all());
+    assert!(!report.is_empty());
+    for violation in report.violations() {
+        assert_eq!(
+            violation.kind(),
+            &ViolationKind::UnlistedRegistry {
+                registry: json::METADATA_ALTERNATE_REGISTRY_URL
+            },
+        );
+    }
+
+    let policy = Policy::parse(&format!(
+        r#"
+        [sources]
+        allow-registries = ["crates-io", "{}/"]
+        "#,
+        json::METADATA_ALTERNATE_REGISTRY_URL
+    ))
+    .expect("policy is valid");
+    assert!(policy.evaluate_package_set(&graph.resolve_all()).is_empty());
+
+    let graph = JsonFixture::metadata1().graph();
+    let policy = Policy::parse(
+        r#"
+        [sources]
+        allow-git = ["https://github.com/rust-lang/cargo"]
+        "#,
+    )
+    .expect("policy is valid");
+    let report = policy.evaluate_package_set(&graph.resolve_all());
+    let violations: Vec<_> = report
+        .violations()
+        .iter()
+        .map(|violation| (violation.package().name(), violation.kind().clone()))
+        .collect();
+    assert_eq!(
+        violations,
+        [(
+            "walkdir",
+            ViolationKind::UnlistedGitRepository {
+                repository: "https://github.com/BurntSushi/walkdir"
+            }
+        )],
+    );
+
+    let policy = Policy::parse(
+        r#"
+        [sources]
+        allow-git = ["https://github.com/BurntSushi/walkdir.git"]
+        "#,
+    )
+    .expect("policy is valid");
+    assert!(policy.evaluate_package_set(&graph.resolve_all()).is_empty());
+}
+
+#[test]
+fn evaluate_cargo_set() {
+    let graph = JsonFixture::metadata_targets1().graph();
+    let policy = Policy::parse(
+        r#"
+        [[bans]]
+        name = "lazy_static"
+        version = "^0.2"
+        "#,
+    )
+    .expect("policy is valid");
+
+    // lazy_static 0.2.11 is only a dependency on non-Windows platforms.
+    let linux = make_cargo_set(graph, "x86_64-unknown-linux-gnu");
+    let report = policy.evaluate_cargo_set(&linux);
+    let violations: Vec<_> = report
+        .violations()
+        .iter()
+        .map(|violation| {
+            (
+                violation.package().id().repr(),
+                violation.build_platforms().to_vec(),
+                violation.chains()[0][0].id().repr(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        violations,
+        [(
+            json::METADATA_TARGETS1_LAZY_STATIC_02,
+            vec![BuildPlatform::Target],
+            json::METADATA_TARGETS1_TESTCRATE,
+        )],
+    );
+
+    let windows = make_cargo_set(graph, "x86_64-pc-windows-msvc");
+    assert!(policy.evaluate_cargo_set(&windows).is_empty());
+}
+
+fn make_cargo_set<'g>(graph: &'g PackageGraph, triple: &'static str) -> CargoSet<'g> {
+    let platform = Platform::new(triple, TargetFeatures::Unknown).expect("known triple");
+    let mut options = CargoOptions::new();
+    options
+        .set_target_platform(platform.clone())
+        .set_host_platform(platform);
+    graph
+        .resolve_workspace()
+        .to_feature_set(StandardFeatures::Default)
+        .into_cargo_set(&options)
+        .expect("cargo set resolved")
+}
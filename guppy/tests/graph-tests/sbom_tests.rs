@@ -0,0 +1,566 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use fixtures::json::{self, JsonFixture};
+use guppy::graph::{
+    cargo::{CargoOptions, CargoResolverVersion},
+    feature::StandardFeatures,
+    sbom::{
+        CargoLockChecksums, CycloneDxBom, GitHubSnapshot, GitHubSnapshotOptions, SbomOptions,
+        SpdxDocument,
+    },
+};
+use pretty_assertions::assert_eq;
+use serde_json::{json, Value};
+
+static CARGO_LOCK: &str = r#"
+version = 3
+
+[[package]]
+name = "bytes"
+version = "0.5.4"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "130aac562c0dd69c56b3b1cc8ffd2e17be31d0b6c25b61c96b76231aa23e39e1"
+
+[[package]]
+name = "lazy_static"
+version = "1.4.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "e2abad23fbc42b3700f2f279844dc832adb2b2eb069b2df918f455c4e18cc646"
+
+[[package]]
+name = "testcrate-dups"
+version = "0.1.0"
+dependencies = [
+ "bytes 0.5.4",
+ "lazy_static 1.4.0",
+]
+"#;
+
+static CARGO_LOCK_V1: &str = r#"
+[[package]]
+name = "lazy_static"
+version = "0.2.11"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "testcrate-dups"
+version = "0.1.0"
+
+[metadata]
+"checksum lazy_static 0.2.11 (registry+https://github.com/rust-lang/crates.io-index)" = "76f033c7ad61445c5b347c7382dd1237847eb1bce590fe50365dcb33d546be73"
+"checksum bytes 0.3.0 (registry+https://github.com/rust-lang/crates.io-index)" = "<none>"
+"#;
+
+#[test]
+fn cargo_lock_checksums() {
+    let graph = JsonFixture::metadata_dups().graph();
+    let metadata = |id: &str| graph.metadata(&fixtures::package_id(id)).unwrap();
+
+    let checksums = CargoLockChecksums::parse(CARGO_LOCK).expect("Cargo.lock is valid");
+    assert_eq!(
+        checksums.len(),
+        2,
+        "workspace packages don't have checksums"
+    );
+    assert_eq!(
+        checksums.get(&metadata(json::METADATA_DUPS_LAZY_STATIC_1)),
+        Some("e2abad23fbc42b3700f2f279844dc832adb2b2eb069b2df918f455c4e18cc646"),
+    );
+    assert_eq!(
+        checksums.get(&metadata(json::METADATA_DUPS_LAZY_STATIC_02)),
+        None
+    );
+    assert_eq!(
+        checksums.get(&metadata(json::METADATA_DUPS_TESTCRATE)),
+        None
+    );
+
+    let checksums = CargoLockChecksums::parse(CARGO_LOCK_V1).expect("v1 Cargo.lock is valid");
+    assert_eq!(checksums.len(), 1, "<none> checksums are skipped");
+    assert_eq!(
+        checksums.get(&metadata(json::METADATA_DUPS_LAZY_STATIC_02)),
+        Some("76f033c7ad61445c5b347c7382dd1237847eb1bce590fe50365dcb33d546be73"),
+    );
+
+    CargoLockChecksums::parse("package = 1").expect_err("invalid Cargo.lock");
+}
+
+#[test]
+fn cyclonedx_from_cargo_set() {
+    let graph = JsonFixture::metadata_dups().graph();
+    let cargo_set = graph
+        .resolve_workspace()
+        .to_feature_set(StandardFeatures::Default)
+        .into_cargo_set(
+            CargoOptions::new()
+                .set_resolver(CargoResolverVersion::V2)
+                .set_include_dev(true),
+        )
+        .expect("cargo set resolved");
+    let checksums = CargoLockChecksums::parse(CARGO_LOCK).expect("Cargo.lock is valid");
+    let mut options = SbomOptions::new();
+    options
+        .set_checksums(&checksums)
+        .set_timestamp("2024-07-01T12:00:00Z")
+        .set_serial_number("urn:uuid:3e671687-395b-41f5-a30f-a58921a69b79");
+
+    let bom = CycloneDxBom::from_cargo_set(&cargo_set, &options);
+    let value: Value =
+        serde_json::from_str(&bom.to_json_string().expect("SBOM serializes")).expect("valid JSON");
+
+    assert_eq!(value["bomFormat"], "CycloneDX");
+    assert_eq!(value["specVersion"], "1.5");
+    assert_eq!(
+        value["serialNumber"],
+        "urn:uuid:3e671687-395b-41f5-a30f-a58921a69b79"
+    );
+    assert_eq!(value["metadata"]["timestamp"], "2024-07-01T12:00:00Z");
+    assert_eq!(value["metadata"]["tools"]["components"][0]["name"], "guppy");
+
+    // With dev-dependencies included, all packages are built.
+    let components = value["components"]
+        .as_array()
+        .expect("components is an array");
+    assert_eq!(components.len(), bom.component_count());
+    let refs: Vec<_> = components
+        .iter()
+        .map(|component| component["bom-ref"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        refs,
+        [
+            json::METADATA_DUPS_BYTES_03,
+            json::METADATA_DUPS_BYTES_05,
+            json::METADATA_DUPS_LAZY_STATIC_02,
+            json::METADATA_DUPS_LAZY_STATIC_1,
+            json::METADATA_DUPS_TESTCRATE,
+        ],
+        "components are sorted by name and version"
+    );
+
+    let lazy_static = &components[3];
+    assert_eq!(lazy_static["type"], "library");
+    assert_eq!(lazy_static["name"], "lazy_static");
+    assert_eq!(lazy_static["version"], "1.4.0");
+    assert_eq!(lazy_static["purl"], "pkg:cargo/lazy_static@1.4.0");
+    assert_eq!(
+        lazy_static["licenses"],
+        json!([{ "expression": "MIT OR Apache-2.0" }]),
+    );
+    assert_eq!(
+        lazy_static["hashes"],
+        json!([{
+            "alg": "SHA-256",
+            "content": "e2abad23fbc42b3700f2f279844dc832adb2b2eb069b2df918f455c4e18cc646",
+        }]),
+    );
+    assert_eq!(
+        lazy_static["externalReferences"][0],
+        json!({ "type": "vcs", "url": "https://github.com/rust-lang-nursery/lazy-static.rs" }),
+    );
+
+    let testcrate = &components[4];
+    assert!(testcrate.get("purl").is_none(), "no purl for path packages");
+    assert!(
+        testcrate.get("hashes").is_none(),
+        "no hash for path packages"
+    );
+    assert!(
+        testcrate.get("licenses").is_none(),
+        "no license in package metadata"
+    );
+
+    let dependencies = value["dependencies"]
+        .as_array()
+        .expect("dependencies is an array");
+    assert_eq!(
+        dependencies.len(),
+        components.len(),
+        "every component has a dependency entry"
+    );
+    let testcrate_deps = dependencies
+        .iter()
+        .find(|dep| dep["ref"] == json::METADATA_DUPS_TESTCRATE)
+        .expect("workspace package has dependencies");
+    let mut depends_on: Vec<_> = testcrate_deps["dependsOn"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|dep| dep.as_str().unwrap())
+        .collect();
+    depends_on.sort_unstable();
+    assert_eq!(
+        depends_on,
+        [
+            json::METADATA_DUPS_BYTES_03,
+            json::METADATA_DUPS_BYTES_05,
+            json::METADATA_DUPS_LAZY_STATIC_02,
+            json::METADATA_DUPS_LAZY_STATIC_1,
+        ],
+    );
+}
+
+#[test]
+fn cyclonedx_from_package_set() {
+    let graph = JsonFixture::metadata_dups().graph();
+    let package_set = graph
+        .query_forward([&fixtures::package_id(json::METADATA_DUPS_BYTES_05)])
+        .expect("valid package ID")
+        .resolve();
+
+    let bom = CycloneDxBom::from_package_set(&package_set, &SbomOptions::new());
+    let value: Value =
+        serde_json::from_str(&bom.to_json_string().expect("SBOM serializes")).expect("valid JSON");
+
+    // Without options, the SBOM is fully determined by its inputs.
+    assert!(value.get("serialNumber").is_none());
+    assert!(value["metadata"].get("timestamp").is_none());
+
+    assert_eq!(bom.component_count(), 1);
+    let component = &value["components"][0];
+    assert_eq!(component["bom-ref"], json::METADATA_DUPS_BYTES_05);
+    assert!(
+        component.get("properties").is_none(),
+        "package sets don't record features"
+    );
+    assert!(component.get("hashes").is_none(), "no checksums provided");
+    assert_eq!(
+        value["dependencies"],
+        json!([{ "ref": json::METADATA_DUPS_BYTES_05, "dependsOn": [] }]),
+    );
+}
+
+#[test]
+fn spdx_from_package_set() {
+    let graph = JsonFixture::metadata_dups().graph();
+    let checksums = CargoLockChecksums::parse(CARGO_LOCK).expect("Cargo.lock is valid");
+    let mut options = SbomOptions::new();
+    options
+        .set_checksums(&checksums)
+        .set_timestamp("2024-07-01T12:00:00Z");
+
+    let document = SpdxDocument::from_package_set(&graph.resolve_all(), &options);
+    assert_eq!(document.package_count(), 5);
+    let value: Value =
+        serde_json::from_str(&document.to_json_string().expect("SPDX document serializes"))
+            .expect("valid JSON");
+
+    assert_eq!(value["spdxVersion"], "SPDX-2.3");
+    assert_eq!(value["dataLicense"], "CC0-1.0");
+    assert_eq!(value["SPDXID"], "SPDXRef-DOCUMENT");
+    assert_eq!(value["name"], "testcrate-dups");
+    assert_eq!(value["creationInfo"]["created"], "2024-07-01T12:00:00Z");
+    let namespace = value["documentNamespace"].as_str().unwrap();
+    assert!(
+        namespace.starts_with("https://spdx.org/spdxdocs/testcrate-dups-"),
+        "namespace is derived from the document: {namespace}"
+    );
+    assert_eq!(
+        SpdxDocument::from_package_set(&graph.resolve_all(), &options).to_tag_value(),
+        document.to_tag_value(),
+        "documents are deterministic with a fixed timestamp"
+    );
+
+    let packages = value["packages"].as_array().expect("packages is an array");
+    let lazy_static = packages
+        .iter()
+        .find(|package| package["SPDXID"] == "SPDXRef-Package-lazy-static-1.4.0")
+        .expect("lazy_static 1.4.0 is present");
+    assert_eq!(lazy_static["name"], "lazy_static");
+    assert_eq!(lazy_static["versionInfo"], "1.4.0");
+    assert_eq!(
+        lazy_static["downloadLocation"],
+        "https://crates.io/api/v1/crates/lazy_static/1.4.0/download"
+    );
+    assert_eq!(lazy_static["licenseDeclared"], "MIT OR Apache-2.0");
+    assert_eq!(lazy_static["licenseConcluded"], "NOASSERTION");
+    assert_eq!(
+        lazy_static["checksums"],
+        json!([{
+            "algorithm": "SHA256",
+            "checksumValue": "e2abad23fbc42b3700f2f279844dc832adb2b2eb069b2df918f455c4e18cc646",
+        }]),
+    );
+    assert_eq!(
+        lazy_static["externalRefs"][0]["referenceLocator"],
+        "pkg:cargo/lazy_static@1.4.0"
+    );
+
+    // Missing license data is recorded as NOASSERTION.
+    let testcrate = packages
+        .iter()
+        .find(|package| package["name"] == "testcrate-dups")
+        .expect("workspace package is present");
+    assert_eq!(testcrate["licenseDeclared"], "NOASSERTION");
+    assert_eq!(testcrate["downloadLocation"], "NOASSERTION");
+    assert!(testcrate.get("checksums").is_none());
+
+    let relationships: Vec<_> = value["relationships"]
+        .as_array()
+        .expect("relationships is an array")
+        .iter()
+        .map(|rel| {
+            format!(
+                "{} {} {}",
+                rel["spdxElementId"].as_str().unwrap(),
+                rel["relationshipType"].as_str().unwrap(),
+                rel["relatedSpdxElement"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        relationships,
+        [
+            "SPDXRef-DOCUMENT DESCRIBES SPDXRef-Package-testcrate-dups-0.1.0",
+            "SPDXRef-Package-bytes-0.3.0 DEV_DEPENDENCY_OF SPDXRef-Package-testcrate-dups-0.1.0",
+            "SPDXRef-Package-testcrate-dups-0.1.0 DEPENDS_ON SPDXRef-Package-bytes-0.5.4",
+            "SPDXRef-Package-lazy-static-0.2.11 DEV_DEPENDENCY_OF SPDXRef-Package-testcrate-dups-0.1.0",
+            "SPDXRef-Package-testcrate-dups-0.1.0 DEPENDS_ON SPDXRef-Package-lazy-static-1.4.0",
+        ],
+    );
+}
+
+#[test]
+fn spdx_tag_value() {
+    let graph = JsonFixture::metadata_dups().graph();
+    let package_set = graph
+        .query_forward([&fixtures::package_id(json::METADATA_DUPS_LAZY_STATIC_1)])
+        .expect("valid package ID")
+        .resolve();
+    let mut options = SbomOptions::new();
+    options
+        .set_timestamp("2024-07-01T12:00:00Z")
+        .set_serial_number("https://example.com/spdxdocs/lazy-static")
+        .set_document_name("lazy-static-sbom");
+
+    let document = SpdxDocument::from_package_set(&package_set, &options);
+    let expected = format!(
+        "SPDXVersion: SPDX-2.3
+DataLicense: CC0-1.0
+SPDXID: SPDXRef-DOCUMENT
+DocumentName: lazy-static-sbom
+DocumentNamespace: https://example.com/spdxdocs/lazy-static
+Creator: Tool: guppy-{version}
+Created: 2024-07-01T12:00:00Z
+
+PackageName: lazy_static
+SPDXID: SPDXRef-Package-lazy-static-1.4.0
+PackageVersion: 1.4.0
+PackageDownloadLocation: https://crates.io/api/v1/crates/lazy_static/1.4.0/download
+FilesAnalyzed: false
+PackageLicenseConcluded: NOASSERTION
+PackageLicenseDeclared: MIT OR Apache-2.0
+PackageCopyrightText: NOASSERTION
+PackageDescription: <text>A macro for declaring lazily evaluated statics in Rust.</text>
+ExternalRef: PACKAGE-MANAGER purl pkg:cargo/lazy_static@1.4.0
+
+Relationship: SPDXRef-DOCUMENT DESCRIBES SPDXRef-Package-lazy-static-1.4.0
+",
+        version = env!("CARGO_PKG_VERSION"),
+    );
+    assert_eq!(document.to_tag_value(), expected);
+}
+
+#[test]
+fn spdx_build_dependencies() {
+    let graph = JsonFixture::metadata_builddep().graph();
+    let cargo_set = graph
+        .resolve_workspace_names(["main"])
+        .expect("main is in the workspace")
+        .to_feature_set(StandardFeatures::Default)
+        .into_cargo_set(&CargoOptions::new())
+        .expect("cargo set resolved");
+    let mut options = SbomOptions::new();
+    options.set_timestamp("2024-07-01T12:00:00Z");
+
+    let document = SpdxDocument::from_cargo_set(&cargo_set, &options);
+    let tag_value = document.to_tag_value();
+    assert!(
+        tag_value.contains("DocumentName: main\n"),
+        "document is named after the initials: {tag_value}"
+    );
+    assert!(tag_value.ends_with(
+        "\nRelationship: SPDXRef-DOCUMENT DESCRIBES SPDXRef-Package-main-0.1.0
+Relationship: SPDXRef-Package-builddep-0.1.0 BUILD_DEPENDENCY_OF SPDXRef-Package-main-0.1.0
+"
+    ));
+}
+
+#[test]
+fn github_snapshot() {
+    let graph = JsonFixture::metadata_dups().graph();
+    let cargo_set = graph
+        .resolve_workspace()
+        .to_feature_set(StandardFeatures::Default)
+        .into_cargo_set(CargoOptions::new().set_include_dev(true))
+        .expect("cargo set resolved");
+    let mut options = GitHubSnapshotOptions::new(
+        "ce34a1fc4e2a4f3e9e7fe1ba51a1a7e8bbbbbb2f",
+        "refs/heads/main",
+        "ci dependencies",
+        "1234",
+    );
+    options
+        .set_job_html_url("https://github.com/example/testcrate-dups/actions/runs/1234")
+        .set_scanned("2024-07-01T12:00:00Z");
+
+    let snapshot = GitHubSnapshot::from_cargo_set(&cargo_set, &options);
+    assert_eq!(snapshot.manifest_count(), 1);
+    let value: Value =
+        serde_json::from_str(&snapshot.to_json_string().expect("snapshot serializes"))
+            .expect("valid JSON");
+
+    let resolved = |purl: &str, relationship: &str, scope: &str| {
+        json!({
+            "package_url": purl,
+            "relationship": relationship,
+            "scope": scope,
+            "dependencies": [],
+        })
+    };
+    assert_eq!(
+        value,
+        json!({
+            "version": 0,
+            "sha": "ce34a1fc4e2a4f3e9e7fe1ba51a1a7e8bbbbbb2f",
+            "ref": "refs/heads/main",
+            "job": {
+                "correlator": "ci dependencies",
+                "id": "1234",
+                "html_url": "https://github.com/example/testcrate-dups/actions/runs/1234",
+            },
+            "detector": {
+                "name": "guppy",
+                "version": env!("CARGO_PKG_VERSION"),
+                "url": "https://github.com/guppy-rs/guppy",
+            },
+            "scanned": "2024-07-01T12:00:00Z",
+            "manifests": {
+                "Cargo.toml": {
+                    "name": "testcrate-dups",
+                    "file": { "source_location": "Cargo.toml" },
+                    "resolved": {
+                        "pkg:cargo/bytes@0.3.0":
+                            resolved("pkg:cargo/bytes@0.3.0", "direct", "development"),
+                        "pkg:cargo/bytes@0.5.4":
+                            resolved("pkg:cargo/bytes@0.5.4", "direct", "runtime"),
+                        "pkg:cargo/lazy_static@0.2.11":
+                            resolved("pkg:cargo/lazy_static@0.2.11", "direct", "development"),
+                        "pkg:cargo/lazy_static@1.4.0":
+                            resolved("pkg:cargo/lazy_static@1.4.0", "direct", "runtime"),
+                    },
+                },
+            },
+        }),
+    );
+}
+
+#[test]
+fn github_snapshot_workspace() {
+    let graph = JsonFixture::metadata2().graph();
+    let cargo_set = graph
+        .resolve_workspace()
+        .to_feature_set(StandardFeatures::Default)
+        .into_cargo_set(&CargoOptions::new())
+        .expect("cargo set resolved");
+    let options = GitHubSnapshotOptions::new(
+        "ce34a1fc4e2a4f3e9e7fe1ba51a1a7e8bbbbbb2f",
+        "refs/heads/main",
+        "ci dependencies",
+        "1234",
+    );
+
+    let snapshot = GitHubSnapshot::from_cargo_set(&cargo_set, &options);
+    let value: Value =
+        serde_json::from_str(&snapshot.to_json_string().expect("snapshot serializes"))
+            .expect("valid JSON");
+    assert!(value["job"].get("html_url").is_none());
+    assert!(value["scanned"].is_string(), "the current time is used");
+
+    let manifests = value["manifests"]
+        .as_object()
+        .expect("manifests is an object");
+    let locations: Vec<_> = manifests.keys().collect();
+    assert_eq!(locations, ["testcrate/Cargo.toml", "walkdir/Cargo.toml"]);
+    assert_eq!(
+        manifests["walkdir/Cargo.toml"]["resolved"],
+        json!({}),
+        "the walkdir workspace package has no dependencies"
+    );
+
+    let resolved = &manifests["testcrate/Cargo.toml"]["resolved"];
+    assert_eq!(
+        resolved["pkg:cargo/datatest@0.4.2"]["relationship"],
+        "direct"
+    );
+    // Path dependencies outside the workspace are listed, without a qualifier.
+    assert_eq!(
+        resolved["pkg:cargo/walkdir@0.1.0"]["relationship"],
+        "direct"
+    );
+    assert_eq!(
+        resolved["pkg:cargo/regex@1.3.1"],
+        json!({
+            "package_url": "pkg:cargo/regex@1.3.1",
+            "relationship": "indirect",
+            "scope": "runtime",
+            "dependencies": [
+                "pkg:cargo/aho-corasick@0.7.6",
+                "pkg:cargo/memchr@2.2.1",
+                "pkg:cargo/regex-syntax@0.6.12",
+                "pkg:cargo/thread_local@0.3.6",
+            ],
+        }),
+    );
+    assert!(
+        resolved.get("pkg:cargo/doc-comment@0.3.1").is_none(),
+        "dev-dependencies of third-party packages aren't built"
+    );
+}
+
+#[test]
+fn github_snapshot_alternate_registries() {
+    let graph = JsonFixture::metadata_alternate_registries().graph();
+    let cargo_set = graph
+        .resolve_workspace()
+        .to_feature_set(StandardFeatures::All)
+        .into_cargo_set(CargoOptions::new().set_include_dev(true))
+        .expect("cargo set resolved");
+    let mut options = GitHubSnapshotOptions::new(
+        "ce34a1fc4e2a4f3e9e7fe1ba51a1a7e8bbbbbb2f",
+        "refs/heads/main",
+        "ci dependencies",
+        "1234",
+    );
+    options.set_scanned("2024-07-01T12:00:00Z");
+
+    let snapshot = GitHubSnapshot::from_cargo_set(&cargo_set, &options);
+    let value: Value =
+        serde_json::from_str(&snapshot.to_json_string().expect("snapshot serializes"))
+            .expect("valid JSON");
+    let resolved = &value["manifests"]["Cargo.toml"]["resolved"];
+
+    let alternate_serde = format!(
+        "pkg:cargo/serde@1.0.130?repository_url={}",
+        json::METADATA_ALTERNATE_REGISTRY_URL
+    );
+    assert_eq!(resolved[&alternate_serde]["relationship"], "direct");
+    assert_eq!(resolved[&alternate_serde]["scope"], "runtime");
+
+    // serde from crates.io is only pulled in through serde_json, a dev-dependency.
+    assert_eq!(
+        resolved["pkg:cargo/serde_json@1.0.68"]["relationship"],
+        "direct"
+    );
+    assert_eq!(
+        resolved["pkg:cargo/serde_json@1.0.68"]["scope"],
+        "development"
+    );
+    assert_eq!(
+        resolved["pkg:cargo/serde@1.0.130"]["relationship"],
+        "indirect"
+    );
+    assert_eq!(resolved["pkg:cargo/serde@1.0.130"]["scope"], "development");
+    assert_eq!(resolved["pkg:cargo/unicode-xid@0.2.2"]["scope"], "runtime");
+}
@@ -7,8 +7,12 @@ use fixtures::{
     package_id,
 };
 use guppy::graph::{
-    cargo::{CargoOptions, CargoResolverVersion, CargoSet},
-    feature::{named_feature_filter, FeatureLabel, FeatureSet, StandardFeatures},
+    cargo::{BuildPlatform, CargoOptions, CargoResolverVersion, CargoSet, DormantDependencies},
+    feature::{
+        named_feature_filter, FeatureCombination, FeatureCombinationKind, FeatureLabel, FeatureSet,
+        StandardFeatures,
+    },
+    DependencyDirection,
 };
 use target_spec::Platform;
 
@@ -410,6 +414,359 @@ fn test_edge_upgrades() {
     }
 }
 
+#[test]
+fn feature_combinations() {
+    let graph = JsonFixture::metadata_weak_namespaced_features().graph();
+    let feature_graph = graph.feature_graph();
+    let package_id = package_id(json::METADATA_WEAK_NAMESPACED_ID);
+
+    let each_feature: Vec<_> = feature_graph
+        .feature_combinations(&package_id, FeatureCombinationKind::EachFeature)
+        .expect("valid package ID")
+        .collect();
+    assert_eq!(each_feature.len(), 21, "no features + 20 named features");
+    assert!(each_feature[0].features().is_empty(), "no features first");
+
+    let depth_2 = FeatureCombinationKind::Powerset { max_depth: Some(2) };
+    let mut unpruned = feature_graph
+        .feature_combinations(&package_id, depth_2)
+        .expect("valid package ID");
+    unpruned.set_prune(false);
+    let unpruned: Vec<_> = unpruned.collect();
+    assert_eq!(
+        unpruned.len(),
+        1 + 20 + 190,
+        "all combinations up to depth 2"
+    );
+
+    let pruned: Vec<_> = feature_graph
+        .feature_combinations(&package_id, depth_2)
+        .expect("valid package ID")
+        .collect();
+    assert!(pruned.len() < unpruned.len(), "some combinations pruned");
+    // The smallvec feature enables foo, so [foo, smallvec] is the same as [smallvec].
+    let has_combination = |combinations: &[FeatureCombination<'_>], features: &[&str]| {
+        combinations
+            .iter()
+            .any(|combination| combination.features() == features)
+    };
+    assert!(has_combination(&unpruned, &["foo", "smallvec"]));
+    assert!(!has_combination(&pruned, &["foo", "smallvec"]));
+    assert!(has_combination(&pruned, &["smallvec"]));
+}
+
+#[test]
+fn feature_impact() {
+    let graph = JsonFixture::metadata_weak_namespaced_features().graph();
+    let impact = graph
+        .feature_graph()
+        .feature_impact(&package_id(json::METADATA_WEAK_NAMESPACED_ID))
+        .expect("valid package ID");
+
+    let names = |feature_name: &str| -> Vec<&str> {
+        impact
+            .additional_packages(feature_name)
+            .expect("known feature")
+            .packages(DependencyDirection::Forward)
+            .map(|package| package.name())
+            .collect()
+    };
+
+    assert_eq!(impact.baseline().len(), 1, "baseline is just the package");
+    assert_eq!(names("foo"), ["arrayvec"], "foo pulls in arrayvec");
+    assert_eq!(names("bar"), ["arrayvec"], "bar pulls in arrayvec");
+    let mut smallvec_names = names("smallvec");
+    smallvec_names.sort_unstable();
+    assert_eq!(
+        smallvec_names,
+        ["arrayvec", "smallvec"],
+        "smallvec pulls in arrayvec through foo"
+    );
+    assert_eq!(
+        names("windows-dep"),
+        ["tinyvec"],
+        "platform-specific deps count"
+    );
+    assert!(
+        names("smallvec-union").is_empty(),
+        "weak features don't pull in packages"
+    );
+    assert!(
+        names("windows-weak").is_empty(),
+        "weak features don't pull in packages"
+    );
+    assert!(
+        impact.additional_packages("nonexistent").is_none(),
+        "unknown feature"
+    );
+    assert_eq!(impact.iter().len(), 20, "all named features analyzed");
+}
+
+#[test]
+fn cargo_set_matrix() {
+    let platforms = [LINUX_TRIPLE, WINDOWS_TRIPLE].map(|triple| {
+        Platform::new(triple, target_spec::TargetFeatures::Unknown).expect("known triple")
+    });
+
+    for resolver in [CargoResolverVersion::V1, CargoResolverVersion::V2] {
+        let feature_set = feature_set_fn(&["windows-named"]);
+        let mut cargo_options = CargoOptions::new();
+        cargo_options.set_resolver(resolver);
+        let matrix = CargoSet::compute_matrix(
+            feature_set.clone(),
+            feature_set.graph().resolve_none(),
+            &cargo_options,
+            platforms.clone(),
+        )
+        .expect("computing matrix should work");
+        assert_eq!(matrix.len(), 2, "two platforms in matrix");
+
+        // Every entry in the matrix should match a standalone simulation.
+        for (platform, cargo_set) in matrix.iter() {
+            let mut cargo_options = cargo_options.clone();
+            cargo_options.set_target_platform(platform.clone());
+            let expected = feature_set
+                .clone()
+                .into_cargo_set(&cargo_options)
+                .expect("resolving cargo should work");
+            assert_eq!(
+                cargo_set.target_features(),
+                expected.target_features(),
+                "for resolver {:?}, target features match",
+                resolver
+            );
+            assert_eq!(
+                cargo_set.host_features(),
+                expected.host_features(),
+                "for resolver {:?}, host features match",
+                resolver
+            );
+        }
+
+        // tinyvec is only built on Windows.
+        let tinyvec_id = package_id(json::METADATA_WEAK_NAMESPACED_TINYVEC);
+        assert!(
+            !matrix
+                .common_features(BuildPlatform::Target)
+                .contains_package(&tinyvec_id)
+                .expect("valid package ID"),
+            "for resolver {:?}, tinyvec is not common to all platforms",
+            resolver
+        );
+        let deltas: Vec<_> = matrix.deltas().collect();
+        assert!(deltas[0].is_empty(), "linux has no extra features");
+        assert!(
+            deltas[1]
+                .added_features(BuildPlatform::Target)
+                .contains_package(&tinyvec_id)
+                .expect("valid package ID"),
+            "for resolver {:?}, tinyvec is in the windows delta",
+            resolver
+        );
+    }
+}
+
+#[cfg(feature = "summaries")]
+#[test]
+fn cargo_set_matrix_summary_v2() {
+    use guppy::graph::summaries::{BuildKey, DepKind, PlatformKind, SummaryV2};
+
+    let platforms = [LINUX_TRIPLE, WINDOWS_TRIPLE].map(|triple| {
+        Platform::new(triple, target_spec::TargetFeatures::Unknown).expect("known triple")
+    });
+    let feature_set = feature_set_fn(&["windows-named"]);
+    let mut cargo_options = CargoOptions::new();
+    cargo_options.set_resolver(CargoResolverVersion::V2);
+    let matrix = CargoSet::compute_matrix(
+        feature_set.clone(),
+        feature_set.graph().resolve_none(),
+        &cargo_options,
+        platforms.clone(),
+    )
+    .expect("computing matrix should work");
+
+    let summary = matrix
+        .to_summary_v2(&cargo_options)
+        .expect("creating summary should work");
+    assert_eq!(
+        summary.platforms().into_iter().collect::<Vec<_>>(),
+        [WINDOWS_TRIPLE, LINUX_TRIPLE],
+    );
+
+    let graph = feature_set.graph().package_graph();
+    let summary_id = |id: &str| {
+        graph
+            .metadata(&package_id(id))
+            .expect("valid package ID")
+            .to_summary_id()
+    };
+
+    // The initial package isn't depended on by anything.
+    let initial_builds = &summary.packages[&summary_id(json::METADATA_WEAK_NAMESPACED_ID)];
+    let initial_linux = &initial_builds[&BuildKey::new(LINUX_TRIPLE, PlatformKind::Target)];
+    assert!(
+        initial_linux.dep_kinds.is_empty(),
+        "initial has no dep kinds"
+    );
+
+    // tinyvec is a normal dependency that is only built on Windows.
+    let tinyvec_builds = &summary.packages[&summary_id(json::METADATA_WEAK_NAMESPACED_TINYVEC)];
+    assert_eq!(
+        tinyvec_builds.keys().collect::<Vec<_>>(),
+        [&BuildKey::new(WINDOWS_TRIPLE, PlatformKind::Target)],
+        "tinyvec is only built on Windows"
+    );
+    let tinyvec_windows = tinyvec_builds.values().next().expect("one build");
+    assert_eq!(
+        tinyvec_windows.dep_kinds.iter().collect::<Vec<_>>(),
+        [&DepKind::Normal],
+    );
+
+    // Each platform matches the v1 summary for a standalone simulation.
+    for platform in &platforms {
+        let mut cargo_options = cargo_options.clone();
+        cargo_options.set_target_platform(platform.clone());
+        let expected = feature_set
+            .clone()
+            .into_cargo_set(&cargo_options)
+            .expect("resolving cargo should work")
+            .to_summary(&cargo_options)
+            .expect("creating summary should work");
+        let actual = summary.to_v1_for_platform(platform.triple_str());
+        assert_eq!(actual.target_packages, expected.target_packages);
+        assert_eq!(actual.host_packages, expected.host_packages);
+    }
+
+    let serialized = summary.to_string().expect("serialization should work");
+    assert_eq!(
+        SummaryV2::parse(&serialized).expect("deserialization should work"),
+        summary,
+    );
+}
+
+#[cfg(feature = "summaries")]
+#[test]
+fn set_build_summary() {
+    use guppy::graph::summaries::{diff::SummaryDiffStatus, PackageStatus, SetSummaryMetadata};
+
+    let graph = JsonFixture::metadata_weak_namespaced_features().graph();
+    let root_id = package_id(json::METADATA_WEAK_NAMESPACED_ID);
+    let smallvec_id = package_id(json::METADATA_WEAK_NAMESPACED_SMALLVEC);
+    let summary_id = |id| {
+        graph
+            .metadata(id)
+            .expect("valid package ID")
+            .to_summary_id()
+    };
+
+    let package_set = graph
+        .resolve_ids([&root_id, &smallvec_id])
+        .expect("valid package IDs");
+    let summary = package_set
+        .to_build_summary("root-and-smallvec")
+        .expect("creating summary should work");
+    let metadata: SetSummaryMetadata = toml::Value::Table(summary.metadata.clone())
+        .try_into()
+        .expect("metadata is valid");
+    assert_eq!(metadata.label, "root-and-smallvec");
+    assert!(summary.host_packages.is_empty(), "no host packages");
+
+    let root_info = &summary.target_packages[&summary_id(&root_id)];
+    assert_eq!(root_info.status, PackageStatus::Initial);
+    assert!(
+        root_info.features.is_empty(),
+        "package sets have no features"
+    );
+    assert_eq!(
+        summary.target_packages[&summary_id(&smallvec_id)].status,
+        PackageStatus::Direct,
+        "smallvec is depended on by the workspace package"
+    );
+
+    // A third-party package on its own is the root of the set.
+    let smallvec_summary = graph
+        .resolve_ids([&smallvec_id])
+        .expect("valid package ID")
+        .to_build_summary("smallvec")
+        .expect("creating summary should work");
+    assert_eq!(
+        smallvec_summary.target_packages[&summary_id(&smallvec_id)].status,
+        PackageStatus::Initial,
+    );
+
+    // Summaries of feature sets record features, and can be diffed against each other.
+    let feature_set =
+        package_set.to_feature_set(named_feature_filter(StandardFeatures::None, ["smallvec"]));
+    let feature_summary = feature_set
+        .to_build_summary("root-and-smallvec")
+        .expect("creating summary should work");
+    assert!(feature_summary.target_packages[&summary_id(&root_id)]
+        .features
+        .contains("smallvec"));
+
+    let diff = summary.diff(&feature_summary);
+    match &diff.target_packages.changed[&summary_id(&root_id)] {
+        SummaryDiffStatus::Modified { added_features, .. } => {
+            assert!(
+                added_features.contains("smallvec"),
+                "smallvec feature added"
+            );
+        }
+        other => panic!("expected root to be modified, found {other:?}"),
+    }
+}
+
+#[test]
+fn dormant_deps() {
+    let dormant_names = |dormant: &DormantDependencies<'static>| -> Vec<&'static str> {
+        let mut names: Vec<_> = dormant.links().map(|link| link.dep_name()).collect();
+        names.sort_unstable();
+        names
+    };
+    let prunable_names = |dormant: &DormantDependencies<'static>| -> Vec<&'static str> {
+        let mut names: Vec<_> = dormant
+            .prunable_packages()
+            .packages(DependencyDirection::Forward)
+            .map(|package| package.name())
+            .collect();
+        names.sort_unstable();
+        names
+    };
+
+    let linux_default = make_linux_cargo_set(feature_set_fn(&[]));
+    let dormant = linux_default.dormant_deps();
+    assert_eq!(
+        dormant_names(&dormant),
+        ["arrayvec", "pathdiff", "smallvec", "tinyvec"],
+        "no optional deps activated by default"
+    );
+    assert_eq!(
+        prunable_names(&dormant),
+        ["arrayvec", "pathdiff", "smallvec", "tinyvec"],
+        "optional deps aren't built through any other path"
+    );
+
+    let linux_smallvec = make_linux_cargo_set(feature_set_fn(&["smallvec"]));
+    let windows_default = make_windows_cargo_set(feature_set_fn(&["windows-dep"]));
+    let dormant = DormantDependencies::new(
+        linux_default.package_graph(),
+        [&linux_default, &linux_smallvec, &windows_default],
+    );
+    assert_eq!(
+        dormant_names(&dormant),
+        ["pathdiff"],
+        "only pathdiff is dormant across all simulations"
+    );
+    assert_eq!(prunable_names(&dormant), ["pathdiff"]);
+    assert_eq!(
+        dormant
+            .links_from(&package_id(json::METADATA_WEAK_NAMESPACED_ID))
+            .count(),
+        1,
+        "dormant dep is declared by the main package"
+    );
+}
+
 fn feature_set_fn(named_features: &[&str]) -> FeatureSet<'static> {
     JsonFixture::metadata_weak_namespaced_features()
         .graph()
@@ -421,12 +778,15 @@ fn feature_set_fn(named_features: &[&str]) -> FeatureSet<'static> {
         ))
 }
 
+const LINUX_TRIPLE: &str = "x86_64-unknown-linux-gnu";
+const WINDOWS_TRIPLE: &str = "x86_64-pc-windows-msvc";
+
 fn make_linux_cargo_set(feature_set: FeatureSet<'static>) -> CargoSet<'static> {
-    make_cargo_set(feature_set, "x86_64-unknown-linux-gnu")
+    make_cargo_set(feature_set, LINUX_TRIPLE)
 }
 
 fn make_windows_cargo_set(feature_set: FeatureSet<'static>) -> CargoSet<'static> {
-    make_cargo_set(feature_set, "x86_64-pc-windows-msvc")
+    make_cargo_set(feature_set, WINDOWS_TRIPLE)
 }
 
 fn make_cargo_set(feature_set: FeatureSet<'static>, triple: &'static str) -> CargoSet<'static> {
@@ -0,0 +1,64 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use determinator::Determinator;
+use guppy::graph::PackageGraph;
+
+/// The number of changed paths, similar to a mass reformatting of a large workspace.
+const CHANGED_PATH_COUNT: usize = 10_000;
+
+pub fn compute_benchmarks(c: &mut Criterion) {
+    let old = make_package_graph(include_str!(
+        "../../../fixtures/large/metadata_libra_f0091a4.json"
+    ));
+    let new = make_package_graph(include_str!(
+        "../../../fixtures/large/metadata_libra_9ffd93b.json"
+    ));
+    let changed_paths = make_changed_paths(&new);
+
+    let mut determinator = Determinator::new(&old, &new);
+    determinator.add_changed_paths(&changed_paths);
+
+    let mut group = c.benchmark_group("determinator");
+    // Each iteration simulates Cargo builds for every workspace package, so keep the sample size
+    // low.
+    group.sample_size(10);
+
+    group.bench_function("compute_10k_paths", |b| {
+        b.iter(|| determinator.compute());
+    });
+
+    // Compare against a single thread to show the speedup from parallel evaluation.
+    let single_thread = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    group.bench_function("compute_10k_paths_single_thread", |b| {
+        b.iter(|| single_thread.install(|| determinator.compute()));
+    });
+
+    group.finish();
+}
+
+fn make_package_graph(json: &str) -> PackageGraph {
+    PackageGraph::from_json(json).unwrap()
+}
+
+/// Spreads changed paths evenly across the packages in the workspace.
+fn make_changed_paths(graph: &PackageGraph) -> Vec<String> {
+    let workspace_paths: Vec<_> = graph
+        .workspace()
+        .iter_by_path()
+        .map(|(path, _)| path)
+        .collect();
+    (0..CHANGED_PATH_COUNT)
+        .map(|i| {
+            let package_path = workspace_paths[i % workspace_paths.len()];
+            format!("{}/src/file_{}.rs", package_path, i)
+        })
+        .collect()
+}
+
+criterion_group!(benches, compute_benchmarks);
+criterion_main!(benches);
@@ -195,7 +195,10 @@ impl GuppyCargoCommon {
             .prop_map(move |(pf, include_dev, target_platform)| Self {
                 pf,
                 include_dev,
-                v2: resolver == CargoResolverVersion::V2,
+                v2: matches!(
+                    resolver,
+                    CargoResolverVersion::V2 | CargoResolverVersion::V3
+                ),
                 target_platform,
                 metadata_opts: metadata_opts.clone(),
             })
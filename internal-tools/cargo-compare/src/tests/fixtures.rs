@@ -90,6 +90,7 @@ impl Fixture {
             let resolver_version = match resolver {
                 CargoResolverVersion::V1 | CargoResolverVersion::V1Install => "1",
                 CargoResolverVersion::V2 => "2",
+                CargoResolverVersion::V3 => "3",
                 _ => panic!("unknown resolver {:?}", resolver),
             };
             writeln!(f, "resolver = \"{}\"", resolver_version).expect("file written successfully");
@@ -6,6 +6,11 @@
 //! This crate has implementations of `Diagnostic` for the various kinds of errors that target-spec
 //! produces. This can be used to pretty-print errors returned by target-spec.
 //!
+//! Where possible, diagnostics include a "did you mean" suggestion for likely typos, such as a
+//! misspelled triple (`x86_64-unknown-linux-gnuu`), `cfg()` key (`target_oss`) or operator
+//! (`nott(...)`). Suggestions are also available programmatically through each diagnostic's
+//! `suggestion` method.
+//!
 //! ## Minimum supported Rust version
 //!
 //! The minimum supported Rust version (MSRV) is **Rust 1.75**. While this crate is in pre-release
@@ -16,9 +21,13 @@
 #![cfg_attr(doc_cfg, feature(doc_cfg, doc_auto_cfg))]
 
 use miette::{Diagnostic, LabeledSpan, SourceCode};
-use std::{error::Error as StdError, fmt};
-use target_spec::errors::{
-    Error as TargetSpecError, ExpressionParseError, PlainStringParseError, TripleParseError,
+use std::{error::Error as StdError, fmt, ops::Range};
+use target_spec::{
+    errors::{
+        Error as TargetSpecError, ExpressionParseError, ExpressionParseErrorKind,
+        PlainStringParseError, TripleParseError,
+    },
+    Triple,
 };
 
 /// Extension trait that converts errors into a [`miette::Diagnostic`].
@@ -47,41 +56,82 @@ impl IntoMietteDiagnostic for TargetSpecError {
 
 /// A wrapper around [`ExpressionParseError`] that implements [`Diagnostic`].
 #[derive(Clone, PartialEq, Eq)]
-pub struct ExpressionParseDiagnostic(ExpressionParseError);
+pub struct ExpressionParseDiagnostic {
+    error: ExpressionParseError,
+    suggestion: Option<Suggestion>,
+}
 
 impl ExpressionParseDiagnostic {
     /// Creates a new `ExpressionParseDiagnostic`.
     pub fn new(error: ExpressionParseError) -> Self {
-        Self(error)
+        let suggestion = Suggestion::for_expression(&error);
+        Self { error, suggestion }
+    }
+
+    /// Returns a suggested replacement for a likely typo in the expression, if any.
+    ///
+    /// For example, for `cfg(target_oss = "linux")` this returns `target_os`.
+    pub fn suggestion(&self) -> Option<&'static str> {
+        self.suggestion
+            .as_ref()
+            .map(|suggestion| suggestion.replacement)
     }
 }
 
 impl fmt::Debug for ExpressionParseDiagnostic {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt::Debug::fmt(&self.0, f)
+        fmt::Debug::fmt(&self.error, f)
     }
 }
 
 impl fmt::Display for ExpressionParseDiagnostic {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt::Display::fmt(&self.0, f)
+        fmt::Display::fmt(&self.error, f)
     }
 }
 
 impl StdError for ExpressionParseDiagnostic {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
-        self.0.source()
+        self.error.source()
     }
 }
 
 impl Diagnostic for ExpressionParseDiagnostic {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new("target_spec::invalid_expression"))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let suggestion = self.suggestion.as_ref()?;
+        Some(Box::new(format!(
+            "did you mean `{}`?",
+            suggestion.replacement
+        )))
+    }
+
     fn source_code(&self) -> Option<&dyn SourceCode> {
-        Some(&self.0.input)
+        Some(&self.error.input)
     }
 
     fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
-        let label = LabeledSpan::new_with_span(Some(self.0.kind.to_string()), self.0.span.clone());
-        Some(Box::new(std::iter::once(label)))
+        let label =
+            LabeledSpan::new_with_span(Some(self.error.kind.to_string()), self.error.span.clone());
+        // If the typo is somewhere other than where the error was detected (e.g. `nott` in
+        // `nott(unix)`, where the error is at the opening paren), point at it as well.
+        let suggestion_label = self
+            .suggestion
+            .as_ref()
+            .filter(|suggestion| suggestion.span != self.error.span)
+            .map(|suggestion| {
+                LabeledSpan::new_with_span(
+                    Some(format!(
+                        "unknown operator, did you mean `{}`?",
+                        suggestion.replacement
+                    )),
+                    suggestion.span.clone(),
+                )
+            });
+        Some(Box::new(std::iter::once(label).chain(suggestion_label)))
     }
 }
 
@@ -99,13 +149,24 @@ pub struct TripleParseDiagnostic {
     error: TripleParseError,
     // Need to store this separately because &str can't be cast to &dyn SourceCode.
     triple_str: String,
+    suggestion: Option<&'static str>,
 }
 
 impl TripleParseDiagnostic {
     /// Creates a new `ExpressionParseDiagnostic`.
     pub fn new(error: TripleParseError) -> Self {
         let triple_str = error.triple_str().to_owned();
-        Self { error, triple_str }
+        let suggestion = closest_match(&triple_str, Triple::builtin_strs());
+        Self {
+            error,
+            triple_str,
+            suggestion,
+        }
+    }
+
+    /// Returns the builtin triple closest to the one that failed to parse, if any is close enough.
+    pub fn suggestion(&self) -> Option<&'static str> {
+        self.suggestion
     }
 }
 
@@ -128,6 +189,15 @@ impl StdError for TripleParseDiagnostic {
 }
 
 impl Diagnostic for TripleParseDiagnostic {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new("target_spec::unknown_triple"))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let suggestion = self.suggestion?;
+        Some(Box::new(format!("did you mean `{suggestion}`?")))
+    }
+
     fn source_code(&self) -> Option<&dyn SourceCode> {
         Some(&self.triple_str as &dyn SourceCode)
     }
@@ -191,6 +261,10 @@ impl StdError for PlainStringParseDiagnostic {
 }
 
 impl Diagnostic for PlainStringParseDiagnostic {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new("target_spec::invalid_triple_identifier"))
+    }
+
     fn source_code(&self) -> Option<&dyn SourceCode> {
         Some(&self.input as &dyn SourceCode)
     }
@@ -211,3 +285,156 @@ impl IntoMietteDiagnostic for PlainStringParseError {
         PlainStringParseDiagnostic::new(self)
     }
 }
+
+/// A suggested replacement for part of an expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Suggestion {
+    span: Range<usize>,
+    replacement: &'static str,
+}
+
+impl Suggestion {
+    fn for_expression(error: &ExpressionParseError) -> Option<Self> {
+        let ExpressionParseErrorKind::Unexpected { expected } = &error.kind else {
+            return None;
+        };
+        let input = error.input.as_str();
+        let found = input.get(error.span.clone())?;
+
+        // A misspelled key, e.g. `target_oss`: the expected list contains the valid keys.
+        let keys = expected
+            .iter()
+            .copied()
+            .filter(|term| !term.is_empty() && term.chars().all(is_identifier_char));
+        if let Some(replacement) = closest_match(found, keys) {
+            return Some(Self {
+                span: error.span.clone(),
+                replacement,
+            });
+        }
+
+        // A misspelled operator, e.g. `nott(unix)`: the error is reported at the opening paren,
+        // and the operator is the identifier just before it.
+        if found == "(" {
+            let before = input[..error.span.start].trim_end();
+            let start = before
+                .char_indices()
+                .rev()
+                .find(|(_, c)| !is_identifier_char(*c))
+                .map_or(0, |(idx, c)| idx + c.len_utf8());
+            let operator = &before[start..];
+            if let Some(replacement) = closest_match(operator, ["all", "any", "not"]) {
+                return Some(Self {
+                    span: start..before.len(),
+                    replacement,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns the candidate closest to `input`, if any is close enough to plausibly be a typo.
+fn closest_match(
+    input: &str,
+    candidates: impl IntoIterator<Item = &'static str>,
+) -> Option<&'static str> {
+    if input.is_empty() {
+        return None;
+    }
+    // This threshold is similar to the one used by rustc.
+    let max_distance = (input.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != input)
+        .map(|candidate| (edit_distance(input, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Returns the Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut current);
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use target_spec::TargetSpec;
+
+    fn expression_diagnostic(input: &str) -> ExpressionParseDiagnostic {
+        match TargetSpec::new(input.to_owned()) {
+            Err(TargetSpecError::InvalidExpression(error)) => error.into_diagnostic(),
+            other => panic!("expected invalid expression for {input}, found {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("target_oss", "target_os"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_expression_suggestions() {
+        let diagnostic = expression_diagnostic("cfg(target_oss = \"linux\")");
+        assert_eq!(diagnostic.suggestion(), Some("target_os"));
+        assert_eq!(
+            diagnostic.help().map(|help| help.to_string()).as_deref(),
+            Some("did you mean `target_os`?")
+        );
+        assert_eq!(diagnostic.labels().unwrap().count(), 1);
+
+        // The misspelled operator is nested, and is labeled separately from the error.
+        let input = "cfg(all(unix, nott(target_arch = \"x86_64\")))";
+        let diagnostic = expression_diagnostic(input);
+        assert_eq!(diagnostic.suggestion(), Some("not"));
+        let labels: Vec<_> = diagnostic.labels().unwrap().collect();
+        assert_eq!(labels.len(), 2);
+        let operator = &labels[1];
+        assert_eq!(
+            &input[operator.offset()..operator.offset() + operator.len()],
+            "nott"
+        );
+
+        // No suggestion for terms unlike anything expected.
+        let diagnostic = expression_diagnostic("cfg(target_something = \"bar\")");
+        assert_eq!(diagnostic.suggestion(), None);
+        assert!(diagnostic.help().is_none());
+    }
+
+    #[test]
+    fn test_triple_suggestions() {
+        let error = Triple::new_strict("x86_64-unknown-linux-gnuu").unwrap_err();
+        let diagnostic = error.into_diagnostic();
+        assert_eq!(diagnostic.suggestion(), Some("x86_64-unknown-linux-gnu"));
+        assert_eq!(
+            diagnostic.help().map(|help| help.to_string()).as_deref(),
+            Some("did you mean `x86_64-unknown-linux-gnu`?")
+        );
+
+        let error = Triple::new_strict("completely-bogus").unwrap_err();
+        assert_eq!(error.into_diagnostic().suggestion(), None);
+    }
+}
@@ -33,9 +33,11 @@ pub(crate) struct TargetDefinition {
     env: Option<String>,
     #[serde(default)]
     vendor: Option<String>,
-    #[serde(default)]
+    // rustc uses target-family and target-endian, but earlier versions of target-spec serialized
+    // these as families and endian.
+    #[serde(default, alias = "target-family")]
     families: Vec<String>,
-    #[serde(default)]
+    #[serde(default, alias = "target-endian")]
     endian: Endian,
     #[serde(default)]
     min_atomic_width: Option<u16>,
@@ -92,11 +94,21 @@ mod target_pointer_width {
     where
         D: Deserializer<'de>,
     {
-        // Pointer width is specified as a string.
-        let string = String::deserialize(deserializer)?;
-        string
-            .parse::<u8>()
-            .map_err(|error| D::Error::custom(format!("error parsing as integer: {error}")))
+        // Pointer width is specified as a string by older versions of rustc, and as an integer by
+        // newer ones.
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum PointerWidth {
+            String(String),
+            Integer(u8),
+        }
+
+        match PointerWidth::deserialize(deserializer)? {
+            PointerWidth::String(string) => string
+                .parse::<u8>()
+                .map_err(|error| D::Error::custom(format!("error parsing as integer: {error}"))),
+            PointerWidth::Integer(width) => Ok(width),
+        }
     }
 
     pub(super) fn serialize<S>(value: &u8, serializer: S) -> Result<S::Ok, S::Error>
@@ -154,6 +166,58 @@ mod tests {
     #[serde(transparent)]
     struct AllTargets(BTreeMap<String, TargetDefinition>);
 
+    #[test]
+    fn test_custom_target_from_path() {
+        // A custom target in the format printed by `rustc --print target-spec-json`.
+        let json = r#"{
+            "arch": "powerpc",
+            "data-layout": "E-m:e-p:32:32-Fn32-i64:64-n32",
+            "env": "gnu",
+            "llvm-target": "powerpc-unknown-none",
+            "max-atomic-width": 32,
+            "panic-strategy": "abort",
+            "target-endian": "big",
+            "target-family": ["unix"],
+            "target-pointer-width": 32
+        }"#;
+        let dir = std::env::temp_dir().join(format!("target-spec-custom-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir created");
+        let path = dir.join("powerpc-custom-none.json");
+        std::fs::write(&path, json).expect("target JSON written");
+
+        let platform = crate::Platform::new_custom_from_path(&path, crate::TargetFeatures::Unknown)
+            .expect("custom platform created");
+        std::fs::remove_dir_all(&dir).expect("temp dir removed");
+        assert_eq!(platform.triple_str(), "powerpc-custom-none");
+        assert!(platform.is_custom(), "platform is custom");
+
+        for (expr, expected) in [
+            (r#"cfg(target_os = "none")"#, true),
+            (r#"cfg(target_arch = "powerpc")"#, true),
+            (r#"cfg(target_env = "gnu")"#, true),
+            (r#"cfg(target_endian = "big")"#, true),
+            (r#"cfg(target_pointer_width = "32")"#, true),
+            (r#"cfg(target_pointer_width = "64")"#, false),
+            (r#"cfg(panic = "abort")"#, true),
+            ("cfg(unix)", true),
+        ] {
+            let spec = crate::TargetSpec::new(expr).expect("expression parsed");
+            assert_eq!(spec.eval(&platform), Some(expected), "evaluating {expr}");
+        }
+
+        let err = crate::Platform::new_custom_from_path(&path, crate::TargetFeatures::Unknown)
+            .expect_err("file was removed");
+        assert!(
+            matches!(
+                err,
+                crate::Error::CustomPlatformCreate(
+                    crate::errors::CustomTripleCreateError::ReadFile { .. }
+                )
+            ),
+            "unexpected error: {err:?}"
+        );
+    }
+
     #[test]
     fn test_all_builtin_specs_recognized() {
         let version = rustc_version::version().expect("rustc_version succeeded");
@@ -20,6 +20,8 @@ pub enum Error {
     CustomTripleCreate(CustomTripleCreateError),
     /// An error occurred while creating a custom platform.
     CustomPlatformCreate(CustomTripleCreateError),
+    /// An error occurred while querying `rustc` for platform information.
+    Rustc(RustcError),
 }
 
 impl fmt::Display for Error {
@@ -36,6 +38,7 @@ impl fmt::Display for Error {
             Error::CustomPlatformCreate(_) => {
                 write!(f, "error creating custom platform")
             }
+            Error::Rustc(_) => write!(f, "error querying rustc for platform information"),
         }
     }
 }
@@ -48,6 +51,7 @@ impl error::Error for Error {
             Error::UnknownPlatformTriple(err) => Some(err),
             Error::CustomTripleCreate(err) => Some(err),
             Error::CustomPlatformCreate(err) => Some(err),
+            Error::Rustc(err) => Some(err),
         }
     }
 }
@@ -323,6 +327,16 @@ pub enum CustomTripleCreateError {
         error: std::sync::Arc<serde_json::Error>,
     },
 
+    #[cfg(feature = "custom")]
+    /// An error occurred while reading a custom target JSON file.
+    ReadFile {
+        /// The path to the file.
+        path: std::path::PathBuf,
+
+        /// The I/O error that occurred.
+        error: std::sync::Arc<std::io::Error>,
+    },
+
     /// A custom platform was asked to be created, but the `custom` feature is currently disabled.
     ///
     /// Currently, this can only happen if a custom platform is deserialized from a
@@ -337,6 +351,14 @@ impl fmt::Display for CustomTripleCreateError {
             Self::Deserialize { triple, .. } => {
                 write!(f, "error deserializing custom target JSON for `{triple}`")
             }
+            #[cfg(feature = "custom")]
+            Self::ReadFile { path, .. } => {
+                write!(
+                    f,
+                    "error reading custom target JSON from `{}`",
+                    path.display()
+                )
+            }
             Self::Unavailable => {
                 write!(f, "custom platform currently unavailable")
             }
@@ -349,11 +371,80 @@ impl error::Error for CustomTripleCreateError {
         match self {
             #[cfg(feature = "custom")]
             Self::Deserialize { error, .. } => Some(error),
+            #[cfg(feature = "custom")]
+            Self::ReadFile { error, .. } => Some(error),
             Self::Unavailable => None,
         }
     }
 }
 
+/// An error returned while querying `rustc` through
+/// [`RustcPlatforms`](crate::RustcPlatforms).
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum RustcError {
+    /// `rustc` couldn't be run.
+    Spawn {
+        /// The command that was run.
+        command: String,
+
+        /// The I/O error that occurred.
+        error: std::sync::Arc<std::io::Error>,
+    },
+
+    /// `rustc` exited with a failure status, for example because it doesn't know about a triple.
+    Failed {
+        /// The command that was run.
+        command: String,
+
+        /// The exit status of the command.
+        status: std::process::ExitStatus,
+
+        /// The standard error output of the command.
+        stderr: String,
+    },
+
+    /// The output of `rustc` couldn't be parsed.
+    InvalidOutput {
+        /// The command that was run.
+        command: String,
+
+        /// A description of what was wrong with the output.
+        message: String,
+    },
+}
+
+impl fmt::Display for RustcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Spawn { command, .. } => write!(f, "error running `{command}`"),
+            Self::Failed {
+                command,
+                status,
+                stderr,
+            } => {
+                write!(f, "`{command}` failed with {status}")?;
+                if !stderr.trim().is_empty() {
+                    write!(f, ":\n{}", stderr.trim_end())?;
+                }
+                Ok(())
+            }
+            Self::InvalidOutput { command, message } => {
+                write!(f, "invalid output from `{command}`: {message}")
+            }
+        }
+    }
+}
+
+impl error::Error for RustcError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Spawn { error, .. } => Some(error),
+            Self::Failed { .. } | Self::InvalidOutput { .. } => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{TargetSpecExpression, TargetSpecPlainString};
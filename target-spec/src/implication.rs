@@ -0,0 +1,280 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Symbolic reasoning over pairs of target specifications.
+//!
+//! Target predicates like `target_os = "linux"` aren't independent of each other: for example,
+//! every platform with `target_os = "linux"` is also `unix`. These relationships are derived from
+//! the builtin platforms, which are grouped into classes by the values of the predicates that
+//! appear in the specifications. All other predicates, such as flags and target features, are
+//! treated as independent variables.
+
+use crate::TargetSpec;
+use cfg_expr::{
+    targets::{TargetInfo, ALL_BUILTINS},
+    Predicate, TargetPredicate,
+};
+use std::collections::BTreeSet;
+
+/// The maximum number of independent predicates, such as flags and target features, that are
+/// enumerated over. Beyond this, the result is unknown.
+const MAX_FREE_ATOMS: usize = 12;
+
+/// Returns whether there's some platform for which `check` returns true, given the results of
+/// evaluating `a` and `b` against it.
+///
+/// Returns `None` if this can't be determined.
+pub(crate) fn exists(
+    a: &TargetSpec,
+    b: &TargetSpec,
+    check: impl Fn(bool, bool) -> bool,
+) -> Option<bool> {
+    let mut atoms = Atoms::default();
+    atoms.add_spec(a);
+    atoms.add_spec(b);
+    if atoms.free.len() > MAX_FREE_ATOMS {
+        return None;
+    }
+
+    for platform_values in &atoms.platform_classes()? {
+        for free_bits in 0..(1_u32 << atoms.free.len()) {
+            let valuation = Valuation {
+                atoms: &atoms,
+                platform_values,
+                free_bits,
+            };
+            if check(valuation.eval(a), valuation.eval(b)) {
+                return Some(true);
+            }
+        }
+    }
+    Some(false)
+}
+
+/// The distinct predicates found in a set of specifications.
+#[derive(Default)]
+struct Atoms<'a> {
+    /// Predicates determined by the platform.
+    platform: Vec<PlatformAtom<'a>>,
+    /// Predicates independent of the platform.
+    free: Vec<Predicate<'a>>,
+}
+
+impl<'a> Atoms<'a> {
+    fn add_spec(&mut self, spec: &'a TargetSpec) {
+        match spec {
+            TargetSpec::PlainString(plain_str) => {
+                self.add_platform(PlatformAtom::Triple(plain_str.as_str()));
+            }
+            TargetSpec::Expression(expr) => {
+                for pred in expr.inner().predicates() {
+                    match pred {
+                        Predicate::Target(target) => {
+                            self.add_platform(PlatformAtom::Target(target));
+                        }
+                        Predicate::TargetFeature(_)
+                        | Predicate::Flag(_)
+                        | Predicate::KeyValue { .. } => {
+                            if !self.free.contains(&pred) {
+                                self.free.push(pred);
+                            }
+                        }
+                        // These always evaluate to false, so there's nothing to enumerate over.
+                        Predicate::Test
+                        | Predicate::DebugAssertions
+                        | Predicate::ProcMacro
+                        | Predicate::Feature(_) => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_platform(&mut self, atom: PlatformAtom<'a>) {
+        if !self.platform.contains(&atom) {
+            self.platform.push(atom);
+        }
+    }
+
+    /// Returns the distinct sets of values the platform predicates take across builtin platforms.
+    ///
+    /// Returns `None` if a predicate doesn't hold for any builtin platform. Such a predicate likely
+    /// refers to a custom platform, which nothing is known about.
+    fn platform_classes(&self) -> Option<BTreeSet<Vec<bool>>> {
+        let mut classes = BTreeSet::new();
+        let mut ever_true = vec![false; self.platform.len()];
+        for target_info in ALL_BUILTINS {
+            let values: Vec<_> = self
+                .platform
+                .iter()
+                .map(|atom| atom.matches(target_info))
+                .collect();
+            for (ever_true, value) in ever_true.iter_mut().zip(&values) {
+                *ever_true |= value;
+            }
+            classes.insert(values);
+        }
+
+        ever_true.into_iter().all(|x| x).then_some(classes)
+    }
+}
+
+#[derive(Eq, PartialEq)]
+enum PlatformAtom<'a> {
+    Target(TargetPredicate),
+    Triple(&'a str),
+}
+
+impl PlatformAtom<'_> {
+    fn matches(&self, target_info: &TargetInfo) -> bool {
+        match self {
+            PlatformAtom::Target(target) => target.matches(target_info),
+            PlatformAtom::Triple(triple_str) => target_info.triple.as_str() == *triple_str,
+        }
+    }
+}
+
+/// An assignment of values to every predicate in a set of `Atoms`.
+struct Valuation<'a, 'b> {
+    atoms: &'b Atoms<'a>,
+    platform_values: &'b [bool],
+    free_bits: u32,
+}
+
+impl Valuation<'_, '_> {
+    fn eval(&self, spec: &TargetSpec) -> bool {
+        match spec {
+            TargetSpec::PlainString(plain_str) => self.platform_value(|atom| {
+                matches!(atom, PlatformAtom::Triple(triple_str) if *triple_str == plain_str.as_str())
+            }),
+            TargetSpec::Expression(expr) => expr.inner().eval(|pred| match pred {
+                Predicate::Target(target) => self.platform_value(
+                    |atom| matches!(atom, PlatformAtom::Target(atom_target) if atom_target == target),
+                ),
+                Predicate::TargetFeature(_) | Predicate::Flag(_) | Predicate::KeyValue { .. } => {
+                    let idx = self
+                        .atoms
+                        .free
+                        .iter()
+                        .position(|free| free == pred)
+                        .expect("all free predicates were collected");
+                    self.free_bits & (1 << idx) != 0
+                }
+                Predicate::Test
+                | Predicate::DebugAssertions
+                | Predicate::ProcMacro
+                | Predicate::Feature(_) => false,
+            }),
+        }
+    }
+
+    fn platform_value(&self, is_atom: impl Fn(&PlatformAtom<'_>) -> bool) -> bool {
+        let idx = self
+            .atoms
+            .platform
+            .iter()
+            .position(is_atom)
+            .expect("all platform predicates were collected");
+        self.platform_values[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn implies(a: &str, b: &str) -> Option<bool> {
+        let a = TargetSpec::new(a.to_owned()).unwrap();
+        let b = TargetSpec::new(b.to_owned()).unwrap();
+        a.implies(&b)
+    }
+
+    fn intersects(a: &str, b: &str) -> Option<bool> {
+        let a = TargetSpec::new(a.to_owned()).unwrap();
+        let b = TargetSpec::new(b.to_owned()).unwrap();
+        let res = a.intersects(&b);
+        assert_eq!(res, b.intersects(&a), "intersects is symmetric");
+        res
+    }
+
+    #[test]
+    fn test_implies() {
+        let cases = [
+            ("cfg(target_os = \"linux\")", "cfg(unix)", Some(true)),
+            ("cfg(unix)", "cfg(target_os = \"linux\")", Some(false)),
+            (
+                "cfg(all(unix, target_arch = \"x86_64\"))",
+                "cfg(unix)",
+                Some(true),
+            ),
+            ("cfg(windows)", "cfg(not(unix))", Some(true)),
+            (
+                "cfg(target_os = \"macos\")",
+                "cfg(any(target_os = \"macos\", target_os = \"ios\"))",
+                Some(true),
+            ),
+            ("x86_64-unknown-linux-gnu", "cfg(unix)", Some(true)),
+            (
+                "x86_64-unknown-linux-gnu",
+                "cfg(target_pointer_width = \"32\")",
+                Some(false),
+            ),
+            (
+                "cfg(target_os = \"linux\")",
+                "x86_64-unknown-linux-gnu",
+                Some(false),
+            ),
+            // Flags and target features are independent of the platform.
+            ("cfg(foo)", "cfg(any(foo, bar))", Some(true)),
+            ("cfg(foo)", "cfg(bar)", Some(false)),
+            ("cfg(all(unix, foo))", "cfg(unix)", Some(true)),
+            ("cfg(unix)", "cfg(all(unix, foo))", Some(false)),
+            (
+                "cfg(target_feature = \"sse2\")",
+                "cfg(target_feature = \"sse2\")",
+                Some(true),
+            ),
+            // A contradiction implies anything.
+            ("cfg(all(foo, not(foo)))", "cfg(windows)", Some(true)),
+            ("cfg(test)", "cfg(windows)", Some(true)),
+            // Nothing is known about custom platforms.
+            ("cfg(target_os = \"my-os\")", "cfg(unix)", None),
+            ("my-custom-platform", "cfg(unix)", None),
+        ];
+
+        for (a, b, expected) in cases {
+            assert_eq!(implies(a, b), expected, "{a} implies {b}");
+        }
+    }
+
+    #[test]
+    fn test_intersects() {
+        let cases = [
+            ("cfg(unix)", "cfg(windows)", Some(false)),
+            ("cfg(unix)", "cfg(target_arch = \"x86_64\")", Some(true)),
+            (
+                "cfg(target_os = \"linux\")",
+                "cfg(target_os = \"macos\")",
+                Some(false),
+            ),
+            (
+                "x86_64-pc-windows-msvc",
+                "cfg(target_env = \"msvc\")",
+                Some(true),
+            ),
+            (
+                "x86_64-pc-windows-msvc",
+                "cfg(target_env = \"gnu\")",
+                Some(false),
+            ),
+            ("cfg(foo)", "cfg(not(bar))", Some(true)),
+            ("cfg(foo)", "cfg(not(foo))", Some(false)),
+            ("cfg(test)", "cfg(unix)", Some(false)),
+            ("cfg(target_os = \"my-os\")", "cfg(unix)", None),
+        ];
+
+        for (a, b, expected) in cases {
+            assert_eq!(intersects(a, b), expected, "{a} intersects {b}");
+        }
+    }
+}
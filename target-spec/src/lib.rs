@@ -36,13 +36,26 @@
 //!
 //! For more advanced usage, see [`Platform`] and [`TargetSpec`].
 //!
+//! Platforms are looked up in a database embedded into `target-spec` at build time. To pick up
+//! platforms added to Rust since then, such as new nightly targets, `rustc` can be queried at
+//! runtime instead: see [`RustcPlatforms`]. To match a specific compiler configuration, including
+//! target features enabled through `RUSTFLAGS`, use captured `rustc --print cfg` output with
+//! [`Platform::from_rustc_cfg`].
+//!
+//! Target specifications can also be compared against each other without reference to a particular
+//! platform: see [`TargetSpec::implies`] and [`TargetSpec::intersects`].
+//! Large expressions, such as those built up by combining many conditions, can be simplified into a
+//! canonical form with [`TargetSpec::normalized`].
+//! To list the builtin platforms a specification matches, optionally filtered by support tier, use
+//! [`TargetSpec::matching_builtins`].
+//!
 //! ## Optional features
 //!
 //! * **`custom`**: Adds support for [custom
 //!   targets](https://docs.rust-embedded.org/embedonomicon/custom-target.html) via
-//!   [`Platform::new_custom`].
+//!   [`Platform::new_custom`] and [`Platform::new_custom_from_path`].
 //! * **`summaries`**: Adds the [`summaries`] module to enable serialization of [`Platform`] and
-//!   [`TargetFeatures`].
+//!   [`TargetFeatures`], and implements serialization for [`TargetSpec`].
 //! * **`proptest1`**: Enables support for property-based testing of [`Platform`] and
 //!   [`TargetFeatures`] using [`proptest`].
 //!
@@ -67,17 +80,23 @@
 #[cfg(feature = "custom")]
 mod custom;
 pub mod errors;
+mod implication;
+mod normalize;
 mod platform;
 #[cfg(feature = "proptest1")]
 mod proptest_helpers;
+mod rustc;
 mod simple_eval;
 mod spec;
 #[cfg(feature = "summaries")]
 pub mod summaries;
+mod tier;
 mod triple;
 
 pub use errors::Error;
 pub use platform::*;
+pub use rustc::*;
 pub use simple_eval::*;
 pub use spec::*;
+pub use tier::*;
 pub use triple::*;
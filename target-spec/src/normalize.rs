@@ -0,0 +1,273 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Simplification of `cfg()` expressions into a canonical form.
+
+use cfg_expr::{expr::Logic, targets::Endian, Expression, Predicate, TargetPredicate};
+use std::{collections::BTreeSet, fmt};
+
+/// Returns the canonical form of `expr`, including the surrounding `cfg()`.
+pub(crate) fn normalize(expr: &Expression) -> String {
+    let node = expr.eval(|pred| Node::Predicate(render_predicate(pred)));
+    format!("cfg({node})")
+}
+
+/// A boolean expression tree, built up by evaluating an `Expression` with `Node` as its logic.
+///
+/// Every constructor simplifies its result, so a tree built this way is always in canonical form:
+///
+/// * nested `all()` and `any()` are flattened, and their operands are deduplicated and sorted;
+/// * `all()` and `any()` with a single operand are replaced with the operand;
+/// * double negations are removed;
+/// * an `all()` containing both `x` and `not(x)` is false, and dually for `any()`;
+/// * absorption is applied: `all(x, any(x, y))` is `x`, and dually for `any()`.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Node {
+    /// `all()`, always true.
+    True,
+    /// `any()`, always false.
+    False,
+    /// A single predicate, rendered as a string.
+    Predicate(String),
+    Not(Box<Node>),
+    /// At least two operands, sorted and deduplicated.
+    All(BTreeSet<Node>),
+    /// At least two operands, sorted and deduplicated.
+    Any(BTreeSet<Node>),
+}
+
+impl Node {
+    /// Combines `self` and `other` with `all()` if `is_all` is true, or `any()` otherwise.
+    fn combine(self, other: Self, is_all: bool) -> Self {
+        let (identity, absorbing) = if is_all {
+            (Node::True, Node::False)
+        } else {
+            (Node::False, Node::True)
+        };
+
+        let mut operands = BTreeSet::new();
+        for node in [self, other] {
+            match node {
+                Node::All(nodes) if is_all => operands.extend(nodes),
+                Node::Any(nodes) if !is_all => operands.extend(nodes),
+                node if node == identity => {}
+                node => {
+                    operands.insert(node);
+                }
+            }
+        }
+
+        if operands.contains(&absorbing) {
+            return absorbing;
+        }
+        if operands
+            .iter()
+            .any(|node| operands.contains(&node.clone().not()))
+        {
+            return absorbing;
+        }
+
+        // Absorption: an operand of the opposite kind is redundant if another operand implies it
+        // (for all()) or is implied by it (for any()).
+        let redundant: Vec<_> = operands
+            .iter()
+            .filter(|node| {
+                let Some(inner) = node.opposite_operands(is_all) else {
+                    return false;
+                };
+                operands.iter().any(|other| {
+                    other != *node
+                        && match other.opposite_operands(is_all) {
+                            Some(other_inner) => other_inner.is_subset(inner),
+                            None => inner.contains(other),
+                        }
+                })
+            })
+            .cloned()
+            .collect();
+        for node in &redundant {
+            operands.remove(node);
+        }
+
+        let mut iter = operands.into_iter();
+        match (iter.next(), iter.next()) {
+            (None, _) => identity,
+            (Some(node), None) => node,
+            (Some(first), Some(second)) => {
+                let mut operands: BTreeSet<_> = iter.collect();
+                operands.insert(first);
+                operands.insert(second);
+                if is_all {
+                    Node::All(operands)
+                } else {
+                    Node::Any(operands)
+                }
+            }
+        }
+    }
+
+    /// Returns the operands of `self` if it is `any()` (for `is_all`) or `all()` (otherwise).
+    fn opposite_operands(&self, is_all: bool) -> Option<&BTreeSet<Node>> {
+        match self {
+            Node::Any(nodes) if is_all => Some(nodes),
+            Node::All(nodes) if !is_all => Some(nodes),
+            _ => None,
+        }
+    }
+}
+
+impl Logic for Node {
+    fn top() -> Self {
+        Node::True
+    }
+
+    fn bottom() -> Self {
+        Node::False
+    }
+
+    fn and(self, other: Self) -> Self {
+        self.combine(other, true)
+    }
+
+    fn or(self, other: Self) -> Self {
+        self.combine(other, false)
+    }
+
+    fn not(self) -> Self {
+        match self {
+            Node::True => Node::False,
+            Node::False => Node::True,
+            Node::Not(node) => *node,
+            node => Node::Not(Box::new(node)),
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, nodes) = match self {
+            Node::True => return f.write_str("all()"),
+            Node::False => return f.write_str("any()"),
+            Node::Predicate(pred) => return f.write_str(pred),
+            Node::Not(node) => return write!(f, "not({node})"),
+            Node::All(nodes) => ("all", nodes),
+            Node::Any(nodes) => ("any", nodes),
+        };
+        write!(f, "{name}(")?;
+        for (idx, node) in nodes.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{node}")?;
+        }
+        f.write_str(")")
+    }
+}
+
+fn render_predicate(pred: &Predicate<'_>) -> String {
+    match pred {
+        Predicate::Target(target) => match target {
+            TargetPredicate::Abi(abi) => format!("target_abi = \"{abi}\""),
+            TargetPredicate::Arch(arch) => format!("target_arch = \"{arch}\""),
+            TargetPredicate::Endian(endian) => {
+                let endian = match endian {
+                    Endian::big => "big",
+                    Endian::little => "little",
+                };
+                format!("target_endian = \"{endian}\"")
+            }
+            TargetPredicate::Env(env) => format!("target_env = \"{env}\""),
+            TargetPredicate::Family(family) => match family.as_str() {
+                // These are always written in their bare form.
+                "unix" | "windows" => family.to_string(),
+                _ => format!("target_family = \"{family}\""),
+            },
+            TargetPredicate::HasAtomic(has_atomic) => {
+                format!("target_has_atomic = \"{has_atomic}\"")
+            }
+            TargetPredicate::Os(os) => format!("target_os = \"{os}\""),
+            TargetPredicate::Panic(panic) => format!("panic = \"{panic}\""),
+            TargetPredicate::PointerWidth(width) => format!("target_pointer_width = \"{width}\""),
+            TargetPredicate::Vendor(vendor) => format!("target_vendor = \"{vendor}\""),
+        },
+        Predicate::Test => "test".to_owned(),
+        Predicate::DebugAssertions => "debug_assertions".to_owned(),
+        Predicate::ProcMacro => "proc_macro".to_owned(),
+        Predicate::Feature(feature) => format!("feature = \"{feature}\""),
+        Predicate::TargetFeature(feature) => format!("target_feature = \"{feature}\""),
+        Predicate::Flag(flag) => (*flag).to_owned(),
+        Predicate::KeyValue { key, val } => format!("{key} = \"{val}\""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let cases = [
+            ("cfg(unix)", "cfg(unix)"),
+            ("cfg(target_family = \"unix\")", "cfg(unix)"),
+            ("cfg(all(unix))", "cfg(unix)"),
+            ("cfg(any())", "cfg(any())"),
+            ("cfg(all())", "cfg(all())"),
+            ("cfg(not(not(windows)))", "cfg(windows)"),
+            // Flattening, deduplication and sorting.
+            (
+                "cfg(all(unix, all(target_arch = \"x86_64\", unix)))",
+                "cfg(all(target_arch = \"x86_64\", unix))",
+            ),
+            (
+                "cfg(any(windows, any(unix, any(windows, foo))))",
+                "cfg(any(foo, unix, windows))",
+            ),
+            (
+                "cfg(any(all(unix, foo), all(foo, unix)))",
+                "cfg(all(foo, unix))",
+            ),
+            // Constants.
+            ("cfg(all(unix, any()))", "cfg(any())"),
+            ("cfg(any(unix, all()))", "cfg(all())"),
+            ("cfg(all(unix, not(any())))", "cfg(unix)"),
+            // Complements.
+            ("cfg(all(unix, not(unix)))", "cfg(any())"),
+            ("cfg(any(foo, unix, not(unix)))", "cfg(all())"),
+            // Absorption.
+            ("cfg(all(unix, any(unix, windows)))", "cfg(unix)"),
+            ("cfg(any(unix, all(unix, foo)))", "cfg(unix)"),
+            (
+                "cfg(all(any(unix, foo), any(unix, foo, bar)))",
+                "cfg(any(foo, unix))",
+            ),
+            (
+                "cfg(any(target_os = \"linux\", all(target_os = \"linux\", target_env = \"gnu\"), target_os = \"macos\"))",
+                "cfg(any(target_os = \"linux\", target_os = \"macos\"))",
+            ),
+            // Other predicates.
+            (
+                "cfg(any(target_pointer_width = \"64\", target_endian = \"big\", panic = \"abort\", target_has_atomic = \"ptr\"))",
+                "cfg(any(panic = \"abort\", target_endian = \"big\", target_has_atomic = \"ptr\", target_pointer_width = \"64\"))",
+            ),
+            (
+                "cfg(all(test, feature = \"foo\", my_key = \"value\", target_feature = \"sse2\"))",
+                "cfg(all(feature = \"foo\", my_key = \"value\", target_feature = \"sse2\", test))",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            let expr = Expression::parse(input).unwrap();
+            let normalized = normalize(&expr);
+            assert_eq!(normalized, expected, "for input: {input}");
+
+            // Normalization is idempotent.
+            let expr = Expression::parse(&normalized)
+                .unwrap_or_else(|err| panic!("normalized {normalized} is valid: {err}"));
+            assert_eq!(
+                normalize(&expr),
+                expected,
+                "for normalized input: {normalized}"
+            );
+        }
+    }
+}
@@ -34,6 +34,7 @@ pub struct Platform {
     triple: Triple,
     target_features: TargetFeatures,
     flags: BTreeSet<Cow<'static, str>>,
+    cfg_values: BTreeSet<(Cow<'static, str>, Cow<'static, str>)>,
 }
 
 impl Platform {
@@ -76,6 +77,7 @@ impl Platform {
             triple,
             target_features,
             flags: BTreeSet::new(),
+            cfg_values: BTreeSet::new(),
         })
     }
 
@@ -85,6 +87,7 @@ impl Platform {
             triple,
             target_features,
             flags: BTreeSet::new(),
+            cfg_values: BTreeSet::new(),
         }
     }
 
@@ -100,9 +103,40 @@ impl Platform {
             triple,
             target_features,
             flags: BTreeSet::new(),
+            cfg_values: BTreeSet::new(),
         })
     }
 
+    /// Creates a new custom `Platform` from a target JSON file, as passed in to `cargo build
+    /// --target my-target.json`.
+    ///
+    /// As with rustc, the triple string is the file name without the `.json` extension. The
+    /// `cfg()` values for the platform, such as `target_os`, `target_arch`, `target_env`,
+    /// `target_endian` and `target_pointer_width`, are derived from the JSON specification.
+    ///
+    /// Returns an error if the file couldn't be read or isn't a valid target specification.
+    #[cfg(feature = "custom")]
+    pub fn new_custom_from_path(
+        path: impl AsRef<std::path::Path>,
+        target_features: TargetFeatures,
+    ) -> Result<Self, Error> {
+        use crate::errors::CustomTripleCreateError;
+
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|error| {
+            Error::CustomPlatformCreate(CustomTripleCreateError::ReadFile {
+                path: path.to_owned(),
+                error: error.into(),
+            })
+        })?;
+        let triple_str = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        Self::new_custom(triple_str, &json, target_features)
+    }
+
     /// Adds a set of flags to accept.
     ///
     /// A flag is a single token like the `foo` in `cfg(not(foo))`.
@@ -114,6 +148,107 @@ impl Platform {
         self.flags.extend(flags.into_iter().map(|s| s.into()));
     }
 
+    /// Adds a set of key-value cfgs to accept.
+    ///
+    /// A key-value cfg is a pair like the `foo = "bar"` in `cfg(foo = "bar")`. A key may be
+    /// associated with several values, as with `target_feature`.
+    ///
+    /// A default `cargo build` will always evaluate custom key-value cfgs to false, but they may
+    /// be set through `RUSTFLAGS`, e.g. `--cfg 'foo="bar"'`.
+    ///
+    /// Keys already understood by `target-spec`, such as `target_os`, are derived from the
+    /// triple and cannot be overridden this way.
+    pub fn add_cfg_values(
+        &mut self,
+        values: impl IntoIterator<Item = (impl Into<Cow<'static, str>>, impl Into<Cow<'static, str>>)>,
+    ) {
+        self.cfg_values.extend(
+            values
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into())),
+        );
+    }
+
+    /// Adds a set of cfgs to accept, in the format passed in to `rustc --cfg`.
+    ///
+    /// Each cfg is either a flag like `foo`, or a key-value pair like `foo="bar"`. This makes it
+    /// possible to mirror the `--cfg` arguments in `RUSTFLAGS`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::{Platform, TargetFeatures};
+    ///
+    /// let mut platform = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap();
+    /// platform.add_cfgs(["my_cfg", "my_key=\"my_value\""]);
+    /// assert!(platform.has_flag("my_cfg"));
+    /// assert!(platform.has_cfg_value("my_key", "my_value"));
+    /// ```
+    pub fn add_cfgs(&mut self, cfgs: impl IntoIterator<Item = impl AsRef<str>>) {
+        for cfg in cfgs {
+            let cfg = cfg.as_ref();
+            match cfg.split_once('=') {
+                Some((key, value)) => {
+                    let value = value.trim();
+                    let value = value
+                        .strip_prefix('"')
+                        .and_then(|value| value.strip_suffix('"'))
+                        .unwrap_or(value);
+                    self.cfg_values
+                        .insert((key.trim().to_owned().into(), value.to_owned().into()));
+                }
+                None => {
+                    self.flags.insert(cfg.trim().to_owned().into());
+                }
+            }
+        }
+    }
+
+    /// Adds a set of flags to accept, returning `self` so that calls can be chained.
+    ///
+    /// See [`add_flags`](Self::add_flags) for more.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::{Platform, TargetFeatures, TargetSpec};
+    ///
+    /// let platform = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown)
+    ///     .unwrap()
+    ///     .with_flags(["my_custom_cfg"])
+    ///     .with_cfg_values([("my_key", "my_value")]);
+    ///
+    /// let spec = TargetSpec::new("cfg(all(unix, my_custom_cfg, my_key = \"my_value\"))").unwrap();
+    /// assert_eq!(spec.eval(&platform), Some(true));
+    /// ```
+    pub fn with_flags(
+        mut self,
+        flags: impl IntoIterator<Item = impl Into<Cow<'static, str>>>,
+    ) -> Self {
+        self.add_flags(flags);
+        self
+    }
+
+    /// Adds a set of key-value cfgs to accept, returning `self` so that calls can be chained.
+    ///
+    /// See [`add_cfg_values`](Self::add_cfg_values) for more.
+    pub fn with_cfg_values(
+        mut self,
+        values: impl IntoIterator<Item = (impl Into<Cow<'static, str>>, impl Into<Cow<'static, str>>)>,
+    ) -> Self {
+        self.add_cfg_values(values);
+        self
+    }
+
+    /// Adds a set of cfgs in the format passed in to `rustc --cfg`, returning `self` so that calls
+    /// can be chained.
+    ///
+    /// See [`add_cfgs`](Self::add_cfgs) for more.
+    pub fn with_cfgs(mut self, cfgs: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        self.add_cfgs(cfgs);
+        self
+    }
+
     /// Returns the target triple string for this platform.
     pub fn triple_str(&self) -> &str {
         self.triple.as_str()
@@ -129,6 +264,20 @@ impl Platform {
         self.flags.contains(flag.as_ref())
     }
 
+    /// Returns the set of key-value cfgs enabled for this platform.
+    pub fn cfg_values(&self) -> impl ExactSizeIterator<Item = (&str, &str)> {
+        self.cfg_values
+            .iter()
+            .map(|(key, value)| (key.deref(), value.deref()))
+    }
+
+    /// Returns true if this key-value cfg was set with `add_cfg_values` or `add_cfgs`.
+    pub fn has_cfg_value(&self, key: impl AsRef<str>, value: impl AsRef<str>) -> bool {
+        self.cfg_values
+            .iter()
+            .any(|(k, v)| k == key.as_ref() && v == value.as_ref())
+    }
+
     /// Returns true if this is a standard platform.
     ///
     /// A standard platform can be either builtin, or heuristically determined.
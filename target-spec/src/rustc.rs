@@ -0,0 +1,437 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{errors::RustcError, Error, Platform, TargetFeatures, Triple};
+use cfg_expr::targets::{
+    Abi, Arch, Endian, Env, Families, Family, HasAtomic, HasAtomics, Os, Panic, TargetInfo, Vendor,
+};
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    process::Command,
+    sync::{Arc, Mutex},
+};
+
+/// Obtains the list of platforms and their `cfg()` values by querying `rustc` at runtime.
+///
+/// By default, `target-spec` uses a static database of platforms, embedded at build time. New
+/// platforms appear in nightly Rust regularly, and this database only picks them up with a new
+/// release of `target-spec`. `RustcPlatforms` instead asks the Rust compiler directly, through
+/// `rustc --print target-list` and `rustc --print cfg --target <triple>`.
+///
+/// Results are cached: `rustc` is invoked at most once for the target list, and at most once per
+/// triple.
+///
+/// Platforms created this way are considered standard, but are neither builtin nor heuristic.
+/// Serializing them through [`summaries`](crate::summaries) only records the triple string.
+///
+/// # Examples
+///
+/// ```no_run
+/// use target_spec::{RustcPlatforms, TargetSpec};
+///
+/// let rustc = RustcPlatforms::new();
+/// let platform = rustc.platform("x86_64-unknown-linux-gnu").unwrap();
+///
+/// let spec = TargetSpec::new(r#"cfg(target_os = "linux")"#).unwrap();
+/// assert_eq!(spec.eval(&platform), Some(true));
+/// ```
+#[derive(Debug)]
+pub struct RustcPlatforms {
+    rustc: OsString,
+    cache: Mutex<RustcCache>,
+}
+
+#[derive(Debug, Default)]
+struct RustcCache {
+    target_list: Option<Arc<[String]>>,
+    targets: BTreeMap<String, RustcTarget>,
+}
+
+#[derive(Clone, Debug)]
+struct RustcTarget {
+    triple: Triple,
+    target_features: Vec<String>,
+    flags: Vec<String>,
+    cfg_values: Vec<(String, String)>,
+}
+
+impl RustcTarget {
+    fn into_platform(self) -> Platform {
+        Platform::from_triple(self.triple, TargetFeatures::features(self.target_features))
+            .with_flags(self.flags)
+            .with_cfg_values(self.cfg_values)
+    }
+}
+
+impl RustcPlatforms {
+    /// Creates a new `RustcPlatforms` that invokes the `rustc` specified by the `RUSTC`
+    /// environment variable, or `rustc` on the `PATH` if it isn't set.
+    pub fn new() -> Self {
+        Self::with_rustc(std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into()))
+    }
+
+    /// Creates a new `RustcPlatforms` that invokes the given `rustc` binary.
+    pub fn with_rustc(rustc: impl Into<OsString>) -> Self {
+        Self {
+            rustc: rustc.into(),
+            cache: Mutex::new(RustcCache::default()),
+        }
+    }
+
+    /// Returns the list of target triples known to `rustc`.
+    pub fn target_list(&self) -> Result<Arc<[String]>, Error> {
+        if let Some(target_list) = &self.lock_cache().target_list {
+            return Ok(target_list.clone());
+        }
+
+        let output = self.run(&["--print", "target-list"])?;
+        let target_list: Arc<[String]> = output
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_owned())
+            .collect();
+        self.lock_cache().target_list = Some(target_list.clone());
+        Ok(target_list)
+    }
+
+    /// Returns the triple for `triple_str`, with `cfg()` values as reported by `rustc`.
+    ///
+    /// Returns an error if `rustc` doesn't know about this triple.
+    pub fn triple(&self, triple_str: &str) -> Result<Triple, Error> {
+        Ok(self.target(triple_str)?.triple)
+    }
+
+    /// Returns the platform for `triple_str`, with `cfg()` values as reported by `rustc`.
+    ///
+    /// The platform has the target features that `rustc` enables by default for this triple, and
+    /// any other cfgs reported by `rustc` as flags and key-value cfgs.
+    ///
+    /// Returns an error if `rustc` doesn't know about this triple.
+    pub fn platform(&self, triple_str: &str) -> Result<Platform, Error> {
+        Ok(self.target(triple_str)?.into_platform())
+    }
+
+    fn target(&self, triple_str: &str) -> Result<RustcTarget, Error> {
+        if let Some(target) = self.lock_cache().targets.get(triple_str) {
+            return Ok(target.clone());
+        }
+
+        let args = ["--print", "cfg", "--target", triple_str];
+        let output = self.run(&args)?;
+        let target = parse_cfg(triple_str, &output).map_err(|message| {
+            Error::Rustc(RustcError::InvalidOutput {
+                command: self.command_string(&args),
+                message,
+            })
+        })?;
+        self.lock_cache()
+            .targets
+            .insert(triple_str.to_owned(), target.clone());
+        Ok(target)
+    }
+
+    fn run(&self, args: &[&str]) -> Result<String, Error> {
+        let output = Command::new(&self.rustc)
+            .args(args)
+            .output()
+            .map_err(|error| {
+                Error::Rustc(RustcError::Spawn {
+                    command: self.command_string(args),
+                    error: error.into(),
+                })
+            })?;
+        if !output.status.success() {
+            return Err(Error::Rustc(RustcError::Failed {
+                command: self.command_string(args),
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }));
+        }
+        String::from_utf8(output.stdout).map_err(|_| {
+            Error::Rustc(RustcError::InvalidOutput {
+                command: self.command_string(args),
+                message: "output is not valid UTF-8".to_owned(),
+            })
+        })
+    }
+
+    fn command_string(&self, args: &[&str]) -> String {
+        let mut command = self.rustc.to_string_lossy().into_owned();
+        for arg in args {
+            command.push(' ');
+            command.push_str(arg);
+        }
+        command
+    }
+
+    fn lock_cache(&self) -> std::sync::MutexGuard<'_, RustcCache> {
+        // The cache is always left in a consistent state, so it's fine to ignore poisoning.
+        self.cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for RustcPlatforms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Platform {
+    /// Creates a new `Platform` from the captured output of `rustc --print cfg --target
+    /// <triple>`.
+    ///
+    /// Unlike the static database used by [`Platform::new`], this reflects the actual compiler
+    /// configuration. In particular, target features enabled through `-C target-feature` or
+    /// `-C target-cpu` are included, so the output can be captured with e.g. `RUSTFLAGS` applied.
+    /// Other cfgs reported by `rustc`, such as those passed in through `--cfg`, are added as
+    /// flags and key-value cfgs.
+    ///
+    /// The platform is standard, but neither builtin nor heuristic.
+    ///
+    /// Returns an error if the output couldn't be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::{Platform, TargetSpec};
+    ///
+    /// // Output of `rustc --print cfg --target x86_64-unknown-linux-gnu -C target-feature=+avx2`,
+    /// // trimmed.
+    /// let output = r#"panic="unwind"
+    /// target_arch="x86_64"
+    /// target_endian="little"
+    /// target_env="gnu"
+    /// target_family="unix"
+    /// target_feature="avx"
+    /// target_feature="avx2"
+    /// target_feature="sse2"
+    /// target_os="linux"
+    /// target_pointer_width="64"
+    /// target_vendor="unknown"
+    /// unix
+    /// "#;
+    /// let platform = Platform::from_rustc_cfg("x86_64-unknown-linux-gnu", output).unwrap();
+    ///
+    /// let spec = TargetSpec::new(r#"cfg(all(unix, target_feature = "avx2"))"#).unwrap();
+    /// assert_eq!(spec.eval(&platform), Some(true));
+    /// let spec = TargetSpec::new(r#"cfg(target_feature = "avx512f")"#).unwrap();
+    /// assert_eq!(spec.eval(&platform), Some(false));
+    /// ```
+    pub fn from_rustc_cfg(triple_str: &str, output: &str) -> Result<Self, Error> {
+        let target = parse_cfg(triple_str, output).map_err(|message| {
+            Error::Rustc(RustcError::InvalidOutput {
+                command: format!("rustc --print cfg --target {triple_str}"),
+                message,
+            })
+        })?;
+        Ok(target.into_platform())
+    }
+}
+
+/// Parses the output of `rustc --print cfg`.
+fn parse_cfg(triple_str: &str, output: &str) -> Result<RustcTarget, String> {
+    let mut arch = None;
+    let mut pointer_width = None;
+    let mut os = None;
+    let mut abi = None;
+    let mut env = None;
+    let mut vendor = None;
+    let mut families = vec![];
+    let mut endian = Endian::little;
+    let mut has_atomics = vec![];
+    let mut panic = Panic::unwind;
+    let mut target_features = vec![];
+    let mut flags = vec![];
+    let mut cfg_values = vec![];
+
+    for line in output.lines() {
+        // Lines are either `name` or `name="value"`.
+        let line = line.trim();
+        let Some((name, value)) = line.split_once('=') else {
+            match line {
+                // These are also reported as target_family.
+                "" | "unix" | "windows" => {}
+                flag => flags.push(flag.to_owned()),
+            }
+            continue;
+        };
+        let value = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .ok_or_else(|| format!("invalid cfg line: {line}"))?
+            .to_owned();
+        // Empty values are reported for unset cfgs, e.g. target_env="" on macOS.
+        let non_empty = (!value.is_empty()).then(|| value.clone());
+
+        match name {
+            "target_arch" => arch = Some(value),
+            "target_pointer_width" => {
+                let width = value
+                    .parse::<u8>()
+                    .map_err(|error| format!("invalid target_pointer_width `{value}`: {error}"))?;
+                pointer_width = Some(width);
+            }
+            "target_os" => os = non_empty,
+            "target_abi" => abi = non_empty,
+            "target_env" => env = non_empty,
+            "target_vendor" => vendor = non_empty,
+            "target_family" => families.push(Family::new(value)),
+            "target_endian" => {
+                endian = match value.as_str() {
+                    "little" => Endian::little,
+                    "big" => Endian::big,
+                    _ => return Err(format!("invalid target_endian `{value}`")),
+                }
+            }
+            "target_has_atomic" => {
+                let has_atomic = match value.as_str() {
+                    "ptr" => HasAtomic::Pointer,
+                    _ => HasAtomic::IntegerSize(value.parse().map_err(|error| {
+                        format!("invalid target_has_atomic `{value}`: {error}")
+                    })?),
+                };
+                has_atomics.push(has_atomic);
+            }
+            "panic" => panic = Panic::new(value),
+            "target_feature" => target_features.push(value),
+            _ => cfg_values.push((name.to_owned(), value)),
+        }
+    }
+
+    let target_info = TargetInfo {
+        triple: cfg_expr::targets::Triple::new(triple_str.to_owned()),
+        os: os.map(Os::new),
+        abi: abi.map(Abi::new),
+        arch: Arch::new(arch.ok_or("target_arch not found")?),
+        env: env.map(Env::new),
+        vendor: vendor.map(Vendor::new),
+        families: Families::new(families),
+        pointer_width: pointer_width.ok_or("target_pointer_width not found")?,
+        endian,
+        has_atomics: HasAtomics::new(has_atomics),
+        panic,
+    };
+    Ok(RustcTarget {
+        triple: Triple::from_rustc(target_info),
+        target_features,
+        flags,
+        cfg_values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TargetSpec;
+
+    #[test]
+    fn test_parse_cfg() {
+        // Output of `rustc --print cfg --target aarch64-apple-darwin --cfg my_flag
+        // --cfg 'my_key="my_value"'`, trimmed.
+        let output = r#"debug_assertions
+my_flag
+my_key="my_value"
+panic="unwind"
+target_abi=""
+target_arch="aarch64"
+target_endian="little"
+target_env=""
+target_family="unix"
+target_feature="neon"
+target_feature="aes"
+target_has_atomic="128"
+target_has_atomic="ptr"
+target_os="macos"
+target_pointer_width="64"
+target_vendor="apple"
+unix
+"#;
+        let platform =
+            Platform::from_rustc_cfg("aarch64-apple-darwin", output).expect("output parsed");
+        assert!(platform.is_standard(), "rustc platforms are standard");
+        assert!(!platform.is_builtin(), "rustc platforms aren't builtin");
+
+        for (expr, expected) in [
+            (r#"cfg(target_os = "macos")"#, true),
+            (r#"cfg(target_arch = "aarch64")"#, true),
+            (r#"cfg(target_env = "")"#, true),
+            (r#"cfg(target_vendor = "apple")"#, true),
+            (r#"cfg(target_has_atomic = "128")"#, true),
+            (r#"cfg(target_has_atomic = "64")"#, false),
+            (r#"cfg(target_feature = "neon")"#, true),
+            (r#"cfg(target_pointer_width = "64")"#, true),
+            ("cfg(unix)", true),
+            ("cfg(windows)", false),
+            ("cfg(my_flag)", true),
+            (r#"cfg(my_key = "my_value")"#, true),
+            (r#"cfg(my_key = "other_value")"#, false),
+        ] {
+            let spec = TargetSpec::new(expr).expect("expression parsed");
+            assert_eq!(spec.eval(&platform), Some(expected), "evaluating {expr}");
+        }
+
+        assert!(
+            parse_cfg("foo", "target_os=\"none\"").is_err(),
+            "target_arch is required"
+        );
+    }
+
+    #[test]
+    fn test_query_rustc() {
+        let rustc = RustcPlatforms::new();
+        let target_list = rustc.target_list().expect("target list obtained");
+        assert!(
+            target_list
+                .iter()
+                .any(|triple| triple == "x86_64-unknown-linux-gnu"),
+            "target list contains x86_64-unknown-linux-gnu"
+        );
+
+        // rustc should agree with the builtin database for a tier 1 platform.
+        let platform = rustc
+            .platform("x86_64-unknown-linux-gnu")
+            .expect("platform obtained");
+        let builtin = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown)
+            .expect("builtin platform");
+        for expr in [
+            r#"cfg(target_os = "linux")"#,
+            r#"cfg(target_env = "gnu")"#,
+            r#"cfg(target_arch = "x86_64")"#,
+            r#"cfg(target_endian = "little")"#,
+            r#"cfg(target_pointer_width = "64")"#,
+            "cfg(unix)",
+        ] {
+            let spec = TargetSpec::new(expr).expect("expression parsed");
+            assert_eq!(
+                spec.eval(&platform),
+                spec.eval(&builtin),
+                "evaluating {expr}"
+            );
+        }
+        assert_eq!(
+            TargetSpec::new(r#"cfg(target_feature = "sse2")"#)
+                .expect("expression parsed")
+                .eval(&platform),
+            Some(true),
+            "target features enabled by default are reported"
+        );
+
+        // Results are cached.
+        assert_eq!(
+            rustc.triple("x86_64-unknown-linux-gnu").expect("cached"),
+            *platform.triple(),
+        );
+
+        let err = rustc
+            .triple("not-a-real-target")
+            .expect_err("unknown triple");
+        assert!(
+            matches!(err, Error::Rustc(RustcError::Failed { .. })),
+            "unexpected error: {err:?}"
+        );
+    }
+}
@@ -142,6 +142,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cfg_values() {
+        let platform = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap();
+        let platform_with_cfgs =
+            platform
+                .clone()
+                .with_cfgs(["my_flag", "my_key=\"a\"", "my_key = \"b\""]);
+
+        for (cfg, expected) in [
+            ("cfg(my_key = \"a\")", true),
+            ("cfg(my_key = \"b\")", true),
+            ("cfg(my_key = \"c\")", false),
+            ("cfg(other_key = \"a\")", false),
+            ("cfg(all(unix, my_flag, my_key = \"a\"))", true),
+            ("cfg(all(windows, my_flag))", false),
+        ] {
+            let spec: TargetSpec = cfg.parse().unwrap();
+            // key-value cfgs missing means false.
+            assert_eq!(spec.eval(&platform), Some(false), "for {}", cfg);
+            assert_eq!(
+                spec.eval(&platform_with_cfgs),
+                Some(expected),
+                "for {}",
+                cfg
+            );
+        }
+    }
+
     #[test]
     fn test_target_feature() {
         // target features are unknown by default.
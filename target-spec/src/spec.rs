@@ -3,9 +3,9 @@
 
 use crate::{
     errors::{ExpressionParseError, PlainStringParseError},
-    Error, Platform, Triple,
+    implication, normalize, BuiltinFilter, Error, Platform, TargetFeatures, Triple,
 };
-use cfg_expr::{Expression, Predicate};
+use cfg_expr::{targets::ALL_BUILTINS, Expression, Predicate};
 use std::{borrow::Cow, fmt, str::FromStr, sync::Arc};
 
 /// A parsed target specification or triple string, as found in a `Cargo.toml` file.
@@ -146,6 +146,96 @@ impl TargetSpec {
             TargetSpec::Expression(expr) => expr.eval(platform),
         }
     }
+
+    /// Returns the builtin platforms matched by this specification and accepted by `filter`.
+    ///
+    /// Platforms are evaluated with unknown target features and no flags. Only platforms for which
+    /// evaluation returns `Some(true)` are included. The triples are returned in sorted order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::{BuiltinFilter, TargetSpec, Tier};
+    ///
+    /// let spec: TargetSpec = "cfg(all(windows, target_arch = \"x86_64\"))".parse().unwrap();
+    /// let mut filter = BuiltinFilter::new();
+    /// filter.set_max_tier(Tier::Tier1);
+    /// assert_eq!(
+    ///     spec.matching_builtins(&filter),
+    ///     ["x86_64-pc-windows-gnu", "x86_64-pc-windows-msvc"],
+    /// );
+    /// ```
+    pub fn matching_builtins(&self, filter: &BuiltinFilter) -> Vec<&'static str> {
+        ALL_BUILTINS
+            .iter()
+            .filter_map(|target_info| {
+                let triple_str = target_info.triple.as_str();
+                let platform = Platform::new_strict(triple_str, TargetFeatures::Unknown)
+                    .expect("builtin triples are always valid");
+                (filter.accepts(platform.triple()) && self.eval(&platform) == Some(true))
+                    .then_some(triple_str)
+            })
+            .collect()
+    }
+
+    /// Returns a simplified, canonical form of this specification.
+    ///
+    /// For [`Self::Expression`], see [`TargetSpecExpression::normalized`]. [`Self::PlainString`]
+    /// is returned unchanged.
+    pub fn normalized(&self) -> Self {
+        match self {
+            TargetSpec::Expression(expr) => TargetSpec::Expression(expr.normalized()),
+            TargetSpec::PlainString(plain_str) => TargetSpec::PlainString(plain_str.clone()),
+        }
+    }
+
+    /// Returns true if every platform matched by `self` is also matched by `other`.
+    ///
+    /// This reasons symbolically over the two specifications, without requiring any platforms to
+    /// be provided. Relationships between target predicates, such as every `target_os = "linux"`
+    /// platform also being `unix`, are derived from the builtin platforms known to `target-spec`.
+    /// Other predicates, such as flags and target features, are treated as independent of each
+    /// other and of the platform.
+    ///
+    /// Returns `Some(true)` if `self` implies `other`, `Some(false)` if it doesn't, or `None` if
+    /// this couldn't be determined. This is typically because a specification refers to a custom
+    /// platform, or because too many flags and target features are involved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::TargetSpec;
+    ///
+    /// let unix: TargetSpec = "cfg(unix)".parse().unwrap();
+    /// let linux: TargetSpec = "cfg(target_os = \"linux\")".parse().unwrap();
+    /// assert_eq!(linux.implies(&unix), Some(true), "cfg(unix) covers Linux");
+    /// assert_eq!(unix.implies(&linux), Some(false), "cfg(unix) also covers macOS");
+    /// ```
+    pub fn implies(&self, other: &TargetSpec) -> Option<bool> {
+        implication::exists(self, other, |a, b| a && !b).map(|found| !found)
+    }
+
+    /// Returns true if some platform is matched by both `self` and `other`.
+    ///
+    /// This reasons symbolically in the same manner as [`Self::implies`].
+    ///
+    /// Returns `Some(true)` if `self` and `other` intersect, `Some(false)` if they don't, or `None`
+    /// if this couldn't be determined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::TargetSpec;
+    ///
+    /// let unix: TargetSpec = "cfg(unix)".parse().unwrap();
+    /// let windows: TargetSpec = "cfg(windows)".parse().unwrap();
+    /// let x86_64: TargetSpec = "cfg(target_arch = \"x86_64\")".parse().unwrap();
+    /// assert_eq!(unix.intersects(&windows), Some(false));
+    /// assert_eq!(unix.intersects(&x86_64), Some(true));
+    /// ```
+    pub fn intersects(&self, other: &TargetSpec) -> Option<bool> {
+        implication::exists(self, other, |a, b| a && b)
+    }
 }
 
 impl FromStr for TargetSpec {
@@ -191,6 +281,41 @@ impl TargetSpecExpression {
         self.inner.original()
     }
 
+    /// Returns a simplified, canonical form of this expression.
+    ///
+    /// The simplifications performed are:
+    ///
+    /// * nested `all()` and `any()` are flattened, and their operands are deduplicated and sorted;
+    /// * `all()` and `any()` with a single operand are replaced with that operand;
+    /// * double negations are removed;
+    /// * expressions like `all(x, not(x))` and `any(x, not(x))` are replaced with `any()` (false)
+    ///   and `all()` (true) respectively, and such constants are then simplified away;
+    /// * absorption is applied: `all(x, any(x, y))` and `any(x, all(x, y))` both become `x`.
+    ///
+    /// Predicates are also written in a consistent form, e.g. `target_family = "unix"` becomes
+    /// `unix`. Two expressions which normalize to the same string are equivalent, though the
+    /// converse doesn't always hold.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::TargetSpecExpression;
+    ///
+    /// let expr = TargetSpecExpression::new(
+    ///     "cfg(any(unix, all(target_os = \"linux\", unix), any(windows, unix)))",
+    /// ).unwrap();
+    /// assert_eq!(expr.normalized().expression_str(), "any(unix, windows)");
+    /// ```
+    pub fn normalized(&self) -> Self {
+        let normalized = normalize::normalize(&self.inner);
+        Self::new(&normalized).expect("normalized expressions are always valid")
+    }
+
+    #[inline]
+    pub(crate) fn inner(&self) -> &Expression {
+        &self.inner
+    }
+
     /// Evaluates this expression against the given platform.
     ///
     /// Returns `Some(true)` if there's a match, `Some(false)` if there's none, or `None` if the
@@ -214,9 +339,9 @@ impl TargetSpecExpression {
                     // This returns false by default but true in some cases.
                     Some(platform.has_flag(flag))
                 }
-                Predicate::KeyValue { .. } => {
-                    // This is always interpreted by Cargo as false.
-                    Some(false)
+                Predicate::KeyValue { key, val } => {
+                    // This returns false by default but true if set through e.g. RUSTFLAGS.
+                    Some(platform.has_cfg_value(key, val))
                 }
             }
         })
@@ -7,10 +7,24 @@
 //! TOML files. This module provides facilities for that.
 //!
 //! Summaries require the `summaries` feature to be enabled.
+//!
+//! With this feature, [`TargetSpec`] also implements `Serialize` and `Deserialize`. A target spec
+//! is represented as a single string, in the same form as in a `Cargo.toml` file:
+//!
+//! * expressions are written as `cfg(...)`, e.g. `"cfg(all(unix, target_arch = \"x86_64\"))"`;
+//! * triples are written as-is, e.g. `"x86_64-unknown-linux-gnu"`.
+//!
+//! This form is stable, and serializing then deserializing a target spec results in an equivalent
+//! spec. For robustness against hand-written files, expressions without the surrounding `cfg()`,
+//! e.g. `"all(unix, target_arch = \"x86_64\")"`, are also accepted while deserializing. Invalid
+//! target specs produce an error describing what went wrong.
 
-use crate::{Error, Platform, TargetFeatures};
+use crate::{Error, Platform, TargetFeatures, TargetSpec};
 use serde::{Deserialize, Serialize};
-use std::{borrow::Cow, collections::BTreeSet};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
 
 impl Platform {
     /// Converts this `Platform` to a serializable form.
@@ -44,6 +58,10 @@ pub struct PlatformSummary {
     /// The flags enabled.
     #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
     pub flags: BTreeSet<String>,
+
+    /// The key-value cfgs enabled, as a map of keys to values.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub cfg_values: BTreeMap<String, BTreeSet<String>>,
 }
 
 impl PlatformSummary {
@@ -54,12 +72,14 @@ impl PlatformSummary {
     /// * `custom_json` is set to None.
     /// * `target_features` is set to [`TargetFeaturesSummary::Unknown`].
     /// * `flags` is empty.
+    /// * `cfg_values` is empty.
     pub fn new(triple_str: impl Into<String>) -> Self {
         Self {
             triple: triple_str.into(),
             custom_json: None,
             target_features: TargetFeaturesSummary::Unknown,
             flags: BTreeSet::new(),
+            cfg_values: BTreeMap::new(),
         }
     }
 
@@ -85,6 +105,20 @@ impl PlatformSummary {
         self
     }
 
+    /// Adds key-value cfgs for this platform.
+    pub fn with_added_cfg_values(
+        mut self,
+        values: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        for (key, value) in values {
+            self.cfg_values
+                .entry(key.into())
+                .or_default()
+                .insert(value.into());
+        }
+        self
+    }
+
     /// Creates a new `PlatformSummary` instance from a platform.
     pub fn from_platform(platform: &Platform) -> Self {
         Self {
@@ -92,6 +126,15 @@ impl PlatformSummary {
             custom_json: platform.custom_json().map(|s| s.to_owned()),
             target_features: TargetFeaturesSummary::new(platform.target_features()),
             flags: platform.flags().map(|flag| flag.to_string()).collect(),
+            cfg_values: platform.cfg_values().fold(
+                BTreeMap::new(),
+                |mut acc: BTreeMap<_, BTreeSet<_>>, (key, value)| {
+                    acc.entry(key.to_owned())
+                        .or_default()
+                        .insert(value.to_owned());
+                    acc
+                },
+            ),
         }
     }
 
@@ -120,6 +163,9 @@ impl PlatformSummary {
         };
 
         platform.add_flags(self.flags.iter().cloned());
+        platform.add_cfg_values(self.cfg_values.iter().flat_map(|(key, values)| {
+            values.iter().map(move |value| (key.clone(), value.clone()))
+        }));
         Ok(platform)
     }
 }
@@ -188,17 +234,20 @@ mod platform_impl {
                     custom_json: None,
                     target_features: TargetFeaturesSummary::default(),
                     flags: BTreeSet::default(),
+                    cfg_values: BTreeMap::default(),
                 }),
                 PlatformSummaryDeserialize::Full {
                     triple,
                     custom_json,
                     target_features,
                     flags,
+                    cfg_values,
                 } => Ok(PlatformSummary {
                     triple,
                     custom_json,
                     target_features,
                     flags,
+                    cfg_values,
                 }),
             }
         }
@@ -219,10 +268,68 @@ mod platform_impl {
             /// The flags enabled.
             #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
             flags: BTreeSet<String>,
+            /// The key-value cfgs enabled.
+            #[serde(default)]
+            cfg_values: BTreeMap<String, BTreeSet<String>>,
         },
     }
 }
 
+mod target_spec_impl {
+    use super::*;
+    use serde::{de::Error as _, Deserializer, Serializer};
+    use std::error::Error as _;
+
+    impl Serialize for TargetSpec {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match self {
+                TargetSpec::Expression(expr) => {
+                    serializer.collect_str(&format_args!("cfg({})", expr.expression_str()))
+                }
+                TargetSpec::PlainString(plain_str) => serializer.serialize_str(plain_str.as_str()),
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for TargetSpec {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let input = String::deserialize(deserializer)?;
+            let input = input.trim();
+            match TargetSpec::new(input.to_owned()) {
+                Ok(spec) => Ok(spec),
+                Err(error) => {
+                    // The input may be an expression without the surrounding cfg(), as produced
+                    // by TargetSpec's Display implementation.
+                    if matches!(error, Error::InvalidTargetSpecString(_))
+                        && !TargetSpec::looks_like_expression(input)
+                    {
+                        if let Ok(spec) = TargetSpec::new(format!("cfg({input})")) {
+                            return Ok(spec);
+                        }
+                    }
+
+                    let detail = match &error {
+                        // The expression error's Display is generic, so use the kind instead.
+                        Error::InvalidExpression(expr_error) => Some(expr_error.kind.to_string()),
+                        other => other.source().map(|source| source.to_string()),
+                    };
+                    let mut message = format!("invalid target spec `{input}`: {error}");
+                    if let Some(detail) = detail {
+                        message.push_str(&format!(": {detail}"));
+                    }
+                    Err(D::Error::custom(message))
+                }
+            }
+        }
+    }
+}
+
 mod target_features_impl {
     use super::*;
     use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
@@ -294,6 +401,7 @@ mod tests {
                 custom_json: None,
                 target_features: TargetFeaturesSummary::Unknown,
                 flags: BTreeSet::new(),
+                cfg_values: BTreeMap::new(),
             },
         ));
         valid.push((
@@ -303,6 +411,7 @@ mod tests {
                 custom_json: None,
                 target_features: TargetFeaturesSummary::Unknown,
                 flags: BTreeSet::new(),
+                cfg_values: BTreeMap::new(),
             },
         ));
         valid.push((
@@ -312,6 +421,7 @@ mod tests {
                 custom_json: None,
                 target_features: TargetFeaturesSummary::Unknown,
                 flags: BTreeSet::new(),
+                cfg_values: BTreeMap::new(),
             },
         ));
         valid.push((
@@ -321,6 +431,7 @@ mod tests {
                 custom_json: None,
                 target_features: TargetFeaturesSummary::All,
                 flags: BTreeSet::new(),
+                cfg_values: BTreeMap::new(),
             },
         ));
         valid.push((
@@ -330,6 +441,7 @@ mod tests {
                 custom_json: None,
                 target_features: TargetFeaturesSummary::Features(BTreeSet::new()),
                 flags: BTreeSet::new(),
+                cfg_values: BTreeMap::new(),
             },
         ));
 
@@ -345,6 +457,7 @@ mod tests {
                 custom_json: Some(custom_json.to_owned()),
                 target_features: TargetFeaturesSummary::Unknown,
                 flags: BTreeSet::new(),
+                cfg_values: BTreeMap::new(),
             },
         ));
 
@@ -357,6 +470,23 @@ mod tests {
                 custom_json: None,
                 target_features: TargetFeaturesSummary::Unknown,
                 flags,
+                cfg_values: BTreeMap::new(),
+            },
+        ));
+
+        let mut cfg_values = BTreeMap::new();
+        cfg_values.insert(
+            "my_key".to_owned(),
+            ["a".to_owned(), "b".to_owned()].into_iter().collect(),
+        );
+        valid.push((
+            r#"platform = { triple = "x86_64-unknown-linux-gnu", cfg-values = { my_key = ["a", "b"] } }"#,
+            PlatformSummary {
+                triple: "x86_64-unknown-linux-gnu".into(),
+                custom_json: None,
+                target_features: TargetFeaturesSummary::Unknown,
+                flags: BTreeSet::new(),
+                cfg_values,
             },
         ));
 
@@ -400,6 +530,69 @@ mod tests {
     }
 }
 
+#[cfg(test)]
+mod target_spec_tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Wrapper {
+        spec: TargetSpec,
+    }
+
+    #[test]
+    fn target_spec_roundtrip() {
+        for (input, expected) in [
+            (
+                r#"cfg(all(unix, target_arch = "x86_64"))"#,
+                r#"cfg(all(unix, target_arch = "x86_64"))"#,
+            ),
+            ("x86_64-unknown-linux-gnu", "x86_64-unknown-linux-gnu"),
+            // Expressions without the surrounding cfg() and extra whitespace are accepted.
+            (
+                r#"all(unix, target_arch = "x86_64")"#,
+                r#"cfg(all(unix, target_arch = "x86_64"))"#,
+            ),
+            (" cfg(unix) ", "cfg(unix)"),
+        ] {
+            let input_toml = format!("spec = '{input}'");
+            let wrapper: Wrapper = toml::from_str(&input_toml)
+                .unwrap_or_else(|err| panic!("input {input} is valid: {err}"));
+            let serialized = toml::to_string(&wrapper).expect("serialized correctly");
+            let value: toml::Value = toml::from_str(&serialized).expect("serialized is valid TOML");
+            assert_eq!(value["spec"].as_str(), Some(expected), "for input: {input}");
+
+            // Round-trip the serialized form.
+            let wrapper_2: Wrapper = toml::from_str(&serialized)
+                .unwrap_or_else(|err| panic!("serialized {serialized} is valid: {err}"));
+            assert_eq!(
+                toml::to_string(&wrapper_2).expect("serialized correctly"),
+                serialized,
+                "for input: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn target_spec_deserialize_invalid() {
+        for (input, message) in [
+            (
+                "spec = 'cfg(all(unix)'",
+                "invalid target spec `cfg(all(unix)`: invalid cfg() expression: unclosed parens",
+            ),
+            (
+                "spec = 'x86_64 linux'",
+                "invalid target spec `x86_64 linux`: failed to parse target spec as a plain string",
+            ),
+        ] {
+            let err = toml::from_str::<Wrapper>(input).expect_err("input is invalid");
+            assert!(
+                err.to_string().contains(message),
+                "for input {input}, error `{err}` contains `{message}`"
+            );
+        }
+    }
+}
+
 #[cfg(all(test, feature = "proptest1"))]
 mod proptests {
     use super::*;
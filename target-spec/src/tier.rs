@@ -0,0 +1,275 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::Triple;
+use std::fmt;
+
+/// The [support tier](https://doc.rust-lang.org/nightly/rustc/platform-support.html) of a builtin
+/// platform.
+///
+/// Tiers are ordered from most to least supported, so `Tier::Tier1 < Tier::Tier3`.
+///
+/// Tier information is embedded into `target-spec`, and may lag behind the latest Rust release.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Tier {
+    /// Tier 1 platforms are guaranteed to work. All tier 1 platforms have host tools.
+    Tier1,
+
+    /// Tier 2 platforms are guaranteed to build. Some tier 2 platforms have host tools.
+    Tier2,
+
+    /// Tier 3 platforms are supported by the Rust codebase, but aren't built or tested
+    /// automatically.
+    Tier3,
+}
+
+impl Tier {
+    /// Returns the tier of the given triple, and whether it has host tools.
+    ///
+    /// Returns `None` if the triple isn't builtin.
+    pub(crate) fn for_triple(triple: &Triple) -> Option<(Self, bool)> {
+        if !triple.is_builtin() {
+            return None;
+        }
+        let triple_str = triple.as_str();
+        if TIER_1.contains(&triple_str) {
+            Some((Tier::Tier1, true))
+        } else if TIER_2_HOST_TOOLS.contains(&triple_str) {
+            Some((Tier::Tier2, true))
+        } else if TIER_2.contains(&triple_str) {
+            Some((Tier::Tier2, false))
+        } else {
+            // Some tier 3 platforms have host tools, but these aren't tracked.
+            Some((Tier::Tier3, false))
+        }
+    }
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tier::Tier1 => write!(f, "tier 1"),
+            Tier::Tier2 => write!(f, "tier 2"),
+            Tier::Tier3 => write!(f, "tier 3"),
+        }
+    }
+}
+
+/// A filter over builtin platforms, used by [`TargetSpec::matching_builtins`].
+///
+/// By default, all builtin platforms are accepted.
+///
+/// # Examples
+///
+/// ```
+/// use target_spec::{BuiltinFilter, Tier};
+///
+/// // Only accept tier 1 and tier 2 platforms with host tools.
+/// let mut filter = BuiltinFilter::new();
+/// filter.set_max_tier(Tier::Tier2).set_host_tools(true);
+/// ```
+///
+/// [`TargetSpec::matching_builtins`]: crate::TargetSpec::matching_builtins
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BuiltinFilter {
+    max_tier: Tier,
+    host_tools: bool,
+}
+
+impl BuiltinFilter {
+    /// Creates a new `BuiltinFilter` which accepts all builtin platforms.
+    pub fn new() -> Self {
+        Self {
+            max_tier: Tier::Tier3,
+            host_tools: false,
+        }
+    }
+
+    /// Only accepts platforms at this tier or a more supported one.
+    ///
+    /// For example, `Tier::Tier2` accepts both tier 1 and tier 2 platforms.
+    pub fn set_max_tier(&mut self, max_tier: Tier) -> &mut Self {
+        self.max_tier = max_tier;
+        self
+    }
+
+    /// If true, only accepts platforms with host tools, i.e. platforms `rustc` and `cargo` are
+    /// available for.
+    pub fn set_host_tools(&mut self, host_tools: bool) -> &mut Self {
+        self.host_tools = host_tools;
+        self
+    }
+
+    /// Returns true if this filter accepts the given triple.
+    ///
+    /// Triples that aren't builtin are never accepted.
+    pub fn accepts(&self, triple: &Triple) -> bool {
+        match Tier::for_triple(triple) {
+            Some((tier, host_tools)) => tier <= self.max_tier && (host_tools || !self.host_tools),
+            None => false,
+        }
+    }
+}
+
+impl Default for BuiltinFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Tier information as of Rust 1.80.
+
+static TIER_1: &[&str] = &[
+    "aarch64-unknown-linux-gnu",
+    "i686-pc-windows-gnu",
+    "i686-pc-windows-msvc",
+    "i686-unknown-linux-gnu",
+    "x86_64-apple-darwin",
+    "x86_64-pc-windows-gnu",
+    "x86_64-pc-windows-msvc",
+    "x86_64-unknown-linux-gnu",
+];
+
+static TIER_2_HOST_TOOLS: &[&str] = &[
+    "aarch64-apple-darwin",
+    "aarch64-pc-windows-msvc",
+    "aarch64-unknown-linux-musl",
+    "arm-unknown-linux-gnueabi",
+    "arm-unknown-linux-gnueabihf",
+    "armv7-unknown-linux-gnueabihf",
+    "loongarch64-unknown-linux-gnu",
+    "powerpc-unknown-linux-gnu",
+    "powerpc64-unknown-linux-gnu",
+    "powerpc64le-unknown-linux-gnu",
+    "riscv64gc-unknown-linux-gnu",
+    "s390x-unknown-linux-gnu",
+    "x86_64-unknown-freebsd",
+    "x86_64-unknown-illumos",
+    "x86_64-unknown-linux-musl",
+    "x86_64-unknown-netbsd",
+];
+
+static TIER_2: &[&str] = &[
+    "aarch64-apple-ios",
+    "aarch64-apple-ios-sim",
+    "aarch64-linux-android",
+    "aarch64-unknown-fuchsia",
+    "aarch64-unknown-linux-ohos",
+    "aarch64-unknown-none",
+    "aarch64-unknown-none-softfloat",
+    "aarch64-unknown-uefi",
+    "arm-linux-androideabi",
+    "arm-unknown-linux-musleabi",
+    "arm-unknown-linux-musleabihf",
+    "arm64ec-pc-windows-msvc",
+    "armebv7r-none-eabi",
+    "armebv7r-none-eabihf",
+    "armv5te-unknown-linux-gnueabi",
+    "armv5te-unknown-linux-musleabi",
+    "armv7-linux-androideabi",
+    "armv7-unknown-linux-gnueabi",
+    "armv7-unknown-linux-musleabi",
+    "armv7-unknown-linux-musleabihf",
+    "armv7-unknown-linux-ohos",
+    "armv7a-none-eabi",
+    "armv7r-none-eabi",
+    "armv7r-none-eabihf",
+    "i586-pc-windows-msvc",
+    "i586-unknown-linux-gnu",
+    "i586-unknown-linux-musl",
+    "i686-linux-android",
+    "i686-unknown-freebsd",
+    "i686-unknown-linux-musl",
+    "i686-unknown-uefi",
+    "loongarch64-unknown-none",
+    "loongarch64-unknown-none-softfloat",
+    "nvptx64-nvidia-cuda",
+    "riscv32i-unknown-none-elf",
+    "riscv32imac-unknown-none-elf",
+    "riscv32imafc-unknown-none-elf",
+    "riscv32imc-unknown-none-elf",
+    "riscv64gc-unknown-none-elf",
+    "riscv64imac-unknown-none-elf",
+    "sparc64-unknown-linux-gnu",
+    "sparcv9-sun-solaris",
+    "thumbv6m-none-eabi",
+    "thumbv7em-none-eabi",
+    "thumbv7em-none-eabihf",
+    "thumbv7m-none-eabi",
+    "thumbv7neon-linux-androideabi",
+    "thumbv7neon-unknown-linux-gnueabihf",
+    "thumbv8m.base-none-eabi",
+    "thumbv8m.main-none-eabi",
+    "thumbv8m.main-none-eabihf",
+    "wasm32-unknown-emscripten",
+    "wasm32-unknown-unknown",
+    "wasm32-wasi",
+    "wasm32-wasip1",
+    "wasm32-wasip1-threads",
+    "x86_64-apple-ios",
+    "x86_64-fortanix-unknown-sgx",
+    "x86_64-linux-android",
+    "x86_64-pc-solaris",
+    "x86_64-unknown-fuchsia",
+    "x86_64-unknown-linux-gnux32",
+    "x86_64-unknown-linux-ohos",
+    "x86_64-unknown-none",
+    "x86_64-unknown-redox",
+    "x86_64-unknown-uefi",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TargetSpec;
+
+    #[test]
+    fn test_tier_lists_are_builtin() {
+        for (list, tier, host_tools) in [
+            (TIER_1, Tier::Tier1, true),
+            (TIER_2_HOST_TOOLS, Tier::Tier2, true),
+            (TIER_2, Tier::Tier2, false),
+        ] {
+            for &triple_str in list {
+                let triple = Triple::new_strict(triple_str)
+                    .unwrap_or_else(|err| panic!("{triple_str} is builtin: {err}"));
+                assert_eq!(
+                    Tier::for_triple(&triple),
+                    Some((tier, host_tools)),
+                    "for triple {triple_str}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_matching_builtins() {
+        let spec: TargetSpec = "cfg(target_os = \"linux\")".parse().unwrap();
+
+        let mut filter = BuiltinFilter::new();
+        filter.set_max_tier(Tier::Tier1);
+        assert_eq!(
+            spec.matching_builtins(&filter),
+            [
+                "aarch64-unknown-linux-gnu",
+                "i686-unknown-linux-gnu",
+                "x86_64-unknown-linux-gnu"
+            ],
+        );
+
+        filter.set_max_tier(Tier::Tier2).set_host_tools(true);
+        let matches = spec.matching_builtins(&filter);
+        assert!(matches.contains(&"x86_64-unknown-linux-musl"));
+        assert!(matches.contains(&"riscv64gc-unknown-linux-gnu"));
+        assert!(!matches.contains(&"armv7-unknown-linux-musleabi"));
+
+        filter.set_host_tools(false);
+        let matches = spec.matching_builtins(&filter);
+        assert!(matches.contains(&"armv7-unknown-linux-musleabi"));
+        assert!(!matches.contains(&"x86_64-pc-windows-msvc"));
+
+        // Tier 3 platforms are included by default.
+        let matches = spec.matching_builtins(&BuiltinFilter::new());
+        assert!(matches.contains(&"mips-unknown-linux-gnu"));
+    }
+}
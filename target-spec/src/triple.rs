@@ -1,11 +1,11 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::{errors::TripleParseError, Platform};
+use crate::{errors::TripleParseError, Platform, Tier};
 use cfg_expr::{
     expr::TargetMatcher,
     target_lexicon,
-    targets::{get_builtin_target_by_triple, TargetInfo},
+    targets::{get_builtin_target_by_triple, TargetInfo, ALL_BUILTINS},
     TargetPredicate,
 };
 use std::{borrow::Cow, cmp::Ordering, hash, str::FromStr};
@@ -96,6 +96,28 @@ impl Triple {
         })
     }
 
+    /// Creates a new `Triple` from target information reported by `rustc`.
+    pub(crate) fn from_rustc(target_info: TargetInfo) -> Self {
+        Self {
+            inner: TripleInner::Rustc(Box::new(target_info)),
+        }
+    }
+
+    /// Returns the triple strings for all builtin platforms, in sorted order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::Triple;
+    ///
+    /// assert!(Triple::builtin_strs().any(|triple_str| triple_str == "x86_64-unknown-linux-gnu"));
+    /// ```
+    pub fn builtin_strs() -> impl ExactSizeIterator<Item = &'static str> {
+        ALL_BUILTINS
+            .iter()
+            .map(|target_info| target_info.triple.as_str())
+    }
+
     /// Returns the string corresponding to this triple.
     #[inline]
     pub fn as_str(&self) -> &str {
@@ -135,6 +157,32 @@ impl Triple {
         self.inner.is_builtin()
     }
 
+    /// Returns the [support tier](https://doc.rust-lang.org/nightly/rustc/platform-support.html)
+    /// of this triple.
+    ///
+    /// Returns `None` if this triple isn't builtin.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::{Tier, Triple};
+    ///
+    /// let triple = Triple::new("x86_64-unknown-linux-gnu").unwrap();
+    /// assert_eq!(triple.tier(), Some(Tier::Tier1));
+    /// ```
+    pub fn tier(&self) -> Option<Tier> {
+        Tier::for_triple(self).map(|(tier, _)| tier)
+    }
+
+    /// Returns true if this is a builtin triple with host tools, i.e. `rustc` and `cargo` are
+    /// available for it.
+    ///
+    /// All tier 1 platforms have host tools. Tier 3 platforms are always reported as not having
+    /// host tools.
+    pub fn has_host_tools(&self) -> bool {
+        Tier::for_triple(self).is_some_and(|(_, host_tools)| host_tools)
+    }
+
     /// Returns true if this triple was heuristically determined.
     ///
     /// All heuristically determined platforms are standard, but most of the time, standard
@@ -207,6 +255,9 @@ enum TripleInner {
         json: String,
     },
 
+    /// Target information obtained by querying `rustc`.
+    Rustc(Box<TargetInfo>),
+
     /// Fall back to the lexicon representation.
     Lexicon {
         triple_str: Cow<'static, str>,
@@ -259,7 +310,7 @@ impl TripleInner {
 
     fn is_standard(&self) -> bool {
         match self {
-            TripleInner::Builtin(_) | TripleInner::Lexicon { .. } => true,
+            TripleInner::Builtin(_) | TripleInner::Rustc(_) | TripleInner::Lexicon { .. } => true,
             #[cfg(feature = "custom")]
             TripleInner::Custom { .. } => false,
         }
@@ -268,7 +319,7 @@ impl TripleInner {
     fn is_builtin(&self) -> bool {
         match self {
             TripleInner::Builtin(_) => true,
-            TripleInner::Lexicon { .. } => false,
+            TripleInner::Rustc(_) | TripleInner::Lexicon { .. } => false,
             #[cfg(feature = "custom")]
             TripleInner::Custom { .. } => false,
         }
@@ -276,7 +327,7 @@ impl TripleInner {
 
     fn is_heuristic(&self) -> bool {
         match self {
-            TripleInner::Builtin(_) => false,
+            TripleInner::Builtin(_) | TripleInner::Rustc(_) => false,
             TripleInner::Lexicon { .. } => true,
             #[cfg(feature = "custom")]
             TripleInner::Custom { .. } => false,
@@ -285,7 +336,7 @@ impl TripleInner {
 
     fn is_custom(&self) -> bool {
         match self {
-            TripleInner::Builtin(_) | TripleInner::Lexicon { .. } => false,
+            TripleInner::Builtin(_) | TripleInner::Rustc(_) | TripleInner::Lexicon { .. } => false,
             #[cfg(feature = "custom")]
             TripleInner::Custom { .. } => true,
         }
@@ -296,6 +347,7 @@ impl TripleInner {
             TripleInner::Builtin(target_info) => target_info.triple.as_str(),
             #[cfg(feature = "custom")]
             TripleInner::Custom { target_info, .. } => target_info.triple.as_str(),
+            TripleInner::Rustc(target_info) => target_info.triple.as_str(),
             TripleInner::Lexicon { triple_str, .. } => triple_str,
         }
     }
@@ -305,7 +357,8 @@ impl TripleInner {
             TripleInner::Builtin(target_info) => target_info.matches(tp),
             #[cfg(feature = "custom")]
             TripleInner::Custom { target_info, .. } => target_info.matches(tp),
-            TripleInner::Lexicon { lexicon_triple, .. } => lexicon_triple.matches(tp),
+            TripleInner::Rustc(target_info) => target_info.matches(tp),
+            TripleInner::Lexicon { lexicon_triple, .. } => lexicon_matches(lexicon_triple, tp),
         }
     }
 
@@ -315,7 +368,7 @@ impl TripleInner {
             TripleInner::Builtin(_) => None,
             #[cfg(feature = "custom")]
             TripleInner::Custom { json, .. } => Some(json),
-            TripleInner::Lexicon { .. } => None,
+            TripleInner::Rustc(_) | TripleInner::Lexicon { .. } => None,
         }
     }
 
@@ -326,11 +379,62 @@ impl TripleInner {
             }
             #[cfg(feature = "custom")]
             TripleInner::Custom { target_info, .. } => TripleInnerProjected::Custom(target_info),
+            TripleInner::Rustc(target_info) => TripleInnerProjected::Rustc(target_info),
             TripleInner::Lexicon { triple_str, .. } => TripleInnerProjected::Lexicon(triple_str),
         }
     }
 }
 
+/// Evaluates a target predicate against a heuristically determined triple.
+///
+/// cfg-expr evaluates `target_abi`, `target_has_atomic` and `panic` to false for all such triples.
+/// Approximate them based on the rest of the triple instead, following rustc's defaults.
+fn lexicon_matches(triple: &target_lexicon::Triple, tp: &TargetPredicate) -> bool {
+    use cfg_expr::targets::HasAtomic;
+    use target_lexicon::OperatingSystem;
+
+    match tp {
+        TargetPredicate::Abi(abi) => abi.as_str() == lexicon_abi(triple.environment),
+        TargetPredicate::HasAtomic(has_atomic) => match triple.pointer_width() {
+            // Assume that atomics are supported up to the pointer width.
+            Ok(width) => match has_atomic {
+                HasAtomic::IntegerSize(size) => *size <= u16::from(width.bits()),
+                HasAtomic::Pointer => true,
+                _ => false,
+            },
+            Err(()) => false,
+        },
+        TargetPredicate::Panic(panic) => {
+            // Bare-metal targets abort on panic, and everything else unwinds.
+            let strategy = match triple.operating_system {
+                OperatingSystem::None_ | OperatingSystem::Unknown => "abort",
+                _ => "unwind",
+            };
+            panic.as_str() == strategy
+        }
+        _ => triple.matches(tp),
+    }
+}
+
+/// Returns the value of `target_abi` that rustc uses for an environment.
+fn lexicon_abi(environment: target_lexicon::Environment) -> &'static str {
+    use target_lexicon::Environment::*;
+
+    match environment {
+        Androideabi | Eabi | Gnueabi | Musleabi | Uclibceabi => "eabi",
+        Eabihf | Gnueabihf | Musleabihf | Uclibceabihf => "eabihf",
+        Gnuabi64 | Muslabi64 => "abi64",
+        GnuIlp32 => "ilp32",
+        GnuLlvm => "llvm",
+        Gnuspe | Spe => "spe",
+        Gnux32 => "x32",
+        Macabi => "macabi",
+        Sim => "sim",
+        Softfloat => "softfloat",
+        _ => "",
+    }
+}
+
 /// This implementation is used for trait impls.
 #[derive(Eq, PartialEq, PartialOrd, Ord, Hash)]
 enum TripleInnerProjected<'a> {
@@ -338,6 +442,7 @@ enum TripleInnerProjected<'a> {
     Builtin(&'a str),
     #[cfg(feature = "custom")]
     Custom(&'a TargetInfo),
+    Rustc(&'a TargetInfo),
     Lexicon(&'a str),
 }
 
@@ -392,6 +497,9 @@ mod tests {
             TripleInner::Builtin(_) => {
                 panic!("should not have been able to parse x86_64-pc-darwin as a builtin");
             }
+            TripleInner::Rustc(_) => {
+                panic!("not obtained from rustc")
+            }
             #[cfg(feature = "custom")]
             TripleInner::Custom { .. } => {
                 panic!("not a custom platform")
@@ -402,4 +510,57 @@ mod tests {
             "lexicon triple matched correctly"
         );
     }
+
+    #[test]
+    fn test_lexicon_newer_predicates() {
+        // For builtin triples, the heuristics for target_abi, target_has_atomic and panic should
+        // agree with the builtin database.
+        let triples = [
+            "x86_64-unknown-linux-gnu",
+            "armv7-unknown-linux-gnueabihf",
+            "arm-unknown-linux-musleabi",
+            "x86_64-unknown-linux-gnux32",
+            "mips64-unknown-linux-gnuabi64",
+            "aarch64-apple-ios-sim",
+            "x86_64-pc-windows-gnullvm",
+            "thumbv7em-none-eabihf",
+        ];
+        let exprs = [
+            r#"cfg(target_abi = "")"#,
+            r#"cfg(target_abi = "eabi")"#,
+            r#"cfg(target_abi = "eabihf")"#,
+            r#"cfg(target_abi = "x32")"#,
+            r#"cfg(target_abi = "abi64")"#,
+            r#"cfg(target_abi = "sim")"#,
+            r#"cfg(target_abi = "llvm")"#,
+            r#"cfg(panic = "unwind")"#,
+            r#"cfg(panic = "abort")"#,
+            r#"cfg(target_has_atomic = "8")"#,
+            r#"cfg(target_has_atomic = "32")"#,
+            r#"cfg(target_has_atomic = "ptr")"#,
+        ];
+
+        for triple_str in triples {
+            let builtin = crate::Platform::new_strict(triple_str, crate::TargetFeatures::Unknown)
+                .expect("builtin triple");
+            let lexicon_triple = triple_str.parse().expect("triple parsed by target-lexicon");
+            let heuristic = crate::Platform::from_triple(
+                super::Triple {
+                    inner: TripleInner::Lexicon {
+                        triple_str: triple_str.into(),
+                        lexicon_triple,
+                    },
+                },
+                crate::TargetFeatures::Unknown,
+            );
+            for expr in exprs {
+                let spec = crate::TargetSpec::new(expr).expect("expression parsed");
+                assert_eq!(
+                    spec.eval(&heuristic),
+                    spec.eval(&builtin),
+                    "evaluating {expr} for {triple_str}"
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,492 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::git::GitRepo;
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{Parser, ValueEnum};
+use color_eyre::eyre::{Result, WrapErr};
+use determinator::{
+    rules::DeterminatorRules, Determinator, DeterminatorSet, ShardWeights, Shards, Utf8Paths0,
+};
+use guppy::{
+    graph::{DependencyDirection, PackageGraph, PackageSet, PackageSource},
+    CargoMetadata, MetadataCommand,
+};
+use serde_json::json;
+use std::io::Read;
+
+/// Figure out which workspace packages changed between two revisions.
+///
+/// For more about cargo-determinator, see <https://docs.rs/cargo-determinator>.
+#[derive(Debug, Parser)]
+#[clap(author, version, about)]
+pub struct Args {
+    /// Git revision to compare against
+    #[clap(
+        long,
+        value_name = "REV",
+        required_unless_present = "old_metadata",
+        conflicts_with_all = ["old_metadata", "changed_paths"],
+    )]
+    base: Option<String>,
+
+    /// Git revision with changes [default: the working directory]
+    #[clap(long, value_name = "REV", requires = "base")]
+    head: Option<String>,
+
+    /// `cargo metadata` JSON output for the old workspace
+    #[clap(long, value_name = "PATH", requires = "new_metadata")]
+    old_metadata: Option<Utf8PathBuf>,
+
+    /// `cargo metadata` JSON output for the new workspace
+    #[clap(long, value_name = "PATH", requires = "old_metadata")]
+    new_metadata: Option<Utf8PathBuf>,
+
+    /// Null-separated list of changed paths, relative to the workspace root, or - for stdin
+    #[clap(long, value_name = "PATH", requires = "old_metadata")]
+    changed_paths: Option<Utf8PathBuf>,
+
+    /// Path to determinator rules
+    #[clap(long, value_name = "PATH")]
+    rules: Option<Utf8PathBuf>,
+
+    /// Split affected packages into this many shards
+    #[clap(long, value_name = "COUNT", value_parser = clap::value_parser!(u32).range(1..))]
+    shards: Option<u32>,
+
+    /// JSON file with per-package weights used to balance shards
+    #[clap(long, value_name = "PATH", requires = "shards")]
+    weights: Option<Utf8PathBuf>,
+
+    #[clap(flatten)]
+    cargo_opts: CargoOpts,
+
+    /// Output format
+    #[clap(long, value_enum, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+}
+
+/// Options passed through to `cargo metadata`, used with --base.
+#[derive(Clone, Debug, Default, Parser)]
+pub(crate) struct CargoOpts {
+    /// Path to Cargo.toml, used with --base
+    #[clap(long, value_name = "PATH")]
+    pub(crate) manifest_path: Option<Utf8PathBuf>,
+
+    /// Require Cargo.lock to be up-to-date
+    #[clap(long)]
+    locked: bool,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require Cargo.lock to be up-to-date, and run without accessing the network
+    #[clap(long)]
+    frozen: bool,
+}
+
+impl CargoOpts {
+    /// Returns a `cargo metadata` command for the current workspace, with these options applied.
+    fn metadata_command(&self) -> MetadataCommand {
+        let mut command = MetadataCommand::new();
+        if let Some(manifest_path) = &self.manifest_path {
+            command.manifest_path(manifest_path);
+        }
+        command.other_options(self.flags());
+        command
+    }
+
+    /// Returns the flags to pass to Cargo, other than the manifest path.
+    pub(crate) fn flags(&self) -> Vec<&'static str> {
+        let mut flags = vec![];
+        if self.locked {
+            flags.push("--locked");
+        }
+        if self.offline {
+            flags.push("--offline");
+        }
+        if self.frozen {
+            flags.push("--frozen");
+        }
+        flags
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum MessageFormat {
+    /// Print the names of affected packages, one per line (or one shard per line)
+    Human,
+    /// Print a JSON object with details about changed and affected packages
+    Json,
+}
+
+impl Args {
+    /// Executes the command.
+    pub fn exec(self) -> Result<()> {
+        println!("{}", self.output()?);
+        Ok(())
+    }
+
+    /// Computes the affected set and returns the output to print.
+    fn output(&self) -> Result<String> {
+        let Inputs {
+            old,
+            new,
+            changed_paths,
+            manifests,
+        } = match &self.base {
+            Some(base) => self.load_git(base)?,
+            None => self.load_metadata()?,
+        };
+
+        let rules = match &self.rules {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .wrap_err_with(|| format!("error reading rules from {}", path))?;
+                DeterminatorRules::parse(&contents)
+                    .wrap_err_with(|| format!("error parsing rules from {}", path))?
+            }
+            None => DeterminatorRules::default(),
+        };
+
+        let mut determinator = Determinator::new(&old, &new);
+        determinator
+            .set_rules(&rules)
+            .wrap_err("error applying determinator rules")?
+            .add_changed_paths(&changed_paths);
+        if let Some((old_manifest, new_manifest)) = &manifests {
+            determinator.set_workspace_manifests(old_manifest, new_manifest);
+        }
+        let determinator_set = determinator.compute();
+        let shards = match self.shards {
+            Some(count) => Some(determinator_set.shards(count as usize, &self.read_weights()?)),
+            None => None,
+        };
+
+        match (self.message_format, shards) {
+            (MessageFormat::Human, None) => {
+                Ok(sorted_names(&determinator_set.affected_set).join("\n"))
+            }
+            (MessageFormat::Human, Some(shards)) => Ok(shards
+                .shards
+                .iter()
+                .map(|shard| shard.packages.join(" "))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            (MessageFormat::Json, shards) => to_json(&determinator_set, shards.as_ref(), &new),
+        }
+    }
+
+    fn read_weights(&self) -> Result<ShardWeights> {
+        match &self.weights {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .wrap_err_with(|| format!("error reading weights from {}", path))?;
+                serde_json::from_str(&contents)
+                    .wrap_err_with(|| format!("error parsing weights from {}", path))
+            }
+            None => Ok(ShardWeights::default()),
+        }
+    }
+
+    fn load_git(&self, base: &str) -> Result<Inputs> {
+        let current = self
+            .cargo_opts
+            .metadata_command()
+            .build_graph()
+            .wrap_err("building package graph failed")?;
+        let repo = GitRepo::new(current.workspace().root())?;
+
+        let old_checkout = repo.checkout(base)?;
+        let old = old_checkout.package_graph(&self.cargo_opts)?;
+        let old_manifest = old_checkout.read_manifest()?;
+        let (new, new_manifest) = match &self.head {
+            Some(head) => {
+                let new_checkout = repo.checkout(head)?;
+                (
+                    new_checkout.package_graph(&self.cargo_opts)?,
+                    new_checkout.read_manifest()?,
+                )
+            }
+            None => {
+                let manifest_path = current.workspace().root().join("Cargo.toml");
+                let manifest = std::fs::read_to_string(&manifest_path)
+                    .wrap_err_with(|| format!("error reading {}", manifest_path))?;
+                (current, manifest)
+            }
+        };
+
+        // Also look for changes to path dependencies outside the workspace.
+        let mut external_dirs: Vec<_> = new
+            .packages()
+            .filter_map(|package| match package.source() {
+                PackageSource::Path(path) if path.starts_with("..") => Some(path),
+                _ => None,
+            })
+            .collect();
+        external_dirs.sort_unstable();
+        external_dirs.dedup();
+        let changed_paths = repo.changed_paths(base, self.head.as_deref(), &external_dirs)?;
+
+        Ok(Inputs {
+            old,
+            new,
+            changed_paths,
+            manifests: Some((old_manifest, new_manifest)),
+        })
+    }
+
+    fn load_metadata(&self) -> Result<Inputs> {
+        let old = read_metadata(self.old_metadata.as_deref().expect("required by clap"))?;
+        let new = read_metadata(self.new_metadata.as_deref().expect("required by clap"))?;
+
+        let changed_paths = match &self.changed_paths {
+            Some(path) => {
+                let mut buf = vec![];
+                if path == "-" {
+                    std::io::stdin()
+                        .read_to_end(&mut buf)
+                        .wrap_err("error reading changed paths from stdin")?;
+                } else {
+                    buf = std::fs::read(path)
+                        .wrap_err_with(|| format!("error reading changed paths from {}", path))?;
+                }
+                Utf8Paths0::from_bytes(buf)
+                    .map_err(|(_, err)| err)
+                    .wrap_err("changed paths are invalid UTF-8")?
+            }
+            None => Utf8Paths0::new(String::new()),
+        };
+        Ok(Inputs {
+            old,
+            new,
+            changed_paths,
+            manifests: None,
+        })
+    }
+}
+
+/// The old and new workspaces to compare.
+struct Inputs {
+    old: PackageGraph,
+    new: PackageGraph,
+    changed_paths: Utf8Paths0,
+    /// The old and new contents of the root `Cargo.toml`, if available.
+    manifests: Option<(String, String)>,
+}
+
+fn read_metadata(path: &Utf8Path) -> Result<PackageGraph> {
+    let json = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("error reading metadata from {}", path))?;
+    CargoMetadata::parse_json(json)
+        .and_then(|metadata| metadata.build_graph())
+        .wrap_err_with(|| format!("error building package graph from {}", path))
+}
+
+fn sorted_names<'g>(package_set: &PackageSet<'g>) -> Vec<&'g str> {
+    let mut names: Vec<_> = package_set
+        .packages(DependencyDirection::Forward)
+        .map(|package| package.name())
+        .collect();
+    names.sort_unstable();
+    names
+}
+
+fn to_json<'g>(
+    determinator_set: &DeterminatorSet<'g>,
+    shards: Option<&Shards>,
+    new: &'g PackageGraph,
+) -> Result<String> {
+    let mut value = json!({
+        "path-changed": sorted_names(&determinator_set.path_changed_set),
+        "summary-changed": sorted_names(&determinator_set.summary_changed_set),
+        "affected": sorted_names(&determinator_set.affected_set),
+        "reasons": determinator_set.to_summary(new).affected,
+    });
+    if let Some(shards) = shards {
+        value["shards"] = json!(shards.shards);
+    }
+    serde_json::to_string_pretty(&value).wrap_err("error serializing output")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_json() {
+        let fixtures = Utf8Path::new(env!("CARGO_MANIFEST_DIR")).join("../../fixtures/guppy");
+        let changed_paths = tempfile::NamedTempFile::new().expect("temp file created");
+        std::fs::write(changed_paths.path(), "fixtures/src/details.rs\0README.md\0")
+            .expect("changed paths written");
+
+        let args = Args::try_parse_from([
+            "cargo-determinator",
+            "--old-metadata",
+            fixtures.join("metadata_guppy_869476c.json").as_str(),
+            "--new-metadata",
+            fixtures.join("metadata_guppy_c9b4f76.json").as_str(),
+            "--changed-paths",
+            changed_paths.path().to_str().expect("temp path is UTF-8"),
+            "--message-format",
+            "json",
+        ])
+        .expect("args parsed");
+        let output: serde_json::Value =
+            serde_json::from_str(&args.output().expect("output computed")).expect("valid JSON");
+
+        assert_eq!(output["path-changed"], json!(["fixtures"]));
+        assert_eq!(output["summary-changed"], json!([]));
+        assert_eq!(
+            output["affected"],
+            json!(["fixture-manager", "fixtures", "guppy"])
+        );
+        assert_eq!(output["reasons"]["fixtures"]["reason"], "path-changed");
+    }
+
+    #[test]
+    fn metadata_shards() {
+        let fixtures = Utf8Path::new(env!("CARGO_MANIFEST_DIR")).join("../../fixtures/guppy");
+        let changed_paths = tempfile::NamedTempFile::new().expect("temp file created");
+        std::fs::write(changed_paths.path(), "fixtures/src/details.rs\0")
+            .expect("changed paths written");
+        let weights = tempfile::NamedTempFile::new().expect("temp file created");
+        std::fs::write(
+            weights.path(),
+            r#"{"default-weight": 1, "weights": {"guppy": 10}}"#,
+        )
+        .expect("weights written");
+
+        let args = Args::try_parse_from([
+            "cargo-determinator",
+            "--old-metadata",
+            fixtures.join("metadata_guppy_869476c.json").as_str(),
+            "--new-metadata",
+            fixtures.join("metadata_guppy_c9b4f76.json").as_str(),
+            "--changed-paths",
+            changed_paths.path().to_str().expect("temp path is UTF-8"),
+            "--shards",
+            "2",
+            "--weights",
+            weights.path().to_str().expect("temp path is UTF-8"),
+        ])
+        .expect("args parsed");
+        assert_eq!(
+            args.output().expect("output computed"),
+            "guppy\nfixture-manager fixtures",
+        );
+
+        Args::try_parse_from(["cargo-determinator", "--base", "main", "--shards", "0"])
+            .expect_err("shard count must be positive");
+    }
+
+    #[test]
+    fn git_base() {
+        let dir = tempfile::TempDir::new().expect("temp dir created");
+        let root = Utf8Path::from_path(dir.path())
+            .expect("temp path is UTF-8")
+            .canonicalize_utf8()
+            .expect("temp dir canonicalized");
+        git(&root, &["init", "-q"]);
+        commit_files(
+            &root,
+            &[
+                (
+                    "Cargo.toml",
+                    "[workspace]\nmembers = [\"crates/*\"]\nresolver = \"2\"\n",
+                ),
+                (
+                    "crates/a/Cargo.toml",
+                    "[package]\nname = \"a\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+                ),
+                ("crates/a/src/lib.rs", "pub fn a() {}\n"),
+                (
+                    "crates/b/Cargo.toml",
+                    "[package]\nname = \"b\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+                     [dependencies]\na = { path = \"../a\" }\n",
+                ),
+                ("crates/b/src/lib.rs", "pub fn b() {}\n"),
+                ("README.md", "A workspace.\n"),
+            ],
+        );
+        commit_files(&root, &[("crates/b/src/lib.rs", "pub fn b() { a::a() }\n")]);
+        // Uncommitted changes in the working directory are compared against --base.
+        std::fs::write(root.join("crates/a/src/lib.rs"), "pub fn a() { }\n")
+            .expect("lib.rs written");
+
+        let manifest_path = root.join("crates/b/Cargo.toml");
+        let output = |extra_args: &[&str]| -> serde_json::Value {
+            let args = Args::try_parse_from(
+                [
+                    "cargo-determinator",
+                    "--manifest-path",
+                    manifest_path.as_str(),
+                    "--offline",
+                    "--message-format",
+                    "json",
+                ]
+                .iter()
+                .chain(extra_args),
+            )
+            .expect("args parsed");
+            serde_json::from_str(&args.output().expect("output computed")).expect("valid JSON")
+        };
+
+        let output_head = output(&["--base", "HEAD"]);
+        assert_eq!(output_head["path-changed"], json!(["a"]));
+        assert_eq!(output_head["affected"], json!(["a", "b"]));
+
+        let output_revs = output(&["--base", "HEAD~", "--head", "HEAD"]);
+        assert_eq!(output_revs["path-changed"], json!(["b"]));
+        assert_eq!(output_revs["affected"], json!(["b"]));
+
+        let args = Args::try_parse_from([
+            "cargo-determinator",
+            "--manifest-path",
+            manifest_path.as_str(),
+            "--base",
+            "no-such-rev",
+        ])
+        .expect("args parsed");
+        let err = args.output().expect_err("revision doesn't exist");
+        assert!(
+            err.to_string().contains("no-such-rev"),
+            "unexpected error: {err}"
+        );
+    }
+
+    /// Writes out `files` in the git repository at `root` and commits them.
+    fn commit_files(root: &Utf8Path, files: &[(&str, &str)]) {
+        for (path, contents) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().expect("path has a parent"))
+                .expect("created dir");
+            std::fs::write(&path, contents).expect("wrote file");
+        }
+        git(root, &["add", "-A"]);
+        git(
+            root,
+            &[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "-c",
+                "commit.gpgsign=false",
+                "commit",
+                "-q",
+                "-m",
+                "commit",
+            ],
+        );
+    }
+
+    fn git(root: &Utf8Path, args: &[&str]) {
+        duct::cmd("git", args)
+            .dir(root)
+            .stdout_null()
+            .run()
+            .expect("git command succeeded");
+    }
+}
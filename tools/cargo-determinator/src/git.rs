@@ -0,0 +1,210 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Helpers for inspecting the workspace at other git revisions.
+
+use crate::command::CargoOpts;
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use determinator::Utf8Paths0;
+use guppy::{graph::PackageGraph, MetadataCommand};
+use std::fs;
+use tempfile::TempDir;
+
+/// The git repository containing the workspace.
+#[derive(Clone, Debug)]
+pub(crate) struct GitRepo {
+    toplevel: Utf8PathBuf,
+    workspace_root: Utf8PathBuf,
+    /// The path of the workspace root, relative to the root of the repository.
+    prefix: Utf8PathBuf,
+}
+
+impl GitRepo {
+    /// Finds the git repository containing the workspace.
+    pub(crate) fn new(workspace_root: &Utf8Path) -> Result<Self> {
+        let output = duct::cmd!("git", "rev-parse", "--show-toplevel", "--show-prefix")
+            .dir(workspace_root)
+            .read()
+            .wrap_err("error finding git repository for workspace")?;
+        let mut lines = output.lines();
+        let toplevel = Utf8Path::new(lines.next().unwrap_or_default())
+            .canonicalize_utf8()
+            .wrap_err("error canonicalizing git repository root")?;
+        // The prefix is empty (and git may omit the line) if the workspace is at the top level.
+        let prefix = lines.next().unwrap_or_default();
+
+        Ok(Self {
+            toplevel,
+            workspace_root: workspace_root.to_path_buf(),
+            prefix: prefix.trim().into(),
+        })
+    }
+
+    /// Returns the paths, relative to the workspace root, of files that changed between `base` and
+    /// `head`.
+    ///
+    /// If `head` is `None`, the working tree is compared against `base`. Files outside the
+    /// workspace are skipped, other than those in `external_dirs`. These are directories relative
+    /// to the workspace root, such as path dependencies outside the workspace; directories outside
+    /// the repository are skipped.
+    pub(crate) fn changed_paths(
+        &self,
+        base: &str,
+        head: Option<&str>,
+        external_dirs: &[&Utf8Path],
+    ) -> Result<Utf8Paths0> {
+        let mut paths = self.changed_paths_in(&self.workspace_root, base, head)?;
+
+        for dir in external_dirs {
+            let abs_dir = self.workspace_root.join(dir);
+            let in_repo = abs_dir
+                .canonicalize_utf8()
+                .is_ok_and(|abs_dir| abs_dir.starts_with(&self.toplevel));
+            if !in_repo {
+                continue;
+            }
+            // Paths are listed relative to the directory, so prefix them with it.
+            let dir_paths = self.changed_paths_in(&abs_dir, base, head)?;
+            for path in dir_paths.split(|&b| b == 0).filter(|path| !path.is_empty()) {
+                paths.extend_from_slice(dir.as_str().as_bytes());
+                paths.push(b'/');
+                paths.extend_from_slice(path);
+                paths.push(0);
+            }
+        }
+
+        Utf8Paths0::from_bytes(paths).map_err(|(_, err)| eyre!("changed paths: {}", err))
+    }
+
+    /// Returns the null-separated paths, relative to `dir`, of files in `dir` that changed.
+    fn changed_paths_in(&self, dir: &Utf8Path, base: &str, head: Option<&str>) -> Result<Vec<u8>> {
+        let mut args = vec!["diff", "-z", "--name-only", "--relative", base];
+        args.extend(head);
+        Ok(duct::cmd("git", args)
+            .dir(dir)
+            .stdout_capture()
+            .run()
+            .wrap_err_with(|| format!("error listing changed files in {}", dir))?
+            .stdout)
+    }
+
+    /// Checks out the files needed to build the package graph at `rev` into a temporary
+    /// directory.
+    ///
+    /// Rather than copying the whole tree, only files that Cargo reads are written out: manifests,
+    /// lockfiles and Cargo configuration. Other Rust source files are created empty, so that Cargo
+    /// discovers the same targets as in a full checkout.
+    pub(crate) fn checkout(&self, rev: &str) -> Result<Checkout> {
+        let dir = TempDir::new().wrap_err("error creating temporary directory")?;
+        let dir_path = Utf8Path::from_path(dir.path())
+            .ok_or_else(|| eyre!("temporary directory path is invalid UTF-8"))?
+            .canonicalize_utf8()
+            .wrap_err("error canonicalizing temporary directory")?;
+
+        let files = duct::cmd!("git", "ls-tree", "-r", "--name-only", "-z", rev)
+            .dir(&self.toplevel)
+            .read()
+            .wrap_err_with(|| format!("error listing files at {}", rev))?;
+        for path in files.split('\0').filter(|path| !path.is_empty()) {
+            let path = Utf8Path::new(path);
+            let contents = if is_cargo_input(path) {
+                duct::cmd!("git", "show", format!("{}:{}", rev, path))
+                    .dir(&self.toplevel)
+                    .stdout_capture()
+                    .run()
+                    .wrap_err_with(|| format!("error reading {} at {}", path, rev))?
+                    .stdout
+            } else if path.extension() == Some("rs") {
+                vec![]
+            } else {
+                continue;
+            };
+            let out_path = dir_path.join(path);
+            if let Some(parent) = out_path.parent() {
+                fs::create_dir_all(parent)
+                    .wrap_err_with(|| format!("error creating directory {}", parent))?;
+            }
+            fs::write(&out_path, contents)
+                .wrap_err_with(|| format!("error writing {}", out_path))?;
+        }
+
+        let workspace_root = dir_path.join(&self.prefix);
+        if !workspace_root.join("Cargo.toml").exists() {
+            bail!("workspace not found at {}", rev);
+        }
+        // If Cargo.lock isn't checked in, start from the current lockfile so that the same
+        // dependency versions are picked where possible. This can differ from the versions that
+        // were actually used at the time, so warn about it.
+        let lockfile = workspace_root.join("Cargo.lock");
+        let current_lockfile = self.workspace_root.join("Cargo.lock");
+        if !lockfile.exists() && current_lockfile.exists() {
+            eprintln!(
+                "warning: Cargo.lock not found at {}, using the current Cargo.lock instead",
+                rev,
+            );
+            fs::copy(&current_lockfile, &lockfile).wrap_err("error copying Cargo.lock")?;
+        }
+
+        Ok(Checkout {
+            _dir: dir,
+            source_toplevel: self.toplevel.clone(),
+            toplevel: dir_path,
+            workspace_root,
+        })
+    }
+}
+
+/// Returns true if `path` is a file that's read while building the package graph.
+fn is_cargo_input(path: &Utf8Path) -> bool {
+    let file_name = path.file_name().unwrap_or_default();
+    let parent_name = path
+        .parent()
+        .and_then(|parent| parent.file_name())
+        .unwrap_or_default();
+    matches!(file_name, "Cargo.toml" | "Cargo.lock")
+        || (parent_name == ".cargo" && matches!(file_name, "config" | "config.toml"))
+}
+
+/// A copy of the repository at a git revision, in a temporary directory.
+#[derive(Debug)]
+pub(crate) struct Checkout {
+    // Held to delete the temporary directory on drop.
+    _dir: TempDir,
+    /// The root of the repository this was checked out from.
+    source_toplevel: Utf8PathBuf,
+    toplevel: Utf8PathBuf,
+    workspace_root: Utf8PathBuf,
+}
+
+impl Checkout {
+    /// Builds the package graph for the checked out workspace, with `cargo_opts` applied.
+    ///
+    /// If a manifest path was passed in, the manifest at the same path within the checkout is
+    /// used.
+    pub(crate) fn package_graph(&self, cargo_opts: &CargoOpts) -> Result<PackageGraph> {
+        let mut command = MetadataCommand::new();
+        command
+            .current_dir(&self.workspace_root)
+            .other_options(cargo_opts.flags());
+        if let Some(manifest_path) = &cargo_opts.manifest_path {
+            let manifest_path = manifest_path
+                .canonicalize_utf8()
+                .wrap_err_with(|| format!("error canonicalizing {}", manifest_path))?;
+            let rel_path = manifest_path
+                .strip_prefix(&self.source_toplevel)
+                .wrap_err_with(|| format!("{} isn't inside the git repository", manifest_path))?;
+            command.manifest_path(self.toplevel.join(rel_path));
+        }
+        command
+            .build_graph()
+            .wrap_err("building package graph failed")
+    }
+
+    /// Reads the root `Cargo.toml` of the checked out workspace.
+    pub(crate) fn read_manifest(&self) -> Result<String> {
+        let manifest_path = self.workspace_root.join("Cargo.toml");
+        fs::read_to_string(&manifest_path)
+            .wrap_err_with(|| format!("error reading {}", manifest_path))
+    }
+}
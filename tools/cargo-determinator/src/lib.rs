@@ -0,0 +1,99 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! `cargo determinator` is a command-line interface to the
+//! [determinator](https://docs.rs/determinator): it figures out which packages in a Rust workspace
+//! changed between two revisions, so that CI only needs to build and test those packages.
+//!
+//! # Installation
+//!
+//! Install `cargo determinator` with:
+//!
+//! ```sh
+//! cargo install cargo-determinator --locked
+//! ```
+//!
+//! # Usage
+//!
+//! Within a Git repository, compare the working directory against a base revision with:
+//!
+//! ```sh
+//! cargo determinator --base origin/main
+//! ```
+//!
+//! This prints out the names of the affected workspace packages, one per line. To compare two
+//! revisions, also pass in `--head`:
+//!
+//! ```sh
+//! cargo determinator --base origin/main --head HEAD
+//! ```
+//!
+//! The manifests and Cargo configuration of the old workspace are checked out into a temporary
+//! directory to run `cargo metadata` on it. If `Cargo.lock` isn't checked in, the current lockfile
+//! is used for the old workspace, and a warning is printed. `--manifest-path`, `--locked`,
+//! `--offline` and `--frozen` are passed through to `cargo metadata`.
+//!
+//! Changed files in path dependencies outside the workspace, such as `../common-protos`, are also
+//! considered if they're in the same Git repository as the workspace.
+//!
+//! Changes to the root `Cargo.toml` are classified by section, so that for example editing the
+//! member list doesn't cause everything to be rebuilt. To configure this, see the
+//! `[workspace-manifest]` section of the rules file.
+//!
+//! ## Without Git
+//!
+//! If `cargo metadata` output for both revisions is already available, for example from build
+//! artifacts, pass it in with `--old-metadata` and `--new-metadata`. Changed paths may be passed
+//! in through `--changed-paths`, in the null-separated format produced by `git diff -z
+//! --name-only`. Use `-` to read them from standard input.
+//!
+//! ```sh
+//! cargo determinator --old-metadata old.json --new-metadata new.json --changed-paths paths.txt
+//! ```
+//!
+//! ## Rules
+//!
+//! To customize how changed paths and packages are processed, pass in a [determinator rules
+//! file](https://docs.rs/determinator/latest/determinator/rules) with `--rules`.
+//!
+//! ## Sharding
+//!
+//! To split the affected packages across parallel CI jobs, pass in the number of jobs with
+//! `--shards`. Each line of output then lists the packages for one shard, separated by spaces.
+//!
+//! Shards are balanced using per-package weights, such as historical build and test durations,
+//! passed in as a JSON file with `--weights`:
+//!
+//! ```json
+//! {
+//!     "default-weight": 60,
+//!     "weights": {
+//!         "guppy": 300,
+//!         "determinator": 45
+//!     }
+//! }
+//! ```
+//!
+//! Packages without a weight are assigned `default-weight`, or the average of the other weights
+//! if it isn't specified. Without `--weights`, every package has the same weight.
+//!
+//! ## JSON output
+//!
+//! With `--message-format json`, a single JSON object is printed to standard output, containing:
+//!
+//! * `path-changed`: packages marked changed because of changed paths
+//! * `summary-changed`: packages marked changed because their simulated builds changed
+//! * `affected`: every package affected by the change, including the above
+//! * `reasons`: why each affected package was selected, as returned by
+//!   [`DeterminatorSet::to_summary`](https://docs.rs/determinator/latest/determinator/struct.DeterminatorSet.html#method.to_summary)
+//! * `shards`: with `--shards`, a list of shards, each with the `packages` in it and their total
+//!   `weight`
+//!
+//! All package lists are sorted by name.
+
+#![warn(missing_docs)]
+
+mod command;
+mod git;
+
+pub use command::Args;
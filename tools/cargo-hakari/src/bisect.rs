@@ -0,0 +1,266 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    diff::{caused_by, compute_lines},
+    git::GitRepo,
+    message,
+    output::OutputContext,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{bail, Result, WrapErr};
+use guppy::graph::PackageGraph;
+use hakari::summaries::{DEFAULT_CONFIG_PATH, FALLBACK_CONFIG_PATH};
+use log::{info, warn};
+use owo_colors::OwoColorize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Finds the first revision where `dep_name` is present in the workspace-hack.
+///
+/// The revisions searched are either `revs`, or the first-parent history of `HEAD` after `since`.
+/// This assumes that once the dependency appears, it stays in the workspace-hack, so revisions
+/// are searched with a binary search.
+pub(crate) fn bisect(
+    package_graph: &PackageGraph,
+    dep_name: &str,
+    since: Option<&str>,
+    revs: &[String],
+    output: OutputContext,
+) -> Result<i32> {
+    let repo = GitRepo::new(package_graph.workspace().root())?;
+    let current = DependencyUse::find(package_graph, repo.toplevel(), dep_name, &output)?;
+    let Some(current) = current else {
+        bail!("dependency {} not found in the workspace-hack", dep_name);
+    };
+
+    let commits = if revs.is_empty() {
+        let mut commits = vec![];
+        if let Some(since) = since {
+            commits.push(repo.resolve(since)?);
+        }
+        commits.extend(repo.first_parent_history(since)?);
+        commits
+    } else {
+        revs.iter()
+            .map(|rev| repo.resolve(rev))
+            .collect::<Result<Vec<_>>>()?
+    };
+    if commits.is_empty() {
+        bail!("no revisions to search");
+    }
+
+    if probe(&repo, &commits[0], dep_name, &output)?.is_some() {
+        bail!(
+            "dependency {} is already present at the oldest revision searched ({}), \
+             pass in an older revision with --since or --rev",
+            dep_name,
+            repo.describe(&commits[0])?,
+        );
+    }
+
+    // Invariant: the dependency is absent at `lo` and present at `hi`, where `hi ==
+    // commits.len()` represents the working tree.
+    let mut lo = 0;
+    let mut hi = commits.len();
+    let mut found = current;
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        match probe(&repo, &commits[mid], dep_name, &output)? {
+            Some(dependency_use) => {
+                hi = mid;
+                found = dependency_use;
+            }
+            None => lo = mid,
+        }
+    }
+
+    let introduced_in = commits.get(hi).map(|commit| commit.as_str());
+    let changed_files: BTreeSet<_> = repo
+        .changed_files(&commits[lo], introduced_in)?
+        .into_iter()
+        .collect();
+    let responsible: Vec<_> = found
+        .caused_by
+        .iter()
+        .filter(|name| {
+            found
+                .manifests
+                .get(*name)
+                .is_some_and(|manifest| changed_files.contains(manifest))
+        })
+        .cloned()
+        .collect();
+    let caused_by: Vec<_> = found.caused_by.into_iter().collect();
+
+    let description = match introduced_in {
+        Some(commit) => Some(repo.describe(commit)?),
+        None => None,
+    };
+    if output.is_json() {
+        message::emit(message::bisect(
+            dep_name,
+            introduced_in,
+            description.as_deref(),
+            &responsible,
+            &caused_by,
+        ))?;
+        return Ok(0);
+    }
+
+    match &description {
+        Some(description) => info!(
+            "{} first appeared in the workspace-hack in {}",
+            dep_name.style(output.styles.package_name),
+            description,
+        ),
+        None => info!(
+            "{} was added to the workspace-hack by uncommitted changes",
+            dep_name.style(output.styles.package_name),
+        ),
+    }
+    if responsible.is_empty() {
+        info!(
+            "no manifests of crates depending on it changed; it is depended on by: {}",
+            caused_by.join(", "),
+        );
+    } else {
+        info!(
+            "caused by changes to the manifests of: {}",
+            responsible
+                .iter()
+                .map(|name| name.style(output.styles.package_name).to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+    }
+
+    Ok(0)
+}
+
+/// Finds how `dep_name` is used by the workspace-hack at `commit`.
+///
+/// Revisions where Hakari isn't configured (e.g. ones from before it was set up) are treated as
+/// not having the dependency. Other errors are returned.
+fn probe(
+    repo: &GitRepo,
+    commit: &str,
+    dep_name: &str,
+    output: &OutputContext,
+) -> Result<Option<DependencyUse>> {
+    info!(
+        "{} {}",
+        "checking".style(output.styles.command),
+        repo.describe(commit)?,
+    );
+    let checkout = repo.checkout(commit)?;
+    let package_graph = checkout
+        .package_graph(output)
+        .wrap_err_with(|| format!("error reading workspace at {}", commit))?;
+    let workspace_root = package_graph.workspace().root();
+    let is_configured = [DEFAULT_CONFIG_PATH, FALLBACK_CONFIG_PATH]
+        .iter()
+        .any(|config_path| workspace_root.join(config_path).is_file());
+    if !is_configured {
+        warn!(
+            "Hakari isn't configured at {}, treating dependency as absent",
+            commit,
+        );
+        return Ok(None);
+    }
+    DependencyUse::find(&package_graph, checkout.toplevel(), dep_name, output)
+        .wrap_err_with(|| format!("error computing workspace-hack at {}", commit))
+}
+
+/// How a dependency is used by the workspace, at a single revision.
+#[derive(Debug)]
+struct DependencyUse {
+    /// The workspace packages that cause the dependency to be in the workspace-hack.
+    caused_by: BTreeSet<String>,
+    /// Paths of workspace package manifests, relative to the repository root.
+    manifests: BTreeMap<String, Utf8PathBuf>,
+}
+
+impl DependencyUse {
+    /// Returns `None` if `dep_name` isn't in the workspace-hack.
+    fn find(
+        package_graph: &PackageGraph,
+        toplevel: &Utf8Path,
+        dep_name: &str,
+        output: &OutputContext,
+    ) -> Result<Option<Self>> {
+        let mut found = false;
+        let mut caused_by_names = BTreeSet::new();
+        for (hakari, lines) in compute_lines(package_graph, output)?.values() {
+            for (key, (package, _)) in lines {
+                if key.name == dep_name {
+                    found = true;
+                    caused_by_names.extend(caused_by(hakari, *package, key.build_platform, &[]));
+                }
+            }
+        }
+        if !found {
+            return Ok(None);
+        }
+
+        let manifests = package_graph
+            .workspace()
+            .iter()
+            .map(|package| {
+                let manifest = package
+                    .manifest_path()
+                    .canonicalize_utf8()
+                    .wrap_err_with(|| {
+                        format!("error canonicalizing {}", package.manifest_path())
+                    })?;
+                let rel_manifest = manifest.strip_prefix(toplevel).unwrap_or(&manifest);
+                Ok((package.name().to_owned(), rel_manifest.to_path_buf()))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Some(Self {
+            caused_by: caused_by_names,
+            manifests,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cargo_cli::CargoOpts,
+        git::tests::{commit_files, make_repo, WORKSPACE_FILES},
+    };
+
+    #[test]
+    fn probe_errors() {
+        let (_dir, root) = make_repo(&[("README.md", "No workspace yet.\n")]);
+        let repo = GitRepo::new(&root).expect("found git repository");
+        let output = OutputContext::new_test(CargoOpts::default());
+        let no_workspace = repo.resolve("HEAD").expect("resolved HEAD");
+
+        commit_files(&root, WORKSPACE_FILES);
+        let no_hakari = repo.resolve("HEAD").expect("resolved HEAD");
+
+        commit_files(&root, &[("crates/a/Cargo.toml", "[package\n")]);
+        let invalid_manifest = repo.resolve("HEAD").expect("resolved HEAD");
+
+        // A revision without Hakari doesn't have the dependency.
+        let dependency_use = probe(&repo, &no_hakari, "dep", &output).expect("probe succeeded");
+        assert!(dependency_use.is_none(), "dependency is absent");
+
+        // Failing to check out a revision or to read its workspace is an error.
+        let err = probe(&repo, &no_workspace, "dep", &output)
+            .expect_err("checkout fails without a workspace");
+        assert!(
+            err.to_string().contains("workspace not found"),
+            "unexpected error: {err}"
+        );
+        let err = probe(&repo, &invalid_manifest, "dep", &output)
+            .expect_err("reading workspace fails with an invalid manifest");
+        assert!(
+            err.to_string().contains("error reading workspace at"),
+            "unexpected error: {err}"
+        );
+    }
+}
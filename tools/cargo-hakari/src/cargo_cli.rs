@@ -5,8 +5,62 @@
 
 use crate::output::OutputContext;
 use camino::Utf8PathBuf;
+use clap::Parser;
+use guppy::MetadataCommand;
 use std::{convert::TryInto, env, path::PathBuf};
 
+/// Options passed through to every Cargo invocation.
+#[derive(Clone, Debug, Default, Parser)]
+pub(crate) struct CargoOpts {
+    /// Path to the Cargo.toml of the workspace to operate on
+    #[clap(long, global = true, value_name = "PATH")]
+    pub(crate) manifest_path: Option<Utf8PathBuf>,
+
+    /// Require Cargo.lock to be up-to-date
+    #[clap(long, global = true)]
+    pub(crate) locked: bool,
+
+    /// Run without accessing the network
+    #[clap(long, global = true)]
+    pub(crate) offline: bool,
+
+    /// Require Cargo.lock to be up-to-date, and run without accessing the network
+    #[clap(long, global = true)]
+    pub(crate) frozen: bool,
+}
+
+impl CargoOpts {
+    /// Returns a `cargo metadata` command for the workspace, with these options applied.
+    pub(crate) fn metadata_command(&self) -> MetadataCommand {
+        let mut command = MetadataCommand::new();
+        if let Some(manifest_path) = &self.manifest_path {
+            command.manifest_path(manifest_path);
+        }
+        command.other_options(self.flags());
+        command
+    }
+
+    /// Returns true if Cargo.lock is required to be up-to-date.
+    pub(crate) fn is_locked(&self) -> bool {
+        self.locked || self.frozen
+    }
+
+    /// Returns the flags to pass to Cargo, other than the manifest path.
+    pub(crate) fn flags(&self) -> Vec<&'static str> {
+        let mut flags = vec![];
+        if self.locked {
+            flags.push("--locked");
+        }
+        if self.offline {
+            flags.push("--offline");
+        }
+        if self.frozen {
+            flags.push("--frozen");
+        }
+        flags
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct CargoCli<'a> {
     cargo_path: Utf8PathBuf,
@@ -54,6 +108,12 @@ impl<'a> CargoCli<'a> {
 
         initial_args.push(self.command);
 
+        let cargo_opts = &self.output.cargo_opts;
+        if let Some(manifest_path) = &cargo_opts.manifest_path {
+            initial_args.extend(["--manifest-path", manifest_path.as_str()]);
+        }
+        initial_args.extend(cargo_opts.flags());
+
         duct::cmd(
             self.cargo_path.as_std_path(),
             initial_args.into_iter().chain(self.args.iter().copied()),
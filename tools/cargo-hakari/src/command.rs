@@ -2,26 +2,37 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    bisect::bisect,
+    cargo_cli::{CargoCli, CargoOpts},
+    config_command::{apply_overrides, ConfigCommand, ConfigOverride},
+    diff::diff_base,
+    doctor::doctor,
     helpers::{read_contents, regenerate_lockfile},
+    message,
     output::{OutputContext, OutputOpts},
-    publish::publish_hakari,
+    platform::PlatformCommand,
+    publish::{publish_hakari, PublishPackages},
+    stats::stats,
+    timings::Phase,
+    upgrade_format::upgrade_format,
+    watch::watch,
 };
 use camino::{Utf8Path, Utf8PathBuf};
-use clap::Parser;
+use clap::{CommandFactory, Parser, ValueEnum};
+use clap_complete::Shell;
 use color_eyre::eyre::{bail, eyre, Result, WrapErr};
-use guppy::{
-    graph::{PackageGraph, PackageSet},
-    MetadataCommand,
-};
+use guppy::graph::{cargo::BuildPlatform, PackageGraph, PackageSet};
 use hakari::{
     cli_ops::{HakariInit, WorkspaceOps},
-    diffy::PatchFormatter,
-    summaries::{HakariConfig, DEFAULT_CONFIG_PATH, FALLBACK_CONFIG_PATH},
-    DepFormatVersion, HakariBuilder, HakariCargoToml, HakariOutputOptions, TomlOutError,
+    diffy::{self, PatchFormatter},
+    explain::{ExplainFilter, HakariExplain, HakariExplainDisplay},
+    summaries::{migrate_config, HakariConfig, DEFAULT_CONFIG_PATH, FALLBACK_CONFIG_PATH},
+    verify::VerifyErrors,
+    DepFormatVersion, Hakari, HakariBuilder, HakariCargoToml, HakariOutputOptions, TomlOutError,
 };
 use log::{error, info};
 use owo_colors::OwoColorize;
-use std::convert::TryFrom;
+use std::{collections::BTreeMap, convert::TryFrom, fmt::Write};
 
 /// The comment to add to the top of the config file.
 pub static CONFIG_COMMENT: &str = r#"# This file contains settings for `cargo hakari`.
@@ -58,7 +69,8 @@ impl Args {
     ///
     /// Returns the exit status, or an error on failure.
     pub fn exec(self) -> Result<i32> {
-        self.command.exec(self.global.output)
+        self.command
+            .exec(self.global.output, self.global.cargo, self.global.config)
     }
 }
 
@@ -66,6 +78,16 @@ impl Args {
 struct GlobalOpts {
     #[clap(flatten)]
     output: OutputOpts,
+    #[clap(flatten)]
+    cargo: CargoOpts,
+
+    /// Override a key in hakari.toml for this invocation only, e.g.
+    /// `--config 'platforms=["x86_64-unknown-linux-gnu"]'`
+    ///
+    /// Keys and values are in the same format as `cargo hakari config set`. Can be passed in
+    /// several times; later overrides take precedence. The config on disk is not changed.
+    #[clap(long = "config", global = true, value_name = "KEY=VALUE")]
+    config: Vec<ConfigOverride>,
 }
 
 /// Manage workspace-hack crates.
@@ -75,12 +97,32 @@ enum Command {
     #[clap(name = "init")]
     Initialize {
         /// Path to generate the workspace-hack crate at, relative to the current directory.
-        path: Utf8PathBuf,
+        #[clap(required_unless_present = "adopt")]
+        path: Option<Utf8PathBuf>,
 
         /// The name of the crate (default: derived from path)
         #[clap(long, short)]
         package_name: Option<String>,
 
+        /// Adopt an existing workspace crate as the workspace-hack crate, rather than creating a
+        /// new one.
+        ///
+        /// The crate's dependency tables are replaced with a section managed by hakari. The crate
+        /// must be a library, and must not have features or depend on other workspace crates.
+        #[clap(long, value_name = "CRATE", conflicts_with_all = ["path", "package_name"])]
+        adopt: Option<String>,
+
+        /// The template to generate the workspace-hack crate from: either `builtin`, or a path to
+        /// a directory.
+        ///
+        /// Files in the directory are copied into the crate. In files ending with `.toml-in`,
+        /// `%PACKAGE_NAME%` is replaced with the name of the crate, and the extension is changed
+        /// to `.toml`. The directory must contain a `Cargo.toml-in` with `### BEGIN HAKARI
+        /// SECTION` and `### END HAKARI SECTION` lines. With `--adopt`, only files missing from
+        /// the crate are added.
+        #[clap(long, value_name = "builtin|PATH", default_value = "builtin")]
+        template: String,
+
         /// Skip writing a stub config to hakari.toml
         #[clap(long)]
         skip_config: bool,
@@ -93,44 +135,207 @@ enum Command {
         dry_run: bool,
 
         /// Proceed with the operation without prompting for confirmation.
-        #[clap(long, short, conflicts_with = "dry-run")]
+        #[clap(long, short, conflicts_with = "dry_run")]
         yes: bool,
     },
 
+    /// Manage the platforms the workspace-hack crate is generated for
+    #[clap(subcommand)]
+    Platform(PlatformCommand),
+
+    /// Inspect, edit and validate hakari.toml
+    #[clap(subcommand)]
+    Config(ConfigCommand),
+
+    /// Upgrade hakari.toml to the latest config version
+    ///
+    /// Options whose defaults differ between config versions are written out explicitly, so the
+    /// upgraded config produces the same output. Comments and formatting are preserved.
+    MigrateConfig {
+        /// Print a diff of the config, but do not write out any changes.
+        ///
+        /// Exits with status 1 if the config would change. Can be combined with `--quiet`.
+        #[clap(long, short = 'n')]
+        dry_run: bool,
+    },
+
+    /// Upgrade the workspace-hack to the latest dep-format-version
+    ///
+    /// Bumps `dep-format-version` in hakari.toml, regenerates the workspace-hack, and updates
+    /// workspace-hack dependency lines in workspace crates if their format changed. The changes to
+    /// all files are printed out as a single diff.
+    UpgradeFormat {
+        /// Print a diff of all changes, but do not write them out.
+        ///
+        /// Exits with status 1 if any files would change. Can be combined with `--quiet`.
+        #[clap(long, short = 'n')]
+        dry_run: bool,
+    },
+
+    /// Remove the workspace-hack from the workspace
+    ///
+    /// Removes the workspace-hack dependency from all workspace crates, removes the
+    /// workspace-hack crate from the workspace and deletes its directory, and deletes the Hakari
+    /// config.
+    Uninstall {
+        /// Print operations that need to be performed, but do not actually perform them.
+        ///
+        /// Exits with status 1 if any operations need to be performed. Can be combined with
+        /// `--quiet`.
+        #[clap(long, short = 'n', conflicts_with = "yes")]
+        dry_run: bool,
+
+        /// Proceed with the operation without prompting for confirmation.
+        #[clap(long, short, conflicts_with = "dry_run")]
+        yes: bool,
+    },
+
+    /// Watch workspace manifests, and regenerate the workspace-hack when they change
+    ///
+    /// Every time a workspace member's Cargo.toml, the workspace root's Cargo.toml or the Hakari
+    /// config changes, this runs the equivalent of `cargo hakari generate` and
+    /// `cargo hakari manage-deps --yes`. Runs until interrupted.
+    Watch {
+        /// Print diffs and operations that need to be performed on every change, but do not
+        /// actually perform them.
+        #[clap(long, short = 'n')]
+        dry_run: bool,
+    },
+
+    /// Show how the workspace-hack has changed since a git revision
+    ///
+    /// Computes the workspace-hack for the manifests at the base revision, and prints out the
+    /// dependencies and features that were added or removed since then, along with the workspace
+    /// crates that caused them. Uncommitted changes in the current workspace are included.
+    ///
+    /// Exits with status 1 if the workspace-hack changed.
+    Diff {
+        /// The git revision to compare against, e.g. `main` or `HEAD~1`.
+        #[clap(long)]
+        base: String,
+    },
+
+    /// Find the git revision that introduced a dependency into the workspace-hack
+    ///
+    /// Searches the first-parent history of HEAD, recomputing the workspace-hack at each revision
+    /// checked, and reports the first revision where the dependency is present along with the
+    /// workspace crates whose manifest changes were responsible. Revisions are searched with a
+    /// binary search, so this assumes that the dependency stayed in the workspace-hack after it
+    /// was introduced.
+    Bisect {
+        /// The name of the dependency to search for.
+        dep_name: String,
+
+        /// Only search revisions after this one. The dependency must not be present at this
+        /// revision.
+        #[clap(long, conflicts_with = "revs")]
+        since: Option<String>,
+
+        /// Search these revisions instead of the git history, oldest first. The dependency must
+        /// not be present at the first revision.
+        #[clap(long = "rev", value_name = "REV")]
+        revs: Vec<String>,
+    },
+
+    /// Check the workspace for common problems with its Hakari setup
+    ///
+    /// Looks for workspace crates that are missing the workspace-hack dependency, path
+    /// dependencies that aren't workspace members, an out-of-date Cargo.lock, registries unknown
+    /// to Hakari, a resolver version that differs from the workspace's, and an outdated
+    /// `dep-format-version`. Each problem is printed along with a suggested fix.
+    ///
+    /// Exits with status 1 if any problems were found.
+    Doctor,
+
+    /// Generate shell completions for cargo-hakari
+    ///
+    /// The completions are written to stdout, and are for the `cargo-hakari` executable.
+    Completions {
+        /// The shell to generate completions for
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+
     #[clap(flatten)]
     WithBuilder(CommandWithBuilder),
 }
 
 impl Command {
-    fn exec(self, output: OutputOpts) -> Result<i32> {
-        let output = output.init();
-        let metadata_command = MetadataCommand::new();
-        let package_graph = metadata_command
-            .build_graph()
+    fn exec(
+        self,
+        output: OutputOpts,
+        cargo_opts: CargoOpts,
+        config_overrides: Vec<ConfigOverride>,
+    ) -> Result<i32> {
+        let output = output.init(cargo_opts, config_overrides)?;
+        let result = self.exec_with_output(output.clone());
+        output.timings.report(&output)?;
+        result
+    }
+
+    fn exec_with_output(self, output: OutputContext) -> Result<i32> {
+        if let Command::Completions { shell } = self {
+            // Completions don't need a workspace, so generate them before building the graph.
+            clap_complete::generate(
+                shell,
+                &mut Args::command(),
+                "cargo-hakari",
+                &mut std::io::stdout(),
+            );
+            return Ok(0);
+        }
+        if let Command::Watch { dry_run } = self {
+            // The package graph is rebuilt every time a manifest changes.
+            return watch(dry_run, output);
+        }
+        if let Command::Doctor = self {
+            // The lockfile is checked before the package graph is built, since building the
+            // graph updates it.
+            return doctor(output);
+        }
+
+        let package_graph = output
+            .timings
+            .time(Phase::Metadata, || {
+                output.cargo_opts.metadata_command().build_graph()
+            })
             .context("building package graph failed")?;
 
         match self {
             Command::Initialize {
                 path,
                 package_name,
+                adopt,
+                template,
                 skip_config,
                 dry_run,
                 yes,
             } => {
-                let package_name = match package_name.as_deref() {
-                    Some(name) => name,
-                    None => match path.file_name() {
-                        Some(name) => name,
-                        None => bail!("invalid path {}", path),
-                    },
-                };
-
-                let workspace_path =
-                    cwd_rel_to_workspace_rel(&path, package_graph.workspace().root())?;
+                let workspace_path;
+                let mut init = match (&adopt, &path) {
+                    (Some(adopt), _) => HakariInit::adopt(&package_graph, adopt)
+                        .with_context(|| "error initializing Hakari package")?,
+                    (None, Some(path)) => {
+                        let package_name = match package_name.as_deref() {
+                            Some(name) => name,
+                            None => match path.file_name() {
+                                Some(name) => name,
+                                None => bail!("invalid path {}", path),
+                            },
+                        };
 
-                let mut init = HakariInit::new(&package_graph, package_name, &workspace_path)
-                    .with_context(|| "error initializing Hakari package")?;
+                        workspace_path =
+                            cwd_rel_to_workspace_rel(path, package_graph.workspace().root())?;
+                        HakariInit::new(&package_graph, package_name, &workspace_path)
+                            .with_context(|| "error initializing Hakari package")?
+                    }
+                    (None, None) => unreachable!("clap requires either path or --adopt"),
+                };
                 init.set_cargo_toml_comment(CARGO_TOML_COMMENT);
+                if template != "builtin" {
+                    init.set_template_dir(Utf8Path::new(&template))
+                        .with_context(|| "error initializing Hakari package")?;
+                }
                 if !skip_config {
                     init.set_config(DEFAULT_CONFIG_PATH.as_ref(), CONFIG_COMMENT)
                         .with_context(|| "error initializing Hakari package")?;
@@ -156,16 +361,85 @@ impl Command {
                     Ok(())
                 })
             }
+            Command::Platform(cmd) => cmd.exec(&package_graph, output),
+            Command::Config(cmd) => cmd.exec(&package_graph, output),
+            Command::Diff { base } => diff_base(&package_graph, &base, output),
+            Command::Bisect {
+                dep_name,
+                since,
+                revs,
+            } => bisect(&package_graph, &dep_name, since.as_deref(), &revs, output),
+            Command::Uninstall { dry_run, yes } => {
+                let (config_path, _) = read_config(&package_graph)?;
+                let rel_config_path = config_path
+                    .strip_prefix(package_graph.workspace().root())
+                    .expect("Hakari config is within the workspace root");
+                let (builder, _) =
+                    make_builder_and_output(&package_graph, &output.config_overrides)?;
+                let ops = builder
+                    .uninstall_ops([rel_config_path])
+                    .expect("hakari-package must be specified in hakari.toml");
+                apply_on_dialog(dry_run, yes, &ops, &output, || {
+                    regenerate_lockfile(output.clone())
+                })
+            }
+            Command::UpgradeFormat { dry_run } => upgrade_format(&package_graph, dry_run, output),
+            Command::Completions { .. } => unreachable!("completions are generated above"),
+            Command::Watch { .. } => unreachable!("watch mode is handled above"),
+            Command::Doctor => unreachable!("doctor is handled above"),
+            Command::MigrateConfig { dry_run } => {
+                let (config_path, contents) = read_config(&package_graph)?;
+                let new_contents = migrate_config(&contents).wrap_err_with(|| {
+                    format!("error migrating Hakari config at {}", config_path)
+                })?;
+                if new_contents == contents {
+                    if output.is_json() {
+                        let patch = diffy::create_patch(&contents, &new_contents);
+                        message::emit(message::diff(&config_path, &patch))?;
+                    }
+                    info!(
+                        "{} is already at the latest config version",
+                        config_path.as_str().style(output.styles.config_path)
+                    );
+                    return Ok(0);
+                }
+
+                let patch = diffy::create_patch(&contents, &new_contents);
+                if output.is_json() {
+                    message::emit(message::diff(&config_path, &patch))?;
+                } else {
+                    let mut formatter = PatchFormatter::new();
+                    if output.color.is_enabled() {
+                        formatter = formatter.with_color();
+                    }
+                    info!("\n{}", formatter.fmt_patch(&patch));
+                }
+                if dry_run {
+                    return Ok(1);
+                }
+
+                std::fs::write(&config_path, &new_contents)
+                    .wrap_err_with(|| format!("error writing Hakari config at {}", config_path))?;
+                if output.is_json() {
+                    message::emit(message::write(&config_path, true))?;
+                }
+                info!(
+                    "updated {}",
+                    config_path.as_str().style(output.styles.config_path)
+                );
+                Ok(0)
+            }
             Command::WithBuilder(cmd) => {
-                let (builder, hakari_output) = make_builder_and_output(&package_graph)?;
-                cmd.exec(builder, hakari_output, output)
+                let (builder, hakari_output) =
+                    make_builder_and_output(&package_graph, &output.config_overrides)?;
+                cmd.exec_any(builder, hakari_output, output)
             }
         }
     }
 }
 
-#[derive(Debug, Parser)]
-enum CommandWithBuilder {
+#[derive(Clone, Debug, Parser)]
+pub(crate) enum CommandWithBuilder {
     /// Generate or update the contents of the workspace-hack crate
     Generate {
         /// Print a diff of contents instead of writing them out. Can be combined with `--quiet`.
@@ -173,6 +447,21 @@ enum CommandWithBuilder {
         /// Exits with status 1 if the contents are different.
         #[clap(long)]
         diff: bool,
+
+        // Print a diff of contents before writing them out. Used by `cargo hakari platform`.
+        #[clap(skip)]
+        show_diff: bool,
+
+        /// After writing out the new contents, check that the workspace-hack works correctly, as
+        /// with `cargo hakari verify`.
+        ///
+        /// If verification fails, the previous contents are restored and this exits with status 1.
+        #[clap(long, conflicts_with = "diff")]
+        verify: bool,
+
+        /// With `--verify`, also run `cargo check` on the workspace-hack crate.
+        #[clap(long, requires = "verify")]
+        cargo_check: bool,
     },
 
     /// Perform verification of the workspace-hack crate
@@ -181,7 +470,30 @@ enum CommandWithBuilder {
     /// every non-omitted third-party crate.
     ///
     /// Exits with status 1 if verification failed.
-    Verify,
+    Verify {
+        /// Only verify the given platform instead of the ones in the Hakari config (can be
+        /// specified multiple times)
+        ///
+        /// Useful for checking a single configuration quickly while debugging.
+        #[clap(long = "platform", value_name = "TRIPLE")]
+        platforms: Vec<String>,
+
+        /// Include dev-dependencies while verifying, overriding `exclude-dev-dependencies`
+        #[clap(long, overrides_with = "no_include_dev")]
+        include_dev: bool,
+
+        /// Exclude dev-dependencies while verifying, overriding `exclude-dev-dependencies`
+        #[clap(long, overrides_with = "include_dev")]
+        no_include_dev: bool,
+    },
+
+    /// Check that the workspace-hack is up-to-date, for use in CI
+    ///
+    /// Runs the equivalent of `cargo hakari generate --diff`, `cargo hakari manage-deps --dry-run`
+    /// and `cargo hakari verify`, then prints out a summary of the results.
+    ///
+    /// Exits with status 1 if any of the checks failed.
+    Check,
 
     /// Manage dependencies from workspace crates to workspace-hack.
     ///
@@ -232,16 +544,70 @@ enum CommandWithBuilder {
     /// to be added if they're built with a second feature set. These cases are marked as
     /// "post-compute fixup".
     ///
-    /// Currently, this command only prints out the different feature sets that get built for a
-    /// dependency, and the workspace crates responsible for them. Further investigation can be done
-    /// through `cargo tree`. In the future, the scope of this command may be extended to provide
-    /// information about intermediate dependencies as well.
+    /// With `--chains`, this command also prints out, for each workspace crate responsible, a
+    /// chain of intermediate dependencies through which it depends on the dependency, similar to
+    /// `cargo tree --invert`. JSON output always includes these chains.
+    ///
+    /// With `--tree`, the workspace crates responsible for each feature set and the chains
+    /// through which they depend on the dependency are printed out as an indented tree instead of
+    /// a table, in the same layout as `cargo tree --invert`.
+    ///
+    /// With `--all`, this command explains every dependency in the workspace-hack. The builds
+    /// included in the report can be narrowed down with `--platform`, `--kind` and
+    /// `--workspace-member`.
     Explain {
         /// The name of the dependency, as present in the workspace-hack.
-        dep_name: String,
+        #[clap(required_unless_present = "all")]
+        dep_name: Option<String>,
+
+        /// Explain every dependency in the workspace-hack.
+        #[clap(long, conflicts_with = "dep_name")]
+        all: bool,
+
+        #[clap(flatten)]
+        filter: ExplainFilterOpts,
+
+        /// Print out a chain of dependencies from each workspace crate to the dependency.
+        #[clap(long)]
+        chains: bool,
+
+        /// Print out the workspace crates and dependency chains as a tree, like `cargo tree -i`.
+        #[clap(long, conflicts_with = "chains")]
+        tree: bool,
+
+        /// The format to output the explanation in.
+        #[clap(long, value_enum, default_value_t = ExplainFormat::Text)]
+        format: ExplainFormat,
     },
 
-    /// Publish a package after temporarily removing the workspace-hack dependency from it.
+    /// List third-party dependencies that aren't in the workspace-hack
+    ///
+    /// For each dependency, prints out why it was left out: it's always built with the same
+    /// feature set, it's excluded in hakari.toml (during traversal or from the final output), it
+    /// enables a denied feature, its source isn't supported, it's only built on platforms not
+    /// listed in hakari.toml, or it isn't built by any workspace crate.
+    ListOmitted,
+
+    /// Estimate the benefit of the workspace-hack
+    ///
+    /// Prints out the number of third-party crates that would be built with more than one feature
+    /// set without the workspace-hack, an estimate of the number of duplicate builds the
+    /// workspace-hack avoids, a breakdown by platform, and the crates for which the most builds are
+    /// avoided.
+    ///
+    /// These numbers are estimates: they count the feature sets built when workspace crates are
+    /// built one at a time, and building several crates at once can unify some of these feature
+    /// sets even without the workspace-hack.
+    Stats {
+        /// The number of crates to list, ordered by the number of builds avoided.
+        #[clap(long, default_value_t = 10)]
+        top: usize,
+    },
+
+    /// Publish packages after temporarily removing the workspace-hack dependency from them.
+    ///
+    /// Packages are published in dependency order, and the workspace-hack dependency is restored
+    /// once all of them are published.
     ///
     /// For more information about publishing options,
     /// see {n}https://docs.rs/cargo-hakari/latest/cargo_hakari/publishing.
@@ -249,9 +615,17 @@ enum CommandWithBuilder {
     /// Trailing arguments are passed through to cargo publish.
     #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
     Publish {
-        /// The name of the package to publish.
-        #[clap(long, short)]
-        package: String,
+        /// The name of a package to publish. Can be specified multiple times.
+        #[clap(long, short, required_unless_present = "workspace")]
+        package: Vec<String>,
+
+        /// Publish all workspace members that can be published (`publish` isn't `false`).
+        #[clap(long, conflicts_with = "package")]
+        workspace: bool,
+
+        /// Exclude a package from `--workspace`. Can be specified multiple times.
+        #[clap(long, requires = "workspace")]
+        exclude: Vec<String>,
 
         /// Arguments to pass through to `cargo publish`.
         #[clap(num_args = 0..)]
@@ -271,6 +645,122 @@ enum CommandWithBuilder {
 }
 
 impl CommandWithBuilder {
+    /// Executes this command, once for every Hakari package if shards are configured.
+    pub(crate) fn exec_any(
+        self,
+        builder: HakariBuilder<'_>,
+        hakari_output: HakariOutputOptions,
+        output: OutputContext,
+    ) -> Result<i32> {
+        if builder.shards().len() == 0 {
+            self.exec(builder, hakari_output, output)
+        } else {
+            self.exec_shards(builder, hakari_output, output)
+        }
+    }
+
+    /// Executes this command once for every Hakari package, if shards are configured.
+    fn exec_shards(
+        self,
+        builder: HakariBuilder<'_>,
+        hakari_output: HakariOutputOptions,
+        output: OutputContext,
+    ) -> Result<i32> {
+        if builder.dep_format_version() >= DepFormatVersion::V5 {
+            bail!(
+                "dep-format-version 5 can't be used with shards \
+                 (dependency names in [workspace.dependencies] must be shared by all shards)"
+            );
+        }
+
+        let shard_builders = builder.shard_builders();
+        match &self {
+            CommandWithBuilder::Explain {
+                dep_name: None,
+                filter,
+                chains,
+                tree,
+                format,
+                ..
+            } => {
+                let hakaris: Vec<_> = shard_builders
+                    .into_iter()
+                    .map(|shard_builder| {
+                        output
+                            .timings
+                            .time(Phase::Compute, || shard_builder.compute())
+                    })
+                    .collect();
+                explain_all(&hakaris, filter, *chains, *tree, *format, &output)?;
+                Ok(0)
+            }
+            CommandWithBuilder::Explain {
+                dep_name: Some(crate_name),
+                filter,
+                chains,
+                tree,
+                format,
+                ..
+            } => {
+                filter.check_unset()?;
+                let mut found = false;
+                for shard_builder in shard_builders {
+                    found |= explain_dep(
+                        &output
+                            .timings
+                            .time(Phase::Compute, || shard_builder.compute()),
+                        crate_name,
+                        *chains,
+                        *tree,
+                        *format,
+                        &output,
+                    )?;
+                }
+                if !found {
+                    bail!(
+                        "crate name '{}' not found in any workspace-hack\n\
+                        (hint: check spelling, or regenerate workspace-hack with `cargo hakari generate`)",
+                        crate_name
+                    );
+                }
+                Ok(0)
+            }
+            CommandWithBuilder::Publish {
+                package,
+                workspace,
+                exclude,
+                pass_through,
+            } => {
+                // Each package is published using the Hakari package that covers it.
+                let to_publish = PublishPackages {
+                    packages: package,
+                    workspace: *workspace,
+                    exclude,
+                };
+                publish_hakari(&to_publish, shard_builders, pass_through, output)?;
+                Ok(0)
+            }
+            _ => {
+                let mut exit_code = 0;
+                for shard_builder in shard_builders {
+                    let hakari_package = *shard_builder
+                        .hakari_package()
+                        .expect("shard builders have a hakari package");
+                    info!(
+                        "for {}:",
+                        hakari_package.name().style(output.styles.package_name)
+                    );
+                    exit_code = exit_code.max(self.clone().exec(
+                        shard_builder,
+                        hakari_output.clone(),
+                        output.clone(),
+                    )?);
+                }
+                Ok(exit_code)
+            }
+        }
+    }
+
     fn exec(
         self,
         builder: HakariBuilder<'_>,
@@ -282,10 +772,23 @@ impl CommandWithBuilder {
             .expect("hakari-package must be specified in hakari.toml");
 
         match self {
-            CommandWithBuilder::Generate { diff } => {
+            CommandWithBuilder::Generate {
+                diff,
+                show_diff,
+                verify,
+                cargo_check,
+            } => {
+                let mode = if show_diff && !diff {
+                    WriteMode::DiffAndWrite
+                } else {
+                    WriteMode::new(diff)
+                };
                 let package_graph = builder.graph();
-                let hakari = builder.compute();
-                let toml_out = match hakari.to_toml_string(&hakari_output) {
+                let hakari = output.timings.time(Phase::Compute, || builder.compute());
+                let toml_out = match output
+                    .timings
+                    .time(Phase::Toml, || hakari.to_toml_string(&hakari_output))
+                {
                     Ok(toml_out) => toml_out,
                     Err(TomlOutError::UnrecognizedRegistry {
                         package_id,
@@ -297,7 +800,7 @@ impl CommandWithBuilder {
                             .expect("package ID obtained from the same graph");
                         error!(
                             "unrecognized registry URL {} found for {} v{}\n\
-                             (add to [registries] section of {})",
+                             (add to [registries] section of {} or .cargo/config.toml)",
                             registry_url.style(output.styles.registry_url),
                             package.name().style(output.styles.package_name),
                             package.version().style(output.styles.package_version),
@@ -309,46 +812,145 @@ impl CommandWithBuilder {
                     Err(err) => Err(err).with_context(|| "error generating new hakari.toml")?,
                 };
 
+                // With format version 5, the workspace-hack references entries in
+                // [workspace.dependencies], so write those out first.
+                let mut exit_code = 0;
+                // The previous contents of changed files, restored if verification fails.
+                let mut originals = vec![];
+                if hakari.builder().dep_format_version() >= DepFormatVersion::V5 {
+                    let existing_workspace_toml = hakari.builder().read_workspace_toml()?;
+                    let workspace_toml_out = hakari
+                        .to_workspace_toml_string(&existing_workspace_toml, &hakari_output)
+                        .with_context(|| "error generating new [workspace.dependencies]")?;
+                    if existing_workspace_toml.is_changed(&workspace_toml_out) {
+                        originals.push(original_contents(&existing_workspace_toml));
+                    }
+                    exit_code = write_to_cargo_toml(
+                        existing_workspace_toml,
+                        &workspace_toml_out,
+                        mode,
+                        output.clone(),
+                    )?;
+                }
+
                 let existing_toml = hakari
                     .read_toml()
                     .expect("hakari-package must be specified")?;
+                if existing_toml.is_changed(&toml_out) {
+                    originals.push(original_contents(&existing_toml));
+                }
 
-                let exit_code =
-                    write_to_cargo_toml(existing_toml, &toml_out, diff, output.clone())?;
+                exit_code = exit_code.max(write_to_cargo_toml(
+                    existing_toml,
+                    &toml_out,
+                    mode,
+                    output.clone(),
+                )?);
+                if verify {
+                    exit_code = exit_code.max(verify_generated(
+                        hakari_package.name(),
+                        cargo_check,
+                        &originals,
+                        &output,
+                    )?);
+                }
                 if hakari.builder().dep_format_version() < DepFormatVersion::latest() {
                     info!(
                         "new hakari format version available: {latest} (current: {})\n\
-                        (add or update `dep-format-version = \"{latest}\"` in {}, then run \
-                        `cargo hakari generate && cargo hakari manage-deps`)",
+                        (run `cargo hakari upgrade-format` to upgrade)",
                         hakari.builder().dep_format_version(),
-                        "hakari.toml".style(output.styles.config_path),
                         latest = DepFormatVersion::latest(),
                     );
                 }
 
                 Ok(exit_code)
             }
-            CommandWithBuilder::Verify => match builder.verify() {
-                Ok(()) => {
-                    info!(
-                        "{} works correctly",
-                        hakari_package.name().style(output.styles.package_name),
-                    );
-                    Ok(0)
+            CommandWithBuilder::Verify {
+                platforms,
+                include_dev,
+                no_include_dev,
+            } => {
+                let mut builder = builder;
+                if !platforms.is_empty() {
+                    builder
+                        .set_platforms(platforms)
+                        .wrap_err("error setting platforms to verify")?;
                 }
-                Err(errs) => {
-                    let mut display = errs.display();
-                    if output.color.is_enabled() {
-                        display.colorize();
-                    }
-                    info!(
-                        "{} didn't work correctly:\n{}",
-                        hakari_package.name().style(output.styles.package_name),
-                        display,
-                    );
-                    Ok(1)
+                if include_dev || no_include_dev {
+                    builder.set_exclude_dev_dependencies(no_include_dev);
+                }
+                report_verify(
+                    hakari_package.name(),
+                    output
+                        .timings
+                        .time(Phase::Compute, || builder.verify().map_err(Box::new)),
+                    &output,
+                )
+            }
+            CommandWithBuilder::Check => {
+                let checks = [
+                    (
+                        "generate",
+                        "cargo hakari generate",
+                        CommandWithBuilder::Generate {
+                            diff: true,
+                            show_diff: false,
+                            verify: false,
+                            cargo_check: false,
+                        },
+                    ),
+                    (
+                        "manage-deps",
+                        "cargo hakari manage-deps",
+                        CommandWithBuilder::ManageDeps {
+                            packages: PackageSelection { packages: vec![] },
+                            dry_run: true,
+                            yes: false,
+                        },
+                    ),
+                    (
+                        "verify",
+                        "cargo hakari verify",
+                        CommandWithBuilder::Verify {
+                            platforms: vec![],
+                            include_dev: false,
+                            no_include_dev: false,
+                        },
+                    ),
+                ];
+
+                let mut exit_code = 0;
+                let mut results = Vec::with_capacity(checks.len());
+                let mut json_results = Vec::with_capacity(checks.len());
+                for (name, fix_command, command) in checks {
+                    let check_exit_code =
+                        command.exec(builder.clone(), hakari_output.clone(), output.clone())?;
+                    json_results.push((name, check_exit_code == 0));
+                    let result = if check_exit_code == 0 {
+                        format!("{}", "ok".style(output.styles.check_passed))
+                    } else if name == "verify" {
+                        format!("{}", "FAILED".style(output.styles.check_failed))
+                    } else {
+                        format!(
+                            "{} (run {} to fix)",
+                            "FAILED".style(output.styles.check_failed),
+                            fix_command.style(output.styles.command),
+                        )
+                    };
+                    results.push(format!("  {:<12} {}", format!("{}:", name), result));
+                    exit_code = exit_code.max(check_exit_code);
                 }
-            },
+
+                if output.is_json() {
+                    message::emit(message::check(hakari_package.name(), &json_results))?;
+                }
+                info!(
+                    "check results for {}:\n{}",
+                    hakari_package.name().style(output.styles.package_name),
+                    results.join("\n"),
+                );
+                Ok(exit_code)
+            }
             CommandWithBuilder::ManageDeps {
                 packages,
                 dry_run,
@@ -358,6 +960,9 @@ impl CommandWithBuilder {
                     .manage_dep_ops(&packages.to_package_set(builder.graph())?)
                     .expect("hakari-package must be specified in hakari.toml");
                 if ops.is_empty() {
+                    if output.is_json() {
+                        message::emit(message::operations(&ops, false))?;
+                    }
                     info!("no operations to perform");
                     return Ok(0);
                 }
@@ -375,6 +980,9 @@ impl CommandWithBuilder {
                     .remove_dep_ops(&packages.to_package_set(builder.graph())?, false)
                     .expect("hakari-package must be specified in hakari.toml");
                 if ops.is_empty() {
+                    if output.is_json() {
+                        message::emit(message::operations(&ops, false))?;
+                    }
                     info!("no operations to perform");
                     return Ok(0);
                 }
@@ -384,48 +992,313 @@ impl CommandWithBuilder {
                 })
             }
             CommandWithBuilder::Explain {
-                dep_name: crate_name,
+                dep_name: None,
+                filter,
+                chains,
+                tree,
+                format,
+                ..
             } => {
-                let hakari = builder.compute();
-                let toml_name_map = hakari.toml_name_map();
-                let dep = toml_name_map.get(crate_name.as_str()).ok_or_else(|| {
-                    eyre!(
+                explain_all(
+                    &[output.timings.time(Phase::Compute, || builder.compute())],
+                    &filter,
+                    chains,
+                    tree,
+                    format,
+                    &output,
+                )?;
+                Ok(0)
+            }
+            CommandWithBuilder::Explain {
+                dep_name: Some(crate_name),
+                filter,
+                chains,
+                tree,
+                format,
+                ..
+            } => {
+                filter.check_unset()?;
+                let hakari = output.timings.time(Phase::Compute, || builder.compute());
+                if !explain_dep(&hakari, &crate_name, chains, tree, format, &output)? {
+                    bail!(
                         "crate name '{}' not found in workspace-hack\n\
                         (hint: check spelling, or regenerate workspace-hack with `cargo hakari generate`)",
                         crate_name
-                    )
-                })?;
-
-                let explain = hakari
-                    .explain(dep.id())
-                    .expect("package ID should be known since it was in the output");
-                let mut display = explain.display();
-                if output.color.is_enabled() {
-                    display.colorize();
+                    );
                 }
-                info!("\n{}", display);
                 Ok(0)
             }
+            CommandWithBuilder::ListOmitted => {
+                list_omitted(
+                    &output.timings.time(Phase::Compute, || builder.compute()),
+                    &output,
+                )?;
+                Ok(0)
+            }
+            CommandWithBuilder::Stats { top } => stats(
+                &output.timings.time(Phase::Compute, || builder.compute()),
+                top,
+                &output,
+            ),
             CommandWithBuilder::Publish {
                 package,
+                workspace,
+                exclude,
                 pass_through,
             } => {
-                publish_hakari(&package, builder, &pass_through, output)?;
+                let to_publish = PublishPackages {
+                    packages: &package,
+                    workspace,
+                    exclude: &exclude,
+                };
+                publish_hakari(&to_publish, vec![builder], &pass_through, output)?;
                 Ok(0)
             }
             CommandWithBuilder::Disable { diff } => {
                 let existing_toml = builder
                     .read_toml()
                     .expect("hakari-package must be specified")?;
-                write_to_cargo_toml(existing_toml, DISABLE_MESSAGE, diff, output)
+                write_to_cargo_toml(existing_toml, DISABLE_MESSAGE, WriteMode::new(diff), output)
+            }
+        }
+    }
+}
+
+/// Prints out why a dependency is in the workspace-hack, returning false if it isn't present.
+fn explain_dep(
+    hakari: &Hakari<'_>,
+    crate_name: &str,
+    chains: bool,
+    tree: bool,
+    format: ExplainFormat,
+    output: &OutputContext,
+) -> Result<bool> {
+    let toml_name_map = hakari.toml_name_map();
+    let dep = match toml_name_map.get(crate_name) {
+        Some(dep) => dep,
+        None => return Ok(false),
+    };
+
+    let explain = hakari
+        .explain(dep.id())
+        .expect("package ID should be known since it was in the output");
+    if output.is_json() {
+        let hakari_package = hakari
+            .builder()
+            .hakari_package()
+            .expect("hakari-package must be specified in hakari.toml");
+        message::emit(message::explain(hakari_package.name(), &explain))?;
+        return Ok(true);
+    }
+
+    match format {
+        ExplainFormat::Text => {
+            info!("\n{}", text_display(&explain, chains, tree, output));
+        }
+        ExplainFormat::Json => {
+            // Write JSON to stdout so that it can be piped into other tools.
+            let json = serde_json::to_string_pretty(&explain.to_summary())
+                .wrap_err("error serializing explanation to JSON")?;
+            println!("{}", json);
+        }
+    }
+    Ok(true)
+}
+
+/// Prints out the third-party dependencies that aren't in the workspace-hack, grouped by the
+/// reason they were left out.
+fn list_omitted(hakari: &Hakari<'_>, output: &OutputContext) -> Result<()> {
+    let omitted = hakari.omitted();
+    if output.is_json() {
+        let hakari_package = hakari
+            .builder()
+            .hakari_package()
+            .expect("hakari-package must be specified in hakari.toml");
+        message::emit(message::omitted(hakari_package.name(), &omitted))?;
+        return Ok(());
+    }
+    if omitted.is_empty() {
+        info!("all third-party dependencies are in the workspace-hack");
+        return Ok(());
+    }
+
+    let mut by_reason: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for dep in &omitted {
+        by_reason.entry(dep.reason).or_default().push(dep.package);
+    }
+    let mut out = String::new();
+    for (reason, mut packages) in by_reason {
+        packages.sort_by(|a, b| (a.name(), a.version()).cmp(&(b.name(), b.version())));
+        writeln!(out, "{} ({}):", reason, packages.len())?;
+        for package in packages {
+            writeln!(
+                out,
+                "  {} v{}",
+                package.name().style(output.styles.package_name),
+                package.version().style(output.styles.package_version),
+            )?;
+        }
+    }
+    info!("{}", out.trim_end());
+    Ok(())
+}
+
+/// Returns a displayer for the text format of `cargo hakari explain`.
+fn text_display<'g, 'a, 'explain>(
+    explain: &'explain HakariExplain<'g, 'a>,
+    chains: bool,
+    tree: bool,
+    output: &OutputContext,
+) -> HakariExplainDisplay<'g, 'a, 'explain> {
+    let mut display = explain.display();
+    if output.color.is_enabled() {
+        display.colorize();
+    }
+    if chains {
+        display.show_chains();
+    }
+    if tree {
+        display.tree();
+    }
+    display
+}
+
+/// Prints out why each dependency in the workspace-hacks is present, only including builds that
+/// match the filter.
+fn explain_all(
+    hakaris: &[Hakari<'_>],
+    filter_opts: &ExplainFilterOpts,
+    chains: bool,
+    tree: bool,
+    format: ExplainFormat,
+    output: &OutputContext,
+) -> Result<()> {
+    let mut summaries = vec![];
+    let mut count = 0;
+    for hakari in hakaris {
+        let filter = filter_opts.to_filter(hakari.builder())?;
+        let hakari_package = hakari
+            .builder()
+            .hakari_package()
+            .expect("hakari-package must be specified in hakari.toml");
+        for explain in hakari.explain_all(&filter) {
+            count += 1;
+            if output.is_json() {
+                message::emit(message::explain(hakari_package.name(), &explain))?;
+                continue;
+            }
+            match format {
+                ExplainFormat::Text => {
+                    let dep = explain.dependency();
+                    info!(
+                        "{} v{}:\n{}",
+                        dep.name().style(output.styles.package_name),
+                        dep.version().style(output.styles.package_version),
+                        text_display(&explain, chains, tree, output),
+                    );
+                }
+                ExplainFormat::Json => summaries.push(explain.to_summary()),
             }
         }
     }
+
+    if !output.is_json() && format == ExplainFormat::Json {
+        // Write JSON to stdout so that it can be piped into other tools.
+        let json = serde_json::to_string_pretty(&summaries)
+            .wrap_err("error serializing explanations to JSON")?;
+        println!("{}", json);
+    }
+    if count == 0 {
+        info!("no dependencies in the workspace-hack match the filters");
+    }
+    Ok(())
+}
+
+/// Filters for `cargo hakari explain --all`.
+#[derive(Clone, Debug, Parser)]
+pub(crate) struct ExplainFilterOpts {
+    /// With --all, only include builds on this platform, which must be listed in the Hakari
+    /// config (builds on every platform are always included)
+    #[clap(long)]
+    platform: Option<String>,
+
+    /// With --all, only include builds of this kind
+    #[clap(long, value_enum)]
+    kind: Option<ExplainKind>,
+
+    /// With --all, only include builds caused by these workspace members
+    #[clap(long = "workspace-member")]
+    workspace_members: Vec<String>,
+}
+
+impl ExplainFilterOpts {
+    /// Returns an error if any filters are set, since they're only supported with `--all`.
+    fn check_unset(&self) -> Result<()> {
+        if self.platform.is_some() || self.kind.is_some() || !self.workspace_members.is_empty() {
+            bail!("--platform, --kind and --workspace-member can only be used with --all");
+        }
+        Ok(())
+    }
+
+    /// Converts these options into an `ExplainFilter`.
+    fn to_filter<'g>(&self, builder: &HakariBuilder<'g>) -> Result<ExplainFilter<'g>> {
+        let mut filter = ExplainFilter::new();
+        if let Some(triple) = &self.platform {
+            if !builder.platforms().any(|platform| platform == triple) {
+                bail!(
+                    "platform {} is not listed in the Hakari config (platforms: {})",
+                    triple,
+                    builder.platforms().collect::<Vec<_>>().join(", "),
+                );
+            }
+            filter.set_platform(triple.clone());
+        }
+        if let Some(kind) = self.kind {
+            filter.set_build_platform(kind.build_platform());
+        }
+        if !self.workspace_members.is_empty() {
+            let workspace = builder.graph().workspace();
+            let package_ids = self
+                .workspace_members
+                .iter()
+                .map(|name| Ok(workspace.member_by_name(name)?.id()))
+                .collect::<Result<Vec<_>>>()?;
+            filter.add_workspace_packages(package_ids);
+        }
+        Ok(filter)
+    }
+}
+
+/// The kinds of builds `cargo hakari explain --all` can be filtered by.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub(crate) enum ExplainKind {
+    /// Regular dependencies, built for the target platform.
+    Normal,
+    /// Build dependencies and procedural macros, built for the host platform.
+    Build,
+}
+
+impl ExplainKind {
+    fn build_platform(self) -> BuildPlatform {
+        match self {
+            ExplainKind::Normal => BuildPlatform::Target,
+            ExplainKind::Build => BuildPlatform::Host,
+        }
+    }
+}
+
+/// The output format for `cargo hakari explain`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub(crate) enum ExplainFormat {
+    /// A human-readable table.
+    Text,
+    /// JSON, in the format described by `hakari::explain::HakariExplainSummary`.
+    Json,
 }
 
 /// Support for packages and features.
-#[derive(Debug, Parser)]
-struct PackageSelection {
+#[derive(Clone, Debug, Default, Parser)]
+pub(crate) struct PackageSelection {
     #[clap(long = "package", short)]
     /// Packages to operate on (default: entire workspace)
     packages: Vec<String>,
@@ -467,19 +1340,47 @@ fn cwd_rel_to_workspace_rel(path: &Utf8Path, workspace_root: &Utf8Path) -> Resul
         })
 }
 
-fn make_builder_and_output(
-    package_graph: &PackageGraph,
-) -> Result<(HakariBuilder<'_>, HakariOutputOptions)> {
-    let (config_path, contents) = read_contents(
+pub(crate) fn make_builder_and_output<'g>(
+    package_graph: &'g PackageGraph,
+    overrides: &[ConfigOverride],
+) -> Result<(HakariBuilder<'g>, HakariOutputOptions)> {
+    let (config_path, contents) = read_config(package_graph)?;
+    builder_and_output_from_config(package_graph, &config_path, &contents, overrides)
+}
+
+/// Reads the Hakari config, returning its path and contents.
+pub(crate) fn read_config(package_graph: &PackageGraph) -> Result<(Utf8PathBuf, String)> {
+    read_contents(
         package_graph.workspace().root(),
         [DEFAULT_CONFIG_PATH, FALLBACK_CONFIG_PATH],
     )
-    .wrap_err("error reading Hakari config")?;
+    .wrap_err("error reading Hakari config")
+}
 
-    let config: HakariConfig = contents
+/// Builds a `HakariBuilder` from the contents of the Hakari config, with `overrides` layered on
+/// top.
+pub(crate) fn builder_and_output_from_config<'g>(
+    package_graph: &'g PackageGraph,
+    config_path: &Utf8Path,
+    contents: &str,
+    overrides: &[ConfigOverride],
+) -> Result<(HakariBuilder<'g>, HakariOutputOptions)> {
+    let contents = apply_overrides(contents, overrides)?;
+    let mut config: HakariConfig = contents
         .parse()
         .wrap_err_with(|| format!("error deserializing Hakari config at {}", config_path))?;
 
+    // Registries declared in .cargo/config.toml don't need to be repeated in the Hakari config.
+    config
+        .builder
+        .add_cargo_config_registries(package_graph)
+        .wrap_err("error reading registries from Cargo config")?;
+    // If the config doesn't force a resolver version, use the one Cargo uses for the workspace.
+    config
+        .builder
+        .add_workspace_resolver(package_graph)
+        .wrap_err("error reading resolver version from workspace Cargo.toml")?;
+
     let builder = config
         .builder
         .to_hakari_builder(package_graph)
@@ -489,36 +1390,208 @@ fn make_builder_and_output(
     Ok((builder, hakari_output))
 }
 
+/// How to update the generated section of a `Cargo.toml`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum WriteMode {
+    /// Write out the new contents.
+    Write,
+
+    /// Print a diff of the new contents instead of writing them out.
+    Diff,
+
+    /// Print a diff of the new contents, then write them out.
+    DiffAndWrite,
+}
+
+impl WriteMode {
+    fn new(diff: bool) -> Self {
+        if diff {
+            WriteMode::Diff
+        } else {
+            WriteMode::Write
+        }
+    }
+}
+
 fn write_to_cargo_toml(
     existing_toml: HakariCargoToml,
     new_contents: &str,
-    diff: bool,
+    mode: WriteMode,
     output: OutputContext,
 ) -> Result<i32> {
-    if diff {
+    if mode != WriteMode::Write {
         let patch = existing_toml.diff_toml(new_contents);
-        let mut formatter = PatchFormatter::new();
-        if output.color.is_enabled() {
-            formatter = formatter.with_color();
+        if output.is_json() {
+            message::emit(message::diff(existing_toml.toml_path(), &patch))?;
+        } else if mode == WriteMode::Diff || !patch.hunks().is_empty() {
+            // Always print the patch for --diff, even if it's empty.
+            let mut formatter = PatchFormatter::new();
+            if output.color.is_enabled() {
+                formatter = formatter.with_color();
+            }
+            info!("\n{}", formatter.fmt_patch(&patch));
         }
-        info!("\n{}", formatter.fmt_patch(&patch));
-        if patch.hunks().is_empty() {
-            // No differences.
+        if mode == WriteMode::Diff {
+            if patch.hunks().is_empty() {
+                // No differences.
+                return Ok(0);
+            } else {
+                return Ok(1);
+            }
+        }
+    }
+
+    let toml_path = existing_toml.toml_path().to_owned();
+    let changed = existing_toml.is_changed(new_contents);
+    if !changed {
+        info!("no changes detected");
+    } else {
+        existing_toml
+            .write_to_file(new_contents)
+            .with_context(|| "error writing updated Hakari contents")?;
+        info!("contents updated");
+    }
+    if output.is_json() {
+        message::emit(message::write(&toml_path, changed))?;
+    }
+    if changed {
+        regenerate_lockfile(output)?;
+    }
+    Ok(0)
+}
+
+/// Prints out the results of verifying a Hakari package, returning the exit code.
+fn report_verify(
+    hakari_package: &str,
+    result: Result<(), Box<VerifyErrors<'_>>>,
+    output: &OutputContext,
+) -> Result<i32> {
+    match result {
+        Ok(()) => {
+            if output.is_json() {
+                message::emit(message::verify(hakari_package, None))?;
+            }
+            info!(
+                "{} works correctly",
+                hakari_package.style(output.styles.package_name),
+            );
             Ok(0)
-        } else {
+        }
+        Err(errs) if output.is_json() => {
+            message::emit(message::verify(hakari_package, Some(&errs)))?;
+            info!(
+                "{} didn't work correctly",
+                hakari_package.style(output.styles.package_name),
+            );
             Ok(1)
         }
-    } else {
-        if !existing_toml.is_changed(new_contents) {
-            info!("no changes detected");
-        } else {
-            existing_toml
-                .write_to_file(new_contents)
-                .with_context(|| "error writing updated Hakari contents")?;
-            info!("contents updated");
-            regenerate_lockfile(output)?;
+        Err(errs) => {
+            let mut display = errs.display();
+            if output.color.is_enabled() {
+                display.colorize();
+            }
+            info!(
+                "{} didn't work correctly:\n{}",
+                hakari_package.style(output.styles.package_name),
+                display,
+            );
+            Ok(1)
         }
+    }
+}
+
+fn original_contents(toml: &HakariCargoToml) -> (Utf8PathBuf, String) {
+    (toml.toml_path().to_owned(), toml.contents().to_owned())
+}
+
+/// Verifies the workspace-hack after `generate` writes it out, restoring the files in `originals`
+/// to their previous contents if verification fails.
+fn verify_generated(
+    hakari_package: &str,
+    cargo_check: bool,
+    originals: &[(Utf8PathBuf, String)],
+    output: &OutputContext,
+) -> Result<i32> {
+    let result = verify_written(hakari_package, cargo_check, output);
+    if matches!(result, Ok(0)) || originals.is_empty() {
+        return result;
+    }
+
+    for (path, contents) in originals {
+        std::fs::write(path, contents)
+            .wrap_err_with(|| format!("error restoring previous contents of {}", path))?;
+    }
+    regenerate_lockfile(output.clone())?;
+    let restored = originals
+        .iter()
+        .map(|(path, _)| path.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    match result {
+        Ok(exit_code) => {
+            error!(
+                "verification failed, restored previous contents of {}",
+                restored
+            );
+            Ok(exit_code)
+        }
+        Err(err) => Err(err.wrap_err(format!(
+            "verification failed, restored previous contents of {}",
+            restored
+        ))),
+    }
+}
+
+fn verify_written(hakari_package: &str, cargo_check: bool, output: &OutputContext) -> Result<i32> {
+    // The package graph has to be rebuilt to pick up the new contents.
+    let package_graph = output
+        .timings
+        .time(Phase::Metadata, || {
+            output.cargo_opts.metadata_command().build_graph()
+        })
+        .wrap_err("building package graph with new contents failed")?;
+    let (builder, _) = make_builder_and_output(&package_graph, &output.config_overrides)?;
+    let builder = if builder.shards().len() == 0 {
+        builder
+    } else {
+        builder
+            .shard_builders()
+            .into_iter()
+            .find(|shard_builder| {
+                shard_builder
+                    .hakari_package()
+                    .is_some_and(|package| package.name() == hakari_package)
+            })
+            .ok_or_else(|| eyre!("Hakari package {} not found", hakari_package))?
+    };
+
+    let exit_code = report_verify(
+        hakari_package,
+        output
+            .timings
+            .time(Phase::Compute, || builder.verify().map_err(Box::new)),
+        output,
+    )?;
+    if exit_code != 0 || !cargo_check {
+        return Ok(exit_code);
+    }
+
+    let mut cargo_cli = CargoCli::new("check", output.clone());
+    cargo_cli.add_args(["-p", hakari_package]);
+    let status = cargo_cli
+        .to_expression()
+        .unchecked()
+        .run()
+        .wrap_err("running cargo check failed")?
+        .status;
+    if status.success() {
         Ok(0)
+    } else {
+        error!(
+            "{} failed",
+            format!("cargo check -p {}", hakari_package).style(output.styles.command),
+        );
+        Ok(1)
     }
 }
 
@@ -529,13 +1602,18 @@ fn apply_on_dialog(
     output: &OutputContext,
     after: impl FnOnce() -> Result<()>,
 ) -> Result<i32> {
-    let mut display = ops.display();
-    if output.color.is_enabled() {
-        display.colorize();
+    if !output.is_json() {
+        let mut display = ops.display();
+        if output.color.is_enabled() {
+            display.colorize();
+        }
+        info!("operations to perform:\n\n{}", display);
     }
-    info!("operations to perform:\n\n{}", display);
 
     if dry_run {
+        if output.is_json() {
+            message::emit(message::operations(ops, false))?;
+        }
         // dry-run + non-empty ops implies exit status 1.
         return Ok(1);
     }
@@ -560,8 +1638,25 @@ fn apply_on_dialog(
     if should_apply {
         ops.apply()?;
         after()?;
+    }
+    if output.is_json() {
+        message::emit(message::operations(ops, should_apply))?;
+    }
+    if should_apply {
         Ok(0)
     } else {
         Ok(1)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args_debug_assert() {
+        // Shell completions are generated from the full command, which catches invalid argument
+        // definitions, so check them here.
+        Args::command().debug_assert();
+    }
+}
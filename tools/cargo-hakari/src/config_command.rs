@@ -0,0 +1,720 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    command::{builder_and_output_from_config, read_config, CommandWithBuilder},
+    helpers::plural,
+    message,
+    output::OutputContext,
+};
+use camino::Utf8Path;
+use clap::Subcommand;
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use guppy::{
+    graph::PackageGraph,
+    platform::{Platform, TargetFeatures},
+};
+use hakari::summaries::{ConfigVersion, HakariConfig};
+use log::{error, info, warn};
+use owo_colors::OwoColorize;
+use std::{borrow::Cow, collections::BTreeSet, str::FromStr};
+use toml_edit::{Array, Document, InlineTable, Item, Table, TableLike, Value};
+
+/// Inspect and edit hakari.toml.
+#[derive(Debug, Subcommand)]
+pub(crate) enum ConfigCommand {
+    /// Check hakari.toml for errors
+    ///
+    /// In addition to syntax errors, this reports unknown keys, platforms that aren't recognized,
+    /// and workspace members that don't exist, along with their locations. Exits with status 1
+    /// if any errors are found.
+    Validate,
+
+    /// Print the value of a key in hakari.toml
+    ///
+    /// If the key isn't set, its default value is printed.
+    Get {
+        /// The key to print, with nested keys separated by `.`, e.g.
+        /// `traversal-excludes.workspace-members`.
+        key: String,
+    },
+
+    /// Set the value of a key in hakari.toml, then regenerate the workspace-hack crate
+    ///
+    /// Comments and formatting in the rest of the file are preserved.
+    Set {
+        /// The key to set, with nested keys separated by `.`, e.g.
+        /// `traversal-excludes.workspace-members`.
+        key: String,
+
+        /// The new value, in TOML syntax, e.g. `true` or `["foo", "bar"]`. Values that aren't valid
+        /// TOML are treated as strings.
+        value: String,
+
+        /// Print a diff of the workspace-hack crate, but do not write out any changes.
+        ///
+        /// Exits with status 1 if the workspace-hack crate would change. Can be combined with
+        /// `--quiet`.
+        #[clap(long, short = 'n')]
+        dry_run: bool,
+    },
+}
+
+impl ConfigCommand {
+    pub(crate) fn exec(self, package_graph: &PackageGraph, output: OutputContext) -> Result<i32> {
+        let (config_path, contents) = read_config(package_graph)?;
+        match self {
+            ConfigCommand::Validate => {
+                let diagnostics = validate(package_graph, &config_path, &contents);
+                let error_count = diagnostics
+                    .iter()
+                    .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                    .count();
+                if output.is_json() {
+                    let diagnostics: Vec<_> = diagnostics.iter().map(Diagnostic::to_json).collect();
+                    message::emit(message::config_validate(&config_path, diagnostics))?;
+                } else {
+                    for diagnostic in &diagnostics {
+                        diagnostic.log(&config_path);
+                    }
+                }
+
+                let warnings = plural(diagnostics.len() - error_count, "warning");
+                if error_count > 0 {
+                    error!(
+                        "{}: found {} and {}",
+                        config_path,
+                        plural(error_count, "error"),
+                        warnings,
+                    );
+                    Ok(1)
+                } else {
+                    info!(
+                        "{} is valid, with {}",
+                        config_path.as_str().style(output.styles.config_path),
+                        warnings,
+                    );
+                    Ok(0)
+                }
+            }
+            ConfigCommand::Get { key } => {
+                let path = parse_key(&key)?;
+                let config: HakariConfig = contents.parse().wrap_err_with(|| {
+                    format!("error deserializing Hakari config at {}", config_path)
+                })?;
+                let doc: Document = contents
+                    .parse()
+                    .wrap_err_with(|| format!("error parsing Hakari config at {}", config_path))?;
+
+                let explicit = get_item(&doc, &path);
+                let effective = serde_json::to_value(&config)
+                    .wrap_err("error serializing Hakari config")?
+                    .pointer(&format!("/{}", path.join("/")))
+                    .cloned();
+                if output.is_json() {
+                    message::emit(message::config_value(
+                        &key,
+                        effective.as_ref(),
+                        explicit.is_none(),
+                    ))?;
+                    return Ok(0);
+                }
+
+                match (explicit, effective.as_ref().and_then(json_to_toml)) {
+                    (Some(Item::Value(value)), _) => println!("{}", value.to_string().trim()),
+                    (Some(item), _) => println!("{}", item.to_string().trim_end()),
+                    (None, Some(value)) => {
+                        println!("{}", value.to_string().trim());
+                        info!("{} is not set, so the default value was printed", key);
+                    }
+                    (None, None) => info!("{} is not set", key),
+                }
+                Ok(0)
+            }
+            ConfigCommand::Set {
+                key,
+                value,
+                dry_run,
+            } => {
+                let path = parse_key(&key)?;
+                let doc: Document = contents
+                    .parse()
+                    .wrap_err_with(|| format!("error parsing Hakari config at {}", config_path))?;
+
+                let new_contents = set_value(&doc, &path, &value)
+                    .wrap_err_with(|| format!("error setting {} in {}", key, config_path))?;
+                if new_contents == contents {
+                    info!("no changes to {}", config_path);
+                    return Ok(0);
+                }
+
+                // Check that the new config is valid before writing it out.
+                let (builder, hakari_output) = builder_and_output_from_config(
+                    package_graph,
+                    &config_path,
+                    &new_contents,
+                    &output.config_overrides,
+                )?;
+                let generate = CommandWithBuilder::Generate {
+                    diff: dry_run,
+                    show_diff: true,
+                    verify: false,
+                    cargo_check: false,
+                };
+                if dry_run {
+                    return generate.exec_any(builder, hakari_output, output);
+                }
+
+                std::fs::write(&config_path, &new_contents)
+                    .wrap_err_with(|| format!("error writing Hakari config at {}", config_path))?;
+                if output.is_json() {
+                    message::emit(message::write(&config_path, true))?;
+                }
+                info!(
+                    "updated {}",
+                    config_path.as_str().style(output.styles.config_path)
+                );
+                generate.exec_any(builder, hakari_output, output)
+            }
+        }
+    }
+}
+
+fn parse_key(key: &str) -> Result<Vec<&str>> {
+    let path: Vec<_> = key.split('.').collect();
+    if path.iter().any(|component| component.is_empty()) {
+        bail!("invalid key {}", key);
+    }
+    if !HakariConfig::KEYS.contains(&path[0]) {
+        match suggest_key(path[0]) {
+            Some(suggestion) => bail!("unknown key {} (did you mean {}?)", path[0], suggestion),
+            None => bail!("unknown key {}", path[0]),
+        }
+    }
+    Ok(path)
+}
+
+fn get_item<'doc>(doc: &'doc Document, path: &[&str]) -> Option<&'doc Item> {
+    let (last, parents) = path.split_last().expect("key is non-empty");
+    let mut table: &dyn TableLike = doc.as_table();
+    for component in parents {
+        table = table.get(component)?.as_table_like()?;
+    }
+    table.get(last)
+}
+
+/// A `key=value` override for the Hakari config, passed in with `--config`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ConfigOverride {
+    key: String,
+    value: String,
+}
+
+impl FromStr for ConfigOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Ok(Self {
+                key: key.trim().to_owned(),
+                value: value.trim().to_owned(),
+            }),
+            _ => Err(format!("expected `key=value`, found `{}`", s)),
+        }
+    }
+}
+
+/// Returns the contents of the config with `overrides` applied in order, leaving the config on
+/// disk unchanged.
+pub(crate) fn apply_overrides<'a>(
+    contents: &'a str,
+    overrides: &[ConfigOverride],
+) -> Result<Cow<'a, str>> {
+    if overrides.is_empty() {
+        return Ok(Cow::Borrowed(contents));
+    }
+
+    let mut contents = contents.to_owned();
+    for config_override in overrides {
+        let doc: Document = contents
+            .parse()
+            .wrap_err("error parsing Hakari config as TOML")?;
+        let path = parse_key(&config_override.key)?;
+        contents = set_value(&doc, &path, &config_override.value)
+            .wrap_err_with(|| format!("error applying --config {}", config_override.key))?;
+    }
+    Ok(Cow::Owned(contents))
+}
+
+/// Returns the new contents of the config with `value` set at `path`.
+///
+/// `value` is parsed as TOML if possible, but is treated as a string if that doesn't produce a
+/// valid config. For example, `resolver = 2` is invalid but `resolver = "2"` is valid.
+pub(crate) fn set_value(doc: &Document, path: &[&str], value: &str) -> Result<String> {
+    let mut candidates = vec![];
+    if let Ok(parsed) = value.parse::<Value>() {
+        candidates.push(parsed);
+    }
+    if !candidates.iter().any(Value::is_str) {
+        candidates.push(Value::from(value));
+    }
+
+    let mut first_err = None;
+    for candidate in candidates {
+        let mut doc = doc.clone();
+        set_item(&mut doc, path, candidate)?;
+        let new_contents = doc.to_string();
+        match new_contents.parse::<HakariConfig>() {
+            Ok(_) => return Ok(new_contents),
+            Err(err) => {
+                first_err.get_or_insert(err);
+            }
+        }
+    }
+    Err(first_err.expect("at least one candidate was tried"))
+        .wrap_err_with(|| format!("invalid value {}", value))
+}
+
+fn set_item(doc: &mut Document, path: &[&str], mut value: Value) -> Result<()> {
+    let (last, parents) = path.split_last().expect("key is non-empty");
+    let mut table: &mut dyn TableLike = doc.as_table_mut();
+    for (idx, component) in parents.iter().enumerate() {
+        if table.get(component).is_none() {
+            table.insert(component, Item::Table(Table::new()));
+        }
+        table = table
+            .get_mut(component)
+            .and_then(Item::as_table_like_mut)
+            .ok_or_else(|| eyre!("{} is not a table", path[..=idx].join(".")))?;
+    }
+
+    match table.get_mut(last) {
+        Some(Item::Value(existing)) => {
+            // Keep comments and whitespace around the existing value.
+            *value.decor_mut() = existing.decor().clone();
+            *existing = value;
+        }
+        Some(item) if item.is_table_like() => {
+            bail!("{} is a table: set keys within it instead", path.join("."))
+        }
+        _ => {
+            table.insert(last, Item::Value(value));
+        }
+    }
+    Ok(())
+}
+
+fn json_to_toml(value: &serde_json::Value) -> Option<Value> {
+    let value = match value {
+        serde_json::Value::Null => return None,
+        serde_json::Value::Bool(value) => Value::from(*value),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(number) => Value::from(number),
+            None => Value::from(number.as_f64()?),
+        },
+        serde_json::Value::String(value) => Value::from(value.as_str()),
+        serde_json::Value::Array(values) => {
+            Value::Array(values.iter().filter_map(json_to_toml).collect::<Array>())
+        }
+        serde_json::Value::Object(map) => {
+            let mut table = InlineTable::new();
+            for (key, value) in map {
+                if let Some(value) = json_to_toml(value) {
+                    table.insert(key, value);
+                }
+            }
+            Value::InlineTable(table)
+        }
+    };
+    Some(value)
+}
+
+// ---
+// Validation
+// ---
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in the config.
+#[derive(Clone, Debug)]
+struct Diagnostic {
+    severity: Severity,
+    message: String,
+    /// The 1-based line and column the problem was found at, if known.
+    location: Option<(usize, usize)>,
+    help: Option<String>,
+}
+
+impl Diagnostic {
+    fn error(message: impl Into<String>, location: Option<(usize, usize)>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            location,
+            help: None,
+        }
+    }
+
+    fn warning(message: impl Into<String>, location: Option<(usize, usize)>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            location,
+            help: None,
+        }
+    }
+
+    fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    fn log(&self, config_path: &Utf8Path) {
+        let mut message = self.message.clone();
+        match self.location {
+            Some((line, column)) => {
+                message.push_str(&format!("\n  --> {}:{}:{}", config_path, line, column))
+            }
+            None => message.push_str(&format!("\n  --> {}", config_path)),
+        }
+        if let Some(help) = &self.help {
+            message.push_str(&format!("\n  = help: {}", help));
+        }
+        match self.severity {
+            Severity::Error => error!("{}", message),
+            Severity::Warning => warn!("{}", message),
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "severity": match self.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            },
+            "message": self.message,
+            "line": self.location.map(|(line, _)| line),
+            "column": self.location.map(|(_, column)| column),
+            "help": self.help,
+        })
+    }
+}
+
+/// Checks the config for problems.
+fn validate(
+    package_graph: &PackageGraph,
+    config_path: &Utf8Path,
+    contents: &str,
+) -> Vec<Diagnostic> {
+    let locator = Locator { contents };
+    let mut diagnostics = vec![];
+
+    let doc: Document = match contents.parse() {
+        Ok(doc) => doc,
+        Err(err) => {
+            // The error message includes the location.
+            diagnostics.push(Diagnostic::error(format!("invalid TOML: {}", err), None));
+            return diagnostics;
+        }
+    };
+
+    for (key, _) in doc.iter() {
+        if !HakariConfig::KEYS.contains(&key) {
+            let mut diagnostic = Diagnostic::warning(
+                format!("unknown key {}, which will be ignored", key),
+                locator.key(&[key]),
+            );
+            if let Some(suggestion) = suggest_key(key) {
+                diagnostic = diagnostic.with_help(format!("did you mean {}?", suggestion));
+            }
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    let config: HakariConfig = match contents.parse() {
+        Ok(config) => config,
+        Err(err) => {
+            // The error message includes the location, if known.
+            diagnostics.push(Diagnostic::error(err.to_string(), None));
+            return diagnostics;
+        }
+    };
+    let error_count = diagnostics.len();
+
+    if config.config_version < ConfigVersion::latest() {
+        diagnostics.push(
+            Diagnostic::warning(
+                format!(
+                    "config-version \"{}\" is outdated (latest: \"{}\")",
+                    config.config_version,
+                    ConfigVersion::latest()
+                ),
+                locator.key(&["config-version"]),
+            )
+            .with_help("run `cargo hakari migrate-config` to upgrade"),
+        );
+    }
+
+    let workspace = package_graph.workspace();
+    match &config.builder.hakari_package {
+        Some(name) => {
+            if workspace.member_by_name(name).is_err() {
+                diagnostics.push(
+                    Diagnostic::error(
+                        format!("hakari-package {} is not a workspace member", name),
+                        locator.key(&["hakari-package"]),
+                    )
+                    .with_help("run `cargo hakari init` to create it"),
+                );
+            }
+        }
+        None => diagnostics.push(Diagnostic::warning(
+            "hakari-package is not set, so the workspace-hack can't be written out",
+            None,
+        )),
+    }
+
+    let platforms_offset = locator.key_offset(&["platforms"]);
+    let mut seen = BTreeSet::new();
+    for triple in &config.builder.platforms {
+        let location = platforms_offset.and_then(|offset| locator.string(offset, triple));
+        if let Err(err) = Platform::new(triple.clone(), TargetFeatures::Unknown) {
+            diagnostics.push(
+                Diagnostic::error(format!("platform {} is not recognized", triple), location)
+                    .with_help(err.to_string()),
+            );
+        } else if !seen.insert(triple) {
+            diagnostics.push(Diagnostic::warning(
+                format!("platform {} is listed more than once", triple),
+                location,
+            ));
+        }
+    }
+
+    for (key, excludes) in [
+        ("traversal-excludes", &config.builder.traversal_excludes),
+        ("final-excludes", &config.builder.final_excludes),
+    ] {
+        let offset = locator.key_offset(&[key, "workspace-members"]);
+        for name in &excludes.workspace_members {
+            // Patterns with wildcards are checked while building the config below.
+            if name.contains(['*', '?']) {
+                continue;
+            }
+            if workspace.member_by_name(name).is_err() {
+                diagnostics.push(Diagnostic::error(
+                    format!("{}: workspace member {} not found", key, name),
+                    offset.and_then(|offset| locator.string(offset, name)),
+                ));
+            }
+        }
+    }
+
+    // Check the rest of the config by building it, unless errors were already found.
+    if diagnostics[error_count..]
+        .iter()
+        .all(|diagnostic| diagnostic.severity == Severity::Warning)
+    {
+        if let Err(err) = builder_and_output_from_config(package_graph, config_path, contents, &[])
+        {
+            diagnostics.push(Diagnostic::error(format!("{:#}", err), None));
+        }
+    }
+
+    diagnostics
+}
+
+/// Finds the locations of keys and values in the config, for diagnostics.
+///
+/// This is a best-effort text search, since the TOML parser doesn't retain locations.
+struct Locator<'a> {
+    contents: &'a str,
+}
+
+impl<'a> Locator<'a> {
+    fn key(&self, path: &[&str]) -> Option<(usize, usize)> {
+        self.key_offset(path).map(|offset| self.line_col(offset))
+    }
+
+    fn key_offset(&self, path: &[&str]) -> Option<usize> {
+        let mut offset = 0;
+        for component in path {
+            offset = self.component(offset, component)?;
+        }
+        Some(offset)
+    }
+
+    /// Finds a key, either as a table header or as `key = ...`, at or after `start`.
+    fn component(&self, start: usize, component: &str) -> Option<usize> {
+        let mut line_start = start;
+        for line in self.contents[start..].split_inclusive('\n') {
+            let trimmed = line.trim_start();
+            let header = trimmed.trim_start_matches('[');
+            if header.len() < trimmed.len()
+                && header.starts_with(component)
+                && header[component.len()..].starts_with(['.', ']'])
+            {
+                return Some(line_start + line.len() - trimmed.len());
+            }
+
+            let mut search_from = 0;
+            while let Some(pos) = line[search_from..].find(component) {
+                let pos = search_from + pos;
+                let before = line[..pos].chars().next_back();
+                let after = line[pos + component.len()..].trim_start_matches('"');
+                if before.map_or(true, |c| " \t{,\"".contains(c))
+                    && after.trim_start().starts_with('=')
+                {
+                    return Some(line_start + pos);
+                }
+                search_from = pos + component.len();
+            }
+            line_start += line.len();
+        }
+        None
+    }
+
+    /// Finds a quoted string at or after `start`.
+    fn string(&self, start: usize, value: &str) -> Option<(usize, usize)> {
+        let rest = &self.contents[start..];
+        [format!("\"{}\"", value), format!("'{}'", value)]
+            .iter()
+            .filter_map(|quoted| rest.find(quoted.as_str()))
+            .min()
+            .map(|pos| self.line_col(start + pos))
+    }
+
+    fn line_col(&self, offset: usize) -> (usize, usize) {
+        let before = &self.contents[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+        (line, before[line_start..].chars().count() + 1)
+    }
+}
+
+/// Returns the known key closest to `key`, if any is close enough to be a likely typo.
+fn suggest_key(key: &str) -> Option<&'static str> {
+    HakariConfig::KEYS
+        .iter()
+        .map(|known| (edit_distance(key, known), *known))
+        .filter(|(distance, _)| *distance <= 2)
+        .min()
+        .map(|(_, known)| known)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<_> = b.chars().collect();
+    let mut prev: Vec<_> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(prev[j + 1] + 1).min(current[j] + 1));
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static INPUT: &str = r#"# Comment
+hakari-package = "workspace-hack"
+platfroms = ["x86_64-unknown-linux-gnu"]
+
+[traversal-excludes]
+workspace-members = ["foo", "bar"]
+"#;
+
+    #[test]
+    fn locate_keys() {
+        let locator = Locator { contents: INPUT };
+        assert_eq!(locator.key(&["hakari-package"]), Some((2, 1)));
+        assert_eq!(locator.key(&["platfroms"]), Some((3, 1)));
+        assert_eq!(locator.key(&["traversal-excludes"]), Some((5, 1)));
+        assert_eq!(
+            locator.key(&["traversal-excludes", "workspace-members"]),
+            Some((6, 1))
+        );
+        let offset = locator
+            .key_offset(&["traversal-excludes", "workspace-members"])
+            .unwrap();
+        assert_eq!(locator.string(offset, "bar"), Some((6, 29)));
+        assert_eq!(locator.key(&["platforms"]), None);
+    }
+
+    #[test]
+    fn suggest_keys() {
+        assert_eq!(suggest_key("platfroms"), Some("platforms"));
+        assert_eq!(suggest_key("hakari_package"), Some("hakari-package"));
+        assert_eq!(suggest_key("something-else"), None);
+    }
+
+    #[test]
+    fn set_values() {
+        static CONFIG: &str = r#"hakari-package = "workspace-hack"
+# Comments are preserved.
+resolver = "1" # trailing comment
+"#;
+        let doc: Document = CONFIG.parse().unwrap();
+
+        assert_eq!(
+            set_value(&doc, &["resolver"], "2").unwrap(),
+            CONFIG.replace("\"1\"", "\"2\""),
+            "invalid TOML values fall back to strings"
+        );
+        assert_eq!(
+            set_value(&doc, &["exact-versions"], "true").unwrap(),
+            format!("{}exact-versions = true\n", CONFIG),
+        );
+        assert_eq!(
+            set_value(
+                &doc,
+                &["traversal-excludes", "workspace-members"],
+                r#"["foo"]"#
+            )
+            .unwrap(),
+            format!(
+                "{}\n[traversal-excludes]\nworkspace-members = [\"foo\"]\n",
+                CONFIG
+            ),
+        );
+        assert!(
+            set_value(&doc, &["exact-versions"], "not-a-bool").is_err(),
+            "invalid values are rejected"
+        );
+    }
+
+    #[test]
+    fn config_overrides() {
+        static CONFIG: &str = "hakari-package = \"workspace-hack\"\nresolver = \"1\"\n";
+        assert!("no-equals".parse::<ConfigOverride>().is_err());
+        assert!("=value".parse::<ConfigOverride>().is_err());
+
+        assert_eq!(apply_overrides(CONFIG, &[]).unwrap(), CONFIG);
+        let overrides: Vec<ConfigOverride> = [
+            "resolver=2",
+            r#"platforms = ["x86_64-unknown-linux-gnu"]"#,
+            "resolver=3",
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+        assert_eq!(
+            apply_overrides(CONFIG, &overrides).unwrap(),
+            "hakari-package = \"workspace-hack\"\nresolver = \"3\"\n\
+             platforms = [\"x86_64-unknown-linux-gnu\"]\n",
+            "later overrides take precedence"
+        );
+        let invalid: ConfigOverride = "exact-versions=not-a-bool".parse().unwrap();
+        assert!(apply_overrides(CONFIG, &[invalid]).is_err());
+        let unknown: ConfigOverride = "platfroms=[]".parse().unwrap();
+        assert!(apply_overrides(CONFIG, &[unknown]).is_err());
+    }
+}
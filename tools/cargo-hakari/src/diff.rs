@@ -0,0 +1,324 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    command::make_builder_and_output, git::GitRepo, message, output::OutputContext, timings::Phase,
+};
+use color_eyre::eyre::{Result, WrapErr};
+use guppy::{
+    graph::{cargo::BuildPlatform, PackageGraph, PackageMetadata},
+    Version,
+};
+use hakari::Hakari;
+use log::info;
+use owo_colors::OwoColorize;
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+/// Computes the workspace-hack at the `base` git revision, and prints out how the workspace-hack
+/// for the current workspace differs from it.
+///
+/// Returns 1 if there are any differences, similar to `cargo hakari generate --diff`.
+pub(crate) fn diff_base(
+    package_graph: &PackageGraph,
+    base: &str,
+    output: OutputContext,
+) -> Result<i32> {
+    let repo = GitRepo::new(package_graph.workspace().root())?;
+    let base_checkout = repo.checkout(&repo.resolve(base)?)?;
+    let base_graph = base_checkout
+        .package_graph(&output)
+        .wrap_err_with(|| format!("error reading workspace at {}", base))?;
+
+    let head_lines = compute_lines(package_graph, &output)?;
+    let base_lines = compute_lines(&base_graph, &output)
+        .wrap_err_with(|| format!("error computing workspace-hack at {}", base))?;
+
+    let mut exit_code = 0;
+    for (hakari_package, (hakari, lines)) in &head_lines {
+        let empty = BTreeMap::new();
+        let base_lines = match base_lines.get(hakari_package) {
+            Some((_, lines)) => lines,
+            None => {
+                info!(
+                    "{} not present at {}",
+                    hakari_package.style(output.styles.package_name),
+                    base,
+                );
+                &empty
+            }
+        };
+        let changes = LineChange::compute(base_lines, lines, hakari);
+
+        if output.is_json() {
+            message::emit(message::hakari_diff(
+                hakari_package,
+                base,
+                changes.iter().map(LineChange::to_json).collect(),
+            ))?;
+        }
+        if changes.is_empty() {
+            info!(
+                "no changes to {} since {}",
+                hakari_package.style(output.styles.package_name),
+                base,
+            );
+            continue;
+        }
+
+        exit_code = 1;
+        if !output.is_json() {
+            let mut out = String::new();
+            for change in &changes {
+                change.fmt(&mut out, &output)?;
+            }
+            info!(
+                "changes to {} since {}:\n{}",
+                hakari_package.style(output.styles.package_name),
+                base,
+                out,
+            );
+        }
+    }
+    for hakari_package in base_lines.keys() {
+        if !head_lines.contains_key(hakari_package) {
+            info!(
+                "{} was removed since {}",
+                hakari_package.style(output.styles.package_name),
+                base,
+            );
+            exit_code = 1;
+        }
+    }
+
+    Ok(exit_code)
+}
+
+/// Lines in the workspace-hack, identified independently of the package graph they're from.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) struct LineKey {
+    pub(crate) name: String,
+    pub(crate) version: Version,
+    pub(crate) source: String,
+    pub(crate) build_platform: BuildPlatform,
+    pub(crate) platform: Option<String>,
+}
+
+pub(crate) type Lines<'g> = BTreeMap<LineKey, (PackageMetadata<'g>, BTreeSet<&'g str>)>;
+
+/// Computes the workspace-hack lines for every Hakari package, keyed by Hakari package name.
+pub(crate) fn compute_lines<'g>(
+    package_graph: &'g PackageGraph,
+    output: &OutputContext,
+) -> Result<BTreeMap<String, (Hakari<'g>, Lines<'g>)>> {
+    let (builder, _) = make_builder_and_output(package_graph, &output.config_overrides)?;
+    let builders = if builder.shards().len() == 0 {
+        vec![builder]
+    } else {
+        builder.shard_builders()
+    };
+
+    Ok(builders
+        .into_iter()
+        .map(|builder| {
+            let hakari_package = builder
+                .hakari_package()
+                .expect("hakari-package must be specified in hakari.toml")
+                .name()
+                .to_owned();
+            let hakari = output.timings.time(Phase::Compute, || builder.compute());
+            let platforms: Vec<_> = hakari.builder().platforms().collect();
+            let lines = hakari
+                .output_map
+                .iter()
+                .flat_map(|(output_key, deps)| {
+                    let platform = output_key.platform_idx.map(|idx| platforms[idx].to_owned());
+                    deps.values().map(move |(package, features)| {
+                        let key = LineKey {
+                            name: package.name().to_owned(),
+                            version: package.version().clone(),
+                            source: package.source().to_string(),
+                            build_platform: output_key.build_platform,
+                            platform: platform.clone(),
+                        };
+                        (key, (*package, features.clone()))
+                    })
+                })
+                .collect();
+            (hakari_package, (hakari, lines))
+        })
+        .collect())
+}
+
+/// A change to a line in the workspace-hack.
+#[derive(Debug)]
+struct LineChange {
+    key: LineKey,
+    kind: LineChangeKind,
+    added_features: Vec<String>,
+    removed_features: Vec<String>,
+    /// The workspace packages that cause the added features to be built.
+    caused_by: Vec<String>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum LineChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl LineChangeKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            LineChangeKind::Added => "added",
+            LineChangeKind::Removed => "removed",
+            LineChangeKind::Changed => "changed",
+        }
+    }
+}
+
+impl LineChange {
+    fn compute(base: &Lines<'_>, head: &Lines<'_>, head_hakari: &Hakari<'_>) -> Vec<Self> {
+        let keys: BTreeSet<_> = base.keys().chain(head.keys()).collect();
+        keys.into_iter()
+            .filter_map(|key| {
+                let base_features = base.get(key).map(|(_, features)| features);
+                let (kind, added_features, removed_features) = match (base_features, head.get(key))
+                {
+                    (None, Some((_, head_features))) => {
+                        (LineChangeKind::Added, to_strings(head_features), vec![])
+                    }
+                    (Some(base_features), None) => {
+                        (LineChangeKind::Removed, vec![], to_strings(base_features))
+                    }
+                    (Some(base_features), Some((_, head_features))) => {
+                        if base_features == head_features {
+                            return None;
+                        }
+                        (
+                            LineChangeKind::Changed,
+                            to_strings(head_features.difference(base_features)),
+                            to_strings(base_features.difference(head_features)),
+                        )
+                    }
+                    (None, None) => unreachable!("key is from one of the maps"),
+                };
+
+                let caused_by = match head.get(key) {
+                    Some((package, _)) => {
+                        caused_by(head_hakari, *package, key.build_platform, &added_features)
+                    }
+                    None => vec![],
+                };
+                Some(Self {
+                    key: key.clone(),
+                    kind,
+                    added_features,
+                    removed_features,
+                    caused_by,
+                })
+            })
+            .collect()
+    }
+
+    fn fmt(&self, out: &mut String, output: &OutputContext) -> Result<()> {
+        let (marker, style) = match self.kind {
+            LineChangeKind::Added => ("+", output.styles.check_passed),
+            LineChangeKind::Removed => ("-", output.styles.check_failed),
+            LineChangeKind::Changed => ("~", output.styles.command),
+        };
+        write!(
+            out,
+            "  {} {} v{} (on the {} platform, {})",
+            marker.style(style),
+            self.key.name.style(output.styles.package_name),
+            self.key.version.style(output.styles.package_version),
+            self.key.build_platform,
+            match &self.key.platform {
+                Some(platform) => platform.style(output.styles.platform).to_string(),
+                None => "all platforms".to_owned(),
+            },
+        )?;
+        let mut features: Vec<_> = self
+            .added_features
+            .iter()
+            .map(|feature| format!("+{}", feature))
+            .collect();
+        features.extend(
+            self.removed_features
+                .iter()
+                .map(|feature| format!("-{}", feature)),
+        );
+        if !features.is_empty() {
+            write!(out, ": {}", features.join(" "))?;
+        }
+        writeln!(out)?;
+        if !self.caused_by.is_empty() {
+            writeln!(out, "      caused by: {}", self.caused_by.join(", "))?;
+        }
+        Ok(())
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "kind": self.kind.as_str(),
+            "name": self.key.name,
+            "version": self.key.version.to_string(),
+            "source": self.key.source,
+            "build-platform": self.key.build_platform.to_string(),
+            "platform": self.key.platform,
+            "added-features": self.added_features,
+            "removed-features": self.removed_features,
+            "caused-by": self.caused_by,
+        })
+    }
+}
+
+/// Returns the workspace packages that cause any of `features` to be built for `package`.
+///
+/// If `features` is empty (e.g. for a line that only had features removed), returns the workspace
+/// packages that cause the package to be built at all on the build platform.
+pub(crate) fn caused_by(
+    hakari: &Hakari<'_>,
+    package: PackageMetadata<'_>,
+    build_platform: BuildPlatform,
+    features: &[String],
+) -> Vec<String> {
+    let summary = match hakari.explain(package.id()) {
+        Ok(explain) => explain.to_summary(),
+        // Can happen for lines added by post-computation fixups.
+        Err(_) => return vec![],
+    };
+    let feature_sets = match build_platform {
+        BuildPlatform::Target => &summary.target,
+        BuildPlatform::Host => &summary.host,
+    };
+
+    let names: BTreeSet<_> = feature_sets
+        .iter()
+        .filter(|feature_set| {
+            features.is_empty()
+                || features
+                    .iter()
+                    .any(|feature| feature_set.features.contains(feature))
+        })
+        .flat_map(|feature_set| {
+            feature_set
+                .workspace_packages
+                .iter()
+                .map(|package| package.name.clone())
+        })
+        .collect();
+    names.into_iter().collect()
+}
+
+fn to_strings<'a>(features: impl IntoIterator<Item = &'a &'a str>) -> Vec<String> {
+    features
+        .into_iter()
+        .map(|feature| (*feature).to_owned())
+        .collect()
+}
@@ -11,6 +11,25 @@
 //!
 //! # Common options
 //!
+//! ## config-version
+//!
+//! The version of the configuration format. Configs created by `cargo hakari init` set this to the
+//! latest version.
+//!
+//! Possible values:
+//! * *"1"*: the original format, used if `config-version` isn't specified. In this version,
+//!   `dep-format-version` defaults to "1".
+//! * *"2"*: options whose defaults have changed over time must be specified explicitly. Currently,
+//!   this is `dep-format-version`.
+//!
+//! To upgrade a config to the latest version without changing its behavior, run
+//! `cargo hakari migrate-config`. This writes out any defaults that the old version relied on,
+//! and preserves comments and formatting.
+//!
+//! ```toml
+//! config-version = "2"
+//! ```
+//!
 //! ## hakari-package
 //!
 //! The name of the hakari-managed crate in the workspace. Must be specified. For example:
@@ -21,10 +40,16 @@
 //!
 //! ## resolver
 //!
-//! The version of the Cargo feature resolver to use. Version 2 is highly recommended.
+//! The version of the Cargo feature resolver to use. Version 2 or above is highly recommended.
 //! For more, see this [Rust blog post](https://blog.rust-lang.org/2021/03/25/Rust-1.51.0.html#cargos-new-feature-resolver).
 //!
-//! Defaults to "1", but `.config/hakari.toml` files created by `cargo hakari init` set it to "2".
+//! The possible values are `"1"`, `"2"` and `"3"`. Version 3 makes the selection of dependency
+//! versions for `Cargo.lock` aware of the workspace's `rust-version`, but resolves features the
+//! same way as version 2.
+//!
+//! Defaults to the version Cargo uses for the workspace: the `resolver` set in the workspace's
+//! root `Cargo.toml`, or the version implied by the edition of the root package if it isn't set.
+//! Setting this option forces `cargo hakari` to use a particular version instead.
 //!
 //! ```toml
 //! resolver = "2"
@@ -46,6 +71,23 @@
 //!     warns if build metadata is added to version strings.)
 //! * *"4"*: Fixes dependency name sorting in the workspace-hack's `Cargo.toml` to always be
 //!   alphabetical. Previously, dependencies would sometimes not be sorted correctly.
+//! * *"5"*: Writes third-party dependencies to `[workspace.dependencies]` in the workspace root's
+//!   `Cargo.toml`, and has the workspace-hack's `Cargo.toml` reference them with
+//!   `workspace = true`. The workspace root's `Cargo.toml` must contain a generated section within
+//!   its `[workspace.dependencies]` table:
+//!
+//!   ```toml
+//!   [workspace.dependencies]
+//!   # Dependencies managed by hand go here.
+//!
+//!   ### BEGIN HAKARI SECTION
+//!   ### END HAKARI SECTION
+//!   ```
+//!
+//!   Dependencies managed by hakari must not also be declared by hand in this table: if they are,
+//!   `cargo hakari generate` fails with an error naming the dependency.
+//!
+//!   This version is opt-in, and is not set by `cargo hakari init`.
 //!
 //! Defaults to "1", but
 //! - starting `cargo hakari 0.9.8`, `.config/hakari.toml` files created by
@@ -97,6 +139,11 @@
 //! ]
 //! ```
 //!
+//! Platforms can also be managed with `cargo hakari platform add <triple>`,
+//! `cargo hakari platform remove <triple>`, and `cargo hakari platform list`. `add` and `remove`
+//! update this list, then regenerate the workspace-hack crate and show the resulting diff. Pass in
+//! `--dry-run` to only show the diff.
+//!
 //! ## traversal-excludes
 //!
 //! Crates to exclude while traversing the dependency graph.
@@ -112,14 +159,22 @@
 //! This is generally useful for crates that have mutually exclusive features, and that turn on
 //! mutually exclusive features in their transitive dependencies.
 //!
+//! Each entry must match at least one package: names and version ranges that don't match anything
+//! are reported as errors.
+//!
 //! Defaults to an empty set.
 //!
 //! ```toml
 //! [traversal-excludes]
-//! workspace-members = ["my-crate", "my-other-crate"]
+//! ## Names may contain the wildcards `*` and `?`.
+//! workspace-members = ["my-crate", "my-other-crate", "my-benchmarks-*"]
 //! third-party = [
-//!     ## Third-party crates accept semver ranges.
-//!     { name = "mutually-exclusive-crate", version = "1.0" },
+//!     ## Third-party crates accept semver ranges. Only matching versions are excluded, so this
+//!     ## excludes the legacy 0.x line of a crate while keeping newer versions unified.
+//!     { name = "mutually-exclusive-crate", version = "0.5" },
+//!
+//!     ## Names of third-party crates may also contain wildcards.
+//!     { name = "my-sys-crates-*", version = ">=1.2, <2" },
 //!
 //!     ## The version specifier can be skipped to include all versions of a crate.
 //!     ## (Cryptography-related crates often use features to switch on different backends.)
@@ -155,11 +210,66 @@
 //!     ## It is a good candidate for exclusion from the final output.
 //!     { name = "fail" },
 //!
-//!     ## Version specifiers and git/path dependencies work similarly to traversal-excludes
-//!     ## above.
+//!     ## Version specifiers, wildcards and git/path dependencies work similarly to
+//!     ## traversal-excludes above.
 //! ]
 //! ```
 //!
+//! ## max-features
+//!
+//! Features of third-party crates that must never be written out to the workspace-hack crate.
+//!
+//! Features that would enable a denied feature, such as `default`, are not written out either.
+//! Unlike `final-excludes`, the other features of the crate are still unified.
+//!
+//! Denying a feature doesn't stop workspace crates from enabling it. If some workspace crates enable
+//! a denied feature and others don't, the crate is built with more than one feature set, and
+//! `cargo hakari verify` reports the conflict.
+//!
+//! Defaults to an empty set.
+//!
+//! ```toml
+//! ## Never build OpenSSL from source unless a crate asks for it explicitly.
+//! [max-features.openssl]
+//! deny = ["vendored"]
+//! ```
+//!
+//! ## shards
+//!
+//! Additional workspace-hack crates, each covering a subset of workspace members.
+//!
+//! With a single workspace-hack crate, changing the dependencies of any workspace member can cause
+//! rebuilds across the entire workspace. If a workspace consists of mostly-disjoint trees of
+//! crates, each tree can be given its own workspace-hack crate, with features unified only across
+//! the members of that tree.
+//!
+//! Each shard specifies its workspace-hack crate through `hakari-package`, and the members it
+//! covers through:
+//! * `members`: names of workspace members. Names may contain the wildcards `*` and `?`.
+//! * `paths`: directories relative to the workspace root. Workspace members within these
+//!   directories are covered.
+//!
+//! A workspace member is covered by the first shard that matches it. Workspace members not covered
+//! by any shard are covered by the top-level `hakari-package`, if specified.
+//!
+//! `cargo hakari generate`, `verify`, `manage-deps`, `remove-deps` and `disable` operate on every
+//! workspace-hack crate. Shards can't currently be used with `dep-format-version = "5"`.
+//!
+//! Defaults to no shards.
+//!
+//! ```toml
+//! hakari-package = "workspace-hack"
+//!
+//! [[shards]]
+//! hakari-package = "product-a-hack"
+//! members = ["product-a-*"]
+//! paths = ["product-a"]
+//!
+//! [[shards]]
+//! hakari-package = "product-b-hack"
+//! paths = ["product-b"]
+//! ```
+//!
 //! ## registries
 //!
 //! Alternate registries,
@@ -169,6 +279,11 @@
 //! This is a temporary workaround until [Cargo issue #9052](https://github.com/rust-lang/cargo/issues/9052)
 //! is resolved.
 //!
+//! Registries declared in Cargo's own configuration files (`.cargo/config.toml` in the workspace
+//! root or its ancestors, and `$CARGO_HOME/config.toml`) are discovered automatically, so they only
+//! need to be listed here if they aren't declared there. If a registry is listed in both places,
+//! the entry here takes precedence.
+//!
 //! Defaults to an empty set.
 //!
 //! ```toml
@@ -193,6 +308,79 @@
 //! exact-versions = true
 //! ```
 //!
+//! ## version-style
+//!
+//! The style of version requirements to output. Takes precedence over `exact-versions`.
+//!
+//! The possible options are:
+//! * `"minimal"`: the loosest semver range that matches the version in use, e.g. `"1"`.
+//! * `"exact"`: the exact version in use as a semver range, e.g. `"1.4.2"`. This is the same as
+//!   `exact-versions = true`.
+//! * `"pinned"`: pin to the exact version in use, e.g. `"=1.4.2"`. With this style, the
+//!   workspace-hack crate never causes a different version of a dependency to be selected, which
+//!   is useful for teams that check in `Cargo.lock` and want fully reproducible builds.
+//! * `"workspace"`: the loosest version requirement that crates in the dependency graph already
+//!   specify for the dependency. With this style, the workspace-hack crate never raises the
+//!   minimum version of a dependency.
+//!
+//! Defaults to `"minimal"`, or `"exact"` if `exact-versions` is set to true.
+//!
+//! ```toml
+//! version-style = "pinned"
+//! ```
+//!
+//! Version requirements are only output for dependencies from registries. Dependencies from git
+//! repositories and from paths outside the workspace are unified as well, and are output with
+//! the same source as in the `Cargo.lock`:
+//!
+//! ```toml
+//! my-fork = { git = "https://github.com/my-org/my-fork", rev = "0227f048", features = ["std"] }
+//! local-dep = { path = "../../vendor/local-dep", default-features = false }
+//! ```
+//!
+//! ## group-by-source
+//!
+//! Group the dependencies in each section of the workspace-hack's `Cargo.toml` by where they come
+//! from, with a comment before each group. Dependencies from crates.io come first, followed by
+//! alternate registries in order of name, then git dependencies, then path dependencies. Within
+//! each group, dependencies are sorted by name.
+//!
+//! Normal and build dependencies are already output in separate sections (`[dependencies]` and
+//! `[build-dependencies]`), so each of those is grouped separately.
+//!
+//! Defaults to false.
+//!
+//! ```toml
+//! group-by-source = true
+//! ```
+//!
+//! ## max-line-width
+//!
+//! Wrap dependency lines longer than this many characters, by putting each feature on its own line.
+//! This keeps diffs to the workspace-hack small when features are added or removed.
+//!
+//! By default, lines are never wrapped.
+//!
+//! ```toml
+//! max-line-width = 100
+//! ```
+//!
+//! ## cfg-platforms
+//!
+//! Output platform-specific dependencies under `cfg()` expressions such as `cfg(unix)`, rather
+//! than under each target triple in [`platforms`](#platforms). A dependency line that's the same
+//! across several platforms is then output once, under an expression that matches exactly those
+//! platforms. If there's no such expression, the dependency is output under each target triple.
+//!
+//! `cfg()` expressions may also match platforms that aren't listed in `platforms`, so this may
+//! cause some features to be enabled on those platforms as well.
+//!
+//! Defaults to false.
+//!
+//! ```toml
+//! cfg-platforms = true
+//! ```
+//!
 //! # Advanced options
 //!
 //! ## unify-target-host
@@ -202,9 +390,9 @@
 //! If the same dependency is built on both the target and host platforms, this option controls
 //! whether and how they should be unified.
 //!
-//! The possible options are `"none"`, `"auto"`, `"unify-if-both"`, and
-//! `"replicate-target-on-host"`. For more about these options, see the documentation for
-//! [`UnifyTargetHost`](hakari::UnifyTargetHost).
+//! The possible options are `"none"`, `"auto"`, `"unify-if-both"`,
+//! `"replicate-target-on-host"`, `"proc-macro-dependencies"`, and `"emit-both"`. For more about
+//! these options, see the documentation for [`UnifyTargetHost`](hakari::UnifyTargetHost).
 //!
 //! Defaults to `"auto"`.
 //!
@@ -212,6 +400,25 @@
 //! unify-target-host = "replicate-target-on-host"
 //! ```
 //!
+//! To find out which option caused a dependency to be unified across target and host, run
+//! `cargo hakari explain <dependency>`.
+//!
+//! ## platform-unify-target-host
+//!
+//! Overrides `unify-target-host` for dependencies specific to some of the
+//! [platforms](#platforms), as a map of target triple to option. Dependencies built on every
+//! platform are always unified according to `unify-target-host`.
+//!
+//! Defaults to an empty map.
+//!
+//! ```toml
+//! unify-target-host = "unify-if-both"
+//!
+//! [platform-unify-target-host]
+//! # Builds for Windows are cross-compiled, so the target and host can't share any dependencies.
+//! x86_64-pc-windows-msvc = "none"
+//! ```
+//!
 //! ## output-single-feature
 //!
 //! By default, `cargo hakari` only outputs lines corresponding to third-party dependencies which
@@ -226,3 +433,19 @@
 //! ```toml
 //! output-single-feature = true
 //! ```
+//!
+//! ## exclude-dev-dependencies
+//!
+//! By default, `cargo hakari` unifies feature sets across builds with and without
+//! dev-dependencies, so that switching between `cargo build` and `cargo test` doesn't cause
+//! rebuilds. Setting this option to true will cause `cargo hakari` to ignore dev-dependencies
+//! entirely, so that the workspace-hack only reflects normal and build dependencies.
+//!
+//! This is useful if dev-dependencies like test or benchmarking frameworks pull in large feature
+//! sets that don't matter for production builds.
+//!
+//! Defaults to false.
+//!
+//! ```toml
+//! exclude-dev-dependencies = true
+//! ```
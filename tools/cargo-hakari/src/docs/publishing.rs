@@ -23,6 +23,21 @@
 //! crate. The dependency will be re-added afterwards, unless the command is interrupted with ctrl-C
 //! (in which case you can use `cargo hakari manage-deps` to finish the job.)
 //!
+//! To publish several crates at once, pass in `-p` multiple times, or use `--workspace` to publish
+//! every crate that doesn't have `publish = false` set:
+//!
+//! ```sh
+//! cargo hakari publish -p <crate1> -p <crate2>
+//! cargo hakari publish --workspace --exclude <crate3>
+//! ```
+//!
+//! Crates are published in dependency order, so a crate is always published after the workspace
+//! crates it depends on. `cargo publish` waits for each crate to be available in the registry's
+//! index before returning (this requires Rust 1.66 or above), so crates depending on it can be
+//! published right after. The workspace-hack dependency is removed from all the crates before the
+//! first one is published, and re-added after the last one is published. If publishing a crate
+//! fails, the dependency is re-added and the crates that were already published are listed.
+//!
 //! This works out of the box. However, it has the downside of requiring `cargo hakari publish`. If
 //! you don't have control over the commands run while publishing the package, it won't be possible
 //! to use this method.
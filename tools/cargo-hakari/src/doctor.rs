@@ -0,0 +1,319 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    cargo_cli::CargoCli, command::make_builder_and_output, helpers::plural, message,
+    output::OutputContext, timings::Phase,
+};
+use camino::Utf8Path;
+use color_eyre::eyre::{Result, WrapErr};
+use guppy::graph::{cargo::CargoResolverVersion, ExternalSource, PackageGraph};
+use hakari::{
+    cli_ops::WorkspaceOpSummary, summaries::HakariBuilderSummary, DepFormatVersion, HakariBuilder,
+};
+use log::{info, warn};
+use owo_colors::OwoColorize;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use toml_edit::Document;
+
+/// Checks the workspace for common problems with its Hakari setup, printing out each problem
+/// found along with a suggested fix.
+///
+/// Returns 1 if any problems were found.
+pub(crate) fn doctor(output: OutputContext) -> Result<i32> {
+    let mut findings = vec![];
+    // Building the package graph updates the lockfile, so check it first.
+    let lockfile_finding = check_lockfile(&output)?;
+    // With --locked, the package graph can't be built until the lockfile is updated.
+    let skip_workspace = lockfile_finding.is_some() && output.cargo_opts.is_locked();
+    findings.extend(lockfile_finding);
+    if !skip_workspace {
+        findings.extend(check_workspace(&output)?);
+    }
+
+    if output.is_json() {
+        message::emit(message::doctor(
+            findings.iter().map(Finding::to_json).collect(),
+        ))?;
+    }
+    if findings.is_empty() {
+        info!("no problems found");
+        return Ok(0);
+    }
+
+    for finding in &findings {
+        warn!(
+            "{}\n  fix: {}",
+            finding.message,
+            finding.fix.style(output.styles.command),
+        );
+    }
+    info!("found {}", plural(findings.len(), "problem"));
+    Ok(1)
+}
+
+/// A problem found by `cargo hakari doctor`.
+#[derive(Clone, Debug)]
+struct Finding {
+    /// The name of the check that found the problem.
+    check: &'static str,
+    message: String,
+    /// A suggested command or action that fixes the problem.
+    fix: String,
+}
+
+impl Finding {
+    fn to_json(&self) -> Value {
+        json!({
+            "check": self.check,
+            "message": self.message,
+            "fix": self.fix,
+        })
+    }
+}
+
+/// Runs the checks that need the package graph.
+fn check_workspace(output: &OutputContext) -> Result<Vec<Finding>> {
+    let package_graph = output
+        .timings
+        .time(Phase::Metadata, || {
+            output.cargo_opts.metadata_command().build_graph()
+        })
+        .wrap_err("building package graph failed")?;
+    let mut findings = check_excluded_crates(&package_graph);
+
+    match make_builder_and_output(&package_graph, &output.config_overrides) {
+        Ok((builder, _)) => {
+            findings.extend(check_workspace_deps(&builder));
+            findings.extend(check_registries(&builder)?);
+            findings.extend(check_resolver(&builder)?);
+            findings.extend(check_dep_format_version(&builder));
+        }
+        Err(err) => findings.push(Finding {
+            check: "config",
+            message: format!("the Hakari config couldn't be loaded: {:#}", err),
+            fix: "cargo hakari config validate".to_owned(),
+        }),
+    }
+    Ok(findings)
+}
+
+/// Checks that `Cargo.lock` exists and is up to date with the workspace manifests.
+fn check_lockfile(output: &OutputContext) -> Result<Option<Finding>> {
+    let mut cargo_cli = CargoCli::new("metadata", output.clone());
+    cargo_cli.add_args(["--format-version", "1"]);
+    if !output.cargo_opts.is_locked() {
+        cargo_cli.add_arg("--locked");
+    }
+    let result = cargo_cli
+        .to_expression()
+        .stdout_null()
+        .stderr_capture()
+        .unchecked()
+        .run()
+        .wrap_err("running cargo metadata failed")?;
+
+    // Other errors (e.g. invalid manifests) are reported while building the package graph.
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    if !result.status.success() && stderr.contains("lock file") {
+        Ok(Some(Finding {
+            check: "lockfile",
+            message: "Cargo.lock is missing or out of date".to_owned(),
+            fix: "cargo update --workspace".to_owned(),
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Checks for path dependencies that aren't workspace members, and so don't depend on the
+/// workspace-hack.
+fn check_excluded_crates(package_graph: &PackageGraph) -> Vec<Finding> {
+    let workspace_excludes = workspace_excludes(package_graph.workspace().root());
+    package_graph
+        .packages()
+        .filter(|package| package.source().is_path())
+        .map(|package| {
+            let path = package
+                .source()
+                .local_path()
+                .expect("path packages have a local path");
+            let fix = if path.starts_with("..") {
+                format!(
+                    "move {} inside the workspace root, then add it to workspace.members",
+                    path
+                )
+            } else if workspace_excludes.contains(path.as_str()) {
+                format!(
+                    "move \"{}\" from workspace.exclude to workspace.members in the root \
+                     Cargo.toml",
+                    path
+                )
+            } else {
+                format!(
+                    "add \"{}\" to workspace.members in the root Cargo.toml",
+                    path
+                )
+            };
+            Finding {
+                check: "excluded-crate",
+                message: format!(
+                    "{} at {} is a path dependency but not a workspace member, so it isn't \
+                     unified with the workspace-hack",
+                    package.name(),
+                    path,
+                ),
+                fix,
+            }
+        })
+        .collect()
+}
+
+/// Returns the paths listed in `workspace.exclude` in the root Cargo.toml.
+///
+/// This is only used to improve suggested fixes, so errors are ignored.
+fn workspace_excludes(workspace_root: &Utf8Path) -> BTreeSet<String> {
+    let Ok(contents) = std::fs::read_to_string(workspace_root.join("Cargo.toml")) else {
+        return BTreeSet::new();
+    };
+    let Ok(document) = contents.parse::<Document>() else {
+        return BTreeSet::new();
+    };
+    document
+        .get("workspace")
+        .and_then(|workspace| workspace.get("exclude"))
+        .and_then(|exclude| exclude.as_array())
+        .map(|exclude| {
+            exclude
+                .iter()
+                .filter_map(|path| path.as_str())
+                .map(|path| path.trim_end_matches('/').to_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Checks that every workspace crate depends on the workspace-hack, and that excluded crates
+/// don't.
+fn check_workspace_deps(builder: &HakariBuilder<'_>) -> Vec<Finding> {
+    let ops = builder
+        .manage_dep_ops(&builder.graph().resolve_workspace())
+        .expect("hakari-package must be specified in hakari.toml");
+
+    let mut missing = BTreeSet::new();
+    let mut excluded = BTreeSet::new();
+    for op in ops.to_summary() {
+        match op {
+            WorkspaceOpSummary::AddDependency { packages, .. } => {
+                missing.extend(packages.into_iter().map(|package| package.name));
+            }
+            WorkspaceOpSummary::RemoveDependency { packages, .. } => {
+                excluded.extend(packages.into_iter().map(|package| package.name));
+            }
+            _ => {}
+        }
+    }
+
+    let mut findings = vec![];
+    if !missing.is_empty() {
+        findings.push(Finding {
+            check: "workspace-hack-dep",
+            message: format!(
+                "the workspace-hack dependency is missing or out of date in {}: {}",
+                plural(missing.len(), "crate"),
+                missing.into_iter().collect::<Vec<_>>().join(", "),
+            ),
+            fix: "cargo hakari manage-deps".to_owned(),
+        });
+    }
+    if !excluded.is_empty() {
+        findings.push(Finding {
+            check: "workspace-hack-dep",
+            message: format!(
+                "crates excluded from the workspace-hack still depend on it: {}",
+                excluded.into_iter().collect::<Vec<_>>().join(", "),
+            ),
+            fix: "cargo hakari manage-deps".to_owned(),
+        });
+    }
+    findings
+}
+
+/// Checks that every alternate registry used by a dependency is known to Hakari.
+fn check_registries(builder: &HakariBuilder<'_>) -> Result<Vec<Finding>> {
+    let summary = HakariBuilderSummary::new(builder).wrap_err("error summarizing Hakari config")?;
+    let known: BTreeSet<_> = summary
+        .registries
+        .values()
+        .map(|url| url.as_str())
+        .collect();
+
+    let unknown: BTreeSet<_> = builder
+        .graph()
+        .packages()
+        .filter(|package| !package.source().is_crates_io())
+        .filter_map(|package| match package.source().parse_external() {
+            Some(ExternalSource::Registry(url)) if !known.contains(url) => Some(url),
+            _ => None,
+        })
+        .collect();
+
+    Ok(unknown
+        .into_iter()
+        .map(|url| Finding {
+            check: "registry",
+            message: format!(
+                "dependencies are fetched from the registry at {}, which isn't listed in \
+                 the Hakari config or in .cargo/config.toml",
+                url,
+            ),
+            fix: format!(
+                "add `<name> = {{ index = \"{}\" }}` to the [registries] table in the Hakari \
+                 config",
+                url,
+            ),
+        })
+        .collect())
+}
+
+/// Checks that the resolver version in the Hakari config matches the one Cargo uses.
+fn check_resolver(builder: &HakariBuilder<'_>) -> Result<Option<Finding>> {
+    let workspace_resolver = builder
+        .read_workspace_resolver()
+        .wrap_err("error reading resolver version from workspace Cargo.toml")?;
+    if builder.resolver() == workspace_resolver {
+        return Ok(None);
+    }
+
+    let version = resolver_str(workspace_resolver)?;
+    Ok(Some(Finding {
+        check: "resolver",
+        message: format!(
+            "the Hakari config uses resolver version {}, but the workspace uses version {}",
+            resolver_str(builder.resolver())?,
+            version,
+        ),
+        fix: format!("cargo hakari config set resolver {}", version),
+    }))
+}
+
+/// Checks that the workspace-hack dependency lines use the latest format.
+fn check_dep_format_version(builder: &HakariBuilder<'_>) -> Option<Finding> {
+    let current = builder.dep_format_version();
+    let latest = DepFormatVersion::latest();
+    (current < latest).then(|| Finding {
+        check: "dep-format-version",
+        message: format!(
+            "the Hakari config uses dep-format-version {}, but the latest version is {}",
+            current, latest,
+        ),
+        fix: "cargo hakari upgrade-format".to_owned(),
+    })
+}
+
+/// Returns a resolver version the way it's written in Cargo.toml and the Hakari config.
+fn resolver_str(resolver: CargoResolverVersion) -> Result<String> {
+    let value = serde_json::to_value(resolver).wrap_err("error serializing resolver version")?;
+    Ok(value.as_str().unwrap_or_default().to_owned())
+}
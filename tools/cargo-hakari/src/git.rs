@@ -0,0 +1,362 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Helpers for inspecting the workspace at other git revisions.
+
+use crate::{output::OutputContext, timings::Phase};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use guppy::{graph::PackageGraph, MetadataCommand};
+use hakari::summaries::{DEFAULT_CONFIG_PATH, FALLBACK_CONFIG_PATH};
+use log::warn;
+use std::fs;
+use tempfile::TempDir;
+
+/// The git repository containing the workspace.
+#[derive(Clone, Debug)]
+pub(crate) struct GitRepo {
+    toplevel: Utf8PathBuf,
+    workspace_root: Utf8PathBuf,
+    /// The path of the workspace root, relative to `toplevel`.
+    rel_root: Utf8PathBuf,
+}
+
+impl GitRepo {
+    /// Finds the git repository containing the workspace.
+    pub(crate) fn new(workspace_root: &Utf8Path) -> Result<Self> {
+        let toplevel = duct::cmd!("git", "rev-parse", "--show-toplevel")
+            .dir(workspace_root)
+            .read()
+            .wrap_err("error finding git repository for workspace")?;
+        let toplevel = Utf8Path::new(toplevel.trim())
+            .canonicalize_utf8()
+            .wrap_err("error canonicalizing git repository root")?;
+        let canonical_root = workspace_root
+            .canonicalize_utf8()
+            .wrap_err("error canonicalizing workspace root")?;
+        let rel_root = canonical_root
+            .strip_prefix(&toplevel)
+            .wrap_err("workspace root isn't inside the git repository")?
+            .to_path_buf();
+
+        Ok(Self {
+            toplevel,
+            workspace_root: workspace_root.to_path_buf(),
+            rel_root,
+        })
+    }
+
+    /// Returns the root of the git repository.
+    pub(crate) fn toplevel(&self) -> &Utf8Path {
+        &self.toplevel
+    }
+
+    /// Resolves a revision to a full commit hash.
+    pub(crate) fn resolve(&self, rev: &str) -> Result<String> {
+        let commit = duct::cmd!(
+            "git",
+            "rev-parse",
+            "--verify",
+            format!("{}^{{commit}}", rev)
+        )
+        .dir(&self.toplevel)
+        .stderr_null()
+        .read()
+        .map_err(|_| eyre!("{} is not a valid git revision", rev))?;
+        Ok(commit.trim().to_owned())
+    }
+
+    /// Returns the commits reachable from `HEAD` but not from `since`, oldest first, following
+    /// only the first parent of merge commits.
+    pub(crate) fn first_parent_history(&self, since: Option<&str>) -> Result<Vec<String>> {
+        let range = match since {
+            Some(since) => format!("{}..HEAD", since),
+            None => "HEAD".to_owned(),
+        };
+        let commits = duct::cmd!("git", "rev-list", "--first-parent", "--reverse", range)
+            .dir(&self.toplevel)
+            .read()
+            .wrap_err("error listing git history")?;
+        Ok(commits.lines().map(|line| line.to_owned()).collect())
+    }
+
+    /// Returns the abbreviated hash and subject line of a commit.
+    pub(crate) fn describe(&self, commit: &str) -> Result<String> {
+        let description = duct::cmd!("git", "log", "-1", "--format=%h %s", commit)
+            .dir(&self.toplevel)
+            .read()
+            .wrap_err_with(|| format!("error describing commit {}", commit))?;
+        Ok(description.trim().to_owned())
+    }
+
+    /// Returns the paths, relative to the repository root, of files that changed between `from`
+    /// and `to`.
+    ///
+    /// If `to` is `None`, the working tree is compared against `from`.
+    pub(crate) fn changed_files(&self, from: &str, to: Option<&str>) -> Result<Vec<Utf8PathBuf>> {
+        let mut args = vec!["diff", "--name-only", from];
+        args.extend(to);
+        let files = duct::cmd("git", args)
+            .dir(&self.toplevel)
+            .read()
+            .wrap_err("error listing changed files")?;
+        Ok(files.lines().map(Utf8PathBuf::from).collect())
+    }
+
+    /// Checks out the files needed to compute the workspace-hack at `commit` into a temporary
+    /// directory.
+    ///
+    /// Rather than copying the whole tree, only files that Cargo and Hakari read are written out:
+    /// manifests, lockfiles, Cargo configuration and the Hakari config. Other Rust source files
+    /// are created empty, so that Cargo discovers the same targets as in a full checkout.
+    pub(crate) fn checkout(&self, commit: &str) -> Result<Checkout> {
+        let dir = TempDir::new().wrap_err("error creating temporary directory")?;
+        let dir_path = Utf8Path::from_path(dir.path())
+            .ok_or_else(|| eyre!("temporary directory path is invalid UTF-8"))?
+            .canonicalize_utf8()
+            .wrap_err("error canonicalizing temporary directory")?;
+
+        let files = duct::cmd!("git", "ls-tree", "-r", "--name-only", "-z", commit)
+            .dir(&self.toplevel)
+            .read()
+            .wrap_err_with(|| format!("error listing files at {}", commit))?;
+        for path in files.split('\0').filter(|path| !path.is_empty()) {
+            let path = Utf8Path::new(path);
+            let contents = if is_cargo_input(path) {
+                duct::cmd!("git", "show", format!("{}:{}", commit, path))
+                    .dir(&self.toplevel)
+                    .stdout_capture()
+                    .run()
+                    .wrap_err_with(|| format!("error reading {} at {}", path, commit))?
+                    .stdout
+            } else if path.extension() == Some("rs") {
+                vec![]
+            } else {
+                continue;
+            };
+            let out_path = dir_path.join(path);
+            if let Some(parent) = out_path.parent() {
+                fs::create_dir_all(parent)
+                    .wrap_err_with(|| format!("error creating directory {}", parent))?;
+            }
+            fs::write(&out_path, contents)
+                .wrap_err_with(|| format!("error writing {}", out_path))?;
+        }
+
+        let workspace_root = dir_path.join(&self.rel_root);
+        if !workspace_root.join("Cargo.toml").exists() {
+            bail!("workspace not found at {}", commit);
+        }
+        // If Cargo.lock isn't checked in, start from the current lockfile so that the same
+        // dependency versions are picked where possible. This can differ from the versions that
+        // were actually used at the time, so warn about it.
+        let lockfile = workspace_root.join("Cargo.lock");
+        let current_lockfile = self.workspace_root.join("Cargo.lock");
+        if !lockfile.exists() && current_lockfile.exists() {
+            warn!(
+                "Cargo.lock not found at {}, using the current Cargo.lock instead",
+                commit,
+            );
+            fs::copy(&current_lockfile, &lockfile).wrap_err("error copying Cargo.lock")?;
+        }
+
+        Ok(Checkout {
+            _dir: dir,
+            source_toplevel: self.toplevel.clone(),
+            toplevel: dir_path,
+            workspace_root,
+        })
+    }
+}
+
+/// Returns true if `path` is a file that's read while computing the workspace-hack.
+fn is_cargo_input(path: &Utf8Path) -> bool {
+    let file_name = path.file_name().unwrap_or_default();
+    let parent_name = path
+        .parent()
+        .and_then(|parent| parent.file_name())
+        .unwrap_or_default();
+    matches!(file_name, "Cargo.toml" | "Cargo.lock")
+        || (parent_name == ".cargo" && matches!(file_name, "config" | "config.toml"))
+        || [DEFAULT_CONFIG_PATH, FALLBACK_CONFIG_PATH]
+            .iter()
+            .any(|config_path| path.ends_with(config_path))
+}
+
+/// A copy of the repository at a git revision, in a temporary directory.
+#[derive(Debug)]
+pub(crate) struct Checkout {
+    // Held to delete the temporary directory on drop.
+    _dir: TempDir,
+    /// The root of the repository this was checked out from.
+    source_toplevel: Utf8PathBuf,
+    toplevel: Utf8PathBuf,
+    workspace_root: Utf8PathBuf,
+}
+
+impl Checkout {
+    /// Returns the root of the checked out repository.
+    pub(crate) fn toplevel(&self) -> &Utf8Path {
+        &self.toplevel
+    }
+
+    /// Builds the package graph for the checked out workspace.
+    ///
+    /// If `--manifest-path` was passed in, the manifest at the same path within the checkout is
+    /// used.
+    pub(crate) fn package_graph(&self, output: &OutputContext) -> Result<PackageGraph> {
+        let mut command = MetadataCommand::new();
+        command
+            .current_dir(&self.workspace_root)
+            .other_options(output.cargo_opts.flags());
+        if let Some(manifest_path) = &output.cargo_opts.manifest_path {
+            let manifest_path = manifest_path
+                .canonicalize_utf8()
+                .wrap_err_with(|| format!("error canonicalizing {}", manifest_path))?;
+            let rel_path = manifest_path
+                .strip_prefix(&self.source_toplevel)
+                .wrap_err_with(|| format!("{} isn't inside the git repository", manifest_path))?;
+            command.manifest_path(self.toplevel.join(rel_path));
+        }
+        output
+            .timings
+            .time(Phase::Metadata, || command.build_graph())
+            .wrap_err("building package graph failed")
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::cargo_cli::CargoOpts;
+
+    /// Creates a git repository with `files` committed to it, returning the temporary directory
+    /// and its canonical path.
+    pub(crate) fn make_repo(files: &[(&str, &str)]) -> (TempDir, Utf8PathBuf) {
+        let dir = TempDir::new().expect("created temporary directory");
+        let root = Utf8Path::from_path(dir.path())
+            .expect("temporary directory is valid UTF-8")
+            .canonicalize_utf8()
+            .expect("canonicalized temporary directory");
+        git(&root, &["init", "-q"]);
+        commit_files(&root, files);
+        (dir, root)
+    }
+
+    /// Writes out `files` and commits them.
+    pub(crate) fn commit_files(root: &Utf8Path, files: &[(&str, &str)]) {
+        for (path, contents) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().expect("path has a parent")).expect("created dir");
+            fs::write(&path, contents).expect("wrote file");
+        }
+        git(root, &["add", "-A"]);
+        git(
+            root,
+            &[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "-c",
+                "commit.gpgsign=false",
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                "commit",
+            ],
+        );
+    }
+
+    fn git(root: &Utf8Path, args: &[&str]) {
+        duct::cmd("git", args)
+            .dir(root)
+            .stdout_null()
+            .run()
+            .expect("git command succeeded");
+    }
+
+    pub(crate) static WORKSPACE_FILES: &[(&str, &str)] = &[
+        (
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\"]\nresolver = \"2\"\n",
+        ),
+        (
+            "crates/a/Cargo.toml",
+            "[package]\nname = \"a\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        ),
+        ("crates/a/src/lib.rs", "pub fn a() {}\n"),
+        (".cargo/config.toml", "[net]\noffline = true\n"),
+        ("README.md", "A workspace.\n"),
+    ];
+
+    #[test]
+    fn checkout_cargo_inputs() {
+        let (_dir, root) = make_repo(WORKSPACE_FILES);
+        let repo = GitRepo::new(&root).expect("found git repository");
+        let commit = repo.resolve("HEAD").expect("resolved HEAD");
+
+        let checkout = repo.checkout(&commit).expect("checked out HEAD");
+        let read = |path: &str| fs::read_to_string(checkout.toplevel().join(path)).ok();
+        for path in ["Cargo.toml", "crates/a/Cargo.toml", ".cargo/config.toml"] {
+            assert_eq!(
+                read(path),
+                Some(fs::read_to_string(root.join(path)).unwrap()),
+                "{path} is checked out"
+            );
+        }
+        assert_eq!(
+            read("crates/a/src/lib.rs").as_deref(),
+            Some(""),
+            "Rust source files are empty"
+        );
+        assert_eq!(read("README.md"), None, "other files are skipped");
+        assert_eq!(read("Cargo.lock"), None, "no lockfile to copy");
+
+        // Cargo.lock isn't checked in, so the current one is used.
+        fs::write(root.join("Cargo.lock"), "version = 3\n").expect("wrote Cargo.lock");
+        let checkout = repo.checkout(&commit).expect("checked out HEAD");
+        assert_eq!(
+            fs::read_to_string(checkout.toplevel().join("Cargo.lock")).ok(),
+            Some("version = 3\n".to_owned()),
+        );
+    }
+
+    #[test]
+    fn checkout_package_graph() {
+        let (_dir, root) = make_repo(WORKSPACE_FILES);
+        let repo = GitRepo::new(&root).expect("found git repository");
+        let checkout = repo
+            .checkout(&repo.resolve("HEAD").expect("resolved HEAD"))
+            .expect("checked out HEAD");
+
+        let output = OutputContext::new_test(CargoOpts::default());
+        let graph = checkout
+            .package_graph(&output)
+            .expect("built package graph");
+        assert_eq!(graph.workspace().root(), checkout.toplevel());
+
+        // --manifest-path is mapped to the same path within the checkout.
+        let output = OutputContext::new_test(CargoOpts {
+            manifest_path: Some(root.join("crates/a/Cargo.toml")),
+            ..CargoOpts::default()
+        });
+        let graph = checkout
+            .package_graph(&output)
+            .expect("built package graph");
+        assert_eq!(graph.workspace().root(), checkout.toplevel());
+
+        let output = OutputContext::new_test(CargoOpts {
+            manifest_path: Some(root.join("crates/missing/Cargo.toml")),
+            ..CargoOpts::default()
+        });
+        let err = checkout
+            .package_graph(&output)
+            .expect_err("manifest path doesn't exist");
+        assert!(
+            err.to_string().contains("crates/missing/Cargo.toml"),
+            "unexpected error: {err}"
+        );
+    }
+}
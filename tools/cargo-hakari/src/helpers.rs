@@ -1,7 +1,7 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::{cargo_cli::CargoCli, output::OutputContext};
+use crate::{cargo_cli::CargoCli, output::OutputContext, timings::Phase};
 use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::{
     eyre::{bail, WrapErr},
@@ -35,15 +35,25 @@ pub(crate) fn read_contents(
     bail!("none of these paths were found:\n{}", paths_tried_str)
 }
 
+/// Formats a count along with a noun, pluralizing the noun if necessary.
+pub(crate) fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
 /// Regenerate the lockfile after dependency updates.
 pub(crate) fn regenerate_lockfile(output: OutputContext) -> Result<()> {
     // This seems to be the cheapest way to update the lockfile.
     // cargo update -p <hakari-package> can sometimes cause unnecessary index updates.
+    let timings = output.timings.clone();
     let cargo_cli = CargoCli::new("tree", output);
-    cargo_cli
-        .to_expression()
-        .stdout_null()
-        .run()
+    timings
+        .time(Phase::Lockfile, || {
+            cargo_cli.to_expression().stdout_null().run()
+        })
         .wrap_err("updating Cargo.lock failed")?;
     Ok(())
 }
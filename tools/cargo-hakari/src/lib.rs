@@ -53,6 +53,17 @@
 //!
 //! If `$HOME/.cargo/bin` is in your `PATH`, the `cargo hakari` command will be available.
 //!
+//! ## Shell completions
+//!
+//! To generate shell completions for bash, elvish, fish, powershell or zsh, run:
+//!
+//! ```sh
+//! cargo hakari completions <shell>
+//! ```
+//!
+//! The completions are written to stdout, and are for the `cargo-hakari` executable. Values such
+//! as package names for `--package` aren't completed.
+//!
 //! # Usage
 //!
 //! ## Getting started
@@ -78,6 +89,19 @@
 //! <img src="https://user-images.githubusercontent.com/180618/135726175-dc00dd0c-68a1-455f-a13d-0dd24f545ca6.png">
 //! </p>
 //!
+//! If the workspace already has a hand-rolled workspace-hack crate, adopt it instead:
+//!
+//! ```sh
+//! cargo hakari init --adopt my-workspace-hack
+//! ```
+//!
+//! This replaces the crate's dependency tables with a section managed by hakari.
+//!
+//! To customize the generated crate (for example, to add license fields or custom metadata), pass
+//! in `--template <dir>`. The directory must contain a `Cargo.toml-in` file, which has
+//! `%PACKAGE_NAME%` replaced with the crate name and must have `### BEGIN HAKARI SECTION` and
+//! `### END HAKARI SECTION` lines. Other files in the directory are copied into the crate as is.
+//!
 //! ### 3. Generate the `Cargo.toml`
 //!
 //! Generate or update the contents of a workspace-hack crate:
@@ -86,6 +110,11 @@
 //! cargo hakari generate
 //! ```
 //!
+//! To check that the new contents work before keeping them, pass in `--verify`. This runs the
+//! equivalent of `cargo hakari verify` after writing out the new contents, and restores the previous
+//! contents if verification fails. Add `--cargo-check` to also run `cargo check` on the workspace-hack
+//! crate.
+//!
 //! ### 4. Add dependencies to the workspace-hack
 //!
 //! Add the workspace-hack crate as a dependency to all other workspace crates:
@@ -114,17 +143,56 @@
 //!
 //! Remember to run `cargo hakari generate` after changing the config.
 //!
+//! Options can also be read and changed from the command line, which preserves comments in the
+//! config and regenerates the workspace-hack:
+//!
+//! ```sh
+//! cargo hakari config get platforms
+//! cargo hakari config set exact-versions true
+//! ```
+//!
+//! To try out a config change without editing the file, pass in `--config key=value` to any command.
+//! Overrides apply to that invocation only, and can be passed in several times:
+//!
+//! ```sh
+//! cargo hakari generate --diff --config 'platforms=["x86_64-unknown-linux-gnu"]'
+//! ```
+//!
+//! To check the config for mistakes such as misspelled keys, unknown platforms and missing
+//! workspace members, run `cargo hakari config validate`.
+//!
+//! New versions of `cargo hakari` occasionally add output formats, selected by `dep-format-version`.
+//! To switch to the latest format, run `cargo hakari upgrade-format`. This updates the config,
+//! regenerates the workspace-hack, and updates the `workspace-hack = ...` lines in workspace crates,
+//! printing out a single diff of all the changes. Pass in `--dry-run` to see the diff without writing
+//! anything out.
+//!
 //! ### 2. Keep the workspace-hack up-to-date in CI
 //!
-//! Run the following commands in CI:
+//! Run the following command in CI:
+//!
+//! ```sh
+//! cargo hakari check
+//! ```
+//!
+//! This checks that the workspace-hack `Cargo.toml` is up-to-date, that all workspace crates depend on
+//! the workspace-hack, and that the workspace-hack works correctly. It is equivalent to running:
 //!
 //! ```sh
 //! cargo hakari generate --diff  # workspace-hack Cargo.toml is up-to-date
 //! cargo hakari manage-deps --dry-run  # all workspace crates depend on workspace-hack
+//! cargo hakari verify  # the workspace-hack works correctly
 //! ```
 //!
-//! If either of these commands exits with a non-zero status, you can choose to fail CI or produce a
-//! warning message.
+//! If any of these checks fail, `cargo hakari check` prints out a summary of the failures and exits
+//! with a non-zero status. You can choose to fail CI or produce a warning message.
+//!
+//! If `cargo hakari check` fails and the cause isn't clear, run `cargo hakari doctor`. This looks
+//! for common problems with the Hakari setup: workspace crates that don't depend on the
+//! workspace-hack, path dependencies that aren't workspace members, an out-of-date `Cargo.lock`,
+//! registries that Hakari doesn't know about, a resolver version that doesn't match the workspace's,
+//! and an outdated `dep-format-version`. Each problem is printed out along with a command or edit that
+//! fixes it.
 //!
 //! For an example, see [this GitHub action used by
 //! `cargo-guppy`](https://github.com/guppy-rs/guppy/blob/main/.github/workflows/hakari.yml).
@@ -132,12 +200,33 @@
 //! All `cargo hakari` commands take a `--quiet` option to suppress output, though showing diff
 //! output in CI is often useful.
 //!
+//! To operate on a workspace other than the one in the current directory, pass in `--manifest-path`.
+//! The `--locked`, `--offline` and `--frozen` options are passed through to Cargo, which is useful in
+//! CI environments without network access.
+//!
+//! To drive `cargo hakari` from other tools, pass in `--message-format json`. With this option, results
+//! such as diff hunks, pending operations, verification failures and explanations are written to
+//! stdout as JSON records, one per line, each with a `reason` field indicating its kind. Log
+//! messages continue to be written to stderr.
+//!
 //! ### 3. Consider a `[patch]` directive
 //!
 //! If your workspace is depended on as a Git or path dependency, it is **strongly recommended**
 //! that you follow the instructions in the [`[patch]` directive
 //! section](https://docs.rs/cargo-hakari/latest/cargo_hakari/patch_directive).
 //!
+//! ### 4. Regenerate the workspace-hack while developing
+//!
+//! To keep the workspace-hack up-to-date as dependencies are added or changed, run:
+//!
+//! ```sh
+//! cargo hakari watch
+//! ```
+//!
+//! This watches the `Cargo.toml` files in the workspace and the Hakari config, and runs
+//! `cargo hakari generate` and `cargo hakari manage-deps` whenever they change. With `--dry-run`,
+//! pending diffs and operations are printed out instead.
+//!
 //! ## Information about the workspace-hack
 //!
 //! The commands in this section provide information about components in the workspace-hack.
@@ -154,6 +243,72 @@
 //! <img src="https://user-images.githubusercontent.com/180618/144933657-c45cf719-ecaf-49e0-b2c7-c8d12adf11c0.png" width=550>
 //! </p>
 //!
+//! To get this information in a machine-readable format, pass in `--format json`.
+//!
+//! To see the workspace crates responsible as a tree, along with the dependencies through which they
+//! pull in the dependency, pass in `--tree`. The tree has the same layout as `cargo tree --invert`, so
+//! the two can be compared directly.
+//!
+//! To explain every dependency in the workspace-hack at once, run:
+//!
+//! ```sh
+//! cargo hakari explain --all
+//! ```
+//!
+//! The report can be narrowed down to builds on a platform (`--platform <triple>`), to build
+//! dependencies and procedural macros (`--kind build`) or to regular dependencies (`--kind normal`),
+//! and to builds caused by some workspace members (`--workspace-member <name>`).
+//!
+//! ### Why isn't a dependency in the workspace-hack?
+//!
+//! ```sh
+//! cargo hakari list-omitted
+//! ```
+//!
+//! This lists the third-party dependencies that aren't in the workspace-hack, grouped by the reason
+//! they were left out: for example, because they're always built with the same feature set, because
+//! they're excluded in `hakari.toml`, or because they're only built on platforms that aren't listed
+//! there.
+//!
+//! ### How has the workspace-hack changed?
+//!
+//! Compare the workspace-hack against the one that would be generated at a git revision:
+//!
+//! ```sh
+//! cargo hakari diff --base main
+//! ```
+//!
+//! This checks out the manifests, lockfiles and configuration of the workspace at the given
+//! revision into a temporary directory, computes the workspace-hack for both revisions, and prints
+//! out dependencies and features that were added or removed, along with the workspace crates that
+//! caused them. This is useful for reviewing the effect of a change to dependencies. The command
+//! exits with status 1 if anything changed.
+//!
+//! ### Which change added a dependency to the workspace-hack?
+//!
+//! Find the git commit that introduced a dependency into the workspace-hack:
+//!
+//! ```sh
+//! cargo hakari bisect <dependency-name>
+//! ```
+//!
+//! This searches the history of `HEAD` with a binary search, recomputing the workspace-hack at each
+//! revision it checks. It then prints out the first commit where the dependency is present, along
+//! with the workspace crates whose `Cargo.toml` changes in that commit were responsible. To limit
+//! the search, pass in `--since <rev>`, or a list of revisions to search with `--rev <rev>`.
+//!
+//! ### How much does the workspace-hack help?
+//!
+//! ```sh
+//! cargo hakari stats
+//! ```
+//!
+//! This prints out the number of third-party crates that would be built with more than one feature set
+//! without the workspace-hack, an estimate of the number of duplicate builds avoided, a breakdown by
+//! platform, and the crates for which the most builds are avoided. These numbers are estimates: they
+//! count the feature sets built when workspace crates are built one at a time, and building several
+//! crates at once can unify some of these feature sets even without the workspace-hack.
+//!
 //! ### Does the workspace-hack ensure that each dependency is built with exactly one feature set?
 //!
 //! ```sh
@@ -165,6 +320,24 @@
 //! report](https://github.com/guppy-rs/guppy/issues/new) with more information would be greatly
 //! appreciated!
 //!
+//! To check a single platform quickly, e.g. while iterating on excludes, pass in `--platform`
+//! (which can be repeated). `--include-dev` and `--no-include-dev` override `exclude-dev-dependencies`
+//! for this run:
+//!
+//! ```sh
+//! cargo hakari verify --platform x86_64-unknown-linux-gnu --no-include-dev
+//! ```
+//!
+//! ### Where is the time going?
+//!
+//! ```sh
+//! cargo hakari generate --timings --log-file hakari.log
+//! ```
+//!
+//! `--timings` reports how long loading metadata, computing the workspace-hack, generating TOML and
+//! regenerating `Cargo.lock` took. With `--message-format json`, this is emitted as a `timings`
+//! record. `--log-file` writes log messages, including debug messages, to a file with timestamps.
+//!
 //! ###
 //! ## Publishing a crate
 //!
@@ -190,6 +363,16 @@
 //! <img src="https://user-images.githubusercontent.com/180618/135726181-9fe86782-6471-4a1d-a511-a6c55dffbbd7.png">
 //! </p>
 //!
+//! To remove hakari from the workspace entirely, run:
+//!
+//! ```sh
+//! cargo hakari uninstall
+//! ```
+//!
+//! This removes the workspace-hack crate as a dependency from all other workspace crates, removes the
+//! workspace-hack crate from the workspace and deletes it, and deletes the Hakari config. Pass in
+//! `--dry-run` to see the operations that would be performed.
+//!
 //! # Configuration
 //!
 //! `cargo hakari` is configured through `.config/hakari.toml` at the root of the workspace. Running
@@ -236,12 +419,23 @@
 //!   option, or
 //! * there is a bugfix involved.
 
+mod bisect;
 mod cargo_cli;
 mod command;
+mod config_command;
+mod diff;
 mod docs;
+mod doctor;
+mod git;
 mod helpers;
+mod message;
 mod output;
+mod platform;
 mod publish;
+mod stats;
+mod timings;
+mod upgrade_format;
+mod watch;
 
 pub use docs::*;
 
@@ -0,0 +1,238 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! JSON records emitted with `--message-format json`.
+//!
+//! Each record is written to stdout as a single line of JSON. Every record has a `reason` field
+//! indicating what kind of record it is, similar to `cargo build --message-format json`.
+
+use camino::Utf8Path;
+use color_eyre::{eyre::WrapErr, Result};
+use hakari::{
+    cli_ops::WorkspaceOps,
+    diffy::{Line, Patch},
+    explain::HakariExplain,
+    verify::VerifyErrors,
+    OmittedDependency,
+};
+use serde_json::{json, Value};
+use std::io::Write;
+
+/// Writes a record out to stdout.
+pub(crate) fn emit(record: Value) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer(&mut stdout, &record).wrap_err("error serializing record to JSON")?;
+    writeln!(stdout).wrap_err("error writing record to stdout")?;
+    Ok(())
+}
+
+/// A diff between the existing and new contents of a file.
+///
+/// Lines in hunks include their trailing newlines, if any.
+pub(crate) fn diff(path: &Utf8Path, patch: &Patch<'_, str>) -> Value {
+    let hunks: Vec<_> = patch
+        .hunks()
+        .iter()
+        .map(|hunk| {
+            let lines: Vec<_> = hunk
+                .lines()
+                .iter()
+                .map(|line| {
+                    let (kind, text) = match line {
+                        Line::Context(text) => ("context", text),
+                        Line::Delete(text) => ("delete", text),
+                        Line::Insert(text) => ("insert", text),
+                    };
+                    json!({ "kind": kind, "text": text })
+                })
+                .collect();
+            json!({
+                "old-start": hunk.old_range().start(),
+                "old-len": hunk.old_range().len(),
+                "new-start": hunk.new_range().start(),
+                "new-len": hunk.new_range().len(),
+                "lines": lines,
+            })
+        })
+        .collect();
+    json!({
+        "reason": "diff",
+        "path": path.as_str(),
+        "changed": !hunks.is_empty(),
+        "hunks": hunks,
+    })
+}
+
+/// The result of writing out new contents to a file.
+pub(crate) fn write(path: &Utf8Path, changed: bool) -> Value {
+    json!({
+        "reason": "write",
+        "path": path.as_str(),
+        "changed": changed,
+    })
+}
+
+/// Operations to be performed on the workspace, and whether they were applied.
+pub(crate) fn operations(ops: &WorkspaceOps<'_, '_>, applied: bool) -> Value {
+    json!({
+        "reason": "operations",
+        "operations": ops.to_summary(),
+        "applied": applied,
+    })
+}
+
+/// The result of `cargo hakari verify`.
+pub(crate) fn verify(hakari_package: &str, errs: Option<&VerifyErrors<'_>>) -> Value {
+    let errors: Vec<_> = errs
+        .into_iter()
+        .flat_map(|errs| errs.errors())
+        .map(|explain| explain.to_summary())
+        .collect();
+    json!({
+        "reason": "verify",
+        "hakari-package": hakari_package,
+        "success": errors.is_empty(),
+        "errors": errors,
+    })
+}
+
+/// Why a dependency is in the workspace-hack.
+pub(crate) fn explain(hakari_package: &str, explain: &HakariExplain<'_, '_>) -> Value {
+    json!({
+        "reason": "explain",
+        "hakari-package": hakari_package,
+        "explain": explain.to_summary(),
+    })
+}
+
+/// The combined results of `cargo hakari check`.
+pub(crate) fn check(hakari_package: &str, results: &[(&str, bool)]) -> Value {
+    let checks: Vec<_> = results
+        .iter()
+        .map(|(name, success)| json!({ "name": name, "success": success }))
+        .collect();
+    json!({
+        "reason": "check",
+        "hakari-package": hakari_package,
+        "success": results.iter().all(|(_, success)| *success),
+        "checks": checks,
+    })
+}
+
+/// A package that was published.
+pub(crate) fn published(name: &str, version: &guppy::Version) -> Value {
+    json!({
+        "reason": "published",
+        "name": name,
+        "version": version.to_string(),
+    })
+}
+
+/// The platforms listed in the Hakari config.
+pub(crate) fn platforms(platforms: &[&str]) -> Value {
+    json!({
+        "reason": "platforms",
+        "platforms": platforms,
+    })
+}
+
+/// Changes to the workspace-hack since a git revision.
+pub(crate) fn hakari_diff(hakari_package: &str, base: &str, changes: Vec<Value>) -> Value {
+    json!({
+        "reason": "hakari-diff",
+        "hakari-package": hakari_package,
+        "base": base,
+        "changes": changes,
+    })
+}
+
+/// The revision that introduced a dependency into the workspace-hack.
+///
+/// `commit` is `None` if the dependency was introduced by uncommitted changes.
+pub(crate) fn bisect(
+    dep_name: &str,
+    commit: Option<&str>,
+    summary: Option<&str>,
+    responsible: &[String],
+    caused_by: &[String],
+) -> Value {
+    json!({
+        "reason": "bisect",
+        "dependency": dep_name,
+        "commit": commit,
+        "summary": summary,
+        "responsible": responsible,
+        "caused-by": caused_by,
+    })
+}
+
+/// The results of `cargo hakari config validate`.
+pub(crate) fn config_validate(path: &Utf8Path, diagnostics: Vec<Value>) -> Value {
+    let success = diagnostics
+        .iter()
+        .all(|diagnostic| diagnostic["severity"] != "error");
+    json!({
+        "reason": "config-validate",
+        "path": path.as_str(),
+        "success": success,
+        "diagnostics": diagnostics,
+    })
+}
+
+/// The value of a key in the Hakari config.
+///
+/// `value` is `None` if the key isn't set and has no default.
+pub(crate) fn config_value(key: &str, value: Option<&Value>, default: bool) -> Value {
+    json!({
+        "reason": "config-value",
+        "key": key,
+        "value": value,
+        "default": default,
+    })
+}
+
+/// The problems found by `cargo hakari doctor`.
+pub(crate) fn doctor(findings: Vec<Value>) -> Value {
+    json!({
+        "reason": "doctor",
+        "success": findings.is_empty(),
+        "findings": findings,
+    })
+}
+
+/// The third-party dependencies that aren't in a workspace-hack.
+pub(crate) fn omitted(hakari_package: &str, omitted: &[OmittedDependency<'_>]) -> Value {
+    let dependencies: Vec<_> = omitted
+        .iter()
+        .map(|dep| {
+            json!({
+                "name": dep.package.name(),
+                "version": dep.package.version().to_string(),
+                "reason": dep.reason.as_str(),
+            })
+        })
+        .collect();
+    json!({
+        "reason": "omitted",
+        "hakari-package": hakari_package,
+        "dependencies": dependencies,
+    })
+}
+
+/// Estimates of the benefit of a workspace-hack, from `cargo hakari stats`.
+pub(crate) fn stats(hakari_package: &str, stats: Value) -> Value {
+    json!({
+        "reason": "stats",
+        "hakari-package": hakari_package,
+        "stats": stats,
+    })
+}
+
+/// The time taken by each phase of a command, from `--timings`.
+pub(crate) fn timings(phases: Vec<Value>, total_seconds: f64) -> Value {
+    json!({
+        "reason": "timings",
+        "phases": phases,
+        "total-seconds": total_seconds,
+    })
+}
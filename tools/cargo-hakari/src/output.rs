@@ -1,11 +1,19 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use crate::{cargo_cli::CargoOpts, config_command::ConfigOverride, timings::Timings};
+use camino::Utf8PathBuf;
 use clap::{Parser, ValueEnum};
+use color_eyre::{eyre::WrapErr, Result};
 use env_logger::fmt::Formatter;
-use log::{Level, LevelFilter, Record};
+use log::{Level, LevelFilter, Log, Metadata, Record};
 use owo_colors::{OwoColorize, Stream, Style};
-use std::{io::Write, sync::Arc};
+use std::{
+    fs::File,
+    io::{LineWriter, Write},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 
 #[derive(Debug, Parser)]
 #[must_use]
@@ -37,14 +45,39 @@ pub(crate) struct OutputOpts {
         default_value_t = Color::Auto,
     )]
     pub(crate) color: Color,
+
+    /// Format for results: human-readable text, or JSON records on stdout
+    #[clap(
+        long,
+        value_enum,
+        global = true,
+        default_value_t = MessageFormat::Human,
+    )]
+    pub(crate) message_format: MessageFormat,
+
+    /// Also write log messages to this file, including debug messages and timestamps
+    #[clap(long, global = true, value_name = "PATH")]
+    pub(crate) log_file: Option<Utf8PathBuf>,
+
+    /// Report how long loading metadata, computing hakari, generating TOML and regenerating the
+    /// lockfile took
+    #[clap(long, global = true)]
+    pub(crate) timings: bool,
 }
 
 impl OutputOpts {
-    pub(crate) fn init(self) -> OutputContext {
+    pub(crate) fn init(
+        self,
+        cargo_opts: CargoOpts,
+        config_overrides: Vec<ConfigOverride>,
+    ) -> Result<OutputContext> {
         let OutputOpts {
             quiet,
             verbose,
             color,
+            message_format,
+            log_file,
+            timings,
         } = self;
         let level = if quiet {
             LevelFilter::Error
@@ -61,18 +94,107 @@ impl OutputOpts {
             styles.colorize();
         }
 
-        env_logger::Builder::from_default_env()
+        let stderr = env_logger::Builder::from_default_env()
             .filter_level(level)
             .format(format_fn)
-            .init();
+            .build();
+        let log_file = log_file
+            .map(|path| {
+                File::create(&path)
+                    .map(|file| Mutex::new(LineWriter::new(file)))
+                    .wrap_err_with(|| format!("error creating log file at {}", path))
+            })
+            .transpose()?;
+        let max_level = if log_file.is_some() {
+            LevelFilter::Debug.max(stderr.filter())
+        } else {
+            stderr.filter()
+        };
+        log::set_boxed_logger(Box::new(HakariLogger {
+            stderr,
+            log_file,
+            start: Instant::now(),
+        }))
+        .expect("logger is only initialized once");
+        log::set_max_level(max_level);
 
-        OutputContext {
+        Ok(OutputContext {
             quiet,
             verbose,
             color,
+            message_format,
             styles: Arc::new(styles),
+            cargo_opts: Arc::new(cargo_opts),
+            config_overrides: config_overrides.into(),
+            timings: Arc::new(Timings::new(timings)),
+        })
+    }
+}
+
+/// Logs messages to stderr, and also to the file passed in with `--log-file`.
+struct HakariLogger {
+    stderr: env_logger::Logger,
+    log_file: Option<Mutex<LineWriter<File>>>,
+    start: Instant,
+}
+
+impl Log for HakariLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.stderr.enabled(metadata)
+            || (self.log_file.is_some() && metadata.level() <= Level::Debug)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.stderr.log(record);
+        let Some(log_file) = &self.log_file else {
+            return;
+        };
+        if record.level() > Level::Debug {
+            return;
+        }
+        // Messages may have been styled for stderr, so strip out colors.
+        let message = strip_ansi(&record.args().to_string());
+        let mut log_file = log_file.lock().expect("log file lock isn't poisoned");
+        // There's nowhere to report errors writing to the log file, so ignore them.
+        let _ = writeln!(
+            log_file,
+            "[{:>9.3}s] {}: {}",
+            self.start.elapsed().as_secs_f64(),
+            record.level().as_str().to_ascii_lowercase(),
+            message,
+        );
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+        if let Some(log_file) = &self.log_file {
+            let _ = log_file
+                .lock()
+                .expect("log file lock isn't poisoned")
+                .flush();
+        }
+    }
+}
+
+/// Removes ANSI escape sequences such as colors from a string.
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip over the escape sequence, which ends with an ASCII letter.
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+        } else {
+            out.push(c);
         }
     }
+    out
 }
 
 #[derive(Clone, Debug)]
@@ -81,7 +203,36 @@ pub(crate) struct OutputContext {
     pub(crate) quiet: bool,
     pub(crate) verbose: bool,
     pub(crate) color: Color,
+    pub(crate) message_format: MessageFormat,
     pub(crate) styles: Arc<Styles>,
+    /// Options passed through to Cargo.
+    pub(crate) cargo_opts: Arc<CargoOpts>,
+    /// Overrides for the Hakari config, passed in with `--config`.
+    pub(crate) config_overrides: Arc<[ConfigOverride]>,
+    /// Time taken by each phase, reported with `--timings`.
+    pub(crate) timings: Arc<Timings>,
+}
+
+impl OutputContext {
+    /// Returns true if results should be emitted as JSON records.
+    pub(crate) fn is_json(&self) -> bool {
+        self.message_format == MessageFormat::Json
+    }
+
+    /// Creates an `OutputContext` for tests, without initializing logging.
+    #[cfg(test)]
+    pub(crate) fn new_test(cargo_opts: CargoOpts) -> Self {
+        Self {
+            quiet: false,
+            verbose: false,
+            color: Color::Never,
+            message_format: MessageFormat::Human,
+            styles: Arc::new(Styles::default()),
+            cargo_opts: Arc::new(cargo_opts),
+            config_overrides: Arc::new([]),
+            timings: Arc::new(Timings::new(false)),
+        }
+    }
 }
 
 fn format_fn(f: &mut Formatter, record: &Record<'_>) -> std::io::Result<()> {
@@ -172,6 +323,18 @@ impl std::str::FromStr for Color {
     }
 }
 
+/// The format in which results are produced.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+#[must_use]
+pub enum MessageFormat {
+    /// Human-readable text, written to stderr.
+    Human,
+    /// JSON records, one per line, written to stdout.
+    ///
+    /// Log messages continue to be written to stderr.
+    Json,
+}
+
 #[derive(Clone, Debug, Default)]
 pub(crate) struct Styles {
     pub(crate) config_path: Style,
@@ -179,6 +342,9 @@ pub(crate) struct Styles {
     pub(crate) registry_url: Style,
     pub(crate) package_name: Style,
     pub(crate) package_version: Style,
+    pub(crate) platform: Style,
+    pub(crate) check_passed: Style,
+    pub(crate) check_failed: Style,
 }
 
 impl Styles {
@@ -188,5 +354,21 @@ impl Styles {
         self.registry_url = Style::new().magenta().bold();
         self.package_name = Style::new().bold();
         self.package_version = Style::new().bold();
+        self.platform = Style::new().bold();
+        self.check_passed = Style::new().green().bold();
+        self.check_failed = Style::new().red().bold();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("no colors"), "no colors");
+        let styled = format!("{} v1.0", "serde".style(Style::new().bold().green()));
+        assert_ne!(styled, "serde v1.0", "styling adds escape sequences");
+        assert_eq!(strip_ansi(&styled), "serde v1.0");
     }
 }
@@ -0,0 +1,231 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    command::{builder_and_output_from_config, read_config, CommandWithBuilder},
+    message,
+    output::OutputContext,
+};
+use clap::Subcommand;
+use color_eyre::eyre::{bail, Result, WrapErr};
+use guppy::{
+    graph::PackageGraph,
+    platform::{Platform, TargetFeatures},
+};
+use log::info;
+use owo_colors::OwoColorize;
+use toml_edit::{Array, Document, Item, Value};
+
+/// Manage the platforms the workspace-hack crate is generated for.
+#[derive(Debug, Subcommand)]
+pub(crate) enum PlatformCommand {
+    /// Add platforms to hakari.toml, then regenerate the workspace-hack crate
+    Add {
+        /// Target triples to add.
+        #[clap(required = true)]
+        triples: Vec<String>,
+
+        /// Print a diff of the workspace-hack crate, but do not write out any changes.
+        ///
+        /// Exits with status 1 if the workspace-hack crate would change. Can be combined with
+        /// `--quiet`.
+        #[clap(long, short = 'n')]
+        dry_run: bool,
+    },
+
+    /// Remove platforms from hakari.toml, then regenerate the workspace-hack crate
+    Remove {
+        /// Target triples to remove.
+        #[clap(required = true)]
+        triples: Vec<String>,
+
+        /// Print a diff of the workspace-hack crate, but do not write out any changes.
+        ///
+        /// Exits with status 1 if the workspace-hack crate would change. Can be combined with
+        /// `--quiet`.
+        #[clap(long, short = 'n')]
+        dry_run: bool,
+    },
+
+    /// List the platforms in hakari.toml
+    List,
+}
+
+impl PlatformCommand {
+    pub(crate) fn exec(self, package_graph: &PackageGraph, output: OutputContext) -> Result<i32> {
+        let (config_path, contents) = read_config(package_graph)?;
+        let mut doc: Document = contents
+            .parse()
+            .wrap_err_with(|| format!("error parsing Hakari config at {}", config_path))?;
+
+        let dry_run = match self {
+            PlatformCommand::Add { triples, dry_run } => {
+                for triple in &triples {
+                    // Check that the platform is known before adding it.
+                    let platform = Platform::new(triple.clone(), TargetFeatures::Unknown)
+                        .wrap_err_with(|| format!("error adding platform {}", triple))?;
+                    if !add_platform(&mut doc, platform.triple_str())? {
+                        info!(
+                            "platform {} is already present",
+                            triple.style(output.styles.platform)
+                        );
+                    }
+                }
+                dry_run
+            }
+            PlatformCommand::Remove { triples, dry_run } => {
+                for triple in &triples {
+                    if !remove_platform(&mut doc, triple)? {
+                        bail!("platform {} not found in {}", triple, config_path);
+                    }
+                }
+                dry_run
+            }
+            PlatformCommand::List => {
+                let platforms = platforms(&doc)?;
+                if output.is_json() {
+                    message::emit(message::platforms(&platforms))?;
+                } else {
+                    for triple in platforms {
+                        println!("{}", triple);
+                    }
+                }
+                return Ok(0);
+            }
+        };
+
+        let new_contents = doc.to_string();
+        if new_contents == contents {
+            info!("no changes to {}", config_path);
+            return Ok(0);
+        }
+
+        // Check that the new config is valid before writing it out.
+        let (builder, hakari_output) = builder_and_output_from_config(
+            package_graph,
+            &config_path,
+            &new_contents,
+            &output.config_overrides,
+        )?;
+        let generate = CommandWithBuilder::Generate {
+            diff: dry_run,
+            show_diff: true,
+            verify: false,
+            cargo_check: false,
+        };
+        if dry_run {
+            return generate.exec_any(builder, hakari_output, output);
+        }
+
+        std::fs::write(&config_path, &new_contents)
+            .wrap_err_with(|| format!("error writing Hakari config at {}", config_path))?;
+        if output.is_json() {
+            message::emit(message::write(&config_path, true))?;
+        }
+        info!(
+            "updated {}",
+            config_path.as_str().style(output.styles.config_path)
+        );
+        generate.exec_any(builder, hakari_output, output)
+    }
+}
+
+fn platforms(doc: &Document) -> Result<Vec<&str>> {
+    let platforms = match doc.get("platforms") {
+        Some(item) => item,
+        None => return Ok(vec![]),
+    };
+    match platforms.as_array() {
+        Some(array) => array
+            .iter()
+            .map(|value| match value.as_str() {
+                Some(triple) => Ok(triple),
+                None => bail!("platforms must be an array of strings"),
+            })
+            .collect(),
+        None => bail!("platforms must be an array of strings"),
+    }
+}
+
+fn platforms_mut(doc: &mut Document) -> Result<&mut Array> {
+    let platforms = doc
+        .as_table_mut()
+        .entry("platforms")
+        .or_insert(Item::Value(Value::Array(Array::new())));
+    match platforms.as_array_mut() {
+        Some(array) => Ok(array),
+        None => bail!("platforms must be an array of strings"),
+    }
+}
+
+/// Adds a platform to the config, returning false if it was already present.
+fn add_platform(doc: &mut Document, triple: &str) -> Result<bool> {
+    if platforms(doc)?.contains(&triple) {
+        return Ok(false);
+    }
+
+    let array = platforms_mut(doc)?;
+    // Match the formatting of the array if it's spread out over several lines, as in the template
+    // created by `cargo hakari init`.
+    if array.to_string().contains('\n') {
+        array.push_formatted(Value::from(triple).decorated("\n    ", ""));
+        array.set_trailing_comma(true);
+    } else {
+        array.push(triple);
+    }
+    Ok(true)
+}
+
+/// Removes a platform from the config, returning false if it wasn't present.
+fn remove_platform(doc: &mut Document, triple: &str) -> Result<bool> {
+    let idx = match platforms(doc)?.iter().position(|&t| t == triple) {
+        Some(idx) => idx,
+        None => return Ok(false),
+    };
+    platforms_mut(doc)?.remove(idx);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_platforms() {
+        static INPUT: &str = r#"hakari-package = "workspace-hack"
+
+# Add triples corresponding to platforms commonly used by developers here.
+platforms = [
+    # "x86_64-unknown-linux-gnu",
+]
+
+[traversal-excludes]
+workspace-members = ["foo"]
+"#;
+
+        let mut doc: Document = INPUT.parse().unwrap();
+        assert!(add_platform(&mut doc, "x86_64-unknown-linux-gnu").unwrap());
+        assert!(add_platform(&mut doc, "aarch64-apple-darwin").unwrap());
+        assert!(!add_platform(&mut doc, "aarch64-apple-darwin").unwrap());
+        assert_eq!(
+            platforms(&doc).unwrap(),
+            ["x86_64-unknown-linux-gnu", "aarch64-apple-darwin"]
+        );
+
+        assert!(remove_platform(&mut doc, "x86_64-unknown-linux-gnu").unwrap());
+        assert!(!remove_platform(&mut doc, "x86_64-unknown-linux-gnu").unwrap());
+        assert_eq!(platforms(&doc).unwrap(), ["aarch64-apple-darwin"]);
+        assert!(remove_platform(&mut doc, "aarch64-apple-darwin").unwrap());
+        assert!(platforms(&doc).unwrap().is_empty());
+
+        // A config without platforms gets a new array, placed before any tables.
+        let mut doc: Document = "hakari-package = \"workspace-hack\"\n\n[registries]\n"
+            .parse()
+            .unwrap();
+        assert!(add_platform(&mut doc, "x86_64-pc-windows-msvc").unwrap());
+        assert_eq!(
+            doc.to_string(),
+            "hakari-package = \"workspace-hack\"\nplatforms = [\"x86_64-pc-windows-msvc\"]\n\n[registries]\n"
+        );
+    }
+}
@@ -1,41 +1,158 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::{cargo_cli::CargoCli, helpers::regenerate_lockfile, output::OutputContext};
-use color_eyre::{eyre::WrapErr, Result};
-use guppy::graph::PackageMetadata;
+use crate::{cargo_cli::CargoCli, helpers::regenerate_lockfile, message, output::OutputContext};
+use color_eyre::{
+    eyre::{bail, eyre, WrapErr},
+    Result,
+};
+use guppy::graph::{DependencyDirection, PackageMetadata, PackageSet};
 use hakari::HakariBuilder;
 use log::{error, info};
 use owo_colors::OwoColorize;
 
-pub(crate) fn publish_hakari(
-    package_name: &str,
-    builder: HakariBuilder<'_>,
+/// The packages to publish.
+#[derive(Clone, Debug)]
+pub(crate) struct PublishPackages<'a> {
+    /// Packages to publish, by name.
+    pub(crate) packages: &'a [String],
+
+    /// If true, publish all workspace members that can be published, other than `exclude`.
+    pub(crate) workspace: bool,
+
+    /// Packages to exclude if `workspace` is true, by name.
+    pub(crate) exclude: &'a [String],
+}
+
+/// Publishes packages in dependency order, after temporarily removing the workspace-hack
+/// dependency from them.
+///
+/// `builders` has one builder per Hakari package: packages are published with the builder that
+/// covers them.
+pub(crate) fn publish_hakari<'g>(
+    to_publish: &PublishPackages<'_>,
+    builders: Vec<HakariBuilder<'g>>,
     pass_through: &[String],
     output: OutputContext,
 ) -> Result<()> {
-    let hakari_package = builder
-        .hakari_package()
-        .expect("hakari-package must be specified in hakari.toml");
-    let workspace = builder.graph().workspace();
-    let package = workspace.member_by_name(package_name)?;
-
-    // Remove the workspace-hack dependency from the package if it isn't published as open source.
-    let mut remove_dep = if hakari_package.publish().is_never() {
-        TempRemoveDep::new(builder, package, output.clone())?
+    let graph = builders
+        .first()
+        .expect("at least one builder is provided")
+        .graph();
+    let workspace = graph.workspace();
+
+    let package_set = if to_publish.workspace {
+        let exclude = to_publish
+            .exclude
+            .iter()
+            .map(|name| Ok(workspace.member_by_name(name)?.id()))
+            .collect::<Result<Vec<_>>>()?;
+        // This includes Hakari packages only if they can be published, in which case the
+        // dependencies on them aren't removed and they must be published first.
+        graph
+            .resolve_workspace()
+            .filter(DependencyDirection::Forward, |package| {
+                !package.publish().is_never() && !exclude.contains(&package.id())
+            })
     } else {
-        info!(
-            "not removing dependency to {} because it is marked as published (publish != false)",
-            hakari_package.name().style(output.styles.package_name)
-        );
-        TempRemoveDep::none()
+        let package_ids = to_publish
+            .packages
+            .iter()
+            .map(|name| Ok(workspace.member_by_name(name)?.id()))
+            .collect::<Result<Vec<_>>>()?;
+        graph.resolve_ids(package_ids)?
     };
+    if package_set.is_empty() {
+        bail!("no packages to publish");
+    }
+
+    // Dependencies must be published before the packages that depend on them.
+    let packages: Vec<_> = package_set.packages(DependencyDirection::Reverse).collect();
+
+    // Remove the workspace-hack dependency from all packages at once: this way, it's restored
+    // exactly once whether or not all packages are published successfully.
+    let mut remove_deps = Vec::with_capacity(builders.len());
+    let sharded = builders.len() > 1;
+    let mut by_builder: Vec<Vec<_>> = vec![vec![]; builders.len()];
+    for package in &packages {
+        let idx = if sharded {
+            builders
+                .iter()
+                .position(|builder| builder.is_covered(package.id()).expect("valid package ID"))
+                .ok_or_else(|| eyre!("package '{}' isn't covered by any shard", package.name()))?
+        } else {
+            0
+        };
+        by_builder[idx].push(package.id());
+    }
+    for (builder, package_ids) in builders.into_iter().zip(by_builder) {
+        if package_ids.is_empty() {
+            continue;
+        }
+        let hakari_package = builder
+            .hakari_package()
+            .expect("hakari-package must be specified in hakari.toml");
+        // Remove the workspace-hack dependency if it isn't published as open source.
+        if hakari_package.publish().is_never() {
+            let package_set = graph.resolve_ids(package_ids)?;
+            remove_deps.push(TempRemoveDep::new(builder, package_set, output.clone())?);
+        } else {
+            info!(
+                "not removing dependency to {} because it is marked as published (publish != false)",
+                hakari_package.name().style(output.styles.package_name)
+            );
+        }
+    }
 
+    let allow_dirty = remove_deps.iter().any(|remove_dep| !remove_dep.is_none());
+    for (idx, package) in packages.iter().enumerate() {
+        if packages.len() > 1 {
+            info!(
+                "publishing {} ({}/{})",
+                package.name().style(output.styles.package_name),
+                idx + 1,
+                packages.len(),
+            );
+        }
+        if let Err(err) = publish_one(*package, pass_through, allow_dirty, &output) {
+            for remove_dep in &mut remove_deps {
+                remove_dep.finish(false)?;
+            }
+            if idx > 0 {
+                error!(
+                    "packages published before the failure: {}",
+                    packages[..idx]
+                        .iter()
+                        .map(|package| package.name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            return Err(err);
+        }
+    }
+
+    for remove_dep in &mut remove_deps {
+        remove_dep.finish(true)?;
+    }
+    Ok(())
+}
+
+/// Runs `cargo publish` for a single package.
+///
+/// Since Rust 1.66, `cargo publish` waits for the package to be available in the index before
+/// returning, so packages that depend on it can be published immediately afterwards.
+fn publish_one(
+    package: PackageMetadata<'_>,
+    pass_through: &[String],
+    allow_dirty: bool,
+    output: &OutputContext,
+) -> Result<()> {
     let mut cargo_cli = CargoCli::new("publish", output.clone());
     cargo_cli.add_args(pass_through.iter().map(|arg| arg.as_str()));
     // Also set --allow-dirty because we make some changes to the working directory.
     // TODO: is there a better way to handle this?
-    if !remove_dep.is_none() {
+    if allow_dirty {
         cargo_cli.add_arg("--allow-dirty");
     }
 
@@ -43,7 +160,7 @@ pub(crate) fn publish_hakari(
         .source()
         .workspace_path()
         .expect("package is in workspace");
-    let abs_path = workspace.root().join(workspace_dir);
+    let abs_path = package.graph().workspace().root().join(workspace_dir);
 
     let all_args = cargo_cli.all_args().join(" ");
 
@@ -52,15 +169,19 @@ pub(crate) fn publish_hakari(
         "executing".style(output.styles.command),
         all_args
     );
-    let expression = cargo_cli.to_expression().dir(abs_path);
+    let mut expression = cargo_cli.to_expression().dir(abs_path);
+    if output.is_json() {
+        // Keep stdout reserved for JSON records.
+        expression = expression.stdout_to_stderr();
+    }
 
-    match expression.run() {
-        Ok(_) => remove_dep.finish(true),
-        Err(err) => {
-            remove_dep.finish(false)?;
-            Err(err).wrap_err_with(|| format!("`{}` failed", all_args))
-        }
+    expression
+        .run()
+        .wrap_err_with(|| format!("`{}` failed", all_args))?;
+    if output.is_json() {
+        message::emit(message::published(package.name(), package.version()))?;
     }
+    Ok(())
 }
 
 /// RAII guard to ensure packages are re-added after being published.
@@ -72,35 +193,35 @@ struct TempRemoveDep<'g> {
 impl<'g> TempRemoveDep<'g> {
     fn new(
         builder: HakariBuilder<'g>,
-        package: PackageMetadata<'g>,
+        package_set: PackageSet<'g>,
         output: OutputContext,
     ) -> Result<Self> {
         let hakari_package = builder
             .hakari_package()
             .expect("hakari-package must be specified in hakari.toml");
-        let package_set = package.to_package_set();
         let remove_ops = builder
             .remove_dep_ops(&package_set, false)
             .expect("hakari-package must be specified in hakari.toml");
+        let names = package_names(&package_set, &output);
         let inner = if remove_ops.is_empty() {
             info!(
                 "dependency from {} to {} not present",
-                package.name().style(output.styles.package_name),
+                names,
                 hakari_package.name().style(output.styles.package_name),
             );
             None
         } else {
             info!(
                 "removing dependency from {} to {}",
-                package.name().style(output.styles.package_name),
+                names,
                 hakari_package.name().style(output.styles.package_name),
             );
             remove_ops
                 .apply()
-                .wrap_err_with(|| format!("error removing dependency from {}", package.name()))?;
+                .wrap_err_with(|| format!("error removing dependency from {}", names))?;
             Some(TempRemoveDepInner {
                 builder,
-                package,
+                package_set,
                 output,
             })
         };
@@ -108,10 +229,6 @@ impl<'g> TempRemoveDep<'g> {
         Ok(Self { inner })
     }
 
-    fn none() -> Self {
-        Self { inner: None }
-    }
-
     fn is_none(&self) -> bool {
         self.inner.is_none()
     }
@@ -137,22 +254,21 @@ impl<'g> Drop for TempRemoveDep<'g> {
 #[derive(Debug)]
 struct TempRemoveDepInner<'g> {
     builder: HakariBuilder<'g>,
-    package: PackageMetadata<'g>,
+    package_set: PackageSet<'g>,
     output: OutputContext,
 }
 
 impl<'g> TempRemoveDepInner<'g> {
     fn finish(self, success: bool) -> Result<()> {
-        let package_set = self.package.to_package_set();
         let add_ops = self
             .builder
-            .add_dep_ops(&package_set, true)
+            .add_dep_ops(&self.package_set, true)
             .expect("hakari-package must be specified in hakari.toml");
 
         if success {
             info!(
                 "re-adding dependency from {} to {}",
-                self.package.name().style(self.output.styles.package_name),
+                package_names(&self.package_set, &self.output),
                 self.builder
                     .hakari_package()
                     .unwrap()
@@ -169,3 +285,11 @@ impl<'g> TempRemoveDepInner<'g> {
         Ok(())
     }
 }
+
+fn package_names(package_set: &PackageSet<'_>, output: &OutputContext) -> String {
+    package_set
+        .packages(DependencyDirection::Reverse)
+        .map(|package| package.name().style(output.styles.package_name).to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
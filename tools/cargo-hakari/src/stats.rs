@@ -0,0 +1,281 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{helpers::plural, message, output::OutputContext};
+use color_eyre::eyre::Result;
+use guppy::{
+    graph::{cargo::BuildPlatform, PackageMetadata},
+    PackageId,
+};
+use hakari::{
+    internals::{ComputedInnerMap, OutputKey},
+    Hakari,
+};
+use log::info;
+use owo_colors::OwoColorize;
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, fmt::Write};
+
+/// Prints out estimates of the benefit of the workspace-hack: the third-party crates that would
+/// otherwise be built with more than one feature set, and the number of builds this avoids.
+pub(crate) fn stats(hakari: &Hakari<'_>, top: usize, output: &OutputContext) -> Result<i32> {
+    let hakari_package = hakari
+        .builder()
+        .hakari_package()
+        .expect("hakari-package must be specified in hakari.toml");
+    let stats = HakariStats::new(hakari);
+    let platforms: Vec<_> = hakari.builder().platforms().collect();
+    let platform_str = |platform_idx: Option<usize>| match platform_idx {
+        Some(idx) => platforms[idx],
+        None => "all",
+    };
+
+    if output.is_json() {
+        message::emit(message::stats(
+            hakari_package.name(),
+            stats.to_json(top, platform_str),
+        ))?;
+        return Ok(0);
+    }
+
+    let (multi_feature, unified) = stats.counts();
+    if multi_feature == 0 {
+        info!(
+            "no third-party crates are built with more than one feature set, so {} has nothing \
+             to unify",
+            hakari_package.name().style(output.styles.package_name),
+        );
+        return Ok(0);
+    }
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{} unifies {} of {} that would otherwise be built with more than one feature set, \
+         avoiding an estimated {}",
+        hakari_package.name().style(output.styles.package_name),
+        unified,
+        plural(multi_feature, "third-party crate"),
+        plural(stats.builds_avoided(), "duplicate build"),
+    )?;
+
+    writeln!(out, "\nby platform:")?;
+    let width = stats
+        .platforms
+        .keys()
+        .map(|&platform_idx| platform_str(platform_idx).len())
+        .max()
+        .unwrap_or_default();
+    for (&platform_idx, platform_stats) in &stats.platforms {
+        writeln!(
+            out,
+            "  {}  {}, {} avoided",
+            format!("{:width$}", platform_str(platform_idx)).style(output.styles.platform),
+            plural(platform_stats.multi_feature, "crate"),
+            plural(platform_stats.builds_avoided, "build"),
+        )?;
+    }
+
+    writeln!(out, "\ntop crates by builds avoided:")?;
+    for dep_stats in stats.top(top) {
+        let package = dep_stats.package;
+        writeln!(
+            out,
+            "  {} v{}: {}, {} avoided",
+            package.name().style(output.styles.package_name),
+            package.version().style(output.styles.package_version),
+            dep_stats.feature_sets_str(),
+            plural(dep_stats.builds_avoided, "build"),
+        )?;
+    }
+    info!("{}", out.trim_end());
+    Ok(0)
+}
+
+/// Estimates of the builds unified by a workspace-hack, computed from [`Hakari::computed_map`].
+///
+/// Feature sets that only exist because of post-computation fixups are not counted, since they
+/// wouldn't be built without the workspace-hack.
+#[derive(Clone, Debug)]
+struct HakariStats<'g> {
+    deps: BTreeMap<&'g PackageId, DepStats<'g>>,
+    /// Stats for each platform in the Hakari config, or `None` for builds on any platform.
+    platforms: BTreeMap<Option<usize>, PlatformStats>,
+}
+
+#[derive(Clone, Debug)]
+struct DepStats<'g> {
+    package: PackageMetadata<'g>,
+    /// The largest number of feature sets the dependency is built with on the target platform,
+    /// across all platforms in the Hakari config.
+    target_sets: usize,
+    /// The same, for the host platform.
+    host_sets: usize,
+    /// The largest number of builds avoided on any single platform.
+    builds_avoided: usize,
+    /// Whether the workspace-hack unifies the dependency on any platform.
+    unified: bool,
+}
+
+impl<'g> DepStats<'g> {
+    /// Returns a description of the number of feature sets built, e.g. "2 feature sets on the
+    /// target, 3 on the host".
+    fn feature_sets_str(&self) -> String {
+        let mut parts = vec![];
+        for (sets, build_platform) in [(self.target_sets, "target"), (self.host_sets, "host")] {
+            if sets == 0 {
+                continue;
+            }
+            if parts.is_empty() {
+                parts.push(format!(
+                    "{} on the {}",
+                    plural(sets, "feature set"),
+                    build_platform
+                ));
+            } else {
+                parts.push(format!("{} on the {}", sets, build_platform));
+            }
+        }
+        parts.join(", ")
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct PlatformStats {
+    /// The number of third-party crates built with more than one feature set.
+    multi_feature: usize,
+    builds_avoided: usize,
+}
+
+impl<'g> HakariStats<'g> {
+    fn new(hakari: &Hakari<'g>) -> Self {
+        let graph = hakari.builder().graph();
+        let mut deps: BTreeMap<&'g PackageId, DepStats<'g>> = BTreeMap::new();
+        let mut platforms: BTreeMap<Option<usize>, PlatformStats> = BTreeMap::new();
+
+        for (&(platform_idx, dep_id), value) in &hakari.computed_map {
+            let target_sets = built_sets(&value.target_inner);
+            let host_sets = built_sets(&value.host_inner);
+            if target_sets <= 1 && host_sets <= 1 {
+                continue;
+            }
+
+            // Each feature set beyond the first is a separate build of the dependency, which the
+            // workspace-hack avoids if it has a line for the dependency.
+            let mut builds_avoided = 0;
+            for (build_platform, sets) in [
+                (BuildPlatform::Target, target_sets),
+                (BuildPlatform::Host, host_sets),
+            ] {
+                let output_key = OutputKey {
+                    platform_idx,
+                    build_platform,
+                };
+                let is_unified = hakari
+                    .output_map
+                    .get(&output_key)
+                    .is_some_and(|deps| deps.contains_key(dep_id));
+                if is_unified {
+                    builds_avoided += sets.saturating_sub(1);
+                }
+            }
+
+            let platform_stats = platforms.entry(platform_idx).or_default();
+            platform_stats.multi_feature += 1;
+            platform_stats.builds_avoided += builds_avoided;
+
+            let package = graph
+                .metadata(dep_id)
+                .expect("package ID obtained from the same graph");
+            let dep_stats = deps.entry(dep_id).or_insert(DepStats {
+                package,
+                target_sets: 0,
+                host_sets: 0,
+                builds_avoided: 0,
+                unified: false,
+            });
+            dep_stats.target_sets = dep_stats.target_sets.max(target_sets);
+            dep_stats.host_sets = dep_stats.host_sets.max(host_sets);
+            dep_stats.builds_avoided = dep_stats.builds_avoided.max(builds_avoided);
+            dep_stats.unified |= builds_avoided > 0;
+        }
+
+        Self { deps, platforms }
+    }
+
+    /// Returns the number of crates built with more than one feature set, and the number of those
+    /// unified by the workspace-hack.
+    fn counts(&self) -> (usize, usize) {
+        let unified = self
+            .deps
+            .values()
+            .filter(|dep_stats| dep_stats.unified)
+            .count();
+        (self.deps.len(), unified)
+    }
+
+    /// Returns an estimate of the number of builds avoided, assuming the workspace is built on
+    /// one platform at a time.
+    fn builds_avoided(&self) -> usize {
+        self.deps
+            .values()
+            .map(|dep_stats| dep_stats.builds_avoided)
+            .sum()
+    }
+
+    /// Returns the `count` dependencies for which the most builds are avoided.
+    fn top(&self, count: usize) -> Vec<&DepStats<'g>> {
+        let mut deps: Vec<_> = self.deps.values().collect();
+        deps.sort_by(|a, b| {
+            b.builds_avoided
+                .cmp(&a.builds_avoided)
+                .then_with(|| (b.target_sets + b.host_sets).cmp(&(a.target_sets + a.host_sets)))
+                .then_with(|| a.package.name().cmp(b.package.name()))
+        });
+        deps.truncate(count);
+        deps
+    }
+
+    fn to_json<'a>(&self, top: usize, platform_str: impl Fn(Option<usize>) -> &'a str) -> Value {
+        let (multi_feature, unified) = self.counts();
+        let platforms: Vec<_> = self
+            .platforms
+            .iter()
+            .map(|(&platform_idx, platform_stats)| {
+                json!({
+                    "platform": platform_str(platform_idx),
+                    "multi-feature-crates": platform_stats.multi_feature,
+                    "builds-avoided": platform_stats.builds_avoided,
+                })
+            })
+            .collect();
+        let top: Vec<_> = self
+            .top(top)
+            .into_iter()
+            .map(|dep_stats| {
+                json!({
+                    "name": dep_stats.package.name(),
+                    "version": dep_stats.package.version().to_string(),
+                    "target-feature-sets": dep_stats.target_sets,
+                    "host-feature-sets": dep_stats.host_sets,
+                    "builds-avoided": dep_stats.builds_avoided,
+                })
+            })
+            .collect();
+        json!({
+            "multi-feature-crates": multi_feature,
+            "unified-crates": unified,
+            "builds-avoided": self.builds_avoided(),
+            "platforms": platforms,
+            "top": top,
+        })
+    }
+}
+
+/// Returns the number of feature sets in `inner` that workspace packages cause to be built.
+fn built_sets(inner: &ComputedInnerMap<'_>) -> usize {
+    inner
+        .values()
+        .filter(|value| !value.workspace_packages.is_empty())
+        .count()
+}
@@ -0,0 +1,127 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{message, output::OutputContext};
+use color_eyre::Result;
+use log::info;
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// A phase of work timed with `--timings`.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Phase {
+    /// Running `cargo metadata` and building the package graph.
+    Metadata,
+    /// Computing the workspace-hack, including verification.
+    Compute,
+    /// Generating the workspace-hack's TOML.
+    Toml,
+    /// Regenerating `Cargo.lock` after changes.
+    Lockfile,
+}
+
+impl Phase {
+    /// Returns the name of the phase used in JSON output.
+    fn as_str(self) -> &'static str {
+        match self {
+            Phase::Metadata => "metadata",
+            Phase::Compute => "compute",
+            Phase::Toml => "toml",
+            Phase::Lockfile => "lockfile",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Phase::Metadata => "loading metadata",
+            Phase::Compute => "computing hakari",
+            Phase::Toml => "generating TOML",
+            Phase::Lockfile => "regenerating lockfile",
+        }
+    }
+}
+
+/// Records how long each [`Phase`] takes over the course of a command.
+#[derive(Debug)]
+pub(crate) struct Timings {
+    enabled: bool,
+    start: Instant,
+    /// Map of phase -> (total time spent, number of times run).
+    phases: Mutex<BTreeMap<Phase, (Duration, usize)>>,
+}
+
+impl Timings {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            start: Instant::now(),
+            phases: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Runs `f`, adding the time it took to `phase`.
+    pub(crate) fn time<T>(&self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        if !self.enabled {
+            return f();
+        }
+        let start = Instant::now();
+        let ret = f();
+        let elapsed = start.elapsed();
+
+        let mut phases = self.phases.lock().expect("timings lock isn't poisoned");
+        let (total, count) = phases.entry(phase).or_default();
+        *total += elapsed;
+        *count += 1;
+        ret
+    }
+
+    /// Prints out the time taken by each phase, if `--timings` was passed in.
+    pub(crate) fn report(&self, output: &OutputContext) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let total = self.start.elapsed();
+        let phases = self.phases.lock().expect("timings lock isn't poisoned");
+
+        if output.is_json() {
+            let phases: Vec<_> = phases
+                .iter()
+                .map(|(phase, (elapsed, count))| {
+                    json!({
+                        "phase": phase.as_str(),
+                        "seconds": elapsed.as_secs_f64(),
+                        "count": count,
+                    })
+                })
+                .collect();
+            return message::emit(message::timings(phases, total.as_secs_f64()));
+        }
+
+        let width = phases
+            .keys()
+            .map(|phase| phase.description().len())
+            .chain(std::iter::once("total".len()))
+            .max()
+            .unwrap_or_default();
+        let mut out = String::from("timings:");
+        for (phase, (elapsed, count)) in phases.iter() {
+            write!(
+                out,
+                "\n  {:width$}  {:>7.2}s",
+                phase.description(),
+                elapsed.as_secs_f64(),
+            )?;
+            if *count > 1 {
+                write!(out, " ({} runs)", count)?;
+            }
+        }
+        write!(out, "\n  {:width$}  {:>7.2}s", "total", total.as_secs_f64())?;
+        info!("{}", out);
+        Ok(())
+    }
+}
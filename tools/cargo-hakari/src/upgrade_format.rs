@@ -0,0 +1,162 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    command::{builder_and_output_from_config, read_config},
+    config_command::set_value,
+    helpers::{plural, regenerate_lockfile},
+    message,
+    output::OutputContext,
+    timings::Phase,
+};
+use camino::Utf8PathBuf;
+use color_eyre::eyre::{Result, WrapErr};
+use guppy::graph::PackageGraph;
+use hakari::{
+    cli_ops::CapturingWriter,
+    diffy::{self, PatchFormatter},
+    summaries::HakariConfig,
+    DepFormatVersion,
+};
+use log::info;
+use owo_colors::OwoColorize;
+use std::collections::BTreeMap;
+use toml_edit::Document;
+
+/// Upgrades `dep-format-version` in the Hakari config to the latest version, then regenerates the
+/// workspace-hack and updates workspace-hack dependency lines to match.
+///
+/// A single diff covering every changed file is printed out. With `dry_run`, nothing is written
+/// and this returns 1 if any file would change.
+pub(crate) fn upgrade_format(
+    package_graph: &PackageGraph,
+    dry_run: bool,
+    output: OutputContext,
+) -> Result<i32> {
+    let (config_path, contents) = read_config(package_graph)?;
+    let config: HakariConfig = contents
+        .parse()
+        .wrap_err_with(|| format!("error deserializing Hakari config at {}", config_path))?;
+    let current = config.builder.dep_format_version;
+    let latest = DepFormatVersion::latest();
+    if current >= latest {
+        info!(
+            "{} is already at the latest dep-format-version ({})",
+            config_path.as_str().style(output.styles.config_path),
+            current,
+        );
+        return Ok(0);
+    }
+
+    let document: Document = contents
+        .parse()
+        .wrap_err_with(|| format!("error parsing Hakari config at {}", config_path))?;
+    let new_contents = set_value(&document, &["dep-format-version"], &latest.to_string())?;
+
+    // Map of path -> (old contents, new contents) for every file that changes.
+    let mut changes = BTreeMap::new();
+    changes.insert(
+        config_path.clone(),
+        (contents.clone(), new_contents.clone()),
+    );
+
+    let (builder, hakari_output) = builder_and_output_from_config(
+        package_graph,
+        &config_path,
+        &new_contents,
+        &output.config_overrides,
+    )?;
+    let builders = if builder.shards().len() == 0 {
+        vec![builder]
+    } else {
+        builder.shard_builders()
+    };
+    for builder in builders {
+        // Dependency lines written in the old format are updated by manage-deps.
+        let ops = builder
+            .manage_dep_ops(&package_graph.resolve_workspace())
+            .expect("hakari-package must be specified in hakari.toml");
+        let mut writer = CapturingWriter::new();
+        ops.apply_with(&mut writer)
+            .wrap_err("error computing workspace-hack dependency updates")?;
+        for (path, new_manifest) in writer.into_files() {
+            let old_manifest = std::fs::read_to_string(&path)
+                .wrap_err_with(|| format!("error reading {}", path))?;
+            let new_manifest = String::from_utf8(new_manifest)
+                .wrap_err_with(|| format!("new contents of {} are not UTF-8", path))?;
+            changes.insert(path, (old_manifest, new_manifest));
+        }
+
+        let hakari = output.timings.time(Phase::Compute, || builder.compute());
+        let toml_out = output
+            .timings
+            .time(Phase::Toml, || hakari.to_toml_string(&hakari_output))
+            .wrap_err("error generating new workspace-hack contents")?;
+        let existing_toml = hakari
+            .read_toml()
+            .expect("hakari-package must be specified in hakari.toml")?;
+        if existing_toml.is_changed(&toml_out) {
+            let mut new_toml = String::with_capacity(existing_toml.contents().len());
+            existing_toml
+                .write_to_fmt(&toml_out, &mut new_toml)
+                .expect("writing to a string is infallible");
+            changes.insert(
+                existing_toml.toml_path().to_owned(),
+                (existing_toml.contents().to_owned(), new_toml),
+            );
+        }
+    }
+    changes.retain(|_, (old, new)| old != new);
+
+    print_diff(package_graph, &changes, &output)?;
+    if dry_run {
+        return Ok(1);
+    }
+
+    for (path, (_, new)) in &changes {
+        std::fs::write(path, new).wrap_err_with(|| format!("error writing {}", path))?;
+        if output.is_json() {
+            message::emit(message::write(path, true))?;
+        }
+    }
+    regenerate_lockfile(output.clone())?;
+    info!(
+        "upgraded dep-format-version from {} to {} ({} updated)",
+        current,
+        latest,
+        plural(changes.len(), "file"),
+    );
+    Ok(0)
+}
+
+/// Prints out the changes to every file as a single diff.
+fn print_diff(
+    package_graph: &PackageGraph,
+    changes: &BTreeMap<Utf8PathBuf, (String, String)>,
+    output: &OutputContext,
+) -> Result<()> {
+    let workspace_root = package_graph.workspace().root();
+    let mut formatter = PatchFormatter::new();
+    if output.color.is_enabled() {
+        formatter = formatter.with_color();
+    }
+
+    let mut diff = String::new();
+    for (path, (old, new)) in changes {
+        let patch = diffy::create_patch(old, new);
+        if output.is_json() {
+            message::emit(message::diff(path, &patch))?;
+            continue;
+        }
+        let rel_path = path.strip_prefix(workspace_root).unwrap_or(path);
+        diff.push_str(&format!(
+            "\n{}:\n{}",
+            rel_path.style(output.styles.config_path),
+            formatter.fmt_patch(&patch),
+        ));
+    }
+    if !diff.is_empty() {
+        info!("{}", diff);
+    }
+    Ok(())
+}
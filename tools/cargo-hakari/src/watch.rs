@@ -0,0 +1,169 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    command::{make_builder_and_output, CommandWithBuilder, PackageSelection},
+    output::OutputContext,
+    timings::Phase,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{Result, WrapErr};
+use guppy::graph::PackageGraph;
+use hakari::summaries::{DEFAULT_CONFIG_PATH, FALLBACK_CONFIG_PATH};
+use log::{error, info};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use owo_colors::OwoColorize;
+use std::{
+    collections::BTreeSet,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+/// How long to wait for further changes after a change is detected, so that a burst of changes
+/// (e.g. an editor saving several files) results in a single run.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watches workspace manifests and the Hakari config, running `generate` and `manage-deps` every
+/// time they change.
+///
+/// If `dry_run` is true, diffs and pending operations are printed out instead of being applied.
+///
+/// Only returns on error.
+pub(crate) fn watch(dry_run: bool, output: OutputContext) -> Result<i32> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).wrap_err("error initializing file watcher")?;
+    let mut watched = WatchedFiles::default();
+
+    loop {
+        // Errors are usually caused by manifests that are being edited, so report them and wait
+        // for the next change.
+        match run_once(dry_run, &output) {
+            Ok(new_watched) => watched.update(&mut watcher, new_watched)?,
+            // Without a first successful run, there's nothing to watch.
+            Err(err) if watched.files.is_empty() => return Err(err),
+            Err(err) => error!("{:?}", err),
+        }
+        info!(
+            "{} for changes to {} files (press Ctrl-C to exit)",
+            "watching".style(output.styles.command),
+            watched.files.len(),
+        );
+
+        wait_for_change(&rx, &watched)?;
+        info!("change detected, re-running");
+    }
+}
+
+/// Runs `generate` and `manage-deps` once, returning the files to watch.
+fn run_once(dry_run: bool, output: &OutputContext) -> Result<WatchedFiles> {
+    let package_graph = output
+        .timings
+        .time(Phase::Metadata, || {
+            output.cargo_opts.metadata_command().build_graph()
+        })
+        .wrap_err("building package graph failed")?;
+    let watched = WatchedFiles::new(&package_graph);
+    let (builder, hakari_output) =
+        make_builder_and_output(&package_graph, &output.config_overrides)?;
+
+    let commands = [
+        CommandWithBuilder::Generate {
+            diff: dry_run,
+            show_diff: false,
+            verify: false,
+            cargo_check: false,
+        },
+        CommandWithBuilder::ManageDeps {
+            packages: PackageSelection::default(),
+            dry_run,
+            // There's nobody to answer prompts in watch mode.
+            yes: true,
+        },
+    ];
+    for command in commands {
+        command.exec_any(builder.clone(), hakari_output.clone(), output.clone())?;
+    }
+
+    Ok(watched)
+}
+
+/// Blocks until one of the watched files changes, then waits for changes to settle down.
+fn wait_for_change(rx: &Receiver<notify::Result<Event>>, watched: &WatchedFiles) -> Result<()> {
+    loop {
+        let event = rx
+            .recv()
+            .wrap_err("file watcher stopped unexpectedly")?
+            .wrap_err("error watching files")?;
+        if watched.is_relevant(&event) {
+            break;
+        }
+    }
+
+    loop {
+        match rx.recv_timeout(DEBOUNCE) {
+            Ok(_) => continue,
+            Err(RecvTimeoutError::Timeout) => return Ok(()),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(RecvTimeoutError::Disconnected)
+                    .wrap_err("file watcher stopped unexpectedly")
+            }
+        }
+    }
+}
+
+/// The set of files that cause a re-run when changed.
+#[derive(Debug, Default)]
+struct WatchedFiles {
+    files: BTreeSet<Utf8PathBuf>,
+    // Directories are watched rather than files, since many editors save files by replacing them.
+    dirs: BTreeSet<Utf8PathBuf>,
+}
+
+impl WatchedFiles {
+    fn new(package_graph: &PackageGraph) -> Self {
+        let workspace = package_graph.workspace();
+        let root = workspace.root();
+
+        let mut files: BTreeSet<_> = workspace
+            .iter()
+            .map(|package| package.manifest_path().to_owned())
+            .collect();
+        files.insert(root.join("Cargo.toml"));
+        files.insert(root.join(DEFAULT_CONFIG_PATH));
+        files.insert(root.join(FALLBACK_CONFIG_PATH));
+
+        let dirs = files
+            .iter()
+            .filter_map(|file| file.parent())
+            .filter(|dir| dir.is_dir())
+            .map(Utf8Path::to_path_buf)
+            .collect();
+        Self { files, dirs }
+    }
+
+    /// Starts watching new directories and stops watching old ones.
+    fn update(&mut self, watcher: &mut RecommendedWatcher, new: WatchedFiles) -> Result<()> {
+        for dir in self.dirs.difference(&new.dirs) {
+            // The directory may have been removed, so ignore errors.
+            let _ = watcher.unwatch(dir.as_std_path());
+        }
+        for dir in new.dirs.difference(&self.dirs) {
+            watcher
+                .watch(dir.as_std_path(), RecursiveMode::NonRecursive)
+                .wrap_err_with(|| format!("error watching directory {}", dir))?;
+        }
+        *self = new;
+        Ok(())
+    }
+
+    fn is_relevant(&self, event: &Event) -> bool {
+        if matches!(event.kind, EventKind::Access(_)) {
+            return false;
+        }
+        event
+            .paths
+            .iter()
+            .any(|path| Utf8Path::from_path(path).is_some_and(|path| self.files.contains(path)))
+    }
+}
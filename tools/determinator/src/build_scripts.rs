@@ -0,0 +1,220 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use guppy::graph::{BuildTargetId, PackageGraph, PackageMetadata};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+};
+
+/// Paths that the build scripts of workspace packages declare through `cargo:rerun-if-changed`.
+///
+/// Build scripts often read files outside their package's directory, for example shared protobuf
+/// definitions. If such a file changes, the package is rebuilt by Cargo, but the determinator
+/// doesn't know about it by default. Once this is passed in to
+/// [`Determinator::set_build_script_paths`](crate::Determinator::set_build_script_paths), a changed
+/// path that is declared here, or that is inside a directory declared here, marks the package
+/// changed.
+///
+/// Declarations can be gathered from:
+/// * the source of build scripts, through [`from_sources`](Self::from_sources), or
+/// * build script output captured from a previous build, for example from
+///   `target/debug/build/<package>-<hash>/output`, through
+///   [`add_build_output`](Self::add_build_output).
+///
+/// Declared paths outside the workspace are ignored, since they can't show up as changed paths.
+///
+/// # Examples
+///
+/// In TOML format:
+///
+/// ```toml
+/// [packages]
+/// my-grpc-service = ["proto", "build-support/codegen.toml"]
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BuildScriptPaths {
+    /// Map of workspace package names to the paths their build scripts declare, relative to the
+    /// root of the workspace.
+    #[serde(default)]
+    pub packages: BTreeMap<String, BTreeSet<Utf8PathBuf>>,
+}
+
+impl BuildScriptPaths {
+    /// Creates a new, empty set of build script paths.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deserializes build script paths from the given TOML string.
+    pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    /// Scans the build scripts of workspace packages in `graph` for `cargo:rerun-if-changed`
+    /// declarations.
+    ///
+    /// Only declarations with a string literal path are found: paths computed at runtime are
+    /// skipped. If a build script's declarations aren't all literals, consider using
+    /// [`add_build_output`](Self::add_build_output) instead.
+    ///
+    /// Returns an error if a build script couldn't be read.
+    pub fn from_sources(graph: &PackageGraph) -> io::Result<Self> {
+        let mut paths = Self::new();
+        for package in graph.workspace().iter() {
+            if let Some(build_script) = package.build_target(&BuildTargetId::BuildScript) {
+                let source = fs::read_to_string(build_script.path())?;
+                paths.add_declarations(graph, package, parse_source(&source));
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Adds the `cargo:rerun-if-changed` declarations in `output`, as printed by the build script
+    /// for the workspace package `package_name`.
+    ///
+    /// Returns an error if `package_name` isn't in the workspace.
+    pub fn add_build_output(
+        &mut self,
+        graph: &PackageGraph,
+        package_name: &str,
+        output: &str,
+    ) -> Result<&mut Self, guppy::Error> {
+        let package = graph.workspace().member_by_name(package_name)?;
+        self.add_declarations(graph, package, parse_output(output));
+        Ok(self)
+    }
+
+    /// Returns the names of the packages that declare `path` or one of its ancestors.
+    pub(crate) fn matches<'a>(&'a self, path: &'a Utf8Path) -> impl Iterator<Item = &'a str> + 'a {
+        self.packages
+            .iter()
+            .filter(move |(_, declared)| declared.iter().any(|declared| path.starts_with(declared)))
+            .map(|(name, _)| name.as_str())
+    }
+
+    fn add_declarations<'a>(
+        &mut self,
+        graph: &PackageGraph,
+        package: PackageMetadata<'_>,
+        declarations: impl IntoIterator<Item = &'a str>,
+    ) {
+        let workspace_root = graph.workspace().root();
+        let package_path = package
+            .source()
+            .workspace_path()
+            .expect("workspace members have workspace paths");
+
+        // Relative paths are relative to the package's directory.
+        let paths: Vec<_> = declarations
+            .into_iter()
+            .filter_map(|declared| {
+                let declared = Utf8Path::new(declared);
+                let path = match declared.strip_prefix(workspace_root) {
+                    Ok(path) => path.to_path_buf(),
+                    Err(_) if declared.is_absolute() => return None,
+                    Err(_) => package_path.join(declared),
+                };
+                normalize(&path)
+            })
+            .collect();
+        if !paths.is_empty() {
+            self.packages
+                .entry(package.name().to_owned())
+                .or_default()
+                .extend(paths);
+        }
+    }
+}
+
+const DIRECTIVES: [&str; 2] = ["cargo:rerun-if-changed=", "cargo::rerun-if-changed="];
+
+/// Returns the paths in string literals containing `cargo:rerun-if-changed`.
+fn parse_source(source: &str) -> Vec<&str> {
+    let mut paths = vec![];
+    for directive in DIRECTIVES {
+        for (start, _) in source.match_indices(directive) {
+            let rest = &source[start + directive.len()..];
+            let end = rest.find(['"', '\\', '\n']).unwrap_or(rest.len());
+            let path = &rest[..end];
+            // Paths with format arguments are computed at runtime.
+            if !path.is_empty() && !path.contains('{') {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
+
+/// Returns the paths declared in the output of a build script.
+fn parse_output(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .filter_map(|line| {
+            DIRECTIVES
+                .iter()
+                .find_map(|directive| line.strip_prefix(directive))
+        })
+        .map(|path| path.trim_end())
+        .filter(|path| !path.is_empty())
+        .collect()
+}
+
+/// Lexically normalizes a path relative to the workspace root, or returns `None` if it points
+/// outside the workspace.
+fn normalize(path: &Utf8Path) -> Option<Utf8PathBuf> {
+    let mut normalized = Utf8PathBuf::new();
+    for component in path.components() {
+        match component {
+            Utf8Component::Normal(component) => normalized.push(component),
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Utf8Component::RootDir | Utf8Component::Prefix(_) => return None,
+        }
+    }
+    // A declaration of the workspace root itself would match every path: ignore it like paths
+    // outside the workspace.
+    (!normalized.as_str().is_empty()).then_some(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_declarations() {
+        let source = r#"
+            fn main() {
+                println!("cargo:rerun-if-changed=../proto/service.proto");
+                println!("cargo::rerun-if-changed=build.rs\n");
+                println!("cargo:rerun-if-changed={}", computed_path());
+            }
+        "#;
+        assert_eq!(
+            parse_source(source),
+            vec!["../proto/service.proto", "build.rs"],
+        );
+
+        let output = "cargo:rustc-cfg=foo\n\
+                      cargo:rerun-if-changed=/ws/proto\n\
+                      cargo::rerun-if-changed=codegen.toml\n";
+        assert_eq!(parse_output(output), vec!["/ws/proto", "codegen.toml"]);
+    }
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(
+            normalize(Utf8Path::new("foo/./bar/../baz")),
+            Some("foo/baz".into()),
+        );
+        assert_eq!(normalize(Utf8Path::new("foo/../..")), None);
+        assert_eq!(normalize(Utf8Path::new("foo/..")), None);
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! On-disk caching for determinator results.
+
+use crate::{
+    reasons::{DeterminatorReason, DeterminatorSummary, ReasonSummary},
+    rules::{DeterminatorRules, PackageMetadataRule},
+    DeterminatorSet,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use guppy::{
+    graph::{DependencyDirection, PackageGraph, PackageSet},
+    PackageId,
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, hash::Hasher, io, path::PathBuf, process};
+use twox_hash::XxHash64;
+
+/// Bump this whenever the format of `CacheEntry` changes.
+const CACHE_FORMAT_VERSION: u32 = 1;
+
+/// An on-disk cache of determinator results.
+///
+/// Evaluating the same pair of revisions again, for example across several CI pipelines, can reuse
+/// a previous result instead of running build simulations again. Pass a cache in to
+/// [`Determinator::compute_cached`](crate::Determinator::compute_cached).
+///
+/// Results are keyed by:
+/// * fingerprints of the old and new package graphs (see
+///   [`GraphFingerprint`](guppy::graph::GraphFingerprint)),
+/// * a hash of the rules (including rules declared in package metadata) and Cargo options, and
+/// * a hash of the changed paths and content hashes.
+///
+/// Fingerprints don't include the location of the workspace on disk, so checkouts at different
+/// paths share cache entries. Each entry is stored as a JSON file in the cache directory.
+/// Unreadable or outdated entries are treated as cache misses.
+#[derive(Clone, Debug)]
+pub struct DeterminatorCache {
+    dir: Utf8PathBuf,
+}
+
+impl DeterminatorCache {
+    /// Creates a new cache that stores entries in the given directory.
+    ///
+    /// The directory is created if it doesn't exist.
+    pub fn new(dir: impl Into<Utf8PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns the directory this cache stores entries in.
+    pub fn dir(&self) -> &Utf8Path {
+        &self.dir
+    }
+
+    pub(crate) fn read<'g>(
+        &self,
+        key: &str,
+        graph: &'g PackageGraph,
+    ) -> Option<DeterminatorSet<'g>> {
+        let contents = fs::read(self.entry_path(key)).ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&contents).ok()?;
+        if entry.format_version != CACHE_FORMAT_VERSION {
+            return None;
+        }
+        entry.into_determinator_set(graph)
+    }
+
+    pub(crate) fn write<'g>(
+        &self,
+        key: &str,
+        determinator_set: &DeterminatorSet<'g>,
+        graph: &'g PackageGraph,
+    ) -> io::Result<()> {
+        let entry = CacheEntry::new(determinator_set, graph);
+        fs::create_dir_all(&self.dir)?;
+
+        // Write to a temporary file and rename it, so that concurrent readers never see a
+        // partially written entry.
+        let path = self.entry_path(key);
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(format!(".tmp-{}", process::id()));
+        let temp_path = PathBuf::from(temp_path);
+
+        let contents = serde_json::to_vec(&entry).map_err(io::Error::from)?;
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, path)
+    }
+
+    fn entry_path(&self, key: &str) -> Utf8PathBuf {
+        self.dir.join(format!("determinator-{}.json", key))
+    }
+}
+
+/// Hashes serializable inputs to the determinator for use in cache keys.
+#[derive(Default)]
+pub(crate) struct CacheKeyHasher(XxHash64);
+
+impl CacheKeyHasher {
+    pub(crate) fn new() -> Self {
+        let mut hasher = Self::default();
+        hasher.write_u32(CACHE_FORMAT_VERSION);
+        hasher.write_str(env!("CARGO_PKG_VERSION"));
+        hasher
+    }
+
+    pub(crate) fn write_u32(&mut self, n: u32) {
+        self.0.write(&n.to_le_bytes());
+    }
+
+    pub(crate) fn write_u64(&mut self, n: u64) {
+        self.0.write(&n.to_le_bytes());
+    }
+
+    pub(crate) fn write_str(&mut self, s: &str) {
+        self.0.write(&(s.len() as u64).to_le_bytes());
+        self.0.write(s.as_bytes());
+    }
+
+    /// Writes the JSON serialization of `value`.
+    pub(crate) fn write_json(&mut self, value: &impl Serialize) {
+        let json = serde_json::to_string(value).expect("serializing to a string always succeeds");
+        self.write_str(&json);
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+}
+
+/// Returns a hash of the rules, used to key cache entries.
+///
+/// If package metadata rules are enabled, the rules declared by workspace packages in `graph` are
+/// hashed as well, since they aren't part of the graph fingerprint.
+pub(crate) fn rules_hash(rules: &DeterminatorRules, graph: &PackageGraph) -> u64 {
+    let mut hasher = CacheKeyHasher::new();
+    hasher.write_json(rules);
+    if rules.use_package_metadata() {
+        for (name, package) in graph.workspace().iter_by_name() {
+            // Invalid rules are rejected while processing the rules, so they can be ignored here.
+            if let Ok(Some(rule)) = PackageMetadataRule::from_package(&package) {
+                hasher.write_str(name);
+                hasher.write_json(&rule);
+            }
+        }
+    }
+    hasher.finish()
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct CacheEntry {
+    format_version: u32,
+    path_changed: Vec<String>,
+    summary_changed: Vec<String>,
+    affected: Vec<String>,
+    summary: DeterminatorSummary,
+}
+
+impl CacheEntry {
+    fn new<'g>(determinator_set: &DeterminatorSet<'g>, graph: &'g PackageGraph) -> Self {
+        let names = |package_set: &PackageSet<'_>| -> Vec<String> {
+            package_set
+                .packages(DependencyDirection::Forward)
+                .map(|package| package.name().to_owned())
+                .collect()
+        };
+
+        Self {
+            format_version: CACHE_FORMAT_VERSION,
+            path_changed: names(&determinator_set.path_changed_set),
+            summary_changed: names(&determinator_set.summary_changed_set),
+            affected: names(&determinator_set.affected_set),
+            summary: determinator_set.to_summary(graph),
+        }
+    }
+
+    /// Converts this entry back into a `DeterminatorSet`, or returns `None` if it doesn't match
+    /// the graph.
+    fn into_determinator_set(self, graph: &PackageGraph) -> Option<DeterminatorSet<'_>> {
+        let workspace = graph.workspace();
+        let id = |name: &str| -> Option<&PackageId> {
+            workspace
+                .member_by_name(name)
+                .ok()
+                .map(|package| package.id())
+        };
+
+        let reasons = self
+            .summary
+            .affected
+            .into_iter()
+            .map(|(name, summary)| {
+                let reason = match summary {
+                    ReasonSummary::PathChanged { paths } => DeterminatorReason::PathChanged(paths),
+                    ReasonSummary::HashChanged => DeterminatorReason::HashChanged,
+                    ReasonSummary::SummaryChanged { changes } => {
+                        DeterminatorReason::SummaryChanged(changes)
+                    }
+                    ReasonSummary::Affected { chain, via } => {
+                        // The last element of the chain is this package, and the one before it
+                        // is the package it was affected by.
+                        let from = chain.len().checked_sub(2).map(|ix| chain[ix].as_str())?;
+                        DeterminatorReason::Affected {
+                            from: id(from)?,
+                            via,
+                        }
+                    }
+                };
+                Some((id(&name)?, reason))
+            })
+            .collect::<Option<_>>()?;
+
+        Some(DeterminatorSet {
+            path_changed_set: graph.resolve_workspace_names(&self.path_changed).ok()?,
+            summary_changed_set: graph.resolve_workspace_names(&self.summary_changed).ok()?,
+            affected_set: graph.resolve_workspace_names(&self.affected).ok()?,
+            reasons,
+        })
+    }
+}
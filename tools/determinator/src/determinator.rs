@@ -2,10 +2,16 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    build_scripts::BuildScriptPaths,
+    cache::{rules_hash, CacheKeyHasher, DeterminatorCache},
     errors::RulesError,
+    explain::{dependency_path, ExplainedPath, PackageExplanation, UpstreamChange},
+    hashes::ContentHashes,
+    manifest::changed_sections,
+    reasons::{AffectedVia, ChangedPath, DeterminatorReason, SummaryChange},
     rules::{
-        DeterminatorPostRule, DeterminatorRules, MarkChangedImpl, PathMatch, PathRuleImpl,
-        RulesImpl,
+        CustomRule, CustomRuleDecision, DeterminatorPostRule, DeterminatorRules, ManifestSection,
+        MarkChangedImpl, PathMatch, RuleIndex, RulesImpl,
     },
 };
 use ahash::AHashMap;
@@ -14,15 +20,20 @@ use globset::Candidate;
 use guppy::{
     graph::{
         cargo::{CargoOptions, CargoSet},
-        feature::{FeatureFilter, FeatureSet, StandardFeatures},
-        DependencyDirection, PackageGraph, PackageMetadata, PackageSet, Workspace,
+        feature::{named_feature_filter, FeatureFilter, FeatureSet, StandardFeatures},
+        summaries::CargoOptionsSummary,
+        DependencyDirection, FingerprintOptions, PackageGraph, PackageMetadata, PackageSet,
+        PackageSource,
     },
     platform::PlatformSpec,
     PackageId,
 };
 use petgraph::{graphmap::GraphMap, Directed};
 use rayon::prelude::*;
-use std::collections::{hash_map::Entry, HashSet};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, BTreeSet, HashSet},
+    io,
+};
 
 /// Determine target dependencies from changed files and packages in a workspace.
 ///
@@ -31,39 +42,68 @@ use std::collections::{hash_map::Entry, HashSet};
 /// This struct has two lifetime parameters:
 /// * `'g` stands for the lifetime of the new graph. The `DeterminatorSet` will be bound to this
 ///   lifetime.
-/// * `'a` is the lifetime of the old graph, Cargo options, and changed paths. The `DeterminatorSet`
-///   will not be bound to this lifetime.
+/// * `'a` is the lifetime of the old graph, Cargo options, changed paths, custom rules, and build
+///   script paths. The `DeterminatorSet` will not be bound to this lifetime.
 #[derive(Clone, Debug)]
 pub struct Determinator<'g, 'a> {
     old: &'a PackageGraph,
     new: &'g PackageGraph,
     rules: RulesImpl<'g>,
+    rules_hash: u64,
     cargo_options: Option<&'a CargoOptions<'a>>,
     old_features_only: Option<FeatureSet<'a>>,
     new_features_only: Option<FeatureSet<'g>>,
+    extra_features: Vec<(StandardFeatures, Vec<&'a str>)>,
     changed_paths: Vec<&'a Utf8Path>,
+    hash_changed_ids: Vec<&'g PackageId>,
+    custom_rules: Vec<&'a dyn CustomRule>,
+    build_script_paths: Option<&'a BuildScriptPaths>,
+    /// The sections of the root `Cargo.toml` that changed, if its contents were provided.
+    workspace_manifest_changes: Option<BTreeSet<ManifestSection>>,
+    /// Map of non-workspace path dependencies, relative to the workspace root, to the workspace
+    /// packages that depend on them.
+    path_dependencies: AHashMap<&'g Utf8Path, Vec<&'g PackageId>>,
 }
 
 impl<'g, 'a> Determinator<'g, 'a> {
     /// Creates a new instance of `Determinator` with old and new package graphs.
+    ///
+    /// If a workspace package declares an invalid
+    /// [package metadata rule](crate::rules::PackageMetadataRule), package metadata rules are
+    /// ignored until [`set_rules`](Self::set_rules) is called, which returns the error.
     pub fn new(old: &'a PackageGraph, new: &'g PackageGraph) -> Self {
+        let mut rules = DeterminatorRules::default();
+        let rules_impl = RulesImpl::new(new, &rules).unwrap_or_else(|_| {
+            rules.set_use_package_metadata(false);
+            RulesImpl::new(new, &rules).expect("default rules should parse")
+        });
         Self {
             old,
             new,
-            rules: RulesImpl::new(new, &DeterminatorRules::default())
-                .expect("default rules should parse"),
+            rules: rules_impl,
+            rules_hash: rules_hash(&rules, new),
             cargo_options: None,
             old_features_only: None,
             new_features_only: None,
+            extra_features: vec![],
             changed_paths: vec![],
+            hash_changed_ids: vec![],
+            custom_rules: vec![],
+            build_script_paths: None,
+            workspace_manifest_changes: None,
+            path_dependencies: path_dependencies(new),
         }
     }
 
     /// Adds a list of changed paths. This list is used as a source of information for the
     /// determinator.
     ///
-    /// This should consist of paths that are changed since the base revision. Paths on Windows may
-    /// use either `/` or `\\` as separators.
+    /// This should consist of paths that are changed since the base revision, relative to the
+    /// workspace root. Paths on Windows may use either `/` or `\\` as separators.
+    ///
+    /// Paths inside path dependencies outside the workspace, such as `../common/src/lib.rs`,
+    /// mark the workspace packages that depend on them changed. Other paths outside the workspace
+    /// root go through rules like any other path.
     ///
     /// [`Utf8Paths0`](crate::Utf8Paths0) in this crate provides a convenient way to handle
     /// null-separated paths as produced by source control systems.
@@ -100,6 +140,31 @@ impl<'g, 'a> Determinator<'g, 'a> {
         self
     }
 
+    /// Adds the changes between two content hash manifests. This may be used instead of, or in
+    /// addition to, [`add_changed_paths`](Self::add_changed_paths).
+    ///
+    /// * Files that were added, removed or changed between `old` and `new` are added as changed
+    ///   paths.
+    /// * Workspace packages that were added or changed are marked changed, without going through
+    ///   path rules. Package names that aren't in the new workspace are ignored.
+    ///
+    /// For more, see the documentation for [`ContentHashes`].
+    pub fn add_changed_hashes(
+        &mut self,
+        old: &'a ContentHashes,
+        new: &'a ContentHashes,
+    ) -> &mut Self {
+        self.changed_paths.extend(old.changed_files(new));
+        let workspace = self.new.workspace();
+        self.hash_changed_ids.extend(
+            old.changed_packages(new)
+                .into_iter()
+                .filter_map(|name| workspace.member_by_name(name).ok())
+                .map(|package| package.id()),
+        );
+        self
+    }
+
     /// Returns what *would* happen if a given path was added to the changed set.
     ///
     /// This does not add any path to the changed set, but indicates what *would* happen if a path
@@ -114,23 +179,62 @@ impl<'g, 'a> Determinator<'g, 'a> {
         path: impl AsRef<Utf8Path>,
         match_cb: impl FnMut(&'g PackageId),
     ) -> PathMatch {
-        process_path(
-            path.as_ref(),
-            &self.new.workspace(),
-            &self.rules.path_rules,
-            match_cb,
-        )
+        let mut match_cb = match_cb;
+        let (path_match, _) = process_path(path.as_ref(), self, |id, _| match_cb(id));
+        path_match
     }
 
     /// Processes and configures determinator rules.
     ///
     /// Returns an error if the rules were invalid in some way.
     pub fn set_rules(&mut self, rules: &DeterminatorRules) -> Result<&mut Self, RulesError> {
-        let rules = RulesImpl::new(self.new, rules)?;
-        self.rules = rules;
+        self.rules = RulesImpl::new(self.new, rules)?;
+        self.rules_hash = rules_hash(rules, self.new);
         Ok(self)
     }
 
+    /// Registers a path rule implemented in Rust.
+    ///
+    /// Custom rules are applied to each changed path in the order they were registered, before
+    /// any path rules set through [`set_rules`](Self::set_rules). Matches are reported with
+    /// [`RuleIndex::Plugin`].
+    ///
+    /// For more, see the documentation for [`CustomRule`].
+    pub fn add_custom_rule(&mut self, rule: &'a dyn CustomRule) -> &mut Self {
+        self.custom_rules.push(rule);
+        self
+    }
+
+    /// Configures paths declared by build scripts through `cargo:rerun-if-changed`.
+    ///
+    /// A changed path that a build script declares, or that is inside a directory a build script
+    /// declares, marks the build script's package changed. This is applied before any other rules,
+    /// and processing continues after it. Matches are reported with [`RuleIndex::BuildScript`].
+    ///
+    /// A changed path that is only matched through build script declarations doesn't cause every
+    /// package to be marked changed.
+    ///
+    /// For more, see the documentation for [`BuildScriptPaths`].
+    pub fn set_build_script_paths(&mut self, paths: &'a BuildScriptPaths) -> &mut Self {
+        self.build_script_paths = Some(paths);
+        self
+    }
+
+    /// Configures the old and new contents of the root `Cargo.toml` of the workspace.
+    ///
+    /// By default, a change to the root `Cargo.toml` causes every package to be marked changed.
+    /// Once this is called, changes to the root `Cargo.toml` are classified by section instead,
+    /// and each kind of change has the effect configured through
+    /// [`DeterminatorRules::workspace_manifest`]. Path rules aren't applied to the root
+    /// `Cargo.toml`. Matches are reported with [`RuleIndex::WorkspaceManifest`].
+    ///
+    /// For more, see the documentation for
+    /// [`WorkspaceManifestRules`](crate::rules::WorkspaceManifestRules).
+    pub fn set_workspace_manifests(&mut self, old: &str, new: &str) -> &mut Self {
+        self.workspace_manifest_changes = Some(changed_sections(old, new));
+        self
+    }
+
     /// Configures Cargo options.
     ///
     /// These options are used to determine if the build for a particular package has changed.
@@ -200,28 +304,56 @@ impl<'g, 'a> Determinator<'g, 'a> {
         Ok(self)
     }
 
+    /// Adds a feature set to run build simulations with, in addition to the standard ones.
+    ///
+    /// By default, each workspace package is simulated with no features, default features, and all
+    /// features enabled. This may miss changes that only show up with a particular combination of
+    /// features, such as a dependency gaining a feature only when one of several optional features
+    /// is enabled. If builds differ by features across CI jobs, add each job's features here.
+    ///
+    /// Each workspace package is simulated with the features selected by `base`, plus any of
+    /// `features` that it defines. This is equivalent to `cargo build -p <package> --features
+    /// <features>` (with `--no-default-features` if `base` is [`StandardFeatures::None`]).
+    pub fn add_feature_set(
+        &mut self,
+        base: StandardFeatures,
+        features: impl IntoIterator<Item = &'a str>,
+    ) -> &mut Self {
+        self.extra_features
+            .push((base, features.into_iter().collect()));
+        self
+    }
+
     /// Uses the old and new sets and the list of changed files to compute the list
     /// of projects that is affected.
     pub fn compute(&self) -> DeterminatorSet<'g> {
         let mut build_state = BuildState::new(self);
 
         // 1-2. Process every changed path.
-        for path in &self.changed_paths {
-            build_state = match build_state.process_path(path) {
-                Some(build_state) => build_state,
-                None => {
-                    // The build state was discarded, which means that the entire workspace is
-                    // changed and affected.
-                    let path_changed_set = self.new.resolve_workspace();
-                    let affected_set = path_changed_set.clone();
-                    return DeterminatorSet {
-                        path_changed_set,
-                        // This is an empty set.
-                        summary_changed_set: self.new.resolve_none(),
-                        affected_set,
-                    };
-                }
-            }
+        if let Err(changed_path) = build_state.process_paths(&self.changed_paths) {
+            // The entire workspace is changed and affected.
+            let path_changed_set = self.new.resolve_workspace();
+            let affected_set = path_changed_set.clone();
+            let reasons = path_changed_set
+                .package_ids(DependencyDirection::Forward)
+                .map(|id| {
+                    let reason = DeterminatorReason::PathChanged(vec![changed_path.clone()]);
+                    (id, reason)
+                })
+                .collect();
+            return DeterminatorSet {
+                path_changed_set,
+                // This is an empty set.
+                summary_changed_set: self.new.resolve_none(),
+                affected_set,
+                reasons,
+            };
+        }
+
+        // Packages with changed content hashes are treated like packages with changed paths.
+        for &id in &self.hash_changed_ids {
+            build_state.path_changed_ids.insert(id);
+            build_state.hash_changed_ids.insert(id);
         }
 
         // 3. Construct the path changed set from the given IDs.
@@ -232,24 +364,270 @@ impl<'g, 'a> Determinator<'g, 'a> {
 
         // 4. Use build summaries as another source of changes.
         build_state.process_build_summaries();
+        let summary_changed_ids: HashSet<_> = build_state.summary_changes.keys().copied().collect();
         let summary_changed_set = self
             .new
-            .resolve_ids(build_state.summary_changed_ids.iter().copied())
+            .resolve_ids(summary_changed_ids.iter().copied())
             .expect("package IDs are all valid");
 
         // 5. The affected set is the transitive closure of the graph constructed by looking at both
         // the build cache and Cargo rules.
-        let affected_set = build_state.reverse_index.affected_closure(
+        let (affected_set, affected_from) = build_state.reverse_index.affected_closure(
             self.new,
             &build_state.path_changed_ids,
-            &build_state.summary_changed_ids,
+            &summary_changed_ids,
+        );
+
+        // 6. Record why each package was selected.
+        let mut reasons = BTreeMap::new();
+        reasons.extend(
+            affected_from
+                .into_iter()
+                .map(|(id, (from, via))| (id, DeterminatorReason::Affected { from, via })),
+        );
+        reasons.extend(
+            build_state
+                .summary_changes
+                .into_iter()
+                .map(|(id, changes)| (id, DeterminatorReason::SummaryChanged(changes))),
+        );
+        reasons.extend(
+            build_state
+                .hash_changed_ids
+                .into_iter()
+                .map(|id| (id, DeterminatorReason::HashChanged)),
+        );
+        reasons.extend(
+            build_state
+                .path_reasons
+                .into_iter()
+                .map(|(id, paths)| (id, DeterminatorReason::PathChanged(paths))),
         );
 
         DeterminatorSet {
             path_changed_set,
             summary_changed_set,
             affected_set,
+            reasons,
+        }
+    }
+
+    /// Like [`compute`](Self::compute), but reuses a previous result from `cache` if one is
+    /// available, and stores the result in `cache` otherwise.
+    ///
+    /// Results are only cached if no [custom rules](Self::add_custom_rule) are registered, since
+    /// their behavior can't be captured in a cache key. For more about how results are keyed, see
+    /// the documentation for [`DeterminatorCache`].
+    ///
+    /// Returns an error if the result couldn't be written to the cache.
+    pub fn compute_cached(&self, cache: &DeterminatorCache) -> io::Result<DeterminatorSet<'g>> {
+        let Some(key) = self.cache_key() else {
+            return Ok(self.compute());
+        };
+        if let Some(determinator_set) = cache.read(&key, self.new) {
+            return Ok(determinator_set);
+        }
+
+        let determinator_set = self.compute();
+        cache.write(&key, &determinator_set, self.new)?;
+        Ok(determinator_set)
+    }
+
+    /// Explains why a workspace package was or wasn't selected by [`compute`](Self::compute).
+    ///
+    /// The explanation includes the package's reason and chain, the changed paths inside its
+    /// directory or matched to it by rules, the changes to its simulated builds, and the changed
+    /// packages it depends on. This runs a full computation, so it's meant for debugging.
+    ///
+    /// Returns an error if `workspace_name` isn't in the new workspace.
+    pub fn explain(&self, workspace_name: &str) -> Result<PackageExplanation<'g>, guppy::Error> {
+        let package = self.new.workspace().member_by_name(workspace_name)?;
+        let mut determinator_set = self.compute();
+
+        let package_dir = package
+            .source()
+            .workspace_path()
+            .expect("workspace members have workspace paths");
+        let paths = self
+            .changed_paths
+            .iter()
+            .filter_map(|path| {
+                let mut rules = vec![];
+                let mut marks_package = false;
+                let (path_match, all_rule) = process_path(path, self, |id, rule| {
+                    if id == package.id() {
+                        marks_package = true;
+                        rules.extend(rule);
+                    }
+                });
+                if matches!(path_match, PathMatch::RuleMatchedAll | PathMatch::NoMatches) {
+                    marks_package = true;
+                    rules.extend(all_rule);
+                }
+                rules.dedup();
+
+                (marks_package || path.starts_with(package_dir)).then(|| ExplainedPath {
+                    path: path.to_path_buf(),
+                    path_match,
+                    marks_package,
+                    rules,
+                })
+            })
+            .collect();
+
+        let new_result = self.new_build_result(package);
+        let summary_changes = self.summary_changes(workspace_name, &new_result);
+
+        let workspace_set = self.new.resolve_workspace();
+        let changed_set = determinator_set
+            .path_changed_set
+            .union(&determinator_set.summary_changed_set);
+        let upstream_changed = new_result
+            .unified_workspace_set(&workspace_set)
+            .intersection(&changed_set)
+            .package_ids(DependencyDirection::Forward)
+            .filter(|&id| id != package.id())
+            .map(|id| UpstreamChange {
+                package_id: id,
+                dependency_path: dependency_path(package, id),
+            })
+            .collect();
+
+        let chain = determinator_set.chain(package.id());
+        Ok(PackageExplanation {
+            package_id: package.id(),
+            reason: determinator_set.reasons.remove(package.id()),
+            chain,
+            paths,
+            summary_changes,
+            upstream_changed,
+        })
+    }
+
+    /// Returns the key for this computation in a `DeterminatorCache`, or `None` if it can't be
+    /// cached.
+    fn cache_key(&self) -> Option<String> {
+        if !self.custom_rules.is_empty() {
+            return None;
         }
+
+        let mut fingerprint_opts = FingerprintOptions::new();
+        fingerprint_opts.set_include_workspace_location(false);
+        let old_fingerprint = self.old.fingerprint_with_options(&fingerprint_opts);
+        let new_fingerprint = self.new.fingerprint_with_options(&fingerprint_opts);
+
+        let default_options = Determinator::default_cargo_options();
+        let cargo_options = self.cargo_options.unwrap_or(&default_options);
+        let old_default_features_only = self.old.feature_graph().resolve_none();
+        let new_default_features_only = self.new.feature_graph().resolve_none();
+        let old_options = CargoOptionsSummary::new(
+            self.old,
+            self.old_features_only
+                .as_ref()
+                .unwrap_or(&old_default_features_only),
+            cargo_options,
+        )
+        .ok()?;
+        let new_options = CargoOptionsSummary::new(
+            self.new,
+            self.new_features_only
+                .as_ref()
+                .unwrap_or(&new_default_features_only),
+            cargo_options,
+        )
+        .ok()?;
+
+        let mut options_hasher = CacheKeyHasher::new();
+        options_hasher.write_u64(self.rules_hash);
+        options_hasher.write_json(&old_options);
+        options_hasher.write_json(&new_options);
+        options_hasher.write_str(&format!("{:?}", self.extra_features));
+        options_hasher.write_json(&self.build_script_paths);
+
+        let mut changed_paths: Vec<_> = self
+            .changed_paths
+            .iter()
+            .map(|path| path.as_str())
+            .collect();
+        changed_paths.sort_unstable();
+        changed_paths.dedup();
+        let mut hash_changed: Vec<_> = self
+            .hash_changed_ids
+            .iter()
+            .map(|&id| {
+                self.new
+                    .metadata(id)
+                    .expect("package ID obtained from the same graph")
+                    .name()
+            })
+            .collect();
+        hash_changed.sort_unstable();
+        hash_changed.dedup();
+
+        let mut changes_hasher = CacheKeyHasher::new();
+        changes_hasher.write_json(&changed_paths);
+        changes_hasher.write_json(&hash_changed);
+        changes_hasher.write_json(&self.workspace_manifest_changes);
+
+        Some(format!(
+            "{}-{}-{:016x}-{:016x}",
+            old_fingerprint,
+            new_fingerprint,
+            options_hasher.finish(),
+            changes_hasher.finish(),
+        ))
+    }
+
+    /// Simulates builds of a package in the new graph.
+    fn new_build_result(&self, package: PackageMetadata<'g>) -> BuildResult<'g> {
+        let default_options = Determinator::default_cargo_options();
+        let cargo_options = self.cargo_options.unwrap_or(&default_options);
+        let default_features_only = self.new.feature_graph().resolve_none();
+        let features_only = self
+            .new_features_only
+            .as_ref()
+            .unwrap_or(&default_features_only);
+
+        BuildResult::new(package, cargo_options, features_only, &self.extra_features)
+    }
+
+    /// Returns the dependencies whose builds changed, or `None` if the package's build summaries
+    /// are unchanged.
+    ///
+    /// The list is empty if the package is new or was renamed.
+    fn summary_changes(
+        &self,
+        name: &str,
+        new_result: &BuildResult<'g>,
+    ) -> Option<Vec<SummaryChange>> {
+        // Look up the package in the old metadata by path. (Workspace packages are uniquely
+        // identified by both name and path -- this could be done by name as well).
+        let old_workspace = self.old.workspace();
+        let old_package = match old_workspace.member_by_name(name) {
+            Ok(package) => package,
+            Err(_) => {
+                // Member not found: this is new or renamed.
+                return Some(vec![]);
+            }
+        };
+
+        let default_options = Determinator::default_cargo_options();
+        let cargo_options = self.cargo_options.unwrap_or(&default_options);
+
+        let default_features_only = self.old.feature_graph().resolve_none();
+        let features_only = self
+            .old_features_only
+            .as_ref()
+            .unwrap_or(&default_features_only);
+
+        let old_result = BuildResult::new(
+            old_package,
+            cargo_options,
+            features_only,
+            &self.extra_features,
+        );
+        let changes = new_result.changes(&old_result, cargo_options);
+        (!changes.is_empty()).then_some(changes)
     }
 }
 
@@ -267,7 +645,8 @@ pub struct DeterminatorSet<'g> {
 
     /// The packages that were marked changed because a file changed.
     ///
-    /// Either a file inside this package changed or a path rule was matched.
+    /// Either a file inside this package changed, a path rule was matched, or the package's
+    /// content hash changed.
     pub path_changed_set: PackageSet<'g>,
 
     /// The packages that were marked changed becuase a simulated Cargo build's summary showed
@@ -276,6 +655,13 @@ pub struct DeterminatorSet<'g> {
     /// This does not include packages marked changed through a path. For example, if a path rule
     /// caused all packages to be marked changed, further steps aren't run and this set is empty.
     pub summary_changed_set: PackageSet<'g>,
+
+    /// The reason each package in the affected set was selected.
+    ///
+    /// Packages marked changed because a path changed have a
+    /// [`PathChanged`](DeterminatorReason::PathChanged) reason, even if their build summaries
+    /// also changed.
+    pub reasons: BTreeMap<&'g PackageId, DeterminatorReason<'g>>,
 }
 
 // ---
@@ -286,7 +672,9 @@ pub struct DeterminatorSet<'g> {
 struct BuildState<'g, 'a, 'b> {
     determinator: &'b Determinator<'g, 'a>,
     path_changed_ids: HashSet<&'g PackageId>,
-    summary_changed_ids: HashSet<&'g PackageId>,
+    path_reasons: AHashMap<&'g PackageId, Vec<ChangedPath>>,
+    hash_changed_ids: HashSet<&'g PackageId>,
+    summary_changes: AHashMap<&'g PackageId, Vec<SummaryChange>>,
     build_cache: CargoBuildCache<'g>,
     reverse_index: ReverseIndex<'g>,
 }
@@ -298,98 +686,155 @@ impl<'g, 'a, 'b> BuildState<'g, 'a, 'b> {
         Self {
             determinator,
             path_changed_ids: HashSet::new(),
-            summary_changed_ids: HashSet::new(),
+            path_reasons: AHashMap::new(),
+            hash_changed_ids: HashSet::new(),
+            summary_changes: AHashMap::new(),
             build_cache,
             reverse_index,
         }
     }
 
-    // A return value of None stands for all packages in the workspace changed.
-    fn process_path(mut self, path: &Utf8Path) -> Option<Self> {
-        let status = process_path(
-            path,
-            &self.determinator.new.workspace(),
-            &self.determinator.rules.path_rules,
-            |id| {
+    // A return value of Err stands for all packages in the workspace changed because of this path.
+    fn process_paths(&mut self, paths: &[&Utf8Path]) -> Result<(), ChangedPath> {
+        // Paths are matched in parallel, then the results are merged in order so that the first
+        // path causing everything to be marked changed is the one reported.
+        let determinator = self.determinator;
+        let matches: Vec<_> = paths
+            .par_iter()
+            .map(|&path| {
+                let mut marked = vec![];
+                let (status, all_rule) =
+                    process_path(path, determinator, |id, rule| marked.push((id, rule)));
+                (path, status, all_rule, marked)
+            })
+            .collect();
+
+        for (path, status, all_rule, marked) in matches {
+            match status {
+                PathMatch::RuleMatchedAll | PathMatch::NoMatches => {
+                    return Err(ChangedPath {
+                        path: path.to_owned(),
+                        rule: all_rule,
+                    });
+                }
+                PathMatch::RuleMatched(_) | PathMatch::AncestorMatched => {}
+            }
+            for (id, rule) in marked {
                 self.path_changed_ids.insert(id);
-            },
-        );
-        match status {
-            PathMatch::RuleMatchedAll | PathMatch::NoMatches => None,
-            PathMatch::RuleMatched(_) | PathMatch::AncestorMatched => Some(self),
+                let changed_path = ChangedPath {
+                    path: path.to_owned(),
+                    rule,
+                };
+                let paths = self.path_reasons.entry(id).or_default();
+                if !paths.contains(&changed_path) {
+                    paths.push(changed_path);
+                }
+            }
         }
+        Ok(())
     }
 
     fn process_build_summaries(&mut self) {
         // For each workspace package, if its build summaries have changed mark it changed.
-        let summary_changed_ids: Vec<_> = self
+        let summary_changes: Vec<_> = self
             .determinator
             .new
             .workspace()
             .par_iter_by_name()
             .filter_map(|(name, package)| {
                 // Don't include packages already marked as changed through paths. (This is documented.)
-                if !self.path_changed_ids.contains(package.id())
-                    && self.build_summaries_changed(name, package)
-                {
-                    Some(package.id())
-                } else {
-                    None
+                if self.path_changed_ids.contains(package.id()) {
+                    return None;
                 }
+                let new_result = &self.build_cache.result_cache[package.id()];
+                self.determinator
+                    .summary_changes(name, new_result)
+                    .map(|changes| (package.id(), changes))
             })
             .collect();
-        self.summary_changed_ids.extend(summary_changed_ids);
+        self.summary_changes.extend(summary_changes);
     }
+}
 
-    fn build_summaries_changed(&self, name: &str, package: PackageMetadata<'g>) -> bool {
-        // Look up the package in the old metadata by path. (Workspace packages are uniquely
-        // identified by both name and path -- this could be done by name as well).
-        let old_workspace = self.determinator.old.workspace();
-        let old_package = match old_workspace.member_by_name(name) {
-            Ok(package) => package,
-            Err(_) => {
-                // Member not found: this is new or renamed.
-                return true;
+/// Matches a path against rules and packages, calling `match_cb` with each package the path marks
+/// changed, along with the rule that marked it (or `None` for the nearest ancestor package).
+///
+/// Also returns the rule that caused all packages to be marked changed, if any.
+fn process_path<'g>(
+    path: &Utf8Path,
+    determinator: &Determinator<'g, '_>,
+    mut match_cb: impl FnMut(&'g PackageId, Option<RuleIndex>),
+) -> (PathMatch, Option<RuleIndex>) {
+    let mut skip_rules = false;
+
+    // 0. Mark packages whose build scripts declare this path. Processing always continues after
+    // this.
+    let mut build_script_matched = false;
+    if let Some(build_script_paths) = determinator.build_script_paths {
+        let workspace = determinator.new.workspace();
+        for name in build_script_paths.matches(path) {
+            // Package names that aren't in the new workspace are ignored.
+            if let Ok(package) = workspace.member_by_name(name) {
+                match_cb(package.id(), Some(RuleIndex::BuildScript));
+                build_script_matched = true;
             }
-        };
+        }
+    }
 
-        let default_options = Determinator::default_cargo_options();
-        let cargo_options = self.determinator.cargo_options.unwrap_or(&default_options);
+    // If the root Cargo.toml changed and its old and new contents are known, apply the rules
+    // for each changed section instead of any other rules.
+    if let Some(sections) = &determinator.workspace_manifest_changes {
+        if path == "Cargo.toml" {
+            return process_workspace_manifest(sections, determinator, match_cb);
+        }
+    }
 
-        let default_features_only = self.determinator.old.feature_graph().resolve_none();
-        let features_only = self
-            .determinator
-            .old_features_only
-            .as_ref()
-            .unwrap_or(&default_features_only);
+    // 1a. Apply any custom rules implemented in Rust.
+    for (index, rule) in determinator.custom_rules.iter().enumerate() {
+        let rule_index = RuleIndex::Plugin(index);
+        let post_rule = match rule.match_path(path, determinator.old, determinator.new) {
+            CustomRuleDecision::NoMatch => continue,
+            CustomRuleDecision::Mark {
+                packages,
+                post_rule,
+            } => {
+                for package in packages {
+                    match_cb(package.id(), Some(rule_index));
+                }
+                post_rule
+            }
+            CustomRuleDecision::MarkAll => return (PathMatch::RuleMatchedAll, Some(rule_index)),
+        };
 
-        let old_result = BuildResult::new(old_package, cargo_options, features_only);
-        let new_result = &self.build_cache.result_cache[package.id()];
-        new_result.is_changed(&old_result, cargo_options)
+        match post_rule {
+            DeterminatorPostRule::Skip => return (PathMatch::RuleMatched(rule_index), None),
+            DeterminatorPostRule::SkipRules => {
+                skip_rules = true;
+                break;
+            }
+            DeterminatorPostRule::Fallthrough => continue,
+        }
     }
-}
 
-fn process_path<'g>(
-    path: &Utf8Path,
-    workspace: &Workspace<'g>,
-    path_rules: &[PathRuleImpl<'g>],
-    mut match_cb: impl FnMut(&'g PackageId),
-) -> PathMatch {
+    // 1b. Apply any rules that match the path.
     let candidate = Candidate::new(path);
-
-    // 1. Apply any rules that match the path.
+    let path_rules = if skip_rules {
+        &[][..]
+    } else {
+        &determinator.rules.path_rules[..]
+    };
     for rule in path_rules {
-        if rule.glob_set.is_match_candidate(&candidate) {
+        if rule.is_match(&candidate) {
             // This glob matches this rule, so execute it.
             match &rule.mark_changed {
                 MarkChangedImpl::Packages(packages) => {
                     for package in packages {
-                        match_cb(package.id());
+                        match_cb(package.id(), Some(rule.rule_index));
                     }
                 }
                 MarkChangedImpl::All => {
                     // Mark all packages changed.
-                    return PathMatch::RuleMatchedAll;
+                    return (PathMatch::RuleMatchedAll, Some(rule.rule_index));
                 }
             }
 
@@ -397,7 +842,7 @@ fn process_path<'g>(
                 DeterminatorPostRule::Skip => {
                     // Skip all further processing for this path but continue reading other
                     // paths.
-                    return PathMatch::RuleMatched(rule.rule_index);
+                    return (PathMatch::RuleMatched(rule.rule_index), None);
                 }
                 DeterminatorPostRule::SkipRules => {
                     // Skip further rule processing but continue to step 2 to match to the
@@ -413,15 +858,81 @@ fn process_path<'g>(
     }
 
     // 2. Map the path to its nearest ancestor package.
+    let workspace = determinator.new.workspace();
     for ancestor in path.ancestors() {
         if let Ok(package) = workspace.member_by_path(ancestor) {
-            match_cb(package.id());
-            return PathMatch::AncestorMatched;
+            match_cb(package.id(), None);
+            return (PathMatch::AncestorMatched, None);
+        }
+        // If the nearest package is a path dependency outside the workspace, mark the workspace
+        // packages that depend on it.
+        if let Some(dependents) = determinator.path_dependencies.get(ancestor) {
+            for &id in dependents {
+                match_cb(id, Some(RuleIndex::PathDependency));
+            }
+            return (PathMatch::AncestorMatched, None);
         }
     }
 
     // 3. If a file didn't match anything so far, rebuild everything.
-    PathMatch::NoMatches
+    if build_script_matched {
+        (PathMatch::RuleMatched(RuleIndex::BuildScript), None)
+    } else {
+        (PathMatch::NoMatches, None)
+    }
+}
+
+/// Applies the workspace manifest rules for each changed section of the root `Cargo.toml`.
+fn process_workspace_manifest<'g>(
+    sections: &BTreeSet<ManifestSection>,
+    determinator: &Determinator<'g, '_>,
+    mut match_cb: impl FnMut(&'g PackageId, Option<RuleIndex>),
+) -> (PathMatch, Option<RuleIndex>) {
+    let mut last_index = RuleIndex::WorkspaceManifest(ManifestSection::Formatting);
+    for &section in sections {
+        let rule_index = RuleIndex::WorkspaceManifest(section);
+        last_index = rule_index;
+        match section {
+            ManifestSection::Formatting => {}
+            ManifestSection::Package => {
+                // Mark the package at the root of the workspace, if any.
+                if let Ok(package) = determinator.new.workspace().member_by_path("") {
+                    match_cb(package.id(), Some(rule_index));
+                }
+            }
+            _ => match &determinator.rules.workspace_manifest[&section] {
+                MarkChangedImpl::Packages(packages) => {
+                    for package in packages {
+                        match_cb(package.id(), Some(rule_index));
+                    }
+                }
+                MarkChangedImpl::All => return (PathMatch::RuleMatchedAll, Some(rule_index)),
+            },
+        }
+    }
+    (PathMatch::RuleMatched(last_index), None)
+}
+
+/// Returns a map of non-workspace path dependencies to the workspace packages that depend on them,
+/// directly or transitively.
+fn path_dependencies(graph: &PackageGraph) -> AHashMap<&Utf8Path, Vec<&PackageId>> {
+    let workspace_set = graph.resolve_workspace();
+    graph
+        .packages()
+        .filter_map(|package| match package.source() {
+            PackageSource::Path(path) => {
+                let dependents = graph
+                    .query_reverse([package.id()])
+                    .expect("package ID obtained from the same graph")
+                    .resolve()
+                    .intersection(&workspace_set)
+                    .package_ids(DependencyDirection::Forward)
+                    .collect();
+                Some((path, dependents))
+            }
+            _ => None,
+        })
+        .collect()
 }
 
 /// Stores a build cache of every package in a workspace.
@@ -446,7 +957,12 @@ impl<'g> CargoBuildCache<'g> {
             .par_iter()
             .map(|package| {
                 let id = package.id();
-                let build_result = BuildResult::new(package, cargo_options, features_only);
+                let build_result = BuildResult::new(
+                    package,
+                    cargo_options,
+                    features_only,
+                    &determinator.extra_features,
+                );
                 (id, build_result)
             })
             .collect();
@@ -462,7 +978,8 @@ struct BuildResult<'g> {
     none: CargoSet<'g>,
     default: CargoSet<'g>,
     all: CargoSet<'g>,
-    // TODO: add support for more feature sets?
+    /// Builds with feature sets added through `Determinator::add_feature_set`, in order.
+    extra: Vec<CargoSet<'g>>,
 }
 
 impl<'g> BuildResult<'g> {
@@ -470,6 +987,7 @@ impl<'g> BuildResult<'g> {
         package: PackageMetadata<'g>,
         cargo_options: &CargoOptions<'_>,
         features_only: &FeatureSet<'g>,
+        extra_features: &[(StandardFeatures, Vec<&str>)],
     ) -> Self {
         let (none, (default, all)) = rayon::join(
             || {
@@ -501,8 +1019,24 @@ impl<'g> BuildResult<'g> {
                 )
             },
         );
+        let extra = extra_features
+            .iter()
+            .map(|(base, features)| {
+                make_cargo_set(
+                    &package,
+                    named_feature_filter(*base, features.iter().copied()),
+                    cargo_options,
+                    features_only,
+                )
+            })
+            .collect();
 
-        Self { none, default, all }
+        Self {
+            none,
+            default,
+            all,
+            extra,
+        }
     }
 
     /// Returns the unified set of workspace dependencies.
@@ -521,26 +1055,42 @@ impl<'g> BuildResult<'g> {
         target_set.union(&host_set).intersection(workspace_set)
     }
 
-    fn is_changed(&self, other: &BuildResult<'_>, cargo_options: &CargoOptions<'_>) -> bool {
-        for (a, b) in self.all_cargo_sets().zip(other.all_cargo_sets()) {
-            let a_summary = a
+    /// Returns the dependencies that changed between `old` and this build result, sorted and
+    /// deduplicated across feature sets and build platforms.
+    fn changes(
+        &self,
+        old: &BuildResult<'_>,
+        cargo_options: &CargoOptions<'_>,
+    ) -> Vec<SummaryChange> {
+        let mut changes = BTreeSet::new();
+        for (new, old) in self.all_cargo_sets().zip(old.all_cargo_sets()) {
+            let new_summary = new
                 .to_summary(cargo_options)
                 .expect("custom platforms currently unsupported");
-            let b_summary = b
+            let old_summary = old
                 .to_summary(cargo_options)
                 .expect("custom platforms currently unsupported");
-            let diff = a_summary.diff(&b_summary);
-            if diff.is_changed() {
-                return true;
+            let diff = old_summary.diff(&new_summary);
+            for (id, status) in diff
+                .target_packages
+                .changed
+                .iter()
+                .chain(&diff.host_packages.changed)
+            {
+                changes.insert(SummaryChange {
+                    id: (*id).clone(),
+                    tag: status.tag(),
+                });
             }
         }
-        false
+        changes.into_iter().collect()
     }
 
     fn all_cargo_sets<'a>(&'a self) -> impl Iterator<Item = &'a CargoSet<'g>> + 'a {
         std::iter::once(&self.none)
             .chain(std::iter::once(&self.default))
             .chain(std::iter::once(&self.all))
+            .chain(&self.extra)
     }
 }
 
@@ -567,7 +1117,7 @@ struct ReverseIndex<'g> {
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 enum ReverseIndexEdge {
     /// This edge was added as a package rule. This always takes precedence over `CargoBuild`.
-    PackageRule,
+    PackageRule(RuleIndex),
     /// This edge was added through the Cargo build cache.
     CargoBuild,
 }
@@ -578,19 +1128,31 @@ impl<'g> ReverseIndex<'g> {
 
         let workspace_set = determinator.new.resolve_workspace();
 
-        // First, look at the result cache and add edges based on that.
-        for (id, build_result) in &build_cache.result_cache {
-            reverse_index.extend(
-                build_result
+        // First, look at the result cache and add edges based on that. Computing the workspace
+        // packages in each build is the expensive part, so do that in parallel.
+        let build_deps: Vec<(&'g PackageId, Vec<&'g PackageId>)> = build_cache
+            .result_cache
+            .par_iter()
+            .map(|(id, build_result)| {
+                let dep_ids = build_result
                     .unified_workspace_set(&workspace_set)
                     .package_ids(DependencyDirection::Forward)
-                    .map(|dep_id| (Some(dep_id), Some(*id), ReverseIndexEdge::CargoBuild)),
+                    .collect();
+                (*id, dep_ids)
+            })
+            .collect();
+        for (id, dep_ids) in build_deps {
+            reverse_index.extend(
+                dep_ids
+                    .into_iter()
+                    .map(|dep_id| (Some(dep_id), Some(id), ReverseIndexEdge::CargoBuild)),
             );
         }
 
         // Now, look at all the package rules and add anything in them to the reverse index.
         // IMPORTANT: This comes later so that PackageRule edges overwrite CargoBuild edges.
-        for package_rule in &determinator.rules.package_rules {
+        for (rule_index, package_rule) in determinator.rules.package_rules.iter().enumerate() {
+            let edge = ReverseIndexEdge::PackageRule(RuleIndex::Package(rule_index));
             for on_affected in package_rule
                 .on_affected
                 .package_ids(DependencyDirection::Forward)
@@ -598,21 +1160,15 @@ impl<'g> ReverseIndex<'g> {
                 match &package_rule.mark_changed {
                     MarkChangedImpl::Packages(packages) => {
                         // Add edges from on_affected to mark_changed.
-                        reverse_index.extend(packages.iter().map(|package| {
-                            (
-                                Some(on_affected),
-                                Some(package.id()),
-                                ReverseIndexEdge::PackageRule,
-                            )
-                        }));
+                        reverse_index.extend(
+                            packages
+                                .iter()
+                                .map(|package| (Some(on_affected), Some(package.id()), edge)),
+                        );
                     }
                     MarkChangedImpl::All => {
                         // Add an edge to the None/"all" sentinel value.
-                        reverse_index.add_edge(
-                            Some(on_affected),
-                            None,
-                            ReverseIndexEdge::PackageRule,
-                        );
+                        reverse_index.add_edge(Some(on_affected), None, edge);
                     }
                 }
             }
@@ -626,10 +1182,17 @@ impl<'g> ReverseIndex<'g> {
         package_graph: &'g PackageGraph,
         path_changed: &HashSet<&'g PackageId>,
         summary_changed: &HashSet<&'g PackageId>,
-    ) -> PackageSet<'g> {
-        // This is a *really* interesting DFS, in that there's one restriction: you can't follow
-        // two CargoBuild edges consecutively. Also, in the initial set, path_changed allows
-        // CargoBuild to be followed once while summary_changed doesn't allow it to be followed.
+    ) -> (
+        PackageSet<'g>,
+        AHashMap<&'g PackageId, (&'g PackageId, AffectedVia)>,
+    ) {
+        // This is a *really* interesting graph traversal, in that there's one restriction: you
+        // can't follow two CargoBuild edges consecutively. Also, in the initial set, path_changed
+        // allows CargoBuild to be followed once while summary_changed doesn't allow it to be
+        // followed.
+        //
+        // The traversal is breadth-first, one level at a time. The neighbors of every node in a
+        // level are found in parallel, then merged in order.
 
         #[derive(Copy, Clone, Debug, Eq, PartialEq)]
         enum FollowCargoBuild {
@@ -639,85 +1202,102 @@ impl<'g> ReverseIndex<'g> {
 
         use FollowCargoBuild::*;
 
-        // The order of what goes in the stack doesn't matter for correctness, but putting Allowed
-        // at the end (and therefore popping it first) lowers the chance of an upgrade re-traversal.
-        let mut stack: Vec<_> = summary_changed
+        // Nodes are discovered at most twice: once with NotAllowed, and once more if they're
+        // upgraded to Allowed. (Cycles can happen with dev deps.)
+        let mut discovered = AHashMap::new();
+        for &id in summary_changed {
+            discovered.insert(id, NotAllowed);
+        }
+        for &id in path_changed {
+            discovered.insert(id, Allowed);
+        }
+        let mut level: Vec<_> = discovered
             .iter()
-            .map(|id| (*id, NotAllowed))
-            .chain(path_changed.iter().map(|id| (*id, Allowed)))
+            .map(|(&id, &follow)| (id, follow))
             .collect();
+        // Sort the initial level so that the traversal, and therefore affected_from, is
+        // deterministic.
+        level.sort_unstable_by_key(|&(id, _)| id);
+
+        // The package and edge that first caused each package outside the initial set to be
+        // discovered. Parents are always discovered before their children, so following these
+        // back always leads to the initial set.
+        let mut affected_from = AHashMap::new();
+        let mut record_parent = |neighbor: &'g PackageId, id: &'g PackageId, edge| {
+            if !path_changed.contains(neighbor) && !summary_changed.contains(neighbor) {
+                affected_from
+                    .entry(neighbor)
+                    .or_insert((id, edge_to_via(edge)));
+            }
+        };
 
-        // Do a DFS with two maps, in case there are cycles (can happen with dev deps).
-        let mut discovered = AHashMap::new();
-        let mut finished = HashSet::new();
-
-        while let Some(&(id, follow)) = stack.last() {
-            let push_neighbors = match discovered.entry(id) {
-                Entry::Vacant(entry) => {
-                    // First time visiting this node. Push neighbors, don't pop the stack.
-                    entry.insert(follow);
-                    true
-                }
-                Entry::Occupied(mut entry) => {
-                    // This node was seen before.
-                    match (entry.get(), follow) {
-                        (NotAllowed, Allowed) => {
-                            // Upgrade this node to Allowed and push neighbors.
-                            entry.insert(follow);
-                            true
-                        }
-                        _ => {
-                            // Already been fully discovered or just NotAllowed -> NotAllowed, no
-                            // point revisiting it.
-                            false
-                        }
-                    }
-                }
-            };
-
-            if push_neighbors {
-                for (_, neighbor, &edge) in self.reverse_index.edges(Some(id)) {
-                    if edge == ReverseIndexEdge::CargoBuild && follow == NotAllowed {
-                        // Can't follow two consecutive CargoBuild edges.
-                        continue;
-                    }
-                    match neighbor {
-                        Some(neighbor) => {
-                            let neighbor_follow = match edge {
-                                ReverseIndexEdge::CargoBuild => NotAllowed,
-                                ReverseIndexEdge::PackageRule => Allowed,
-                            };
-
-                            match (discovered.get(&neighbor), neighbor_follow) {
-                                (None, _) => {
-                                    // Node has not been discovered yet. Add it to the stack to
-                                    // be visited.
-                                    stack.push((neighbor, neighbor_follow))
-                                }
-                                (Some(NotAllowed), Allowed) => {
-                                    // Node was previously discovered with NotAllowed but is
-                                    // now discovered with Allowed. This is an upgrade. Add it to
-                                    // the stack to be visited.
-                                    stack.push((neighbor, neighbor_follow))
+        while !level.is_empty() {
+            let neighbors: Vec<Vec<_>> = level
+                .par_iter()
+                .map(|&(id, follow)| {
+                    self.reverse_index
+                        .edges(Some(id))
+                        .filter(|&(_, _, &edge)| {
+                            // Can't follow two consecutive CargoBuild edges.
+                            edge != ReverseIndexEdge::CargoBuild || follow == Allowed
+                        })
+                        .map(|(_, neighbor, &edge)| (id, neighbor, edge))
+                        .collect()
+                })
+                .collect();
+
+            let mut next_level = vec![];
+            for (id, neighbor, edge) in neighbors.into_iter().flatten() {
+                match neighbor {
+                    Some(neighbor) => {
+                        let neighbor_follow = match edge {
+                            ReverseIndexEdge::CargoBuild => NotAllowed,
+                            ReverseIndexEdge::PackageRule(_) => Allowed,
+                        };
+                        match discovered.entry(neighbor) {
+                            Entry::Vacant(entry) => {
+                                // Node has not been discovered yet. Visit it in the next level.
+                                entry.insert(neighbor_follow);
+                                record_parent(neighbor, id, edge);
+                                next_level.push((neighbor, neighbor_follow));
+                            }
+                            Entry::Occupied(mut entry) => {
+                                if (*entry.get(), neighbor_follow) == (NotAllowed, Allowed) {
+                                    // Node was previously discovered with NotAllowed but is now
+                                    // discovered with Allowed. This is an upgrade, so visit it
+                                    // again.
+                                    entry.insert(neighbor_follow);
+                                    next_level.push((neighbor, neighbor_follow));
                                 }
-                                _ => {}
                             }
                         }
-                        None => {
-                            // Build everything, can just exit here.
-                            return package_graph.resolve_workspace();
+                    }
+                    None => {
+                        // Build everything, can just exit here.
+                        let workspace_set = package_graph.resolve_workspace();
+                        for neighbor in workspace_set.package_ids(DependencyDirection::Forward) {
+                            if neighbor != id {
+                                record_parent(neighbor, id, edge);
+                            }
                         }
+                        return (workspace_set, affected_from);
                     }
                 }
-            } else {
-                stack.pop();
-                finished.insert(id);
             }
+            level = next_level;
         }
 
-        // At the end of this process, finished contains all nodes discovered.
-        package_graph
-            .resolve_ids(finished.iter().copied())
-            .expect("all IDs are valid")
+        // At the end of this process, discovered contains all nodes reached.
+        let affected_set = package_graph
+            .resolve_ids(discovered.keys().copied())
+            .expect("all IDs are valid");
+        (affected_set, affected_from)
+    }
+}
+
+fn edge_to_via(edge: ReverseIndexEdge) -> AffectedVia {
+    match edge {
+        ReverseIndexEdge::CargoBuild => AffectedVia::CargoBuild,
+        ReverseIndexEdge::PackageRule(rule_index) => AffectedVia::PackageRule(rule_index),
     }
 }
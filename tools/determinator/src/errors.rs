@@ -10,7 +10,8 @@ use std::{error, fmt};
 #[derive(Debug)]
 pub struct RulesError {
     rule_index: RuleIndex,
-    kind: RulesErrorKind,
+    package_name: Option<String>,
+    kind: Box<RulesErrorKind>,
 }
 
 impl RulesError {
@@ -19,6 +20,12 @@ impl RulesError {
         self.rule_index
     }
 
+    /// Returns the name of the workspace package that declared the rule, for
+    /// [package metadata rules](crate::rules::PackageMetadataRule).
+    pub fn package_name(&self) -> Option<&str> {
+        self.package_name.as_deref()
+    }
+
     /// Returns the kind of error that occurred.
     pub fn kind(&self) -> &RulesErrorKind {
         &self.kind
@@ -31,7 +38,24 @@ impl RulesError {
     pub(crate) fn resolve_ref(rule_index: RuleIndex, err: guppy::Error) -> Self {
         Self {
             rule_index,
-            kind: RulesErrorKind::ResolveRef(err),
+            package_name: None,
+            kind: Box::new(RulesErrorKind::ResolveRef(err)),
+        }
+    }
+
+    pub(crate) fn only_excludes(rule_index: RuleIndex) -> Self {
+        Self {
+            rule_index,
+            package_name: None,
+            kind: Box::new(RulesErrorKind::OnlyExcludes),
+        }
+    }
+
+    pub(crate) fn package_metadata(rule_index: RuleIndex, err: serde_json::Error) -> Self {
+        Self {
+            rule_index,
+            package_name: None,
+            kind: Box::new(RulesErrorKind::PackageMetadataParse(Box::new(err))),
         }
     }
 
@@ -40,7 +64,16 @@ impl RulesError {
             glob: err.glob().map(|s| s.to_owned()),
             err: Box::new(err),
         };
-        Self { rule_index, kind }
+        Self {
+            rule_index,
+            package_name: None,
+            kind: Box::new(kind),
+        }
+    }
+
+    pub(crate) fn with_package_name(mut self, package_name: &str) -> Self {
+        self.package_name = Some(package_name.to_owned());
+        self
     }
 }
 
@@ -48,17 +81,23 @@ impl fmt::Display for RulesError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "error while resolving determinator rules: {}: {}",
-            self.rule_index, self.kind
-        )
+            "error while resolving determinator rules: {}",
+            self.rule_index
+        )?;
+        if let Some(package_name) = &self.package_name {
+            write!(f, " (declared by package {})", package_name)?;
+        }
+        write!(f, ": {}", self.kind)
     }
 }
 
 impl error::Error for RulesError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
-        match &self.kind {
+        match &*self.kind {
             RulesErrorKind::ResolveRef(err) => Some(err),
             RulesErrorKind::GlobParse { err, .. } => Some(&**err),
+            RulesErrorKind::PackageMetadataParse(err) => Some(&**err),
+            RulesErrorKind::OnlyExcludes => None,
         }
     }
 }
@@ -77,6 +116,12 @@ pub enum RulesErrorKind {
         /// The error that occurred while parsing the glob.
         err: Box<dyn error::Error + Send + Sync>,
     },
+
+    /// A path rule only had globs starting with `!`, so it couldn't match any paths.
+    OnlyExcludes,
+
+    /// A workspace package's `[package.metadata.determinator]` section couldn't be parsed.
+    PackageMetadataParse(Box<dyn error::Error + Send + Sync>),
 }
 
 impl fmt::Display for RulesErrorKind {
@@ -90,6 +135,14 @@ impl fmt::Display for RulesErrorKind {
             RulesErrorKind::GlobParse { glob: None, err } => {
                 write!(f, "while parsing a glob: {}", err)
             }
+            RulesErrorKind::OnlyExcludes => write!(
+                f,
+                "globs starting with ! exclude paths matched by other globs, but the rule has no \
+                 other globs"
+            ),
+            RulesErrorKind::PackageMetadataParse(err) => {
+                write!(f, "while parsing [package.metadata.determinator]: {}", err)
+            }
         }
     }
 }
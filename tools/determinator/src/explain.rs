@@ -0,0 +1,121 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    reasons::{DeterminatorReason, SummaryChange},
+    rules::{PathMatch, RuleIndex},
+};
+use ahash::AHashMap;
+use camino::Utf8PathBuf;
+use guppy::{graph::PackageMetadata, PackageId};
+use std::collections::VecDeque;
+
+/// An explanation of why a package was or wasn't selected by the determinator.
+///
+/// Returned by [`Determinator::explain`](crate::Determinator::explain).
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct PackageExplanation<'g> {
+    /// The package ID.
+    pub package_id: &'g PackageId,
+
+    /// The reason the package is in the affected set, or `None` if it isn't affected.
+    pub reason: Option<DeterminatorReason<'g>>,
+
+    /// The chain of packages that caused this package to be affected, as returned by
+    /// [`DeterminatorSet::chain`](crate::DeterminatorSet::chain).
+    pub chain: Vec<&'g PackageId>,
+
+    /// Changed paths that are inside this package's directory, or that marked this package
+    /// changed, in the order they were added.
+    pub paths: Vec<ExplainedPath>,
+
+    /// The dependencies whose builds changed between the old and new graphs, or `None` if the
+    /// package's simulated builds are unchanged.
+    ///
+    /// The list is empty if the package is new or was renamed.
+    ///
+    /// Unlike [`DeterminatorSet::summary_changed_set`](crate::DeterminatorSet::summary_changed_set),
+    /// this is computed even if the package was marked changed through a path.
+    pub summary_changes: Option<Vec<SummaryChange>>,
+
+    /// Workspace packages in this package's simulated builds that were marked changed, through
+    /// either paths or build summaries.
+    pub upstream_changed: Vec<UpstreamChange<'g>>,
+}
+
+impl<'g> PackageExplanation<'g> {
+    /// Returns true if the package is in the affected set.
+    pub fn is_affected(&self) -> bool {
+        self.reason.is_some()
+    }
+}
+
+/// A changed path considered while explaining a package.
+///
+/// Part of [`PackageExplanation`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct ExplainedPath {
+    /// The path, as passed in to
+    /// [`Determinator::add_changed_paths`](crate::Determinator::add_changed_paths).
+    pub path: Utf8PathBuf,
+
+    /// How the path was matched, as returned by
+    /// [`Determinator::match_path`](crate::Determinator::match_path).
+    pub path_match: PathMatch,
+
+    /// Whether this path marks the package changed.
+    ///
+    /// This is true for paths that cause every package to be marked changed.
+    pub marks_package: bool,
+
+    /// The rules that marked the package changed.
+    ///
+    /// This is empty if the path marked the package changed through its nearest ancestor, or if
+    /// it doesn't mark the package changed.
+    pub rules: Vec<RuleIndex>,
+}
+
+/// A changed workspace package that another package depends on.
+///
+/// Part of [`PackageExplanation`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct UpstreamChange<'g> {
+    /// The package ID of the changed package.
+    pub package_id: &'g PackageId,
+
+    /// A shortest dependency path from the explained package to the changed package, inclusive.
+    pub dependency_path: Vec<&'g PackageId>,
+}
+
+/// Returns a shortest path through dependency edges from `from` to `to`, inclusive, or an empty
+/// list if `to` isn't a dependency of `from`.
+pub(crate) fn dependency_path<'g>(from: PackageMetadata<'g>, to: &PackageId) -> Vec<&'g PackageId> {
+    let mut parents: AHashMap<&'g PackageId, &'g PackageId> = AHashMap::new();
+    let mut queue = VecDeque::from([from]);
+
+    while let Some(package) = queue.pop_front() {
+        if package.id() == to {
+            let mut path = vec![package.id()];
+            let mut current = package.id();
+            while let Some(&parent) = parents.get(current) {
+                path.push(parent);
+                current = parent;
+            }
+            path.reverse();
+            return path;
+        }
+
+        for link in package.direct_links() {
+            let dep = link.to();
+            if dep.id() != from.id() && !parents.contains_key(dep.id()) {
+                parents.insert(dep.id(), package.id());
+                queue.push_back(dep);
+            }
+        }
+    }
+
+    vec![]
+}
@@ -0,0 +1,137 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A manifest of content hashes for files and packages in a workspace.
+///
+/// Comparing the manifests for two versions of a workspace lets the determinator figure out what
+/// changed without a list of changed paths, for example when working from build artifacts rather
+/// than a source control checkout. Pass in the old and new manifests to
+/// [`Determinator::add_changed_hashes`](crate::Determinator::add_changed_hashes).
+///
+/// Hashes are opaque strings: any hash algorithm may be used, as long as the old and new manifests
+/// use the same one.
+///
+/// # Examples
+///
+/// In TOML format:
+///
+/// ```toml
+/// [files]
+/// "Cargo.toml" = "9f86d081884c7d65"
+/// "guppy/src/lib.rs" = "60303ae22b998861"
+///
+/// [packages]
+/// determinator = "fd61a03af4f77d87"
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ContentHashes {
+    /// Map of file paths, relative to the root of the workspace, to the hashes of their contents.
+    ///
+    /// A file that is added, removed, or has a different hash in the new manifest is treated as
+    /// a changed path, and goes through the same rules as paths passed in to
+    /// [`Determinator::add_changed_paths`](crate::Determinator::add_changed_paths).
+    #[serde(default)]
+    pub files: BTreeMap<Utf8PathBuf, String>,
+
+    /// Map of workspace package names to hashes of their contents.
+    ///
+    /// A package that is in the new workspace and is added or has a different hash in the new
+    /// manifest is marked changed. Path rules aren't applied to packages.
+    #[serde(default)]
+    pub packages: BTreeMap<String, String>,
+}
+
+impl ContentHashes {
+    /// Deserializes a content hash manifest from the given TOML string.
+    pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    /// Returns the files that were added, removed or changed in `new` compared to `self`.
+    pub fn changed_files<'a>(&'a self, new: &'a ContentHashes) -> Vec<&'a Utf8Path> {
+        let mut changed: Vec<_> = new
+            .files
+            .iter()
+            .filter(|&(path, hash)| self.files.get(path) != Some(hash))
+            .map(|(path, _)| path.as_path())
+            .chain(
+                self.files
+                    .keys()
+                    .filter(|path| !new.files.contains_key(*path))
+                    .map(|path| path.as_path()),
+            )
+            .collect();
+        changed.sort_unstable();
+        changed
+    }
+
+    /// Returns the names of the packages that were added or changed in `new` compared to `self`.
+    ///
+    /// Removed packages aren't returned, since they aren't in the new workspace.
+    pub fn changed_packages<'a>(&'a self, new: &'a ContentHashes) -> Vec<&'a str> {
+        new.packages
+            .iter()
+            .filter(|&(name, hash)| self.packages.get(name) != Some(hash))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes() {
+        let old = ContentHashes::parse(
+            r#"[files]
+            "a/src/lib.rs" = "1"
+            "b/src/lib.rs" = "2"
+            "removed.txt" = "3"
+
+            [packages]
+            a = "4"
+            b = "5"
+            removed = "6"
+            "#,
+        )
+        .expect("old manifest parsed");
+        let new = ContentHashes::parse(
+            r#"[files]
+            "a/src/lib.rs" = "1"
+            "b/src/lib.rs" = "changed"
+            "added.txt" = "7"
+
+            [packages]
+            a = "4"
+            b = "changed"
+            added = "8"
+            "#,
+        )
+        .expect("new manifest parsed");
+
+        assert_eq!(
+            old.changed_files(&new),
+            vec![
+                Utf8Path::new("added.txt"),
+                Utf8Path::new("b/src/lib.rs"),
+                Utf8Path::new("removed.txt"),
+            ],
+        );
+        assert_eq!(old.changed_packages(&new), vec!["added", "b"]);
+        assert!(
+            new.changed_files(&new).is_empty(),
+            "no changes against self"
+        );
+
+        assert!(
+            ContentHashes::parse("[foo]\nbar = \"baz\"").is_err(),
+            "unknown sections are rejected"
+        );
+    }
+}
@@ -43,6 +43,30 @@
 //! }
 //! ```
 //!
+//! To find out why each package was selected, for example to explain selections in CI logs, use
+//! [`DeterminatorSet::reasons`]: it records the changed paths and the rules they matched, the
+//! dependencies whose builds changed, or the chain of packages back to a changed one.
+//! [`DeterminatorSet::to_summary`] returns a version of these reasons that can be serialized to JSON.
+//!
+//! To debug why a particular package was or wasn't selected, use [`Determinator::explain`].
+//! It lists the changed paths and rules involving the package, the changes to its simulated
+//! builds, and the changed packages it depends on.
+//!
+//! If a list of changed paths isn't available, for example when working from build artifacts, the
+//! determinator can also compare manifests of file and package content hashes: see
+//! [`ContentHashes`] and [`Determinator::add_changed_hashes`].
+//!
+//! To reuse results when the same pair of revisions is evaluated repeatedly, for example across
+//! CI pipelines, results can be cached on disk: see [`DeterminatorCache`] and
+//! [`Determinator::compute_cached`].
+//!
+//! To split the affected set across parallel CI jobs, use [`DeterminatorSet::shards`].
+//! It balances packages across shards using per-package weights, such as historical build and
+//! test durations: see [`ShardWeights`]. The shards can be serialized to JSON.
+//!
+//! For a command-line interface to the determinator, see
+//! [`cargo determinator`](https://docs.rs/cargo-determinator).
+//!
 //! # Platform support
 //!
 //! * **Unix platforms**: The determinator works and is supported.
@@ -72,6 +96,13 @@
 //! gnarly edge cases. For more information, see the documentation for
 //! [`Utf8Paths0`].
 //!
+//! By default, a change to the root `Cargo.toml` of the workspace causes everything to be rebuilt,
+//! since it may contain build flags or profile overrides. If its old and new contents are passed in through
+//! [`Determinator::set_workspace_manifests`], changes are classified instead: for example, editing
+//! the member list or `[workspace.dependencies]` relies on build simulations, while editing
+//! profiles still rebuilds everything. To configure this, see
+//! [`WorkspaceManifestRules`](rules::WorkspaceManifestRules).
+//!
 //! These simple rules may need to be customized for particular scenarios (e.g. to ignore certain
 //! files, or mark a package changed if a file outside of it changes). For those situations, the
 //! determinator lets you specify *custom rules*. See the
@@ -95,9 +126,16 @@
 //!   * no features enabled
 //!   * default features
 //!   * all features enabled
+//!   * additional feature sets configured through [`Determinator::add_feature_set`], such as
+//!     those used by particular CI jobs
 //!
 //! If any of these simulated builds indicates that a workspace package has had any dependency
-//! changes, then it is marked changed.
+//! changes, including changes to the features enabled on a dependency, then it is marked changed.
+//!
+//! This means that changes to `Cargo.lock` don't need to be treated as a global trigger. The
+//! default rules ignore `Cargo.lock` itself, and a lockfile-only change such as a version bump to a
+//! dev-dependency marks only the workspace packages whose simulated builds include that
+//! dependency.
 //!
 //! ## Environment changes
 //!
@@ -152,8 +190,12 @@
 //!
 //! **The determinator cannot run [build scripts](https://doc.rust-lang.org/cargo/reference/build-scripts.html).**
 //! The standard Cargo method for declaring a dependency on a file or environment variable is to
-//! output `rerun-if-changed` or `rerun-if-env-changed` instructions in build scripts. These
-//! instructions must be duplicated through custom rules.
+//! output `rerun-if-changed` or `rerun-if-env-changed` instructions in build scripts.
+//!
+//! As an opt-in, `rerun-if-changed` instructions can be read from build script sources or from
+//! the output of a previous build: see [`BuildScriptPaths`]. Other instructions, as well as
+//! `rerun-if-changed` instructions with paths computed at runtime, must be duplicated through
+//! custom rules.
 //!
 //! **The determinator doesn't track the [`include` and `exclude` fields in
 //! `Cargo.toml`](https://doc.rust-lang.org/cargo/reference/manifest.html#the-exclude-and-include-fields).**
@@ -200,9 +242,12 @@
 //! without a version bump. `cargo build` can recognize those changes because it compares mtimes of
 //! files on disk, but the determinator cannot do that.
 //!
-//! This is not expected to be a problem for most projects that use workspaces. If
-//! there's future demand, it would be possible to add support for changes to non-workspace path
-//! dependencies if they're in the same repository.
+//! To handle this, pass in changed paths inside non-workspace path dependencies relative to the
+//! workspace root, for example `../common-protos/src/lib.rs`. Such a path marks every workspace
+//! package that depends on the path dependency as changed.
+//!
+//! [`cargo determinator`](https://docs.rs/cargo-determinator) does this automatically for path dependencies in
+//! the same Git repository as the workspace.
 //!
 //! # Alternatives and tradeoffs
 //!
@@ -244,9 +289,19 @@
 //! This determinator is inspired by, and shares its name with, the target determinator used in
 //! Facebook's main source repository.
 
+mod build_scripts;
+mod cache;
 mod determinator;
 pub mod errors;
+mod explain;
+mod hashes;
+mod manifest;
 mod paths0;
+mod reasons;
 pub mod rules;
+mod shards;
 
-pub use crate::{determinator::*, paths0::*};
+pub use crate::{
+    build_scripts::*, cache::*, determinator::*, explain::*, hashes::*, paths0::*, reasons::*,
+    shards::*,
+};
@@ -0,0 +1,177 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Classification of changes to the root `Cargo.toml` of a workspace.
+
+use crate::rules::ManifestSection;
+use std::collections::{BTreeMap, BTreeSet};
+use toml::Value;
+
+/// Returns the sections that differ between the old and new contents of the root manifest.
+///
+/// If either manifest is invalid, this returns `ManifestSection::Other`. If the manifests only
+/// differ in comments or formatting, this returns `ManifestSection::Formatting`.
+pub(crate) fn changed_sections(old: &str, new: &str) -> BTreeSet<ManifestSection> {
+    let (Ok(old), Ok(new)) = (old.parse::<Value>(), new.parse::<Value>()) else {
+        return [ManifestSection::Other].into_iter().collect();
+    };
+    let old = sections(&old);
+    let new = sections(&new);
+
+    let mut changed: BTreeSet<_> = old
+        .keys()
+        .chain(new.keys())
+        .filter(|section| old.get(section) != new.get(section))
+        .copied()
+        .collect();
+    if changed.is_empty() {
+        changed.insert(ManifestSection::Formatting);
+    }
+    changed
+}
+
+/// Splits a manifest into sections, keyed by the dotted path of each top-level key in them.
+fn sections(manifest: &Value) -> BTreeMap<ManifestSection, BTreeMap<String, &Value>> {
+    let mut sections: BTreeMap<_, BTreeMap<_, _>> = BTreeMap::new();
+    let mut insert = |section, key: String, value| {
+        sections.entry(section).or_default().insert(key, value);
+    };
+
+    let table = match manifest.as_table() {
+        Some(table) => table,
+        None => {
+            insert(ManifestSection::Other, String::new(), manifest);
+            return sections;
+        }
+    };
+
+    for (key, value) in table {
+        match (key.as_str(), value.as_table()) {
+            ("workspace", Some(workspace)) => {
+                for (sub_key, value) in workspace {
+                    let section = match sub_key.as_str() {
+                        "members" | "exclude" | "default-members" => ManifestSection::Members,
+                        "dependencies" => ManifestSection::Dependencies,
+                        "metadata" => ManifestSection::Metadata,
+                        _ => ManifestSection::Other,
+                    };
+                    insert(section, format!("workspace.{}", sub_key), value);
+                }
+            }
+            ("package" | "project", Some(package)) => {
+                for (sub_key, value) in package {
+                    let section = match sub_key.as_str() {
+                        "metadata" => ManifestSection::Metadata,
+                        _ => ManifestSection::Package,
+                    };
+                    insert(section, format!("{}.{}", key, sub_key), value);
+                }
+            }
+            ("profile", _) => insert(ManifestSection::Profiles, key.clone(), value),
+            (
+                "dependencies" | "dev-dependencies" | "dev_dependencies" | "build-dependencies"
+                | "build_dependencies" | "target" | "features" | "lib" | "bin" | "test" | "bench"
+                | "example" | "badges",
+                _,
+            ) => insert(ManifestSection::Package, key.clone(), value),
+            _ => insert(ManifestSection::Other, key.clone(), value),
+        }
+    }
+
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify() {
+        let base = r#"
+            [workspace]
+            members = ["a", "b"]
+            resolver = "2"
+
+            [workspace.dependencies]
+            serde = "1"
+
+            [workspace.metadata.release]
+            shared-version = true
+
+            [profile.release]
+            debug = true
+        "#;
+
+        let cases: &[(&str, &[ManifestSection])] = &[
+            (
+                r#"
+                # A comment.
+                [workspace]
+                resolver = "2"
+                members = [
+                    "a",
+                    "b",
+                ]
+
+                [workspace.dependencies]
+                serde = "1"
+
+                [workspace.metadata.release]
+                shared-version = true
+
+                [profile.release]
+                debug = true
+                "#,
+                &[ManifestSection::Formatting],
+            ),
+            (
+                &base.replace(r#"["a", "b"]"#, r#"["a", "b", "c"]"#),
+                &[ManifestSection::Members],
+            ),
+            (
+                &base.replace(r#"serde = "1""#, r#"serde = "1.0.200""#),
+                &[ManifestSection::Dependencies],
+            ),
+            (
+                &base.replace("shared-version = true", "shared-version = false"),
+                &[ManifestSection::Metadata],
+            ),
+            (
+                &base.replace("debug = true", "debug = false"),
+                &[ManifestSection::Profiles],
+            ),
+            (
+                &base.replace(r#"resolver = "2""#, r#"resolver = "1""#),
+                &[ManifestSection::Other],
+            ),
+            (
+                &format!(
+                    "{}\n[patch.crates-io]\nserde = {{ path = \"serde\" }}\n",
+                    base
+                ),
+                &[ManifestSection::Other],
+            ),
+            (
+                &format!("{}\n[package]\nname = \"root\"\n", base),
+                &[ManifestSection::Package],
+            ),
+            (
+                &base
+                    .replace(r#"["a", "b"]"#, r#"["a"]"#)
+                    .replace("debug = true", "debug = false"),
+                &[ManifestSection::Members, ManifestSection::Profiles],
+            ),
+            ("this is not valid TOML", &[ManifestSection::Other]),
+        ];
+
+        for (new, expected) in cases {
+            let expected: BTreeSet<_> = expected.iter().copied().collect();
+            assert_eq!(
+                changed_sections(base, new),
+                expected,
+                "sections changed for:\n{}",
+                new
+            );
+        }
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{rules::RuleIndex, DeterminatorSet};
+use camino::Utf8PathBuf;
+use guppy::{
+    graph::{
+        summaries::{diff::SummaryDiffTag, SummaryId},
+        PackageGraph,
+    },
+    PackageId,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The reason a package is in the [`affected_set`](DeterminatorSet::affected_set) of a
+/// [`DeterminatorSet`].
+///
+/// Returned as part of [`DeterminatorSet::reasons`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DeterminatorReason<'g> {
+    /// The package was marked changed because one or more paths changed.
+    ///
+    /// If a changed path caused every package to be marked changed, every package has this reason
+    /// with just that path.
+    PathChanged(Vec<ChangedPath>),
+
+    /// The package was marked changed because its content hash changed.
+    ///
+    /// For more, see [`Determinator::add_changed_hashes`](crate::Determinator::add_changed_hashes).
+    HashChanged,
+
+    /// The package was marked changed because the results of a simulated Cargo build changed.
+    ///
+    /// The list contains the dependencies that changed. It is empty if the package is new or was
+    /// renamed.
+    SummaryChanged(Vec<SummaryChange>),
+
+    /// The package was affected because another package was marked changed or affected.
+    ///
+    /// To get the full chain of packages back to a changed package, use
+    /// [`DeterminatorSet::chain`].
+    Affected {
+        /// The package that caused this package to be affected.
+        from: &'g PackageId,
+        /// How this package was affected by `from`.
+        via: AffectedVia,
+    },
+}
+
+/// A changed path that caused a package to be marked changed.
+///
+/// Part of [`DeterminatorReason::PathChanged`].
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ChangedPath {
+    /// The path, as passed in to [`Determinator::add_changed_paths`](crate::Determinator::add_changed_paths).
+    pub path: Utf8PathBuf,
+
+    /// The path rule that matched this path.
+    ///
+    /// This is `None` if the path was matched to its nearest ancestor package, or if it didn't
+    /// match any rule or package and so caused every package to be marked changed. If the nearest
+    /// ancestor package is a path dependency outside the workspace, this is
+    /// [`RuleIndex::PathDependency`].
+    pub rule: Option<RuleIndex>,
+}
+
+/// A dependency whose build changed, causing a package to be marked changed.
+///
+/// Part of [`DeterminatorReason::SummaryChanged`].
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SummaryChange {
+    /// The dependency that changed.
+    pub id: SummaryId,
+
+    /// Whether the dependency was added, modified or removed.
+    #[serde(with = "summary_diff_tag_impl")]
+    pub tag: SummaryDiffTag,
+}
+
+/// How a package was affected by another package.
+///
+/// Part of [`DeterminatorReason::Affected`].
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AffectedVia {
+    /// The package depends on the other package in a simulated Cargo build.
+    CargoBuild,
+    /// A package rule marked this package changed when the other package was affected.
+    PackageRule(RuleIndex),
+}
+
+/// A serializable summary of why each package was selected by the determinator, keyed by
+/// workspace package name.
+///
+/// Returned by [`DeterminatorSet::to_summary`].
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DeterminatorSummary {
+    /// Map of affected workspace package names to the reason they were selected.
+    pub affected: BTreeMap<String, ReasonSummary>,
+}
+
+/// A serializable version of [`DeterminatorReason`], with package IDs replaced by names.
+///
+/// Part of [`DeterminatorSummary`].
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case", tag = "reason")]
+#[non_exhaustive]
+pub enum ReasonSummary {
+    /// The package was marked changed because one or more paths changed.
+    PathChanged {
+        /// The changed paths.
+        paths: Vec<ChangedPath>,
+    },
+
+    /// The package was marked changed because its content hash changed.
+    HashChanged,
+
+    /// The package was marked changed because the results of a simulated Cargo build changed.
+    SummaryChanged {
+        /// The dependencies that changed.
+        changes: Vec<SummaryChange>,
+    },
+
+    /// The package was affected because another package was marked changed or affected.
+    Affected {
+        /// The names of the packages from the one that was marked changed to this one, inclusive.
+        chain: Vec<String>,
+        /// How this package was affected by the previous package in the chain.
+        via: AffectedVia,
+    },
+}
+
+impl<'g> DeterminatorSet<'g> {
+    /// Returns the chain of packages that caused `package_id` to be affected, starting from a
+    /// package that was marked changed and ending at `package_id`.
+    ///
+    /// Returns a single-element list if `package_id` was marked changed itself, and an empty list
+    /// if it isn't in the affected set.
+    pub fn chain(&self, package_id: &PackageId) -> Vec<&'g PackageId> {
+        let mut chain = vec![];
+        let mut current = match self.reasons.get_key_value(package_id) {
+            Some((&id, _)) => id,
+            None => return chain,
+        };
+        chain.push(current);
+        while let Some(DeterminatorReason::Affected { from, .. }) = self.reasons.get(current) {
+            current = from;
+            chain.push(current);
+        }
+        chain.reverse();
+        chain
+    }
+
+    /// Returns a serializable summary of why each package in the affected set was selected.
+    ///
+    /// `package_graph` is the new package graph passed in to the
+    /// [`Determinator`](crate::Determinator).
+    pub fn to_summary(&self, package_graph: &'g PackageGraph) -> DeterminatorSummary {
+        let name = |package_id: &PackageId| -> String {
+            package_graph
+                .metadata(package_id)
+                .expect("package ID obtained from the same graph")
+                .name()
+                .to_owned()
+        };
+
+        let affected = self
+            .reasons
+            .iter()
+            .map(|(&package_id, reason)| {
+                let summary = match reason {
+                    DeterminatorReason::PathChanged(paths) => ReasonSummary::PathChanged {
+                        paths: paths.clone(),
+                    },
+                    DeterminatorReason::HashChanged => ReasonSummary::HashChanged,
+                    DeterminatorReason::SummaryChanged(changes) => ReasonSummary::SummaryChanged {
+                        changes: changes.clone(),
+                    },
+                    DeterminatorReason::Affected { via, .. } => ReasonSummary::Affected {
+                        chain: self.chain(package_id).into_iter().map(name).collect(),
+                        via: *via,
+                    },
+                };
+                (name(package_id), summary)
+            })
+            .collect();
+
+        DeterminatorSummary { affected }
+    }
+}
+
+mod summary_diff_tag_impl {
+    use super::*;
+    use serde::{de::Error, Deserializer, Serializer};
+
+    pub fn serialize<S>(tag: &SummaryDiffTag, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let s = match tag {
+            SummaryDiffTag::Added => "added",
+            SummaryDiffTag::Modified => "modified",
+            SummaryDiffTag::Removed => "removed",
+        };
+        s.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SummaryDiffTag, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        match s.as_str() {
+            "added" => Ok(SummaryDiffTag::Added),
+            "modified" => Ok(SummaryDiffTag::Modified),
+            "removed" => Ok(SummaryDiffTag::Removed),
+            other => Err(D::Error::custom(format!(
+                "unknown summary diff tag: {}",
+                other
+            ))),
+        }
+    }
+}
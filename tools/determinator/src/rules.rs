@@ -24,6 +24,15 @@
 //!
 //! Determinator rules are a configuration file format and can be read from a TOML file.
 //!
+//! For logic that can't be expressed through TOML, path rules can also be implemented in Rust
+//! through the [`CustomRule`] trait. These are applied before any path rules from TOML.
+//!
+//! Workspace packages can also declare the paths they own in their `Cargo.toml`: see
+//! [`PackageMetadataRule`].
+//!
+//! Changes to the root `Cargo.toml` of the workspace can be classified by section, such as member
+//! list or profile edits, each with its own effect: see [`WorkspaceManifestRules`].
+//!
 //! # Default path rules
 //!
 //! The determinator ships with a set of default path rules for common files such as `.gitignore`
@@ -64,6 +73,16 @@
 //! post-rule = "skip-rules"
 //! ```
 //!
+//! Globs starting with `!` exclude paths from a rule. Exclusions take precedence over other
+//! globs, so to mark `docs-site` changed for files in `docs`, but not for the generated files in
+//! `docs/api`:
+//!
+//! ```toml
+//! [[path-rule]]
+//! globs = ["docs/**", "!docs/api/**"]
+//! mark-changed = ["docs-site"]
+//! ```
+//!
 //! To build everything if a special file changes:
 //!
 //! ```toml
@@ -72,6 +91,24 @@
 //! mark-changed = "all"
 //! ```
 //!
+//! Files like `deny.toml` or `.cargo/config.toml` may only affect some packages. To mark a subset
+//! of packages changed, selected by name, path or dependencies, rather than everything:
+//!
+//! ```toml
+//! [[path-rule]]
+//! globs = [".cargo/config.toml"]
+//!
+//! # Mark changed packages under tools/, along with every workspace package that depends on
+//! # openssl-sys, except for guppy-benchmarks.
+//! [path-rule.mark-changed]
+//! paths = ["tools/**"]
+//! depends-on = ["openssl-sys"]
+//! exclude = ["guppy-benchmarks"]
+//! ```
+//!
+//! Custom rules are applied before default rules, so this overrides the default rule that marks
+//! everything changed if `.cargo/config.toml` changes.
+//!
 //! To apply multiple rules to a file, say `CODE_OF_CONDUCT.md`:
 //!
 //! ```toml
@@ -105,11 +142,12 @@
 //! ```
 
 use crate::errors::RulesError;
-use globset::{Glob, GlobSet, GlobSetBuilder};
-use guppy::graph::{PackageGraph, PackageMetadata, PackageSet, Workspace};
+use camino::Utf8Path;
+use globset::{Candidate, Glob, GlobSet, GlobSetBuilder};
+use guppy::graph::{PackageGraph, PackageMetadata, PackageSet};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use std::{collections::BTreeMap, fmt};
 
 /// Rules for the target determinator.
 ///
@@ -125,6 +163,13 @@ pub struct DeterminatorRules {
     #[serde(default = "default_true", rename = "use-default-rules")]
     use_default_rules: bool,
 
+    /// Whether to use path rules declared by workspace packages in
+    /// `[package.metadata.determinator]`. For more, see [`PackageMetadataRule`].
+    ///
+    /// This is true by default.
+    #[serde(default = "default_true", rename = "use-package-metadata")]
+    use_package_metadata: bool,
+
     /// A list of rules that each changed file path is matched against.
     #[serde(default, rename = "path-rule")]
     pub path_rules: Vec<PathRule>,
@@ -135,6 +180,13 @@ pub struct DeterminatorRules {
     /// packages here act as "virtual dependencies" for the determinator.
     #[serde(default, rename = "package-rule")]
     pub package_rules: Vec<PackageRule>,
+
+    /// What to mark changed if sections of the workspace's root `Cargo.toml` change.
+    ///
+    /// This is only used if the old and new contents of the root `Cargo.toml` are passed in
+    /// through [`Determinator::set_workspace_manifests`](crate::Determinator::set_workspace_manifests).
+    #[serde(default, rename = "workspace-manifest")]
+    pub workspace_manifest: WorkspaceManifestRules,
 }
 
 /// The `Default` impl is the set of custom rules used by the determinator if
@@ -145,8 +197,10 @@ impl Default for DeterminatorRules {
     fn default() -> Self {
         Self {
             use_default_rules: true,
+            use_package_metadata: true,
             path_rules: vec![],
             package_rules: vec![],
+            workspace_manifest: WorkspaceManifestRules::default(),
         }
     }
 }
@@ -170,6 +224,19 @@ impl DeterminatorRules {
         toml::from_str(s)
     }
 
+    /// Returns true if path rules declared in `[package.metadata.determinator]` are used.
+    ///
+    /// This is true by default. For more, see [`PackageMetadataRule`].
+    pub fn use_package_metadata(&self) -> bool {
+        self.use_package_metadata
+    }
+
+    /// Sets whether path rules declared in `[package.metadata.determinator]` are used.
+    pub fn set_use_package_metadata(&mut self, use_package_metadata: bool) -> &mut Self {
+        self.use_package_metadata = use_package_metadata;
+        self
+    }
+
     doc_comment! {
         concat!("\
 Contains the default rules in a TOML file format.
@@ -217,7 +284,12 @@ The latest version of the default rules is available
 pub struct PathRule {
     /// The globs to match against.
     ///
-    /// A changed path matches a rule if it matches any of the globs on this list.
+    /// Globs starting with `!` are exclusions. A changed path matches a rule if it matches any of
+    /// the other globs on this list, and none of the exclusions. Exclusions always take
+    /// precedence, regardless of where they appear in the list.
+    ///
+    /// A rule with exclusions must also have at least one glob that isn't an exclusion. To match
+    /// a path that starts with `!`, escape it as `\!`.
     ///
     /// # Examples
     ///
@@ -226,6 +298,12 @@ pub struct PathRule {
     /// ```toml
     /// globs = ["foo", "**/bar/*.rs"]
     /// ```
+    ///
+    /// To match everything in `docs` except for `docs/api`:
+    ///
+    /// ```toml
+    /// globs = ["docs/**", "!docs/api/**"]
+    /// ```
     pub globs: Vec<String>,
 
     /// The set of packages to mark as changed.
@@ -243,6 +321,12 @@ pub struct PathRule {
     /// ```toml
     /// mark-changed = ["guppy", "determinator"]
     /// ```
+    ///
+    /// or a [`PackageSelector`] table:
+    ///
+    /// ```toml
+    /// mark-changed = { names = ["guppy-*"], depends-on = ["toml"] }
+    /// ```
     #[serde(with = "mark_changed_impl")]
     pub mark_changed: DeterminatorMarkChanged,
 
@@ -295,6 +379,127 @@ pub enum DeterminatorPostRule {
     Fallthrough,
 }
 
+/// A path rule implemented in Rust, for logic that can't be expressed through TOML rules.
+///
+/// Custom rules are registered with
+/// [`Determinator::add_custom_rule`](crate::Determinator::add_custom_rule). They are applied in
+/// the order they were registered, before any [path rules](PathRule) from TOML.
+///
+/// # Examples
+///
+/// To mark packages changed if a `.proto` file they list in `package.metadata.protos` changes:
+///
+/// ```
+/// use camino::Utf8Path;
+/// use determinator::rules::{CustomRule, CustomRuleDecision, DeterminatorPostRule};
+/// use guppy::graph::PackageGraph;
+///
+/// #[derive(Debug)]
+/// struct ProtoRule;
+///
+/// impl CustomRule for ProtoRule {
+///     fn match_path<'g>(
+///         &self,
+///         path: &Utf8Path,
+///         _old: &PackageGraph,
+///         new: &'g PackageGraph,
+///     ) -> CustomRuleDecision<'g> {
+///         if path.extension() != Some("proto") {
+///             return CustomRuleDecision::NoMatch;
+///         }
+///         let packages = new
+///             .workspace()
+///             .iter()
+///             .filter(|package| {
+///                 package.metadata_table()["protos"]
+///                     .as_array()
+///                     .is_some_and(|protos| protos.iter().any(|p| p.as_str() == Some(path.as_str())))
+///             })
+///             .collect();
+///         CustomRuleDecision::Mark {
+///             packages,
+///             // Also match the .proto file to its nearest package.
+///             post_rule: DeterminatorPostRule::SkipRules,
+///         }
+///     }
+/// }
+/// ```
+pub trait CustomRule: fmt::Debug + Send + Sync {
+    /// Decides what to do with a changed path.
+    ///
+    /// `old` and `new` are the package graphs passed in to the
+    /// [`Determinator`](crate::Determinator).
+    fn match_path<'g>(
+        &self,
+        path: &Utf8Path,
+        old: &PackageGraph,
+        new: &'g PackageGraph,
+    ) -> CustomRuleDecision<'g>;
+}
+
+/// The decision made by a [`CustomRule`] for a changed path.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum CustomRuleDecision<'g> {
+    /// The rule doesn't apply to this path. Further rules are applied.
+    NoMatch,
+
+    /// Mark these workspace packages from the new graph as changed, then perform `post_rule`.
+    Mark {
+        /// The packages to mark changed.
+        packages: Vec<PackageMetadata<'g>>,
+        /// The operation to perform after marking packages changed.
+        post_rule: DeterminatorPostRule,
+    },
+
+    /// Mark every package as changed.
+    MarkAll,
+}
+
+/// A path rule declared by a workspace package in its `Cargo.toml`, under
+/// `[package.metadata.determinator]`.
+///
+/// This lets packages declare the files they own next to their code, rather than in a central rules
+/// file. If a changed path matches, the declaring package is marked changed.
+///
+/// Package metadata rules are applied after [path rules](PathRule) from the rules file, and before
+/// the default rules. To disable them, set `use-package-metadata = false` at the top level of the
+/// rules file.
+///
+/// # Examples
+///
+/// To mark a package changed if any shaders change, except for their README:
+///
+/// ```toml
+/// [package.metadata.determinator]
+/// # Globs are relative to the root of the workspace.
+/// globs = ["assets/shaders/**", "!assets/shaders/README.md"]
+/// # Continue to match the path to other rules and its nearest package.
+/// post-rule = "fallthrough"
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PackageMetadataRule {
+    /// The globs to match against. These have the same format as [`PathRule::globs`].
+    pub globs: Vec<String>,
+
+    /// The operation to perform after applying the rule. Set to "skip" by default.
+    #[serde(default)]
+    pub post_rule: DeterminatorPostRule,
+}
+
+impl PackageMetadataRule {
+    /// Reads the rule declared by this package, if any.
+    pub(crate) fn from_package(
+        package: &PackageMetadata<'_>,
+    ) -> Result<Option<Self>, serde_json::Error> {
+        match package.metadata_table().get("determinator") {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(value) => Self::deserialize(value).map(Some),
+        }
+    }
+}
+
 /// Package-based rules for the determinator.
 ///
 /// These rules customize the behavior of the determinator based on affected packages, and can be
@@ -335,11 +540,17 @@ pub struct PackageRule {
     /// mark-changed = "all"
     /// ```
     ///
-    /// or an array of workspace package names:
+    /// an array of workspace package names:
     ///
     /// ```toml
     /// mark-changed = ["guppy", "determinator"]
     /// ```
+    ///
+    /// or a [`PackageSelector`] table:
+    ///
+    /// ```toml
+    /// mark-changed = { paths = ["tools/**"] }
+    /// ```
     #[serde(with = "mark_changed_impl")]
     pub mark_changed: DeterminatorMarkChanged,
 }
@@ -360,6 +571,12 @@ pub struct PackageRule {
 /// mark-changed = ["guppy", "determinator"]
 /// ```
 ///
+/// or a table selecting a subset of packages, as described in [`PackageSelector`]:
+///
+/// ```toml
+/// mark-changed = { names = ["guppy-*"], paths = ["tools/**"] }
+/// ```
+///
 /// For more examples, see [the module-level documentation](index.html).
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case", untagged)]
@@ -373,6 +590,9 @@ pub enum DeterminatorMarkChanged {
     /// ```
     Packages(Vec<String>),
 
+    /// Mark the workspace packages selected by name, path or dependencies as changed.
+    Select(PackageSelector),
+
     /// Mark the entire tree as changed. Skip over all further processing and return the entire
     /// workspace as affected.
     ///
@@ -380,6 +600,179 @@ pub enum DeterminatorMarkChanged {
     All,
 }
 
+/// A set of workspace packages, selected by name, path or dependencies.
+///
+/// Used to mark a subset of the workspace changed: see [`DeterminatorMarkChanged::Select`]. This
+/// is useful for files like `rust-toolchain.toml`, `.cargo/config.toml` or `deny.toml` that
+/// affect more than one package, but not necessarily the entire workspace.
+///
+/// A package is selected if it matches any of `names`, `paths` or `depends-on`, and doesn't match
+/// `exclude`.
+///
+/// # Examples
+///
+/// In TOML format, as part of a path rule:
+///
+/// ```toml
+/// [[path-rule]]
+/// globs = ["deny.toml"]
+///
+/// [path-rule.mark-changed]
+/// names = ["guppy-*"]
+/// paths = ["tools/**"]
+/// depends-on = ["openssl-sys"]
+/// exclude = ["guppy-benchmarks"]
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PackageSelector {
+    /// Globs matched against the names of workspace packages.
+    ///
+    /// Names without glob metacharacters must match a workspace package.
+    #[serde(default)]
+    pub names: Vec<String>,
+
+    /// Globs matched against the paths of workspace packages, relative to the workspace root.
+    #[serde(default)]
+    pub paths: Vec<String>,
+
+    /// Names of packages, in the workspace or not. Workspace packages that depend on any of these,
+    /// directly or transitively, are selected. Workspace packages named here are selected as well.
+    ///
+    /// Dependencies of every kind and on every platform are considered. Names that aren't in the
+    /// package graph are ignored, since dependencies may come and go over time.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+
+    /// Globs matched against the names of workspace packages. Matching packages are never selected.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// What to mark changed for each kind of change to the root `Cargo.toml` of a workspace.
+///
+/// By default, a change to the root `Cargo.toml` causes every package to be marked changed.
+/// If the old and new contents of the root `Cargo.toml` are passed in through
+/// [`Determinator::set_workspace_manifests`](crate::Determinator::set_workspace_manifests),
+/// changes are classified into [`ManifestSection`]s, and each kind of change has the effect
+/// configured here instead. This replaces path rules for the root `Cargo.toml`.
+///
+/// Changes to member lists, `[workspace.dependencies]` and the root package are already reflected
+/// in the package graph, so build simulations pick up packages that are affected by them.
+///
+/// # Examples
+///
+/// In TOML format, with the defaults:
+///
+/// ```toml
+/// [workspace-manifest]
+/// members = []
+/// dependencies = []
+/// metadata = []
+/// profiles = "all"
+/// other = "all"
+/// ```
+///
+/// Each value is specified in the same way as `mark-changed` in path rules: see
+/// [`DeterminatorMarkChanged`].
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct WorkspaceManifestRules {
+    /// What to mark changed if `members`, `exclude` or `default-members` in `[workspace]` change.
+    #[serde(default = "mark_changed_none", with = "mark_changed_impl")]
+    pub members: DeterminatorMarkChanged,
+
+    /// What to mark changed if `[workspace.dependencies]` changes.
+    #[serde(default = "mark_changed_none", with = "mark_changed_impl")]
+    pub dependencies: DeterminatorMarkChanged,
+
+    /// What to mark changed if `[workspace.metadata]` or `[package.metadata]` changes.
+    #[serde(default = "mark_changed_none", with = "mark_changed_impl")]
+    pub metadata: DeterminatorMarkChanged,
+
+    /// What to mark changed if any `[profile]` section changes.
+    #[serde(default = "mark_changed_all", with = "mark_changed_impl")]
+    pub profiles: DeterminatorMarkChanged,
+
+    /// What to mark changed if anything else changes, such as `[patch]` sections or the resolver
+    /// version, or if the root `Cargo.toml` couldn't be parsed.
+    #[serde(default = "mark_changed_all", with = "mark_changed_impl")]
+    pub other: DeterminatorMarkChanged,
+}
+
+impl Default for WorkspaceManifestRules {
+    fn default() -> Self {
+        Self {
+            members: mark_changed_none(),
+            dependencies: mark_changed_none(),
+            metadata: mark_changed_none(),
+            profiles: mark_changed_all(),
+            other: mark_changed_all(),
+        }
+    }
+}
+
+impl WorkspaceManifestRules {
+    /// Returns what to mark changed for the given section, or `None` if the section has a fixed
+    /// effect.
+    pub fn get(&self, section: ManifestSection) -> Option<&DeterminatorMarkChanged> {
+        match section {
+            ManifestSection::Members => Some(&self.members),
+            ManifestSection::Dependencies => Some(&self.dependencies),
+            ManifestSection::Metadata => Some(&self.metadata),
+            ManifestSection::Profiles => Some(&self.profiles),
+            ManifestSection::Other => Some(&self.other),
+            ManifestSection::Formatting | ManifestSection::Package => None,
+        }
+    }
+}
+
+fn mark_changed_none() -> DeterminatorMarkChanged {
+    DeterminatorMarkChanged::Packages(vec![])
+}
+
+fn mark_changed_all() -> DeterminatorMarkChanged {
+    DeterminatorMarkChanged::All
+}
+
+/// A kind of change to the root `Cargo.toml` of a workspace.
+///
+/// For more, see [`WorkspaceManifestRules`].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ManifestSection {
+    /// Only comments or formatting changed. Nothing is marked changed.
+    Formatting,
+    /// `members`, `exclude` or `default-members` in `[workspace]` changed.
+    Members,
+    /// `[workspace.dependencies]` changed.
+    Dependencies,
+    /// `[workspace.metadata]` or `[package.metadata]` changed.
+    Metadata,
+    /// A `[profile]` section changed.
+    Profiles,
+    /// The manifest of the package at the root of the workspace changed, other than
+    /// `[package.metadata]`. The root package is marked changed.
+    Package,
+    /// Anything else changed, or the manifest couldn't be parsed.
+    Other,
+}
+
+impl fmt::Display for ManifestSection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            ManifestSection::Formatting => "formatting",
+            ManifestSection::Members => "members",
+            ManifestSection::Dependencies => "dependencies",
+            ManifestSection::Metadata => "metadata",
+            ManifestSection::Profiles => "profiles",
+            ManifestSection::Package => "package",
+            ManifestSection::Other => "other",
+        };
+        write!(f, "{}", s)
+    }
+}
+
 /// The result of matching a file path against a determinator.
 ///
 /// Returned by `Determinator::match_path`.
@@ -399,8 +792,9 @@ pub enum PathMatch {
 
 /// The index of a rule.
 ///
-/// Used in `PathMatch` and while returning errors.
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+/// Used in `PathMatch`, `DeterminatorReason` and while returning errors.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum RuleIndex {
     /// The custom path rule at this index.
     CustomPath(usize),
@@ -410,6 +804,24 @@ pub enum RuleIndex {
     ///
     /// All package rules are custom: there are no default package rules.
     Package(usize),
+    /// The [`CustomRule`] plugin at this index, in the order it was registered.
+    Plugin(usize),
+    /// The [`PackageMetadataRule`] at this index.
+    ///
+    /// Package metadata rules are indexed in order of the names of the workspace packages that
+    /// declare them.
+    PackageMetadata(usize),
+    /// A `cargo:rerun-if-changed` declaration in a build script.
+    ///
+    /// For more, see [`BuildScriptPaths`](crate::BuildScriptPaths).
+    BuildScript,
+    /// The path is inside a path dependency that isn't in the workspace, and the package depends
+    /// on it.
+    PathDependency,
+    /// The root `Cargo.toml` of the workspace changed in this section.
+    ///
+    /// For more, see [`WorkspaceManifestRules`].
+    WorkspaceManifest(ManifestSection),
 }
 
 impl fmt::Display for RuleIndex {
@@ -418,6 +830,13 @@ impl fmt::Display for RuleIndex {
             RuleIndex::CustomPath(index) => write!(f, "custom path rule {}", index),
             RuleIndex::DefaultPath(index) => write!(f, "default path rule {}", index),
             RuleIndex::Package(index) => write!(f, "package rule {}", index),
+            RuleIndex::Plugin(index) => write!(f, "custom rule plugin {}", index),
+            RuleIndex::PackageMetadata(index) => write!(f, "package metadata rule {}", index),
+            RuleIndex::BuildScript => write!(f, "build script rerun-if-changed declaration"),
+            RuleIndex::PathDependency => write!(f, "non-workspace path dependency"),
+            RuleIndex::WorkspaceManifest(section) => {
+                write!(f, "workspace manifest rule for {}", section)
+            }
         }
     }
 }
@@ -431,6 +850,7 @@ impl fmt::Display for RuleIndex {
 pub(crate) struct RulesImpl<'g> {
     pub(crate) path_rules: Vec<PathRuleImpl<'g>>,
     pub(crate) package_rules: Vec<PackageRuleImpl<'g>>,
+    pub(crate) workspace_manifest: BTreeMap<ManifestSection, MarkChangedImpl<'g>>,
 }
 
 impl<'g> RulesImpl<'g> {
@@ -444,7 +864,34 @@ impl<'g> RulesImpl<'g> {
             .path_rules
             .iter()
             .enumerate()
-            .map(|(idx, rule)| (RuleIndex::CustomPath(idx), rule));
+            .map(|(idx, rule)| (RuleIndex::CustomPath(idx), rule, None));
+
+        let mut metadata_path_rules = vec![];
+        if options.use_package_metadata {
+            for package in workspace.iter_by_name().map(|(_, package)| package) {
+                let rule_index = RuleIndex::PackageMetadata(metadata_path_rules.len());
+                let rule = PackageMetadataRule::from_package(&package).map_err(|err| {
+                    RulesError::package_metadata(rule_index, err).with_package_name(package.name())
+                })?;
+                if let Some(rule) = rule {
+                    let rule = PathRule {
+                        globs: rule.globs,
+                        mark_changed: DeterminatorMarkChanged::Packages(vec![package
+                            .name()
+                            .to_owned()]),
+                        post_rule: rule.post_rule,
+                    };
+                    metadata_path_rules.push((package.name(), rule));
+                }
+            }
+        }
+        let metadata_path_rules =
+            metadata_path_rules
+                .iter()
+                .enumerate()
+                .map(|(idx, (package_name, rule))| {
+                    (RuleIndex::PackageMetadata(idx), rule, Some(*package_name))
+                });
 
         let default_path_rules = if options.use_default_rules {
             let default_rules = DeterminatorRules::default_rules();
@@ -456,10 +903,11 @@ impl<'g> RulesImpl<'g> {
         let default_path_rules = default_path_rules
             .iter()
             .enumerate()
-            .map(|(idx, rule)| (RuleIndex::DefaultPath(idx), rule));
+            .map(|(idx, rule)| (RuleIndex::DefaultPath(idx), rule, None));
 
-        // Default rules come after custom ones.
+        // Package metadata rules come after custom ones, and default rules come last.
         let path_rules = custom_path_rules
+            .chain(metadata_path_rules)
             .chain(default_path_rules)
             .map(
                 |(
@@ -469,26 +917,55 @@ impl<'g> RulesImpl<'g> {
                         mark_changed,
                         post_rule,
                     },
+                    package_name,
                 )| {
-                    // Convert the globs to a globset.
+                    let make_error = |err: RulesError| match package_name {
+                        Some(package_name) => err.with_package_name(package_name),
+                        None => err,
+                    };
+                    // Convert the globs to globsets, with globs starting with ! going to a
+                    // separate set of exclusions.
                     let mut builder = GlobSetBuilder::new();
+                    let mut exclude_builder = GlobSetBuilder::new();
+                    let mut has_includes = false;
+                    let mut has_excludes = false;
                     for glob in globs {
-                        let glob = Glob::new(glob)
-                            .map_err(|err| RulesError::glob_parse(rule_index, err))?;
-                        builder.add(glob);
+                        match glob.strip_prefix('!') {
+                            Some(glob) => {
+                                let glob = Glob::new(glob).map_err(|err| {
+                                    make_error(RulesError::glob_parse(rule_index, err))
+                                })?;
+                                exclude_builder.add(glob);
+                                has_excludes = true;
+                            }
+                            None => {
+                                let glob = Glob::new(glob).map_err(|err| {
+                                    make_error(RulesError::glob_parse(rule_index, err))
+                                })?;
+                                builder.add(glob);
+                                has_includes = true;
+                            }
+                        }
+                    }
+                    if has_excludes && !has_includes {
+                        return Err(make_error(RulesError::only_excludes(rule_index)));
                     }
 
                     let glob_set = builder
                         .build()
-                        .map_err(|err| RulesError::glob_parse(rule_index, err))?;
+                        .map_err(|err| make_error(RulesError::glob_parse(rule_index, err)))?;
+                    let exclude_set = exclude_builder
+                        .build()
+                        .map_err(|err| make_error(RulesError::glob_parse(rule_index, err)))?;
 
                     // Convert workspace paths to packages.
-                    let mark_changed = MarkChangedImpl::new(&workspace, mark_changed)
-                        .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
+                    let mark_changed = MarkChangedImpl::new(graph, rule_index, mark_changed)
+                        .map_err(make_error)?;
 
                     Ok(PathRuleImpl {
                         rule_index,
                         glob_set,
+                        exclude_set,
                         mark_changed,
                         post_rule: *post_rule,
                     })
@@ -512,8 +989,7 @@ impl<'g> RulesImpl<'g> {
                     let on_affected = graph
                         .resolve_workspace_names(on_affected)
                         .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
-                    let mark_changed = MarkChangedImpl::new(&workspace, mark_changed)
-                        .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
+                    let mark_changed = MarkChangedImpl::new(graph, rule_index, mark_changed)?;
                     Ok(PackageRuleImpl {
                         on_affected,
                         mark_changed,
@@ -522,9 +998,29 @@ impl<'g> RulesImpl<'g> {
             )
             .collect::<Result<Vec<_>, _>>()?;
 
+        let workspace_manifest = [
+            ManifestSection::Members,
+            ManifestSection::Dependencies,
+            ManifestSection::Metadata,
+            ManifestSection::Profiles,
+            ManifestSection::Other,
+        ]
+        .into_iter()
+        .map(|section| {
+            let mark_changed = options
+                .workspace_manifest
+                .get(section)
+                .expect("section is configurable");
+            let mark_changed =
+                MarkChangedImpl::new(graph, RuleIndex::WorkspaceManifest(section), mark_changed)?;
+            Ok((section, mark_changed))
+        })
+        .collect::<Result<_, _>>()?;
+
         Ok(Self {
             path_rules,
             package_rules,
+            workspace_manifest,
         })
     }
 }
@@ -533,10 +1029,20 @@ impl<'g> RulesImpl<'g> {
 pub(crate) struct PathRuleImpl<'g> {
     pub(crate) rule_index: RuleIndex,
     pub(crate) glob_set: GlobSet,
+    /// Globs prefixed with `!`. These take precedence over `glob_set`.
+    pub(crate) exclude_set: GlobSet,
     pub(crate) mark_changed: MarkChangedImpl<'g>,
     pub(crate) post_rule: DeterminatorPostRule,
 }
 
+impl<'g> PathRuleImpl<'g> {
+    /// Returns true if the candidate matches at least one glob and none of the exclusions.
+    pub(crate) fn is_match(&self, candidate: &Candidate<'_>) -> bool {
+        self.glob_set.is_match_candidate(candidate)
+            && !self.exclude_set.is_match_candidate(candidate)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct PackageRuleImpl<'g> {
     pub(crate) on_affected: PackageSet<'g>,
@@ -551,18 +1057,86 @@ pub(crate) enum MarkChangedImpl<'g> {
 
 impl<'g> MarkChangedImpl<'g> {
     fn new(
-        workspace: &Workspace<'g>,
+        graph: &'g PackageGraph,
+        rule_index: RuleIndex,
         mark_changed: &DeterminatorMarkChanged,
-    ) -> Result<Self, guppy::Error> {
+    ) -> Result<Self, RulesError> {
         match mark_changed {
             DeterminatorMarkChanged::Packages(names) => Ok(MarkChangedImpl::Packages(
-                workspace.members_by_names(names)?,
+                graph
+                    .workspace()
+                    .members_by_names(names)
+                    .map_err(|err| RulesError::resolve_ref(rule_index, err))?,
+            )),
+            DeterminatorMarkChanged::Select(selector) => Ok(MarkChangedImpl::Packages(
+                resolve_selector(graph, rule_index, selector)?,
             )),
             DeterminatorMarkChanged::All => Ok(MarkChangedImpl::All),
         }
     }
 }
 
+fn resolve_selector<'g>(
+    graph: &'g PackageGraph,
+    rule_index: RuleIndex,
+    selector: &PackageSelector,
+) -> Result<Vec<PackageMetadata<'g>>, RulesError> {
+    let workspace = graph.workspace();
+    let build_glob_set = |globs: &[String]| -> Result<GlobSet, RulesError> {
+        let mut builder = GlobSetBuilder::new();
+        for glob in globs {
+            builder.add(Glob::new(glob).map_err(|err| RulesError::glob_parse(rule_index, err))?);
+        }
+        builder
+            .build()
+            .map_err(|err| RulesError::glob_parse(rule_index, err))
+    };
+
+    // Names without glob metacharacters are likely typos if they don't match anything, so check
+    // them like other lists of names.
+    let exact_names = selector
+        .names
+        .iter()
+        .filter(|name| !name.contains(['*', '?', '[', '{']));
+    workspace
+        .members_by_names::<Vec<_>>(exact_names)
+        .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
+
+    let names = build_glob_set(&selector.names)?;
+    let paths = build_glob_set(&selector.paths)?;
+    let exclude = build_glob_set(&selector.exclude)?;
+
+    let depends_on_ids = graph
+        .packages()
+        .filter(|package| {
+            selector
+                .depends_on
+                .iter()
+                .any(|name| name == package.name())
+        })
+        .map(|package| package.id());
+    let depends_on = graph
+        .query_reverse(depends_on_ids)
+        .expect("package IDs obtained from the same graph")
+        .resolve()
+        .intersection(&graph.resolve_workspace());
+
+    Ok(workspace
+        .iter_by_name()
+        .map(|(_, package)| package)
+        .filter(|package| {
+            let path = package
+                .source()
+                .workspace_path()
+                .expect("workspace members have workspace paths");
+            (names.is_match(package.name())
+                || paths.is_match(path)
+                || depends_on.contains(package.id()).unwrap_or(false))
+                && !exclude.is_match(package.name())
+        })
+        .collect())
+}
+
 mod mark_changed_impl {
     use super::*;
     use serde::{de::Error, Deserializer, Serializer};
@@ -576,6 +1150,7 @@ mod mark_changed_impl {
     {
         match mark_changed {
             DeterminatorMarkChanged::Packages(names) => names.serialize(serializer),
+            DeterminatorMarkChanged::Select(selector) => selector.serialize(serializer),
             DeterminatorMarkChanged::All => "all".serialize(serializer),
         }
     }
@@ -596,6 +1171,9 @@ mod mark_changed_impl {
             MarkChangedDeserialized::VecString(strings) => {
                 Ok(DeterminatorMarkChanged::Packages(strings))
             }
+            MarkChangedDeserialized::Select(selector) => {
+                Ok(DeterminatorMarkChanged::Select(selector))
+            }
         }
     }
 
@@ -604,6 +1182,7 @@ mod mark_changed_impl {
     enum MarkChangedDeserialized {
         String(String),
         VecString(Vec<String>),
+        Select(PackageSelector),
     }
 }
 
@@ -627,6 +1206,10 @@ mod tests {
         globs = ["none/**/test", "foo/bar"]
         mark-changed = []
 
+        [[path-rule]]
+        globs = ["deny.toml"]
+        mark-changed = { names = ["a-*"], depends-on = ["b"], exclude = ["a-c"] }
+
         [[package-rule]]
         on-affected = ["foo"]
         mark-changed = ["wat"]
@@ -634,10 +1217,15 @@ mod tests {
         [[package-rule]]
         on-affected = ["test1"]
         mark-changed = "all"
+
+        [workspace-manifest]
+        metadata = ["m"]
+        profiles = []
         "#;
 
         let expected = DeterminatorRules {
             use_default_rules: true,
+            use_package_metadata: true,
             path_rules: vec![
                 PathRule {
                     globs: vec!["all/*".to_owned()],
@@ -654,6 +1242,16 @@ mod tests {
                     mark_changed: DeterminatorMarkChanged::Packages(vec![]),
                     post_rule: DeterminatorPostRule::Skip,
                 },
+                PathRule {
+                    globs: vec!["deny.toml".to_owned()],
+                    mark_changed: DeterminatorMarkChanged::Select(PackageSelector {
+                        names: vec!["a-*".to_owned()],
+                        paths: vec![],
+                        depends_on: vec!["b".to_owned()],
+                        exclude: vec!["a-c".to_owned()],
+                    }),
+                    post_rule: DeterminatorPostRule::Skip,
+                },
             ],
             package_rules: vec![
                 PackageRule {
@@ -665,6 +1263,11 @@ mod tests {
                     mark_changed: DeterminatorMarkChanged::All,
                 },
             ],
+            workspace_manifest: WorkspaceManifestRules {
+                metadata: DeterminatorMarkChanged::Packages(vec!["m".to_owned()]),
+                profiles: DeterminatorMarkChanged::Packages(vec![]),
+                ..WorkspaceManifestRules::default()
+            },
         };
 
         assert_eq!(
@@ -747,6 +1350,16 @@ mod tests {
             globs = ["a/b"]
             mark-changed = [123, "abc"]
             "#,
+            // mark-changed table has an unrecognized key
+            r#"[[path-rule]]
+            globs = ["a/b"]
+            mark-changed = { names = ["a"], foo = "bar" }
+            "#,
+            // mark-changed table has a key that isn't a list of strings
+            r#"[[path-rule]]
+            globs = ["a/b"]
+            mark-changed = { paths = "a/**" }
+            "#,
             // post-rule is invalid
             r#"[[path-rule]]
             globs = ["a/b"]
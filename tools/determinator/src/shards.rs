@@ -0,0 +1,124 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::DeterminatorSet;
+use guppy::graph::DependencyDirection;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Per-package weights used to balance shards, for example historical build and test durations.
+///
+/// Weights are in arbitrary units: any unit may be used as long as it's the same for every
+/// package. Pass this in to [`DeterminatorSet::shards`].
+///
+/// # Examples
+///
+/// In JSON format:
+///
+/// ```json
+/// {
+///     "default-weight": 60,
+///     "weights": {
+///         "guppy": 300,
+///         "determinator": 45
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ShardWeights {
+    /// The weight of packages that aren't in `weights`, such as newly added packages.
+    ///
+    /// If this is `None`, the average of `weights` is used, or 1 if `weights` is empty.
+    #[serde(default)]
+    pub default_weight: Option<u64>,
+
+    /// Map of workspace package names to their weights.
+    #[serde(default)]
+    pub weights: BTreeMap<String, u64>,
+}
+
+impl ShardWeights {
+    /// Returns the weight of the given package.
+    pub fn weight(&self, name: &str) -> u64 {
+        match self.weights.get(name) {
+            Some(&weight) => weight,
+            None => self.default_weight(),
+        }
+    }
+
+    fn default_weight(&self) -> u64 {
+        self.default_weight.unwrap_or_else(|| {
+            if self.weights.is_empty() {
+                1
+            } else {
+                self.weights.values().sum::<u64>() / self.weights.len() as u64
+            }
+        })
+    }
+}
+
+/// The affected set split into shards of roughly equal weight, for running across parallel CI
+/// jobs.
+///
+/// Returned by [`DeterminatorSet::shards`].
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Shards {
+    /// The list of shards. Every shard is present, even if it's empty.
+    pub shards: Vec<Shard>,
+}
+
+/// A single shard of packages.
+///
+/// Part of [`Shards`].
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Shard {
+    /// The names of the packages in this shard, sorted by name.
+    pub packages: Vec<String>,
+
+    /// The total weight of the packages in this shard.
+    pub weight: u64,
+}
+
+impl<'g> DeterminatorSet<'g> {
+    /// Splits the affected set into `count` shards of roughly equal total weight.
+    ///
+    /// Packages are assigned in decreasing order of weight, each to the shard with the lowest total
+    /// weight so far. The result is deterministic for a given affected set and weights, so every CI
+    /// job can compute the shards independently and pick out its own.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is 0.
+    pub fn shards(&self, count: usize, weights: &ShardWeights) -> Shards {
+        assert!(count > 0, "shard count must be greater than 0");
+
+        let mut packages: Vec<_> = self
+            .affected_set
+            .packages(DependencyDirection::Forward)
+            .map(|package| (weights.weight(package.name()), package.name()))
+            .collect();
+        // Sort by decreasing weight, breaking ties by name.
+        packages.sort_unstable_by(|(a_weight, a_name), (b_weight, b_name)| {
+            b_weight.cmp(a_weight).then_with(|| a_name.cmp(b_name))
+        });
+
+        let mut shards = vec![Shard::default(); count];
+        for (weight, name) in packages {
+            // min_by_key returns the first minimum, so ties go to the shard with the lowest index.
+            let shard = shards
+                .iter_mut()
+                .min_by_key(|shard| shard.weight)
+                .expect("count is greater than 0");
+            shard.packages.push(name.to_owned());
+            shard.weight += weight;
+        }
+        for shard in &mut shards {
+            shard.packages.sort_unstable();
+        }
+
+        Shards { shards }
+    }
+}
@@ -3,13 +3,21 @@
 
 //! Higher-level unit tests for the target determinator.
 
+use camino::{Utf8Path, Utf8PathBuf};
 use cfg_if::cfg_if;
 use determinator::{
-    rules::{DeterminatorRules, PathMatch, RuleIndex},
-    Determinator, Utf8Paths0,
+    rules::{
+        CustomRule, CustomRuleDecision, DeterminatorPostRule, DeterminatorRules, ManifestSection,
+        PathMatch, RuleIndex,
+    },
+    AffectedVia, BuildScriptPaths, ChangedPath, ContentHashes, Determinator, DeterminatorCache,
+    DeterminatorReason, ReasonSummary, Shard, ShardWeights, Utf8Paths0,
 };
 use fixtures::json::JsonFixture;
-use guppy::{graph::feature::StandardFeatures, CargoMetadata};
+use guppy::{
+    graph::{feature::StandardFeatures, DependencyDirection, PackageGraph},
+    CargoMetadata,
+};
 
 #[test]
 fn guppy_no_rules() {
@@ -184,6 +192,184 @@ fn guppy_path_rules() {
     }
 }
 
+#[test]
+fn guppy_reasons() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let opts = read_options(new, "path-rules.toml");
+    let graph = new.graph();
+    let id = |name: &str| {
+        graph
+            .workspace()
+            .member_by_name(name)
+            .expect("workspace name is valid")
+            .id()
+    };
+
+    let mut determinator = Determinator::new(old.graph(), graph);
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec!["fixtures/src/details.rs", "CONTRIBUTING.md"]);
+    let determinator_set = determinator.compute();
+
+    let reason_ids: Vec<_> = determinator_set.reasons.keys().copied().collect();
+    let mut affected_ids: Vec<_> = determinator_set
+        .affected_set
+        .package_ids(DependencyDirection::Forward)
+        .collect();
+    affected_ids.sort();
+    assert_eq!(
+        reason_ids, affected_ids,
+        "every affected package has a reason"
+    );
+
+    assert_eq!(
+        determinator_set.reasons[id("fixtures")],
+        DeterminatorReason::PathChanged(vec![ChangedPath {
+            path: "fixtures/src/details.rs".into(),
+            rule: None,
+        }]),
+    );
+    assert_eq!(
+        determinator_set.reasons[id("cargo-guppy")],
+        DeterminatorReason::PathChanged(vec![ChangedPath {
+            path: "CONTRIBUTING.md".into(),
+            rule: Some(RuleIndex::CustomPath(1)),
+        }]),
+    );
+    assert_eq!(
+        determinator_set.reasons[id("fixture-manager")],
+        DeterminatorReason::Affected {
+            from: id("fixtures"),
+            via: AffectedVia::CargoBuild,
+        },
+    );
+    assert_eq!(
+        determinator_set.chain(id("fixture-manager")),
+        vec![id("fixtures"), id("fixture-manager")],
+    );
+
+    let summary = determinator_set.to_summary(graph);
+    assert_eq!(
+        summary.affected["fixture-manager"],
+        ReasonSummary::Affected {
+            chain: vec!["fixtures".to_owned(), "fixture-manager".to_owned()],
+            via: AffectedVia::CargoBuild,
+        },
+    );
+
+    // If a path causes every package to be marked changed, every package has that path as its
+    // reason.
+    determinator.add_changed_paths(vec!["Cargo.toml"]);
+    let determinator_set = determinator.compute();
+    for reason in determinator_set.reasons.values() {
+        assert_eq!(
+            reason,
+            &DeterminatorReason::PathChanged(vec![ChangedPath {
+                path: "Cargo.toml".into(),
+                rule: Some(RuleIndex::DefaultPath(1)),
+            }]),
+        );
+    }
+}
+
+#[test]
+fn guppy_content_hashes() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let opts = read_options(new, "path-rules.toml");
+    let graph = new.graph();
+
+    let old_hashes = ContentHashes::parse(
+        r#"[files]
+        "fixtures/src/details.rs" = "1"
+        "guppy/README.md" = "2"
+        "target-spec/src/lib.rs" = "3"
+
+        [packages]
+        cargo-compare = "4"
+        guppy-cmdlib = "5"
+        "#,
+    )
+    .expect("old manifest parsed");
+    let new_hashes = ContentHashes::parse(
+        r#"[files]
+        "fixtures/src/details.rs" = "changed"
+        "guppy/README.md" = "changed"
+        "target-spec/src/lib.rs" = "3"
+
+        [packages]
+        cargo-compare = "4"
+        guppy-cmdlib = "changed"
+        "#,
+    )
+    .expect("new manifest parsed");
+
+    let mut determinator = Determinator::new(old.graph(), graph);
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_hashes(&old_hashes, &new_hashes);
+    let determinator_set = determinator.compute();
+
+    // The README is ignored by the path rules, and target-spec and cargo-compare are unchanged.
+    let expected_changed = graph
+        .resolve_workspace_names(vec!["fixtures", "guppy-cmdlib"])
+        .expect("workspace names resolved");
+    assert_eq!(determinator_set.path_changed_set, expected_changed);
+
+    let guppy_cmdlib = graph
+        .workspace()
+        .member_by_name("guppy-cmdlib")
+        .expect("workspace name is valid");
+    assert_eq!(
+        determinator_set.reasons[guppy_cmdlib.id()],
+        DeterminatorReason::HashChanged,
+    );
+}
+
+#[test]
+fn guppy_cache() {
+    let old = JsonFixture::metadata_guppy_78cb7e8();
+    let new = JsonFixture::metadata_guppy_869476c();
+    let opts = read_options(new, "path-rules.toml");
+    let dir = std::env::temp_dir().join(format!("determinator-cache-test-{}", std::process::id()));
+    let cache = DeterminatorCache::new(Utf8PathBuf::try_from(dir).expect("temp dir is UTF-8"));
+    let entries = || {
+        std::fs::read_dir(cache.dir())
+            .expect("cache dir exists")
+            .count()
+    };
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator
+        .set_rules(&opts)
+        .expect("rules set correctly")
+        .add_changed_paths(vec!["fixtures/src/details.rs", "README.md"]);
+    let expected = determinator.compute();
+
+    for _ in 0..2 {
+        let determinator_set = determinator.compute_cached(&cache).expect("cache written");
+        assert_eq!(determinator_set.path_changed_set, expected.path_changed_set);
+        assert_eq!(
+            determinator_set.summary_changed_set,
+            expected.summary_changed_set
+        );
+        assert_eq!(determinator_set.affected_set, expected.affected_set);
+        assert_eq!(determinator_set.reasons, expected.reasons);
+        assert_eq!(entries(), 1, "result cached once");
+    }
+
+    // A different set of changed paths is cached separately.
+    determinator.add_changed_paths(vec!["Cargo.toml"]);
+    let determinator_set = determinator.compute_cached(&cache).expect("cache written");
+    assert_eq!(
+        determinator_set.affected_set,
+        new.graph().resolve_workspace(),
+        "Cargo.toml causes everything to be affected"
+    );
+    assert_eq!(entries(), 2, "new result cached");
+
+    std::fs::remove_dir_all(cache.dir()).expect("removed cache dir");
+}
+
 #[test]
 fn guppy_package_rules() {
     // There are no dependency changes between the old and new fixtures, only file changes.
@@ -356,6 +542,18 @@ fn guppy_deps() {
         "some packages affected"
     );
 
+    // A lockfile change on its own isn't a global trigger: the same packages are marked changed.
+    determinator.add_changed_paths(vec!["Cargo.lock"]);
+    let determinator_set = determinator.compute();
+    assert!(
+        determinator_set.path_changed_set.is_empty(),
+        "Cargo.lock doesn't cause path changes"
+    );
+    assert_eq!(
+        determinator_set.affected_set, expected,
+        "only packages whose builds changed are affected"
+    );
+
     // Try setting fixture-manager as features-only. This should cause guppy's summaries feature to
     // always be enabled, which means that fixtures, guppy-cmdlib and guppy-benchmarks should be
     // added to the expected set.
@@ -386,6 +584,405 @@ fn guppy_deps() {
     );
 }
 
+#[test]
+fn guppy_feature_sets() {
+    let old = JsonFixture::metadata_guppy_78cb7e8();
+    let new = JsonFixture::metadata_guppy_869476c();
+    let opts = read_options(new, "path-rules.toml");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    let expected = determinator.compute();
+
+    // These feature sets don't pick up any dependency changes beyond those already seen in the
+    // standard builds, so nothing else is marked changed. Packages that don't define a feature are
+    // built with just the base features.
+    determinator
+        .add_feature_set(StandardFeatures::None, ["proptest010"])
+        .add_feature_set(StandardFeatures::Default, ["summaries"]);
+    let determinator_set = determinator.compute();
+    assert_eq!(
+        determinator_set.summary_changed_set, expected.summary_changed_set,
+        "no additional summary changes"
+    );
+}
+
+#[test]
+fn guppy_explain() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let opts = read_options(new, "path-rules.toml");
+    let graph = new.graph();
+    let id = |name: &str| {
+        graph
+            .workspace()
+            .member_by_name(name)
+            .expect("workspace name is valid")
+            .id()
+    };
+
+    let mut determinator = Determinator::new(old.graph(), graph);
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec![
+        "fixtures/src/details.rs",
+        "fixtures/README.md",
+        "CONTRIBUTING.md",
+    ]);
+
+    // Paths inside the package's directory are listed, even if a rule skipped them.
+    let explanation = determinator.explain("fixtures").expect("fixtures is valid");
+    assert!(explanation.is_affected(), "fixtures is affected");
+    assert_eq!(explanation.chain, vec![id("fixtures")]);
+    let paths: Vec<_> = explanation
+        .paths
+        .iter()
+        .map(|path| {
+            (
+                path.path.as_str(),
+                path.path_match,
+                path.marks_package,
+                path.rules.clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            (
+                "fixtures/src/details.rs",
+                PathMatch::AncestorMatched,
+                true,
+                vec![]
+            ),
+            (
+                "fixtures/README.md",
+                PathMatch::RuleMatched(RuleIndex::CustomPath(0)),
+                false,
+                vec![]
+            ),
+        ],
+    );
+    assert!(
+        explanation.upstream_changed.is_empty(),
+        "fixtures has no changed dependencies"
+    );
+
+    // Paths outside the package's directory are listed if a rule marked the package changed.
+    let explanation = determinator
+        .explain("cargo-guppy")
+        .expect("cargo-guppy is valid");
+    assert_eq!(explanation.paths.len(), 1, "one path marks cargo-guppy");
+    assert_eq!(explanation.paths[0].path, "CONTRIBUTING.md");
+    assert!(explanation.paths[0].marks_package, "path marks cargo-guppy");
+    assert_eq!(explanation.paths[0].rules, vec![RuleIndex::CustomPath(1)]);
+
+    // Affected packages list the changed packages they depend on.
+    let explanation = determinator
+        .explain("fixture-manager")
+        .expect("fixture-manager is valid");
+    assert_eq!(
+        explanation.reason,
+        Some(DeterminatorReason::Affected {
+            from: id("fixtures"),
+            via: AffectedVia::CargoBuild,
+        }),
+    );
+    assert_eq!(
+        explanation.chain,
+        vec![id("fixtures"), id("fixture-manager")]
+    );
+    assert!(explanation.paths.is_empty(), "no paths for fixture-manager");
+    assert_eq!(explanation.summary_changes, None, "builds unchanged");
+    let upstream: Vec<_> = explanation
+        .upstream_changed
+        .iter()
+        .map(|change| (change.package_id, change.dependency_path.clone()))
+        .collect();
+    assert_eq!(
+        upstream,
+        vec![(id("fixtures"), vec![id("fixture-manager"), id("fixtures")])],
+    );
+
+    // Packages that weren't affected have an empty explanation.
+    let explanation = determinator
+        .explain("target-spec")
+        .expect("target-spec is valid");
+    assert!(!explanation.is_affected(), "target-spec isn't affected");
+    assert!(explanation.chain.is_empty(), "no chain for target-spec");
+    assert!(explanation.paths.is_empty(), "no paths for target-spec");
+    assert!(
+        explanation.upstream_changed.is_empty(),
+        "no changed dependencies for target-spec"
+    );
+
+    determinator
+        .explain("no-such-package")
+        .expect_err("unknown package name");
+}
+
+#[test]
+fn guppy_shards() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let opts = read_options(new, "path-rules.toml");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec!["fixtures/src/details.rs", "CONTRIBUTING.md"]);
+    let determinator_set = determinator.compute();
+
+    let weights: ShardWeights = serde_json::from_str(
+        r#"{
+            "default-weight": 2,
+            "weights": { "guppy": 10, "fixtures": 6, "fixture-manager": 5 }
+        }"#,
+    )
+    .expect("weights parsed");
+    let shards = determinator_set.shards(2, &weights);
+    assert_eq!(
+        shards.shards,
+        vec![
+            Shard {
+                packages: vec!["cargo-guppy".to_owned(), "guppy".to_owned()],
+                weight: 12,
+            },
+            Shard {
+                packages: vec!["fixture-manager".to_owned(), "fixtures".to_owned()],
+                weight: 11,
+            },
+        ],
+    );
+
+    // With more shards than packages, some shards are empty.
+    let shards = determinator_set.shards(5, &ShardWeights::default());
+    assert_eq!(shards.shards.len(), 5, "every shard is present");
+    let sizes: Vec<_> = shards
+        .shards
+        .iter()
+        .map(|shard| shard.packages.len())
+        .collect();
+    assert_eq!(sizes, vec![1, 1, 1, 1, 0]);
+}
+
+#[test]
+fn guppy_scoped_rules() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let rules = DeterminatorRules::parse(
+        r#"
+        [[path-rule]]
+        globs = [".cargo/config.toml"]
+
+        [path-rule.mark-changed]
+        names = ["target-*"]
+        paths = ["internal-tools/proptest-*"]
+        # guppy-cmdlib is a workspace package and criterion is a third-party one.
+        depends-on = ["guppy-cmdlib", "criterion", "no-such-dependency"]
+        exclude = ["fixture-manager"]
+        "#,
+    )
+    .expect("rules parsed");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&rules).expect("rules set correctly");
+
+    // This rule overrides the default rule that marks everything changed.
+    determinator.add_changed_paths(vec![".cargo/config.toml"]);
+    let determinator_set = determinator.compute();
+    let expected = new
+        .graph()
+        .resolve_workspace_names(vec![
+            "cargo-compare",
+            "cargo-guppy",
+            "guppy-benchmarks",
+            "guppy-cmdlib",
+            "proptest-ext",
+            "target-spec",
+        ])
+        .expect("workspace names resolved");
+    assert_eq!(
+        determinator_set.path_changed_set, expected,
+        "selected packages marked changed"
+    );
+
+    // Names without glob metacharacters must be workspace packages.
+    let rules = DeterminatorRules::parse(
+        r#"
+        [[path-rule]]
+        globs = ["deny.toml"]
+        mark-changed = { names = ["no-such-package"] }
+        "#,
+    )
+    .expect("rules parsed");
+    let err = determinator
+        .set_rules(&rules)
+        .expect_err("unknown package name");
+    assert_eq!(err.rule_index(), RuleIndex::CustomPath(0));
+}
+
+#[test]
+fn guppy_build_scripts() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let graph = new.graph();
+    let id = |name: &str| {
+        graph
+            .workspace()
+            .member_by_name(name)
+            .expect("workspace name is valid")
+            .id()
+    };
+
+    let mut build_script_paths = BuildScriptPaths::parse(
+        r#"
+        [packages]
+        guppy-cmdlib = ["fixtures/src/details.rs"]
+        "#,
+    )
+    .expect("build script paths parsed");
+    // Relative paths are relative to the package, and paths outside the workspace are ignored.
+    build_script_paths
+        .add_build_output(
+            graph,
+            "target-spec",
+            "cargo:rustc-cfg=foo\n\
+             cargo:rerun-if-changed=/home/rain/dev/cargo-guppy/proto/spec.toml\n\
+             cargo:rerun-if-changed=../shared\n\
+             cargo:rerun-if-changed=/etc/hosts\n",
+        )
+        .expect("target-spec is valid");
+    assert_eq!(
+        build_script_paths.packages["target-spec"],
+        ["proto/spec.toml", "shared"]
+            .into_iter()
+            .map(Utf8PathBuf::from)
+            .collect(),
+    );
+
+    let mut determinator = Determinator::new(old.graph(), graph);
+    determinator.set_build_script_paths(&build_script_paths);
+
+    // Paths matched only through build scripts don't cause everything to be rebuilt.
+    let mut matches = vec![];
+    assert_eq!(
+        determinator.match_path("shared/codegen/mod.rs", |id| matches.push(id)),
+        PathMatch::RuleMatched(RuleIndex::BuildScript),
+    );
+    assert_eq!(matches, vec![id("target-spec")]);
+
+    // Other matching continues after build scripts.
+    let mut matches = vec![];
+    assert_eq!(
+        determinator.match_path("fixtures/src/details.rs", |id| matches.push(id)),
+        PathMatch::AncestorMatched,
+    );
+    assert_eq!(matches, vec![id("guppy-cmdlib"), id("fixtures")]);
+
+    determinator.add_changed_paths(vec!["proto/spec.toml"]);
+    let determinator_set = determinator.compute();
+    assert_eq!(
+        determinator_set.path_changed_set,
+        graph
+            .resolve_workspace_names(["target-spec"])
+            .expect("workspace names resolved"),
+    );
+    assert_eq!(
+        determinator_set.reasons[id("target-spec")],
+        DeterminatorReason::PathChanged(vec![ChangedPath {
+            path: "proto/spec.toml".into(),
+            rule: Some(RuleIndex::BuildScript),
+        }]),
+    );
+}
+
+#[test]
+fn guppy_workspace_manifest() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let rules = DeterminatorRules::parse(
+        r#"
+        [workspace-manifest]
+        metadata = ["guppy"]
+        "#,
+    )
+    .expect("rules parsed");
+
+    let old_manifest = r#"
+        [workspace]
+        members = ["guppy", "tools/determinator"]
+
+        [workspace.metadata.release]
+        shared-version = false
+
+        [profile.release]
+        debug = true
+    "#;
+    let members_changed = old_manifest.replace(
+        r#"["guppy", "tools/determinator"]"#,
+        r#"["guppy", "tools/cargo-determinator", "tools/determinator"]"#,
+    );
+    let metadata_changed = old_manifest.replace("shared-version = false", "shared-version = true");
+    let profiles_changed = old_manifest.replace("debug = true", "debug = false");
+
+    // Without the contents of the root Cargo.toml, the default rules rebuild everything.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&rules).expect("rules set correctly");
+    assert_eq!(
+        determinator.match_path("Cargo.toml", |_| panic!("no packages should be matched")),
+        PathMatch::RuleMatchedAll,
+    );
+
+    let cases = [
+        (
+            &members_changed,
+            PathMatch::RuleMatched(RuleIndex::WorkspaceManifest(ManifestSection::Members)),
+            vec![],
+        ),
+        (
+            &metadata_changed,
+            PathMatch::RuleMatched(RuleIndex::WorkspaceManifest(ManifestSection::Metadata)),
+            vec!["guppy"],
+        ),
+        (&profiles_changed, PathMatch::RuleMatchedAll, vec![]),
+    ];
+    for (new_manifest, expected_match, expected_names) in cases {
+        let mut determinator = Determinator::new(old.graph(), new.graph());
+        determinator
+            .set_rules(&rules)
+            .expect("rules set correctly")
+            .set_workspace_manifests(old_manifest, new_manifest);
+        let mut matches = vec![];
+        assert_eq!(
+            determinator.match_path("Cargo.toml", |id| matches.push(id)),
+            expected_match,
+            "path match for:\n{}",
+            new_manifest,
+        );
+        let expected_ids: Vec<_> = expected_names
+            .iter()
+            .map(|name| {
+                new.graph()
+                    .workspace()
+                    .member_by_name(name)
+                    .expect("workspace name is valid")
+                    .id()
+            })
+            .collect();
+        assert_eq!(matches, expected_ids, "matches for:\n{}", new_manifest);
+    }
+
+    // A member list edit doesn't cause anything to be marked changed through paths.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator
+        .set_workspace_manifests(old_manifest, &members_changed)
+        .add_changed_paths(vec!["Cargo.toml"]);
+    let determinator_set = determinator.compute();
+    assert!(
+        determinator_set.path_changed_set.is_empty(),
+        "nothing marked changed through paths"
+    );
+}
+
 #[test]
 fn guppy_match_paths() {
     let old = JsonFixture::metadata_guppy_869476c();
@@ -439,10 +1036,329 @@ fn guppy_match_paths() {
     }
 }
 
+#[test]
+fn guppy_match_paths_excludes() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let opts = DeterminatorRules::parse(
+        r#"[[path-rule]]
+        globs = ["!guppy/src/graph/**", "guppy/**", "!**/*.md"]
+        mark-changed = ["cargo-guppy"]
+        "#,
+    )
+    .expect("rules parsed");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator
+        .set_rules(&opts)
+        .expect("options set correctly");
+
+    // Exclusions take precedence regardless of their position in the list.
+    let expected = vec![
+        (
+            "guppy/src/lib.rs",
+            PathMatch::RuleMatched(RuleIndex::CustomPath(0)),
+        ),
+        ("guppy/src/graph/mod.rs", PathMatch::AncestorMatched),
+        (
+            "guppy/README.md",
+            PathMatch::RuleMatched(RuleIndex::DefaultPath(4)),
+        ),
+        ("cargo-guppy/src/main.rs", PathMatch::AncestorMatched),
+    ];
+    for (path, m) in expected {
+        assert_eq!(
+            determinator.match_path(path, |_| {}),
+            m,
+            "expected rule match for {}",
+            path
+        );
+    }
+
+    // A rule with only exclusions can't match anything.
+    let opts = DeterminatorRules::parse(
+        r#"[[path-rule]]
+        globs = ["!docs/api/**"]
+        mark-changed = []
+        "#,
+    )
+    .expect("rules parsed");
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    let err = determinator
+        .set_rules(&opts)
+        .expect_err("rule with only exclusions is rejected");
+    assert_eq!(err.rule_index(), RuleIndex::CustomPath(0));
+}
+
+/// Marks `<name>` changed for `protos/<name>.proto`, and everything changed for
+/// `rust-toolchain`.
+#[derive(Debug)]
+struct ProtoRule {
+    post_rule: DeterminatorPostRule,
+}
+
+impl CustomRule for ProtoRule {
+    fn match_path<'g>(
+        &self,
+        path: &Utf8Path,
+        _old: &PackageGraph,
+        new: &'g PackageGraph,
+    ) -> CustomRuleDecision<'g> {
+        if path == "rust-toolchain" {
+            return CustomRuleDecision::MarkAll;
+        }
+        if !path.starts_with("protos") || path.extension() != Some("proto") {
+            return CustomRuleDecision::NoMatch;
+        }
+        let packages = path
+            .file_stem()
+            .and_then(|name| new.workspace().member_by_name(name).ok())
+            .into_iter()
+            .collect();
+        CustomRuleDecision::Mark {
+            packages,
+            post_rule: self.post_rule,
+        }
+    }
+}
+
+#[test]
+fn guppy_custom_rules() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let opts = DeterminatorRules::parse(
+        r#"[[path-rule]]
+        globs = ["protos/**"]
+        mark-changed = ["cargo-guppy"]
+        "#,
+    )
+    .expect("rules parsed");
+    let graph = new.graph();
+    let id = |name: &str| {
+        graph
+            .workspace()
+            .member_by_name(name)
+            .expect("workspace name is valid")
+            .id()
+    };
+
+    let skip = ProtoRule {
+        post_rule: DeterminatorPostRule::Skip,
+    };
+    let mut determinator = Determinator::new(old.graph(), graph);
+    determinator
+        .set_rules(&opts)
+        .expect("options set correctly")
+        .add_custom_rule(&skip);
+
+    // Custom rules are applied before rules from TOML.
+    let expected = vec![
+        (
+            "protos/guppy.proto",
+            PathMatch::RuleMatched(RuleIndex::Plugin(0)),
+        ),
+        (
+            "protos/README.md",
+            PathMatch::RuleMatched(RuleIndex::CustomPath(0)),
+        ),
+        ("rust-toolchain", PathMatch::RuleMatchedAll),
+        ("Cargo.toml", PathMatch::RuleMatchedAll),
+        ("guppy/src/lib.rs", PathMatch::AncestorMatched),
+    ];
+    for (path, m) in expected {
+        assert_eq!(
+            determinator.match_path(path, |_| {}),
+            m,
+            "expected rule match for {}",
+            path
+        );
+    }
+
+    determinator.add_changed_paths(vec!["protos/guppy.proto"]);
+    let determinator_set = determinator.compute();
+    assert_eq!(
+        determinator_set.reasons[id("guppy")],
+        DeterminatorReason::PathChanged(vec![ChangedPath {
+            path: "protos/guppy.proto".into(),
+            rule: Some(RuleIndex::Plugin(0)),
+        }]),
+    );
+    assert!(
+        !determinator_set
+            .path_changed_set
+            .contains(id("cargo-guppy"))
+            .unwrap(),
+        "TOML rules skipped"
+    );
+
+    // With fallthrough, TOML rules are applied as well.
+    let fallthrough = ProtoRule {
+        post_rule: DeterminatorPostRule::Fallthrough,
+    };
+    let mut determinator = Determinator::new(old.graph(), graph);
+    determinator
+        .set_rules(&opts)
+        .expect("options set correctly")
+        .add_custom_rule(&fallthrough)
+        .add_changed_paths(vec!["protos/guppy.proto"]);
+    let determinator_set = determinator.compute();
+    let mut changed: Vec<_> = determinator_set
+        .path_changed_set
+        .package_ids(DependencyDirection::Forward)
+        .collect();
+    changed.sort();
+    let mut expected = vec![id("guppy"), id("cargo-guppy")];
+    expected.sort();
+    assert_eq!(changed, expected, "custom rule and TOML rule both applied");
+}
+
+#[test]
+fn guppy_package_metadata_rules() {
+    let old = JsonFixture::metadata_guppy_c9b4f76();
+    let with_metadata = |metadata: &str| {
+        let json = old.json().replace(
+            r#""manifest_path":"/home/rain/dev/cargo-guppy/fixtures/Cargo.toml","metadata":null"#,
+            &format!(
+                r#""manifest_path":"/home/rain/dev/cargo-guppy/fixtures/Cargo.toml","metadata":{}"#,
+                metadata
+            ),
+        );
+        CargoMetadata::parse_json(json)
+            .expect("metadata parsed")
+            .build_graph()
+            .expect("graph built")
+    };
+    let new =
+        with_metadata(r#"{"determinator":{"globs":["testcases/**","!testcases/README.md"]}}"#);
+    let fixtures_id = new
+        .workspace()
+        .member_by_name("fixtures")
+        .expect("fixtures is a workspace member")
+        .id();
+
+    let mut determinator = Determinator::new(old.graph(), &new);
+    let mut matched = vec![];
+    assert_eq!(
+        determinator.match_path("testcases/foo.json", |id| matched.push(id)),
+        PathMatch::RuleMatched(RuleIndex::PackageMetadata(0)),
+    );
+    assert_eq!(
+        matched,
+        vec![fixtures_id],
+        "declaring package marked changed"
+    );
+    assert_eq!(
+        determinator.match_path("testcases/README.md", |_| {}),
+        PathMatch::RuleMatched(RuleIndex::DefaultPath(4)),
+        "exclusions in package metadata are respected"
+    );
+
+    let opts = DeterminatorRules::parse("use-package-metadata = false").expect("rules parsed");
+    determinator.set_rules(&opts).expect("rules set correctly");
+    assert_eq!(
+        determinator.match_path("testcases/foo.json", |_| {}),
+        PathMatch::NoMatches,
+        "package metadata rules disabled"
+    );
+
+    // Package metadata rules aren't part of the graph fingerprint, but changing them is a cache
+    // miss.
+    let dir = std::env::temp_dir().join(format!(
+        "determinator-metadata-cache-test-{}",
+        std::process::id()
+    ));
+    let cache = DeterminatorCache::new(Utf8PathBuf::try_from(dir).expect("temp dir is UTF-8"));
+    let other = with_metadata(r#"{"determinator":{"globs":["other/**"]}}"#);
+    for (new, expected_entries) in [(&new, 1), (&new, 1), (&other, 2)] {
+        let mut determinator = Determinator::new(old.graph(), new);
+        determinator.add_changed_paths(vec!["testcases/foo.json"]);
+        let determinator_set = determinator.compute_cached(&cache).expect("cache written");
+        let expected = determinator.compute();
+        assert_eq!(determinator_set.path_changed_set, expected.path_changed_set);
+        assert_eq!(determinator_set.affected_set, expected.affected_set);
+        let entries = std::fs::read_dir(cache.dir())
+            .expect("cache dir exists")
+            .count();
+        assert_eq!(entries, expected_entries);
+    }
+    std::fs::remove_dir_all(cache.dir()).expect("removed cache dir");
+
+    // Invalid metadata is reported along with the package that declared it.
+    let new = with_metadata(r#"{"determinator":{"globz":["testcases/**"]}}"#);
+    let mut determinator = Determinator::new(old.graph(), &new);
+    let err = determinator
+        .set_rules(&DeterminatorRules::default())
+        .expect_err("invalid metadata is rejected");
+    assert_eq!(err.rule_index(), RuleIndex::PackageMetadata(0));
+    assert_eq!(err.package_name(), Some("fixtures"));
+}
+
 static GIT_MATCH_PATHS_DIFF: &str =
     include_str!("../../../fixtures/determinator-paths/git-diff.out");
 
 // Test matching paths against this repository.
+#[test]
+fn path_dependencies_outside_workspace() {
+    // This workspace has path dependencies on ../quote and ../walkdir.
+    let fixture = JsonFixture::metadata2();
+    let graph = fixture.graph();
+    let dependents = |name: &str| -> Vec<_> {
+        let package = graph
+            .packages()
+            .find(|package| package.name() == name && package.source().is_path())
+            .expect("path dependency found");
+        let mut ids: Vec<_> = graph
+            .query_reverse([package.id()])
+            .expect("valid package ID")
+            .resolve()
+            .intersection(&graph.resolve_workspace())
+            .package_ids(DependencyDirection::Forward)
+            .collect();
+        ids.sort();
+        ids
+    };
+    let walkdir_dependents = dependents("walkdir");
+    assert!(
+        !walkdir_dependents.is_empty(),
+        "workspace packages depend on ../walkdir"
+    );
+
+    let determinator = Determinator::new(graph, graph);
+    let mut matches = vec![];
+    assert_eq!(
+        determinator.match_path("../walkdir/src/lib.rs", |id| matches.push(id)),
+        PathMatch::AncestorMatched,
+    );
+    matches.sort();
+    assert_eq!(matches, walkdir_dependents);
+
+    // Paths outside the workspace that aren't in a path dependency cause everything to be
+    // rebuilt, just like paths inside the workspace that aren't in a package.
+    assert_eq!(
+        determinator.match_path("../other/src/lib.rs", |_| {}),
+        PathMatch::NoMatches,
+    );
+
+    let mut determinator = Determinator::new(graph, graph);
+    determinator.add_changed_paths(vec!["../walkdir/src/lib.rs"]);
+    let determinator_set = determinator.compute();
+    let mut path_changed: Vec<_> = determinator_set
+        .path_changed_set
+        .package_ids(DependencyDirection::Forward)
+        .collect();
+    path_changed.sort();
+    assert_eq!(path_changed, walkdir_dependents);
+    for id in path_changed {
+        assert_eq!(
+            determinator_set.reasons[id],
+            DeterminatorReason::PathChanged(vec![ChangedPath {
+                path: "../walkdir/src/lib.rs".into(),
+                rule: Some(RuleIndex::PathDependency),
+            }]),
+        );
+    }
+}
+
 #[test]
 fn git_match_paths() {
     let paths = Utf8Paths0::new(GIT_MATCH_PATHS_DIFF);
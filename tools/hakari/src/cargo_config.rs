@@ -0,0 +1,189 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Support for reading alternate registries from Cargo configuration files.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{collections::BTreeMap, error, fmt, io};
+use toml_edit::Document;
+
+/// Reads alternate registries, as a map of name to index URL, from the Cargo configuration files
+/// that apply to `dir`.
+///
+/// Configuration files are discovered the same way Cargo does: `.cargo/config.toml` (or
+/// `.cargo/config`) in `dir` and each of its ancestors, followed by `$CARGO_HOME/config.toml`. If a
+/// registry is declared in more than one file, the file closest to `dir` takes precedence.
+pub(crate) fn cargo_config_registries(
+    dir: &Utf8Path,
+) -> Result<BTreeMap<String, String>, CargoConfigError> {
+    let cargo_home = home::cargo_home()
+        .ok()
+        .and_then(|path| Utf8PathBuf::try_from(path).ok());
+    let config_dirs = dir
+        .ancestors()
+        .map(|ancestor| ancestor.join(".cargo"))
+        .chain(cargo_home);
+
+    let mut registries = BTreeMap::new();
+    let mut seen = Vec::new();
+    for config_dir in config_dirs {
+        // $CARGO_HOME is usually ~/.cargo, which may have already been visited.
+        if seen.contains(&config_dir) {
+            continue;
+        }
+        // Like Cargo, prefer the file without an extension if both exist.
+        for file_name in ["config", "config.toml"] {
+            let config_path = config_dir.join(file_name);
+            let contents = match std::fs::read_to_string(&config_path) {
+                Ok(contents) => contents,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(CargoConfigError::Io { config_path, error }),
+            };
+            for (name, index) in parse_registries(&config_path, &contents)? {
+                registries.entry(name).or_insert(index);
+            }
+            break;
+        }
+        seen.push(config_dir);
+    }
+
+    Ok(registries)
+}
+
+fn parse_registries(
+    config_path: &Utf8Path,
+    contents: &str,
+) -> Result<Vec<(String, String)>, CargoConfigError> {
+    let doc: Document = contents.parse().map_err(|error| CargoConfigError::Parse {
+        config_path: config_path.to_owned(),
+        error: Box::new(error),
+    })?;
+    let registries = match doc.get("registries").and_then(|item| item.as_table_like()) {
+        Some(registries) => registries,
+        None => return Ok(vec![]),
+    };
+
+    let mut out = Vec::new();
+    for (name, item) in registries.iter() {
+        // Registries may only have a token or credential provider configured, in which case the
+        // index is declared elsewhere.
+        let index = match item.as_table_like().and_then(|table| table.get("index")) {
+            Some(index) => index,
+            None => continue,
+        };
+        match index.as_str() {
+            Some(index) => out.push((name.to_owned(), index.to_owned())),
+            None => {
+                return Err(CargoConfigError::InvalidIndex {
+                    config_path: config_path.to_owned(),
+                    name: name.to_owned(),
+                })
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// An error that can occur while reading alternate registries from Cargo configuration files.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CargoConfigError {
+    /// A configuration file could not be read.
+    Io {
+        /// The path that was attempted to be read.
+        config_path: Utf8PathBuf,
+
+        /// The error that occurred.
+        error: io::Error,
+    },
+
+    /// A configuration file could not be parsed as TOML.
+    Parse {
+        /// The path that was read.
+        config_path: Utf8PathBuf,
+
+        /// The error that occurred.
+        error: Box<dyn error::Error + Send + Sync>,
+    },
+
+    /// The index of a registry was not a string.
+    InvalidIndex {
+        /// The path that was read.
+        config_path: Utf8PathBuf,
+
+        /// The name of the registry.
+        name: String,
+    },
+}
+
+impl fmt::Display for CargoConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CargoConfigError::Io { config_path, .. } => {
+                write!(f, "error while reading path '{}'", config_path)
+            }
+            CargoConfigError::Parse { config_path, .. } => {
+                write!(f, "error while parsing path '{}'", config_path)
+            }
+            CargoConfigError::InvalidIndex { config_path, name } => {
+                write!(
+                    f,
+                    "in '{}', index for registry '{}' is not a string",
+                    config_path, name
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for CargoConfigError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CargoConfigError::Io { error, .. } => Some(error),
+            CargoConfigError::Parse { error, .. } => Some(&**error),
+            CargoConfigError::InvalidIndex { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cargo_config() {
+        let config_path = Utf8Path::new(".cargo/config.toml");
+        let contents = r#"
+        [build]
+        target-dir = "target"
+
+        [registries]
+        my-registry = { index = "https://github.com/fakeorg/crates.io-index" }
+        token-only = { token = "abc" }
+
+        [registries.your-registry]
+        index = "sparse+https://foobar/index/"
+        "#;
+        assert_eq!(
+            parse_registries(config_path, contents).unwrap(),
+            [
+                (
+                    "my-registry".to_owned(),
+                    "https://github.com/fakeorg/crates.io-index".to_owned()
+                ),
+                (
+                    "your-registry".to_owned(),
+                    "sparse+https://foobar/index/".to_owned()
+                ),
+            ]
+        );
+
+        assert!(parse_registries(config_path, "[build]\njobs = 4\n")
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            parse_registries(config_path, "[registries.foo]\nindex = 1\n"),
+            Err(CargoConfigError::InvalidIndex { name, .. }) if name == "foo"
+        ));
+    }
+}
@@ -4,7 +4,9 @@
 use atomicwrites::{AtomicFile, OverwriteBehavior};
 use camino::{Utf8Path, Utf8PathBuf};
 use diffy::Patch;
-use std::{error, fmt, io};
+use guppy::graph::cargo::CargoResolverVersion;
+use std::{collections::BTreeSet, error, fmt, io};
+use toml_edit::{Document, Item, TableLike};
 
 /// Support for maintaining `Cargo.toml` files that unify features in a workspace.
 ///
@@ -36,6 +38,11 @@ use std::{error, fmt, io};
 /// lines is managed by this struct, and changes to it may not be preserved. The part of the file
 /// outside this section can be edited and its contents will be preserved.
 ///
+/// For example, custom `[package.metadata]`, `[lints]` or `[features]` tables can be kept either
+/// before `BEGIN HAKARI SECTION` or after `END HAKARI SECTION`. Note that TOML doesn't allow a
+/// table to be defined twice, so tables that are generated (such as `[dependencies]`) can't be
+/// added outside the generated section.
+///
 /// # Setting up a new package
 ///
 /// For Hakari to manage a package, a bit of initial prep work must be done:
@@ -168,6 +175,33 @@ impl HakariCargoToml {
         &self.contents[self.start_offset..self.end_offset]
     }
 
+    /// Returns the keys of the table at `table_path` that are declared outside the generated
+    /// section, in sorted order.
+    pub(crate) fn non_generated_keys(
+        &self,
+        table_path: &[&str],
+    ) -> Result<BTreeSet<String>, CargoTomlError> {
+        let contents = format!(
+            "{}{}",
+            &self.contents[..self.start_offset],
+            &self.contents[self.end_offset..]
+        );
+        let doc: Document = contents.parse().map_err(|error| CargoTomlError::Parse {
+            toml_path: self.toml_path.clone(),
+            error: Box::new(error),
+        })?;
+        let mut table = Some(doc.as_table() as &dyn TableLike);
+        for key in table_path {
+            table = table
+                .and_then(|table| table.get(key))
+                .and_then(|item| item.as_table_like());
+        }
+        Ok(table
+            .into_iter()
+            .flat_map(|table| table.iter().map(|(key, _)| key.to_owned()))
+            .collect())
+    }
+
     /// Returns true if the contents on disk are different from the provided TOML output.
     pub fn is_changed(&self, toml: &str) -> bool {
         self.generated_contents() != toml
@@ -236,6 +270,68 @@ impl HakariCargoToml {
     }
 }
 
+/// Reads the version of the Cargo resolver that applies to the workspace at `workspace_root`.
+pub(crate) fn read_workspace_resolver(
+    workspace_root: &Utf8Path,
+) -> Result<CargoResolverVersion, CargoTomlError> {
+    let toml_path = workspace_root.join("Cargo.toml");
+    let contents = match std::fs::read_to_string(&toml_path) {
+        Ok(contents) => contents,
+        Err(error) => return Err(CargoTomlError::Io { toml_path, error }),
+    };
+    workspace_resolver(&toml_path, &contents)
+}
+
+/// Reads the version of the Cargo resolver that applies to a workspace from the contents of its
+/// root `Cargo.toml`, the same way Cargo does.
+///
+/// The resolver is read from `workspace.resolver` or `package.resolver`. If neither is set, it is
+/// implied by the edition of the root package: edition 2024 implies version 3, and edition 2021
+/// implies version 2. Otherwise, version 1 is used.
+pub(crate) fn workspace_resolver(
+    toml_path: &Utf8Path,
+    contents: &str,
+) -> Result<CargoResolverVersion, CargoTomlError> {
+    let doc: Document = contents.parse().map_err(|error| CargoTomlError::Parse {
+        toml_path: toml_path.to_owned(),
+        error: Box::new(error),
+    })?;
+    let get = |table: &str, key: &str| -> Option<&Item> {
+        doc.get(table)
+            .and_then(|item| item.as_table_like())
+            .and_then(|table| table.get(key))
+    };
+
+    if let Some(resolver) = get("workspace", "resolver").or_else(|| get("package", "resolver")) {
+        return match resolver.as_str() {
+            Some("1") => Ok(CargoResolverVersion::V1),
+            Some("2") => Ok(CargoResolverVersion::V2),
+            Some("3") => Ok(CargoResolverVersion::V3),
+            _ => Err(CargoTomlError::InvalidResolver {
+                toml_path: toml_path.to_owned(),
+                value: resolver.to_string().trim().to_owned(),
+            }),
+        };
+    }
+
+    // The edition may be inherited from the workspace, as `edition.workspace = true`.
+    let edition = get("package", "edition").and_then(|edition| match edition.as_str() {
+        Some(edition) => Some(edition),
+        None => doc
+            .get("workspace")
+            .and_then(|item| item.as_table_like())
+            .and_then(|workspace| workspace.get("package"))
+            .and_then(|item| item.as_table_like())
+            .and_then(|package| package.get("edition"))
+            .and_then(|edition| edition.as_str()),
+    });
+    match edition {
+        Some("2024") => Ok(CargoResolverVersion::V3),
+        Some("2021") => Ok(CargoResolverVersion::V2),
+        _ => Ok(CargoResolverVersion::V1),
+    }
+}
+
 /// An error that can occur while reading or writing a `Cargo.toml` file.
 #[derive(Debug)]
 #[non_exhaustive]
@@ -255,6 +351,24 @@ pub enum CargoTomlError {
         /// The path that was read.
         toml_path: Utf8PathBuf,
     },
+
+    /// The `Cargo.toml` could not be parsed as TOML.
+    Parse {
+        /// The path that was read.
+        toml_path: Utf8PathBuf,
+
+        /// The error that occurred.
+        error: Box<dyn error::Error + Send + Sync>,
+    },
+
+    /// The `resolver` specified in the `Cargo.toml` is not a known resolver version.
+    InvalidResolver {
+        /// The path that was read.
+        toml_path: Utf8PathBuf,
+
+        /// The value of `resolver`.
+        value: String,
+    },
 }
 
 impl fmt::Display for CargoTomlError {
@@ -273,6 +387,12 @@ impl fmt::Display for CargoTomlError {
                     toml_path
                 )
             }
+            CargoTomlError::Parse { toml_path, .. } => {
+                write!(f, "error while parsing path '{}'", toml_path)
+            }
+            CargoTomlError::InvalidResolver { toml_path, value } => {
+                write!(f, "in '{}', unknown resolver version {}", toml_path, value)
+            }
         }
     }
 }
@@ -281,7 +401,100 @@ impl error::Error for CargoTomlError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             CargoTomlError::Io { error, .. } => Some(error),
-            CargoTomlError::GeneratedSectionNotFound { .. } => None,
+            CargoTomlError::Parse { error, .. } => Some(&**error),
+            CargoTomlError::GeneratedSectionNotFound { .. }
+            | CargoTomlError::InvalidResolver { .. } => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preserve_outside_generated_section() {
+        static CONTENTS: &str = r#"[package]
+name = "workspace-hack"
+
+[package.metadata.custom]
+key = "value"
+
+### BEGIN HAKARI SECTION
+[dependencies]
+old = { version = "1" }
+
+### END HAKARI SECTION
+
+[lints.rust]
+unsafe_code = "forbid"
+"#;
+
+        let toml = HakariCargoToml::new_in_memory("Cargo.toml", CONTENTS.to_owned())
+            .expect("generated section found");
+        assert_eq!(
+            toml.generated_contents(),
+            "[dependencies]\nold = { version = \"1\" }\n\n"
+        );
+
+        let new_section = "[dependencies]\nnew = { version = \"2\" }\n\n";
+        assert!(toml.is_changed(new_section));
+        let mut out = String::new();
+        toml.write_to_fmt(new_section, &mut out)
+            .expect("writing to a string succeeded");
+        assert_eq!(
+            out,
+            CONTENTS.replace("old = { version = \"1\" }", "new = { version = \"2\" }"),
+            "only the generated section is replaced"
+        );
+    }
+
+    #[test]
+    fn parse_workspace_resolver() {
+        let resolver = |contents: &str| workspace_resolver(Utf8Path::new("Cargo.toml"), contents);
+
+        for (contents, expected) in [
+            // Explicitly set resolvers.
+            ("[workspace]\nresolver = \"3\"\n", CargoResolverVersion::V3),
+            (
+                "[package]\nname = \"foo\"\nedition = \"2024\"\nresolver = \"1\"\n",
+                CargoResolverVersion::V1,
+            ),
+            // Resolvers implied by the edition, which may be inherited from the workspace.
+            (
+                "[package]\nname = \"foo\"\nedition = \"2024\"\n",
+                CargoResolverVersion::V3,
+            ),
+            (
+                "[workspace.package]\nedition = \"2021\"\n\n\
+                 [package]\nname = \"foo\"\nedition.workspace = true\n",
+                CargoResolverVersion::V2,
+            ),
+            (
+                "[package]\nname = \"foo\"\nedition = \"2018\"\n",
+                CargoResolverVersion::V1,
+            ),
+            // Virtual workspaces without a resolver use version 1.
+            (
+                "[workspace]\nmembers = [\"foo\"]\n",
+                CargoResolverVersion::V1,
+            ),
+        ] {
+            assert_eq!(
+                resolver(contents).expect("resolver is valid"),
+                expected,
+                "for contents:\n{}",
+                contents
+            );
+        }
+
+        assert!(matches!(
+            resolver("[workspace]\nresolver = \"4\"\n"),
+            Err(CargoTomlError::InvalidResolver { value, .. }) if value == "\"4\""
+        ));
+        assert!(matches!(
+            resolver("[workspace"),
+            Err(CargoTomlError::Parse { .. })
+        ));
+    }
+}
@@ -1,11 +1,15 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::cli_ops::workspace_ops::{WorkspaceOp, WorkspaceOps};
+use crate::{
+    cli_ops::workspace_ops::{WorkspaceOp, WorkspaceOps},
+    HakariCargoToml,
+};
 use camino::{Utf8Path, Utf8PathBuf};
-use guppy::graph::PackageGraph;
+use guppy::graph::{BuildTargetId, PackageGraph};
 use include_dir::{include_dir, Dir, DirEntry};
-use std::{borrow::Cow, convert::TryInto, error, fmt, io};
+use std::{borrow::Cow, collections::BTreeMap, convert::TryInto, error, fmt, io};
+use toml_edit::Document;
 
 const CRATE_TEMPLATE_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/templates/crate");
 const CONFIG_TEMPLATE: &str = include_str!("../../templates/hakari.toml-in");
@@ -18,6 +22,10 @@ pub struct HakariInit<'g, 'a> {
     crate_path: &'a Utf8Path,
     config: Option<(&'a Utf8Path, &'a str)>,
     cargo_toml_comment: &'a str,
+    // Files read from a custom template directory, or None to use the built-in template.
+    template_files: Option<BTreeMap<Utf8PathBuf, Vec<u8>>>,
+    // The existing contents of the Cargo.toml, if an existing crate is being adopted.
+    adopt_manifest: Option<String>,
 }
 
 impl<'g, 'a> HakariInit<'g, 'a> {
@@ -79,6 +87,64 @@ impl<'g, 'a> HakariInit<'g, 'a> {
             crate_path,
             config: None,
             cargo_toml_comment: "",
+            template_files: None,
+            adopt_manifest: None,
+        })
+    }
+
+    /// Creates a new `HakariInit` that adopts an existing workspace package as the workspace-hack
+    /// package.
+    ///
+    /// The package must be a library, must not depend on other workspace packages, and must not
+    /// have any features. Its dependency tables are replaced with an empty Hakari section, and
+    /// any files from the template that aren't present in the package are added to it.
+    pub fn adopt(package_graph: &'g PackageGraph, package_name: &'a str) -> Result<Self, InitError>
+    where
+        'g: 'a,
+    {
+        let workspace = package_graph.workspace();
+        let package = workspace.member_by_name(package_name).map_err(|_| {
+            InitError::AdoptPackageNotFound {
+                package_name: package_name.to_owned(),
+            }
+        })?;
+        let invalid = |reason: String| InitError::AdoptInvalidPackage {
+            package_name: package_name.to_owned(),
+            reason,
+        };
+
+        if package.build_target(&BuildTargetId::Library).is_none() {
+            return Err(invalid("package does not have a library target".to_owned()));
+        }
+        if let Some(link) = package.direct_links().find(|link| link.to().in_workspace()) {
+            return Err(invalid(format!(
+                "package depends on workspace package {}",
+                link.to().name()
+            )));
+        }
+        if package.named_features().next().is_some() {
+            return Err(invalid("package has features".to_owned()));
+        }
+
+        let manifest_path = package.manifest_path();
+        let manifest = std::fs::read_to_string(manifest_path).map_err(|error| InitError::Io {
+            path: manifest_path.to_owned(),
+            error,
+        })?;
+        // Check that the manifest can be rewritten.
+        adopt_manifest_contents(&manifest).map_err(invalid)?;
+
+        Ok(Self {
+            package_graph,
+            package_name,
+            crate_path: package
+                .source()
+                .workspace_path()
+                .expect("package returned by workspace"),
+            config: None,
+            cargo_toml_comment: "",
+            template_files: None,
+            adopt_manifest: Some(manifest),
         })
     }
 
@@ -108,12 +174,44 @@ impl<'g, 'a> HakariInit<'g, 'a> {
         self
     }
 
+    /// Specifies a directory to use as the template for the workspace-hack package, instead of the
+    /// built-in template.
+    ///
+    /// Files in the directory are copied into the package. In files with the `.toml-in`
+    /// extension, `%PACKAGE_NAME%` and `%CARGO_TOML_COMMENT%` are substituted, and the extension
+    /// is changed to `.toml`. The directory must contain a `Cargo.toml-in` with `BEGIN HAKARI
+    /// SECTION` and `END HAKARI SECTION` markers.
+    pub fn set_template_dir(&mut self, dir: &Utf8Path) -> Result<&mut Self, InitError> {
+        let mut files = BTreeMap::new();
+        read_template_dir(dir, Utf8Path::new(""), &mut files)?;
+
+        let invalid = |reason: String| InitError::InvalidTemplate {
+            path: dir.to_owned(),
+            reason,
+        };
+        let cargo_toml = match files.get(Utf8Path::new("Cargo.toml-in")) {
+            Some(contents) => std::str::from_utf8(contents)
+                .map_err(|_| invalid("Cargo.toml-in is not valid UTF-8".to_owned()))?,
+            None => return Err(invalid("Cargo.toml-in not found".to_owned())),
+        };
+        let cargo_toml = self.process_toml_in(cargo_toml);
+        cargo_toml
+            .parse::<Document>()
+            .map_err(|err| invalid(format!("Cargo.toml-in is not valid TOML: {}", err)))?;
+        HakariCargoToml::new_in_memory(dir.join("Cargo.toml-in"), cargo_toml)
+            .map_err(|err| invalid(format!("invalid Cargo.toml-in: {}", err)))?;
+
+        self.template_files = Some(files);
+        Ok(self)
+    }
+
     /// Returns the workspace operations corresponding to this initialization.
     pub fn make_ops(&self) -> WorkspaceOps<'g, 'a> {
-        WorkspaceOps::new(
-            self.package_graph,
-            std::iter::once(self.make_new_crate_op()),
-        )
+        let op = match &self.adopt_manifest {
+            Some(manifest) => self.make_adopt_crate_op(manifest),
+            None => self.make_new_crate_op(),
+        };
+        WorkspaceOps::new(self.package_graph, std::iter::once(op))
     }
 
     // ---
@@ -121,55 +219,193 @@ impl<'g, 'a> HakariInit<'g, 'a> {
     // ---
 
     fn make_new_crate_op(&self) -> WorkspaceOp<'g, 'a> {
-        let files = CRATE_TEMPLATE_DIR
-            .find("**/*")
-            .expect("pattern **/* is valid")
-            .flat_map(|entry| {
-                match entry {
+        WorkspaceOp::NewCrate {
+            crate_path: self.crate_path,
+            files: self.make_crate_files(),
+            root_files: self.make_root_files(),
+        }
+    }
+
+    fn make_adopt_crate_op(&self, manifest: &str) -> WorkspaceOp<'g, 'a> {
+        let crate_dir = self.package_graph.workspace().root().join(self.crate_path);
+        let mut files: BTreeMap<_, _> = self
+            .make_crate_files()
+            .into_iter()
+            .filter(|(path, _)| {
+                path.as_ref() != Utf8Path::new("Cargo.toml") && !crate_dir.join(path).exists()
+            })
+            .collect();
+
+        let new_manifest =
+            adopt_manifest_contents(manifest).expect("manifest was checked in HakariInit::adopt");
+        if new_manifest != manifest {
+            files.insert(
+                Cow::Borrowed(Utf8Path::new("Cargo.toml")),
+                Cow::Owned(new_manifest.into_bytes()),
+            );
+        }
+
+        WorkspaceOp::AdoptCrate {
+            crate_path: self.crate_path,
+            files,
+            root_files: self.make_root_files(),
+        }
+    }
+
+    fn make_crate_files(&self) -> BTreeMap<Cow<'a, Utf8Path>, Cow<'a, [u8]>> {
+        match &self.template_files {
+            Some(template_files) => template_files
+                .iter()
+                .map(|(path, contents)| {
+                    self.process_template_file(
+                        Cow::Owned(path.clone()),
+                        Cow::Owned(contents.clone()),
+                    )
+                })
+                .collect(),
+            None => CRATE_TEMPLATE_DIR
+                .find("**/*")
+                .expect("pattern **/* is valid")
+                .flat_map(|entry| match entry {
                     DirEntry::File(file) => {
                         let path: &Utf8Path = file
                             .path()
                             .try_into()
                             .expect("embedded path is valid UTF-8");
-                        // .toml-in files need a bit of processing.
-                        if path.extension() == Some("toml-in") {
-                            let contents = file
-                                .contents_utf8()
-                                .expect("embedded .toml-in is valid UTF-8");
-                            let contents = contents.replace("%PACKAGE_NAME%", self.package_name);
-                            let contents =
-                                contents.replace("%CARGO_TOML_COMMENT%\n", self.cargo_toml_comment);
-                            Some((
-                                Cow::Owned(path.with_extension("toml")),
-                                Cow::Owned(contents.into_bytes()),
-                            ))
-                        } else {
-                            Some((Cow::Borrowed(path), Cow::Borrowed(file.contents())))
-                        }
+                        Some(self.process_template_file(
+                            Cow::Borrowed(path),
+                            Cow::Borrowed(file.contents()),
+                        ))
                     }
                     DirEntry::Dir(_) => None,
-                }
-            })
-            .collect();
+                })
+                .collect(),
+        }
+    }
 
-        let root_files = self
-            .config
+    fn make_root_files(&self) -> BTreeMap<Cow<'a, Utf8Path>, Cow<'a, [u8]>> {
+        self.config
             .into_iter()
             .map(|(path, comment)| {
                 let contents = CONFIG_TEMPLATE.replace("%PACKAGE_NAME%", self.package_name);
                 let contents = contents.replace("%CONFIG_COMMENT%\n", comment);
                 (Cow::Borrowed(path), Cow::Owned(contents.into_bytes()))
             })
-            .collect();
+            .collect()
+    }
 
-        WorkspaceOp::NewCrate {
-            crate_path: self.crate_path,
-            files,
-            root_files,
+    fn process_template_file(
+        &self,
+        path: Cow<'a, Utf8Path>,
+        contents: Cow<'a, [u8]>,
+    ) -> (Cow<'a, Utf8Path>, Cow<'a, [u8]>) {
+        // .toml-in files need a bit of processing.
+        if path.extension() == Some("toml-in") {
+            let contents = std::str::from_utf8(&contents).expect(".toml-in is valid UTF-8");
+            let contents = self.process_toml_in(contents);
+            (
+                Cow::Owned(path.with_extension("toml")),
+                Cow::Owned(contents.into_bytes()),
+            )
+        } else {
+            (path, contents)
         }
     }
+
+    fn process_toml_in(&self, contents: &str) -> String {
+        let contents = contents.replace("%PACKAGE_NAME%", self.package_name);
+        contents.replace("%CARGO_TOML_COMMENT%\n", self.cargo_toml_comment)
+    }
 }
 
+/// Reads all files in a template directory into `files`, keyed by their path relative to the
+/// template root.
+fn read_template_dir(
+    root: &Utf8Path,
+    rel_dir: &Utf8Path,
+    files: &mut BTreeMap<Utf8PathBuf, Vec<u8>>,
+) -> Result<(), InitError> {
+    let dir = if rel_dir.as_str().is_empty() {
+        root.to_owned()
+    } else {
+        root.join(rel_dir)
+    };
+    let io_err = |path: &Utf8Path| {
+        let path = path.to_owned();
+        move |error| InitError::Io { path, error }
+    };
+
+    for entry in dir.read_dir_utf8().map_err(io_err(&dir))? {
+        let entry = entry.map_err(io_err(&dir))?;
+        let rel_path = rel_dir.join(entry.file_name());
+        let file_type = entry.file_type().map_err(io_err(entry.path()))?;
+        if file_type.is_dir() {
+            read_template_dir(root, &rel_path, files)?;
+        } else {
+            let contents = std::fs::read(entry.path()).map_err(io_err(entry.path()))?;
+            if rel_path.extension() == Some("toml-in") && std::str::from_utf8(&contents).is_err() {
+                return Err(InitError::InvalidTemplate {
+                    path: entry.path().to_owned(),
+                    reason: "file is not valid UTF-8".to_owned(),
+                });
+            }
+            files.insert(rel_path, contents);
+        }
+    }
+    Ok(())
+}
+
+/// Rewrites the manifest of a package being adopted, removing its dependency tables and adding an
+/// empty Hakari section.
+///
+/// Manifests that already have a Hakari section are returned unchanged.
+fn adopt_manifest_contents(manifest: &str) -> Result<String, String> {
+    if HakariCargoToml::new_in_memory("Cargo.toml", manifest.to_owned()).is_ok() {
+        return Ok(manifest.to_owned());
+    }
+
+    let mut doc = manifest
+        .parse::<Document>()
+        .map_err(|err| format!("error parsing Cargo.toml: {}", err))?;
+    let table = doc.as_table_mut();
+    for key in DEPENDENCY_TABLES {
+        table.remove(key);
+    }
+    if let Some(target) = table.get_mut("target") {
+        let target = target
+            .as_table_like_mut()
+            .ok_or_else(|| "Cargo.toml has an invalid [target] table".to_owned())?;
+        let mut empty_platforms = vec![];
+        for (platform, item) in target.iter_mut() {
+            if let Some(platform_table) = item.as_table_like_mut() {
+                for key in DEPENDENCY_TABLES {
+                    platform_table.remove(key);
+                }
+                if platform_table.is_empty() {
+                    empty_platforms.push(platform.get().to_owned());
+                }
+            }
+        }
+        for platform in empty_platforms {
+            target.remove(&platform);
+        }
+        if target.is_empty() {
+            table.remove("target");
+        }
+    }
+
+    let mut contents = doc.to_string();
+    if !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(HakariCargoToml::BEGIN_SECTION);
+    contents.push_str(HakariCargoToml::END_SECTION);
+    Ok(contents)
+}
+
+/// Dependency tables managed by hakari.
+const DEPENDENCY_TABLES: [&str; 2] = ["dependencies", "build-dependencies"];
+
 /// An error that occurred while attempting to initialize `hakari`.
 #[derive(Debug)]
 #[non_exhaustive]
@@ -204,6 +440,30 @@ pub enum InitError {
         abs_path: Utf8PathBuf,
     },
 
+    /// The package to adopt was not found in the workspace.
+    AdoptPackageNotFound {
+        /// The name of the package.
+        package_name: String,
+    },
+
+    /// The package to adopt can't be used as a workspace-hack package.
+    AdoptInvalidPackage {
+        /// The name of the package.
+        package_name: String,
+
+        /// Why the package can't be used.
+        reason: String,
+    },
+
+    /// The template for the workspace-hack package is invalid.
+    InvalidTemplate {
+        /// The path to the template directory or file.
+        path: Utf8PathBuf,
+
+        /// Why the template is invalid.
+        reason: String,
+    },
+
     /// An IO error occurred while working with the given path.
     Io {
         /// The path.
@@ -243,6 +503,22 @@ impl fmt::Display for InitError {
             InitError::WorkspacePathExists { abs_path } => {
                 write!(f, "workspace path {} already exists", abs_path)
             }
+            InitError::AdoptPackageNotFound { package_name } => {
+                write!(f, "package {} not found in workspace", package_name)
+            }
+            InitError::AdoptInvalidPackage {
+                package_name,
+                reason,
+            } => {
+                write!(
+                    f,
+                    "package {} can't be adopted as the workspace-hack: {}",
+                    package_name, reason
+                )
+            }
+            InitError::InvalidTemplate { path, reason } => {
+                write!(f, "invalid template at {}: {}", path, reason)
+            }
             InitError::Io { path, .. } => {
                 write!(f, "IO error while accessing {}", path)
             }
@@ -257,7 +533,66 @@ impl error::Error for InitError {
             InitError::ConfigPathExists { .. }
             | InitError::PackageNameExists { .. }
             | InitError::WorkspacePathNotInRoot { .. }
-            | InitError::WorkspacePathExists { .. } => None,
+            | InitError::WorkspacePathExists { .. }
+            | InitError::AdoptPackageNotFound { .. }
+            | InitError::AdoptInvalidPackage { .. }
+            | InitError::InvalidTemplate { .. } => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adopt_manifest() {
+        static CONTENTS: &str = r#"[package]
+name = "my-hack"
+version = "0.1.0"
+publish = false
+
+[dependencies]
+serde = { version = "1", features = ["derive"] }
+
+[build-dependencies]
+syn = "2"
+
+[target.'cfg(windows)'.dependencies]
+winapi = "0.3"
+
+[target.'cfg(unix)'.dev-dependencies]
+libc = "0.2"
+
+[dev-dependencies]
+proptest = "1"
+"#;
+
+        let adopted = adopt_manifest_contents(CONTENTS).expect("manifest is valid");
+        assert_eq!(
+            adopted,
+            r#"[package]
+name = "my-hack"
+version = "0.1.0"
+publish = false
+
+[target.'cfg(unix)'.dev-dependencies]
+libc = "0.2"
+
+[dev-dependencies]
+proptest = "1"
+
+### BEGIN HAKARI SECTION
+
+### END HAKARI SECTION
+"#,
+        );
+        assert_eq!(
+            adopt_manifest_contents(&adopted).expect("adopted manifest is valid"),
+            adopted,
+            "manifests with a Hakari section are left unchanged"
+        );
+
+        adopt_manifest_contents("[package\n").expect_err("invalid TOML is rejected");
+    }
+}
@@ -8,10 +8,12 @@ use crate::{
     hakari::DepFormatVersion,
     HakariBuilder, WorkspaceHackLineStyle,
 };
+use camino::Utf8Path;
 use guppy::{
     graph::{DependencyDirection, PackageLink, PackageMetadata, PackageSet},
     VersionReq,
 };
+use std::borrow::Cow;
 
 impl<'g> HakariBuilder<'g> {
     /// Returns the set of operations that need to be performed to add the workspace-hack
@@ -31,13 +33,18 @@ impl<'g> HakariBuilder<'g> {
                 let link_opt = package
                     .link_to(hakari_package.id())
                     .expect("valid package ID");
-                let should_be_included = !self.is_excluded(package.id()).expect("valid package ID");
+                // Packages covered by a different Hakari package shouldn't depend on this one.
+                let should_be_included = !self.is_excluded(package.id()).expect("valid package ID")
+                    && self.covers(package.id());
                 match (link_opt, should_be_included) {
                     (None, true) => Some(true),
                     (Some(_), false) => Some(false),
                     (Some(link), true) => match self.dep_format_version {
                         DepFormatVersion::V1 => None,
-                        DepFormatVersion::V2 | DepFormatVersion::V3 | DepFormatVersion::V4 => {
+                        DepFormatVersion::V2
+                        | DepFormatVersion::V3
+                        | DepFormatVersion::V4
+                        | DepFormatVersion::V5 => {
                             needs_update_v2(hakari_package, link, self.workspace_hack_line_style)
                                 .then_some(true)
                         }
@@ -151,6 +158,64 @@ impl<'g> HakariBuilder<'g> {
         };
         Some(WorkspaceOps::new(graph, op))
     }
+
+    /// Returns the set of operations that need to be performed to remove the workspace-hack from
+    /// the workspace.
+    ///
+    /// This removes the workspace-hack dependency from all workspace crates, removes the
+    /// workspace-hack crate (along with the crates for any [shards](Self::add_shard)) from the
+    /// workspace and deletes it, and deletes `root_files`. `root_files` are relative to the
+    /// workspace root, and typically include the Hakari config.
+    ///
+    /// Returns `None` if the hakari package wasn't specified at construction time.
+    ///
+    /// Requires the `cli-support` feature to be enabled.
+    pub fn uninstall_ops<'a>(
+        &'a self,
+        root_files: impl IntoIterator<Item = &'a Utf8Path>,
+    ) -> Option<WorkspaceOps<'g, 'a>> {
+        let graph = self.graph();
+        let mut hakari_packages = vec![*self.hakari_package()?];
+        hakari_packages.extend(self.shards().map(|shard| *shard.hakari_package()));
+        // The workspace-hack crates are deleted, so there's no need to update their manifests.
+        let workspace_set =
+            graph
+                .resolve_workspace()
+                .filter(DependencyDirection::Reverse, |package| {
+                    !hakari_packages
+                        .iter()
+                        .any(|hakari_package| hakari_package.id() == package.id())
+                });
+
+        let mut ops = vec![];
+        for hakari_package in &hakari_packages {
+            let remove_from = workspace_set.filter(DependencyDirection::Reverse, |package| {
+                graph
+                    .directly_depends_on(package.id(), hakari_package.id())
+                    .expect("valid package ID")
+            });
+            if !remove_from.is_empty() {
+                ops.push(WorkspaceOp::RemoveDependency {
+                    name: hakari_package.name(),
+                    remove_from,
+                });
+            }
+        }
+        for hakari_package in &hakari_packages {
+            ops.push(WorkspaceOp::RemoveCrate {
+                name: hakari_package.name(),
+                crate_path: hakari_package
+                    .source()
+                    .workspace_path()
+                    .expect("hakari package is in workspace"),
+            });
+        }
+        let root_files: Vec<_> = root_files.into_iter().map(Cow::Borrowed).collect();
+        if !root_files.is_empty() {
+            ops.push(WorkspaceOp::RemoveFiles { root_files });
+        }
+        Some(WorkspaceOps::new(graph, ops))
+    }
 }
 
 #[allow(clippy::if_same_then_else, clippy::needless_bool)]
@@ -11,6 +11,8 @@
 mod initialize;
 mod manage_deps;
 mod workspace_ops;
+mod workspace_writer;
 
 pub use initialize::*;
 pub use workspace_ops::*;
+pub use workspace_writer::*;
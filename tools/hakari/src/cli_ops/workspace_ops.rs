@@ -2,17 +2,19 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    cargo_toml::HakariCargoToml,
+    cli_ops::{DiskWriter, WorkspaceWriter},
     hakari::{DepFormatVersion, WorkspaceHackLineStyle},
     helpers::VersionDisplay,
 };
-use atomicwrites::{AtomicFile, OverwriteBehavior};
 use camino::{Utf8Path, Utf8PathBuf};
 use guppy::{
     graph::{DependencyDirection, PackageGraph, PackageMetadata, PackageSet},
     Version,
 };
 use owo_colors::{OwoColorize, Style};
-use std::{borrow::Cow, cmp::Ordering, collections::BTreeMap, error, fmt, fs, io, io::Write};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, cmp::Ordering, collections::BTreeMap, error, fmt, io};
 use toml_edit::{
     Array, Document, Formatted, InlineTable, Item, Table, TableLike, TomlError, Value,
 };
@@ -41,6 +43,14 @@ impl<'g, 'a> WorkspaceOps<'g, 'a> {
         WorkspaceOpsDisplay::new(self)
     }
 
+    /// Returns these workspace operations in serializable form.
+    ///
+    /// This contains the same information as [`WorkspaceOpsDisplay`], and is meant to be consumed
+    /// by other tools, e.g. in JSON form.
+    pub fn to_summary(&self) -> Vec<WorkspaceOpSummary> {
+        self.ops.iter().map(|op| op.to_summary()).collect()
+    }
+
     /// Returns true if no workspace operations are specified.
     #[inline]
     pub fn is_empty(&self) -> bool {
@@ -51,8 +61,19 @@ impl<'g, 'a> WorkspaceOps<'g, 'a> {
     ///
     /// Returns an error if any operations failed to complete.
     pub fn apply(&self) -> Result<(), ApplyError> {
+        self.apply_with(&mut DiskWriter::new())
+    }
+
+    /// Apply these workspace operations, reading and writing files through the given
+    /// [`WorkspaceWriter`].
+    ///
+    /// For example, pass in a [`CapturingWriter`](crate::cli_ops::CapturingWriter) to record the
+    /// changes that would be made without writing them out to disk.
+    ///
+    /// Returns an error if any operations failed to complete.
+    pub fn apply_with(&self, writer: &mut dyn WorkspaceWriter) -> Result<(), ApplyError> {
         let workspace_root = self.graph.workspace().root();
-        let canonical_workspace_root = workspace_root.canonicalize_utf8().map_err(|error| {
+        let canonical_workspace_root = writer.canonicalize(workspace_root).map_err(|error| {
             ApplyError::io(
                 "unable to canonicalize workspace root",
                 workspace_root.to_owned(),
@@ -60,7 +81,7 @@ impl<'g, 'a> WorkspaceOps<'g, 'a> {
             )
         })?;
         for op in &self.ops {
-            op.apply(&canonical_workspace_root)?;
+            op.apply(writer, &canonical_workspace_root)?;
         }
         Ok(())
     }
@@ -73,6 +94,11 @@ pub(crate) enum WorkspaceOp<'g, 'a> {
         files: BTreeMap<Cow<'a, Utf8Path>, Cow<'a, [u8]>>,
         root_files: BTreeMap<Cow<'a, Utf8Path>, Cow<'a, [u8]>>,
     },
+    AdoptCrate {
+        crate_path: &'a Utf8Path,
+        files: BTreeMap<Cow<'a, Utf8Path>, Cow<'a, [u8]>>,
+        root_files: BTreeMap<Cow<'a, Utf8Path>, Cow<'a, [u8]>>,
+    },
     AddDependency {
         name: &'a str,
         crate_path: &'a Utf8Path,
@@ -85,29 +111,89 @@ pub(crate) enum WorkspaceOp<'g, 'a> {
         name: &'a str,
         remove_from: PackageSet<'g>,
     },
+    RemoveCrate {
+        name: &'a str,
+        crate_path: &'a Utf8Path,
+    },
+    RemoveFiles {
+        root_files: Vec<Cow<'a, Utf8Path>>,
+    },
 }
 
 impl<'g, 'a> WorkspaceOp<'g, 'a> {
-    fn apply(&self, canonical_workspace_root: &Utf8Path) -> Result<(), ApplyError> {
+    fn to_summary(&self) -> WorkspaceOpSummary {
         match self {
             WorkspaceOp::NewCrate {
                 crate_path,
                 files,
                 root_files,
-            } => {
-                Self::create_new_crate(canonical_workspace_root, crate_path, files)?;
-                // Now that the crate has been created, we can canonicalize it.
-                let crate_path = canonical_rel_path(crate_path, canonical_workspace_root)?;
-
-                for (rel_path, contents) in root_files {
-                    let abs_path = canonical_workspace_root.join(rel_path.as_ref());
-                    let parent = abs_path.parent().expect("abs path should have a parent");
-                    std::fs::create_dir_all(parent)
-                        .map_err(|err| ApplyError::io("error creating directories", parent, err))?;
-                    write_contents(contents, &abs_path)?;
+            } => WorkspaceOpSummary::CreateCrate {
+                crate_path: crate_path.to_string(),
+                files: files.keys().map(|file| file.to_string()).collect(),
+                root_files: root_files.keys().map(|file| file.to_string()).collect(),
+            },
+            WorkspaceOp::AdoptCrate {
+                crate_path,
+                files,
+                root_files,
+            } => WorkspaceOpSummary::AdoptCrate {
+                crate_path: crate_path.to_string(),
+                files: files.keys().map(|file| file.to_string()).collect(),
+                root_files: root_files.keys().map(|file| file.to_string()).collect(),
+            },
+            WorkspaceOp::AddDependency {
+                name,
+                crate_path,
+                version,
+                add_to,
+                ..
+            } => WorkspaceOpSummary::AddDependency {
+                name: name.to_string(),
+                version: version.to_string(),
+                crate_path: crate_path.to_string(),
+                packages: WorkspaceOpPackageSummary::from_package_set(add_to),
+            },
+            WorkspaceOp::RemoveDependency { name, remove_from } => {
+                WorkspaceOpSummary::RemoveDependency {
+                    name: name.to_string(),
+                    packages: WorkspaceOpPackageSummary::from_package_set(remove_from),
                 }
+            }
+            WorkspaceOp::RemoveCrate { name, crate_path } => WorkspaceOpSummary::RemoveCrate {
+                name: name.to_string(),
+                crate_path: crate_path.to_string(),
+            },
+            WorkspaceOp::RemoveFiles { root_files } => WorkspaceOpSummary::RemoveFiles {
+                root_files: root_files.iter().map(|file| file.to_string()).collect(),
+            },
+        }
+    }
 
-                Self::add_to_root_toml(canonical_workspace_root, &crate_path)
+    fn apply(
+        &self,
+        writer: &mut dyn WorkspaceWriter,
+        canonical_workspace_root: &Utf8Path,
+    ) -> Result<(), ApplyError> {
+        match self {
+            WorkspaceOp::NewCrate {
+                crate_path,
+                files,
+                root_files,
+            } => {
+                Self::create_new_crate(writer, canonical_workspace_root, crate_path, files)?;
+                // Now that the crate has been created, we can canonicalize it.
+                let crate_path = canonical_rel_path(writer, crate_path, canonical_workspace_root)?;
+                Self::write_root_files(writer, canonical_workspace_root, root_files)?;
+                Self::add_to_root_toml(writer, canonical_workspace_root, &crate_path)
+            }
+            WorkspaceOp::AdoptCrate {
+                crate_path,
+                files,
+                root_files,
+            } => {
+                // The crate is already in the workspace, so only its files need to be written out.
+                Self::create_new_crate(writer, canonical_workspace_root, crate_path, files)?;
+                Self::write_root_files(writer, canonical_workspace_root, root_files)
             }
             WorkspaceOp::AddDependency {
                 name,
@@ -117,9 +203,10 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
                 line_style,
                 add_to,
             } => {
-                let crate_path = canonical_rel_path(crate_path, canonical_workspace_root)?;
+                let crate_path = canonical_rel_path(writer, crate_path, canonical_workspace_root)?;
                 for package in add_to.packages(DependencyDirection::Reverse) {
                     Self::add_to_cargo_toml(
+                        writer,
                         name,
                         version,
                         &crate_path,
@@ -132,7 +219,29 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
             }
             WorkspaceOp::RemoveDependency { name, remove_from } => {
                 for package in remove_from.packages(DependencyDirection::Reverse) {
-                    Self::remove_from_cargo_toml(name, package)?;
+                    Self::remove_from_cargo_toml(writer, name, package)?;
+                }
+                Ok(())
+            }
+            WorkspaceOp::RemoveCrate { name, crate_path } => {
+                if crate_path.as_str().is_empty() {
+                    return Err(ApplyError::misc(
+                        "can't remove the crate at the workspace root",
+                        canonical_workspace_root,
+                    ));
+                }
+                Self::remove_from_root_toml(writer, canonical_workspace_root, name, crate_path)?;
+                let abs_path = canonical_workspace_root.join(crate_path);
+                writer
+                    .remove_dir_all(&abs_path)
+                    .map_err(|err| ApplyError::io("error removing crate directory", abs_path, err))
+            }
+            WorkspaceOp::RemoveFiles { root_files } => {
+                for rel_path in root_files {
+                    let abs_path = canonical_workspace_root.join(rel_path.as_ref());
+                    writer
+                        .remove_file(&abs_path)
+                        .map_err(|err| ApplyError::io("error removing file", abs_path, err))?;
                 }
                 Ok(())
             }
@@ -144,6 +253,7 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
     // ---
 
     fn create_new_crate(
+        writer: &mut dyn WorkspaceWriter,
         workspace_root: &Utf8Path,
         crate_path: &Utf8Path,
         files: &BTreeMap<Cow<'_, Utf8Path>, Cow<'_, [u8]>>,
@@ -155,7 +265,8 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
                 Some(parent) => abs_path.join(parent),
                 None => abs_path.clone(),
             };
-            std::fs::create_dir_all(&dir_path)
+            writer
+                .create_dir_all(&dir_path)
                 .map_err(|err| ApplyError::io("error creating directories", &dir_path, err))?;
 
             // Write out the file.
@@ -164,18 +275,35 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
                     ApplyError::misc("does not contain a file name", path.as_ref())
                 })?,
             );
-            write_contents(contents, &dir_path)?;
+            write_contents(writer, contents, &dir_path)?;
+        }
+        Ok(())
+    }
+
+    fn write_root_files(
+        writer: &mut dyn WorkspaceWriter,
+        workspace_root: &Utf8Path,
+        root_files: &BTreeMap<Cow<'_, Utf8Path>, Cow<'_, [u8]>>,
+    ) -> Result<(), ApplyError> {
+        for (rel_path, contents) in root_files {
+            let abs_path = workspace_root.join(rel_path.as_ref());
+            let parent = abs_path.parent().expect("abs path should have a parent");
+            writer
+                .create_dir_all(parent)
+                .map_err(|err| ApplyError::io("error creating directories", parent, err))?;
+            write_contents(writer, contents, &abs_path)?;
         }
         Ok(())
     }
 
     fn add_to_root_toml(
+        writer: &mut dyn WorkspaceWriter,
         workspace_root: &Utf8Path,
         crate_path: &Utf8Path,
     ) -> Result<(), ApplyError> {
         let root_toml_path = workspace_root.join("Cargo.toml");
 
-        let mut doc = read_toml(&root_toml_path)?;
+        let mut doc = read_toml(writer, &root_toml_path)?;
         let members = Self::get_workspace_members_array(&root_toml_path, &mut doc)?;
 
         let add = |members: &mut Array, idx: usize| {
@@ -226,7 +354,70 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
             add(members, members.len());
         }
 
-        write_document(&doc, &root_toml_path)
+        write_document(writer, &doc, &root_toml_path)
+    }
+
+    /// Removes the crate from `workspace.members`, along with the generated section and any
+    /// `workspace.dependencies` entry for it.
+    fn remove_from_root_toml(
+        writer: &mut dyn WorkspaceWriter,
+        workspace_root: &Utf8Path,
+        name: &str,
+        crate_path: &Utf8Path,
+    ) -> Result<(), ApplyError> {
+        let root_toml_path = workspace_root.join("Cargo.toml");
+        let mut contents = writer
+            .read_to_string(&root_toml_path)
+            .map_err(|err| ApplyError::io("error reading TOML file", &root_toml_path, err))?;
+
+        // With dep-format-version 5, the root Cargo.toml has a generated section as well.
+        if let Some(begin) = contents.find(HakariCargoToml::BEGIN_SECTION) {
+            // The newline at the end of BEGIN_SECTION may be shared with END_SECTION.
+            let search_from = begin + HakariCargoToml::BEGIN_SECTION.len() - 1;
+            if let Some(end) = contents[search_from..].find(HakariCargoToml::END_SECTION) {
+                let end = search_from + end + HakariCargoToml::END_SECTION.len();
+                // BEGIN_SECTION starts with the newline ending the previous line, unless that
+                // line is blank.
+                let replacement = if contents[..begin].ends_with('\n') {
+                    ""
+                } else {
+                    "\n"
+                };
+                contents.replace_range(begin..end, replacement);
+            }
+        }
+
+        let mut doc = contents.parse::<Document>().map_err(|err| {
+            ApplyError::toml("error deserializing TOML file", &root_toml_path, err)
+        })?;
+        let members = Self::get_workspace_members_array(&root_toml_path, &mut doc)?;
+        // Members that are globs may match the crate as well: those are left alone, since they
+        // won't match it any more once the crate is removed.
+        let mut idx = 0;
+        while idx < members.len() {
+            let is_crate = members
+                .get(idx)
+                .and_then(|member| member.as_str())
+                .is_some_and(|member| {
+                    let member = member.trim_start_matches("./").trim_end_matches('/');
+                    Utf8Path::new(member) == crate_path
+                });
+            if is_crate {
+                members.remove(idx);
+            } else {
+                idx += 1;
+            }
+        }
+
+        if let Some(workspace_deps) = doc
+            .get_mut("workspace")
+            .and_then(|workspace| workspace.get_mut("dependencies"))
+            .and_then(|deps| deps.as_table_like_mut())
+        {
+            workspace_deps.remove(name);
+        }
+
+        write_document(writer, &doc, &root_toml_path)
     }
 
     fn get_workspace_members_array<'doc>(
@@ -283,6 +474,7 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
     }
 
     fn add_to_cargo_toml(
+        writer: &mut dyn WorkspaceWriter,
         name: &str,
         version: &Version,
         crate_path: &Utf8Path,
@@ -291,7 +483,7 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
         package: PackageMetadata<'g>,
     ) -> Result<(), ApplyError> {
         let manifest_path = package.manifest_path();
-        let mut doc = read_toml(manifest_path)?;
+        let mut doc = read_toml(writer, manifest_path)?;
         let dep_table = Self::get_or_insert_dependencies_table(manifest_path, &mut doc)?;
 
         let package_path = package
@@ -306,7 +498,7 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
 
         dep_table.insert(name, Item::Value(Value::InlineTable(path_table)));
 
-        write_document(&doc, manifest_path)
+        write_document(writer, &doc, manifest_path)
     }
 
     fn inline_table_for_add(
@@ -360,15 +552,19 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
         }
     }
 
-    fn remove_from_cargo_toml(name: &str, package: PackageMetadata<'g>) -> Result<(), ApplyError> {
+    fn remove_from_cargo_toml(
+        writer: &mut dyn WorkspaceWriter,
+        name: &str,
+        package: PackageMetadata<'g>,
+    ) -> Result<(), ApplyError> {
         let manifest_path = package.manifest_path();
-        let mut doc = read_toml(manifest_path)?;
+        let mut doc = read_toml(writer, manifest_path)?;
         let dep_table = Self::get_or_insert_dependencies_table(manifest_path, &mut doc)?;
         // TODO: someone might have added the workspace-hack package under a different name.
         // Handle that if someone complains.
         dep_table.remove(name);
 
-        write_document(&doc, manifest_path)
+        write_document(writer, &doc, manifest_path)
     }
 
     fn get_or_insert_dependencies_table<'doc>(
@@ -421,13 +617,14 @@ fn with_forward_slashes(path: &Utf8Path) -> Utf8PathBuf {
 // ---
 
 fn canonical_rel_path(
+    writer: &mut dyn WorkspaceWriter,
     path: &Utf8Path,
     canonical_base: &Utf8Path,
 ) -> Result<Utf8PathBuf, ApplyError> {
     let abs_path = canonical_base.join(path);
     // Canonicalize the path now to remove .. etc.
-    let canonical_path = abs_path
-        .canonicalize_utf8()
+    let canonical_path = writer
+        .canonicalize(&abs_path)
         .map_err(|err| ApplyError::io("error canonicalizing path", &abs_path, err))?;
     canonical_path
         .strip_prefix(canonical_base)
@@ -445,32 +642,33 @@ fn canonical_rel_path(
 // Read/write functions
 // ---
 
-fn read_toml(manifest_path: &Utf8Path) -> Result<Document, ApplyError> {
-    let toml = fs::read_to_string(manifest_path)
+fn read_toml(
+    writer: &mut dyn WorkspaceWriter,
+    manifest_path: &Utf8Path,
+) -> Result<Document, ApplyError> {
+    let toml = writer
+        .read_to_string(manifest_path)
         .map_err(|err| ApplyError::io("error reading TOML file", manifest_path, err))?;
     toml.parse::<Document>()
         .map_err(|err| ApplyError::toml("error deserializing TOML file", manifest_path, err))
 }
 
-fn write_contents(contents: &[u8], path: &Utf8Path) -> Result<(), ApplyError> {
-    write_atomic(path, |file| file.write_all(contents))
-}
-
-fn write_document(document: &Document, path: &Utf8Path) -> Result<(), ApplyError> {
-    write_atomic(path, |file| write!(file, "{}", document))
+fn write_contents(
+    writer: &mut dyn WorkspaceWriter,
+    contents: &[u8],
+    path: &Utf8Path,
+) -> Result<(), ApplyError> {
+    writer
+        .write(path, contents)
+        .map_err(|err| ApplyError::io("error writing file", path, err))
 }
 
-fn write_atomic(
+fn write_document(
+    writer: &mut dyn WorkspaceWriter,
+    document: &Document,
     path: &Utf8Path,
-    cb: impl FnOnce(&mut fs::File) -> Result<(), io::Error>,
 ) -> Result<(), ApplyError> {
-    let atomic_file = AtomicFile::new(path, OverwriteBehavior::AllowOverwrite);
-    match atomic_file.write(cb) {
-        Ok(()) => Ok(()),
-        Err(atomicwrites::Error::Internal(err)) | Err(atomicwrites::Error::User(err)) => {
-            Err(ApplyError::io("error writing file", path, err))
-        }
-    }
+    write_contents(writer, document.to_string().as_bytes(), path)
 }
 
 /// An error that occurred while writing out changes to a workspace.
@@ -549,6 +747,103 @@ enum ApplyErrorKind {
     Misc,
 }
 
+/// A single workspace operation in serializable form, as returned by [`WorkspaceOps::to_summary`].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "op",
+    rename_all = "kebab-case",
+    rename_all_fields = "kebab-case"
+)]
+#[non_exhaustive]
+pub enum WorkspaceOpSummary {
+    /// Create a new crate and add it to the workspace.
+    CreateCrate {
+        /// The path to the crate, relative to the workspace root.
+        crate_path: String,
+
+        /// Files to create within the crate, relative to the crate path.
+        files: Vec<String>,
+
+        /// Files to create at the workspace root, relative to the workspace root.
+        root_files: Vec<String>,
+    },
+
+    /// Take over an existing workspace crate as the workspace-hack crate.
+    AdoptCrate {
+        /// The path to the crate, relative to the workspace root.
+        crate_path: String,
+
+        /// Files to create or overwrite within the crate, relative to the crate path.
+        files: Vec<String>,
+
+        /// Files to create at the workspace root, relative to the workspace root.
+        root_files: Vec<String>,
+    },
+
+    /// Add or update a dependency in the given packages.
+    AddDependency {
+        /// The name of the dependency.
+        name: String,
+
+        /// The version of the dependency.
+        version: String,
+
+        /// The path to the dependency, relative to the workspace root.
+        crate_path: String,
+
+        /// The packages to add the dependency to.
+        packages: Vec<WorkspaceOpPackageSummary>,
+    },
+
+    /// Remove a dependency from the given packages.
+    RemoveDependency {
+        /// The name of the dependency.
+        name: String,
+
+        /// The packages to remove the dependency from.
+        packages: Vec<WorkspaceOpPackageSummary>,
+    },
+
+    /// Remove a crate from the workspace, and delete its directory.
+    RemoveCrate {
+        /// The name of the crate.
+        name: String,
+
+        /// The path to the crate, relative to the workspace root.
+        crate_path: String,
+    },
+
+    /// Delete files at the workspace root.
+    RemoveFiles {
+        /// Files to delete, relative to the workspace root.
+        root_files: Vec<String>,
+    },
+}
+
+/// A workspace package affected by a [`WorkspaceOpSummary`].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct WorkspaceOpPackageSummary {
+    /// The name of the package.
+    pub name: String,
+
+    /// The path to the package, relative to the workspace root.
+    pub path: String,
+}
+
+impl WorkspaceOpPackageSummary {
+    fn from_package_set(package_set: &PackageSet<'_>) -> Vec<Self> {
+        package_names_paths(package_set)
+            .into_iter()
+            .map(|(name, path)| Self {
+                name: name.to_owned(),
+                path: path.to_string(),
+            })
+            .collect()
+    }
+}
+
 /// A display formatter for [`WorkspaceOps`].
 #[derive(Clone, Debug)]
 pub struct WorkspaceOpsDisplay<'g, 'a, 'ops> {
@@ -569,6 +864,24 @@ impl<'g, 'a, 'ops> WorkspaceOpsDisplay<'g, 'a, 'ops> {
         self.styles.colorize();
         self
     }
+
+    fn fmt_root_files(
+        &self,
+        root_files: &BTreeMap<Cow<'a, Utf8Path>, Cow<'a, [u8]>>,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        if !root_files.is_empty() {
+            writeln!(
+                f,
+                "* {} at workspace root:",
+                "create files".style(self.styles.create_bold_style)
+            )?;
+            for file in root_files.keys() {
+                writeln!(f, "   - {}", file.style(self.styles.create_style))?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<'g, 'a, 'ops> fmt::Display for WorkspaceOpsDisplay<'g, 'a, 'ops> {
@@ -603,16 +916,28 @@ impl<'g, 'a, 'ops> fmt::Display for WorkspaceOpsDisplay<'g, 'a, 'ops> {
                         crate_path.style(self.styles.add_style),
                         workspace_root_manifest.style(self.styles.add_to_style),
                     )?;
-                    if !root_files.is_empty() {
-                        writeln!(
-                            f,
-                            "* {} at workspace root:",
-                            "create files".style(self.styles.create_bold_style)
-                        )?;
-                        for file in root_files.keys() {
+                    self.fmt_root_files(root_files, f)?;
+                }
+                WorkspaceOp::AdoptCrate {
+                    crate_path,
+                    files,
+                    root_files,
+                } => {
+                    write!(
+                        f,
+                        "* {} at {}",
+                        "adopt crate".style(self.styles.create_bold_style),
+                        crate_path.style(self.styles.create_bold_style),
+                    )?;
+                    if !files.is_empty() {
+                        writeln!(f, ", writing files:")?;
+                        for file in files.keys() {
                             writeln!(f, "   - {}", file.style(self.styles.create_style))?;
                         }
+                    } else {
+                        writeln!(f)?;
                     }
+                    self.fmt_root_files(root_files, f)?;
                 }
                 WorkspaceOp::AddDependency {
                     name,
@@ -655,6 +980,26 @@ impl<'g, 'a, 'ops> fmt::Display for WorkspaceOpsDisplay<'g, 'a, 'ops> {
                         )?;
                     }
                 }
+                WorkspaceOp::RemoveCrate { name, crate_path } => {
+                    writeln!(
+                        f,
+                        "* {} {} (at path {}) from {}, deleting its directory",
+                        "remove crate".style(self.styles.remove_bold_style),
+                        name.style(self.styles.remove_style),
+                        crate_path.style(self.styles.remove_style),
+                        workspace_root_manifest.style(self.styles.remove_from_style),
+                    )?;
+                }
+                WorkspaceOp::RemoveFiles { root_files } => {
+                    writeln!(
+                        f,
+                        "* {} at workspace root:",
+                        "delete files".style(self.styles.remove_bold_style)
+                    )?;
+                    for file in root_files {
+                        writeln!(f, "   - {}", file.style(self.styles.remove_style))?;
+                    }
+                }
             }
         }
         Ok(())
@@ -710,6 +1055,8 @@ fn package_names_paths<'g>(package_set: &PackageSet<'g>) -> Vec<(&'g str, &'g Ut
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{cli_ops::CapturingWriter, HakariBuilder};
+    use fixtures::json::JsonFixture;
 
     #[test]
     fn test_inline_table_for_add() {
@@ -788,4 +1135,123 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn ops_to_summary() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let hakari_package = graph
+            .workspace()
+            .member_by_name("guppy-summaries")
+            .expect("guppy-summaries is a workspace member");
+        let builder = HakariBuilder::new(graph, Some(hakari_package.id())).expect("valid builder");
+
+        let package_set = graph
+            .resolve_workspace_names(["guppy", "guppy-cmdlib"])
+            .expect("valid workspace names");
+        let ops = builder
+            .remove_dep_ops(&package_set, true)
+            .expect("hakari package specified");
+        assert_eq!(
+            ops.to_summary(),
+            vec![WorkspaceOpSummary::RemoveDependency {
+                name: "guppy-summaries".to_owned(),
+                packages: vec![
+                    WorkspaceOpPackageSummary {
+                        name: "guppy".to_owned(),
+                        path: "guppy".to_owned(),
+                    },
+                    WorkspaceOpPackageSummary {
+                        name: "guppy-cmdlib".to_owned(),
+                        path: "guppy-cmdlib".to_owned(),
+                    },
+                ],
+            }],
+        );
+    }
+
+    #[test]
+    fn uninstall_ops_to_summary() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let hakari_package = graph
+            .workspace()
+            .member_by_name("guppy-summaries")
+            .expect("guppy-summaries is a workspace member");
+        let builder = HakariBuilder::new(graph, Some(hakari_package.id())).expect("valid builder");
+
+        let ops = builder
+            .uninstall_ops([Utf8Path::new(".config/hakari.toml")])
+            .expect("hakari package specified");
+        let summary = ops.to_summary();
+        assert!(
+            matches!(
+                &summary[0],
+                WorkspaceOpSummary::RemoveDependency { name, packages }
+                    if name == "guppy-summaries"
+                        && packages.iter().all(|package| package.name != "guppy-summaries")
+            ),
+            "first op removes dependency: {:?}",
+            summary,
+        );
+        assert_eq!(
+            &summary[1..],
+            [
+                WorkspaceOpSummary::RemoveCrate {
+                    name: "guppy-summaries".to_owned(),
+                    crate_path: "guppy-summaries".to_owned(),
+                },
+                WorkspaceOpSummary::RemoveFiles {
+                    root_files: vec![".config/hakari.toml".to_owned()],
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn remove_from_root_toml() {
+        let workspace_root = Utf8Path::new("/nonexistent-workspace");
+        let root_toml_path = workspace_root.join("Cargo.toml");
+        let mut writer = CapturingWriter::new();
+        writer
+            .write(
+                &root_toml_path,
+                br#"[workspace]
+members = [
+    "a",
+    "./my-hack/",
+    "crates/*",
+]
+
+[workspace.dependencies]
+anyhow = "1"
+my-hack = { path = "my-hack" }
+
+### BEGIN HAKARI SECTION
+serde = "1"
+### END HAKARI SECTION
+"#,
+            )
+            .expect("write succeeded");
+
+        WorkspaceOp::remove_from_root_toml(
+            &mut writer,
+            workspace_root,
+            "my-hack",
+            Utf8Path::new("my-hack"),
+        )
+        .expect("removal succeeded");
+        assert_eq!(
+            writer
+                .read_to_string(&root_toml_path)
+                .expect("read succeeded"),
+            r#"[workspace]
+members = [
+    "a",
+    "crates/*",
+]
+
+[workspace.dependencies]
+anyhow = "1"
+"#,
+        );
+    }
 }
@@ -0,0 +1,303 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use atomicwrites::{AtomicFile, OverwriteBehavior};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    io::Write,
+};
+
+/// The filesystem backend through which [`WorkspaceOps`](crate::cli_ops::WorkspaceOps) are
+/// applied.
+///
+/// All reads and writes performed by
+/// [`WorkspaceOps::apply_with`](crate::cli_ops::WorkspaceOps::apply_with) go through this trait.
+/// Implementations can redirect or intercept file modifications, e.g. to capture them for a dry
+/// run, or to turn them into patches for other systems.
+///
+/// Two implementations are provided:
+/// * [`DiskWriter`] writes changes out to disk. This is what
+///   [`WorkspaceOps::apply`](crate::cli_ops::WorkspaceOps::apply) uses.
+/// * [`CapturingWriter`] records changes in memory, leaving the disk untouched.
+pub trait WorkspaceWriter {
+    /// Reads the contents of the file at `path`.
+    ///
+    /// If `path` was written to earlier, the new contents must be returned.
+    fn read_to_string(&mut self, path: &Utf8Path) -> io::Result<String>;
+
+    /// Writes `contents` to the file at `path`, replacing any existing contents.
+    fn write(&mut self, path: &Utf8Path, contents: &[u8]) -> io::Result<()>;
+
+    /// Creates the directory at `path`, along with any missing parents.
+    fn create_dir_all(&mut self, path: &Utf8Path) -> io::Result<()>;
+
+    /// Removes the file at `path`.
+    fn remove_file(&mut self, path: &Utf8Path) -> io::Result<()>;
+
+    /// Removes the directory at `path`, along with all of its contents.
+    fn remove_dir_all(&mut self, path: &Utf8Path) -> io::Result<()>;
+
+    /// Returns the canonical form of `path`, with all intermediate components normalized and
+    /// symbolic links resolved.
+    ///
+    /// `path` may refer to a directory created earlier through
+    /// [`create_dir_all`](Self::create_dir_all).
+    fn canonicalize(&mut self, path: &Utf8Path) -> io::Result<Utf8PathBuf>;
+}
+
+/// A [`WorkspaceWriter`] that writes changes out to disk.
+///
+/// Files are written atomically.
+#[derive(Clone, Debug, Default)]
+pub struct DiskWriter;
+
+impl DiskWriter {
+    /// Creates a new `DiskWriter`.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl WorkspaceWriter for DiskWriter {
+    fn read_to_string(&mut self, path: &Utf8Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&mut self, path: &Utf8Path, contents: &[u8]) -> io::Result<()> {
+        let atomic_file = AtomicFile::new(path, OverwriteBehavior::AllowOverwrite);
+        match atomic_file.write(|file| file.write_all(contents)) {
+            Ok(()) => Ok(()),
+            Err(atomicwrites::Error::Internal(err)) | Err(atomicwrites::Error::User(err)) => {
+                Err(err)
+            }
+        }
+    }
+
+    fn create_dir_all(&mut self, path: &Utf8Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn remove_file(&mut self, path: &Utf8Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&mut self, path: &Utf8Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn canonicalize(&mut self, path: &Utf8Path) -> io::Result<Utf8PathBuf> {
+        path.canonicalize_utf8()
+    }
+}
+
+/// A [`WorkspaceWriter`] that records changes in memory instead of writing them out to disk.
+///
+/// Files that haven't been written to are read from disk. The recorded changes can be inspected
+/// with [`files`](Self::files), and compared against the existing contents on disk with e.g.
+/// [`diffy::create_patch`](crate::diffy::create_patch).
+#[derive(Clone, Debug, Default)]
+pub struct CapturingWriter {
+    files: BTreeMap<Utf8PathBuf, Vec<u8>>,
+    dirs: BTreeSet<Utf8PathBuf>,
+    removed: BTreeSet<Utf8PathBuf>,
+}
+
+impl CapturingWriter {
+    /// Creates a new, empty `CapturingWriter`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the files written so far, along with their new contents.
+    ///
+    /// The paths are absolute, and the map is sorted by path.
+    pub fn files(&self) -> &BTreeMap<Utf8PathBuf, Vec<u8>> {
+        &self.files
+    }
+
+    /// Returns the directories created so far that don't exist on disk.
+    pub fn created_dirs(&self) -> impl Iterator<Item = &Utf8Path> + '_ {
+        self.dirs.iter().map(|dir| dir.as_path())
+    }
+
+    /// Returns the files and directories on disk removed so far.
+    pub fn removed(&self) -> impl Iterator<Item = &Utf8Path> + '_ {
+        self.removed.iter().map(|path| path.as_path())
+    }
+
+    fn is_removed(&self, path: &Utf8Path) -> bool {
+        path.ancestors()
+            .any(|ancestor| self.removed.contains(ancestor))
+    }
+
+    /// Consumes this writer, returning the files written along with their new contents.
+    pub fn into_files(self) -> BTreeMap<Utf8PathBuf, Vec<u8>> {
+        self.files
+    }
+}
+
+impl WorkspaceWriter for CapturingWriter {
+    fn read_to_string(&mut self, path: &Utf8Path) -> io::Result<String> {
+        match self.files.get(path) {
+            Some(contents) => String::from_utf8(contents.clone())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            None if self.is_removed(path) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("path {} was removed", path),
+            )),
+            None => fs::read_to_string(path),
+        }
+    }
+
+    fn write(&mut self, path: &Utf8Path, contents: &[u8]) -> io::Result<()> {
+        self.files.insert(path.to_owned(), contents.to_vec());
+        Ok(())
+    }
+
+    fn remove_file(&mut self, path: &Utf8Path) -> io::Result<()> {
+        let written = self.files.remove(path).is_some();
+        if !self.is_removed(path) && path.is_file() {
+            self.removed.insert(path.to_owned());
+        } else if !written {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("path {} not found", path),
+            ));
+        }
+        Ok(())
+    }
+
+    fn remove_dir_all(&mut self, path: &Utf8Path) -> io::Result<()> {
+        let created = self.dirs.remove(path);
+        self.files.retain(|file, _| !file.starts_with(path));
+        self.dirs.retain(|dir| !dir.starts_with(path));
+        if !self.is_removed(path) && path.is_dir() {
+            self.removed.insert(path.to_owned());
+        } else if !created {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("path {} not found", path),
+            ));
+        }
+        Ok(())
+    }
+
+    fn create_dir_all(&mut self, path: &Utf8Path) -> io::Result<()> {
+        let mut path = path;
+        while !path.exists() {
+            self.dirs.insert(path.to_owned());
+            match path.parent() {
+                Some(parent) => path = parent,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn canonicalize(&mut self, path: &Utf8Path) -> io::Result<Utf8PathBuf> {
+        if path.exists() {
+            return path.canonicalize_utf8();
+        }
+
+        // Canonicalize the closest ancestor that exists on disk, then normalize the remaining
+        // components (which must have been created through this writer) lexically.
+        let mut existing = path;
+        let mut rest = Vec::new();
+        while !existing.exists() {
+            if !self.dirs.contains(existing) && !self.files.contains_key(existing) {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("path {} not found", path),
+                ));
+            }
+            let mut components = existing.components();
+            rest.extend(components.next_back());
+            existing = components.as_path();
+        }
+
+        let mut canonical = existing.canonicalize_utf8()?;
+        for component in rest.into_iter().rev() {
+            match component {
+                Utf8Component::ParentDir => {
+                    canonical.pop();
+                }
+                Utf8Component::Normal(component) => canonical.push(component),
+                _ => {}
+            }
+        }
+        Ok(canonical)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capturing_writer() {
+        let manifest_dir = Utf8Path::new(env!("CARGO_MANIFEST_DIR"));
+        let manifest_path = manifest_dir.join("Cargo.toml");
+        let mut writer = CapturingWriter::new();
+
+        // Reads go through to disk until the file is written to.
+        let original = writer
+            .read_to_string(&manifest_path)
+            .expect("Cargo.toml exists");
+        assert!(original.contains("name = \"hakari\""));
+        writer
+            .write(&manifest_path, b"[package]\n")
+            .expect("write succeeded");
+        assert_eq!(
+            writer
+                .read_to_string(&manifest_path)
+                .expect("read succeeded"),
+            "[package]\n",
+        );
+        assert_eq!(
+            fs::read_to_string(&manifest_path).expect("Cargo.toml exists"),
+            original,
+            "disk is untouched"
+        );
+
+        // Directories that don't exist on disk can be canonicalized once created.
+        let new_dir = manifest_dir.join("not-a-dir/nested");
+        writer
+            .canonicalize(&new_dir)
+            .expect_err("directory not created yet");
+        writer.create_dir_all(&new_dir).expect("create succeeded");
+        assert!(!new_dir.exists(), "directory not created on disk");
+        let canonical_dir = manifest_dir
+            .canonicalize_utf8()
+            .expect("manifest dir exists");
+        assert_eq!(
+            writer.canonicalize(&new_dir).expect("directory created"),
+            canonical_dir.join("not-a-dir/nested"),
+        );
+        assert_eq!(
+            writer.created_dirs().collect::<Vec<_>>(),
+            vec![manifest_dir.join("not-a-dir"), new_dir.clone()],
+        );
+
+        assert_eq!(
+            writer.clone().into_files().into_keys().collect::<Vec<_>>(),
+            vec![manifest_path.clone()],
+        );
+
+        // Removed files can't be read, even if they exist on disk.
+        writer
+            .remove_file(&manifest_path)
+            .expect("remove succeeded");
+        writer
+            .read_to_string(&manifest_path)
+            .expect_err("file was removed");
+        assert!(manifest_path.exists(), "file not removed on disk");
+        writer.remove_dir_all(&new_dir).expect("remove succeeded");
+        writer
+            .remove_dir_all(&new_dir)
+            .expect_err("directory already removed");
+        assert_eq!(writer.removed().collect::<Vec<_>>(), vec![manifest_path]);
+        assert!(writer.into_files().is_empty());
+    }
+}
@@ -1,8 +1,8 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::explain::HakariExplain;
-use guppy::graph::{feature::StandardFeatures, DependencyDirection};
+use crate::explain::{simplify::Simple, ExplainInnerValue, HakariExplain};
+use guppy::graph::{feature::StandardFeatures, DependencyDirection, PackageMetadata};
 use itertools::{Itertools, Position};
 use owo_colors::{OwoColorize, Style};
 use std::{collections::BTreeSet, fmt};
@@ -14,6 +14,8 @@ use tabular::{Row, Table};
 #[derive(Clone, Debug)]
 pub struct HakariExplainDisplay<'g, 'a, 'explain> {
     explain: &'explain HakariExplain<'g, 'a>,
+    show_chains: bool,
+    tree: bool,
     styles: Box<Styles>,
 }
 
@@ -21,6 +23,8 @@ impl<'g, 'a, 'explain> HakariExplainDisplay<'g, 'a, 'explain> {
     pub(super) fn new(explain: &'explain HakariExplain<'g, 'a>) -> Self {
         Self {
             explain,
+            show_chains: false,
+            tree: false,
             styles: Box::default(),
         }
     }
@@ -31,25 +35,26 @@ impl<'g, 'a, 'explain> HakariExplainDisplay<'g, 'a, 'explain> {
         self
     }
 
-    fn display_platform_str(
-        &self,
-        platform_idx: Option<usize>,
-        f: &mut fmt::Formatter,
-    ) -> fmt::Result {
-        match platform_idx {
-            Some(idx) => {
-                let triple_str = self.explain.platforms[idx].triple_str();
-                write!(f, "{}", triple_str.style(self.styles.platform_style))
-            }
-            None => write!(f, "all"),
-        }
+    /// Adds a chain of dependencies from each workspace package to the dependency to the output.
+    ///
+    /// For more about dependency chains, see [`HakariExplain::chain`].
+    pub fn show_chains(&mut self) -> &mut Self {
+        self.show_chains = true;
+        self
     }
-}
 
-const DITTO_MARK: &str = "\"";
+    /// Prints out an indented tree for each feature set instead of a table, similar to
+    /// `cargo tree --invert`.
+    ///
+    /// The root of each tree is the dependency, and the leaves are the workspace packages that
+    /// caused it to be built with that feature set. The packages in between are the dependency
+    /// chains from each workspace package, so [`show_chains`](Self::show_chains) has no effect.
+    pub fn tree(&mut self) -> &mut Self {
+        self.tree = true;
+        self
+    }
 
-impl<'g, 'a, 'explain> fmt::Display for HakariExplainDisplay<'g, 'a, 'explain> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn display_table(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut table = Table::new("  {:^}  |  {:^}  {:^}  {:^}");
         // header row
         let row = Row::new()
@@ -83,34 +88,6 @@ impl<'g, 'a, 'explain> fmt::Display for HakariExplainDisplay<'g, 'a, 'explain> {
                     for (idx, (include_dev, standard_features, platform_idx)) in
                         inner_value.sets.iter().enumerate()
                     {
-                        let include_dev_display =
-                            include_dev.display_with(&self.styles.star_style, |include_dev, f| {
-                                match include_dev {
-                                    true => write!(f, "{}", "yes".style(self.styles.yes_style)),
-                                    false => write!(f, "{}", "no".style(self.styles.no_style)),
-                                }
-                            });
-                        let features_display = standard_features.display_with(
-                            &self.styles.star_style,
-                            |features, f| {
-                                let features_str = match features {
-                                    StandardFeatures::None => "none",
-                                    StandardFeatures::Default => "default",
-                                    StandardFeatures::All => "all",
-                                };
-                                write!(
-                                    f,
-                                    "{}",
-                                    features_str.style(self.styles.standard_features_style)
-                                )
-                            },
-                        );
-
-                        let platform_display = platform_idx
-                            .display_with(&self.styles.star_style, |&platform_idx, f| {
-                                self.display_platform_str(platform_idx, f)
-                            });
-
                         let mut row = Row::new();
                         if idx == 0 {
                             row.add_ansi_cell(&name_display);
@@ -118,9 +95,9 @@ impl<'g, 'a, 'explain> fmt::Display for HakariExplainDisplay<'g, 'a, 'explain> {
                             row.add_ansi_cell(DITTO_MARK.style(self.styles.ditto_style));
                         }
 
-                        row.add_ansi_cell(include_dev_display)
-                            .add_ansi_cell(features_display)
-                            .add_ansi_cell(platform_display);
+                        row.add_ansi_cell(self.include_dev_display(include_dev))
+                            .add_ansi_cell(self.standard_features_display(standard_features))
+                            .add_ansi_cell(self.platform_display(platform_idx));
                         table.add_row(row);
                     }
                 }
@@ -133,14 +110,9 @@ impl<'g, 'a, 'explain> fmt::Display for HakariExplainDisplay<'g, 'a, 'explain> {
                         row.add_ansi_cell(DITTO_MARK.style(self.styles.ditto_style));
                     }
 
-                    let platform_display = platform_idx
-                        .display_with(&self.styles.star_style, |&platform_idx, f| {
-                            self.display_platform_str(platform_idx, f)
-                        });
-
                     row.add_ansi_cell("-")
                         .add_ansi_cell("-")
-                        .add_ansi_cell(platform_display);
+                        .add_ansi_cell(self.platform_display(platform_idx));
                     table.add_row(row);
                 }
             }
@@ -148,6 +120,256 @@ impl<'g, 'a, 'explain> fmt::Display for HakariExplainDisplay<'g, 'a, 'explain> {
 
         writeln!(f, "{}", table)
     }
+
+    fn display_tree(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dep = self.explain.metadata;
+        for (build_platform, explain_map) in self.explain.explain_maps() {
+            for (&features, inner) in explain_map {
+                writeln!(
+                    f,
+                    "on the {} platform, feature set {} was built by:",
+                    build_platform.style(self.styles.build_platform_style),
+                    FeatureDisplay { features }.style(self.styles.feature_style),
+                )?;
+
+                let package_set = self
+                    .explain
+                    .graph
+                    .resolve_ids(inner.workspace_packages.keys().copied())
+                    .expect("keys derived from package graph");
+
+                let mut root = TreeNode::default();
+                for package_id in package_set.package_ids(DependencyDirection::Reverse) {
+                    let inner_value = &inner.workspace_packages[package_id];
+                    // Chains go from the workspace package to the dependency, so reverse them
+                    // and skip the dependency itself.
+                    let path: Vec<_> = match self.explain.chains.get(package_id) {
+                        Some(chain) => chain.iter().rev().skip(1).copied().collect(),
+                        None => vec![inner_value.metadata],
+                    };
+                    root.insert(&path).value = Some(inner_value);
+                }
+
+                writeln!(f, "{} v{}", dep.name(), dep.version())?;
+                let fixup_count = usize::from(!inner.fixup_platforms.is_empty());
+                let count = root.children.len() + fixup_count;
+                for (idx, (package, node)) in root.children.iter().enumerate() {
+                    self.display_tree_node(*package, node, "", idx + 1 == count, f)?;
+                }
+                if fixup_count > 0 {
+                    write!(f, "└── post-compute fixup (platform: ")?;
+                    for (position, platform_idx) in inner.fixup_platforms.iter().with_position() {
+                        write!(f, "{}", self.platform_display(platform_idx))?;
+                        if matches!(position, Position::First | Position::Middle) {
+                            write!(f, ", ")?;
+                        }
+                    }
+                    writeln!(f, ")")?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn display_tree_node(
+        &self,
+        package: PackageMetadata<'g>,
+        node: &TreeNode<'g, '_>,
+        prefix: &str,
+        last: bool,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        // Workspace packages are highlighted, like in the table.
+        let name_style = match node.value {
+            Some(_) => self.styles.package_name_style,
+            None => Style::new(),
+        };
+        writeln!(
+            f,
+            "{}{}{} v{}",
+            prefix,
+            branch,
+            package.name().style(name_style),
+            package.version(),
+        )?;
+
+        let prefix = format!("{}{}", prefix, indent);
+        if let Some(inner_value) = node.value {
+            // List each set of build options below the package, continuing the tree's lines.
+            let continuation = if node.children.is_empty() {
+                "  "
+            } else {
+                "│ "
+            };
+            for (include_dev, standard_features, platform_idx) in &inner_value.sets {
+                writeln!(
+                    f,
+                    "{}{}= include dev: {}, features: {}, platform: {}",
+                    prefix,
+                    continuation,
+                    self.include_dev_display(include_dev),
+                    self.standard_features_display(standard_features),
+                    self.platform_display(platform_idx),
+                )?;
+            }
+        }
+        for (idx, (child, child_node)) in node.children.iter().enumerate() {
+            let last = idx + 1 == node.children.len();
+            self.display_tree_node(*child, child_node, &prefix, last, f)?;
+        }
+        Ok(())
+    }
+
+    fn include_dev_display<'s>(&'s self, include_dev: &'s Simple<bool>) -> impl fmt::Display + 's {
+        include_dev.display_with(
+            &self.styles.star_style,
+            |include_dev, f| match include_dev {
+                true => write!(f, "{}", "yes".style(self.styles.yes_style)),
+                false => write!(f, "{}", "no".style(self.styles.no_style)),
+            },
+        )
+    }
+
+    fn standard_features_display<'s>(
+        &'s self,
+        standard_features: &'s Simple<StandardFeatures>,
+    ) -> impl fmt::Display + 's {
+        standard_features.display_with(&self.styles.star_style, |features, f| {
+            let features_str = match features {
+                StandardFeatures::None => "none",
+                StandardFeatures::Default => "default",
+                StandardFeatures::All => "all",
+            };
+            write!(
+                f,
+                "{}",
+                features_str.style(self.styles.standard_features_style)
+            )
+        })
+    }
+
+    fn platform_display<'s>(
+        &'s self,
+        platform_idx: &'s Simple<Option<usize>>,
+    ) -> impl fmt::Display + 's {
+        platform_idx.display_with(&self.styles.star_style, |&platform_idx, f| {
+            self.display_platform_str(platform_idx, f)
+        })
+    }
+
+    fn display_chains(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let package_set = self
+            .explain
+            .graph
+            .resolve_ids(self.explain.chains.keys().copied())
+            .expect("keys derived from package graph");
+
+        writeln!(f, "dependency chains:")?;
+        for package_id in package_set.package_ids(DependencyDirection::Reverse) {
+            let chain = &self.explain.chains[package_id];
+            write!(f, "  ")?;
+            for (position, package) in chain.iter().with_position() {
+                match position {
+                    Position::First | Position::Only => {
+                        write!(
+                            f,
+                            "{}",
+                            package.name().style(self.styles.package_name_style)
+                        )?;
+                    }
+                    Position::Middle | Position::Last => {
+                        write!(f, " -> {} v{}", package.name(), package.version())?;
+                    }
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+
+    fn display_target_host_unified(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "unified across target and host:")?;
+        for &(build_platform, platform_idx, strategy) in &self.explain.target_host_unified {
+            write!(
+                f,
+                "  on the {} platform (",
+                build_platform.style(self.styles.build_platform_style)
+            )?;
+            self.display_platform_str(platform_idx, f)?;
+            writeln!(f, "): {}", strategy)?;
+        }
+        Ok(())
+    }
+
+    fn display_platform_str(
+        &self,
+        platform_idx: Option<usize>,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        match platform_idx {
+            Some(idx) => {
+                let triple_str = self.explain.platforms[idx].triple_str();
+                write!(f, "{}", triple_str.style(self.styles.platform_style))
+            }
+            None => write!(f, "all"),
+        }
+    }
+}
+
+const DITTO_MARK: &str = "\"";
+
+impl<'g, 'a, 'explain> fmt::Display for HakariExplainDisplay<'g, 'a, 'explain> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tree {
+            self.display_tree(f)?;
+        } else {
+            self.display_table(f)?;
+        }
+
+        if !self.explain.target_host_unified.is_empty() {
+            self.display_target_host_unified(f)?;
+        }
+
+        if self.show_chains && !self.tree {
+            self.display_chains(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// A node in the tree printed by [`HakariExplainDisplay::tree`].
+#[derive(Debug, Default)]
+struct TreeNode<'g, 'explain> {
+    children: Vec<(PackageMetadata<'g>, TreeNode<'g, 'explain>)>,
+    /// Set if this node is a workspace package that causes the dependency to be built.
+    value: Option<&'explain ExplainInnerValue<'g>>,
+}
+
+impl<'g, 'explain> TreeNode<'g, 'explain> {
+    /// Inserts a path of packages below this node, returning the node for the last package.
+    fn insert(&mut self, path: &[PackageMetadata<'g>]) -> &mut Self {
+        let Some((first, rest)) = path.split_first() else {
+            return self;
+        };
+        let idx = match self
+            .children
+            .iter()
+            .position(|(package, _)| package.id() == first.id())
+        {
+            Some(idx) => idx,
+            None => {
+                self.children.push((*first, TreeNode::default()));
+                self.children.len() - 1
+            }
+        };
+        self.children[idx].1.insert(rest)
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -205,3 +427,58 @@ impl<'g, 'a> fmt::Display for FeatureDisplay<'g, 'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{explain::ExplainFilter, HakariBuilder};
+    use fixtures::json::JsonFixture;
+
+    #[test]
+    fn tree() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let hakari = HakariBuilder::new(graph, None)
+            .expect("valid builder")
+            .compute();
+        let explains = hakari.explain_all(&ExplainFilter::new());
+        assert!(!explains.is_empty(), "some dependencies explained");
+
+        for explain in &explains {
+            let dep = explain.dependency();
+            let output = explain.display().tree().to_string();
+            let mut lines = output.lines();
+            assert!(
+                lines.next().is_some_and(|line| line.starts_with("on the ")),
+                "tree for {} starts with a heading",
+                dep.name()
+            );
+            assert_eq!(
+                lines.next(),
+                Some(format!("{} v{}", dep.name(), dep.version()).as_str()),
+                "root of the tree is the dependency"
+            );
+
+            assert!(
+                output.contains("= include dev: "),
+                "build options are listed for {}",
+                dep.name()
+            );
+            for (_, explain_map) in explain.explain_maps() {
+                for inner in explain_map.values() {
+                    for value in inner.workspace_packages.values() {
+                        let name = value.metadata.name();
+                        assert!(
+                            output.lines().any(|line| {
+                                line.trim_start_matches(['│', '├', '└', '─', ' '])
+                                    .starts_with(&format!("{} v", name))
+                            }),
+                            "workspace package {} is in the tree for {}:\n{}",
+                            name,
+                            dep.name(),
+                            output
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
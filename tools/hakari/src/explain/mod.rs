@@ -4,22 +4,33 @@
 //! Information about why a dependency is in the workspace-hack.
 //!
 //! [`HakariExplain`] instances are produced by [`Hakari::explain`]. The current API is limited
-//! to displaying these instances if the `cli-support` feature is enabled.
+//! to displaying these instances, or converting them to a serializable
+//! [`HakariExplainSummary`], if the `cli-support` feature is enabled, and to looking up
+//! [dependency chains](HakariExplain::chain) from workspace packages to the dependency and
+//! [target-host unification](HakariExplain::target_host_unified) for it.
+//!
+//! To explain every dependency in the workspace-hack at once, use [`Hakari::explain_all`] along
+//! with an [`ExplainFilter`].
 
 #[cfg(feature = "cli-support")]
 mod display;
 mod simplify;
+#[cfg(feature = "cli-support")]
+mod summary;
 
 #[cfg(feature = "cli-support")]
 pub use display::HakariExplainDisplay;
+#[cfg(feature = "cli-support")]
+pub use summary::*;
 
-use crate::{explain::simplify::*, Hakari};
+use crate::{explain::simplify::*, hakari::TraversalExcludes, Hakari, UnifyTargetHost};
+use ahash::AHashMap;
 use guppy::{
     graph::{cargo::BuildPlatform, feature::StandardFeatures, PackageGraph, PackageMetadata},
     PackageId,
 };
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, VecDeque},
     sync::Arc,
 };
 use target_spec::Platform;
@@ -32,10 +43,11 @@ pub struct HakariExplain<'g, 'a> {
     #[cfg_attr(not(feature = "cli-support"), allow(dead_code))]
     graph: &'g PackageGraph,
     metadata: PackageMetadata<'g>,
-    #[cfg_attr(not(feature = "cli-support"), allow(dead_code))]
     platforms: &'a [Arc<Platform>],
     target_map: ExplainMap<'g, 'a>,
     host_map: ExplainMap<'g, 'a>,
+    chains: BTreeMap<&'g PackageId, Vec<PackageMetadata<'g>>>,
+    target_host_unified: Vec<(BuildPlatform, Option<usize>, UnifyTargetHost)>,
 }
 
 type ExplainMap<'g, 'a> = BTreeMap<&'a BTreeSet<&'g str>, ExplainInner<'g>>;
@@ -61,11 +73,118 @@ struct ExplainInnerValue<'g> {
     )>,
 }
 
+/// A filter for the builds included in a [`HakariExplain`].
+///
+/// Used by [`Hakari::explain_all`]. By default, all builds are included.
+#[derive(Clone, Debug, Default)]
+pub struct ExplainFilter<'g> {
+    build_platform: Option<BuildPlatform>,
+    platform: Option<String>,
+    workspace_packages: Option<BTreeSet<&'g PackageId>>,
+}
+
+impl<'g> ExplainFilter<'g> {
+    /// Creates a new `ExplainFilter` which includes all builds.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only includes builds on the given build platform: [`Target`](BuildPlatform::Target) for
+    /// regular dependencies, or [`Host`](BuildPlatform::Host) for build dependencies and
+    /// procedural macros.
+    pub fn set_build_platform(&mut self, build_platform: BuildPlatform) -> &mut Self {
+        self.build_platform = Some(build_platform);
+        self
+    }
+
+    /// Only includes builds that happen on the given platform.
+    ///
+    /// Builds that happen on every platform are always included. If `triple_str` isn't one of the
+    /// [platforms](crate::HakariBuilder::set_platforms), only those builds are included.
+    pub fn set_platform(&mut self, triple_str: impl Into<String>) -> &mut Self {
+        self.platform = Some(triple_str.into());
+        self
+    }
+
+    /// Only includes builds caused by the given workspace packages.
+    ///
+    /// Dependencies [fixed up](crate::verify) by Hakari aren't caused by any workspace package, so
+    /// they're excluded if this is set.
+    ///
+    /// Can be called multiple times to add more packages.
+    pub fn add_workspace_packages(
+        &mut self,
+        package_ids: impl IntoIterator<Item = &'g PackageId>,
+    ) -> &mut Self {
+        self.workspace_packages
+            .get_or_insert_with(BTreeSet::new)
+            .extend(package_ids);
+        self
+    }
+
+    /// Returns the platform index to include, or `Some(None)` if only builds that happen on every
+    /// platform should be included.
+    fn platform_idx(&self, hakari: &Hakari<'g>) -> Option<Option<usize>> {
+        self.platform.as_ref().map(|triple_str| {
+            hakari
+                .builder
+                .platforms
+                .iter()
+                .position(|platform| platform.triple_str() == triple_str)
+        })
+    }
+
+    fn matches(
+        &self,
+        build_platform: BuildPlatform,
+        platform: Option<usize>,
+        filter_platform_idx: Option<Option<usize>>,
+    ) -> bool {
+        self.build_platform.map_or(true, |filter_build_platform| {
+            filter_build_platform == build_platform
+        }) && filter_platform_idx.map_or(true, |filter_platform_idx| {
+            platform.is_none() || platform == filter_platform_idx
+        })
+    }
+
+    fn matches_workspace_package(&self, package_id: &PackageId) -> bool {
+        self.workspace_packages
+            .as_ref()
+            .map_or(true, |package_ids| package_ids.contains(package_id))
+    }
+}
+
 impl<'g, 'a> HakariExplain<'g, 'a> {
-    pub(crate) fn new(hakari: &'a Hakari<'g>, dep_id: &PackageId) -> Result<Self, guppy::Error> {
+    pub(crate) fn new(
+        hakari: &'a Hakari<'g>,
+        dep_id: &PackageId,
+        filter: &ExplainFilter<'g>,
+    ) -> Result<Self, guppy::Error> {
         let graph = hakari.builder.graph();
         let metadata = hakari.builder.graph().metadata(dep_id)?;
-        let intermediate = ExplainIntermediate::new(hakari, metadata.id())?;
+        let filter_platform_idx = filter.platform_idx(hakari);
+        let intermediate =
+            ExplainIntermediate::new(hakari, metadata.id(), filter, filter_platform_idx)?;
+        let chains = intermediate.chains(hakari, metadata);
+        let target_host_unified = hakari
+            .target_host_unified
+            .iter()
+            .filter(|((output_key, dep_id), _)| {
+                *dep_id == metadata.id()
+                    && filter.matches(
+                        output_key.build_platform,
+                        output_key.platform_idx,
+                        filter_platform_idx,
+                    )
+            })
+            .map(|((output_key, _), strategy)| {
+                (
+                    output_key.build_platform,
+                    output_key.platform_idx,
+                    *strategy,
+                )
+            })
+            .collect();
 
         let target_map = Self::simplify_map(hakari, intermediate.target_map);
         let host_map = Self::simplify_map(hakari, intermediate.host_map);
@@ -76,6 +195,8 @@ impl<'g, 'a> HakariExplain<'g, 'a> {
             platforms: &hakari.builder.platforms,
             target_map,
             host_map,
+            chains,
+            target_host_unified,
         })
     }
 
@@ -116,12 +237,61 @@ impl<'g, 'a> HakariExplain<'g, 'a> {
         self.metadata
     }
 
+    /// Returns a chain of dependencies from a workspace package to the dependency associated with
+    /// this `HakariExplain` instance, similar to the paths shown by `cargo tree --invert`.
+    ///
+    /// The chain starts with the workspace package and ends with the dependency. If there are
+    /// several chains, one of the shortest ones is returned. Chains are computed over the package
+    /// graph, so they don't account for platform-specific or optional dependencies not being
+    /// enabled.
+    ///
+    /// Returns `None` if this workspace package doesn't cause the dependency to be built with any
+    /// feature set.
+    pub fn chain(&self, workspace_package: &PackageId) -> Option<&[PackageMetadata<'g>]> {
+        self.chains
+            .get(workspace_package)
+            .map(|chain| chain.as_slice())
+    }
+
+    /// Returns the lines for the dependency in the workspace-hack that include features from the
+    /// other build platform, along with the
+    /// [`UnifyTargetHost`](crate::HakariBuilder::set_unify_target_host) strategy that caused this.
+    ///
+    /// Each element is a build platform (target or host) the line is for, the platform the line is
+    /// specific to (or `None` for lines that aren't platform-specific), and the strategy. The
+    /// strategy is never [`Auto`](UnifyTargetHost::Auto): the strategy `Auto` resolved to is
+    /// returned instead.
+    ///
+    /// For example, with [`ReplicateTargetOnHost`](UnifyTargetHost::ReplicateTargetOnHost), lines
+    /// built only on the target platform are also written out for the host platform.
+    pub fn target_host_unified(
+        &self,
+    ) -> impl ExactSizeIterator<Item = (BuildPlatform, Option<&'a Platform>, UnifyTargetHost)> + '_
+    {
+        let platforms = self.platforms;
+        self.target_host_unified
+            .iter()
+            .map(move |&(build_platform, platform_idx, strategy)| {
+                (
+                    build_platform,
+                    platform_idx.map(|idx| &*platforms[idx]),
+                    strategy,
+                )
+            })
+    }
+
     /// Returns a displayer for the output.
     #[cfg(feature = "cli-support")]
     pub fn display<'explain>(&'explain self) -> HakariExplainDisplay<'g, 'a, 'explain> {
         HakariExplainDisplay::new(self)
     }
 
+    /// Converts this `HakariExplain` to a serializable summary.
+    #[cfg(feature = "cli-support")]
+    pub fn to_summary(&self) -> HakariExplainSummary {
+        HakariExplainSummary::new(self)
+    }
+
     // Used by the display module.
     #[allow(dead_code)]
     fn explain_maps(&self) -> [(BuildPlatform, &ExplainMap<'g, 'a>); 2] {
@@ -154,7 +324,12 @@ struct IntermediateInnerValue<'g> {
 }
 
 impl<'g, 'a> ExplainIntermediate<'g, 'a> {
-    fn new(hakari: &'a Hakari<'g>, dep_id: &'g PackageId) -> Result<Self, guppy::Error> {
+    fn new(
+        hakari: &'a Hakari<'g>,
+        dep_id: &'g PackageId,
+        filter: &ExplainFilter<'g>,
+        filter_platform_idx: Option<Option<usize>>,
+    ) -> Result<Self, guppy::Error> {
         let mut target_map: IntermediateMap<'g, 'a> = BTreeMap::new();
         let mut host_map: IntermediateMap<'g, 'a> = BTreeMap::new();
 
@@ -170,6 +345,9 @@ impl<'g, 'a> ExplainIntermediate<'g, 'a> {
             };
 
             for (build_platform, inner_map) in computed_value.inner_maps() {
+                if !filter.matches(build_platform, platform, filter_platform_idx) {
+                    continue;
+                }
                 let map = match build_platform {
                     BuildPlatform::Target => &mut target_map,
                     BuildPlatform::Host => &mut host_map,
@@ -179,6 +357,9 @@ impl<'g, 'a> ExplainIntermediate<'g, 'a> {
                     for &(workspace_package, standard_features, include_dev) in
                         &inner_value.workspace_packages
                     {
+                        if !filter.matches_workspace_package(workspace_package.id()) {
+                            continue;
+                        }
                         map.entry(features)
                             .or_default()
                             .workspace_packages
@@ -191,7 +372,7 @@ impl<'g, 'a> ExplainIntermediate<'g, 'a> {
                             .insert((include_dev, standard_features, platform));
                     }
 
-                    if inner_value.fixed_up {
+                    if inner_value.fixed_up && filter.workspace_packages.is_none() {
                         map.entry(features)
                             .or_default()
                             .fixup_platforms
@@ -210,4 +391,75 @@ impl<'g, 'a> ExplainIntermediate<'g, 'a> {
             host_map,
         })
     }
+
+    /// Computes a shortest dependency chain from each workspace package to the dependency.
+    fn chains(
+        &self,
+        hakari: &Hakari<'g>,
+        dep: PackageMetadata<'g>,
+    ) -> BTreeMap<&'g PackageId, Vec<PackageMetadata<'g>>> {
+        let excludes = hakari.builder.make_traversal_excludes();
+
+        // For each workspace package, whether any of its builds include dev-dependencies.
+        let mut workspace_packages: BTreeMap<&'g PackageId, (PackageMetadata<'g>, bool)> =
+            BTreeMap::new();
+        for inner in self.target_map.values().chain(self.host_map.values()) {
+            for (&package_id, value) in &inner.workspace_packages {
+                let include_dev = value.sets.iter().any(|&(include_dev, _, _)| include_dev);
+                workspace_packages
+                    .entry(package_id)
+                    .or_insert((value.metadata, false))
+                    .1 |= include_dev;
+            }
+        }
+
+        workspace_packages
+            .into_iter()
+            .filter_map(|(package_id, (package, include_dev))| {
+                let chain = shortest_chain(package, dep, include_dev, &excludes)?;
+                Some((package_id, chain))
+            })
+            .collect()
+    }
+}
+
+/// Finds a shortest chain of dependencies from `from` to `to` through a breadth-first search.
+fn shortest_chain<'g>(
+    from: PackageMetadata<'g>,
+    to: PackageMetadata<'g>,
+    include_dev: bool,
+    excludes: &TraversalExcludes<'g, '_>,
+) -> Option<Vec<PackageMetadata<'g>>> {
+    // Map of packages to the package they were first reached from.
+    let mut parents: AHashMap<&'g PackageId, Option<PackageMetadata<'g>>> = AHashMap::new();
+    parents.insert(from.id(), None);
+    let mut queue = VecDeque::from([from]);
+
+    while let Some(package) = queue.pop_front() {
+        if package.id() == to.id() {
+            let mut chain = vec![package];
+            let mut current = package;
+            while let Some(parent) = parents[current.id()] {
+                chain.push(parent);
+                current = parent;
+            }
+            chain.reverse();
+            return Some(chain);
+        }
+
+        for link in package.direct_links() {
+            // Dev-dependencies are only built for the workspace package being built.
+            if link.dev_only() && (package.id() != from.id() || !include_dev) {
+                continue;
+            }
+            let next = link.to();
+            if excludes.is_excluded(next.id()) || parents.contains_key(next.id()) {
+                continue;
+            }
+            parents.insert(next.id(), Some(package));
+            queue.push_back(next);
+        }
+    }
+
+    None
 }
@@ -0,0 +1,377 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    explain::{simplify::Simple, ExplainMap, HakariExplain},
+    UnifyTargetHost,
+};
+use guppy::graph::{feature::StandardFeatures, DependencyDirection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// A [`HakariExplain`] in serializable form.
+///
+/// This contains the same information as [`HakariExplainDisplay`](crate::explain::HakariExplainDisplay),
+/// and is meant to be consumed by other tools, e.g. in JSON form.
+///
+/// In this summary, a field that is `None` (serialized as `null`) matches every possible value. For
+/// example, an [`ExplainBuildSummary`] with `include_dev: None` means that the feature set was
+/// built both with and without dev-dependencies.
+///
+/// Requires the `cli-support` feature to be enabled.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct HakariExplainSummary {
+    /// The name of the dependency.
+    pub name: String,
+
+    /// The version of the dependency.
+    pub version: String,
+
+    /// The package ID of the dependency.
+    pub package_id: String,
+
+    /// Feature sets the dependency is built with on the target platform.
+    pub target: Vec<ExplainFeatureSetSummary>,
+
+    /// Feature sets the dependency is built with on the host platform.
+    pub host: Vec<ExplainFeatureSetSummary>,
+
+    /// Lines in the workspace-hack that include features from the other build platform, and the
+    /// strategy that caused them to.
+    ///
+    /// For more, see [`HakariExplain::target_host_unified`].
+    #[serde(default)]
+    pub target_host_unified: Vec<ExplainTargetHostSummary>,
+}
+
+/// A feature set a dependency is built with, and the reasons it's built that way.
+///
+/// Part of a [`HakariExplainSummary`].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct ExplainFeatureSetSummary {
+    /// The features the dependency is built with.
+    pub features: BTreeSet<String>,
+
+    /// The workspace packages that cause the dependency to be built with this feature set, in
+    /// reverse dependency order.
+    pub workspace_packages: Vec<ExplainWorkspacePackageSummary>,
+
+    /// The platforms on which a post-computation fixup caused this feature set to be built.
+    ///
+    /// For the meaning of each element, see [`ExplainBuildSummary::platform`].
+    pub fixup_platforms: Vec<Option<String>>,
+}
+
+/// A workspace package that causes a dependency to be built with a feature set.
+///
+/// Part of an [`ExplainFeatureSetSummary`].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct ExplainWorkspacePackageSummary {
+    /// The name of the workspace package.
+    pub name: String,
+
+    /// The builds of this workspace package that cause the feature set to be built.
+    pub builds: Vec<ExplainBuildSummary>,
+
+    /// The package IDs in a chain of dependencies from this workspace package to the dependency,
+    /// starting with the workspace package and ending with the dependency.
+    ///
+    /// For more about dependency chains, see [`HakariExplain::chain`].
+    #[serde(default)]
+    pub chain: Vec<String>,
+}
+
+/// A simulated build of a workspace package.
+///
+/// Part of an [`ExplainWorkspacePackageSummary`].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct ExplainBuildSummary {
+    /// Whether dev-dependencies were included in the build, or `None` for both.
+    pub include_dev: Option<bool>,
+
+    /// The features of the workspace package that were enabled: `"none"`, `"default"` or `"all"`,
+    /// or `None` for all three.
+    pub features: Option<String>,
+
+    /// The platform the build was simulated on.
+    ///
+    /// This is `Some("always")` for the simulation that isn't specific to any platform, the target
+    /// triple for the platforms specified in the config, or `None` for all of them.
+    pub platform: Option<String>,
+}
+
+/// A line in the workspace-hack that includes features from the other build platform.
+///
+/// Part of a [`HakariExplainSummary`].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct ExplainTargetHostSummary {
+    /// The build platform the line is for: `"target"` or `"host"`.
+    pub build_platform: String,
+
+    /// The target triple the line is specific to, or `None` if it isn't platform-specific.
+    pub platform: Option<String>,
+
+    /// The strategy that caused the line to include features from the other build platform.
+    pub strategy: UnifyTargetHost,
+}
+
+impl HakariExplainSummary {
+    /// Creates a new `HakariExplainSummary` from a [`HakariExplain`].
+    pub fn new(explain: &HakariExplain<'_, '_>) -> Self {
+        Self {
+            name: explain.metadata.name().to_owned(),
+            version: explain.metadata.version().to_string(),
+            package_id: explain.metadata.id().repr().to_owned(),
+            target: Self::feature_sets(explain, &explain.target_map),
+            host: Self::feature_sets(explain, &explain.host_map),
+            target_host_unified: explain
+                .target_host_unified()
+                .map(
+                    |(build_platform, platform, strategy)| ExplainTargetHostSummary {
+                        build_platform: build_platform.to_string(),
+                        platform: platform.map(|platform| platform.triple_str().to_owned()),
+                        strategy,
+                    },
+                )
+                .collect(),
+        }
+    }
+
+    fn feature_sets(
+        explain: &HakariExplain<'_, '_>,
+        map: &ExplainMap<'_, '_>,
+    ) -> Vec<ExplainFeatureSetSummary> {
+        let platform_str = |platform: &Simple<Option<usize>>| match platform {
+            Simple::Any => None,
+            Simple::Some(None) => Some("always".to_owned()),
+            Simple::Some(Some(idx)) => Some(explain.platforms[*idx].triple_str().to_owned()),
+        };
+
+        map.iter()
+            .map(|(&features, inner)| {
+                let package_set = explain
+                    .graph
+                    .resolve_ids(inner.workspace_packages.keys().copied())
+                    .expect("keys derived from package graph");
+                // Use the same order as the display output.
+                let workspace_packages = package_set
+                    .package_ids(DependencyDirection::Reverse)
+                    .map(|package_id| {
+                        let inner_value = &inner.workspace_packages[package_id];
+                        let builds = inner_value
+                            .sets
+                            .iter()
+                            .map(
+                                |(include_dev, standard_features, platform)| ExplainBuildSummary {
+                                    include_dev: match include_dev {
+                                        Simple::Any => None,
+                                        Simple::Some(include_dev) => Some(*include_dev),
+                                    },
+                                    features: match standard_features {
+                                        Simple::Any => None,
+                                        Simple::Some(standard_features) => Some(
+                                            match standard_features {
+                                                StandardFeatures::None => "none",
+                                                StandardFeatures::Default => "default",
+                                                StandardFeatures::All => "all",
+                                            }
+                                            .to_owned(),
+                                        ),
+                                    },
+                                    platform: platform_str(platform),
+                                },
+                            )
+                            .collect();
+                        let chain = explain
+                            .chain(package_id)
+                            .unwrap_or_default()
+                            .iter()
+                            .map(|package| package.id().repr().to_owned())
+                            .collect();
+                        ExplainWorkspacePackageSummary {
+                            name: inner_value.metadata.name().to_owned(),
+                            builds,
+                            chain,
+                        }
+                    })
+                    .collect();
+
+                ExplainFeatureSetSummary {
+                    features: features.iter().map(|&feature| feature.to_owned()).collect(),
+                    workspace_packages,
+                    fixup_platforms: inner.fixup_platforms.iter().map(platform_str).collect(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{explain::ExplainFilter, HakariBuilder};
+    use fixtures::json::JsonFixture;
+    use guppy::{graph::cargo::BuildPlatform, PackageId};
+
+    #[test]
+    fn explain_summary() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let mut builder = HakariBuilder::new(graph, None).expect("valid builder");
+        builder
+            .set_platforms(["x86_64-unknown-linux-gnu"])
+            .expect("known platform");
+        let hakari = builder.compute();
+
+        let toml_name_map = hakari.toml_name_map();
+        assert!(!toml_name_map.is_empty(), "some dependencies were unified");
+        for dep in toml_name_map.values() {
+            let summary = hakari
+                .explain(dep.id())
+                .expect("dependency is in the output")
+                .to_summary();
+            assert_eq!(summary.name, dep.name());
+            assert_eq!(summary.package_id, dep.id().repr());
+
+            let feature_sets: Vec<_> = summary.target.iter().chain(&summary.host).collect();
+            assert!(
+                feature_sets.len() >= 2,
+                "{} is built with at least two feature sets",
+                dep.name()
+            );
+            for feature_set in feature_sets {
+                assert!(
+                    !feature_set.workspace_packages.is_empty()
+                        || !feature_set.fixup_platforms.is_empty(),
+                    "each feature set of {} has a reason to be built",
+                    dep.name()
+                );
+                for package in &feature_set.workspace_packages {
+                    let chain = &package.chain;
+                    assert!(
+                        chain.len() >= 2,
+                        "chain from {} to {} found",
+                        package.name,
+                        dep.name()
+                    );
+                    let first = graph
+                        .metadata(&PackageId::new(chain[0].as_str()))
+                        .expect("chain package IDs are valid");
+                    assert_eq!(
+                        first.name(),
+                        package.name,
+                        "chain starts at workspace package"
+                    );
+                    assert_eq!(
+                        chain.last(),
+                        Some(&summary.package_id),
+                        "chain ends at dependency"
+                    );
+                }
+
+                let platforms = feature_set
+                    .workspace_packages
+                    .iter()
+                    .flat_map(|package| &package.builds)
+                    .map(|build| &build.platform)
+                    .chain(&feature_set.fixup_platforms);
+                for platform in platforms {
+                    assert!(
+                        matches!(
+                            platform.as_deref(),
+                            None | Some("always") | Some("x86_64-unknown-linux-gnu")
+                        ),
+                        "unexpected platform {:?}",
+                        platform
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn explain_all_filters() {
+        let graph = JsonFixture::metadata_libra_9ffd93b().graph();
+        let mut builder = HakariBuilder::new(graph, None).expect("valid builder");
+        builder
+            .set_platforms(["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"])
+            .expect("known platforms");
+        let hakari = builder.compute();
+
+        let all = hakari.explain_all(&ExplainFilter::new());
+        assert_eq!(
+            all.len(),
+            hakari.toml_name_map().len(),
+            "every dependency in the output is explained"
+        );
+
+        let mut filter = ExplainFilter::new();
+        filter.set_build_platform(BuildPlatform::Host);
+        let host = hakari.explain_all(&filter);
+        assert!(!host.is_empty(), "some dependencies are built on the host");
+        assert!(host.len() < all.len(), "some dependencies are target-only");
+        for explain in &host {
+            let summary = explain.to_summary();
+            assert!(
+                summary.target.is_empty(),
+                "{} has no target builds",
+                summary.name
+            );
+            assert!(!summary.host.is_empty(), "{} has host builds", summary.name);
+        }
+
+        let mut filter = ExplainFilter::new();
+        filter.set_platform("x86_64-pc-windows-msvc");
+        for explain in hakari.explain_all(&filter) {
+            let summary = explain.to_summary();
+            for feature_set in summary.target.iter().chain(&summary.host) {
+                let platforms = feature_set
+                    .workspace_packages
+                    .iter()
+                    .flat_map(|package| &package.builds)
+                    .map(|build| &build.platform)
+                    .chain(&feature_set.fixup_platforms);
+                for platform in platforms {
+                    assert!(
+                        matches!(
+                            platform.as_deref(),
+                            Some("always") | Some("x86_64-pc-windows-msvc")
+                        ),
+                        "unexpected platform {:?} for {}",
+                        platform,
+                        summary.name,
+                    );
+                }
+            }
+        }
+
+        let member = graph
+            .workspace()
+            .member_by_name("libra-node")
+            .expect("libra-node is a workspace member");
+        let mut filter = ExplainFilter::new();
+        filter.add_workspace_packages([member.id()]);
+        let member_explains = hakari.explain_all(&filter);
+        assert!(!member_explains.is_empty(), "libra-node causes some builds");
+        for explain in &member_explains {
+            let summary = explain.to_summary();
+            for feature_set in summary.target.iter().chain(&summary.host) {
+                assert!(
+                    feature_set.fixup_platforms.is_empty(),
+                    "fixups are excluded"
+                );
+                for package in &feature_set.workspace_packages {
+                    assert_eq!(package.name, "libra-node", "only libra-node is included");
+                }
+            }
+        }
+    }
+}
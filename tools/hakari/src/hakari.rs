@@ -2,10 +2,13 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
-    explain::HakariExplain,
+    cargo_config::cargo_config_registries,
+    cargo_toml::read_workspace_resolver,
+    explain::{ExplainFilter, HakariExplain},
+    incremental::IncrementalBuild,
     toml_name_map,
-    toml_out::{write_toml, HakariOutputOptions},
-    CargoTomlError, HakariCargoToml, TomlOutError,
+    toml_out::{write_toml, write_workspace_toml, HakariOutputOptions},
+    CargoConfigError, CargoTomlError, HakariCargoToml, TomlOutError,
 };
 use ahash::AHashMap;
 use bimap::BiHashMap;
@@ -14,7 +17,10 @@ use guppy::{
     errors::TargetSpecError,
     graph::{
         cargo::{BuildPlatform, CargoOptions, CargoResolverVersion, CargoSet, InitialsPlatform},
-        feature::{named_feature_filter, FeatureId, FeatureLabel, FeatureSet, StandardFeatures},
+        feature::{
+            named_feature_filter, FeatureGraph, FeatureId, FeatureLabel, FeatureSet,
+            StandardFeatures,
+        },
         DependencyDirection, PackageGraph, PackageMetadata,
     },
     platform::{Platform, PlatformSpec, TargetFeatures},
@@ -40,11 +46,21 @@ pub struct HakariBuilder<'g> {
     pub(crate) verify_mode: bool,
     pub(crate) traversal_excludes: HashSet<&'g PackageId>,
     final_excludes: HashSet<&'g PackageId>,
+    denied_features: BTreeSet<FeatureId<'g>>,
     pub(crate) registries: BiHashMap<String, String, ahash::RandomState, ahash::RandomState>,
     unify_target_host: UnifyTargetHost,
+    platform_unify_target_host: BTreeMap<String, UnifyTargetHost>,
     output_single_feature: bool,
+    exclude_dev_dependencies: bool,
     pub(crate) dep_format_version: DepFormatVersion,
     pub(crate) workspace_hack_line_style: WorkspaceHackLineStyle,
+    shards: Vec<HakariShard<'g>>,
+    // Dependencies added through add_simulated_dependency, keyed by the workspace package they're
+    // added to.
+    pub(crate) simulated_deps: BTreeMap<&'g PackageId, FeatureSet<'g>>,
+    // These are only set for builders returned by shard_builders.
+    pub(crate) members: Option<BTreeSet<&'g PackageId>>,
+    sibling_hakari_packages: Vec<&'g PackageId>,
 }
 
 impl<'g> HakariBuilder<'g> {
@@ -79,11 +95,18 @@ impl<'g> HakariBuilder<'g> {
             verify_mode: false,
             traversal_excludes: HashSet::new(),
             final_excludes: HashSet::new(),
+            denied_features: BTreeSet::new(),
             registries: BiHashMap::with_hashers(Default::default(), Default::default()),
             unify_target_host: UnifyTargetHost::default(),
+            platform_unify_target_host: BTreeMap::new(),
             output_single_feature: false,
+            exclude_dev_dependencies: false,
             dep_format_version: DepFormatVersion::default(),
             workspace_hack_line_style: WorkspaceHackLineStyle::default(),
+            shards: vec![],
+            simulated_deps: BTreeMap::new(),
+            members: None,
+            sibling_hakari_packages: vec![],
         })
     }
 
@@ -119,6 +142,17 @@ impl<'g> HakariBuilder<'g> {
         ))
     }
 
+    /// Reads the workspace root's `Cargo.toml` from disk, returning a `HakariCargoToml`.
+    ///
+    /// With [`DepFormatVersion::V5`], this can be used with
+    /// [`Hakari::to_workspace_toml_string`](Hakari::to_workspace_toml_string) to manage the
+    /// generated section of the `[workspace.dependencies]` table.
+    ///
+    /// Returns an error if there was an issue reading the TOML file from disk.
+    pub fn read_workspace_toml(&self) -> Result<HakariCargoToml, CargoTomlError> {
+        HakariCargoToml::new(self.graph.workspace().root().join("Cargo.toml"))
+    }
+
     /// Sets a list of platforms for `hakari` to use.
     ///
     /// By default, `hakari` unifies features that are always enabled across all platforms. If
@@ -153,8 +187,9 @@ impl<'g> HakariBuilder<'g> {
     /// Sets the Cargo resolver version.
     ///
     /// By default, `HakariBuilder` uses [version 2](CargoResolverVersion::V2) of the Cargo
-    /// resolver. For more about Cargo resolvers, see the documentation for
-    /// [`CargoResolverVersion`](CargoResolverVersion).
+    /// resolver. To use the version Cargo uses for the workspace, pass in the result of
+    /// [`read_workspace_resolver`](Self::read_workspace_resolver). For more about Cargo resolvers,
+    /// see the documentation for [`CargoResolverVersion`](CargoResolverVersion).
     pub fn set_resolver(&mut self, resolver: CargoResolverVersion) -> &mut Self {
         self.resolver = resolver;
         self
@@ -165,6 +200,18 @@ impl<'g> HakariBuilder<'g> {
         self.resolver
     }
 
+    /// Reads the version of the Cargo resolver used by the workspace from its root `Cargo.toml`.
+    ///
+    /// The resolver is read from `workspace.resolver` or `package.resolver`, or implied by the
+    /// edition of the root package if neither is set: for example, packages on edition 2024 use
+    /// [version 3](CargoResolverVersion::V3) of the resolver.
+    ///
+    /// Returns an error if the `Cargo.toml` couldn't be read or parsed, or if it specifies an
+    /// unknown resolver version.
+    pub fn read_workspace_resolver(&self) -> Result<CargoResolverVersion, CargoTomlError> {
+        read_workspace_resolver(self.graph.workspace().root())
+    }
+
     /// Pretends that the provided packages don't exist during graph traversals.
     ///
     /// Users may wish to not consider certain packages while figuring out the unified feature set.
@@ -192,11 +239,11 @@ impl<'g> HakariBuilder<'g> {
 
     /// Returns the packages currently excluded during graph traversals.
     ///
-    /// Also returns the Hakari package if specified. This is because the Hakari package is treated
-    /// as excluded while performing unification.
+    /// Also returns the Hakari package if specified, as well as the Hakari packages for any
+    /// [shards](Self::add_shard). This is because Hakari packages are treated as excluded while
+    /// performing unification.
     pub fn traversal_excludes<'b>(&'b self) -> impl Iterator<Item = &'g PackageId> + 'b {
-        let excludes = self.make_traversal_excludes();
-        excludes.iter()
+        self.make_traversal_excludes().into_iter()
     }
 
     /// Returns true if a package ID is currently excluded during traversal.
@@ -235,6 +282,42 @@ impl<'g> HakariBuilder<'g> {
         self.final_excludes.iter().copied()
     }
 
+    /// Adds features of a third-party package that must never be written out to the
+    /// workspace-hack.
+    ///
+    /// Features that would enable a denied feature, such as `default` or a feature of another
+    /// package that depends on it, are not written out either. Denied features are not considered
+    /// in [verify mode](crate::verify): if some workspace packages enable a denied feature while
+    /// others don't, verification fails.
+    ///
+    /// Returns an error if the package ID or any of the features aren't known to the graph.
+    pub fn add_denied_features<'b>(
+        &mut self,
+        package_id: &PackageId,
+        features: impl IntoIterator<Item = &'b str>,
+    ) -> Result<&mut Self, guppy::Error> {
+        let package = self.graph.metadata(package_id)?;
+        let denied_features: Vec<FeatureId<'g>> = features
+            .into_iter()
+            .map(|feature| {
+                let named = package
+                    .named_features()
+                    .find(|&named| named == feature)
+                    .ok_or_else(|| {
+                        guppy::Error::UnknownFeatureId(package.id().clone(), feature.to_owned())
+                    })?;
+                Ok(FeatureId::new(package.id(), FeatureLabel::Named(named)))
+            })
+            .collect::<Result<_, guppy::Error>>()?;
+        self.denied_features.extend(denied_features);
+        Ok(self)
+    }
+
+    /// Returns the features that must never be written out to the workspace-hack.
+    pub fn denied_features<'b>(&'b self) -> impl Iterator<Item = FeatureId<'g>> + 'b {
+        self.denied_features.iter().copied()
+    }
+
     /// Returns true if a package ID is currently excluded from the final output.
     ///
     /// Returns an error if this package ID isn't known to the underlying graph.
@@ -270,6 +353,24 @@ impl<'g> HakariBuilder<'g> {
         self
     }
 
+    /// Adds alternate registries declared in Cargo configuration files.
+    ///
+    /// The configuration files are discovered the same way Cargo does, starting from the workspace
+    /// root: `.cargo/config.toml` in the workspace root and each of its ancestors, followed by
+    /// `$CARGO_HOME/config.toml`. Registries that were already added, by either name or URL, take
+    /// precedence over the ones read from these files.
+    ///
+    /// Returns an error if a configuration file couldn't be read or parsed.
+    pub fn add_cargo_config_registries(&mut self) -> Result<&mut Self, CargoConfigError> {
+        let registries = cargo_config_registries(self.graph.workspace().root())?;
+        for (name, url) in registries {
+            if !self.registries.contains_left(&name) && !self.registries.contains_right(&url) {
+                self.registries.insert(name, url);
+            }
+        }
+        Ok(self)
+    }
+
     /// Whether and how to unify feature sets across target and host platforms.
     ///
     /// This is an advanced feature that most users don't need to set. For more information about
@@ -284,6 +385,36 @@ impl<'g> HakariBuilder<'g> {
         self.unify_target_host
     }
 
+    /// Sets how to unify feature sets across target and host platforms for dependencies specific
+    /// to one of the [platforms](Self::set_platforms), overriding
+    /// [`set_unify_target_host`](Self::set_unify_target_host).
+    ///
+    /// `triple_str` must be one of the platforms for this option to have an effect. Dependencies
+    /// that are built on every platform are always unified according to `set_unify_target_host`.
+    ///
+    /// This is useful when cross-compiling to some of the platforms: for example, unification
+    /// across target and host can be disabled for those platforms, while keeping it for the
+    /// platform builds usually happen on.
+    pub fn set_platform_unify_target_host(
+        &mut self,
+        triple_str: impl Into<String>,
+        unify_target_host: UnifyTargetHost,
+    ) -> &mut Self {
+        self.platform_unify_target_host
+            .insert(triple_str.into(), unify_target_host);
+        self
+    }
+
+    /// Returns the per-platform overrides for `unify_target_host`, as pairs of target triples and
+    /// values.
+    pub fn platform_unify_target_host(
+        &self,
+    ) -> impl ExactSizeIterator<Item = (&str, UnifyTargetHost)> + '_ {
+        self.platform_unify_target_host
+            .iter()
+            .map(|(triple_str, unify_target_host)| (triple_str.as_str(), *unify_target_host))
+    }
+
     /// Whether to unify feature sets for all dependencies.
     ///
     /// By default, Hakari only produces output for dependencies that are built with more
@@ -301,6 +432,25 @@ impl<'g> HakariBuilder<'g> {
         self.output_single_feature
     }
 
+    /// Whether to ignore dev-dependencies while computing the workspace-hack.
+    ///
+    /// By default, Hakari simulates builds both with and without dev-dependencies, so that feature
+    /// sets are unified across `cargo build` and `cargo test`. If set to true, Hakari only
+    /// simulates builds without dev-dependencies. Dependencies and features that are only pulled
+    /// in by dev-dependencies (e.g. test or benchmarking frameworks) will then not be unified.
+    ///
+    /// This results in a smaller workspace-hack, at the cost of some rebuilds when switching
+    /// between building and testing.
+    pub fn set_exclude_dev_dependencies(&mut self, exclude_dev_dependencies: bool) -> &mut Self {
+        self.exclude_dev_dependencies = exclude_dev_dependencies;
+        self
+    }
+
+    /// Returns the current value of `exclude_dev_dependencies`.
+    pub fn exclude_dev_dependencies(&self) -> bool {
+        self.exclude_dev_dependencies
+    }
+
     /// Version of hakari data to output.
     ///
     /// For more, see the documentation for [`DepFormatVersion`](DepFormatVersion).
@@ -330,9 +480,127 @@ impl<'g> HakariBuilder<'g> {
         self.workspace_hack_line_style
     }
 
+    /// Adds a shard: an additional Hakari package that covers the given workspace members.
+    ///
+    /// Large workspaces sometimes consist of mostly-disjoint trees of packages. With a single
+    /// Hakari package, a change to the dependencies of one tree causes rebuilds in all the
+    /// others. Shards allow each tree to have its own Hakari package, with features unified only
+    /// across the workspace members in that tree.
+    ///
+    /// Workspace members are covered by the first shard that lists them. Members that aren't
+    /// covered by any shard are covered by the Hakari package passed into [`new`](Self::new), if
+    /// any. Hakari packages are never covered by a shard, and are always excluded during graph
+    /// traversals.
+    ///
+    /// Members of a shard that depend on members of another shard will also pull in the other
+    /// shard's Hakari package.
+    ///
+    /// To compute the output for each Hakari package, use
+    /// [`shard_builders`](Self::shard_builders).
+    ///
+    /// Returns an error if any of the package IDs specified aren't known to the graph, or aren't
+    /// in the workspace.
+    pub fn add_shard<'b>(
+        &mut self,
+        hakari_id: &PackageId,
+        members: impl IntoIterator<Item = &'b PackageId>,
+    ) -> Result<&mut Self, guppy::Error> {
+        let hakari_package = self.workspace_metadata(hakari_id)?;
+        let members = members
+            .into_iter()
+            .map(|package_id| Ok(self.workspace_metadata(package_id)?.id()))
+            .collect::<Result<_, _>>()?;
+        self.shards.push(HakariShard {
+            hakari_package,
+            members,
+        });
+        Ok(self)
+    }
+
+    /// Returns the shards added through [`add_shard`](Self::add_shard).
+    pub fn shards(&self) -> impl ExactSizeIterator<Item = &HakariShard<'g>> + '_ {
+        self.shards.iter()
+    }
+
+    /// Returns true if Cargo builds for this workspace member are simulated by this builder.
+    ///
+    /// This is false for workspace members covered by a shard, and for members excluded during
+    /// traversal.
+    ///
+    /// Returns an error if this package ID isn't known to the underlying graph.
+    pub fn is_covered(&self, package_id: &PackageId) -> Result<bool, guppy::Error> {
+        Ok(!self.is_traversal_excluded(package_id)? && self.covers(package_id))
+    }
+
+    /// Returns one builder for every Hakari package.
+    ///
+    /// The first builder is for the Hakari package passed into [`new`](Self::new), if any, and
+    /// covers workspace members not covered by any shard. It is followed by one builder for each
+    /// shard, in the order they were added.
+    ///
+    /// Each returned builder has no shards of its own, and can be used to compute, verify, and
+    /// manage dependencies for a single Hakari package.
+    pub fn shard_builders(&self) -> Vec<HakariBuilder<'g>> {
+        let hakari_ids: Vec<_> = self
+            .hakari_package
+            .iter()
+            .chain(self.shards.iter().map(|shard| &shard.hakari_package))
+            .map(|package| package.id())
+            .collect();
+        let make_builder = |hakari_package: Option<PackageMetadata<'g>>, members| {
+            let mut builder = self.clone();
+            builder.shards.clear();
+            builder.members = Some(members);
+            builder.sibling_hakari_packages = self
+                .sibling_hakari_packages
+                .iter()
+                .copied()
+                .chain(hakari_ids.iter().copied())
+                .filter(|&id| Some(id) != hakari_package.map(|package| package.id()))
+                .collect();
+            builder.hakari_package = hakari_package;
+            builder
+        };
+
+        let mut builders = Vec::with_capacity(self.shards.len() + 1);
+        if self.hakari_package.is_some() {
+            let members = self
+                .graph
+                .workspace()
+                .iter()
+                .map(|package| package.id())
+                .filter(|package_id| self.covers(package_id))
+                .collect();
+            builders.push(make_builder(self.hakari_package, members));
+        }
+        for (idx, shard) in self.shards.iter().enumerate() {
+            let members = shard
+                .members
+                .iter()
+                .copied()
+                .filter(|&package_id| {
+                    // Earlier shards take precedence.
+                    !hakari_ids.contains(&package_id)
+                        && !self.shards[..idx]
+                            .iter()
+                            .any(|prev| prev.members.contains(package_id))
+                        && self
+                            .members
+                            .as_ref()
+                            .map_or(true, |members| members.contains(package_id))
+                })
+                .collect();
+            builders.push(make_builder(Some(shard.hakari_package), members));
+        }
+        builders
+    }
+
     /// Computes the `Hakari` for this builder.
+    ///
+    /// If shards were added, this only covers workspace members not covered by any shard. Use
+    /// [`shard_builders`](Self::shard_builders) to compute the output for every Hakari package.
     pub fn compute(self) -> Hakari<'g> {
-        Hakari::build(self)
+        Hakari::build(self, None)
     }
 
     // ---
@@ -346,17 +614,49 @@ impl<'g> HakariBuilder<'g> {
         self.traversal_excludes.iter().copied()
     }
 
-    fn make_traversal_excludes<'b>(&'b self) -> TraversalExcludes<'g, 'b> {
-        let hakari_package = if self.verify_mode {
-            None
+    pub(crate) fn make_traversal_excludes<'b>(&'b self) -> TraversalExcludes<'g, 'b> {
+        // In verify mode, other Hakari packages are built whenever a package depends on members
+        // covered by them, so they must not be excluded either.
+        let hakari_packages = if self.verify_mode {
+            vec![]
         } else {
-            self.hakari_package.map(|package| package.id())
+            self.hakari_package
+                .iter()
+                .map(|package| package.id())
+                .chain(self.sibling_hakari_packages.iter().copied())
+                .chain(self.shards.iter().map(|shard| shard.hakari_package.id()))
+                .collect()
         };
 
         TraversalExcludes {
             excludes: &self.traversal_excludes,
-            hakari_package,
+            hakari_packages,
+        }
+    }
+
+    pub(crate) fn workspace_metadata(
+        &self,
+        package_id: &PackageId,
+    ) -> Result<PackageMetadata<'g>, guppy::Error> {
+        let package = self.graph.metadata(package_id)?;
+        if !package.in_workspace() {
+            return Err(guppy::Error::UnknownWorkspaceName(
+                package.name().to_string(),
+            ));
         }
+        Ok(package)
+    }
+
+    /// Returns true if this workspace member is covered by this builder, ignoring traversal
+    /// excludes.
+    pub(crate) fn covers(&self, package_id: &PackageId) -> bool {
+        self.members
+            .as_ref()
+            .map_or(true, |members| members.contains(package_id))
+            && !self
+                .shards
+                .iter()
+                .any(|shard| shard.members.contains(package_id))
     }
 
     fn make_features_only<'b>(&'b self) -> FeatureSet<'g> {
@@ -372,6 +672,29 @@ impl<'g> HakariBuilder<'g> {
     }
 }
 
+/// A Hakari package and the workspace members it covers.
+///
+/// Added through [`HakariBuilder::add_shard`].
+#[derive(Clone, Debug)]
+pub struct HakariShard<'g> {
+    hakari_package: PackageMetadata<'g>,
+    members: BTreeSet<&'g PackageId>,
+}
+
+impl<'g> HakariShard<'g> {
+    /// Returns the Hakari package for this shard.
+    pub fn hakari_package(&self) -> &PackageMetadata<'g> {
+        &self.hakari_package
+    }
+
+    /// Returns the workspace members listed for this shard.
+    ///
+    /// Members listed by an earlier shard are covered by that shard instead.
+    pub fn members(&self) -> impl ExactSizeIterator<Item = &'g PackageId> + '_ {
+        self.members.iter().copied()
+    }
+}
+
 #[cfg(feature = "cli-support")]
 mod summaries {
     use super::*;
@@ -434,20 +757,43 @@ mod summaries {
                 .package_ids(DependencyDirection::Forward)
                 .collect();
 
-            Ok(Self {
+            let mut denied_features = BTreeSet::new();
+            for (name, max_features) in &summary.max_features {
+                denied_features.extend(max_features.to_denied_features(graph, name)?);
+            }
+
+            let mut builder = Self {
                 graph: DebugIgnore(graph),
                 hakari_package,
-                resolver: summary.resolver,
+                resolver: summary.resolver.unwrap_or(CargoResolverVersion::V2),
                 verify_mode: false,
                 unify_target_host: summary.unify_target_host,
+                platform_unify_target_host: summary.platform_unify_target_host.clone(),
                 output_single_feature: summary.output_single_feature,
+                exclude_dev_dependencies: summary.exclude_dev_dependencies,
                 dep_format_version: summary.dep_format_version,
                 workspace_hack_line_style: summary.workspace_hack_line_style,
                 platforms,
                 registries,
                 traversal_excludes,
                 final_excludes,
-            })
+                denied_features,
+                shards: vec![],
+                simulated_deps: BTreeMap::new(),
+                members: None,
+                sibling_hakari_packages: vec![],
+            };
+
+            for shard in &summary.shards {
+                let hakari_package = graph.workspace().member_by_name(&shard.hakari_package)?;
+                let members = shard.to_package_set(graph)?;
+                builder.add_shard(
+                    hakari_package.id(),
+                    members.package_ids(DependencyDirection::Forward),
+                )?;
+            }
+
+            Ok(builder)
         }
     }
 }
@@ -511,6 +857,22 @@ pub enum UnifyTargetHost {
     /// This is most useful if some workspace packages are proc macros or build dependencies
     /// used by other packages.
     ReplicateTargetOnHost,
+
+    /// Perform unification across target and host feature sets, as with
+    /// [`UnifyIfBoth`](Self::UnifyIfBoth), but only for dependencies of proc macros.
+    ///
+    /// Dependencies of proc macros, such as `syn` and `quote`, are commonly built on both the
+    /// target and the host. Unifying them avoids building them twice, while dependencies only
+    /// used by build scripts don't cause extra features to be enabled on the target.
+    ProcMacroDependencies,
+
+    /// Perform no unification across the target and host feature sets, but replicate target-only
+    /// lines to the host as with [`ReplicateTargetOnHost`](Self::ReplicateTargetOnHost).
+    ///
+    /// With this option, features enabled on the host never cause extra features to be enabled on
+    /// the target. This is most useful when cross-compiling, where the target and host builds
+    /// can't be shared anyway.
+    EmitBoth,
 }
 
 /// The default for `UnifyTargetHost`: automatically choose unification strategy based on the
@@ -522,6 +884,20 @@ impl Default for UnifyTargetHost {
     }
 }
 
+/// Displays the value as it's written in `hakari.toml`, e.g. `unify-if-both`.
+impl fmt::Display for UnifyTargetHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnifyTargetHost::None => write!(f, "none"),
+            UnifyTargetHost::Auto => write!(f, "auto"),
+            UnifyTargetHost::UnifyIfBoth => write!(f, "unify-if-both"),
+            UnifyTargetHost::ReplicateTargetOnHost => write!(f, "replicate-target-on-host"),
+            UnifyTargetHost::ProcMacroDependencies => write!(f, "proc-macro-dependencies"),
+            UnifyTargetHost::EmitBoth => write!(f, "emit-both"),
+        }
+    }
+}
+
 /// Format version for hakari.
 ///
 /// Older versions are kept around for backwards compatibility.
@@ -554,10 +930,25 @@ pub enum DepFormatVersion {
     /// #65](https://github.com/guppy-rs/guppy/issues/65).
     #[cfg_attr(feature = "cli-support", serde(rename = "4"))]
     V4,
+
+    /// Writes third-party dependencies to the `[workspace.dependencies]` table in the workspace
+    /// root's `Cargo.toml`, and references them from the workspace-hack's `Cargo.toml` with
+    /// `workspace = true`.
+    ///
+    /// The workspace root's `Cargo.toml` must have a generated section within its
+    /// `[workspace.dependencies]` table: see [`HakariBuilder::read_workspace_toml`] and
+    /// [`Hakari::to_workspace_toml_string`].
+    ///
+    /// Since this changes the workspace root's `Cargo.toml`, this version must be opted into: it
+    /// is not returned by [`latest`](Self::latest).
+    #[cfg_attr(feature = "cli-support", serde(rename = "5"))]
+    V5,
 }
 
 impl DepFormatVersion {
     /// Returns the highest format version supported by this version of `cargo hakari`.
+    ///
+    /// This does not include [`V5`](Self::V5), which must be opted into.
     #[inline]
     pub fn latest() -> Self {
         DepFormatVersion::V4
@@ -571,6 +962,7 @@ impl fmt::Display for DepFormatVersion {
             DepFormatVersion::V2 => write!(f, "2"),
             DepFormatVersion::V3 => write!(f, "3"),
             DepFormatVersion::V4 => write!(f, "4"),
+            DepFormatVersion::V5 => write!(f, "5"),
         }
     }
 }
@@ -625,6 +1017,10 @@ pub struct Hakari<'g> {
     ///
     /// This map is not used to generate the TOML output.
     pub computed_map: ComputedMap<'g>,
+
+    /// Lines in the output map that include features from the other build platform (target or
+    /// host), and the strategy that caused them to.
+    pub(crate) target_host_unified: BTreeMap<(OutputKey, &'g PackageId), UnifyTargetHost>,
 }
 
 impl<'g> Hakari<'g> {
@@ -662,6 +1058,47 @@ impl<'g> Hakari<'g> {
         )
     }
 
+    /// Writes `[workspace.dependencies]` lines for the workspace root's `Cargo.toml` to the given
+    /// `fmt::Write` instance.
+    ///
+    /// Each third-party dependency in the workspace-hack's output is written out once, along with
+    /// its source and `default-features = false`. Features are specified by the workspace-hack's
+    /// `Cargo.toml`.
+    ///
+    /// This is used with [`DepFormatVersion::V5`]. The lines are meant to be placed in the
+    /// generated section of `workspace_toml`, the workspace root's `Cargo.toml` obtained through
+    /// [`HakariBuilder::read_workspace_toml`].
+    ///
+    /// Returns an error if a dependency is already declared in `[workspace.dependencies]` outside
+    /// the generated section, since Cargo rejects duplicate keys.
+    pub fn write_workspace_toml(
+        &self,
+        workspace_toml: &HakariCargoToml,
+        options: &HakariOutputOptions,
+        out: impl fmt::Write,
+    ) -> Result<(), TomlOutError> {
+        write_workspace_toml(
+            &self.builder,
+            &self.output_map,
+            workspace_toml,
+            options,
+            self.builder.dep_format_version,
+            out,
+        )
+    }
+
+    /// A convenience method around `write_workspace_toml` that returns a new string with
+    /// `[workspace.dependencies]` lines.
+    pub fn to_workspace_toml_string(
+        &self,
+        workspace_toml: &HakariCargoToml,
+        options: &HakariOutputOptions,
+    ) -> Result<String, TomlOutError> {
+        let mut out = String::new();
+        self.write_workspace_toml(workspace_toml, options, &mut out)?;
+        Ok(out)
+    }
+
     /// Returns a map of dependency names as present in the workspace-hack's `Cargo.toml` to their
     /// corresponding [`PackageMetadata`].
     ///
@@ -679,7 +1116,30 @@ impl<'g> Hakari<'g> {
         &self,
         package_id: &'g PackageId,
     ) -> Result<HakariExplain<'g, '_>, guppy::Error> {
-        HakariExplain::new(self, package_id)
+        HakariExplain::new(self, package_id, &ExplainFilter::new())
+    }
+
+    /// Returns a `HakariExplain` for every dependency in the workspace-hack's `Cargo.toml`, sorted
+    /// by name and version.
+    ///
+    /// Each `HakariExplain` only includes builds that match `filter`. Dependencies with no matching
+    /// builds are skipped.
+    pub fn explain_all(&self, filter: &ExplainFilter<'g>) -> Vec<HakariExplain<'g, '_>> {
+        let mut dependencies: Vec<_> = self
+            .output_map
+            .values()
+            .flat_map(|deps| deps.values().map(|(package, _)| *package))
+            .collect();
+        dependencies.sort_unstable_by(|a, b| {
+            (a.name(), a.version(), a.id()).cmp(&(b.name(), b.version(), b.id()))
+        });
+        dependencies.dedup_by_key(|package| package.id());
+
+        dependencies
+            .into_iter()
+            // An error here means that no builds matched the filter.
+            .filter_map(|package| HakariExplain::new(self, package.id(), filter).ok())
+            .collect()
     }
 
     /// A convenience method around `write_toml` that returns a new string with `Cargo.toml` lines.
@@ -696,23 +1156,26 @@ impl<'g> Hakari<'g> {
     // Helper methods
     // ---
 
-    fn build(builder: HakariBuilder<'g>) -> Self {
+    pub(crate) fn build(
+        builder: HakariBuilder<'g>,
+        incremental: Option<IncrementalBuild<'g>>,
+    ) -> Self {
         let graph = *builder.graph;
-        let mut computed_map_build = ComputedMapBuild::new(&builder);
+        let mut computed_map_build = ComputedMapBuild::new(&builder, incremental);
         let platform_specs: Vec<_> = builder
             .platforms
             .iter()
             .map(|platform| PlatformSpec::Platform(platform.clone()))
             .collect();
 
-        let unify_target_host = builder.unify_target_host.to_impl(graph);
+        let unify_target_host = UnifyTargetHostMap::new(&builder);
 
         // Collect all the dependencies that need to be unified, by platform and build type.
         let mut map_build: OutputMapBuild<'g> = OutputMapBuild::new(graph);
         map_build.insert_all(
             computed_map_build.iter(),
             builder.output_single_feature,
-            unify_target_host,
+            &unify_target_host,
         );
 
         if !builder.output_single_feature {
@@ -720,29 +1183,39 @@ impl<'g> Hakari<'g> {
             // further builds with the given target and host features, and use that to add in any
             // extra features that need to be considered.
             loop {
-                let mut add_extra = HashSet::new();
-                for (output_key, features) in map_build.iter_feature_sets() {
-                    let initials_platform = match output_key.build_platform {
-                        BuildPlatform::Target => InitialsPlatform::Standard,
-                        BuildPlatform::Host => InitialsPlatform::Host,
-                    };
+                // Simulating builds is the most expensive part of each iteration, and the
+                // simulations are independent of each other, so run them in parallel.
+                let feature_sets: Vec<_> = map_build.iter_feature_sets().collect();
+                let excludes = &computed_map_build.excludes;
+                let cargo_sets: Vec<_> = feature_sets
+                    .into_par_iter()
+                    .map(|(output_key, features)| {
+                        let initials_platform = match output_key.build_platform {
+                            BuildPlatform::Target => InitialsPlatform::Standard,
+                            BuildPlatform::Host => InitialsPlatform::Host,
+                        };
 
-                    let mut cargo_opts = CargoOptions::new();
-                    let platform_spec = match output_key.platform_idx {
-                        Some(idx) => platform_specs[idx].clone(),
-                        None => PlatformSpec::Always,
-                    };
-                    // Third-party dependencies are built without including dev.
-                    cargo_opts
-                        .set_include_dev(false)
-                        .set_initials_platform(initials_platform)
-                        .set_platform(platform_spec)
-                        .set_resolver(builder.resolver)
-                        .add_omitted_packages(computed_map_build.excludes.iter());
-                    let cargo_set = features
-                        .into_cargo_set(&cargo_opts)
-                        .expect("into_cargo_set processed successfully");
+                        let mut cargo_opts = CargoOptions::new();
+                        let platform_spec = match output_key.platform_idx {
+                            Some(idx) => platform_specs[idx].clone(),
+                            None => PlatformSpec::Always,
+                        };
+                        // Third-party dependencies are built without including dev.
+                        cargo_opts
+                            .set_include_dev(false)
+                            .set_initials_platform(initials_platform)
+                            .set_platform(platform_spec)
+                            .set_resolver(builder.resolver)
+                            .add_omitted_packages(excludes.iter());
+                        let cargo_set = features
+                            .into_cargo_set(&cargo_opts)
+                            .expect("into_cargo_set processed successfully");
+                        (output_key, cargo_set)
+                    })
+                    .collect();
 
+                let mut add_extra = HashSet::new();
+                for (output_key, cargo_set) in cargo_sets {
                     // Check the features for the cargo set to see if any further dependencies were
                     // built with a different result and weren't included in the hakari map
                     // originally.
@@ -792,22 +1265,40 @@ impl<'g> Hakari<'g> {
                         (platform_idx, dep_id, v)
                     }),
                     builder.output_single_feature,
-                    unify_target_host,
+                    &unify_target_host,
                 );
             }
         }
 
         let computed_map = computed_map_build.computed_map;
+        let mut target_host_unified = std::mem::take(&mut map_build.target_host_unified);
+        // In verify mode, denied features must be kept so that conflicts involving them are
+        // detected.
+        let empty = BTreeSet::new();
+        let denied_features = if builder.verify_mode {
+            &empty
+        } else {
+            &builder.denied_features
+        };
         let output_map = map_build.finish(
             &builder.final_excludes,
+            denied_features,
+            builder.graph.feature_graph(),
             builder.dep_format_version,
             builder.output_single_feature,
         );
+        // Lines may have been removed from the output map, e.g. through final excludes.
+        target_host_unified.retain(|(output_key, dep_id), _| {
+            output_map
+                .get(output_key)
+                .is_some_and(|inner_map| inner_map.contains_key(dep_id))
+        });
 
         Self {
             builder,
             output_map,
             computed_map,
+            target_host_unified,
         }
     }
 }
@@ -887,18 +1378,25 @@ impl<'g> ComputedInnerValue<'g> {
 }
 
 #[derive(Debug)]
-struct TraversalExcludes<'g, 'b> {
+pub(crate) struct TraversalExcludes<'g, 'b> {
     excludes: &'b HashSet<&'g PackageId>,
-    hakari_package: Option<&'g PackageId>,
+    hakari_packages: Vec<&'g PackageId>,
 }
 
 impl<'g, 'b> TraversalExcludes<'g, 'b> {
-    fn iter(&self) -> impl Iterator<Item = &'g PackageId> + 'b {
-        self.excludes.iter().copied().chain(self.hakari_package)
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &'g PackageId> + '_ {
+        self.excludes
+            .iter()
+            .copied()
+            .chain(self.hakari_packages.iter().copied())
+    }
+
+    fn into_iter(self) -> impl Iterator<Item = &'g PackageId> + 'b {
+        self.excludes.iter().copied().chain(self.hakari_packages)
     }
 
-    fn is_excluded(&self, package_id: &PackageId) -> bool {
-        self.hakari_package == Some(package_id) || self.excludes.contains(package_id)
+    pub(crate) fn is_excluded(&self, package_id: &PackageId) -> bool {
+        self.hakari_packages.contains(&package_id) || self.excludes.contains(package_id)
     }
 }
 
@@ -910,7 +1408,7 @@ struct ComputedMapBuild<'g, 'b> {
 }
 
 impl<'g, 'b> ComputedMapBuild<'g, 'b> {
-    fn new(builder: &'b HakariBuilder<'g>) -> Self {
+    fn new(builder: &'b HakariBuilder<'g>, incremental: Option<IncrementalBuild<'g>>) -> Self {
         // This was just None or All for a bit under the theory that feature sets are additive only,
         // but unfortunately we cannot exploit this property because it doesn't account for the fact
         // that some dependencies might not be built *at all*, under certain feature combinations.
@@ -950,6 +1448,11 @@ impl<'g, 'b> ComputedMapBuild<'g, 'b> {
             (StandardFeatures::All, true),
         ];
 
+        let features_include_dev: Vec<_> = features_include_dev
+            .into_iter()
+            .filter(|&(_, include_dev)| !(include_dev && builder.exclude_dev_dependencies))
+            .collect();
+
         // Features for the "always" platform spec.
         let always_features = features_include_dev
             .iter()
@@ -980,8 +1483,13 @@ impl<'g, 'b> ComputedMapBuild<'g, 'b> {
         let features_only = builder.make_features_only();
         let excludes_ref = &excludes;
         let features_only_ref = &features_only;
+        let (mut computed_map, affected) = match incremental {
+            Some(incremental) => (incremental.computed_map, Some(incremental.affected)),
+            None => (ComputedMap::new(), None),
+        };
+        let affected_ref = affected.as_ref();
 
-        let computed_map: ComputedMap<'g> = platforms_features
+        let new_map: ComputedMap<'g> = platforms_features
             .into_par_iter()
             // The cargo_set computation in the inner iterator is the most expensive part of the
             // process, so use flat_map instead of flat_map_iter.
@@ -994,14 +1502,28 @@ impl<'g, 'b> ComputedMapBuild<'g, 'b> {
                     .add_omitted_packages(excludes.iter());
 
                 workspace.par_iter().map(move |workspace_package| {
-                    if excludes_ref.is_excluded(workspace_package.id()) {
-                        // Skip this package since it was excluded during traversal.
+                    if excludes_ref.is_excluded(workspace_package.id())
+                        || !builder.covers(workspace_package.id())
+                    {
+                        // Skip this package since it was excluded during traversal, or is covered
+                        // by a different Hakari package.
+                        return BTreeMap::new();
+                    }
+                    if affected_ref
+                        .is_some_and(|affected| !affected.contains(workspace_package.id()))
+                    {
+                        // The results for this package were reused from a previous computation.
                         return BTreeMap::new();
                     }
 
-                    let initials = workspace_package
+                    let mut initials = workspace_package
                         .to_package_set()
                         .to_feature_set(feature_filter);
+                    if let Some(simulated) = builder.simulated_deps.get(workspace_package.id()) {
+                        // Simulated dependencies are built regardless of the workspace package's
+                        // features, just like non-optional dependencies.
+                        initials = initials.union(simulated);
+                    }
                     let cargo_set =
                         CargoSet::new(initials, features_only_ref.clone(), &cargo_options)
                             .expect("cargo resolution should succeed");
@@ -1063,6 +1585,9 @@ impl<'g, 'b> ComputedMapBuild<'g, 'b> {
                 }
                 acc
             });
+        for (k, v) in new_map {
+            computed_map.entry(k).or_default().merge(v);
+        }
 
         Self {
             excludes,
@@ -1257,10 +1782,21 @@ impl<'g, 'a> ValueDescribe<'g, 'a> {
         self,
         output_single_feature: bool,
         unify_target_host: UnifyTargetHostImpl,
-        mut insert_cb: impl FnMut(BuildPlatform, &'a ComputedInnerMap<'g>),
+        // Called with the build platform to insert into, the build platform the features are from,
+        // and the features.
+        mut insert_cb: impl FnMut(BuildPlatform, BuildPlatform, &'a ComputedInnerMap<'g>),
     ) {
         use BuildPlatform::*;
 
+        let replicate = matches!(
+            unify_target_host,
+            UnifyTargetHostImpl::ReplicateTargetOnHost | UnifyTargetHostImpl::EmitBoth
+        );
+        let unify_both = matches!(
+            unify_target_host,
+            UnifyTargetHostImpl::UnifyIfBoth | UnifyTargetHostImpl::ReplicateTargetOnHost
+        );
+
         match self {
             ValueDescribe::None => {
                 // Empty, ignore. (This should probably never happen anyway.)
@@ -1268,28 +1804,28 @@ impl<'g, 'a> ValueDescribe<'g, 'a> {
             ValueDescribe::SingleTarget(target_inner) => {
                 // Just one way to unify these.
                 if output_single_feature {
-                    insert_cb(Target, target_inner);
-                    if unify_target_host == UnifyTargetHostImpl::ReplicateTargetOnHost {
-                        insert_cb(Host, target_inner);
+                    insert_cb(Target, Target, target_inner);
+                    if replicate {
+                        insert_cb(Host, Target, target_inner);
                     }
                 }
             }
             ValueDescribe::SingleHost(host_inner) => {
                 // Just one way to unify other.
                 if output_single_feature {
-                    insert_cb(Host, host_inner);
+                    insert_cb(Host, Host, host_inner);
                 }
             }
             ValueDescribe::MultiTarget(target_inner) => {
                 // Unify features for target.
-                insert_cb(Target, target_inner);
-                if unify_target_host == UnifyTargetHostImpl::ReplicateTargetOnHost {
-                    insert_cb(Host, target_inner);
+                insert_cb(Target, Target, target_inner);
+                if replicate {
+                    insert_cb(Host, Target, target_inner);
                 }
             }
             ValueDescribe::MultiHost(host_inner) => {
                 // Unify features for host.
-                insert_cb(Host, host_inner);
+                insert_cb(Host, Host, host_inner);
             }
             ValueDescribe::SingleMatchingBoth {
                 target_inner,
@@ -1297,8 +1833,8 @@ impl<'g, 'a> ValueDescribe<'g, 'a> {
             } => {
                 // Just one way to unify across both.
                 if output_single_feature {
-                    insert_cb(Target, target_inner);
-                    insert_cb(Host, host_inner);
+                    insert_cb(Target, Target, target_inner);
+                    insert_cb(Host, Host, host_inner);
                 }
             }
             ValueDescribe::SingleNonMatchingBoth {
@@ -1306,11 +1842,11 @@ impl<'g, 'a> ValueDescribe<'g, 'a> {
                 host_inner,
             } => {
                 // Unify features for both across both.
-                insert_cb(Target, target_inner);
-                insert_cb(Host, host_inner);
-                if unify_target_host != UnifyTargetHostImpl::None {
-                    insert_cb(Target, host_inner);
-                    insert_cb(Host, target_inner);
+                insert_cb(Target, Target, target_inner);
+                insert_cb(Host, Host, host_inner);
+                if unify_both {
+                    insert_cb(Target, Host, host_inner);
+                    insert_cb(Host, Target, target_inner);
                 }
             }
             ValueDescribe::MultiTargetSingleHost {
@@ -1318,11 +1854,11 @@ impl<'g, 'a> ValueDescribe<'g, 'a> {
                 host_inner,
             } => {
                 // Unify features for both across both.
-                insert_cb(Target, target_inner);
-                insert_cb(Host, host_inner);
-                if unify_target_host != UnifyTargetHostImpl::None {
-                    insert_cb(Target, host_inner);
-                    insert_cb(Host, target_inner);
+                insert_cb(Target, Target, target_inner);
+                insert_cb(Host, Host, host_inner);
+                if unify_both {
+                    insert_cb(Target, Host, host_inner);
+                    insert_cb(Host, Target, target_inner);
                 }
             }
             ValueDescribe::MultiHostSingleTarget {
@@ -1330,11 +1866,11 @@ impl<'g, 'a> ValueDescribe<'g, 'a> {
                 host_inner,
             } => {
                 // Unify features for both across both.
-                insert_cb(Target, target_inner);
-                insert_cb(Host, host_inner);
-                if unify_target_host != UnifyTargetHostImpl::None {
-                    insert_cb(Target, host_inner);
-                    insert_cb(Host, target_inner);
+                insert_cb(Target, Target, target_inner);
+                insert_cb(Host, Host, host_inner);
+                if unify_both {
+                    insert_cb(Target, Host, host_inner);
+                    insert_cb(Host, Target, target_inner);
                 }
             }
             ValueDescribe::MultiBoth {
@@ -1342,11 +1878,11 @@ impl<'g, 'a> ValueDescribe<'g, 'a> {
                 host_inner,
             } => {
                 // Unify features for both across both.
-                insert_cb(Target, target_inner);
-                insert_cb(Host, host_inner);
-                if unify_target_host != UnifyTargetHostImpl::None {
-                    insert_cb(Target, host_inner);
-                    insert_cb(Host, target_inner);
+                insert_cb(Target, Target, target_inner);
+                insert_cb(Host, Host, host_inner);
+                if unify_both {
+                    insert_cb(Target, Host, host_inner);
+                    insert_cb(Host, Target, target_inner);
                 }
             }
         }
@@ -1357,6 +1893,7 @@ impl<'g, 'a> ValueDescribe<'g, 'a> {
 struct OutputMapBuild<'g> {
     graph: &'g PackageGraph,
     output_map: OutputMap<'g>,
+    target_host_unified: BTreeMap<(OutputKey, &'g PackageId), UnifyTargetHost>,
 }
 
 impl<'g> OutputMapBuild<'g> {
@@ -1364,6 +1901,7 @@ impl<'g> OutputMapBuild<'g> {
         Self {
             graph,
             output_map: OutputMap::new(),
+            target_host_unified: BTreeMap::new(),
         }
     }
 
@@ -1390,17 +1928,26 @@ impl<'g> OutputMapBuild<'g> {
         &mut self,
         values: impl IntoIterator<Item = (Option<usize>, &'g PackageId, &'a ComputedValue<'g>)>,
         output_single_feature: bool,
-        unify_target_host: UnifyTargetHostImpl,
+        unify_target_host: &UnifyTargetHostMap<'g>,
     ) where
         'g: 'a,
     {
         for (platform_idx, dep_id, v) in values {
+            let strategy = unify_target_host.get(platform_idx);
             let describe = v.describe();
             describe.insert(
                 output_single_feature,
-                unify_target_host,
-                |build_platform, inner| {
+                unify_target_host.for_dep(strategy, dep_id),
+                |build_platform, from_build_platform, inner| {
                     self.insert_inner(platform_idx, build_platform, dep_id, inner);
+                    if build_platform != from_build_platform {
+                        let output_key = OutputKey {
+                            platform_idx,
+                            build_platform,
+                        };
+                        self.target_host_unified
+                            .insert((output_key, dep_id), strategy.to_public());
+                    }
                 },
             );
         }
@@ -1461,6 +2008,8 @@ impl<'g> OutputMapBuild<'g> {
     fn finish(
         mut self,
         final_excludes: &HashSet<&'g PackageId>,
+        denied_features: &BTreeSet<FeatureId<'g>>,
+        feature_graph: FeatureGraph<'g>,
         dep_format: DepFormatVersion,
         output_single_feature: bool,
     ) -> OutputMap<'g> {
@@ -1518,6 +2067,10 @@ impl<'g> OutputMapBuild<'g> {
             self.output_map.insert(always_key, always_map);
         }
 
+        if !denied_features.is_empty() {
+            Self::remove_denied_features(&mut self.output_map, denied_features, feature_graph);
+        }
+
         // Remove final-excludes, and get rid of any maps that are empty.
         self.output_map.retain(|_, inner_map| {
             for package_id in final_excludes {
@@ -1529,6 +2082,48 @@ impl<'g> OutputMapBuild<'g> {
         self.output_map
     }
 
+    /// Removes denied features, and any features that would enable them, from the output map.
+    fn remove_denied_features(
+        output_map: &mut OutputMap<'g>,
+        denied_features: &BTreeSet<FeatureId<'g>>,
+        feature_graph: FeatureGraph<'g>,
+    ) {
+        // The same features are often present in several maps, so cache results.
+        let mut cache: AHashMap<FeatureId<'g>, bool> = AHashMap::new();
+        let mut enables_denied = |feature_id: FeatureId<'g>| {
+            *cache.entry(feature_id).or_insert_with(|| {
+                let feature_set = feature_graph
+                    .query_forward([feature_id])
+                    .expect("feature IDs in the output map are valid")
+                    .resolve();
+                denied_features.iter().any(|&denied| {
+                    feature_set
+                        .contains(denied)
+                        .expect("denied feature IDs are valid")
+                })
+            })
+        };
+
+        for inner_map in output_map.values_mut() {
+            inner_map.retain(|&package_id, (_, features)| {
+                // If the package's non-optional dependencies enable a denied feature, the package
+                // can't be output at all.
+                if enables_denied(FeatureId::base(package_id)) {
+                    return false;
+                }
+                if features.is_empty() {
+                    // This is an empty feature set tracked because of output_single_feature.
+                    return true;
+                }
+                features.retain(|&feature| {
+                    !enables_denied(FeatureId::new(package_id, FeatureLabel::Named(feature)))
+                });
+                // If all features were removed, don't output the package at all.
+                !features.is_empty()
+            });
+        }
+    }
+
     /// Removes all features from the map that aren't at the root of the provided feature graph.
     ///
     /// Many crates have a notion of public and private features. Private features are not
@@ -1582,6 +2177,95 @@ enum UnifyTargetHostImpl {
     None,
     UnifyIfBoth,
     ReplicateTargetOnHost,
+    ProcMacroDependencies,
+    EmitBoth,
+}
+
+impl UnifyTargetHostImpl {
+    fn to_public(self) -> UnifyTargetHost {
+        match self {
+            UnifyTargetHostImpl::None => UnifyTargetHost::None,
+            UnifyTargetHostImpl::UnifyIfBoth => UnifyTargetHost::UnifyIfBoth,
+            UnifyTargetHostImpl::ReplicateTargetOnHost => UnifyTargetHost::ReplicateTargetOnHost,
+            UnifyTargetHostImpl::ProcMacroDependencies => UnifyTargetHost::ProcMacroDependencies,
+            UnifyTargetHostImpl::EmitBoth => UnifyTargetHost::EmitBoth,
+        }
+    }
+}
+
+/// The unification strategy to use for each platform.
+#[derive(Debug)]
+struct UnifyTargetHostMap<'g> {
+    default: UnifyTargetHostImpl,
+    // Indexed by platform.
+    platforms: Vec<UnifyTargetHostImpl>,
+    // Only computed if the ProcMacroDependencies strategy is in use.
+    proc_macro_deps: HashSet<&'g PackageId>,
+}
+
+impl<'g> UnifyTargetHostMap<'g> {
+    fn new(builder: &HakariBuilder<'g>) -> Self {
+        let graph = *builder.graph;
+        let default = builder.unify_target_host.to_impl(graph);
+        let platforms: Vec<_> = builder
+            .platforms
+            .iter()
+            .map(|platform| {
+                match builder
+                    .platform_unify_target_host
+                    .get(platform.triple_str())
+                {
+                    Some(unify_target_host) => unify_target_host.to_impl(graph),
+                    None => default,
+                }
+            })
+            .collect();
+
+        let proc_macro_deps = if std::iter::once(&default)
+            .chain(&platforms)
+            .any(|&strategy| strategy == UnifyTargetHostImpl::ProcMacroDependencies)
+        {
+            let proc_macros = graph
+                .packages()
+                .filter(|package| package.is_proc_macro())
+                .map(|package| package.id());
+            graph
+                .query_forward(proc_macros)
+                .expect("package IDs are valid")
+                .resolve_with_fn(|_, link| !link.dev_only())
+                .package_ids(DependencyDirection::Forward)
+                .collect()
+        } else {
+            HashSet::new()
+        };
+
+        Self {
+            default,
+            platforms,
+            proc_macro_deps,
+        }
+    }
+
+    fn get(&self, platform_idx: Option<usize>) -> UnifyTargetHostImpl {
+        match platform_idx {
+            Some(idx) => self.platforms[idx],
+            None => self.default,
+        }
+    }
+
+    /// Returns the strategy to use for this dependency.
+    fn for_dep(&self, strategy: UnifyTargetHostImpl, dep_id: &PackageId) -> UnifyTargetHostImpl {
+        match strategy {
+            UnifyTargetHostImpl::ProcMacroDependencies => {
+                if self.proc_macro_deps.contains(dep_id) {
+                    UnifyTargetHostImpl::UnifyIfBoth
+                } else {
+                    UnifyTargetHostImpl::None
+                }
+            }
+            other => other,
+        }
+    }
 }
 
 impl UnifyTargetHost {
@@ -1590,6 +2274,8 @@ impl UnifyTargetHost {
             UnifyTargetHost::None => UnifyTargetHostImpl::None,
             UnifyTargetHost::UnifyIfBoth => UnifyTargetHostImpl::UnifyIfBoth,
             UnifyTargetHost::ReplicateTargetOnHost => UnifyTargetHostImpl::ReplicateTargetOnHost,
+            UnifyTargetHost::ProcMacroDependencies => UnifyTargetHostImpl::ProcMacroDependencies,
+            UnifyTargetHost::EmitBoth => UnifyTargetHostImpl::EmitBoth,
             UnifyTargetHost::Auto => {
                 let workspace_set = graph.resolve_workspace();
                 // Is any package a proc macro?
@@ -1617,7 +2303,7 @@ impl UnifyTargetHost {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::UnifyTargetHost;
+    use crate::{verify::VerifySuggestion, UnifyTargetHost};
     use fixtures::json::JsonFixture;
 
     #[test]
@@ -1649,4 +2335,319 @@ mod tests {
             "internal build deps => replicate target on host"
         );
     }
+
+    #[test]
+    fn unify_target_host_strategies() {
+        let graph = JsonFixture::metadata_libra_9ffd93b().graph();
+        let compute = |unify_target_host| {
+            let mut builder = HakariBuilder::new(graph, None).expect("valid builder");
+            builder.set_unify_target_host(unify_target_host);
+            builder.compute()
+        };
+        fn target_lines<'g>(hakari: &Hakari<'g>) -> OutputMap<'g> {
+            hakari
+                .output_map
+                .iter()
+                .filter(|(output_key, _)| output_key.build_platform == BuildPlatform::Target)
+                .map(|(output_key, inner_map)| (*output_key, inner_map.clone()))
+                .collect()
+        }
+
+        // Auto resolves to ReplicateTargetOnHost for this fixture, and explain reports that.
+        let hakari = compute(UnifyTargetHost::Auto);
+        let (&(output_key, dep_id), &strategy) = hakari
+            .target_host_unified
+            .iter()
+            .next()
+            .expect("some lines unified across target and host");
+        assert_eq!(strategy, UnifyTargetHost::ReplicateTargetOnHost);
+        let explain = hakari.explain(dep_id).expect("dependency is in the output");
+        assert!(
+            explain
+                .target_host_unified()
+                .any(|(build_platform, _, strategy)| {
+                    build_platform == output_key.build_platform
+                        && strategy == UnifyTargetHost::ReplicateTargetOnHost
+                }),
+            "explain reports the strategy"
+        );
+
+        // With ProcMacroDependencies, only dependencies of proc macros are unified.
+        let proc_macro_deps: HashSet<_> = graph
+            .query_forward(
+                graph
+                    .packages()
+                    .filter(|package| package.is_proc_macro())
+                    .map(|package| package.id()),
+            )
+            .expect("package IDs are valid")
+            .resolve_with_fn(|_, link| !link.dev_only())
+            .package_ids(DependencyDirection::Forward)
+            .collect();
+        let hakari = compute(UnifyTargetHost::ProcMacroDependencies);
+        assert!(
+            !hakari.target_host_unified.is_empty(),
+            "some proc macro dependencies unified"
+        );
+        for (&(_, dep_id), &strategy) in &hakari.target_host_unified {
+            assert_eq!(strategy, UnifyTargetHost::ProcMacroDependencies);
+            assert!(
+                proc_macro_deps.contains(dep_id),
+                "{} is a dependency of a proc macro",
+                dep_id
+            );
+        }
+
+        // With EmitBoth, target lines are replicated on the host, but host features never make
+        // their way to the target.
+        let hakari = compute(UnifyTargetHost::EmitBoth);
+        assert!(
+            !hakari.target_host_unified.is_empty(),
+            "some target lines replicated"
+        );
+        for (&(output_key, _), &strategy) in &hakari.target_host_unified {
+            assert_eq!(strategy, UnifyTargetHost::EmitBoth);
+            assert_eq!(output_key.build_platform, BuildPlatform::Host);
+        }
+        assert_eq!(
+            target_lines(&hakari),
+            target_lines(&compute(UnifyTargetHost::None)),
+            "target lines are the same as with no unification"
+        );
+        assert!(
+            compute(UnifyTargetHost::None)
+                .target_host_unified
+                .is_empty(),
+            "nothing unified with None"
+        );
+    }
+
+    #[test]
+    fn platform_unify_target_host() {
+        let graph = JsonFixture::metadata_libra_9ffd93b().graph();
+        let mut builder = HakariBuilder::new(graph, None).expect("valid builder");
+        builder
+            .set_platforms(["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"])
+            .expect("known platforms")
+            .set_unify_target_host(UnifyTargetHost::None)
+            .set_platform_unify_target_host(
+                "x86_64-unknown-linux-gnu",
+                UnifyTargetHost::ReplicateTargetOnHost,
+            );
+        let hakari = builder.clone().compute();
+
+        assert!(
+            !hakari.target_host_unified.is_empty(),
+            "some platform-specific lines unified"
+        );
+        for (&(output_key, _), &strategy) in &hakari.target_host_unified {
+            assert_eq!(
+                output_key.platform_idx,
+                Some(0),
+                "only Linux-specific lines are unified"
+            );
+            assert_eq!(strategy, UnifyTargetHost::ReplicateTargetOnHost);
+        }
+
+        #[cfg(feature = "cli-support")]
+        {
+            let summary = crate::summaries::HakariBuilderSummary::new(&builder)
+                .expect("builder => summary conversion");
+            let builder2 = HakariBuilder::from_summary(graph, &summary)
+                .expect("summary => builder conversion");
+            assert_eq!(
+                builder2.platform_unify_target_host().collect::<Vec<_>>(),
+                vec![(
+                    "x86_64-unknown-linux-gnu",
+                    UnifyTargetHost::ReplicateTargetOnHost
+                )],
+            );
+        }
+    }
+
+    #[test]
+    fn exclude_dev_dependencies() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let include_dev = |hakari: &Hakari<'_>| {
+            hakari.computed_map.values().any(|value| {
+                value.inner_maps().into_iter().any(|(_, inner_map)| {
+                    inner_map.values().any(|inner_value| {
+                        inner_value
+                            .workspace_packages
+                            .iter()
+                            .any(|&(_, _, include_dev)| include_dev)
+                    })
+                })
+            })
+        };
+        let output_len = |hakari: &Hakari<'_>| {
+            hakari
+                .output_map
+                .values()
+                .map(|deps| deps.len())
+                .sum::<usize>()
+        };
+
+        let mut builder = HakariBuilder::new(graph, None).expect("valid builder");
+        let hakari = builder.clone().compute();
+        assert!(
+            include_dev(&hakari),
+            "dev-dependencies are simulated by default"
+        );
+
+        builder.set_exclude_dev_dependencies(true);
+        let hakari_no_dev = builder.compute();
+        assert!(
+            !include_dev(&hakari_no_dev),
+            "dev-dependencies are not simulated if excluded"
+        );
+        assert!(
+            output_len(&hakari_no_dev) < output_len(&hakari),
+            "excluding dev-dependencies results in a smaller output ({} vs {})",
+            output_len(&hakari_no_dev),
+            output_len(&hakari),
+        );
+    }
+
+    #[test]
+    fn denied_features() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let serde = graph
+            .packages()
+            .find(|package| package.name() == "serde")
+            .expect("serde is in the graph");
+        let serde_features = |hakari: &Hakari<'_>| {
+            hakari
+                .output_map
+                .values()
+                .filter_map(|deps| deps.get(serde.id()))
+                .flat_map(|(_, features)| features.iter().map(|&feature| feature.to_owned()))
+                .collect::<BTreeSet<_>>()
+        };
+
+        let mut builder = HakariBuilder::new(graph, None).expect("valid builder");
+        let hakari = builder.clone().compute();
+        assert!(serde_features(&hakari).contains("std"));
+        assert!(serde_features(&hakari).contains("default"));
+
+        builder
+            .add_denied_features(serde.id(), ["std"])
+            .expect("std is a feature of serde");
+        let hakari = builder.clone().compute();
+        let features = serde_features(&hakari);
+        assert!(!features.contains("std"), "std is denied");
+        assert!(!features.contains("default"), "default enables std");
+        assert!(features.contains("derive"), "derive doesn't enable std");
+
+        builder
+            .add_denied_features(serde.id(), ["not-a-feature"])
+            .expect_err("unknown feature");
+
+        // Verification reports the denied feature as a conflict.
+        let errs = builder.verify().expect_err("no workspace-hack package");
+        let denied: Vec<_> = errs
+            .suggestions()
+            .into_iter()
+            .filter_map(|suggestion| match suggestion {
+                VerifySuggestion::DeniedFeatures { package, features } => {
+                    Some((package.name(), features))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(denied, [("serde", ["std"].into_iter().collect())]);
+    }
+
+    #[test]
+    fn shard_builders() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let workspace = graph.workspace();
+        let id = |name: &str| workspace.member_by_name(name).expect("known member").id();
+
+        let mut builder =
+            HakariBuilder::new(graph, Some(id("guppy-summaries"))).expect("valid hakari package");
+        builder
+            .add_shard(
+                id("proptest-ext"),
+                [
+                    id("cargo-compare"),
+                    id("fixture-manager"),
+                    id("guppy-benchmarks"),
+                    // Hakari packages are never covered by a shard.
+                    id("guppy-summaries"),
+                ],
+            )
+            .expect("valid shard");
+        let hakari_ids = [id("guppy-summaries"), id("proptest-ext")];
+
+        let shard_builders = builder.shard_builders();
+        assert_eq!(shard_builders.len(), 2, "one builder per hakari package");
+
+        let mut covered_by = BTreeMap::new();
+        for (shard_builder, hakari_id) in shard_builders.iter().zip(hakari_ids) {
+            assert_eq!(
+                shard_builder.hakari_package().map(|package| package.id()),
+                Some(hakari_id),
+            );
+            assert_eq!(
+                shard_builder.shards().len(),
+                0,
+                "shard builders have no shards"
+            );
+            for other_id in hakari_ids {
+                assert!(
+                    shard_builder
+                        .is_traversal_excluded(other_id)
+                        .expect("valid package ID"),
+                    "all hakari packages are excluded"
+                );
+            }
+
+            for package in workspace.iter() {
+                if shard_builder
+                    .is_covered(package.id())
+                    .expect("valid package ID")
+                {
+                    assert_eq!(
+                        covered_by.insert(package.name(), hakari_id),
+                        None,
+                        "{} is covered by a single builder",
+                        package.name()
+                    );
+                }
+            }
+
+            // Only covered packages should contribute to the output.
+            let hakari = shard_builder.clone().compute();
+            for value in hakari.computed_map.values() {
+                for (_, inner_map) in value.inner_maps() {
+                    for inner_value in inner_map.values() {
+                        for (package, _, _) in &inner_value.workspace_packages {
+                            assert!(
+                                shard_builder.is_covered(package.id()).unwrap(),
+                                "{} is covered by this builder",
+                                package.name()
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        assert_eq!(
+            covered_by.len(),
+            workspace.iter().len() - hakari_ids.len(),
+            "all workspace members other than hakari packages are covered"
+        );
+        assert_eq!(covered_by["cargo-compare"], id("proptest-ext"));
+        assert_eq!(covered_by["guppy"], id("guppy-summaries"));
+
+        // The original builder covers the same packages as the first shard builder.
+        for package in workspace.iter() {
+            assert_eq!(
+                builder.is_covered(package.id()).unwrap(),
+                covered_by.get(package.name()) == Some(&id("guppy-summaries")),
+            );
+        }
+    }
 }
@@ -1,7 +1,8 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use guppy::Version;
+use guppy::{Version, VersionReq};
+use semver::{Comparator, Op, Prerelease};
 use std::fmt;
 
 /// A formatting wrapper that may print out a minimum version that would match the provided version.
@@ -54,11 +55,67 @@ impl<'a> fmt::Display for VersionDisplay<'a> {
     }
 }
 
+/// Returns the loosest of `reqs` that matches `version`, written out as a caret requirement.
+///
+/// Requirements are compared by their minimum versions. Returns `None` if no requirement matches.
+pub(crate) fn loosest_version_req<'a>(
+    version: &Version,
+    reqs: impl IntoIterator<Item = &'a VersionReq>,
+) -> Option<String> {
+    let lower_bound = reqs
+        .into_iter()
+        .filter(|req| req.matches(version))
+        .filter_map(|req| {
+            // The minimum version of a requirement is the highest of its lower bounds.
+            req.comparators
+                .iter()
+                .filter(|comparator| !matches!(comparator.op, Op::Less | Op::LessEq))
+                .max_by(|a, b| comparator_key(a).cmp(&comparator_key(b)))
+        })
+        .min_by(|a, b| comparator_key(a).cmp(&comparator_key(b)))?;
+
+    let caret = Comparator {
+        op: Op::Caret,
+        ..lower_bound.clone()
+    };
+    // Requirements like ">=0.5, <0.8" have a minimum version whose caret requirement may not match
+    // the version in use.
+    if !caret.matches(version) {
+        return None;
+    }
+
+    let mut out = caret.major.to_string();
+    if let Some(minor) = caret.minor {
+        out.push_str(&format!(".{}", minor));
+        if let Some(patch) = caret.patch {
+            out.push_str(&format!(".{}", patch));
+            if !caret.pre.is_empty() {
+                out.push_str(&format!("-{}", caret.pre));
+            }
+        }
+    }
+    Some(out)
+}
+
+fn comparator_key(comparator: &Comparator) -> (u64, u64, u64, bool, &Prerelease, bool, bool) {
+    (
+        comparator.major,
+        comparator.minor.unwrap_or(0),
+        comparator.patch.unwrap_or(0),
+        // Releases sort after their prereleases.
+        comparator.pre.is_empty(),
+        &comparator.pre,
+        // Prefer shorter requirements like "1" over "1.0.0".
+        comparator.minor.is_some(),
+        comparator.patch.is_some(),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use fixtures::json::*;
-    use guppy::{graph::DependencyDirection, VersionReq};
+    use guppy::graph::DependencyDirection;
 
     #[test]
     fn min_version() {
@@ -108,6 +165,44 @@ mod tests {
         }
     }
 
+    #[test]
+    fn loosest_req() {
+        let cases: &[(&str, &[&str], Option<&str>)] = &[
+            ("1.4.2", &["1.2", "1.3.5", "=1.4.2"], Some("1.2")),
+            ("1.4.2", &["1", "1.0.0"], Some("1")),
+            ("1.4.2", &["~1.4", ">=1.4.1"], Some("1.4")),
+            ("0.7.1", &["0.7.1", "0.7"], Some("0.7")),
+            ("0.7.1", &[">=0.5, <0.8"], None),
+            ("0.7.1", &["0.6"], None),
+            (
+                "2.0.0-rc.2",
+                &["2.0.0-rc.1", "2.0.0-rc.2"],
+                Some("2.0.0-rc.1"),
+            ),
+            ("1.4.2", &["*"], None),
+        ];
+
+        for &(version_str, reqs, expected) in cases {
+            let version = Version::parse(version_str).expect("valid version");
+            let reqs: Vec<_> = reqs
+                .iter()
+                .map(|req| VersionReq::parse(req).expect("valid version req"))
+                .collect();
+            let actual = loosest_version_req(&version, &reqs);
+            assert_eq!(
+                actual.as_deref(),
+                expected,
+                "for version {}, reqs {:?}",
+                version_str,
+                reqs
+            );
+            if let Some(actual) = actual {
+                let req = VersionReq::parse(&actual).expect("valid version req");
+                assert!(req.matches(&version), "{} matches {}", actual, version);
+            }
+        }
+    }
+
     #[test]
     fn min_versions_match() {
         for (&name, fixture) in JsonFixture::all_fixtures() {
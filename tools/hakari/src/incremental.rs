@@ -0,0 +1,349 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Incremental Hakari computation.
+
+use crate::{
+    hakari::{ComputedInnerValue, ComputedMap, ComputedValue},
+    Hakari, HakariBuilder,
+};
+use ahash::AHashMap;
+use guppy::{
+    graph::{cargo::CargoResolverVersion, DependencyDirection, PackageGraph},
+    platform::Platform,
+    PackageId,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    hash::Hasher,
+    sync::Arc,
+};
+use twox_hash::XxHash64;
+
+impl<'g> HakariBuilder<'g> {
+    /// Computes the `Hakari` for this builder, reusing results from a previous computation.
+    ///
+    /// `previous` is usually computed from an earlier state of the same workspace, for example
+    /// before a `Cargo.toml` or `Cargo.lock` change. Packages are compared across the two package
+    /// graphs, and only workspace packages that depend, directly or transitively, on a package that
+    /// was added or changed are recomputed. For small changes to large workspaces, this is much
+    /// faster than [`compute`](Self::compute).
+    ///
+    /// The result is the same as that of `compute`. This falls back to a full computation in
+    /// verify mode, with [simulated dependencies](Self::add_simulated_dependency), or if `previous`
+    /// was computed with different options that affect which dependencies are built: platforms,
+    /// the resolver version, traversal excludes, shards or whether dev-dependencies are excluded.
+    pub fn compute_incremental(self, previous: &Hakari<'_>) -> Hakari<'g> {
+        let incremental = IncrementalBuild::new(&self, previous);
+        Hakari::build(self, incremental)
+    }
+}
+
+/// Results reused from a previous computation.
+#[derive(Debug)]
+pub(crate) struct IncrementalBuild<'g> {
+    /// The part of the computed map contributed by workspace packages that aren't affected.
+    pub(crate) computed_map: ComputedMap<'g>,
+
+    /// The workspace packages that need to be recomputed.
+    pub(crate) affected: HashSet<&'g PackageId>,
+}
+
+impl<'g> IncrementalBuild<'g> {
+    fn new(builder: &HakariBuilder<'g>, previous: &Hakari<'_>) -> Option<Self> {
+        if builder.verify_mode
+            || !builder.simulated_deps.is_empty()
+            || !previous.builder.simulated_deps.is_empty()
+            || ComputedMapOptions::new(builder) != ComputedMapOptions::new(&previous.builder)
+        {
+            return None;
+        }
+
+        let graph = builder.graph();
+        let old_fingerprints = package_fingerprints(previous.builder.graph());
+        let changed = package_fingerprints(graph)
+            .into_iter()
+            .filter(|(package_id, fingerprint)| {
+                old_fingerprints.get(*package_id) != Some(fingerprint)
+            })
+            .map(|(package_id, _)| package_id);
+
+        // Packages excluded during traversal (including Hakari packages, whose contents change
+        // whenever the output does) are never built, so changes to them don't propagate.
+        let excludes = builder.make_traversal_excludes();
+        let changed_closure = graph
+            .query_reverse(changed)
+            .expect("package IDs are valid")
+            .resolve_with_fn(|_, link| {
+                !excludes.is_excluded(link.from().id()) && !excludes.is_excluded(link.to().id())
+            });
+        let affected: HashSet<_> = graph
+            .workspace()
+            .iter()
+            .map(|package| package.id())
+            .filter(|package_id| changed_closure.contains(package_id).unwrap_or(false))
+            .collect();
+
+        let computed_map = reuse_computed_map(graph, &previous.computed_map, &affected)?;
+        Some(Self {
+            computed_map,
+            affected,
+        })
+    }
+}
+
+/// Options that determine the computed map for a workspace package.
+#[derive(Debug, Eq, PartialEq)]
+struct ComputedMapOptions<'a> {
+    platforms: &'a [Arc<Platform>],
+    resolver: CargoResolverVersion,
+    exclude_dev_dependencies: bool,
+    traversal_excludes: BTreeSet<&'a str>,
+    members: Option<BTreeSet<&'a str>>,
+    shards: Vec<(&'a str, BTreeSet<&'a str>)>,
+}
+
+impl<'a> ComputedMapOptions<'a> {
+    fn new(builder: &'a HakariBuilder<'_>) -> Self {
+        Self {
+            platforms: &builder.platforms,
+            resolver: builder.resolver(),
+            exclude_dev_dependencies: builder.exclude_dev_dependencies(),
+            traversal_excludes: builder.traversal_excludes().map(|id| id.repr()).collect(),
+            members: builder
+                .members
+                .as_ref()
+                .map(|members| members.iter().map(|id| id.repr()).collect()),
+            shards: builder
+                .shards()
+                .map(|shard| {
+                    (
+                        shard.hakari_package().id().repr(),
+                        shard.members().map(|id| id.repr()).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Returns a hash of the parts of each package that can influence feature resolution: its feature
+/// definitions and its dependencies.
+fn package_fingerprints(graph: &PackageGraph) -> AHashMap<&PackageId, u64> {
+    let feature_graph = graph.feature_graph();
+
+    // Links across packages, keyed by the package they're from.
+    let mut descriptions: BTreeMap<&PackageId, Vec<String>> = BTreeMap::new();
+    for link in feature_graph
+        .resolve_all()
+        .conditional_links(DependencyDirection::Forward)
+    {
+        let (from, to) = link.endpoints();
+        descriptions
+            .entry(from.package_id())
+            .or_default()
+            .push(format!(
+                "link {:?} -> {} {:?}: {:?} {:?} {:?}",
+                from.label(),
+                to.package_id(),
+                to.label(),
+                link.normal(),
+                link.build(),
+                link.dev(),
+            ));
+    }
+
+    graph
+        .packages()
+        .map(|package| {
+            let mut description = descriptions.remove(package.id()).unwrap_or_default();
+            for link in package.direct_links() {
+                description.push(format!(
+                    "dep {} {} {}: {:?} {:?} {:?}",
+                    link.to().id(),
+                    link.dep_name(),
+                    link.resolved_name(),
+                    link.normal(),
+                    link.build(),
+                    link.dev(),
+                ));
+            }
+
+            // Features within the package that each feature enables.
+            let features = feature_graph
+                .all_features_for(package.id())
+                .expect("package ID is valid");
+            for label in features.labels() {
+                let enabled = feature_graph
+                    .query_forward([(package.id(), *label)])
+                    .expect("feature ID is valid")
+                    .resolve_with_fn(|_, _| false);
+                let enabled = enabled
+                    .features_for(package.id())
+                    .expect("package ID is valid")
+                    .map(|features| features.labels().to_vec())
+                    .unwrap_or_default();
+                description.push(format!("feature {:?}: {:?}", label, enabled));
+            }
+
+            // Links are returned in graph order, which isn't stable across graphs.
+            description.sort_unstable();
+            let mut hasher = XxHash64::default();
+            for line in &description {
+                hasher.write(line.as_bytes());
+                hasher.write_u8(0);
+            }
+            (package.id(), hasher.finish())
+        })
+        .collect()
+}
+
+/// Converts the parts of a previous computed map contributed by workspace packages that aren't
+/// affected into the new graph.
+///
+/// Returns `None` if the previous map refers to dependencies or features that no longer exist.
+fn reuse_computed_map<'g>(
+    graph: &'g PackageGraph,
+    previous: &ComputedMap<'_>,
+    affected: &HashSet<&'g PackageId>,
+) -> Option<ComputedMap<'g>> {
+    let mut computed_map = ComputedMap::new();
+    for (&(platform_idx, dep_id), value) in previous {
+        let mut new_value = ComputedValue::default();
+        for (build_platform, inner_map) in value.inner_maps() {
+            for (features, inner_value) in inner_map {
+                let workspace_packages: Vec<_> = inner_value
+                    .workspace_packages
+                    .iter()
+                    .filter_map(|&(package, feature_filter, include_dev)| {
+                        // Workspace packages that were removed aren't present in the new graph.
+                        let package = graph.metadata(package.id()).ok()?;
+                        (!affected.contains(package.id())).then_some((
+                            package,
+                            feature_filter,
+                            include_dev,
+                        ))
+                    })
+                    .collect();
+                // Entries that were only added during fixups are recomputed as well.
+                if workspace_packages.is_empty() {
+                    continue;
+                }
+
+                let dep = graph.metadata(dep_id).ok()?;
+                let features = features
+                    .iter()
+                    .map(|&feature| dep.named_features().find(|&name| name == feature))
+                    .collect::<Option<_>>()?;
+                new_value.get_inner_mut(build_platform).insert(
+                    features,
+                    ComputedInnerValue {
+                        workspace_packages,
+                        fixed_up: false,
+                    },
+                );
+            }
+        }
+
+        if !new_value.target_inner.is_empty() || !new_value.host_inner.is_empty() {
+            let dep_id = graph.metadata(dep_id).ok()?.id();
+            computed_map.insert((platform_idx, dep_id), new_value);
+        }
+    }
+    Some(computed_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+    use guppy::graph::{cargo::BuildPlatform, feature::StandardFeatures};
+
+    #[test]
+    fn compute_incremental() {
+        let old_graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let new_graph = JsonFixture::metadata_guppy_869476c().graph();
+        let previous = HakariBuilder::new(old_graph, None)
+            .expect("valid builder")
+            .compute();
+
+        // With no changes to the graph, everything is reused.
+        let builder = HakariBuilder::new(old_graph, None).expect("valid builder");
+        let incremental = IncrementalBuild::new(&builder, &previous).expect("options match");
+        assert!(incremental.affected.is_empty(), "no packages affected");
+        assert_same(
+            &builder.clone().compute_incremental(&previous),
+            &builder.compute(),
+        );
+
+        // Across graphs, the result matches a full computation.
+        let builder = HakariBuilder::new(new_graph, None).expect("valid builder");
+        let incremental = IncrementalBuild::new(&builder, &previous).expect("options match");
+        assert!(
+            !incremental.affected.is_empty(),
+            "some packages affected by changes"
+        );
+        assert!(
+            incremental.affected.len() < new_graph.workspace().iter().count(),
+            "some packages not affected by changes: {:?}",
+            incremental.affected,
+        );
+        assert_same(
+            &builder.clone().compute_incremental(&previous),
+            &builder.compute(),
+        );
+
+        // Changing options falls back to a full computation.
+        let mut builder = HakariBuilder::new(new_graph, None).expect("valid builder");
+        builder.set_exclude_dev_dependencies(true);
+        assert!(
+            IncrementalBuild::new(&builder, &previous).is_none(),
+            "options don't match"
+        );
+        assert_same(
+            &builder.clone().compute_incremental(&previous),
+            &builder.compute(),
+        );
+    }
+
+    fn assert_same(incremental: &Hakari<'_>, full: &Hakari<'_>) {
+        assert_eq!(incremental.output_map, full.output_map, "output maps match");
+        assert_eq!(
+            normalize(&incremental.computed_map),
+            normalize(&full.computed_map),
+            "computed maps match"
+        );
+    }
+
+    type NormalizedMap<'g> = BTreeMap<
+        (
+            Option<usize>,
+            &'g PackageId,
+            BuildPlatform,
+            BTreeSet<&'g str>,
+        ),
+        (BTreeSet<(&'g PackageId, StandardFeatures, bool)>, bool),
+    >;
+
+    fn normalize<'g>(computed_map: &ComputedMap<'g>) -> NormalizedMap<'g> {
+        let mut normalized = BTreeMap::new();
+        for (&(platform_idx, dep_id), value) in computed_map {
+            for (build_platform, inner_map) in value.inner_maps() {
+                for (features, inner_value) in inner_map {
+                    let workspace_packages = inner_value
+                        .workspace_packages
+                        .iter()
+                        .map(|(package, features, include_dev)| {
+                            (package.id(), *features, *include_dev)
+                        })
+                        .collect();
+                    normalized.insert(
+                        (platform_idx, dep_id, build_platform, features.clone()),
+                        (workspace_packages, inner_value.fixed_up),
+                    );
+                }
+            }
+        }
+        normalized
+    }
+}
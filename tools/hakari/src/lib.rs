@@ -122,22 +122,31 @@
 //!
 //! These features will be added as time permits.
 
+mod cargo_config;
 mod cargo_toml;
 #[cfg(feature = "cli-support")]
 pub mod cli_ops;
 pub mod explain;
 mod hakari;
 mod helpers;
+mod incremental;
+mod omitted;
 #[cfg(feature = "proptest1")]
 mod proptest_helpers;
+pub mod simulate;
 #[cfg(feature = "cli-support")]
 pub mod summaries;
 mod toml_out;
 pub mod verify;
 
 pub use crate::{
+    cargo_config::CargoConfigError,
     cargo_toml::*,
-    hakari::{DepFormatVersion, Hakari, HakariBuilder, UnifyTargetHost, WorkspaceHackLineStyle},
+    hakari::{
+        DepFormatVersion, Hakari, HakariBuilder, HakariShard, UnifyTargetHost,
+        WorkspaceHackLineStyle,
+    },
+    omitted::*,
     toml_out::*,
 };
 
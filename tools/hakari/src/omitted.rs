@@ -0,0 +1,267 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::Hakari;
+use guppy::{
+    graph::{cargo::CargoOptions, feature::StandardFeatures, DependencyDirection, PackageMetadata},
+    platform::PlatformSpec,
+    PackageId,
+};
+use std::{collections::HashSet, fmt};
+
+/// A third-party dependency that isn't in the workspace-hack, along with the reason it was left
+/// out.
+///
+/// Returned by [`Hakari::omitted`].
+#[derive(Clone, Debug)]
+pub struct OmittedDependency<'g> {
+    /// The dependency.
+    pub package: PackageMetadata<'g>,
+
+    /// Why the dependency isn't in the workspace-hack.
+    pub reason: OmittedReason,
+}
+
+/// The reason a third-party dependency isn't in the workspace-hack.
+///
+/// Part of [`OmittedDependency`].
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum OmittedReason {
+    /// The dependency is always built with the same feature set, so it doesn't need to be unified.
+    ///
+    /// Such dependencies are still written out if
+    /// [`output_single_feature`](crate::HakariBuilder::set_output_single_feature) is set.
+    SingleFeatureSet,
+
+    /// The dependency was [excluded during
+    /// traversal](crate::HakariBuilder::add_traversal_excludes).
+    TraversalExcluded,
+
+    /// The dependency was [excluded from the final
+    /// output](crate::HakariBuilder::add_final_excludes).
+    FinalExcluded,
+
+    /// The dependency's non-optional dependencies enable a [denied
+    /// feature](crate::HakariBuilder::add_denied_features).
+    DeniedFeatures,
+
+    /// The dependency comes from a source that Hakari can't write out, such as an unrecognized
+    /// external source.
+    UnsupportedSource,
+
+    /// The dependency is only built on platforms that aren't in the [list of
+    /// platforms](crate::HakariBuilder::set_platforms).
+    PlatformFiltered,
+
+    /// The dependency isn't built by any workspace package, e.g. because it's an optional
+    /// dependency that isn't enabled, or it's only depended on by excluded packages.
+    NotBuilt,
+}
+
+impl OmittedReason {
+    /// Returns a short, kebab-case name for this reason.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OmittedReason::SingleFeatureSet => "single-feature-set",
+            OmittedReason::TraversalExcluded => "traversal-excluded",
+            OmittedReason::FinalExcluded => "final-excluded",
+            OmittedReason::DeniedFeatures => "denied-features",
+            OmittedReason::UnsupportedSource => "unsupported-source",
+            OmittedReason::PlatformFiltered => "platform-filtered",
+            OmittedReason::NotBuilt => "not-built",
+        }
+    }
+}
+
+impl fmt::Display for OmittedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OmittedReason::SingleFeatureSet => write!(f, "always built with one feature set"),
+            OmittedReason::TraversalExcluded => write!(f, "excluded during traversal"),
+            OmittedReason::FinalExcluded => write!(f, "excluded from the final output"),
+            OmittedReason::DeniedFeatures => write!(f, "enables a denied feature"),
+            OmittedReason::UnsupportedSource => write!(f, "unsupported source"),
+            OmittedReason::PlatformFiltered => {
+                write!(f, "only built on platforms not listed in the config")
+            }
+            OmittedReason::NotBuilt => write!(f, "not built by any workspace package"),
+        }
+    }
+}
+
+impl<'g> Hakari<'g> {
+    /// Returns the third-party dependencies of the workspace packages covered by this `Hakari`
+    /// that aren't in the workspace-hack, along with the reason each one was left out.
+    ///
+    /// The dependencies are returned in forward dependency order.
+    pub fn omitted(&self) -> Vec<OmittedDependency<'g>> {
+        let builder = &self.builder;
+        let graph = builder.graph();
+        let excludes = builder.make_traversal_excludes();
+        let final_excludes: HashSet<_> = builder.final_excludes().collect();
+
+        let covered: Vec<_> = graph
+            .workspace()
+            .iter()
+            .filter(|package| builder.covers(package.id()))
+            .map(|package| package.id())
+            .collect();
+        let candidates = graph
+            .query_forward(covered.iter().copied())
+            .expect("workspace package IDs are valid")
+            .resolve();
+
+        let in_output: HashSet<&'g PackageId> = self
+            .output_map
+            .values()
+            .flat_map(|inner_map| inner_map.keys().copied())
+            .collect();
+        let in_computed: HashSet<&'g PackageId> = self
+            .computed_map
+            .keys()
+            .map(|&(_, package_id)| package_id)
+            .collect();
+        let any_platform = self.built_on_any_platform(&covered);
+
+        candidates
+            .packages(DependencyDirection::Forward)
+            .filter(|package| !package.in_workspace() && !in_output.contains(package.id()))
+            .map(|package| {
+                let package_id = package.id();
+                let reason = if excludes.is_excluded(package_id) {
+                    OmittedReason::TraversalExcluded
+                } else if final_excludes.contains(package_id) {
+                    OmittedReason::FinalExcluded
+                } else if package.source().is_external()
+                    && package.source().parse_external().is_none()
+                {
+                    OmittedReason::UnsupportedSource
+                } else if in_computed.contains(package_id) {
+                    if self.is_multi_feature(package_id) {
+                        OmittedReason::DeniedFeatures
+                    } else {
+                        OmittedReason::SingleFeatureSet
+                    }
+                } else if any_platform.contains(package_id) {
+                    OmittedReason::PlatformFiltered
+                } else {
+                    OmittedReason::NotBuilt
+                };
+                OmittedDependency { package, reason }
+            })
+            .collect()
+    }
+
+    /// Returns true if the package is built with more than one feature set on any platform.
+    fn is_multi_feature(&self, package_id: &PackageId) -> bool {
+        self.computed_map
+            .iter()
+            .filter(|((_, id), _)| *id == package_id)
+            .any(|(_, value)| value.target_inner.len() > 1 || value.host_inner.len() > 1)
+    }
+
+    /// Returns the packages built by the given workspace packages on any platform, with all
+    /// features and dev-dependencies enabled.
+    fn built_on_any_platform(&self, workspace_ids: &[&'g PackageId]) -> HashSet<&'g PackageId> {
+        let builder = &self.builder;
+        let excludes = builder.make_traversal_excludes();
+        let initials = builder
+            .graph()
+            .resolve_ids(
+                workspace_ids
+                    .iter()
+                    .copied()
+                    .filter(|package_id| !excludes.is_excluded(package_id)),
+            )
+            .expect("workspace package IDs are valid")
+            .to_feature_set(StandardFeatures::All);
+
+        let mut cargo_options = CargoOptions::new();
+        cargo_options
+            .set_include_dev(true)
+            .set_resolver(builder.resolver())
+            .set_platform(PlatformSpec::Any)
+            .add_omitted_packages(excludes.iter());
+        let cargo_set = initials
+            .into_cargo_set(&cargo_options)
+            .expect("cargo resolution should succeed");
+        let target_set = cargo_set.target_features().to_package_set();
+        let host_set = cargo_set.host_features().to_package_set();
+        target_set
+            .package_ids(DependencyDirection::Forward)
+            .chain(host_set.package_ids(DependencyDirection::Forward))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HakariBuilder;
+    use fixtures::json::JsonFixture;
+
+    #[test]
+    fn omitted_reasons() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let hakari = HakariBuilder::new(graph, None)
+            .expect("valid builder")
+            .compute();
+        let omitted = hakari.omitted();
+        assert!(
+            omitted
+                .iter()
+                .any(|dep| dep.reason == OmittedReason::SingleFeatureSet),
+            "some dependencies are built with a single feature set"
+        );
+        assert!(
+            omitted
+                .iter()
+                .any(|dep| dep.reason == OmittedReason::PlatformFiltered),
+            "with no platforms specified, some platform-specific dependencies aren't built"
+        );
+        for dep in &omitted {
+            assert!(!dep.package.in_workspace(), "only third-party packages");
+            assert!(
+                hakari
+                    .output_map
+                    .values()
+                    .all(|inner_map| !inner_map.contains_key(dep.package.id())),
+                "omitted dependency {} is not in the output",
+                dep.package.name()
+            );
+        }
+
+        // Excluding dependencies in the output causes them to be reported as excluded.
+        let mut in_output = hakari
+            .output_map
+            .values()
+            .flat_map(|inner_map| inner_map.keys().copied());
+        let traversal_excluded = in_output.next().expect("output is non-empty");
+        let final_excluded = in_output
+            .find(|&package_id| package_id != traversal_excluded)
+            .expect("output has at least two dependencies");
+
+        let mut builder = HakariBuilder::new(graph, None).expect("valid builder");
+        builder
+            .add_traversal_excludes([traversal_excluded])
+            .expect("valid package ID")
+            .add_final_excludes([final_excluded])
+            .expect("valid package ID");
+        let reason = |omitted: &[OmittedDependency<'_>], package_id| {
+            omitted
+                .iter()
+                .find(|dep| dep.package.id() == package_id)
+                .map(|dep| dep.reason)
+        };
+        let omitted = builder.compute().omitted();
+        assert_eq!(
+            reason(&omitted, traversal_excluded),
+            Some(OmittedReason::TraversalExcluded)
+        );
+        assert_eq!(
+            reason(&omitted, final_excluded),
+            Some(OmittedReason::FinalExcluded)
+        );
+    }
+}
@@ -43,6 +43,7 @@ impl<'g> HakariBuilder<'g> {
             hash_set(graph.proptest1_id_strategy(), 0..8),
             any::<UnifyTargetHost>(),
             any::<bool>(),
+            any::<bool>(),
             any::<DepFormatVersion>(),
             any::<WorkspaceHackLineStyle>(),
         )
@@ -55,6 +56,7 @@ impl<'g> HakariBuilder<'g> {
                     final_excludes,
                     unify_target_host,
                     output_single_feature,
+                    exclude_dev_dependencies,
                     dep_format_version,
                     line_style,
                 )| {
@@ -75,7 +77,8 @@ impl<'g> HakariBuilder<'g> {
                         .set_unify_target_host(unify_target_host)
                         .set_dep_format_version(dep_format_version)
                         .set_workspace_hack_line_style(line_style)
-                        .set_output_single_feature(output_single_feature);
+                        .set_output_single_feature(output_single_feature)
+                        .set_exclude_dev_dependencies(exclude_dev_dependencies);
                     builder
                 },
             )
@@ -0,0 +1,156 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Predict how the workspace-hack would change if a dependency were added.
+//!
+//! Simulations are performed through [`HakariBuilder::simulate_add`], or through
+//! [`HakariBuilder::add_simulated_dependency`] for more than one dependency at a time. They work
+//! on the existing [`PackageGraph`](guppy::graph::PackageGraph), so the dependency must already be
+//! present somewhere in the graph (for example, as a dependency of another workspace package).
+//! Simulating a dependency that isn't in the `Cargo.lock` at all requires adding it to a manifest
+//! and regenerating the graph.
+
+use crate::{Hakari, HakariBuilder, HakariOutputOptions, TomlOutError};
+use guppy::{graph::feature::FeatureLabel, PackageId};
+
+impl<'g> HakariBuilder<'g> {
+    /// Simulates adding a normal dependency to a workspace package, without changing its manifest.
+    ///
+    /// `features` are the named features enabled for the dependency, as in the `features` list in
+    /// `Cargo.toml`. Unlike in `Cargo.toml`, default features are not enabled automatically:
+    /// include `"default"` in `features` to enable them.
+    ///
+    /// Subsequent calls to [`compute`](Self::compute) will behave as if `to_package` depended on
+    /// `dep_id` with these features. This method can be called several times to simulate adding
+    /// several dependencies at once.
+    ///
+    /// Returns an error if `dep_id` or any of the features aren't known to the graph, or if
+    /// `to_package` isn't in the workspace.
+    pub fn add_simulated_dependency<'a>(
+        &mut self,
+        dep_id: &PackageId,
+        features: impl IntoIterator<Item = &'a str>,
+        to_package: &PackageId,
+    ) -> Result<&mut Self, guppy::Error> {
+        let to_package = self.workspace_metadata(to_package)?;
+        let dep = self.graph().metadata(dep_id)?;
+
+        let feature_ids = std::iter::once(FeatureLabel::Base)
+            .chain(features.into_iter().map(FeatureLabel::Named))
+            .map(|label| (dep.id(), label));
+        let feature_set = self.graph().feature_graph().resolve_ids(feature_ids)?;
+
+        self.simulated_deps
+            .entry(to_package.id())
+            .and_modify(|existing| *existing = existing.union(&feature_set))
+            .or_insert(feature_set);
+        Ok(self)
+    }
+
+    /// Simulates adding a normal dependency to a workspace package, returning the `Hakari`
+    /// instances before and after the dependency is added.
+    ///
+    /// For more about the arguments, see
+    /// [`add_simulated_dependency`](Self::add_simulated_dependency).
+    pub fn simulate_add<'a>(
+        &self,
+        dep_id: &PackageId,
+        features: impl IntoIterator<Item = &'a str>,
+        to_package: &PackageId,
+    ) -> Result<HakariSimulation<'g>, guppy::Error> {
+        let mut after = self.clone();
+        after.add_simulated_dependency(dep_id, features, to_package)?;
+        Ok(HakariSimulation {
+            before: self.clone().compute(),
+            after: after.compute(),
+        })
+    }
+}
+
+/// The result of simulating adding a dependency.
+///
+/// Returned by [`HakariBuilder::simulate_add`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct HakariSimulation<'g> {
+    /// The `Hakari` instance without the simulated dependency.
+    pub before: Hakari<'g>,
+
+    /// The `Hakari` instance with the simulated dependency added.
+    pub after: Hakari<'g>,
+}
+
+impl<'g> HakariSimulation<'g> {
+    /// Returns true if adding the dependency would change the contents of the workspace-hack.
+    pub fn is_changed(&self) -> bool {
+        self.before.output_map != self.after.output_map
+    }
+
+    /// Returns the TOML for the workspace-hack before and after the simulated dependency is added,
+    /// in that order.
+    ///
+    /// The two strings can be compared with [`diffy::create_patch`](crate::diffy::create_patch)
+    /// to produce a diff.
+    pub fn to_toml_strings(
+        &self,
+        options: &HakariOutputOptions,
+    ) -> Result<(String, String), TomlOutError> {
+        Ok((
+            self.before.to_toml_string(options)?,
+            self.after.to_toml_string(options)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+
+    #[test]
+    fn simulate_add() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let workspace = graph.workspace();
+        let builder = HakariBuilder::new(graph, None).expect("valid builder");
+
+        let guppy_id = workspace
+            .member_by_name("guppy")
+            .expect("known member")
+            .id();
+        let cmdlib_id = workspace
+            .member_by_name("guppy-cmdlib")
+            .expect("known member")
+            .id();
+        let serde = graph
+            .packages()
+            .find(|package| package.name() == "serde")
+            .expect("serde is in the graph");
+
+        // guppy-cmdlib doesn't depend on serde directly, but gets all the features guppy enables
+        // through guppy. Simulating an extra dependency with those features doesn't change anything.
+        let simulation = builder
+            .simulate_add(serde.id(), ["default", "derive"], cmdlib_id)
+            .expect("simulation succeeded");
+        assert!(!simulation.is_changed(), "serde features already unified");
+
+        // Adding serde with a new feature to a workspace package changes the serde line in the
+        // workspace-hack.
+        let simulation = builder
+            .simulate_add(serde.id(), ["default", "derive", "rc"], guppy_id)
+            .expect("simulation succeeded");
+        assert!(simulation.is_changed(), "rc feature is new");
+        let (before, after) = simulation
+            .to_toml_strings(&HakariOutputOptions::new())
+            .expect("TOML output succeeded");
+        assert!(!before.contains("\"rc\""), "rc not present before");
+        assert!(after.contains("\"rc\""), "rc present after: {}", after);
+
+        // Errors for unknown features and non-workspace packages.
+        builder
+            .simulate_add(serde.id(), ["not-a-feature"], guppy_id)
+            .expect_err("unknown feature");
+        builder
+            .simulate_add(serde.id(), [], serde.id())
+            .expect_err("serde is not in the workspace");
+    }
+}
@@ -6,16 +6,30 @@
 //! Requires the `cli-support` feature to be enabled.
 
 use crate::{
+    cargo_config::cargo_config_registries,
+    cargo_toml::read_workspace_resolver,
     hakari::{DepFormatVersion, WorkspaceHackLineStyle},
-    HakariBuilder, HakariOutputOptions, TomlOutError, UnifyTargetHost,
+    CargoConfigError, CargoTomlError, HakariBuilder, HakariOutputOptions, TomlOutError,
+    UnifyTargetHost, VersionStyle,
 };
+use camino::Utf8Path;
 use guppy::{
     errors::TargetSpecError,
-    graph::{cargo::CargoResolverVersion, summaries::PackageSetSummary, PackageGraph},
+    graph::{
+        cargo::CargoResolverVersion,
+        feature::{FeatureId, FeatureLabel},
+        summaries::PackageSetSummary,
+        DependencyDirection, PackageGraph, PackageSet,
+    },
 };
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, fmt, str::FromStr};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error, fmt,
+    str::FromStr,
+};
 use toml::Serializer;
+use toml_edit::{Document, Item};
 
 /// The location of the configuration used by `cargo hakari`, relative to the workspace root.
 pub static DEFAULT_CONFIG_PATH: &str = ".config/hakari.toml";
@@ -30,6 +44,12 @@ pub static FALLBACK_CONFIG_PATH: &str = ".guppy/hakari.toml";
 #[serde(rename_all = "kebab-case")]
 #[non_exhaustive]
 pub struct HakariConfig {
+    /// The version of the configuration format.
+    ///
+    /// Configs that don't specify a version are treated as [`ConfigVersion::V1`].
+    #[serde(default)]
+    pub config_version: ConfigVersion,
+
     /// Builder options.
     #[serde(flatten)]
     pub builder: HakariBuilderSummary,
@@ -39,12 +59,200 @@ pub struct HakariConfig {
     pub output: OutputOptionsSummary,
 }
 
+impl HakariConfig {
+    /// The top-level keys recognized in a `hakari.toml` file.
+    ///
+    /// Unknown keys are ignored while deserializing, so this can be used to detect typos.
+    pub const KEYS: &'static [&'static str] = &[
+        "config-version",
+        "hakari-package",
+        "resolver",
+        // Deprecated alias for "resolver".
+        "version",
+        "unify-target-host",
+        "output-single-feature",
+        "exclude-dev-dependencies",
+        "dep-format-version",
+        "workspace-hack-line-style",
+        "platforms",
+        "traversal-excludes",
+        "final-excludes",
+        "platform-unify-target-host",
+        "max-features",
+        "registries",
+        "shards",
+        "exact-versions",
+        "version-style",
+        "absolute-paths",
+        "group-by-source",
+        "max-line-width",
+        "cfg-platforms",
+        "builder-summary",
+    ];
+}
+
 impl FromStr for HakariConfig {
     type Err = toml::de::Error;
 
     /// Deserializes a [`HakariConfig`] from the given TOML string.
+    ///
+    /// Returns an error if the config version requires options that aren't specified.
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        toml::from_str(input)
+        let config: Self = toml::from_str(input)?;
+        if config.config_version >= ConfigVersion::V2 {
+            // serde defaults can't be distinguished from values that were specified, so look at
+            // the raw TOML.
+            let raw: toml::Value = toml::from_str(input)?;
+            if raw.get("dep-format-version").is_none() {
+                return Err(serde::de::Error::custom(format!(
+                    "dep-format-version must be specified with config-version \"{}\"",
+                    config.config_version
+                )));
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// The version of the `hakari.toml` configuration format.
+///
+/// New versions are introduced when the meaning of an existing config would otherwise change. Use
+/// [`migrate_config`] to upgrade a config to the latest version without changing its behavior.
+///
+/// Requires the `cli-support` feature to be enabled.
+#[derive(Copy, Clone, Debug, Default, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[non_exhaustive]
+pub enum ConfigVersion {
+    /// The original configuration format, used if `config-version` isn't specified.
+    ///
+    /// In this version, `dep-format-version` defaults to `"1"`.
+    #[serde(rename = "1")]
+    #[default]
+    V1,
+
+    /// Options whose defaults have changed over time must be specified explicitly. Currently, this
+    /// is `dep-format-version`.
+    #[serde(rename = "2")]
+    V2,
+}
+
+impl ConfigVersion {
+    /// Returns the latest config version supported by this version of `hakari`.
+    pub fn latest() -> Self {
+        ConfigVersion::V2
+    }
+}
+
+impl fmt::Display for ConfigVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigVersion::V1 => write!(f, "1"),
+            ConfigVersion::V2 => write!(f, "2"),
+        }
+    }
+}
+
+/// Upgrades the contents of a `hakari.toml` file to the [latest config
+/// version](ConfigVersion::latest), returning the new contents.
+///
+/// Options whose defaults differ between versions are written out explicitly, so the upgraded
+/// config behaves the same way as the original one. Comments and formatting are preserved.
+///
+/// Returns the contents unchanged if the config is already at the latest version.
+///
+/// Requires the `cli-support` feature to be enabled.
+pub fn migrate_config(contents: &str) -> Result<String, ConfigMigrateError> {
+    let mut doc: Document = contents
+        .parse()
+        .map_err(|err| ConfigMigrateError::Parse(Box::new(err)))?;
+    let version = match doc.get("config-version") {
+        None => ConfigVersion::V1,
+        Some(item) => match item.as_str() {
+            Some("1") => ConfigVersion::V1,
+            Some("2") => ConfigVersion::V2,
+            _ => {
+                return Err(ConfigMigrateError::UnknownConfigVersion {
+                    value: item.to_string().trim().to_owned(),
+                })
+            }
+        },
+    };
+    if version == ConfigVersion::latest() {
+        return Ok(contents.to_owned());
+    }
+
+    // The comment at the top of the file is stored as part of the first key, and must stay there
+    // after config-version is inserted.
+    let first_key = doc
+        .as_table()
+        .iter()
+        .find(|(_, item)| item.is_value())
+        .map(|(key, _)| key.to_owned());
+    let header_decor = first_key
+        .as_deref()
+        .and_then(|key| doc.as_table_mut().key_decor_mut(key))
+        .map(std::mem::take);
+
+    // V1 -> V2: make the implied default for dep-format-version explicit.
+    let table = doc.as_table_mut();
+    if !table.contains_key("dep-format-version") {
+        table.insert(
+            "dep-format-version",
+            Item::Value(DepFormatVersion::V1.to_string().into()),
+        );
+    }
+
+    table.insert(
+        "config-version",
+        Item::Value(ConfigVersion::latest().to_string().into()),
+    );
+    // This is a stable sort, so the order of other keys is preserved.
+    table.sort_values_by(|k1, _, k2, _| {
+        (k2.get() == "config-version").cmp(&(k1.get() == "config-version"))
+    });
+    if let (Some(header), Some(decor)) = (header_decor, table.key_decor_mut("config-version")) {
+        *decor = header;
+    }
+
+    Ok(doc.to_string())
+}
+
+/// An error that occurred while migrating a `hakari.toml` file.
+///
+/// Returned by [`migrate_config`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigMigrateError {
+    /// The config could not be parsed as TOML.
+    Parse(Box<dyn error::Error + Send + Sync>),
+
+    /// The config specified a `config-version` that isn't known to this version of `hakari`.
+    UnknownConfigVersion {
+        /// The value of `config-version`.
+        value: String,
+    },
+}
+
+impl fmt::Display for ConfigMigrateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigMigrateError::Parse(_) => write!(f, "error parsing Hakari config"),
+            ConfigMigrateError::UnknownConfigVersion { value } => write!(
+                f,
+                "unknown config-version {} (latest supported: \"{}\")",
+                value,
+                ConfigVersion::latest()
+            ),
+        }
+    }
+}
+
+impl error::Error for ConfigMigrateError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ConfigMigrateError::Parse(err) => Some(&**err),
+            ConfigMigrateError::UnknownConfigVersion { .. } => None,
+        }
     }
 }
 
@@ -63,9 +271,14 @@ pub struct HakariBuilderSummary {
 
     /// The Cargo resolver version used.
     ///
+    /// If this is `None`, [`add_workspace_resolver`](Self::add_workspace_resolver) sets it to the
+    /// version Cargo uses for the workspace. Setting it forces a particular version to be used for
+    /// Hakari's computations. If it is still `None` when a `HakariBuilder` is created, version 2 is
+    /// used.
+    ///
     /// For more information, see the documentation for [`CargoResolverVersion`].
-    #[serde(alias = "version")]
-    pub resolver: CargoResolverVersion,
+    #[serde(default, alias = "version", skip_serializing_if = "Option::is_none")]
+    pub resolver: Option<CargoResolverVersion>,
 
     /// Unification across target and host.
     #[serde(default)]
@@ -75,6 +288,10 @@ pub struct HakariBuilderSummary {
     #[serde(default)]
     pub output_single_feature: bool,
 
+    /// Whether dev-dependencies were ignored while computing the workspace-hack.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclude_dev_dependencies: bool,
+
     /// Format version for hakari.
     #[serde(default)]
     pub dep_format_version: DepFormatVersion,
@@ -95,6 +312,22 @@ pub struct HakariBuilderSummary {
     #[serde(default)]
     pub final_excludes: PackageSetSummary,
 
+    /// Overrides for `unify_target_host` for dependencies specific to a platform, as a map of
+    /// target triple to value.
+    ///
+    /// For more information, see the documentation for
+    /// [`HakariBuilder::set_platform_unify_target_host`](crate::HakariBuilder::set_platform_unify_target_host).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub platform_unify_target_host: BTreeMap<String, UnifyTargetHost>,
+
+    /// Features of third-party packages that must never be written out to the workspace-hack, as a
+    /// map of package name to [`MaxFeaturesSummary`].
+    ///
+    /// For more information, see the documentation for
+    /// [`HakariBuilder::add_denied_features`](crate::HakariBuilder::add_denied_features).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub max_features: BTreeMap<String, MaxFeaturesSummary>,
+
     /// The list of alternate registries, as a map of name to URL.
     ///
     /// This is a temporary workaround until [Cargo issue #9052](https://github.com/rust-lang/cargo/issues/9052)
@@ -105,6 +338,13 @@ pub struct HakariBuilderSummary {
         with = "registries_impl"
     )]
     pub registries: BTreeMap<String, String>,
+
+    /// Additional Hakari packages, each covering a subset of workspace members.
+    ///
+    /// For more information, see the documentation for
+    /// [`HakariBuilder::add_shard`](crate::HakariBuilder::add_shard).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shards: Vec<HakariShardSummary>,
 }
 
 impl HakariBuilderSummary {
@@ -123,7 +363,7 @@ impl HakariBuilderSummary {
                 .platforms()
                 .map(|triple_str| triple_str.to_owned())
                 .collect::<Vec<_>>(),
-            resolver: builder.resolver(),
+            resolver: Some(builder.resolver()),
             traversal_excludes: PackageSetSummary::from_package_ids(
                 builder.graph(),
                 builder.traversal_excludes_only(),
@@ -134,18 +374,76 @@ impl HakariBuilderSummary {
                 builder.final_excludes(),
             )
             .expect("all package IDs are valid"),
+            max_features: MaxFeaturesSummary::from_builder(builder),
             registries: builder
                 .registries
                 .iter()
                 .map(|(name, url)| (name.clone(), url.clone()))
                 .collect(),
             unify_target_host: builder.unify_target_host(),
+            platform_unify_target_host: builder
+                .platform_unify_target_host()
+                .map(|(triple_str, unify_target_host)| (triple_str.to_owned(), unify_target_host))
+                .collect(),
             output_single_feature: builder.output_single_feature(),
+            exclude_dev_dependencies: builder.exclude_dev_dependencies(),
             dep_format_version: builder.dep_format_version,
             workspace_hack_line_style: builder.workspace_hack_line_style,
+            shards: builder
+                .shards()
+                .map(|shard| HakariShardSummary {
+                    hakari_package: shard.hakari_package().name().to_owned(),
+                    members: shard
+                        .members()
+                        .map(|package_id| {
+                            builder
+                                .graph()
+                                .metadata(package_id)
+                                .expect("valid package ID")
+                                .name()
+                                .to_owned()
+                        })
+                        .collect(),
+                    paths: BTreeSet::new(),
+                })
+                .collect(),
         })
     }
 
+    /// If [`resolver`](Self::resolver) isn't set, sets it to the version of the Cargo resolver used
+    /// by this graph's workspace.
+    ///
+    /// For how the version is determined, see [`HakariBuilder::read_workspace_resolver`].
+    pub fn add_workspace_resolver(
+        &mut self,
+        graph: &PackageGraph,
+    ) -> Result<&mut Self, CargoTomlError> {
+        if self.resolver.is_none() {
+            self.resolver = Some(read_workspace_resolver(graph.workspace().root())?);
+        }
+        Ok(self)
+    }
+
+    /// Adds alternate registries declared in the Cargo configuration files that apply to this
+    /// graph's workspace.
+    ///
+    /// This allows registries to be used without also listing them in `registries`. Registries
+    /// already present in this summary, by either name or URL, take precedence. For more, see
+    /// [`HakariBuilder::add_cargo_config_registries`].
+    pub fn add_cargo_config_registries(
+        &mut self,
+        graph: &PackageGraph,
+    ) -> Result<&mut Self, CargoConfigError> {
+        let registries = cargo_config_registries(graph.workspace().root())?;
+        for (name, url) in registries {
+            if !self.registries.contains_key(&name) && !self.registries.values().any(|v| *v == url)
+            {
+                self.registries.insert(name, url);
+            }
+        }
+        Ok(self)
+    }
+
     /// Creates a `HakariBuilder` from this summary and a `PackageGraph`.
     ///
     /// Returns an error if this summary references a package that's not present, or if there was
@@ -208,6 +506,143 @@ impl<'g> HakariBuilder<'g> {
     }
 }
 
+/// A shard for `hakari` in serializable form: a Hakari package and the workspace members it covers.
+///
+/// A workspace member is covered by this shard if it matches any of the names in `members`, or is
+/// within any of the directories in `paths`.
+///
+/// Requires the `cli-support` feature to be enabled.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct HakariShardSummary {
+    /// The name of the Hakari package for this shard.
+    pub hakari_package: String,
+
+    /// Names of workspace members covered by this shard.
+    ///
+    /// Names may contain the wildcards `*`, which matches any sequence of characters, and `?`,
+    /// which matches any single character.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub members: BTreeSet<String>,
+
+    /// Directories, relative to the workspace root, whose workspace members are covered by this
+    /// shard.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub paths: BTreeSet<String>,
+}
+
+impl HakariShardSummary {
+    /// Returns the set of workspace members covered by this shard.
+    ///
+    /// Returns an error if a name doesn't match any workspace member.
+    pub fn to_package_set<'g>(
+        &self,
+        graph: &'g PackageGraph,
+    ) -> Result<PackageSet<'g>, guppy::Error> {
+        let members = PackageSetSummary {
+            workspace_members: self.members.clone(),
+            ..PackageSetSummary::default()
+        }
+        .to_package_set(
+            graph,
+            format!("resolving members of shard {}", self.hakari_package),
+        )?;
+        let mut package_ids: Vec<_> = members.package_ids(DependencyDirection::Forward).collect();
+        for path in &self.paths {
+            let path = Utf8Path::new(path);
+            package_ids.extend(
+                graph
+                    .workspace()
+                    .iter_by_path()
+                    .filter(|(workspace_path, _)| workspace_path.starts_with(path))
+                    .map(|(_, package)| package.id()),
+            );
+        }
+        graph.resolve_ids(package_ids)
+    }
+}
+
+/// Features of a third-party package that must never be written out to the workspace-hack.
+///
+/// Part of [`HakariBuilderSummary`]. Applies to every third-party package with a given name,
+/// regardless of its version or source.
+///
+/// Requires the `cli-support` feature to be enabled.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct MaxFeaturesSummary {
+    /// Features that are denied.
+    #[serde(default)]
+    pub deny: BTreeSet<String>,
+}
+
+impl MaxFeaturesSummary {
+    /// Returns the denied features for every third-party package named `name`.
+    ///
+    /// Returns an error if there are no third-party packages with this name, or if a denied feature
+    /// isn't present in any of them.
+    pub fn to_denied_features<'g>(
+        &self,
+        graph: &'g PackageGraph,
+        name: &str,
+    ) -> Result<Vec<FeatureId<'g>>, guppy::Error> {
+        let packages: Vec<_> = graph
+            .packages()
+            .filter(|package| package.name() == name && !package.in_workspace())
+            .collect();
+        if packages.is_empty() {
+            return Err(guppy::Error::UnknownPackageSetSummary {
+                message: format!(
+                    "resolving hakari max-features: no third-party package named '{}'",
+                    name
+                ),
+                unknown_summary_ids: vec![],
+                unknown_workspace_members: vec![],
+                unknown_third_party: vec![],
+            });
+        }
+
+        let mut denied_features = Vec::new();
+        for feature in &self.deny {
+            let feature_ids = packages.iter().filter_map(|package| {
+                package
+                    .named_features()
+                    .find(|&named| named == feature)
+                    .map(|named| FeatureId::new(package.id(), FeatureLabel::Named(named)))
+            });
+            let len = denied_features.len();
+            denied_features.extend(feature_ids);
+            if denied_features.len() == len {
+                return Err(guppy::Error::UnknownFeatureId(
+                    packages[0].id().clone(),
+                    feature.clone(),
+                ));
+            }
+        }
+        Ok(denied_features)
+    }
+
+    fn from_builder(builder: &HakariBuilder<'_>) -> BTreeMap<String, Self> {
+        let mut max_features: BTreeMap<String, Self> = BTreeMap::new();
+        for feature_id in builder.denied_features() {
+            let package = builder
+                .graph()
+                .metadata(feature_id.package_id())
+                .expect("denied features are known to the graph");
+            if let FeatureLabel::Named(feature) = feature_id.label() {
+                max_features
+                    .entry(package.name().to_owned())
+                    .or_default()
+                    .deny
+                    .insert(feature.to_owned());
+            }
+        }
+        max_features
+    }
+}
+
 /// Options for `hakari` TOML output, in serializable form.
 ///
 /// TODO: add a configuration.md file.
@@ -219,10 +654,26 @@ pub struct OutputOptionsSummary {
     #[serde(default)]
     exact_versions: bool,
 
+    /// The style of version requirements to output. Takes precedence over `exact-versions`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version_style: Option<VersionStyle>,
+
     /// Output absolute paths for path dependencies.
     #[serde(default)]
     absolute_paths: bool,
 
+    /// Group dependencies by source, with a comment before each group.
+    #[serde(default)]
+    group_by_source: bool,
+
+    /// Wrap dependency lines longer than this width.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_line_width: Option<usize>,
+
+    /// Output platform-specific dependencies under `cfg()` expressions where possible.
+    #[serde(default)]
+    cfg_platforms: bool,
+
     /// Output a [`HakariBuilderSummary`] as comments.
     #[serde(default)]
     builder_summary: bool,
@@ -231,18 +682,36 @@ pub struct OutputOptionsSummary {
 impl OutputOptionsSummary {
     /// Creates a new `OutputOptionsSummary`.
     pub fn new(options: &HakariOutputOptions) -> Self {
+        // Use exact-versions where possible, for compatibility with older versions of hakari.
+        let (exact_versions, version_style) = match options.version_style {
+            VersionStyle::Minimal => (false, None),
+            VersionStyle::Exact => (true, None),
+            other => (false, Some(other)),
+        };
         Self {
-            exact_versions: options.exact_versions,
+            exact_versions,
+            version_style,
             absolute_paths: options.absolute_paths,
+            group_by_source: options.group_by_source,
+            max_line_width: options.max_line_width,
+            cfg_platforms: options.cfg_platforms,
             builder_summary: options.builder_summary,
         }
     }
 
     /// Converts this summary to the options.
     pub fn to_options(&self) -> HakariOutputOptions {
+        let version_style = match (self.version_style, self.exact_versions) {
+            (Some(version_style), _) => version_style,
+            (None, true) => VersionStyle::Exact,
+            (None, false) => VersionStyle::Minimal,
+        };
         HakariOutputOptions {
-            exact_versions: self.exact_versions,
+            version_style,
             absolute_paths: self.absolute_paths,
+            group_by_source: self.group_by_source,
+            max_line_width: self.max_line_width,
+            cfg_platforms: self.cfg_platforms,
             builder_summary: self.builder_summary,
         }
     }
@@ -304,6 +773,126 @@ mod tests {
     use super::*;
     use fixtures::json::*;
 
+    #[test]
+    fn config_keys_complete() {
+        // Set every option, so that none of them are skipped while serializing.
+        static INPUT: &str = r#"config-version = "2"
+hakari-package = "workspace-hack"
+resolver = "2"
+unify-target-host = "auto"
+output-single-feature = true
+exclude-dev-dependencies = true
+dep-format-version = "4"
+workspace-hack-line-style = "full"
+platforms = ["x86_64-unknown-linux-gnu"]
+exact-versions = true
+version-style = "exact"
+absolute-paths = true
+group-by-source = true
+max-line-width = 100
+cfg-platforms = true
+builder-summary = true
+
+[traversal-excludes]
+workspace-members = ["foo"]
+
+[final-excludes]
+workspace-members = ["bar"]
+
+[platform-unify-target-host]
+x86_64-unknown-linux-gnu = "none"
+
+[max-features.serde]
+deny = ["rc"]
+
+[registries]
+my-registry = { index = "https://example.com/index" }
+
+[[shards]]
+hakari-package = "shard-hack"
+members = ["baz"]
+"#;
+
+        let config: HakariConfig = INPUT.parse().expect("config is valid");
+        let serialized = toml::Value::try_from(&config).expect("config serialized");
+        let serialized = serialized.as_table().expect("config is a table");
+        for key in serialized.keys() {
+            assert!(
+                HakariConfig::KEYS.contains(&key.as_str()),
+                "serialized key {} is in HakariConfig::KEYS",
+                key
+            );
+        }
+        for key in HakariConfig::KEYS {
+            assert!(
+                *key == "version" || serialized.contains_key(*key),
+                "key {} in HakariConfig::KEYS is serialized",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn migrate_config_v1() {
+        static V1_INPUT: &str = r#"# This file contains settings for `cargo hakari`.
+
+hakari-package = "workspace-hack"
+
+# Comments are preserved.
+resolver = "2"
+
+[traversal-excludes]
+workspace-members = ["foo"]
+"#;
+        static V2_OUTPUT: &str = r#"# This file contains settings for `cargo hakari`.
+
+config-version = "2"
+hakari-package = "workspace-hack"
+
+# Comments are preserved.
+resolver = "2"
+dep-format-version = "1"
+
+[traversal-excludes]
+workspace-members = ["foo"]
+"#;
+
+        let v1: HakariConfig = V1_INPUT.parse().expect("V1 config parsed");
+        assert_eq!(v1.config_version, ConfigVersion::V1);
+        assert_eq!(v1.builder.dep_format_version, DepFormatVersion::V1);
+
+        let migrated = migrate_config(V1_INPUT).expect("migration succeeded");
+        assert_eq!(migrated, V2_OUTPUT, "migrated config matches");
+        let v2: HakariConfig = migrated.parse().expect("V2 config parsed");
+        assert_eq!(v2.config_version, ConfigVersion::V2);
+        assert_eq!(
+            v2.builder, v1.builder,
+            "migration doesn't change builder options"
+        );
+        assert_eq!(
+            migrate_config(&migrated).expect("migration succeeded"),
+            migrated,
+            "migration is idempotent"
+        );
+
+        // V2 configs must specify dep-format-version.
+        "config-version = \"2\"\nresolver = \"2\"\n"
+            .parse::<HakariConfig>()
+            .expect_err("dep-format-version is required");
+        assert!(matches!(
+            migrate_config("config-version = \"3\"\n"),
+            Err(ConfigMigrateError::UnknownConfigVersion { value }) if value == "\"3\""
+        ));
+    }
+
+    #[test]
+    fn config_template() {
+        let template =
+            include_str!("../templates/hakari.toml-in").replace("%CONFIG_COMMENT%\n", "");
+        let config: HakariConfig = template.parse().expect("template parsed");
+        assert_eq!(config.config_version, ConfigVersion::latest());
+    }
+
     #[test]
     fn parse_registries() {
         static PARSE_REGISTRIES_INPUT: &str = r#"
@@ -354,4 +943,190 @@ mod tests {
             "summary => serialized => summary roundtrip"
         );
     }
+
+    #[test]
+    fn parse_version_style() {
+        let options = |input: &str| {
+            toml::from_str::<OutputOptionsSummary>(input)
+                .expect("failed to parse toml")
+                .to_options()
+        };
+        assert_eq!(options("").version_style(), VersionStyle::Minimal);
+        assert_eq!(
+            options("exact-versions = true").version_style(),
+            VersionStyle::Exact
+        );
+        assert_eq!(
+            options("exact-versions = true\nversion-style = \"pinned\"").version_style(),
+            VersionStyle::Pinned
+        );
+
+        for version_style in [
+            VersionStyle::Minimal,
+            VersionStyle::Exact,
+            VersionStyle::Pinned,
+            VersionStyle::Workspace,
+        ] {
+            let mut options = HakariOutputOptions::new();
+            options.set_version_style(version_style);
+            let summary = OutputOptionsSummary::new(&options);
+            let serialized = toml::to_string(&summary).expect("serialized to TOML correctly");
+            let summary2: OutputOptionsSummary =
+                toml::from_str(&serialized).expect("deserialized from TOML correctly");
+            assert_eq!(
+                summary, summary2,
+                "summary => serialized => summary roundtrip"
+            );
+            assert_eq!(summary2.to_options().version_style(), version_style);
+        }
+    }
+
+    #[test]
+    fn parse_excludes_versions_globs() {
+        static PARSE_EXCLUDES_INPUT: &str = r#"
+        resolver = "2"
+
+        [traversal-excludes]
+        workspace-members = ["cargo-*"]
+        third-party = [
+            # Only exclude the older version of itertools.
+            { name = "itertools", version = "0.8" },
+            { name = "serde_*" },
+        ]
+        "#;
+
+        let summary: HakariBuilderSummary =
+            toml::from_str(PARSE_EXCLUDES_INPUT).expect("failed to parse toml");
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let builder = summary
+            .to_hakari_builder(graph)
+            .expect("summary => builder conversion");
+
+        let excludes: BTreeSet<_> = builder
+            .traversal_excludes_only()
+            .map(|package_id| {
+                let package = graph.metadata(package_id).unwrap();
+                format!("{} {}", package.name(), package.version())
+            })
+            .collect();
+        assert_eq!(
+            excludes,
+            [
+                "cargo-compare 0.1.0",
+                "cargo-guppy 0.1.0",
+                "itertools 0.8.2",
+                "serde_cbor 0.11.1",
+                "serde_derive 1.0.116",
+                "serde_ignored 0.1.2",
+                "serde_json 1.0.58",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        );
+
+        // Globs that don't match anything are reported as errors.
+        let summary: HakariBuilderSummary = toml::from_str(
+            r#"
+            resolver = "2"
+            [final-excludes]
+            third-party = [{ name = "unknown-*" }]
+            "#,
+        )
+        .expect("failed to parse toml");
+        summary
+            .to_hakari_builder(graph)
+            .expect_err("unknown-* doesn't match anything");
+    }
+
+    #[test]
+    fn parse_max_features() {
+        static PARSE_MAX_FEATURES_INPUT: &str = r#"
+        resolver = "2"
+
+        [max-features.serde]
+        deny = ["std", "rc"]
+        "#;
+
+        let summary: HakariBuilderSummary =
+            toml::from_str(PARSE_MAX_FEATURES_INPUT).expect("failed to parse toml");
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let builder = summary
+            .to_hakari_builder(graph)
+            .expect("summary => builder conversion");
+
+        let denied: BTreeSet<_> = builder
+            .denied_features()
+            .map(|feature_id| {
+                let package = graph.metadata(feature_id.package_id()).unwrap();
+                format!("{}/{}", package.name(), feature_id.label())
+            })
+            .collect();
+        assert_eq!(
+            denied,
+            ["serde/rc", "serde/std"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        );
+        assert_eq!(
+            HakariBuilderSummary::new(&builder).expect("no custom platforms"),
+            summary,
+            "summary round-trips"
+        );
+
+        // Unknown packages and features are reported as errors.
+        for input in [
+            "[max-features.unknown]\ndeny = [\"std\"]",
+            "[max-features.serde]\ndeny = [\"unknown\"]",
+            // Workspace packages can't have denied features.
+            "[max-features.guppy]\ndeny = [\"summaries\"]",
+        ] {
+            let summary: HakariBuilderSummary =
+                toml::from_str(&format!("resolver = \"2\"\n{}", input))
+                    .expect("failed to parse toml");
+            summary
+                .to_hakari_builder(graph)
+                .expect_err("unknown package or feature");
+        }
+    }
+
+    #[test]
+    fn parse_shards() {
+        static PARSE_SHARDS_INPUT: &str = r#"
+        resolver = "2"
+        hakari-package = "guppy-summaries"
+
+        [[shards]]
+        hakari-package = "proptest-ext"
+        members = ["cargo-*"]
+        paths = ["internal-tools"]
+        "#;
+
+        let summary: HakariBuilderSummary =
+            toml::from_str(PARSE_SHARDS_INPUT).expect("failed to parse toml");
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let builder = summary
+            .to_hakari_builder(graph)
+            .expect("summary => builder conversion");
+
+        let shard = builder.shards().next().expect("one shard");
+        assert_eq!(shard.hakari_package().name(), "proptest-ext");
+        let members: BTreeSet<_> = shard
+            .members()
+            .map(|package_id| graph.metadata(package_id).unwrap().name())
+            .collect();
+        assert_eq!(
+            members,
+            [
+                "cargo-compare",
+                "cargo-guppy",
+                "fixture-manager",
+                "guppy-benchmarks",
+                "proptest-ext"
+            ]
+            .into_iter()
+            .collect(),
+        );
+    }
 }
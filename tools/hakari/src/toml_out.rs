@@ -7,11 +7,11 @@
 use crate::summaries::HakariBuilderSummary;
 use crate::{
     hakari::{HakariBuilder, OutputMap},
-    helpers::VersionDisplay,
-    DepFormatVersion,
+    helpers::{loosest_version_req, VersionDisplay},
+    CargoTomlError, DepFormatVersion, HakariCargoToml,
 };
 use ahash::AHashMap;
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use cfg_if::cfg_if;
 use guppy::{
     errors::TargetSpecError,
@@ -20,18 +20,23 @@ use guppy::{
 };
 use std::{
     borrow::Cow,
-    collections::HashSet,
+    collections::{BTreeMap, BTreeSet, HashSet},
     error, fmt,
     hash::{Hash, Hasher},
+    sync::Arc,
 };
-use toml_edit::{Array, Document, InlineTable, Item, Table, Value};
+use target_spec::{Platform, TargetSpecExpression};
+use toml_edit::{Array, Document, InlineTable, Item, Key, Table, Value};
 use twox_hash::XxHash64;
 
 /// Options for Hakari TOML output.
 #[derive(Clone, Debug)]
 pub struct HakariOutputOptions {
-    pub(crate) exact_versions: bool,
+    pub(crate) version_style: VersionStyle,
     pub(crate) absolute_paths: bool,
+    pub(crate) group_by_source: bool,
+    pub(crate) max_line_width: Option<usize>,
+    pub(crate) cfg_platforms: bool,
     #[cfg(feature = "cli-support")]
     pub(crate) builder_summary: bool,
 }
@@ -40,12 +45,18 @@ impl HakariOutputOptions {
     /// Creates a new instance with default settings.
     ///
     /// The default settings are:
-    /// * do not output exact versions
+    /// * output minimal versions ([`VersionStyle::Minimal`])
+    /// * do not group dependencies by source
+    /// * do not wrap long lines
+    /// * output platform-specific dependencies under target triples
     /// * do not output a summary of builder options
     pub fn new() -> Self {
         Self {
-            exact_versions: false,
+            version_style: VersionStyle::Minimal,
             absolute_paths: false,
+            group_by_source: false,
+            max_line_width: None,
+            cfg_platforms: false,
             #[cfg(feature = "cli-support")]
             builder_summary: false,
         }
@@ -66,11 +77,31 @@ impl HakariOutputOptions {
     ///
     /// If set to true, and the `Cargo.lock` file isn't checked in, Hakari's output will vary based
     /// on the repository it is run in. Most of the time this isn't desirable.
+    ///
+    /// This is equivalent to calling [`set_version_style`](Self::set_version_style) with
+    /// [`VersionStyle::Exact`] if true, or [`VersionStyle::Minimal`] if false.
     pub fn set_exact_versions(&mut self, exact_versions: bool) -> &mut Self {
-        self.exact_versions = exact_versions;
+        self.version_style = if exact_versions {
+            VersionStyle::Exact
+        } else {
+            VersionStyle::Minimal
+        };
         self
     }
 
+    /// Sets the style of version requirements written out for dependencies.
+    ///
+    /// For more, see the documentation for [`VersionStyle`].
+    pub fn set_version_style(&mut self, version_style: VersionStyle) -> &mut Self {
+        self.version_style = version_style;
+        self
+    }
+
+    /// Returns the style of version requirements written out for dependencies.
+    pub fn version_style(&self) -> VersionStyle {
+        self.version_style
+    }
+
     /// If set to true, outputs absolute paths for path dependencies.
     ///
     /// By default, `hakari` outputs relative paths, for example:
@@ -97,6 +128,101 @@ impl HakariOutputOptions {
         self
     }
 
+    /// If set to true, groups the dependencies in each section by where they come from, with a
+    /// comment before each group.
+    ///
+    /// Each section (for example `[dependencies]` or `[build-dependencies]`) is split into groups,
+    /// in this order:
+    ///
+    /// ```toml
+    /// # Registry: crates.io
+    /// serde = { version = "1", features = ["derive"] }
+    ///
+    /// # Registry: my-registry
+    /// my-dep = { version = "0.3", registry = "my-registry" }
+    ///
+    /// # Git dependencies
+    /// my-fork = { git = "https://github.com/my-org/my-fork", rev = "0227f048" }
+    ///
+    /// # Path dependencies
+    /// local-dep = { path = "../../vendor/local-dep", default-features = false }
+    /// ```
+    ///
+    /// Alternate registries are ordered by name, and dependencies within a group are sorted by
+    /// name. Groups without any dependencies are omitted.
+    pub fn set_group_by_source(&mut self, group_by_source: bool) -> &mut Self {
+        self.group_by_source = group_by_source;
+        self
+    }
+
+    /// Returns true if dependencies are grouped by source.
+    pub fn group_by_source(&self) -> bool {
+        self.group_by_source
+    }
+
+    /// Sets the maximum width of dependency lines, or `None` to never wrap lines.
+    ///
+    /// Dependency lines longer than this are wrapped by putting each feature on its own line:
+    ///
+    /// ```toml
+    /// serde = { version = "1", features = [
+    ///     "derive",
+    ///     "rc",
+    /// ] }
+    /// ```
+    ///
+    /// Lines without features are never wrapped.
+    pub fn set_max_line_width(&mut self, max_line_width: Option<usize>) -> &mut Self {
+        self.max_line_width = max_line_width;
+        self
+    }
+
+    /// Returns the maximum width of dependency lines, or `None` if lines aren't wrapped.
+    pub fn max_line_width(&self) -> Option<usize> {
+        self.max_line_width
+    }
+
+    /// If set to true, outputs platform-specific dependencies under `cfg()` expressions rather than
+    /// target triples where possible.
+    ///
+    /// By default, dependencies specific to one of the [configured
+    /// platforms](HakariBuilder::set_platforms) are output in a section per target triple:
+    ///
+    /// ```toml
+    /// [target.x86_64-unknown-linux-gnu.dependencies]
+    /// libc = { version = "0.2", features = ["extra_traits"] }
+    ///
+    /// [target.aarch64-apple-darwin.dependencies]
+    /// libc = { version = "0.2", features = ["extra_traits"] }
+    /// ```
+    ///
+    /// With this option set, a dependency line that's the same across a set of platforms is output
+    /// once, under a `cfg()` expression that matches exactly that set among the configured
+    /// platforms:
+    ///
+    /// ```toml
+    /// [target.'cfg(unix)'.dependencies]
+    /// libc = { version = "0.2", features = ["extra_traits"] }
+    /// ```
+    ///
+    /// Expressions are built out of `unix`, `windows`, and the `target_family`, `target_os`,
+    /// `target_env` and `target_arch` of each platform. If no such expression matches the set of
+    /// platforms exactly, the dependency is output under each target triple as before.
+    ///
+    /// ## Note
+    ///
+    /// `cfg()` expressions may also match platforms that aren't configured. For example, with the
+    /// above output, `libc` would also be built with the `extra_traits` feature on FreeBSD.
+    pub fn set_cfg_platforms(&mut self, cfg_platforms: bool) -> &mut Self {
+        self.cfg_platforms = cfg_platforms;
+        self
+    }
+
+    /// Returns true if platform-specific dependencies are output under `cfg()` expressions.
+    pub fn cfg_platforms(&self) -> bool {
+        self.cfg_platforms
+    }
+
     /// If set to true, outputs a summary of the builder options used to generate the `Hakari`, as
     /// TOML comments.
     ///
@@ -122,6 +248,51 @@ impl Default for HakariOutputOptions {
     }
 }
 
+/// The style of version requirements written out by Hakari.
+///
+/// Call `HakariOutputOptions::set_version_style` to configure this option.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "cli-support", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "cli-support", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub enum VersionStyle {
+    /// Output the loosest possible version requirement that matches the version in use, e.g.
+    /// `"1"` for version 1.4.2.
+    ///
+    /// This is the default behavior.
+    Minimal,
+
+    /// Output the exact version in use as a caret requirement, e.g. `"1.4.2"`.
+    ///
+    /// This is equivalent to `HakariOutputOptions::set_exact_versions(true)`.
+    Exact,
+
+    /// Pin to the exact version in use, e.g. `"=1.4.2"`.
+    ///
+    /// With this style, the workspace-hack crate never causes a different version of a dependency
+    /// to be selected. This is most useful if the `Cargo.lock` file is checked in, and updates to
+    /// it are made deliberately: the workspace-hack crate will then need to be regenerated.
+    Pinned,
+
+    /// Output the loosest version requirement already implied by the dependency graph.
+    ///
+    /// For each dependency, Hakari looks at the version requirements that packages in the graph
+    /// specify for it, and outputs the one with the lowest minimum version, e.g. `"1.2"` if one
+    /// package depends on `"1.2"` and another on `"1.3.5"`. This means that the workspace-hack
+    /// crate never raises the minimum version of a dependency.
+    ///
+    /// If no such requirement matches the version in use, the minimal version is output instead.
+    Workspace,
+}
+
+/// The default for `VersionStyle`: output minimal versions.
+impl Default for VersionStyle {
+    #[inline]
+    fn default() -> Self {
+        VersionStyle::Minimal
+    }
+}
+
 /// An error that occurred while writing out TOML.
 #[derive(Debug)]
 #[non_exhaustive]
@@ -173,6 +344,19 @@ pub enum TomlOutError {
         /// The registry URL that wasn't recognized.
         registry_url: String,
     },
+
+    /// The workspace root's `Cargo.toml` couldn't be read.
+    WorkspaceToml(CargoTomlError),
+
+    /// A dependency to be written to `[workspace.dependencies]` is already declared outside the
+    /// generated section of the workspace root's `Cargo.toml`.
+    WorkspaceDependencyConflict {
+        /// The path to the workspace root's `Cargo.toml`.
+        toml_path: Utf8PathBuf,
+
+        /// The name of the dependency.
+        name: String,
+    },
 }
 
 /// Returns a map from dependency names as present in the workspace `Cargo.toml` to their
@@ -252,6 +436,15 @@ impl fmt::Display for TomlOutError {
                     package_id, registry_url,
                 )
             }
+            TomlOutError::WorkspaceToml(_) => {
+                write!(f, "while reading the workspace root's Cargo.toml")
+            }
+            TomlOutError::WorkspaceDependencyConflict { toml_path, name } => write!(
+                f,
+                "in '{}', '{}' is already declared in [workspace.dependencies] outside the \
+                 generated section (remove it so that hakari can manage it)",
+                toml_path, name,
+            ),
         }
     }
 }
@@ -263,9 +456,11 @@ impl error::Error for TomlOutError {
             #[cfg(feature = "cli-support")]
             TomlOutError::Toml { err, .. } => Some(err),
             TomlOutError::FmtWrite(err) => Some(err),
+            TomlOutError::WorkspaceToml(err) => Some(err),
             TomlOutError::PathWithoutHakari { .. }
             | TomlOutError::UnrecognizedExternal { .. }
-            | TomlOutError::UnrecognizedRegistry { .. } => None,
+            | TomlOutError::UnrecognizedRegistry { .. }
+            | TomlOutError::WorkspaceDependencyConflict { .. } => None,
         }
     }
 }
@@ -287,15 +482,7 @@ pub(crate) fn write_toml(
         }
     }
 
-    let mut packages_by_name: AHashMap<&str, HashSet<_>> = AHashMap::new();
-    for vals in output_map.values() {
-        for (&package_id, (package, _)) in vals {
-            packages_by_name
-                .entry(package.name())
-                .or_default()
-                .insert(package_id);
-        }
-    }
+    let packages_by_name = packages_by_name(output_map);
 
     let hakari_path = builder.hakari_package().map(|package| {
         package
@@ -310,16 +497,24 @@ pub(crate) fn write_toml(
     // hakari did.
     let mut first_element = true;
 
-    for (key, vals) in output_map {
-        let dep_table_parent = match key.platform_idx {
-            Some(idx) => {
+    for section in output_sections(builder, output_map, options.cfg_platforms) {
+        let dep_table_parent = match &section.target {
+            Some(target) => {
                 let target_table = get_or_insert_table(document.as_table_mut(), "target");
-                get_or_insert_table(target_table, builder.platforms[idx].triple_str())
+                // Quote cfg() expressions with single quotes, as Cargo's documentation does.
+                let key = if target.starts_with("cfg(") {
+                    Key::parse(&format!("'{}'", target))
+                        .expect("cfg() expressions are valid keys")
+                        .remove(0)
+                } else {
+                    Key::new(target.as_ref())
+                };
+                get_or_insert_table(target_table, key)
             }
             None => document.as_table_mut(),
         };
 
-        let dep_table = match key.build_platform {
+        let dep_table = match section.build_platform {
             BuildPlatform::Target => get_or_insert_table(dep_table_parent, "dependencies"),
             BuildPlatform::Host => get_or_insert_table(dep_table_parent, "build-dependencies"),
         };
@@ -329,141 +524,72 @@ pub(crate) fn write_toml(
             first_element = false;
         }
 
-        for (dep, all_features) in vals.values() {
+        let mut source_groups = AHashMap::new();
+        for (dep, all_features) in section.deps {
             let mut itable = InlineTable::new();
 
-            let name: Cow<str> = if packages_by_name[dep.name()].len() > 1 {
-                itable.insert("package", dep.name().into());
+            let is_hashed = packages_by_name[dep.name()].len() > 1;
+            let name: Cow<str> = if is_hashed {
                 make_hashed_name(dep, dep_format).into()
             } else {
                 dep.name().into()
             };
 
-            let source = dep.source();
-            if source.is_crates_io() {
-                itable.insert(
-                    "version",
-                    format!(
-                        "{}",
-                        VersionDisplay::new(
-                            dep.version(),
-                            options.exact_versions,
-                            dep_format < DepFormatVersion::V3
-                        )
-                    )
-                    .into(),
-                );
+            if dep_format >= DepFormatVersion::V5 {
+                // The source and default-features = false are specified in
+                // [workspace.dependencies], so only features need to be listed here.
+                itable.insert("workspace", true.into());
+                let feature_array: Array = all_features.iter().copied().collect();
+                if !feature_array.is_empty() {
+                    itable.insert("features", feature_array.into());
+                }
             } else {
-                match source {
-                    PackageSource::Workspace(path) | PackageSource::Path(path) => {
-                        // PackageSource::Workspace shouldn't be possible unless the Hakari map
-                        // was fiddled with. Regardless, we can handle it fine.
-                        let path_out = if options.absolute_paths {
-                            // TODO: canonicalize paths here, removing .. etc? tricky if the path is
-                            // missing (as in tests)
-                            builder.graph().workspace().root().join(path)
-                        } else {
-                            let hakari_path =
-                                hakari_path.ok_or_else(|| TomlOutError::PathWithoutHakari {
-                                    package_id: dep.id().clone(),
-                                    rel_path: path.to_path_buf(),
-                                })?;
-                            pathdiff::diff_utf8_paths(path, hakari_path)
-                                .expect("both hakari_path and path are relative")
-                        }
-                        .into_string();
-
-                        cfg_if! {
-                            if #[cfg(windows)] {
-                                // TODO: is replacing \\ with / totally safe on Windows? Might run
-                                // into issues with UNC paths.
-                                let path_out = path_out.replace("\\", "/");
-                                itable.insert("path", path_out.into());
-                            } else {
-                                itable.insert("path", path_out.into());
-                            }
-                        };
-                    }
-                    PackageSource::External(s) => match source.parse_external() {
-                        Some(ExternalSource::Git {
-                            repository, req, ..
-                        }) => {
-                            itable.insert("git", repository.into());
-                            match req {
-                                GitReq::Branch(branch) => {
-                                    itable.insert("branch", branch.into());
-                                }
-                                GitReq::Tag(tag) => {
-                                    itable.insert("tag", tag.into());
-                                }
-                                GitReq::Rev(rev) => {
-                                    itable.insert("rev", rev.into());
-                                }
-                                GitReq::Default => {}
-                                _ => {
-                                    return Err(TomlOutError::UnrecognizedExternal {
-                                        package_id: dep.id().clone(),
-                                        source: s.to_string(),
-                                    });
-                                }
-                            };
-                        }
-                        Some(ExternalSource::Registry(registry_url)) => {
-                            let registry_name = builder
-                                .registries
-                                .get_by_right(registry_url)
-                                .ok_or_else(|| TomlOutError::UnrecognizedRegistry {
-                                    package_id: dep.id().clone(),
-                                    registry_url: registry_url.to_owned(),
-                                })?;
-                            itable.insert(
-                                "version",
-                                format!(
-                                    "{}",
-                                    VersionDisplay::new(
-                                        dep.version(),
-                                        options.exact_versions,
-                                        dep_format < DepFormatVersion::V3
-                                    )
-                                )
-                                .into(),
-                            );
-                            itable.insert("registry", registry_name.into());
-                        }
-                        _ => {
-                            return Err(TomlOutError::UnrecognizedExternal {
-                                package_id: dep.id().clone(),
-                                source: s.to_string(),
-                            });
-                        }
-                    },
+                if is_hashed {
+                    itable.insert("package", dep.name().into());
                 }
-            };
+                insert_source(&mut itable, builder, dep, options, dep_format, hakari_path)?;
 
-            if !all_features.contains(&"default") {
-                itable.insert("default-features", false.into());
-            }
+                if !all_features.contains(&"default") {
+                    itable.insert("default-features", false.into());
+                }
 
-            let feature_array: Array = all_features
-                .iter()
-                .filter_map(|&label| {
-                    // Only care about named features here.
-                    match label {
-                        "default" => None,
-                        feature_name => Some(feature_name),
-                    }
-                })
-                .collect();
-            if !feature_array.is_empty() {
-                itable.insert("features", feature_array.into());
+                let feature_array: Array = all_features
+                    .iter()
+                    .filter_map(|&label| {
+                        // Only care about named features here.
+                        match label {
+                            "default" => None,
+                            feature_name => Some(feature_name),
+                        }
+                    })
+                    .collect();
+                if !feature_array.is_empty() {
+                    itable.insert("features", feature_array.into());
+                }
             }
 
             itable.fmt();
+            if let Some(max_line_width) = options.max_line_width {
+                // Account for the " = " between the name and the inline table.
+                if name.len() + 3 + itable.to_string().len() > max_line_width {
+                    wrap_features(&mut itable);
+                }
+            }
 
+            if options.group_by_source {
+                source_groups.insert(name.to_string(), SourceGroup::new(builder, dep));
+            }
             dep_table.insert(name.as_ref(), Item::Value(Value::InlineTable(itable)));
         }
 
-        if dep_format >= DepFormatVersion::V4 {
+        if options.group_by_source {
+            dep_table.sort_values_by(|k1, _, k2, _| {
+                let key1 = (&source_groups[k1.get()], k1.get());
+                let key2 = (&source_groups[k2.get()], k2.get());
+                key1.cmp(&key2)
+            });
+            write_group_headers(dep_table, &source_groups);
+        } else if dep_format >= DepFormatVersion::V4 {
             dep_table.sort_values();
         }
     }
@@ -478,6 +604,451 @@ pub(crate) fn write_toml(
     Ok(())
 }
 
+pub(crate) fn write_workspace_toml(
+    builder: &HakariBuilder<'_>,
+    output_map: &OutputMap<'_>,
+    workspace_toml: &HakariCargoToml,
+    options: &HakariOutputOptions,
+    dep_format: DepFormatVersion,
+    mut out: impl fmt::Write,
+) -> Result<(), TomlOutError> {
+    let packages_by_name = packages_by_name(output_map);
+    // Entries written by hand can't be declared again in the generated section.
+    let existing_names = workspace_toml
+        .non_generated_keys(&["workspace", "dependencies"])
+        .map_err(TomlOutError::WorkspaceToml)?;
+
+    let mut document = Document::new();
+    let dep_table = document.as_table_mut();
+
+    for vals in output_map.values() {
+        for (dep, _) in vals.values() {
+            let is_hashed = packages_by_name[dep.name()].len() > 1;
+            let name: Cow<str> = if is_hashed {
+                make_hashed_name(dep, dep_format).into()
+            } else {
+                dep.name().into()
+            };
+            // A dependency may be present in several platform-specific sections, but it only
+            // needs to be specified once here.
+            if dep_table.contains_key(&name) {
+                continue;
+            }
+            if existing_names.contains(name.as_ref()) {
+                return Err(TomlOutError::WorkspaceDependencyConflict {
+                    toml_path: workspace_toml.toml_path().to_owned(),
+                    name: name.into_owned(),
+                });
+            }
+
+            let mut itable = InlineTable::new();
+            if is_hashed {
+                itable.insert("package", dep.name().into());
+            }
+            // Paths in [workspace.dependencies] are relative to the workspace root.
+            insert_source(
+                &mut itable,
+                builder,
+                dep,
+                options,
+                dep_format,
+                Some(Utf8Path::new("")),
+            )?;
+            // Default features are enabled through the workspace-hack's features list.
+            itable.insert("default-features", false.into());
+            itable.fmt();
+
+            dep_table.insert(name.as_ref(), Item::Value(Value::InlineTable(itable)));
+        }
+    }
+
+    dep_table.sort_values();
+    write!(out, "{}", document)?;
+
+    Ok(())
+}
+
+/// A section of dependencies in the workspace-hack's `Cargo.toml`, for example
+/// `[target.'cfg(unix)'.dependencies]`.
+#[derive(Debug)]
+struct OutputSection<'a, 'g> {
+    /// The target triple or `cfg()` expression, or `None` for platform-independent dependencies.
+    target: Option<Cow<'a, str>>,
+    build_platform: BuildPlatform,
+    deps: Vec<&'a (PackageMetadata<'g>, BTreeSet<&'g str>)>,
+}
+
+/// Returns the sections to write out for `output_map`, in order.
+///
+/// With `cfg_platforms` set, dependency lines that are the same across several platforms are
+/// moved to sections for `cfg()` expressions matching those platforms.
+fn output_sections<'a, 'g>(
+    builder: &'a HakariBuilder<'g>,
+    output_map: &'a OutputMap<'g>,
+    cfg_platforms: bool,
+) -> Vec<OutputSection<'a, 'g>> {
+    let platforms = &builder.platforms;
+    let mut sections = Vec::new();
+
+    // For each dependency line, the platforms it's output for.
+    #[allow(clippy::type_complexity)]
+    let mut platform_lines: BTreeMap<
+        (BuildPlatform, &'g PackageId, &'a BTreeSet<&'g str>),
+        (
+            &'a (PackageMetadata<'g>, BTreeSet<&'g str>),
+            BTreeSet<usize>,
+        ),
+    > = BTreeMap::new();
+
+    for (key, vals) in output_map {
+        match key.platform_idx {
+            Some(idx) if cfg_platforms => {
+                for (&dep_id, val) in vals {
+                    platform_lines
+                        .entry((key.build_platform, dep_id, &val.1))
+                        .or_insert_with(|| (val, BTreeSet::new()))
+                        .1
+                        .insert(idx);
+                }
+            }
+            platform_idx => sections.push(OutputSection {
+                target: platform_idx.map(|idx| platforms[idx].triple_str().into()),
+                build_platform: key.build_platform,
+                deps: vals.values().collect(),
+            }),
+        }
+    }
+
+    // Sections are ordered by the first configured platform they match.
+    let mut target_sections: BTreeMap<(usize, Cow<'a, str>, BuildPlatform), Vec<_>> =
+        BTreeMap::new();
+    let mut cfgs: AHashMap<BTreeSet<usize>, Option<String>> = AHashMap::new();
+    for ((build_platform, _, _), (val, platform_idxs)) in platform_lines {
+        let cfg = match cfgs.get(&platform_idxs) {
+            Some(cfg) => cfg.clone(),
+            None => {
+                let cfg = platform_cfg(platforms, &platform_idxs);
+                cfgs.insert(platform_idxs.clone(), cfg.clone());
+                cfg
+            }
+        };
+
+        match cfg {
+            Some(cfg) => {
+                let first_idx = *platform_idxs.first().expect("at least one platform");
+                target_sections
+                    .entry((first_idx, cfg.into(), build_platform))
+                    .or_default()
+                    .push(val);
+            }
+            None => {
+                for idx in platform_idxs {
+                    target_sections
+                        .entry((idx, platforms[idx].triple_str().into(), build_platform))
+                        .or_default()
+                        .push(val);
+                }
+            }
+        }
+    }
+
+    sections.extend(
+        target_sections
+            .into_iter()
+            .map(|((_, target, build_platform), deps)| OutputSection {
+                target: Some(target),
+                build_platform,
+                deps,
+            }),
+    );
+    sections
+}
+
+/// Predicates that `cfg()` expressions for [`HakariOutputOptions::set_cfg_platforms`] are built
+/// from, in order of preference.
+static CFG_PREDICATES: &[&str] = &[
+    "unix",
+    "windows",
+    "target_family = \"wasm\"",
+    "target_os = \"linux\"",
+    "target_os = \"macos\"",
+    "target_os = \"windows\"",
+    "target_os = \"android\"",
+    "target_os = \"ios\"",
+    "target_os = \"freebsd\"",
+    "target_os = \"netbsd\"",
+    "target_os = \"openbsd\"",
+    "target_os = \"dragonfly\"",
+    "target_os = \"illumos\"",
+    "target_os = \"solaris\"",
+    "target_os = \"fuchsia\"",
+    "target_os = \"wasi\"",
+    "target_os = \"none\"",
+    "target_env = \"gnu\"",
+    "target_env = \"musl\"",
+    "target_env = \"msvc\"",
+    "target_arch = \"x86_64\"",
+    "target_arch = \"x86\"",
+    "target_arch = \"aarch64\"",
+    "target_arch = \"arm\"",
+    "target_arch = \"wasm32\"",
+    "target_arch = \"riscv64\"",
+    "target_arch = \"powerpc64\"",
+    "target_arch = \"s390x\"",
+];
+
+/// Returns a `cfg()` expression that matches exactly `platform_idxs` among `platforms`, or `None`
+/// if there's no such expression.
+fn platform_cfg(platforms: &[Arc<Platform>], platform_idxs: &BTreeSet<usize>) -> Option<String> {
+    // The platforms each predicate matches, ignoring predicates that don't match all of
+    // platform_idxs.
+    let mut candidates = Vec::new();
+    for &predicate in CFG_PREDICATES {
+        let expr = TargetSpecExpression::new(&format!("cfg({})", predicate))
+            .expect("predicates are valid");
+        // If the result isn't known for some platform, this predicate can't be used.
+        let matches: Option<BTreeSet<_>> = platforms
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, platform)| match expr.eval(platform) {
+                Some(true) => Some(Some(idx)),
+                Some(false) => None,
+                None => Some(None),
+            })
+            .collect();
+        if let Some(matches) = matches {
+            if matches.is_superset(platform_idxs) {
+                candidates.push((predicate, matches));
+            }
+        }
+    }
+
+    // Prefer a single predicate, then a combination of two.
+    if let Some((predicate, _)) = candidates
+        .iter()
+        .find(|(_, matches)| matches == platform_idxs)
+    {
+        return Some(format!("cfg({})", predicate));
+    }
+    for (i, (predicate1, matches1)) in candidates.iter().enumerate() {
+        for (predicate2, matches2) in &candidates[i + 1..] {
+            if matches1.intersection(matches2).eq(platform_idxs) {
+                return Some(format!("cfg(all({}, {}))", predicate1, predicate2));
+            }
+        }
+    }
+    None
+}
+
+/// Where a dependency comes from, used to group dependencies with
+/// [`HakariOutputOptions::set_group_by_source`].
+///
+/// The order of variants is the order groups are output in.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum SourceGroup<'a> {
+    CratesIo,
+    // The name of the registry, or its URL if it isn't known.
+    Registry(&'a str),
+    Git,
+    Path,
+}
+
+impl<'a> SourceGroup<'a> {
+    fn new(builder: &'a HakariBuilder<'_>, dep: &PackageMetadata<'a>) -> Self {
+        let source = dep.source();
+        if source.is_crates_io() {
+            return SourceGroup::CratesIo;
+        }
+        match source.parse_external() {
+            Some(ExternalSource::Registry(registry_url)) => SourceGroup::Registry(
+                builder
+                    .registries
+                    .get_by_right(registry_url)
+                    .map_or(registry_url, |name| name.as_str()),
+            ),
+            Some(ExternalSource::Git { .. }) => SourceGroup::Git,
+            // Unrecognized external sources are reported as errors while writing out the source.
+            _ => SourceGroup::Path,
+        }
+    }
+
+    fn header(&self) -> Cow<'static, str> {
+        match self {
+            SourceGroup::CratesIo => "# Registry: crates.io".into(),
+            SourceGroup::Registry(name) => format!("# Registry: {}", name).into(),
+            SourceGroup::Git => "# Git dependencies".into(),
+            SourceGroup::Path => "# Path dependencies".into(),
+        }
+    }
+}
+
+/// Adds a comment before the first dependency in each group. The table must already be sorted by
+/// group.
+fn write_group_headers(dep_table: &mut Table, source_groups: &AHashMap<String, SourceGroup<'_>>) {
+    let keys: Vec<_> = dep_table.iter().map(|(key, _)| key.to_owned()).collect();
+    let mut prev_group = None;
+    for key in keys {
+        let group = &source_groups[&key];
+        if prev_group == Some(group) {
+            continue;
+        }
+        // Separate groups with a blank line.
+        let separator = if prev_group.is_some() { "\n" } else { "" };
+        if let Some(decor) = dep_table.key_decor_mut(&key) {
+            decor.set_prefix(format!("{}{}\n", separator, group.header()));
+        }
+        prev_group = Some(group);
+    }
+}
+
+/// Puts each feature in an inline table on its own line.
+fn wrap_features(itable: &mut InlineTable) {
+    if let Some(features) = itable.get_mut("features").and_then(|v| v.as_array_mut()) {
+        for feature in features.iter_mut() {
+            feature.decor_mut().set_prefix("\n    ");
+            feature.decor_mut().set_suffix("");
+        }
+        features.set_trailing_comma(true);
+        features.set_trailing("\n");
+    }
+}
+
+fn packages_by_name<'g>(output_map: &OutputMap<'g>) -> AHashMap<&'g str, HashSet<&'g PackageId>> {
+    let mut packages_by_name: AHashMap<&str, HashSet<_>> = AHashMap::new();
+    for vals in output_map.values() {
+        for (&package_id, (package, _)) in vals {
+            packages_by_name
+                .entry(package.name())
+                .or_default()
+                .insert(package_id);
+        }
+    }
+    packages_by_name
+}
+
+/// Inserts the source of a dependency (version, path, git or registry) into `itable`.
+///
+/// `base_path` is the directory that relative paths are computed against, and is `None` if no
+/// Hakari package was specified.
+fn insert_source(
+    itable: &mut InlineTable,
+    builder: &HakariBuilder<'_>,
+    dep: &PackageMetadata<'_>,
+    options: &HakariOutputOptions,
+    dep_format: DepFormatVersion,
+    base_path: Option<&Utf8Path>,
+) -> Result<(), TomlOutError> {
+    let source = dep.source();
+    if source.is_crates_io() {
+        itable.insert("version", version_req_str(dep, options, dep_format).into());
+        return Ok(());
+    }
+
+    match source {
+        PackageSource::Workspace(path) | PackageSource::Path(path) => {
+            // PackageSource::Workspace shouldn't be possible unless the Hakari map
+            // was fiddled with. Regardless, we can handle it fine.
+            let path_out = if options.absolute_paths {
+                // TODO: canonicalize paths here, removing .. etc? tricky if the path is
+                // missing (as in tests)
+                builder.graph().workspace().root().join(path)
+            } else {
+                let base_path = base_path.ok_or_else(|| TomlOutError::PathWithoutHakari {
+                    package_id: dep.id().clone(),
+                    rel_path: path.to_path_buf(),
+                })?;
+                pathdiff::diff_utf8_paths(path, base_path)
+                    .expect("both base_path and path are relative")
+            }
+            .into_string();
+
+            cfg_if! {
+                if #[cfg(windows)] {
+                    // TODO: is replacing \\ with / totally safe on Windows? Might run
+                    // into issues with UNC paths.
+                    let path_out = path_out.replace("\\", "/");
+                    itable.insert("path", path_out.into());
+                } else {
+                    itable.insert("path", path_out.into());
+                }
+            };
+        }
+        PackageSource::External(s) => match source.parse_external() {
+            Some(ExternalSource::Git {
+                repository, req, ..
+            }) => {
+                itable.insert("git", repository.into());
+                match req {
+                    GitReq::Branch(branch) => {
+                        itable.insert("branch", branch.into());
+                    }
+                    GitReq::Tag(tag) => {
+                        itable.insert("tag", tag.into());
+                    }
+                    GitReq::Rev(rev) => {
+                        itable.insert("rev", rev.into());
+                    }
+                    GitReq::Default => {}
+                    _ => {
+                        return Err(TomlOutError::UnrecognizedExternal {
+                            package_id: dep.id().clone(),
+                            source: s.to_string(),
+                        });
+                    }
+                };
+            }
+            Some(ExternalSource::Registry(registry_url)) => {
+                let registry_name =
+                    builder
+                        .registries
+                        .get_by_right(registry_url)
+                        .ok_or_else(|| TomlOutError::UnrecognizedRegistry {
+                            package_id: dep.id().clone(),
+                            registry_url: registry_url.to_owned(),
+                        })?;
+                itable.insert("version", version_req_str(dep, options, dep_format).into());
+                itable.insert("registry", registry_name.into());
+            }
+            _ => {
+                return Err(TomlOutError::UnrecognizedExternal {
+                    package_id: dep.id().clone(),
+                    source: s.to_string(),
+                });
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Returns the version requirement to write out for a dependency.
+fn version_req_str(
+    dep: &PackageMetadata<'_>,
+    options: &HakariOutputOptions,
+    dep_format: DepFormatVersion,
+) -> String {
+    let with_build_metadata = dep_format < DepFormatVersion::V3;
+    match options.version_style {
+        VersionStyle::Minimal => {
+            VersionDisplay::new(dep.version(), false, with_build_metadata).to_string()
+        }
+        VersionStyle::Exact => {
+            VersionDisplay::new(dep.version(), true, with_build_metadata).to_string()
+        }
+        VersionStyle::Pinned => format!(
+            "={}",
+            VersionDisplay::new(dep.version(), true, with_build_metadata)
+        ),
+        VersionStyle::Workspace => loosest_version_req(
+            dep.version(),
+            dep.reverse_direct_links().map(|link| link.version_req()),
+        )
+        .unwrap_or_else(|| {
+            VersionDisplay::new(dep.version(), false, with_build_metadata).to_string()
+        }),
+    }
+}
+
 /// Generate a unique, stable package name from the metadata.
 fn make_hashed_name(dep: &PackageMetadata<'_>, dep_format: DepFormatVersion) -> String {
     // Use a fixed seed to ensure stable hashes.
@@ -496,9 +1067,9 @@ fn make_hashed_name(dep: &PackageMetadata<'_>, dep_format: DepFormatVersion) ->
     format!("{}-{:x}", dep.name(), hash)
 }
 
-fn get_or_insert_table<'t>(parent: &'t mut Table, key: &str) -> &'t mut Table {
+fn get_or_insert_table(parent: &mut Table, key: impl Into<Key>) -> &mut Table {
     let table = parent
-        .entry(key)
+        .entry_format(&key.into())
         .or_insert(Item::Table(Table::new()))
         .as_table_mut()
         .expect("just inserted this table");
@@ -512,6 +1083,7 @@ mod tests {
     use fixtures::json::*;
     use guppy::graph::DependencyDirection;
     use std::collections::{btree_map::Entry, BTreeMap};
+    use target_spec::TargetFeatures;
 
     #[test]
     fn make_package_name_unique() {
@@ -582,4 +1154,359 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn workspace_dependencies() {
+        let fixture = JsonFixture::metadata_alternate_registries();
+        let mut builder =
+            HakariBuilder::new(fixture.graph(), None).expect("builder initialization succeeded");
+        builder
+            .set_output_single_feature(true)
+            .set_dep_format_version(DepFormatVersion::V5)
+            .add_registries([("alt-registry", METADATA_ALTERNATE_REGISTRY_URL)]);
+        let hakari = builder.compute();
+
+        let output_options = HakariOutputOptions::new();
+        let output = hakari
+            .to_toml_string(&output_options)
+            .expect("workspace-hack output succeeded");
+        let workspace_toml = HakariCargoToml::new_in_memory(
+            "Cargo.toml",
+            format!(
+                "[workspace.dependencies]\n{}{}",
+                HakariCargoToml::BEGIN_SECTION,
+                HakariCargoToml::END_SECTION
+            ),
+        )
+        .expect("generated section found");
+        let workspace_output = hakari
+            .to_workspace_toml_string(&workspace_toml, &output_options)
+            .expect("workspace output succeeded");
+
+        static MATCH_STRINGS: &[&str] = &[
+            // The workspace-hack only specifies features.
+            r#"serde-e7e45184a9cd0878 = { workspace = true, features = ["derive"] }"#,
+            r#"serde_json = { workspace = true, features = ["default"] }"#,
+        ];
+        for &needle in MATCH_STRINGS {
+            assert!(
+                output.contains(needle),
+                "output did not contain string '{}', actual output follows:\n***\n{}\n",
+                needle,
+                output
+            );
+        }
+
+        static WORKSPACE_MATCH_STRINGS: &[&str] = &[
+            // Sources and renames are specified in [workspace.dependencies].
+            r#"serde-e7e45184a9cd0878 = { package = "serde", version = "1", registry = "alt-registry", default-features = false }"#,
+            r#"serde-dff4ba8e3ae991db = { package = "serde", version = "1", default-features = false }"#,
+            r#"serde_json = { version = "1", default-features = false }"#,
+        ];
+        for &needle in WORKSPACE_MATCH_STRINGS {
+            assert!(
+                workspace_output.contains(needle),
+                "workspace output did not contain string '{}', actual output follows:\n***\n{}\n",
+                needle,
+                workspace_output
+            );
+        }
+    }
+
+    #[test]
+    fn workspace_dependencies_existing() {
+        let fixture = JsonFixture::metadata_alternate_registries();
+        let mut builder =
+            HakariBuilder::new(fixture.graph(), None).expect("builder initialization succeeded");
+        builder
+            .set_output_single_feature(true)
+            .set_dep_format_version(DepFormatVersion::V5)
+            .add_registries([("alt-registry", METADATA_ALTERNATE_REGISTRY_URL)]);
+        let hakari = builder.compute();
+        let output_options = HakariOutputOptions::new();
+        // The previously generated section is replaced, so its entries don't conflict.
+        let workspace_toml = |existing: &str| {
+            HakariCargoToml::new_in_memory(
+                "Cargo.toml",
+                format!(
+                    "[workspace.dependencies]\n{}{}serde_json = \"0.9\"\n{}\n[workspace.dependencies.other]\nversion = \"1\"\n",
+                    existing,
+                    HakariCargoToml::BEGIN_SECTION,
+                    HakariCargoToml::END_SECTION
+                ),
+            )
+            .expect("generated section found")
+        };
+
+        // Unrelated entries don't conflict, whether they're inline or in their own tables.
+        let workspace_output = hakari
+            .to_workspace_toml_string(&workspace_toml("unrelated = \"1\"\n"), &output_options)
+            .expect("no conflicting entries");
+        assert!(
+            workspace_output
+                .contains(r#"serde_json = { version = "1", default-features = false }"#),
+            "actual output: {workspace_output}"
+        );
+
+        for existing in [
+            "serde_json = \"1\"\n",
+            "serde_json = { version = \"1\", features = [\"std\"] }\n",
+        ] {
+            let err = hakari
+                .to_workspace_toml_string(&workspace_toml(existing), &output_options)
+                .expect_err("serde_json is already declared");
+            assert!(
+                matches!(&err, TomlOutError::WorkspaceDependencyConflict { name, .. } if name == "serde_json"),
+                "unexpected error: {err}"
+            );
+        }
+
+        // Tables declared after the generated section are also checked.
+        let workspace_toml = HakariCargoToml::new_in_memory(
+            "Cargo.toml",
+            format!(
+                "[workspace.dependencies]{}{}\n[workspace.dependencies.serde_json]\nversion = \"1\"\n",
+                HakariCargoToml::BEGIN_SECTION,
+                HakariCargoToml::END_SECTION
+            ),
+        )
+        .expect("generated section found");
+        let err = hakari
+            .to_workspace_toml_string(&workspace_toml, &output_options)
+            .expect_err("serde_json is already declared");
+        assert_eq!(
+            err.to_string(),
+            "in 'Cargo.toml', 'serde_json' is already declared in [workspace.dependencies] outside \
+             the generated section (remove it so that hakari can manage it)",
+        );
+    }
+
+    #[test]
+    fn git_and_path_sources() {
+        // metadata1 has walkdir from both crates.io and a git repository, and quote from both
+        // crates.io and a path outside the workspace.
+        let fixture = JsonFixture::metadata1();
+        let mut builder =
+            HakariBuilder::new(fixture.graph(), None).expect("builder initialization succeeded");
+        builder.set_output_single_feature(true);
+        let hakari = builder.compute();
+
+        // Relative paths are computed against the Hakari package, so they need one to be set.
+        let err = hakari
+            .to_toml_string(&HakariOutputOptions::new())
+            .expect_err("no Hakari package specified => error");
+        assert!(
+            matches!(&err, TomlOutError::PathWithoutHakari { package_id, .. } if package_id.repr().starts_with("quote ")),
+            "unexpected error: {err}"
+        );
+
+        let mut output_options = HakariOutputOptions::new();
+        output_options.set_absolute_paths(true);
+        let output = hakari
+            .to_toml_string(&output_options)
+            .expect("git and path sources are supported");
+
+        static MATCH_STRINGS: &[&str] = &[
+            r#"walkdir = { git = "https://github.com/BurntSushi/walkdir", tag = "2.2.9", default-features = false }"#,
+            r#"quote = { path = "/fakepath/testcrate/../quote", features = ["proc-macro"] }"#,
+        ];
+        for &needle in MATCH_STRINGS {
+            assert!(
+                output.contains(needle),
+                "output did not contain string '{}', actual output follows:\n***\n{}\n",
+                needle,
+                output
+            );
+        }
+    }
+
+    #[test]
+    fn group_by_source() {
+        let fixture = JsonFixture::metadata_alternate_registries();
+        let mut builder =
+            HakariBuilder::new(fixture.graph(), None).expect("builder initialization succeeded");
+        builder
+            .set_output_single_feature(true)
+            .add_registries([("alt-registry", METADATA_ALTERNATE_REGISTRY_URL)]);
+        let hakari = builder.compute();
+
+        let mut output_options = HakariOutputOptions::new();
+        output_options
+            .set_group_by_source(true)
+            .set_max_line_width(Some(80));
+        let output = hakari
+            .to_toml_string(&output_options)
+            .expect("workspace-hack output succeeded");
+
+        // crates.io comes before alternate registries.
+        let crates_io = output
+            .find("# Registry: crates.io\n")
+            .unwrap_or_else(|| panic!("crates.io header present in:\n{}", output));
+        let alt_registry = output
+            .find("\n\n# Registry: alt-registry\n")
+            .unwrap_or_else(|| panic!("alt-registry header present in:\n{}", output));
+        assert!(crates_io < alt_registry, "groups are in order:\n{}", output);
+        assert!(
+            output.contains("\n# Registry: alt-registry\nserde-e7e45184a9cd0878 = "),
+            "header is directly followed by the first dependency in the group:\n{}",
+            output
+        );
+
+        // Long lines are wrapped, and the output is still valid TOML.
+        assert!(
+            output.contains("features = [\n    \""),
+            "features are wrapped:\n{}",
+            output
+        );
+        for line in output.lines() {
+            assert!(
+                line.len() <= 80 || !line.contains("features = [\""),
+                "line '{}' is wrapped",
+                line
+            );
+        }
+        output
+            .parse::<Document>()
+            .expect("wrapped output is valid TOML");
+
+        // The output is stable.
+        let output2 = hakari
+            .to_toml_string(&output_options)
+            .expect("workspace-hack output succeeded");
+        assert_eq!(output, output2, "output is deterministic");
+    }
+
+    #[test]
+    fn cfg_platforms() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let mut builder =
+            HakariBuilder::new(graph, None).expect("builder initialization succeeded");
+        builder
+            .set_platforms([
+                "x86_64-unknown-linux-gnu",
+                "x86_64-apple-darwin",
+                "x86_64-pc-windows-msvc",
+            ])
+            .expect("platforms are known");
+        let hakari = builder.compute();
+
+        let triple_output = hakari
+            .to_toml_string(&HakariOutputOptions::new())
+            .expect("workspace-hack output succeeded");
+        let mut output_options = HakariOutputOptions::new();
+        output_options.set_cfg_platforms(true);
+        let cfg_output = hakari
+            .to_toml_string(&output_options)
+            .expect("workspace-hack output succeeded");
+        assert!(
+            triple_output.contains("[target.x86_64-apple-darwin.dependencies]\nlibc = "),
+            "libc output per triple by default:\n{}",
+            triple_output
+        );
+        for section in [
+            "[target.'cfg(unix)'.dependencies]\nlibc = ",
+            "[target.'cfg(unix)'.build-dependencies]\nlibc = ",
+            "[target.'cfg(windows)'.dependencies]\nwinapi = ",
+        ] {
+            assert!(
+                cfg_output.contains(section),
+                "section {:?} present in:\n{}",
+                section,
+                cfg_output
+            );
+        }
+        assert!(
+            !cfg_output.contains("x86_64"),
+            "no triples in output:\n{}",
+            cfg_output
+        );
+        cfg_output
+            .parse::<Document>()
+            .expect("cfg output is valid TOML");
+    }
+
+    #[test]
+    fn platform_cfgs() {
+        let platforms: Vec<_> = [
+            "x86_64-unknown-linux-gnu",
+            "x86_64-unknown-linux-musl",
+            "x86_64-apple-darwin",
+            "aarch64-apple-darwin",
+            "x86_64-pc-windows-msvc",
+        ]
+        .into_iter()
+        .map(|triple| {
+            Arc::new(Platform::new(triple, TargetFeatures::Unknown).expect("known platform"))
+        })
+        .collect();
+
+        let cases: &[(&[usize], Option<&str>)] = &[
+            (&[0, 1, 2, 3], Some("cfg(unix)")),
+            (&[4], Some("cfg(windows)")),
+            (&[0, 1], Some("cfg(target_os = \"linux\")")),
+            (&[1], Some("cfg(target_env = \"musl\")")),
+            (&[3], Some("cfg(target_arch = \"aarch64\")")),
+            (
+                &[2],
+                Some("cfg(all(target_os = \"macos\", target_arch = \"x86_64\"))"),
+            ),
+            (&[0, 2], None),
+        ];
+        for (platform_idxs, expected) in cases {
+            let platform_idxs = platform_idxs.iter().copied().collect();
+            assert_eq!(
+                platform_cfg(&platforms, &platform_idxs).as_deref(),
+                *expected,
+                "cfg for {:?} matches",
+                platform_idxs
+            );
+        }
+    }
+
+    #[test]
+    fn version_styles() {
+        let fixture = JsonFixture::metadata_alternate_registries();
+        let mut builder =
+            HakariBuilder::new(fixture.graph(), None).expect("builder initialization succeeded");
+        builder
+            .set_output_single_feature(true)
+            .add_registries([("alt-registry", METADATA_ALTERNATE_REGISTRY_URL)]);
+        let hakari = builder.compute();
+
+        let cases: &[(VersionStyle, &[&str])] = &[
+            (
+                VersionStyle::Pinned,
+                &[
+                    r#"itoa = { version = "=0.4.8", default-features = false }"#,
+                    r#"serde_derive = { version = "=1.0.130", registry = "alt-registry" }"#,
+                ],
+            ),
+            (
+                VersionStyle::Workspace,
+                &[
+                    // Requirements are taken from the packages that depend on these crates.
+                    r#"itoa = { version = "0.4.3", default-features = false }"#,
+                    r#"ryu = { version = "1.0", default-features = false }"#,
+                    r#"syn = { version = "1.0.60", registry = "alt-registry","#,
+                ],
+            ),
+        ];
+
+        for &(version_style, needles) in cases {
+            let mut output_options = HakariOutputOptions::new();
+            output_options.set_version_style(version_style);
+            let output = hakari
+                .to_toml_string(&output_options)
+                .expect("workspace-hack output succeeded");
+            for &needle in needles {
+                assert!(
+                    output.contains(needle),
+                    "for {:?}, output did not contain string '{}', actual output follows:\n***\n{}\n",
+                    version_style,
+                    needle,
+                    output
+                );
+            }
+        }
+    }
 }
@@ -1,7 +1,7 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::verify::VerifyErrors;
+use crate::verify::{VerifyErrors, VerifySuggestion};
 use indenter::indented;
 use owo_colors::{OwoColorize, Style};
 use std::fmt::{self, Write};
@@ -51,17 +51,107 @@ impl<'g, 'verify> fmt::Display for VerifyErrorsDisplay<'g, 'verify> {
             write!(indented(f).with_str("  "), "{}", display)?;
         }
 
+        let suggestions = self.verify.suggestions();
+        if !suggestions.is_empty() {
+            writeln!(f, "\nsuggested fixes, most impactful first:")?;
+        }
+        for suggestion in &suggestions {
+            write!(f, "  * ")?;
+            self.fmt_suggestion(suggestion, f)?;
+            writeln!(f)?;
+        }
+
         Ok(())
     }
 }
 
+impl<'g, 'verify> VerifyErrorsDisplay<'g, 'verify> {
+    fn fmt_suggestion(
+        &self,
+        suggestion: &VerifySuggestion<'g>,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match suggestion {
+            VerifySuggestion::RemovePlatform { platform, packages } => {
+                write!(
+                    f,
+                    "remove {} from platforms (fixes {}: ",
+                    platform.triple_str().style(self.styles.platform_style),
+                    dependency_count(packages.len()),
+                )?;
+                for (idx, package) in packages.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(
+                        f,
+                        "{}",
+                        package.name().style(self.styles.dependency_id_style)
+                    )?;
+                }
+                write!(f, ")")
+            }
+            VerifySuggestion::AddFinalExclude { package } => write!(
+                f,
+                "add {} {} to final-excludes (fixes {})",
+                package.name().style(self.styles.dependency_id_style),
+                package.version(),
+                dependency_count(suggestion.impact()),
+            ),
+            VerifySuggestion::DeniedFeatures { package, features } => write!(
+                f,
+                "features [{}] of {} are denied through max-features, but enabled by some \
+                 workspace packages: stop enabling them, or remove them from max-features",
+                features.iter().copied().collect::<Vec<_>>().join(", "),
+                package.name().style(self.styles.dependency_id_style),
+            ),
+            VerifySuggestion::NonUnifiedFeatures {
+                package,
+                platform,
+                build_platform,
+                features,
+            } => {
+                write!(
+                    f,
+                    "features [{}] of {} could not be unified on the {} platform",
+                    features.iter().copied().collect::<Vec<_>>().join(", "),
+                    package.name().style(self.styles.dependency_id_style),
+                    build_platform,
+                )?;
+                if let Some(platform) = platform {
+                    write!(
+                        f,
+                        " for {}",
+                        platform.triple_str().style(self.styles.platform_style)
+                    )?;
+                }
+                write!(
+                    f,
+                    ": enable them in workspace packages that depend on {}",
+                    package.name()
+                )
+            }
+        }
+    }
+}
+
+fn dependency_count(count: usize) -> String {
+    if count == 1 {
+        "1 dependency".to_owned()
+    } else {
+        format!("{} dependencies", count)
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 struct Styles {
     dependency_id_style: Style,
+    platform_style: Style,
 }
 
 impl Styles {
     fn colorize(&mut self) {
         self.dependency_id_style = Style::new().bright_magenta();
+        self.platform_style = Style::new().yellow();
     }
 }
@@ -17,11 +17,16 @@
 
 #[cfg(feature = "cli-support")]
 mod display;
+mod suggestions;
 
 #[cfg(feature = "cli-support")]
 pub use display::VerifyErrorsDisplay;
+pub use suggestions::VerifySuggestion;
 
-use crate::{explain::HakariExplain, Hakari, HakariBuilder};
+use crate::{
+    explain::{ExplainFilter, HakariExplain},
+    Hakari, HakariBuilder,
+};
 use guppy::PackageId;
 use std::collections::BTreeSet;
 
@@ -77,10 +82,18 @@ impl<'g> VerifyErrors<'g> {
     /// Returns individual verification errors as [`HakariExplain`] instances.
     pub fn errors<'a>(&'a self) -> impl ExactSizeIterator<Item = HakariExplain<'g, 'a>> + 'a {
         let hakari = &self.hakari;
-        self.dependency_ids
-            .iter()
-            .copied()
-            .map(move |id| HakariExplain::new(hakari, id).expect("package ID is from this graph"))
+        self.dependency_ids.iter().copied().map(move |id| {
+            HakariExplain::new(hakari, id, &ExplainFilter::new())
+                .expect("package ID is from this graph")
+        })
+    }
+
+    /// Returns suggested changes that address these errors, most impactful first.
+    ///
+    /// The impact of a suggestion is the number of dependencies that would pass verification if it
+    /// were applied; see [`VerifySuggestion::impact`].
+    pub fn suggestions(&self) -> Vec<VerifySuggestion<'g>> {
+        VerifySuggestion::compute(self)
     }
 
     /// Returns a displayer for this instance.
@@ -90,6 +103,65 @@ impl<'g> VerifyErrors<'g> {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+
+    #[test]
+    fn suggestions() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let mut builder = HakariBuilder::new(graph, None).expect("valid builder");
+        builder
+            .set_platforms(["x86_64-unknown-linux-gnu"])
+            .expect("known platform");
+
+        // Without a workspace-hack package, verification fails for every unified dependency.
+        let errs = builder
+            .clone()
+            .verify()
+            .expect_err("verification fails without a workspace-hack");
+        let suggestions = errs.suggestions();
+        assert!(
+            suggestions
+                .windows(2)
+                .all(|pair| pair[0].impact() >= pair[1].impact()),
+            "suggestions are sorted by impact"
+        );
+
+        let mut final_excludes = vec![];
+        for suggestion in &suggestions {
+            match suggestion {
+                VerifySuggestion::RemovePlatform { platform, packages } => {
+                    assert_eq!(platform.triple_str(), "x86_64-unknown-linux-gnu");
+                    assert!(!packages.is_empty());
+                }
+                VerifySuggestion::AddFinalExclude { package } => {
+                    final_excludes.push(package.id());
+                }
+                VerifySuggestion::DeniedFeatures { .. } => {
+                    panic!("no features were denied");
+                }
+                VerifySuggestion::NonUnifiedFeatures { features, .. } => {
+                    assert!(!features.is_empty(), "some features weren't unified");
+                }
+            }
+        }
+
+        // Applying all final-exclude suggestions makes verification pass.
+        assert!(!final_excludes.is_empty());
+        builder
+            .add_final_excludes(final_excludes)
+            .expect("known package IDs");
+        if let Err(errs) = builder.verify() {
+            panic!(
+                "verification passes after applying suggestions, but failed for {:?}",
+                errs.dependency_ids
+            );
+        }
+    }
+}
+
 #[cfg(test)]
 #[cfg(feature = "cli-support")]
 mod cli_support_tests {
@@ -0,0 +1,201 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::verify::VerifyErrors;
+use guppy::{
+    graph::{
+        cargo::BuildPlatform,
+        feature::{FeatureId, FeatureLabel},
+        PackageMetadata,
+    },
+    PackageId,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+use target_spec::Platform;
+
+/// A suggested change that addresses some [`VerifyErrors`].
+///
+/// Returned by [`VerifyErrors::suggestions`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum VerifySuggestion<'g> {
+    /// Remove a platform from the list of [`platforms`](crate::HakariBuilder::set_platforms).
+    ///
+    /// This is suggested if some dependencies are only built with more than one feature set on
+    /// this platform.
+    RemovePlatform {
+        /// The platform to remove.
+        platform: Arc<Platform>,
+
+        /// The dependencies that would no longer fail verification.
+        packages: Vec<PackageMetadata<'g>>,
+    },
+
+    /// Add a dependency to the [final excludes](crate::HakariBuilder::add_final_excludes).
+    ///
+    /// The workspace-hack will no longer attempt to unify features for this dependency.
+    AddFinalExclude {
+        /// The dependency to exclude.
+        package: PackageMetadata<'g>,
+    },
+
+    /// Some features of a dependency are [denied](crate::HakariBuilder::add_denied_features), but
+    /// are enabled by some workspace packages and not others.
+    ///
+    /// To address this, either stop enabling these features in workspace packages, or stop
+    /// denying them.
+    DeniedFeatures {
+        /// The dependency.
+        package: PackageMetadata<'g>,
+
+        /// The denied features that caused a conflict.
+        features: BTreeSet<&'g str>,
+    },
+
+    /// Some features of a dependency could not be unified through the workspace-hack.
+    ///
+    /// There is no config change that unifies these features. To address this, either enable these
+    /// features directly in the workspace packages that depend on this dependency, or add it to the
+    /// final excludes.
+    NonUnifiedFeatures {
+        /// The dependency.
+        package: PackageMetadata<'g>,
+
+        /// The platform on which the features weren't unified.
+        ///
+        /// `None` for the platform-independent computation.
+        platform: Option<Arc<Platform>>,
+
+        /// The build platform (target or host) on which the features weren't unified.
+        build_platform: BuildPlatform,
+
+        /// The features that weren't unified.
+        features: BTreeSet<&'g str>,
+    },
+}
+
+impl<'g> VerifySuggestion<'g> {
+    /// Returns the number of failing dependencies that would pass verification if this suggestion
+    /// were applied.
+    ///
+    /// [`DeniedFeatures`](Self::DeniedFeatures) and [`NonUnifiedFeatures`](Self::NonUnifiedFeatures)
+    /// suggestions don't correspond to a single config change, so they always have an impact of 0.
+    pub fn impact(&self) -> usize {
+        match self {
+            VerifySuggestion::RemovePlatform { packages, .. } => packages.len(),
+            VerifySuggestion::AddFinalExclude { .. } => 1,
+            VerifySuggestion::DeniedFeatures { .. }
+            | VerifySuggestion::NonUnifiedFeatures { .. } => 0,
+        }
+    }
+
+    pub(super) fn compute(verify: &VerifyErrors<'g>) -> Vec<Self> {
+        let hakari = &verify.hakari;
+        let platforms = &hakari.builder.platforms;
+
+        // For each failing dependency, the platforms it fails on.
+        let mut failing: BTreeMap<&'g PackageId, (PackageMetadata<'g>, BTreeSet<Option<usize>>)> =
+            BTreeMap::new();
+        let mut non_unified = Vec::new();
+        for (output_key, inner_map) in &hakari.output_map {
+            for (&package_id, (package, features)) in inner_map {
+                failing
+                    .entry(package_id)
+                    .or_insert_with(|| (*package, BTreeSet::new()))
+                    .1
+                    .insert(output_key.platform_idx);
+                non_unified.push(VerifySuggestion::NonUnifiedFeatures {
+                    package: *package,
+                    platform: output_key.platform_idx.map(|idx| platforms[idx].clone()),
+                    build_platform: output_key.build_platform,
+                    features: features.clone(),
+                });
+            }
+        }
+
+        let mut suggestions: Vec<_> = platforms
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, platform)| {
+                let packages: Vec<_> = failing
+                    .values()
+                    .filter(|(_, platform_idxs)| {
+                        platform_idxs.len() == 1 && platform_idxs.contains(&Some(idx))
+                    })
+                    .map(|(package, _)| *package)
+                    .collect();
+                (!packages.is_empty()).then(|| VerifySuggestion::RemovePlatform {
+                    platform: platform.clone(),
+                    packages,
+                })
+            })
+            .collect();
+        suggestions.extend(
+            failing
+                .values()
+                .map(|(package, _)| VerifySuggestion::AddFinalExclude { package: *package }),
+        );
+        suggestions.extend(Self::denied_features(verify));
+        suggestions.extend(non_unified);
+
+        // This is a stable sort, so suggestions with the same impact stay in the order above.
+        suggestions.sort_by_key(|suggestion| std::cmp::Reverse(suggestion.impact()));
+        suggestions
+    }
+
+    fn denied_features(verify: &VerifyErrors<'g>) -> Vec<Self> {
+        let hakari = &verify.hakari;
+        let denied_features: Vec<_> = hakari
+            .builder
+            .denied_features()
+            // Only consider denied features of dependencies that failed verification.
+            .filter(|feature_id| verify.dependency_ids.contains(feature_id.package_id()))
+            .collect();
+        if denied_features.is_empty() {
+            return vec![];
+        }
+
+        // Find denied features enabled by the features that couldn't be unified.
+        let feature_graph = hakari.builder.graph().feature_graph();
+        let mut conflicts: BTreeMap<&'g PackageId, BTreeSet<&'g str>> = BTreeMap::new();
+        for inner_map in hakari.output_map.values() {
+            for (&package_id, (_, features)) in inner_map {
+                let feature_ids = features
+                    .iter()
+                    .map(|&feature| FeatureId::new(package_id, FeatureLabel::Named(feature)));
+                let feature_set = feature_graph
+                    .query_forward(feature_ids)
+                    .expect("feature IDs in the output map are valid")
+                    .resolve();
+                for denied in &denied_features {
+                    if feature_set
+                        .contains(*denied)
+                        .expect("denied feature IDs are valid")
+                    {
+                        if let FeatureLabel::Named(feature) = denied.label() {
+                            conflicts
+                                .entry(denied.package_id())
+                                .or_default()
+                                .insert(feature);
+                        }
+                    }
+                }
+            }
+        }
+
+        conflicts
+            .into_iter()
+            .map(|(package_id, features)| VerifySuggestion::DeniedFeatures {
+                package: hakari
+                    .builder
+                    .graph()
+                    .metadata(package_id)
+                    .expect("denied features are known to the graph"),
+                features,
+            })
+            .collect()
+    }
+}